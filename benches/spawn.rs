@@ -0,0 +1,55 @@
+use aloy_engine::ecs::world::World;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+// only spawned, never read
+#[allow(dead_code)]
+#[derive(Clone)]
+struct Projectile {
+    damage: u32,
+}
+
+#[allow(dead_code)]
+#[derive(Clone)]
+struct Velocity(f32, f32);
+
+#[allow(dead_code)]
+struct Position(f32, f32);
+
+// Spawning a wave of projectiles one by one against a single batch
+fn spawn_projectiles(c: &mut Criterion) {
+    let mut group = c.benchmark_group("spawn_projectiles");
+    for count in [1_000, 10_000] {
+        group.bench_with_input(BenchmarkId::new("spawn", count), &count, |b, &count| {
+            b.iter(|| {
+                let mut world = World::new();
+                for index in 0..count {
+                    world.spawn((
+                        Projectile { damage: 5 },
+                        Velocity(0.0, 9.0),
+                        Position(index as f32, 0.0),
+                    ));
+                }
+                black_box(world)
+            })
+        });
+        group.bench_with_input(
+            BenchmarkId::new("spawn_batch", count),
+            &count,
+            |b, &count| {
+                b.iter(|| {
+                    let mut world = World::new();
+                    world.spawn_batch(
+                        (Projectile { damage: 5 }, Velocity(0.0, 9.0)),
+                        count,
+                        |index| (Position(index as f32, 0.0),),
+                    );
+                    black_box(world)
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, spawn_projectiles);
+criterion_main!(benches);
@@ -0,0 +1,463 @@
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+
+use log::error;
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::{
+        engine_events::animation_events::{AnimationEvents, AnimationMarker},
+        event::{EntityId, TargetedEvent},
+    },
+    math::transform::Mat4,
+};
+
+use super::{
+    clip::AnimationClip,
+    ik::{IkConstraint, IkErrors},
+    morph::blend_morph_weights,
+    skeleton::{Pose, Skeleton},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct AnimatorId(u64);
+
+#[derive(Debug, Clone)]
+struct Playback {
+    clip: Arc<AnimationClip>,
+    time: f32,
+    looping: bool,
+}
+
+impl Playback {
+    // Returns the markers passed
+    fn advance(&mut self, delta: f32) -> Vec<String> {
+        let from = self.time;
+        let duration = self.clip.duration;
+        let mut to = from + delta;
+        if self.looping && duration > 0.0 {
+            to %= duration;
+        } else {
+            to = to.min(duration);
+        }
+        self.time = to;
+        if delta <= 0.0 || (!self.looping && from == to) {
+            return Vec::new();
+        }
+        // a whole loop or more, every marker was passed
+        if self.looping && delta >= duration {
+            return self
+                .clip
+                .markers
+                .iter()
+                .map(|marker| marker.name.clone())
+                .collect();
+        }
+        self.clip
+            .get_markers_between(from, to)
+            .map(|marker| marker.name.clone())
+            .collect()
+    }
+}
+
+// One layer of an animator. Layers above the base one blend over it with
+// their weight, limited to the joints of their mask (e.g. an upper body
+// aiming layer over the locomotion).
+#[derive(Debug, Clone)]
+pub struct AnimationLayer {
+    pub name: String,
+    pub weight: f32,
+    pub mask: Option<Vec<f32>>,
+    playback: Option<Playback>,
+    // the clip faded out by a crossfade and how far along the fade is
+    fading: Option<(Playback, f32, f32)>,
+}
+
+impl AnimationLayer {
+    fn new(name: &str, mask: Option<Vec<f32>>) -> Self {
+        Self {
+            name: name.to_string(),
+            weight: 1.0,
+            mask,
+            playback: None,
+            fading: None,
+        }
+    }
+
+    pub fn get_clip(&self) -> Option<&AnimationClip> {
+        self.playback
+            .as_ref()
+            .map(|playback| playback.clip.as_ref())
+    }
+
+    pub fn get_time(&self) -> Option<f32> {
+        self.playback.as_ref().map(|playback| playback.time)
+    }
+}
+
+// Plays clips on a skeleton and produces the pose and joint palette of every
+// frame
+#[derive(Debug, Clone)]
+pub struct Animator {
+    skeleton: Arc<Skeleton>,
+    layers: Vec<AnimationLayer>,
+    // solved in order after the layers are blended
+    ik: Vec<IkConstraint>,
+    pose: Pose,
+    // blend shape weights by mesh name
+    morph_weights: BTreeMap<String, Vec<f32>>,
+    // set from code, they win over the clips
+    morph_overrides: BTreeMap<(String, usize), f32>,
+    pub speed: f32,
+    // marker events are targeted at this entity when it is set
+    pub owner: Option<EntityId>,
+}
+
+impl Animator {
+    pub fn new(skeleton: Arc<Skeleton>) -> Self {
+        Self {
+            pose: skeleton.get_rest_pose(),
+            skeleton,
+            layers: vec![AnimationLayer::new("base", None)],
+            ik: Vec::new(),
+            morph_weights: BTreeMap::new(),
+            morph_overrides: BTreeMap::new(),
+            speed: 1.0,
+            owner: None,
+        }
+    }
+
+    pub fn get_skeleton(&self) -> &Skeleton {
+        &self.skeleton
+    }
+
+    pub fn add_layer(&mut self, name: &str, mask: Option<Vec<f32>>) -> usize {
+        self.layers.push(AnimationLayer::new(name, mask));
+        self.layers.len() - 1
+    }
+
+    pub fn get_layer(&self, layer: usize) -> Option<&AnimationLayer> {
+        self.layers.get(layer)
+    }
+
+    pub fn set_layer_weight(&mut self, layer: usize, weight: f32) {
+        if let Some(layer) = self.layers.get_mut(layer) {
+            layer.weight = weight;
+        }
+    }
+
+    pub fn play(&mut self, layer: usize, clip: Arc<AnimationClip>, looping: bool) {
+        if let Some(layer) = self.layers.get_mut(layer) {
+            layer.fading = None;
+            layer.playback = Some(Playback {
+                clip,
+                time: 0.0,
+                looping,
+            });
+        }
+    }
+
+    // Blends from the current clip of the layer to `clip` over `duration`
+    pub fn crossfade(
+        &mut self,
+        layer: usize,
+        clip: Arc<AnimationClip>,
+        looping: bool,
+        duration: Duration,
+    ) {
+        let Some(layer) = self.layers.get_mut(layer) else {
+            return;
+        };
+        let previous = layer.playback.replace(Playback {
+            clip,
+            time: 0.0,
+            looping,
+        });
+        layer.fading = previous.map(|previous| (previous, 0.0, duration.as_secs_f32()));
+    }
+
+    pub fn stop(&mut self, layer: usize) {
+        if let Some(layer) = self.layers.get_mut(layer) {
+            layer.playback = None;
+            layer.fading = None;
+        }
+    }
+
+    pub fn add_ik(&mut self, constraint: IkConstraint) -> Result<usize, IkErrors> {
+        constraint.validate(&self.skeleton)?;
+        self.ik.push(constraint);
+        Ok(self.ik.len() - 1)
+    }
+
+    // For moving the target every frame, the joints should stay the same
+    pub fn get_ik_mut(&mut self, index: usize) -> Option<&mut IkConstraint> {
+        self.ik.get_mut(index)
+    }
+
+    pub fn remove_ik(&mut self, index: usize) -> Option<IkConstraint> {
+        (index < self.ik.len()).then(|| self.ik.remove(index))
+    }
+
+    // Advances every layer, rebuilds the pose and returns the markers passed
+    // as (clip, marker) names
+    pub fn update(&mut self, delta: Duration) -> Vec<(String, String)> {
+        let delta = delta.as_secs_f32() * self.speed;
+        let mut markers = Vec::new();
+        let mut pose = self.skeleton.get_rest_pose();
+        let mut morphs = BTreeMap::new();
+
+        for layer in self.layers.iter_mut() {
+            let Some(playback) = layer.playback.as_mut() else {
+                continue;
+            };
+            let clip_name = playback.clip.name.clone();
+            markers.extend(
+                playback
+                    .advance(delta)
+                    .into_iter()
+                    .map(|marker| (clip_name.clone(), marker)),
+            );
+
+            let mut layer_pose = pose.clone();
+            let mut layer_morphs = morphs.clone();
+            playback.clip.sample(playback.time, &mut layer_pose);
+            playback
+                .clip
+                .sample_morphs(playback.time, &mut layer_morphs);
+            if let Some((previous, elapsed, duration)) = layer.fading.as_mut() {
+                previous.advance(delta);
+                *elapsed += delta;
+                let mut previous_pose = pose.clone();
+                let mut previous_morphs = morphs.clone();
+                previous.clip.sample(previous.time, &mut previous_pose);
+                previous
+                    .clip
+                    .sample_morphs(previous.time, &mut previous_morphs);
+                // weight of the clip fading out
+                let fade = 1.0 - (*elapsed / duration.max(f32::EPSILON)).min(1.0);
+                layer_pose.blend(&previous_pose, fade, None);
+                blend_morph_weights(&mut layer_morphs, &previous_morphs, fade);
+                if fade <= 0.0 {
+                    layer.fading = None;
+                }
+            }
+            pose.blend(&layer_pose, layer.weight, layer.mask.as_deref());
+            blend_morph_weights(&mut morphs, &layer_morphs, layer.weight);
+        }
+        for constraint in self.ik.iter() {
+            constraint.apply(&self.skeleton, &mut pose);
+        }
+        for ((mesh, target), weight) in self.morph_overrides.iter() {
+            let weights = morphs.entry(mesh.clone()).or_insert_with(Vec::new);
+            if weights.len() <= *target {
+                weights.resize(target + 1, 0.0);
+            }
+            weights[*target] = *weight;
+        }
+        self.pose = pose;
+        self.morph_weights = morphs;
+        markers
+    }
+
+    pub fn get_pose(&self) -> &Pose {
+        &self.pose
+    }
+
+    // Weights of the blend shapes of `mesh`, None when nothing drives it
+    pub fn get_morph_weights(&self, mesh: &str) -> Option<&[f32]> {
+        self.morph_weights.get(mesh).map(Vec::as_slice)
+    }
+
+    // Drives a blend shape from code (lip sync, damage, ...), None hands it
+    // back to the clips. Used from the next update.
+    pub fn set_morph_override(&mut self, mesh: &str, target: usize, weight: Option<f32>) {
+        let key = (mesh.to_string(), target);
+        match weight {
+            Some(weight) => self.morph_overrides.insert(key, weight),
+            None => self.morph_overrides.remove(&key),
+        };
+    }
+
+    pub fn get_skinning_matrices(&self) -> Vec<Mat4> {
+        self.pose.get_skinning_matrices(&self.skeleton)
+    }
+}
+
+// Every animator of the scene, advanced with the scaled delta so they slow
+// down with the time scale. Markers are sent as AnimationEvents::Marker.
+#[derive(Debug, Default)]
+pub struct Animators {
+    animators: BTreeMap<AnimatorId, Animator>,
+    next_id: u64,
+}
+
+impl Animators {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, animator: Animator) -> AnimatorId {
+        let id = AnimatorId(self.next_id);
+        self.next_id += 1;
+        self.animators.insert(id, animator);
+        id
+    }
+
+    pub fn get(&self, id: AnimatorId) -> Option<&Animator> {
+        self.animators.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: AnimatorId) -> Option<&mut Animator> {
+        self.animators.get_mut(&id)
+    }
+
+    pub fn remove(&mut self, id: AnimatorId) -> Option<Animator> {
+        self.animators.remove(&id)
+    }
+
+    pub fn update(&mut self, delta: Duration) -> Vec<(Option<EntityId>, AnimationMarker)> {
+        let mut fired = Vec::new();
+        for (id, animator) in self.animators.iter_mut() {
+            for (clip, name) in animator.update(delta) {
+                let marker = AnimationMarker {
+                    animator: *id,
+                    clip,
+                    name,
+                };
+                fired.push((animator.owner, marker));
+            }
+        }
+        fired
+    }
+}
+
+impl Subsystem for Animators {
+    fn get_name(&self) -> &str {
+        "Animators"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        for (owner, marker) in self.update(ctx.time.get_delta()) {
+            let event = AnimationEvents::Marker(marker);
+            let result = match owner {
+                Some(owner) => ctx
+                    .event_queue
+                    .emit(Box::new(TargetedEvent::new(owner, event))),
+                None => ctx.event_queue.emit(Box::new(event)),
+            };
+            if let Err(err) = result {
+                error!("unable to emit animation event {:?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        animation::{
+            clip::{Interpolation, Track},
+            morph::MorphTrack,
+            skeleton::Joint,
+        },
+        math::{transform::Transform, vector::Vec3},
+    };
+
+    use super::*;
+
+    fn skeleton() -> Arc<Skeleton> {
+        let joint = |name: &str, parent| Joint {
+            name: name.to_string(),
+            parent,
+            rest: Transform::IDENTITY,
+            inverse_bind: Mat4::IDENTITY,
+        };
+        Arc::new(Skeleton::new(vec![joint("hips", None), joint("spine", Some(0))]).unwrap())
+    }
+
+    fn slide(name: &str, to: Vec3) -> Arc<AnimationClip> {
+        let mut clip = AnimationClip::new(name, 2);
+        for joint in 0..2 {
+            let track = Track::new(vec![0.0, 1.0], vec![Vec3::ZERO, to], Interpolation::Linear);
+            clip.set_translation(joint, track);
+        }
+        clip.add_marker(0.5, "footstep");
+        Arc::new(clip)
+    }
+
+    #[test]
+    fn test_playback_markers_and_crossfade() {
+        let mut animator = Animator::new(skeleton());
+        animator.play(0, slide("walk", Vec3::X), true);
+
+        assert!(animator.update(Duration::from_millis(250)).is_empty());
+        assert_eq!(
+            animator.get_pose().locals[0].translation,
+            Vec3::new(0.25, 0.0, 0.0)
+        );
+        let markers = animator.update(Duration::from_millis(500));
+        assert_eq!(markers, vec![("walk".to_string(), "footstep".to_string())]);
+        // wraps around and passes the marker again
+        assert_eq!(animator.update(Duration::from_millis(1000)).len(), 1);
+
+        animator.crossfade(0, slide("strafe", Vec3::Z), true, Duration::from_secs(1));
+        animator.update(Duration::from_millis(500));
+        let halfway = animator.get_pose().locals[0].translation;
+        assert!(halfway.x > 0.0 && halfway.z > 0.0);
+        animator.update(Duration::from_millis(600));
+        assert_eq!(animator.get_pose().locals[0].translation.x, 0.0);
+    }
+
+    #[test]
+    fn test_morph_weights_from_clips_and_code() {
+        let mut blink = AnimationClip::new("blink", 2);
+        blink.add_morph_track(MorphTrack::new(
+            "face",
+            vec![0.0, 1.0],
+            vec![0.0, 0.0, 1.0, 0.0],
+            2,
+            Interpolation::Linear,
+        ));
+        let mut animator = Animator::new(skeleton());
+        animator.play(0, Arc::new(blink), false);
+        assert_eq!(animator.get_morph_weights("face"), None);
+
+        animator.update(Duration::from_millis(500));
+        assert_eq!(animator.get_morph_weights("face"), Some(&[0.5, 0.0][..]));
+        animator.set_morph_override("face", 1, Some(0.8));
+        animator.update(Duration::from_millis(500));
+        assert_eq!(animator.get_morph_weights("face"), Some(&[1.0, 0.8][..]));
+        animator.set_morph_override("face", 1, None);
+        animator.update(Duration::ZERO);
+        assert_eq!(animator.get_morph_weights("face"), Some(&[1.0, 0.0][..]));
+    }
+
+    #[test]
+    fn test_masked_layers() {
+        let skeleton = skeleton();
+        let mut animator = Animator::new(skeleton.clone());
+        let upper = animator.add_layer("upper body", Some(skeleton.get_mask_below(1)));
+        animator.play(upper, slide("aim", Vec3::Y), false);
+        animator.set_layer_weight(upper, 0.5);
+
+        animator.update(Duration::from_secs(2));
+        let pose = animator.get_pose();
+        assert_eq!(pose.locals[0].translation, Vec3::ZERO);
+        assert_eq!(pose.locals[1].translation, Vec3::new(0.0, 0.5, 0.0));
+        // clamped at the end of a clip that does not loop
+        assert_eq!(animator.get_layer(upper).unwrap().get_time(), Some(1.0));
+
+        // the spine is turned to the target after the layers
+        let look = animator
+            .add_ik(IkConstraint::fabrik(vec![0, 1], Vec3::new(0.0, 0.0, 1.0)))
+            .unwrap();
+        animator.get_ik_mut(look).unwrap().target = Vec3::X;
+        animator.update(Duration::from_millis(10));
+        let rotation = animator.get_pose().locals[0].rotation;
+        let spine = rotation.rotate(Vec3::new(0.0, 0.5, 0.0));
+        assert!(spine.distance(Vec3::new(0.5, 0.0, 0.0)) < 1e-4);
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+
+use crate::math::{
+    transform::Quat,
+    vector::{Vec3, Vec4},
+};
+
+use super::{morph::MorphTrack, skeleton::Pose};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Interpolation {
+    Step,
+    #[default]
+    Linear,
+}
+
+pub trait Interpolate: Copy {
+    fn interpolate(self, other: Self, t: f32) -> Self;
+}
+
+impl Interpolate for Vec3 {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        self.lerp(other, t)
+    }
+}
+
+impl Interpolate for Vec4 {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        self.lerp(other, t)
+    }
+}
+
+impl Interpolate for Quat {
+    fn interpolate(self, other: Self, t: f32) -> Self {
+        self.nlerp(other, t)
+    }
+}
+
+// Keyframes of one property, times in seconds and ascending
+#[derive(Debug, Clone, PartialEq)]
+pub struct Track<T> {
+    pub times: Vec<f32>,
+    pub values: Vec<T>,
+    pub interpolation: Interpolation,
+}
+
+impl<T: Interpolate> Track<T> {
+    pub fn new(times: Vec<f32>, values: Vec<T>, interpolation: Interpolation) -> Self {
+        Self {
+            times,
+            values,
+            interpolation,
+        }
+    }
+
+    pub fn get_duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or(0.0)
+    }
+
+    pub fn sample(&self, time: f32) -> Option<T> {
+        let last = self.times.len().min(self.values.len()).checked_sub(1)?;
+        let next = self.times[..=last].partition_point(|key| *key <= time);
+        if next == 0 {
+            return Some(self.values[0]);
+        }
+        if next > last {
+            return Some(self.values[last]);
+        }
+        let (from, to) = (self.times[next - 1], self.times[next]);
+        match self.interpolation {
+            Interpolation::Step => Some(self.values[next - 1]),
+            Interpolation::Linear => {
+                let t = (time - from) / (to - from).max(f32::EPSILON);
+                Some(self.values[next - 1].interpolate(self.values[next], t))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct JointTracks {
+    pub translation: Option<Track<Vec3>>,
+    pub rotation: Option<Track<Quat>>,
+    pub scale: Option<Track<Vec3>>,
+}
+
+// A named point in the clip, e.g. "footstep_left", sent as an
+// AnimationEvents::Marker when playback passes it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipMarker {
+    pub time: f32,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AnimationClip {
+    pub name: String,
+    pub duration: f32,
+    // indexed by joint, joints without tracks keep the pose they had
+    pub joints: Vec<JointTracks>,
+    // blend shape weights, one track per mesh
+    pub morphs: Vec<MorphTrack>,
+    pub markers: Vec<ClipMarker>,
+}
+
+impl AnimationClip {
+    pub fn new(name: &str, joint_count: usize) -> Self {
+        Self {
+            name: name.to_string(),
+            joints: vec![JointTracks::default(); joint_count],
+            ..Default::default()
+        }
+    }
+
+    pub fn set_translation(&mut self, joint: usize, track: Track<Vec3>) {
+        self.duration = self.duration.max(track.get_duration());
+        self.joints[joint].translation = Some(track);
+    }
+
+    pub fn set_rotation(&mut self, joint: usize, track: Track<Quat>) {
+        self.duration = self.duration.max(track.get_duration());
+        self.joints[joint].rotation = Some(track);
+    }
+
+    pub fn set_scale(&mut self, joint: usize, track: Track<Vec3>) {
+        self.duration = self.duration.max(track.get_duration());
+        self.joints[joint].scale = Some(track);
+    }
+
+    pub fn add_morph_track(&mut self, track: MorphTrack) {
+        self.duration = self.duration.max(track.get_duration());
+        self.morphs.retain(|morph| morph.mesh != track.mesh);
+        self.morphs.push(track);
+    }
+
+    pub fn add_marker(&mut self, time: f32, name: &str) {
+        self.markers.push(ClipMarker {
+            time,
+            name: name.to_string(),
+        });
+        self.markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+    }
+
+    pub fn sample(&self, time: f32, pose: &mut Pose) {
+        for (local, tracks) in pose.locals.iter_mut().zip(&self.joints) {
+            if let Some(translation) = tracks.translation.as_ref().and_then(|t| t.sample(time)) {
+                local.translation = translation;
+            }
+            if let Some(rotation) = tracks.rotation.as_ref().and_then(|t| t.sample(time)) {
+                local.rotation = rotation;
+            }
+            if let Some(scale) = tracks.scale.as_ref().and_then(|t| t.sample(time)) {
+                local.scale = scale;
+            }
+        }
+    }
+
+    // Sets the weights of every mesh the clip has a track for
+    pub fn sample_morphs(&self, time: f32, weights: &mut BTreeMap<String, Vec<f32>>) {
+        for track in self.morphs.iter() {
+            if let Some(sampled) = track.sample(time) {
+                weights.insert(track.mesh.clone(), sampled);
+            }
+        }
+    }
+
+    // Markers in (from, to], `to` may be smaller than `from` when a looping
+    // clip wrapped around
+    pub fn get_markers_between(&self, from: f32, to: f32) -> impl Iterator<Item = &ClipMarker> {
+        self.markers.iter().filter(move |marker| match from <= to {
+            true => marker.time > from && marker.time <= to,
+            false => marker.time > from || marker.time <= to,
+        })
+    }
+}
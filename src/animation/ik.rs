@@ -0,0 +1,299 @@
+use thiserror::Error;
+
+use crate::math::{transform::Quat, vector::Vec3};
+
+use super::skeleton::{Pose, Skeleton};
+
+const MIN_LENGTH: f32 = 1e-5;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum IkErrors {
+    #[error("joint {0} does not exist")]
+    UnknownJoint(usize),
+
+    #[error("joint {1} is not below joint {0}")]
+    NotAChain(usize, usize),
+
+    #[error("a chain needs at least two joints")]
+    ChainTooShort,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IkSolver {
+    // Analytic solver for limbs, e.g. hip, knee, ankle. The limb bends
+    // towards `pole` when given, otherwise the way it already bends.
+    TwoBone {
+        root: usize,
+        mid: usize,
+        end: usize,
+        pole: Option<Vec3>,
+    },
+    // Iterative solver for longer chains like spines, necks and tails, every
+    // joint of the chain is below the previous one
+    Fabrik {
+        chain: Vec<usize>,
+        iterations: usize,
+        tolerance: f32,
+    },
+}
+
+impl IkSolver {
+    pub fn get_chain(&self) -> Vec<usize> {
+        match self {
+            Self::TwoBone { root, mid, end, .. } => vec![*root, *mid, *end],
+            Self::Fabrik { chain, .. } => chain.clone(),
+        }
+    }
+}
+
+// Pulls the end of a chain to a target in model space (foot placement on
+// uneven ground, a head looking at something...). Applied by the Animator
+// after sampling, `weight` blends from the animated pose to the solved one.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IkConstraint {
+    pub solver: IkSolver,
+    pub target: Vec3,
+    pub weight: f32,
+}
+
+impl IkConstraint {
+    pub fn two_bone(root: usize, mid: usize, end: usize, target: Vec3) -> Self {
+        Self {
+            solver: IkSolver::TwoBone {
+                root,
+                mid,
+                end,
+                pole: None,
+            },
+            target,
+            weight: 1.0,
+        }
+    }
+
+    pub fn fabrik(chain: Vec<usize>, target: Vec3) -> Self {
+        Self {
+            solver: IkSolver::Fabrik {
+                chain,
+                iterations: 10,
+                tolerance: 1e-3,
+            },
+            target,
+            weight: 1.0,
+        }
+    }
+
+    pub fn validate(&self, skeleton: &Skeleton) -> Result<(), IkErrors> {
+        let chain = self.solver.get_chain();
+        if chain.len() < 2 {
+            return Err(IkErrors::ChainTooShort);
+        }
+        if let Some(joint) = chain.iter().find(|joint| **joint >= skeleton.len()) {
+            return Err(IkErrors::UnknownJoint(*joint));
+        }
+        for pair in chain.windows(2) {
+            if !skeleton.is_ancestor(pair[0], pair[1]) {
+                return Err(IkErrors::NotAChain(pair[0], pair[1]));
+            }
+        }
+        Ok(())
+    }
+
+    // Expects a validated constraint
+    pub fn apply(&self, skeleton: &Skeleton, pose: &mut Pose) {
+        if self.weight <= 0.0 {
+            return;
+        }
+        let models = pose.get_model_matrices(skeleton);
+        let chain = self.solver.get_chain();
+        let positions: Vec<Vec3> = chain
+            .iter()
+            .map(|joint| models[*joint].cols[3].truncate())
+            .collect();
+        let solved = match &self.solver {
+            IkSolver::TwoBone { pole, .. } => solve_two_bone(&positions, self.target, *pole),
+            IkSolver::Fabrik {
+                iterations,
+                tolerance,
+                ..
+            } => solve_fabrik(&positions, self.target, *iterations, *tolerance),
+        };
+        rotate_chain(skeleton, pose, &chain, &solved, self.weight);
+    }
+}
+
+fn solve_two_bone(positions: &[Vec3], target: Vec3, pole: Option<Vec3>) -> Vec<Vec3> {
+    let (root, mid, end) = (positions[0], positions[1], positions[2]);
+    let upper = root.distance(mid);
+    let lower = mid.distance(end);
+    let to_target = target - root;
+    // kept just short of fully stretched so the knee does not pop
+    let distance = to_target.length().clamp(
+        (upper - lower).abs() + MIN_LENGTH,
+        upper + lower - MIN_LENGTH,
+    );
+    let direction = match to_target.length() > MIN_LENGTH {
+        true => to_target.normalize(),
+        false => (end - root).normalize(),
+    };
+
+    let hint = pole.map_or(mid - root, |pole| pole - root);
+    let mut bend = hint - direction * hint.dot(direction);
+    if bend.length() < MIN_LENGTH {
+        bend = direction.cross(Vec3::X);
+        if bend.length() < MIN_LENGTH {
+            bend = direction.cross(Vec3::Y);
+        }
+    }
+    let bend = bend.normalize();
+
+    // law of cosines for the angle at the root
+    let cos = ((upper * upper + distance * distance - lower * lower) / (2.0 * upper * distance))
+        .clamp(-1.0, 1.0);
+    let sin = (1.0 - cos * cos).sqrt();
+    vec![
+        root,
+        root + direction * (upper * cos) + bend * (upper * sin),
+        root + direction * distance,
+    ]
+}
+
+fn solve_fabrik(positions: &[Vec3], target: Vec3, iterations: usize, tolerance: f32) -> Vec<Vec3> {
+    let lengths: Vec<f32> = positions
+        .windows(2)
+        .map(|pair| pair[0].distance(pair[1]))
+        .collect();
+    let root = positions[0];
+    let mut solved = positions.to_vec();
+    let last = solved.len() - 1;
+
+    if root.distance(target) >= lengths.iter().sum::<f32>() {
+        // out of reach, stretch towards it
+        let direction = (target - root).normalize();
+        for index in 0..last {
+            solved[index + 1] = solved[index] + direction * lengths[index];
+        }
+        return solved;
+    }
+
+    for _ in 0..iterations {
+        if solved[last].distance(target) <= tolerance {
+            break;
+        }
+        solved[last] = target;
+        for index in (0..last).rev() {
+            let direction = (solved[index] - solved[index + 1]).normalize();
+            solved[index] = solved[index + 1] + direction * lengths[index];
+        }
+        solved[0] = root;
+        for index in 0..last {
+            let direction = (solved[index + 1] - solved[index]).normalize();
+            solved[index + 1] = solved[index] + direction * lengths[index];
+        }
+    }
+    solved
+}
+
+// Rotates every joint of the chain, parent first, so the next joint ends up
+// at its solved position
+fn rotate_chain(
+    skeleton: &Skeleton,
+    pose: &mut Pose,
+    chain: &[usize],
+    solved: &[Vec3],
+    weight: f32,
+) {
+    for index in 0..chain.len() - 1 {
+        let (joint, child) = (chain[index], chain[index + 1]);
+        let models = pose.get_model_matrices(skeleton);
+        let rotations = pose.get_model_rotations(skeleton);
+        let current = models[child].cols[3].truncate() - models[joint].cols[3].truncate();
+        let wanted = solved[index + 1] - solved[index];
+        if current.length() < MIN_LENGTH || wanted.length() < MIN_LENGTH {
+            continue;
+        }
+
+        let delta = Quat::from_rotation_arc(current.normalize(), wanted.normalize());
+        let parent = skeleton.get_joints()[joint]
+            .parent
+            .map_or(Quat::IDENTITY, |parent| rotations[parent]);
+        let local = parent.conjugate() * delta * rotations[joint];
+        let rotation = &mut pose.locals[joint].rotation;
+        *rotation = rotation.nlerp(local.normalize(), weight.min(1.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        animation::skeleton::Joint,
+        math::transform::{Mat4, Transform},
+    };
+
+    use super::*;
+
+    // joints one unit apart along x
+    fn line(joints: usize) -> Skeleton {
+        let joints = (0..joints)
+            .map(|index| Joint {
+                name: format!("joint{}", index),
+                parent: index.checked_sub(1),
+                rest: match index {
+                    0 => Transform::IDENTITY,
+                    _ => Transform::from_translation(Vec3::X),
+                },
+                inverse_bind: Mat4::IDENTITY,
+            })
+            .collect();
+        Skeleton::new(joints).unwrap()
+    }
+
+    fn position(skeleton: &Skeleton, pose: &Pose, joint: usize) -> Vec3 {
+        pose.get_model_matrices(skeleton)[joint].cols[3].truncate()
+    }
+
+    #[test]
+    fn test_two_bone_reaches_target() {
+        let skeleton = line(3);
+        let mut pose = skeleton.get_rest_pose();
+        let mut constraint = IkConstraint::two_bone(0, 1, 2, Vec3::new(1.0, 1.0, 0.0));
+        constraint.solver = IkSolver::TwoBone {
+            root: 0,
+            mid: 1,
+            end: 2,
+            pole: Some(Vec3::new(0.0, 0.0, 1.0)),
+        };
+        constraint.validate(&skeleton).unwrap();
+        constraint.apply(&skeleton, &mut pose);
+
+        let end = position(&skeleton, &pose, 2);
+        assert!(end.distance(Vec3::new(1.0, 1.0, 0.0)) < 1e-3);
+        let mid = position(&skeleton, &pose, 1);
+        assert!((mid.length() - 1.0).abs() < 1e-4);
+        // bent towards the pole
+        assert!(mid.z > 0.0);
+
+        let backwards = IkConstraint::two_bone(2, 1, 0, Vec3::ZERO);
+        assert_eq!(
+            backwards.validate(&skeleton),
+            Err(IkErrors::NotAChain(2, 1))
+        );
+    }
+
+    #[test]
+    fn test_fabrik_chain() {
+        let skeleton = line(4);
+        let target = Vec3::new(1.5, 1.5, 0.0);
+        let constraint = IkConstraint::fabrik(vec![0, 1, 2, 3], target);
+        constraint.validate(&skeleton).unwrap();
+
+        let mut pose = skeleton.get_rest_pose();
+        constraint.apply(&skeleton, &mut pose);
+        assert!(position(&skeleton, &pose, 3).distance(target) < 1e-2);
+
+        // out of reach stretches straight at the target
+        let far = IkConstraint::fabrik(vec![0, 1, 2, 3], Vec3::new(0.0, 10.0, 0.0));
+        let mut pose = skeleton.get_rest_pose();
+        far.apply(&skeleton, &mut pose);
+        assert!(position(&skeleton, &pose, 3).distance(Vec3::new(0.0, 3.0, 0.0)) < 1e-3);
+    }
+}
@@ -0,0 +1,394 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use gltf::{
+    animation::{util::ReadOutputs, Interpolation as GltfInterpolation},
+    buffer::Source,
+    Gltf,
+};
+use thiserror::Error;
+
+use crate::math::{
+    transform::{Mat4, Quat, Transform},
+    vector::Vec3,
+};
+
+use super::{
+    clip::{AnimationClip, Interpolation, Track},
+    morph::{MorphMesh, MorphTarget, MorphTrack},
+    skeleton::{Joint, Skeleton, SkeletonErrors},
+};
+
+#[derive(Debug, Error)]
+pub enum RigImportErrors {
+    #[error("unable to read {0}: {1}")]
+    Io(PathBuf, std::io::Error),
+
+    #[error("invalid glTF: {0}")]
+    Gltf(#[from] gltf::Error),
+
+    #[error("buffer {0} is missing")]
+    MissingBuffer(usize),
+
+    #[error("buffers embedded as data URIs are not supported")]
+    UnsupportedDataUri,
+
+    #[error("the file has no skin")]
+    NoSkin,
+
+    #[error("{0}")]
+    Skeleton(#[from] SkeletonErrors),
+}
+
+// A skeleton with the clips animating it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rig {
+    pub skeleton: Skeleton,
+    pub clips: Vec<AnimationClip>,
+}
+
+// Loads the first skin of a .gltf or .glb file and every animation driving
+// its joints
+pub fn load_rig(path: &Path) -> Result<Rig, RigImportErrors> {
+    let bytes = fs::read(path).map_err(|err| RigImportErrors::Io(path.to_path_buf(), err))?;
+    load_rig_from_slice(&bytes, path.parent())
+}
+
+// `base` is where external .bin buffers are looked up
+pub fn load_rig_from_slice(bytes: &[u8], base: Option<&Path>) -> Result<Rig, RigImportErrors> {
+    let gltf = Gltf::from_slice(bytes)?;
+    let buffers = read_buffers(&gltf, base)?;
+    let get_buffer = |buffer: gltf::Buffer| buffers.get(buffer.index()).map(Vec::as_slice);
+
+    let skin = gltf.skins().next().ok_or(RigImportErrors::NoSkin)?;
+    let mut parents = HashMap::new();
+    for node in gltf.nodes() {
+        for child in node.children() {
+            parents.insert(child.index(), node.index());
+        }
+    }
+    let skin_joints: Vec<gltf::Node> = skin.joints().collect();
+    let is_joint = |node: usize| skin_joints.iter().any(|joint| joint.index() == node);
+    // the closest ancestor that is a joint, nodes in between are skipped
+    let joint_parent = |node: usize| {
+        let mut current = parents.get(&node).copied();
+        while let Some(parent) = current {
+            if is_joint(parent) {
+                return Some(parent);
+            }
+            current = parents.get(&parent).copied();
+        }
+        None
+    };
+    let depth = |node: usize| {
+        let (mut depth, mut current) = (0, joint_parent(node));
+        while let Some(parent) = current {
+            depth += 1;
+            current = joint_parent(parent);
+        }
+        depth
+    };
+
+    let inverse_binds: Vec<Mat4> = skin
+        .reader(get_buffer)
+        .read_inverse_bind_matrices()
+        .map(|matrices| matrices.map(Mat4::from_cols_array).collect())
+        .unwrap_or_default();
+    let mut order: Vec<usize> = (0..skin_joints.len()).collect();
+    order.sort_by_key(|index| depth(skin_joints[*index].index()));
+    // node index to joint index in the sorted skeleton
+    let joint_of: HashMap<usize, usize> = order
+        .iter()
+        .enumerate()
+        .map(|(joint, index)| (skin_joints[*index].index(), joint))
+        .collect();
+
+    let joints = order
+        .iter()
+        .map(|index| {
+            let node = &skin_joints[*index];
+            let (translation, rotation, scale) = node.transform().decomposed();
+            Joint {
+                name: node
+                    .name()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("joint{}", node.index())),
+                parent: joint_parent(node.index()).map(|parent| joint_of[&parent]),
+                rest: Transform {
+                    translation: to_vec3(translation),
+                    rotation: to_quat(rotation),
+                    scale: to_vec3(scale),
+                },
+                inverse_bind: inverse_binds.get(*index).copied().unwrap_or_default(),
+            }
+        })
+        .collect();
+    let skeleton = Skeleton::new(joints)?;
+
+    let mut clips = Vec::new();
+    for animation in gltf.animations() {
+        let name = animation
+            .name()
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("animation{}", animation.index()));
+        let mut clip = AnimationClip::new(&name, skeleton.len());
+        for channel in animation.channels() {
+            let node = channel.target().node();
+            let joint = joint_of.get(&node.index()).copied();
+            let reader = channel.reader(get_buffer);
+            let Some(times) = reader.read_inputs().map(Iterator::collect::<Vec<f32>>) else {
+                continue;
+            };
+            let gltf_interpolation = channel.sampler().interpolation();
+            let interpolation = match gltf_interpolation {
+                GltfInterpolation::Step => Interpolation::Step,
+                _ => Interpolation::Linear,
+            };
+            match (reader.read_outputs(), joint) {
+                (Some(ReadOutputs::Translations(values)), Some(joint)) => {
+                    let values = keys(gltf_interpolation, values.map(to_vec3).collect());
+                    clip.set_translation(joint, Track::new(times, values, interpolation));
+                }
+                (Some(ReadOutputs::Rotations(values)), Some(joint)) => {
+                    let values = keys(gltf_interpolation, values.into_f32().map(to_quat).collect());
+                    clip.set_rotation(joint, Track::new(times, values, interpolation));
+                }
+                (Some(ReadOutputs::Scales(values)), Some(joint)) => {
+                    let values = keys(gltf_interpolation, values.map(to_vec3).collect());
+                    clip.set_scale(joint, Track::new(times, values, interpolation));
+                }
+                (Some(ReadOutputs::MorphTargetWeights(values)), _) => {
+                    let Some(mesh) = node.mesh() else {
+                        continue;
+                    };
+                    let values: Vec<f32> = values.into_f32().collect();
+                    let per_key = match gltf_interpolation {
+                        GltfInterpolation::CubicSpline => times.len() * 3,
+                        _ => times.len(),
+                    };
+                    let target_count = values.len() / per_key.max(1);
+                    let weights = keys(gltf_interpolation, chunk(values, target_count));
+                    clip.add_morph_track(MorphTrack::new(
+                        &mesh_name(&mesh),
+                        times,
+                        weights.concat(),
+                        target_count,
+                        interpolation,
+                    ));
+                }
+                _ => {}
+            }
+        }
+        clips.push(clip);
+    }
+
+    Ok(Rig { skeleton, clips })
+}
+
+// Every mesh with blend shapes, primitives of a mesh are merged. glTF only
+// names targets through extras, they are called target0, target1...
+pub fn load_morph_meshes(path: &Path) -> Result<Vec<MorphMesh>, RigImportErrors> {
+    let bytes = fs::read(path).map_err(|err| RigImportErrors::Io(path.to_path_buf(), err))?;
+    load_morph_meshes_from_slice(&bytes, path.parent())
+}
+
+pub fn load_morph_meshes_from_slice(
+    bytes: &[u8],
+    base: Option<&Path>,
+) -> Result<Vec<MorphMesh>, RigImportErrors> {
+    let gltf = Gltf::from_slice(bytes)?;
+    let buffers = read_buffers(&gltf, base)?;
+    let get_buffer = |buffer: gltf::Buffer| buffers.get(buffer.index()).map(Vec::as_slice);
+
+    let mut meshes = Vec::new();
+    for mesh in gltf.meshes() {
+        let mut morph_mesh = MorphMesh {
+            name: mesh_name(&mesh),
+            positions: Vec::new(),
+            normals: Vec::new(),
+            targets: Vec::new(),
+            default_weights: mesh.weights().map(<[f32]>::to_vec).unwrap_or_default(),
+        };
+        for primitive in mesh.primitives() {
+            let reader = primitive.reader(get_buffer);
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+            let positions: Vec<Vec3> = positions.map(to_vec3).collect();
+            let vertex_count = positions.len();
+            let normals = reader
+                .read_normals()
+                .map(|normals| normals.map(to_vec3).collect())
+                .unwrap_or_else(|| vec![Vec3::ZERO; vertex_count]);
+
+            for (index, (position_deltas, normal_deltas, _)) in
+                reader.read_morph_targets().enumerate()
+            {
+                if morph_mesh.targets.len() <= index {
+                    morph_mesh.targets.push(MorphTarget {
+                        name: format!("target{}", index),
+                        positions: vec![Vec3::ZERO; morph_mesh.positions.len()],
+                        normals: vec![Vec3::ZERO; morph_mesh.normals.len()],
+                    });
+                }
+                let target = &mut morph_mesh.targets[index];
+                match position_deltas {
+                    Some(deltas) => target.positions.extend(deltas.map(to_vec3)),
+                    None => target.positions.extend(vec![Vec3::ZERO; vertex_count]),
+                }
+                match normal_deltas {
+                    Some(deltas) => target.normals.extend(deltas.map(to_vec3)),
+                    None => target.normals.extend(vec![Vec3::ZERO; vertex_count]),
+                }
+            }
+            morph_mesh.positions.extend(positions);
+            morph_mesh.normals.extend(normals);
+        }
+        if !morph_mesh.targets.is_empty() {
+            morph_mesh
+                .default_weights
+                .resize(morph_mesh.targets.len(), 0.0);
+            meshes.push(morph_mesh);
+        }
+    }
+    Ok(meshes)
+}
+
+fn mesh_name(mesh: &gltf::Mesh) -> String {
+    mesh.name()
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("mesh{}", mesh.index()))
+}
+
+pub(crate) fn read_buffers(
+    gltf: &Gltf,
+    base: Option<&Path>,
+) -> Result<Vec<Vec<u8>>, RigImportErrors> {
+    gltf.buffers()
+        .map(|buffer| match buffer.source() {
+            Source::Bin => gltf
+                .blob
+                .clone()
+                .ok_or(RigImportErrors::MissingBuffer(buffer.index())),
+            Source::Uri(uri) if uri.starts_with("data:") => {
+                Err(RigImportErrors::UnsupportedDataUri)
+            }
+            Source::Uri(uri) => {
+                let path = base.unwrap_or(Path::new("")).join(uri);
+                fs::read(&path).map_err(|err| RigImportErrors::Io(path, err))
+            }
+        })
+        .collect()
+}
+
+// Cubic splines store in tangent, value, out tangent per key, only the
+// values are kept
+fn keys<T>(interpolation: GltfInterpolation, values: Vec<T>) -> Vec<T> {
+    match interpolation {
+        GltfInterpolation::CubicSpline => values.into_iter().skip(1).step_by(3).collect(),
+        _ => values,
+    }
+}
+
+fn chunk(values: Vec<f32>, size: usize) -> Vec<Vec<f32>> {
+    values.chunks(size.max(1)).map(<[f32]>::to_vec).collect()
+}
+
+fn to_vec3([x, y, z]: [f32; 3]) -> Vec3 {
+    Vec3::new(x, y, z)
+}
+
+fn to_quat([x, y, z, w]: [f32; 4]) -> Quat {
+    Quat::new(x, y, z, w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A binary glTF with an arm of two joints, listed child first, and a
+    // clip moving the elbow
+    fn arm_glb() -> Vec<u8> {
+        let mut bin = Vec::new();
+        let mut inverse_binds = [Mat4::IDENTITY; 2];
+        inverse_binds[0] = Transform::from_translation(-Vec3::X).to_matrix();
+        for matrix in inverse_binds {
+            for value in matrix.to_cols_array().iter().flatten() {
+                bin.extend(value.to_le_bytes());
+            }
+        }
+        for value in [0.0f32, 1.0, 1.0, 0.0, 0.0, 0.0, 2.0, 0.0] {
+            bin.extend(value.to_le_bytes());
+        }
+
+        let json = format!(
+            r#"{{
+            "asset": {{"version": "2.0"}},
+            "nodes": [
+                {{"name": "elbow", "translation": [1.0, 0.0, 0.0]}},
+                {{"name": "shoulder", "children": [0]}}
+            ],
+            "skins": [{{"joints": [0, 1], "inverseBindMatrices": 0}}],
+            "buffers": [{{"byteLength": {}}}],
+            "bufferViews": [
+                {{"buffer": 0, "byteOffset": 0, "byteLength": 128}},
+                {{"buffer": 0, "byteOffset": 128, "byteLength": 8}},
+                {{"buffer": 0, "byteOffset": 136, "byteLength": 24}}
+            ],
+            "accessors": [
+                {{"bufferView": 0, "componentType": 5126, "count": 2, "type": "MAT4"}},
+                {{"bufferView": 1, "componentType": 5126, "count": 2, "type": "SCALAR",
+                  "min": [0.0], "max": [1.0]}},
+                {{"bufferView": 2, "componentType": 5126, "count": 2, "type": "VEC3"}}
+            ],
+            "animations": [{{
+                "name": "wave",
+                "channels": [{{"sampler": 0, "target": {{"node": 0, "path": "translation"}}}}],
+                "samplers": [{{"input": 1, "output": 2, "interpolation": "STEP"}}]
+            }}]
+        }}"#,
+            bin.len()
+        );
+        let mut json = json.into_bytes();
+        json.resize(json.len().div_ceil(4) * 4, b' ');
+
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        let mut glb = Vec::new();
+        glb.extend(b"glTF");
+        glb.extend(2u32.to_le_bytes());
+        glb.extend((length as u32).to_le_bytes());
+        glb.extend((json.len() as u32).to_le_bytes());
+        glb.extend(b"JSON");
+        glb.extend(json);
+        glb.extend((bin.len() as u32).to_le_bytes());
+        glb.extend(b"BIN\0");
+        glb.extend(bin);
+        glb
+    }
+
+    #[test]
+    fn test_load_rig_from_glb() {
+        let rig = load_rig_from_slice(&arm_glb(), None).unwrap();
+
+        let joints = rig.skeleton.get_joints();
+        assert_eq!(joints[0].name, "shoulder");
+        assert_eq!(joints[1].name, "elbow");
+        assert_eq!(joints[1].parent, Some(0));
+        assert_eq!(joints[1].rest.translation, Vec3::X);
+        assert_eq!(
+            joints[1].inverse_bind,
+            Transform::from_translation(-Vec3::X).to_matrix()
+        );
+
+        let clip = &rig.clips[0];
+        assert_eq!((clip.name.as_str(), clip.duration), ("wave", 1.0));
+        let mut pose = rig.skeleton.get_rest_pose();
+        clip.sample(0.9, &mut pose);
+        assert_eq!(pose.locals[1].translation, Vec3::X);
+        clip.sample(1.0, &mut pose);
+        assert_eq!(pose.locals[1].translation, Vec3::new(0.0, 2.0, 0.0));
+    }
+}
@@ -0,0 +1,7 @@
+pub mod animator;
+pub mod clip;
+pub mod ik;
+pub mod import;
+pub mod morph;
+pub mod skeleton;
+pub mod sprite;
@@ -0,0 +1,180 @@
+use std::collections::BTreeMap;
+
+use crate::math::vector::Vec3;
+
+use super::clip::Interpolation;
+
+// Offsets from the base mesh, one per vertex. Normals can be left empty for
+// targets that only move vertices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MorphTarget {
+    pub name: String,
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+}
+
+// A mesh with blend shapes, e.g. the visemes and expressions of a face
+#[derive(Debug, Clone, PartialEq)]
+pub struct MorphMesh {
+    pub name: String,
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub targets: Vec<MorphTarget>,
+    // weights the mesh rests at when nothing drives it
+    pub default_weights: Vec<f32>,
+}
+
+impl MorphMesh {
+    pub fn find_target(&self, name: &str) -> Option<usize> {
+        self.targets.iter().position(|target| target.name == name)
+    }
+
+    // The blended positions and normals, what the vertex stage does on the
+    // GPU
+    pub fn blend(&self, weights: &[f32]) -> (Vec<Vec3>, Vec<Vec3>) {
+        let mut positions = self.positions.clone();
+        let mut normals = self.normals.clone();
+        for (target, weight) in self.targets.iter().zip(weights) {
+            if *weight == 0.0 {
+                continue;
+            }
+            for (position, delta) in positions.iter_mut().zip(&target.positions) {
+                *position += *delta * *weight;
+            }
+            for (normal, delta) in normals.iter_mut().zip(&target.normals) {
+                *normal += *delta * *weight;
+            }
+        }
+        for normal in normals.iter_mut() {
+            *normal = normal.normalize();
+        }
+        (positions, normals)
+    }
+}
+
+// Weights of every target of a mesh over time. `weights` holds
+// `target_count` values per key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MorphTrack {
+    pub mesh: String,
+    pub times: Vec<f32>,
+    pub weights: Vec<f32>,
+    pub target_count: usize,
+    pub interpolation: Interpolation,
+}
+
+impl MorphTrack {
+    pub fn new(
+        mesh: &str,
+        times: Vec<f32>,
+        weights: Vec<f32>,
+        target_count: usize,
+        interpolation: Interpolation,
+    ) -> Self {
+        Self {
+            mesh: mesh.to_string(),
+            times,
+            weights,
+            target_count,
+            interpolation,
+        }
+    }
+
+    pub fn get_duration(&self) -> f32 {
+        self.times.last().copied().unwrap_or(0.0)
+    }
+
+    fn get_key(&self, key: usize) -> &[f32] {
+        &self.weights[key * self.target_count..(key + 1) * self.target_count]
+    }
+
+    pub fn sample(&self, time: f32) -> Option<Vec<f32>> {
+        let keys = match self.target_count {
+            0 => 0,
+            count => self.times.len().min(self.weights.len() / count),
+        };
+        let last = keys.checked_sub(1)?;
+        let next = self.times[..=last].partition_point(|key| *key <= time);
+        if next == 0 {
+            return Some(self.get_key(0).to_vec());
+        }
+        if next > last {
+            return Some(self.get_key(last).to_vec());
+        }
+        let (from, to) = (self.times[next - 1], self.times[next]);
+        let t = match self.interpolation {
+            Interpolation::Step => 0.0,
+            Interpolation::Linear => (time - from) / (to - from).max(f32::EPSILON),
+        };
+        let weights = self
+            .get_key(next - 1)
+            .iter()
+            .zip(self.get_key(next))
+            .map(|(from, to)| from + (to - from) * t)
+            .collect();
+        Some(weights)
+    }
+}
+
+// Moves `weight` of the way from `into` to `other` for every mesh in
+// `other`, meshes missing from `into` start at zero
+pub fn blend_morph_weights(
+    into: &mut BTreeMap<String, Vec<f32>>,
+    other: &BTreeMap<String, Vec<f32>>,
+    weight: f32,
+) {
+    let weight = weight.clamp(0.0, 1.0);
+    for (mesh, targets) in other {
+        let current = into
+            .entry(mesh.clone())
+            .or_insert_with(|| vec![0.0; targets.len()]);
+        current.resize(current.len().max(targets.len()), 0.0);
+        for (current, target) in current.iter_mut().zip(targets) {
+            *current += (target - *current) * weight;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_and_sample() {
+        let mesh = MorphMesh {
+            name: "face".to_string(),
+            positions: vec![Vec3::ZERO, Vec3::X],
+            normals: vec![Vec3::Z, Vec3::Z],
+            targets: vec![
+                MorphTarget {
+                    name: "smile".to_string(),
+                    positions: vec![Vec3::Y, Vec3::ZERO],
+                    normals: Vec::new(),
+                },
+                MorphTarget {
+                    name: "blink".to_string(),
+                    positions: vec![Vec3::ZERO, Vec3::new(0.0, -2.0, 0.0)],
+                    normals: Vec::new(),
+                },
+            ],
+            default_weights: vec![0.0, 0.0],
+        };
+        let (positions, normals) = mesh.blend(&[0.5, 0.25]);
+        assert_eq!(
+            positions,
+            vec![Vec3::new(0.0, 0.5, 0.0), Vec3::new(1.0, -0.5, 0.0)]
+        );
+        assert_eq!(normals, vec![Vec3::Z, Vec3::Z]);
+        assert_eq!(mesh.find_target("blink"), Some(1));
+
+        let track = MorphTrack::new(
+            "face",
+            vec![0.0, 1.0],
+            vec![0.0, 1.0, 1.0, 0.0],
+            2,
+            Interpolation::Linear,
+        );
+        assert_eq!(track.sample(0.25), Some(vec![0.25, 0.75]));
+        assert_eq!(track.sample(2.0), Some(vec![1.0, 0.0]));
+    }
+}
@@ -0,0 +1,187 @@
+use thiserror::Error;
+
+use crate::math::transform::{Mat4, Quat, Transform};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SkeletonErrors {
+    #[error("joint {0} comes before its parent")]
+    ParentAfterChild(usize),
+
+    #[error("joint {0} has a parent that does not exist")]
+    UnknownParent(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Joint {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub rest: Transform,
+    // model space to joint space in the bind pose
+    pub inverse_bind: Mat4,
+}
+
+// Joints are sorted so every parent comes before its children, a pose can
+// then be resolved to model space in a single pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skeleton {
+    joints: Vec<Joint>,
+}
+
+impl Skeleton {
+    pub fn new(joints: Vec<Joint>) -> Result<Self, SkeletonErrors> {
+        for (index, joint) in joints.iter().enumerate() {
+            match joint.parent {
+                Some(parent) if parent >= joints.len() => {
+                    return Err(SkeletonErrors::UnknownParent(index))
+                }
+                Some(parent) if parent >= index => {
+                    return Err(SkeletonErrors::ParentAfterChild(index))
+                }
+                _ => {}
+            }
+        }
+        Ok(Self { joints })
+    }
+
+    pub fn get_joints(&self) -> &[Joint] {
+        &self.joints
+    }
+
+    pub fn len(&self) -> usize {
+        self.joints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.joints.is_empty()
+    }
+
+    pub fn find_joint(&self, name: &str) -> Option<usize> {
+        self.joints.iter().position(|joint| joint.name == name)
+    }
+
+    pub fn is_ancestor(&self, ancestor: usize, joint: usize) -> bool {
+        let mut current = self.joints.get(joint).and_then(|joint| joint.parent);
+        while let Some(parent) = current {
+            if parent == ancestor {
+                return true;
+            }
+            current = self.joints[parent].parent;
+        }
+        false
+    }
+
+    pub fn get_rest_pose(&self) -> Pose {
+        Pose {
+            locals: self.joints.iter().map(|joint| joint.rest).collect(),
+        }
+    }
+
+    // A layer mask covering `root` and everything below it, e.g. the upper
+    // body from the spine up
+    pub fn get_mask_below(&self, root: usize) -> Vec<f32> {
+        let mut mask = vec![0.0; self.joints.len()];
+        for (index, joint) in self.joints.iter().enumerate() {
+            let inside = index == root || joint.parent.is_some_and(|parent| mask[parent] > 0.0);
+            if inside {
+                mask[index] = 1.0;
+            }
+        }
+        mask
+    }
+}
+
+// Local transforms of every joint, relative to its parent
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pose {
+    pub locals: Vec<Transform>,
+}
+
+impl Pose {
+    // Moves `weight` of the way towards `other`, per joint scaled by the mask
+    pub fn blend(&mut self, other: &Pose, weight: f32, mask: Option<&[f32]>) {
+        for (index, (local, target)) in self.locals.iter_mut().zip(&other.locals).enumerate() {
+            let mask = mask
+                .and_then(|mask| mask.get(index))
+                .copied()
+                .unwrap_or(1.0);
+            let weight = (weight * mask).clamp(0.0, 1.0);
+            if weight > 0.0 {
+                *local = local.lerp(target, weight);
+            }
+        }
+    }
+
+    pub fn get_model_matrices(&self, skeleton: &Skeleton) -> Vec<Mat4> {
+        let mut models: Vec<Mat4> = Vec::with_capacity(self.locals.len());
+        for (joint, local) in skeleton.get_joints().iter().zip(&self.locals) {
+            let model = match joint.parent {
+                Some(parent) => models[parent] * local.to_matrix(),
+                None => local.to_matrix(),
+            };
+            models.push(model);
+        }
+        models
+    }
+
+    // Rotation of every joint in model space
+    pub fn get_model_rotations(&self, skeleton: &Skeleton) -> Vec<Quat> {
+        let mut rotations: Vec<Quat> = Vec::with_capacity(self.locals.len());
+        for (joint, local) in skeleton.get_joints().iter().zip(&self.locals) {
+            let rotation = match joint.parent {
+                Some(parent) => rotations[parent] * local.rotation,
+                None => local.rotation,
+            };
+            rotations.push(rotation);
+        }
+        rotations
+    }
+
+    // The joint palette the skinning vertex shader reads, one matrix per
+    // joint taking a bind pose vertex to its posed position
+    pub fn get_skinning_matrices(&self, skeleton: &Skeleton) -> Vec<Mat4> {
+        self.get_model_matrices(skeleton)
+            .into_iter()
+            .zip(skeleton.get_joints())
+            .map(|(model, joint)| model * joint.inverse_bind)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::vector::Vec3;
+
+    use super::*;
+
+    #[test]
+    fn test_skinning_matrices_follow_parents() {
+        let joint = |name: &str, parent, rest| Joint {
+            name: name.to_string(),
+            parent,
+            rest,
+            inverse_bind: Mat4::IDENTITY,
+        };
+        let mut arm = vec![
+            joint("shoulder", None, Transform::IDENTITY),
+            joint("elbow", Some(0), Transform::from_translation(Vec3::X)),
+        ];
+        // bind pose of the elbow is one unit along x
+        arm[1].inverse_bind = Transform::from_translation(-Vec3::X).to_matrix();
+        let skeleton = Skeleton::new(arm.clone()).unwrap();
+
+        let rest = skeleton.get_rest_pose().get_skinning_matrices(&skeleton);
+        assert_eq!(rest[1].transform_point(Vec3::X), Vec3::X);
+
+        let mut raised = skeleton.get_rest_pose();
+        raised.locals[0].translation = Vec3::Y;
+        let skinning = raised.get_skinning_matrices(&skeleton);
+        assert_eq!(
+            skinning[1].transform_point(Vec3::X),
+            Vec3::new(1.0, 1.0, 0.0)
+        );
+
+        arm.swap(0, 1);
+        assert_eq!(Skeleton::new(arm), Err(SkeletonErrors::ParentAfterChild(0)));
+        assert_eq!(skeleton.get_mask_below(1), vec![0.0, 1.0]);
+    }
+}
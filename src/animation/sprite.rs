@@ -0,0 +1,435 @@
+use std::{collections::BTreeMap, path::Path, sync::Arc, time::Duration};
+
+use log::error;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    core::{
+        data_file::{load_data_file, DataFileErrors},
+        runner::subsystem::{Subsystem, SubsystemContext},
+    },
+    event_system::{
+        engine_events::animation_events::{AnimationEvents, AnimationFinished},
+        event::{EntityId, TargetedEvent},
+    },
+    math::{rect::Rect, vector::Vec2},
+};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SpriteErrors {
+    #[error("unable to load sprite sheet: {0}")]
+    File(#[from] DataFileErrors),
+
+    #[error("the sprite sheet has no clip {0}")]
+    UnknownClip(String),
+
+    #[error("clip {0} uses frames outside of the sheet")]
+    FramesOutOfSheet(String),
+}
+
+// A run of frames of the sheet played one after the other. `first` and
+// `last` are frame indices, `last` included, counted left to right then
+// top to bottom.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpriteClip {
+    pub name: String,
+    pub first: u32,
+    pub last: u32,
+    // seconds per frame
+    pub frame_time: f32,
+    // per frame overrides of `frame_time`, e.g. a held anticipation frame
+    pub frame_times: Vec<f32>,
+    pub looping: bool,
+}
+
+impl Default for SpriteClip {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            first: 0,
+            last: 0,
+            frame_time: 0.1,
+            frame_times: Vec::new(),
+            looping: true,
+        }
+    }
+}
+
+impl SpriteClip {
+    pub fn get_frame_count(&self) -> u32 {
+        self.last.saturating_sub(self.first) + 1
+    }
+
+    // How long the `index`th frame of the clip shows
+    pub fn get_frame_time(&self, index: u32) -> f32 {
+        self.frame_times
+            .get(index as usize)
+            .copied()
+            .unwrap_or(self.frame_time)
+            .max(0.0)
+    }
+
+    pub fn get_duration(&self) -> f32 {
+        (0..self.get_frame_count())
+            .map(|index| self.get_frame_time(index))
+            .sum()
+    }
+}
+
+// A texture cut in a grid of equally sized frames and the clips played from
+// them, usually loaded next to the texture:
+//
+//     columns = 8
+//     rows = 4
+//
+//     [[clips]]
+//     name = "run"
+//     first = 8
+//     last = 15
+//     frame_time = 0.08
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpriteSheet {
+    pub columns: u32,
+    pub rows: u32,
+    pub clips: Vec<SpriteClip>,
+}
+
+impl Default for SpriteSheet {
+    fn default() -> Self {
+        Self {
+            columns: 1,
+            rows: 1,
+            clips: Vec::new(),
+        }
+    }
+}
+
+impl SpriteSheet {
+    pub fn new(columns: u32, rows: u32) -> Self {
+        Self {
+            columns: columns.max(1),
+            rows: rows.max(1),
+            clips: Vec::new(),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SpriteErrors> {
+        let sheet: Self = load_data_file(path)?;
+        sheet.validate()?;
+        Ok(sheet)
+    }
+
+    pub fn validate(&self) -> Result<(), SpriteErrors> {
+        let frames = self.get_frame_count();
+        match self
+            .clips
+            .iter()
+            .find(|clip| clip.last < clip.first || clip.last >= frames)
+        {
+            Some(clip) => Err(SpriteErrors::FramesOutOfSheet(clip.name.clone())),
+            None => Ok(()),
+        }
+    }
+
+    pub fn add_clip(&mut self, clip: SpriteClip) -> Result<(), SpriteErrors> {
+        if clip.last < clip.first || clip.last >= self.get_frame_count() {
+            return Err(SpriteErrors::FramesOutOfSheet(clip.name));
+        }
+        self.clips.retain(|existing| existing.name != clip.name);
+        self.clips.push(clip);
+        Ok(())
+    }
+
+    pub fn get_clip(&self, name: &str) -> Option<&SpriteClip> {
+        self.clips.iter().find(|clip| clip.name == name)
+    }
+
+    pub fn get_frame_count(&self) -> u32 {
+        self.columns.max(1) * self.rows.max(1)
+    }
+
+    // The frame's part of the texture, in the fractions `draw_sprite` takes
+    pub fn get_frame_uv(&self, frame: u32) -> Rect {
+        let (columns, rows) = (self.columns.max(1), self.rows.max(1));
+        let size = Vec2::new(1.0 / columns as f32, 1.0 / rows as f32);
+        let position = Vec2::new(
+            (frame % columns) as f32 * size.x,
+            (frame / columns % rows) as f32 * size.y,
+        );
+        Rect::from_size(position, size)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SpritePlayerId(u64);
+
+// Plays the clips of one sheet, e.g. a character's. Advanced with the
+// scaled delta by SpritePlayers, or by hand with `update`.
+#[derive(Debug, Clone)]
+pub struct SpritePlayer {
+    sheet: Arc<SpriteSheet>,
+    clip: Option<usize>,
+    // frame of the clip and how long it has shown
+    index: u32,
+    time: f32,
+    finished: bool,
+    pub speed: f32,
+    pub flip_x: bool,
+    pub flip_y: bool,
+    // receives the AnimationFinished events
+    pub owner: Option<EntityId>,
+}
+
+impl SpritePlayer {
+    pub fn new(sheet: Arc<SpriteSheet>) -> Self {
+        Self {
+            sheet,
+            clip: None,
+            index: 0,
+            time: 0.0,
+            finished: false,
+            speed: 1.0,
+            flip_x: false,
+            flip_y: false,
+            owner: None,
+        }
+    }
+
+    pub fn get_sheet(&self) -> &SpriteSheet {
+        &self.sheet
+    }
+
+    // Starts the clip from its first frame. Playing the current clip does
+    // nothing, even once it finished, so this can be called every frame.
+    pub fn play(&mut self, clip: &str) -> Result<(), SpriteErrors> {
+        if self.get_clip().is_some_and(|playing| playing.name == clip) {
+            return Ok(());
+        }
+        self.restart(clip)
+    }
+
+    // Starts the clip from its first frame even when it already plays
+    pub fn restart(&mut self, clip: &str) -> Result<(), SpriteErrors> {
+        let index = self
+            .sheet
+            .clips
+            .iter()
+            .position(|existing| existing.name == clip)
+            .ok_or_else(|| SpriteErrors::UnknownClip(clip.to_string()))?;
+        self.clip = Some(index);
+        self.index = 0;
+        self.time = 0.0;
+        self.finished = false;
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        self.clip = None;
+    }
+
+    pub fn get_clip(&self) -> Option<&SpriteClip> {
+        self.clip.map(|clip| &self.sheet.clips[clip])
+    }
+
+    // A clip that does not loop stays on its last frame once finished
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    // The frame of the sheet to draw
+    pub fn get_frame(&self) -> u32 {
+        self.get_clip()
+            .map(|clip| clip.first + self.index)
+            .unwrap_or(0)
+    }
+
+    pub fn get_uv(&self) -> Rect {
+        let uv = self.sheet.get_frame_uv(self.get_frame());
+        let from = Vec2::new(self.flip_x as u8 as f32, self.flip_y as u8 as f32);
+        uv.sub(from, Vec2::new(1.0 - from.x, 1.0 - from.y))
+    }
+
+    // True when a clip that does not loop reached its end during this
+    // update
+    pub fn update(&mut self, delta: Duration) -> bool {
+        let Some(clip) = self.clip.map(|clip| &self.sheet.clips[clip]) else {
+            return false;
+        };
+        if self.finished {
+            return false;
+        }
+        self.time += delta.as_secs_f32() * self.speed.max(0.0);
+        let count = clip.get_frame_count();
+        loop {
+            let frame_time = clip.get_frame_time(self.index);
+            if self.time < frame_time {
+                return false;
+            }
+            if self.index + 1 == count && !clip.looping {
+                self.time = frame_time;
+                self.finished = true;
+                return true;
+            }
+            // a clip of zero length frames would loop forever
+            if frame_time <= 0.0 && clip.get_duration() <= 0.0 {
+                self.time = 0.0;
+                return false;
+            }
+            self.time -= frame_time;
+            self.index = (self.index + 1) % count;
+        }
+    }
+}
+
+// Every sprite player of the scene, advanced with the scaled delta. Clips
+// reaching their end are sent as AnimationEvents::Finished, targeted at the
+// player's owner when it has one.
+#[derive(Debug, Default)]
+pub struct SpritePlayers {
+    players: BTreeMap<SpritePlayerId, SpritePlayer>,
+    next_id: u64,
+}
+
+impl SpritePlayers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, player: SpritePlayer) -> SpritePlayerId {
+        let id = SpritePlayerId(self.next_id);
+        self.next_id += 1;
+        self.players.insert(id, player);
+        id
+    }
+
+    pub fn get(&self, id: SpritePlayerId) -> Option<&SpritePlayer> {
+        self.players.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: SpritePlayerId) -> Option<&mut SpritePlayer> {
+        self.players.get_mut(&id)
+    }
+
+    pub fn remove(&mut self, id: SpritePlayerId) -> Option<SpritePlayer> {
+        self.players.remove(&id)
+    }
+
+    pub fn update(&mut self, delta: Duration) -> Vec<(Option<EntityId>, AnimationFinished)> {
+        let mut finished = Vec::new();
+        for (id, player) in self.players.iter_mut() {
+            if player.update(delta) {
+                let clip = player
+                    .get_clip()
+                    .map(|clip| clip.name.clone())
+                    .unwrap_or_default();
+                finished.push((player.owner, AnimationFinished { player: *id, clip }));
+            }
+        }
+        finished
+    }
+}
+
+impl Subsystem for SpritePlayers {
+    fn get_name(&self) -> &str {
+        "SpritePlayers"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        for (owner, finished) in self.update(ctx.time.get_delta()) {
+            let event = AnimationEvents::Finished(finished);
+            let result = match owner {
+                Some(owner) => ctx
+                    .event_queue
+                    .emit(Box::new(TargetedEvent::new(owner, event))),
+                None => ctx.event_queue.emit(Box::new(event)),
+            };
+            if let Err(err) = result {
+                error!("unable to emit animation event {:?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flipbook_playback() {
+        let sheet: SpriteSheet = toml::from_str(
+            r#"
+            columns = 4
+            rows = 2
+
+            [[clips]]
+            name = "run"
+            first = 0
+            last = 3
+            frame_time = 0.1
+
+            [[clips]]
+            name = "attack"
+            first = 4
+            last = 6
+            frame_time = 0.1
+            frame_times = [0.3]
+            looping = false
+            "#,
+        )
+        .unwrap();
+        sheet.validate().unwrap();
+        assert_eq!(
+            sheet.get_frame_uv(5),
+            Rect::new(Vec2::new(0.25, 0.5), Vec2::new(0.5, 1.0))
+        );
+
+        let mut players = SpritePlayers::new();
+        let mut player = SpritePlayer::new(Arc::new(sheet));
+        player.play("run").unwrap();
+        let id = players.add(player);
+
+        players.update(Duration::from_millis(250));
+        assert_eq!(players.get(id).unwrap().get_frame(), 2);
+        // wraps around and never finishes
+        assert!(players.update(Duration::from_millis(200)).is_empty());
+        assert_eq!(players.get(id).unwrap().get_frame(), 0);
+
+        let player = players.get_mut(id).unwrap();
+        assert_eq!(
+            player.play("jump"),
+            Err(SpriteErrors::UnknownClip("jump".to_string()))
+        );
+        player.play("attack").unwrap();
+        player.flip_x = true;
+        players.update(Duration::from_millis(350));
+        assert_eq!(players.get(id).unwrap().get_frame(), 5);
+        assert_eq!(
+            players.get(id).unwrap().get_uv(),
+            Rect::new(Vec2::new(0.5, 0.5), Vec2::new(0.25, 1.0))
+        );
+
+        let finished = players.update(Duration::from_millis(200));
+        assert_eq!(
+            finished,
+            vec![(
+                None,
+                AnimationFinished {
+                    player: id,
+                    clip: "attack".to_string(),
+                }
+            )]
+        );
+        // holds the last frame
+        let player = players.get(id).unwrap();
+        assert!(player.is_finished());
+        assert_eq!(player.get_frame(), 6);
+        assert!(players.update(Duration::from_secs(1)).is_empty());
+    }
+}
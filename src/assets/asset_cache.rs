@@ -0,0 +1,277 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AssetCacheErrors {
+    #[error("unable to read from the asset cache: {0}")]
+    UnableToRead(io::Error),
+
+    #[error("unable to write to the asset cache: {0}")]
+    UnableToWrite(io::Error),
+
+    #[error("importer {0} failed: {1}")]
+    ImportFailed(String, String),
+}
+
+// A cache key is the hash of the source bytes together with the importer
+// that produced the artifact. Bumping the importer version invalidates every
+// artifact it produced earlier without having to wipe the cache directory.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    importer: String,
+    importer_version: u32,
+    content_hash: String,
+}
+
+impl CacheKey {
+    pub fn new(source: &[u8], importer: &str, importer_version: u32) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(source);
+        let content_hash = hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+
+        Self {
+            importer: importer.to_string(),
+            importer_version,
+            content_hash,
+        }
+    }
+
+    pub fn get_importer(&self) -> &str {
+        &self.importer
+    }
+
+    pub fn get_importer_version(&self) -> u32 {
+        self.importer_version
+    }
+
+    pub fn get_content_hash(&self) -> &str {
+        &self.content_hash
+    }
+
+    // <importer>/v<version>/<first two chars of hash>/<hash>
+    fn relative_path(&self) -> PathBuf {
+        PathBuf::from(escape_path_component(&self.importer))
+            .join(format!("v{}", self.importer_version))
+            .join(&self.content_hash[..2])
+            .join(&self.content_hash)
+    }
+}
+
+// Importer names end up as a directory name, anything besides ascii letters,
+// digits, `-` and `_` is escaped as %xx so names like `../x` or `a/b` cannot
+// leave the importer's directory or collide with another importer
+fn escape_path_component(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            escaped.push(byte as char);
+        } else {
+            escaped.push_str(&format!("%{:02x}", byte));
+        }
+    }
+    escaped
+}
+
+#[derive(Debug, Clone)]
+pub struct AssetCache {
+    root: PathBuf,
+}
+
+impl AssetCache {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn get_root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn path_for(&self, key: &CacheKey) -> PathBuf {
+        self.root.join(key.relative_path())
+    }
+
+    pub fn contains(&self, key: &CacheKey) -> bool {
+        self.path_for(key).is_file()
+    }
+
+    pub fn get(&self, key: &CacheKey) -> Result<Option<Vec<u8>>, AssetCacheErrors> {
+        match fs::read(self.path_for(key)) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(AssetCacheErrors::UnableToRead(err)),
+        }
+    }
+
+    pub fn put(&self, key: &CacheKey, artifact: &[u8]) -> Result<(), AssetCacheErrors> {
+        let path = self.path_for(key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(AssetCacheErrors::UnableToWrite)?;
+        }
+
+        // write to a temporary file first and rename it, so that a shared cache
+        // never exposes a half written artifact to another process
+        let tmp_path = path.with_extension(format!("tmp-{}", std::process::id()));
+        let write_result = fs::File::create(&tmp_path).and_then(|mut file| {
+            file.write_all(artifact)?;
+            file.sync_all()
+        });
+        if let Err(err) = write_result {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(AssetCacheErrors::UnableToWrite(err));
+        }
+
+        fs::rename(&tmp_path, &path).map_err(AssetCacheErrors::UnableToWrite)
+    }
+
+    pub fn remove(&self, key: &CacheKey) -> Result<bool, AssetCacheErrors> {
+        match fs::remove_file(self.path_for(key)) {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(AssetCacheErrors::UnableToWrite(err)),
+        }
+    }
+
+    // Returns the cached artifact for the source, running the importer only on
+    // a cache miss.
+    pub fn get_or_import<F>(
+        &self,
+        source: &[u8],
+        importer: &str,
+        importer_version: u32,
+        import: F,
+    ) -> Result<Vec<u8>, AssetCacheErrors>
+    where
+        F: FnOnce(&[u8]) -> Result<Vec<u8>, String>,
+    {
+        let key = CacheKey::new(source, importer, importer_version);
+        if let Some(artifact) = self.get(&key)? {
+            info!("asset cache hit for {} ({})", importer, key.content_hash);
+            return Ok(artifact);
+        }
+
+        info!("asset cache miss for {} ({})", importer, key.content_hash);
+        let artifact = import(source)
+            .map_err(|err| AssetCacheErrors::ImportFailed(importer.to_string(), err))?;
+
+        if let Err(err) = self.put(&key, &artifact) {
+            // the artifact is still usable even if we could not persist it
+            warn!("unable to store artifact in asset cache: {}", err);
+        }
+        Ok(artifact)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use super::*;
+
+    static CACHE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    fn temp_cache() -> AssetCache {
+        let id = CACHE_COUNTER.fetch_add(1, Ordering::SeqCst);
+        let root = std::env::temp_dir().join(format!(
+            "aloy-asset-cache-test-{}-{}",
+            std::process::id(),
+            id
+        ));
+        let _ = fs::remove_dir_all(&root);
+        AssetCache::new(root)
+    }
+
+    #[test]
+    fn test_cache_key_is_content_addressed() {
+        let key1 = CacheKey::new(b"shader source", "shader", 1);
+        let key2 = CacheKey::new(b"shader source", "shader", 1);
+        let key3 = CacheKey::new(b"other source", "shader", 1);
+        let key4 = CacheKey::new(b"shader source", "shader", 2);
+
+        assert_eq!(key1, key2);
+        assert_ne!(key1, key3);
+        assert_ne!(key1, key4, "Importer version should be part of the key");
+    }
+
+    #[test]
+    fn test_importer_names_stay_inside_the_cache() {
+        let cache = AssetCache::new("cache");
+        for importer in ["../outside", "a/b", "a\\b", "..", "/abs"] {
+            let path = cache.path_for(&CacheKey::new(b"source", importer, 1));
+            let importer_dir = path.strip_prefix("cache").unwrap().components().next();
+            assert!(
+                matches!(importer_dir, Some(std::path::Component::Normal(_))),
+                "{} escaped to {:?}",
+                importer,
+                path
+            );
+            assert_eq!(path.components().count(), 5, "{:?}", path);
+        }
+        assert_ne!(
+            cache.path_for(&CacheKey::new(b"source", "a/b", 1)),
+            cache.path_for(&CacheKey::new(b"source", "a_b", 1))
+        );
+        assert!(cache
+            .path_for(&CacheKey::new(b"source", "shader", 1))
+            .starts_with("cache/shader/v1"));
+    }
+
+    #[test]
+    fn test_put_and_get_artifact() {
+        let cache = temp_cache();
+        let key = CacheKey::new(b"texture", "texture", 1);
+
+        assert!(cache.get(&key).unwrap().is_none());
+        cache.put(&key, b"transcoded").expect("put should succeed");
+        assert!(cache.contains(&key));
+        assert_eq!(cache.get(&key).unwrap().unwrap(), b"transcoded");
+
+        assert!(cache.remove(&key).unwrap());
+        assert!(!cache.contains(&key));
+        let _ = fs::remove_dir_all(cache.get_root());
+    }
+
+    #[test]
+    fn test_get_or_import_skips_importer_on_hit() {
+        let cache = temp_cache();
+        let calls = Cell::new(0);
+        let import = |source: &[u8]| {
+            calls.set(calls.get() + 1);
+            Ok(source.iter().rev().copied().collect())
+        };
+
+        let first = cache
+            .get_or_import(b"navmesh", "navmesh", 1, import)
+            .unwrap();
+        let second = cache
+            .get_or_import(b"navmesh", "navmesh", 1, import)
+            .unwrap();
+
+        assert_eq!(first, b"hsemvan");
+        assert_eq!(first, second);
+        assert_eq!(calls.get(), 1, "Importer should only run on a cache miss");
+        let _ = fs::remove_dir_all(cache.get_root());
+    }
+
+    #[test]
+    fn test_get_or_import_failure() {
+        let cache = temp_cache();
+        let result = cache.get_or_import(b"broken", "shader", 1, |_| Err("syntax".to_string()));
+
+        assert!(matches!(result, Err(AssetCacheErrors::ImportFailed(_, _))));
+        assert!(!cache.contains(&CacheKey::new(b"broken", "shader", 1)));
+    }
+}
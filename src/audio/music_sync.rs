@@ -0,0 +1,268 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use log::error;
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::engine_events::audio_events::{AudioEvents, SyncPoint},
+};
+
+// Shared between the audio callback, which advances it by the number of
+// samples it has actually played, and the engine thread which reads it.
+#[derive(Debug, Clone)]
+pub struct PlaybackClock {
+    samples_played: Arc<AtomicU64>,
+    sample_rate: u32,
+}
+
+impl PlaybackClock {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            samples_played: Arc::new(AtomicU64::new(0)),
+            sample_rate,
+        }
+    }
+
+    pub fn get_sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    pub fn advance(&self, samples: u64) {
+        self.samples_played.fetch_add(samples, Ordering::Release);
+    }
+
+    pub fn seek(&self, sample: u64) {
+        self.samples_played.store(sample, Ordering::Release);
+    }
+
+    pub fn get_position(&self) -> u64 {
+        self.samples_played.load(Ordering::Acquire)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Marker {
+    pub name: String,
+    pub sample: u64,
+}
+
+#[derive(Debug)]
+pub struct TrackSync {
+    name: String,
+    clock: PlaybackClock,
+    bpm: f64,
+    // sample of the first beat, songs rarely start exactly on a beat
+    first_beat: u64,
+    markers: Vec<Marker>,
+    last_position: Option<u64>,
+}
+
+impl TrackSync {
+    pub fn new(name: &str, clock: PlaybackClock, bpm: f64, first_beat: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            clock,
+            bpm,
+            first_beat,
+            markers: Vec::new(),
+            last_position: None,
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn add_marker(&mut self, name: &str, sample: u64) {
+        self.markers.push(Marker {
+            name: name.to_string(),
+            sample,
+        });
+        self.markers.sort_by_key(|marker| marker.sample);
+    }
+
+    fn samples_per_beat(&self) -> f64 {
+        self.clock.get_sample_rate() as f64 * 60.0 / self.bpm
+    }
+
+    fn beat_sample(&self, beat: u64) -> u64 {
+        self.first_beat + (beat as f64 * self.samples_per_beat()).round() as u64
+    }
+
+    fn sync_point(&self, sample: u64) -> SyncPoint {
+        SyncPoint {
+            track: self.name.clone(),
+            sample,
+            seconds: sample as f64 / self.clock.get_sample_rate() as f64,
+        }
+    }
+
+    // Returns every beat and marker crossed since the last poll, in playback
+    // order. A backwards jump (seek or loop) only resets the position.
+    pub fn poll(&mut self) -> Vec<AudioEvents> {
+        let position = self.clock.get_position();
+        let mut events = Vec::new();
+
+        let last = match self.last_position {
+            Some(last) if position < last => {
+                self.last_position = Some(position);
+                return Vec::new();
+            }
+            Some(last) if position == last => return Vec::new(),
+            last => last,
+        };
+
+        let is_new = |sample: u64| sample <= position && last.is_none_or(|last| sample > last);
+
+        if self.bpm > 0.0 {
+            let mut beat = match last {
+                Some(last) if last >= self.first_beat => {
+                    ((last - self.first_beat) as f64 / self.samples_per_beat()).floor() as u64
+                }
+                _ => 0,
+            };
+            loop {
+                let sample = self.beat_sample(beat);
+                if sample > position {
+                    break;
+                }
+                if is_new(sample) {
+                    events.push((
+                        sample,
+                        AudioEvents::BeatReached(self.sync_point(sample), beat),
+                    ));
+                }
+                beat += 1;
+            }
+        }
+
+        for marker in self.markers.iter().filter(|marker| is_new(marker.sample)) {
+            events.push((
+                marker.sample,
+                AudioEvents::MarkerReached(self.sync_point(marker.sample), marker.name.clone()),
+            ));
+        }
+
+        self.last_position = Some(position);
+        // stable sort keeps beats before markers that land on the same sample
+        events.sort_by_key(|(sample, _)| *sample);
+        events.into_iter().map(|(_, event)| event).collect()
+    }
+}
+
+// Polls all synced tracks once per frame and pushes the crossed beats and
+// markers onto the event queue.
+#[derive(Debug, Default)]
+pub struct MusicSync {
+    tracks: Vec<TrackSync>,
+}
+
+impl MusicSync {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_track(&mut self, track: TrackSync) {
+        self.tracks.push(track);
+    }
+
+    pub fn remove_track(&mut self, name: &str) -> Option<TrackSync> {
+        let index = self.tracks.iter().position(|track| track.name == name)?;
+        Some(self.tracks.remove(index))
+    }
+
+    pub fn get_track_mut(&mut self, name: &str) -> Option<&mut TrackSync> {
+        self.tracks.iter_mut().find(|track| track.name == name)
+    }
+}
+
+impl Subsystem for MusicSync {
+    fn get_name(&self) -> &str {
+        "MusicSync"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        for track in self.tracks.iter_mut() {
+            for event in track.poll() {
+                if let Err(err) = ctx.event_queue.emit(Box::new(event)) {
+                    error!("unable to emit music sync event {:?}", err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beats(events: &[AudioEvents]) -> Vec<(u64, u64)> {
+        events
+            .iter()
+            .filter_map(|event| match event {
+                AudioEvents::BeatReached(point, beat) => Some((*beat, point.sample)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_beats_are_reported_with_exact_samples() {
+        // 120 bpm at 1000 Hz is a beat every 500 samples
+        let clock = PlaybackClock::new(1000);
+        let mut track = TrackSync::new("theme", clock.clone(), 120.0, 100);
+
+        assert!(track.poll().is_empty(), "No beat before the first one");
+
+        clock.advance(1234);
+        assert_eq!(beats(&track.poll()), vec![(0, 100), (1, 600), (2, 1100)]);
+
+        clock.advance(1);
+        assert!(track.poll().is_empty(), "Beats should not repeat");
+
+        clock.advance(400);
+        assert_eq!(beats(&track.poll()), vec![(3, 1600)]);
+    }
+
+    #[test]
+    fn test_markers_in_playback_order() {
+        let clock = PlaybackClock::new(1000);
+        let mut track = TrackSync::new("theme", clock.clone(), 0.0, 0);
+        track.add_marker("drop", 900);
+        track.add_marker("intro", 10);
+
+        clock.advance(1000);
+        let events = track.poll();
+        let names: Vec<&str> = events
+            .iter()
+            .map(|event| match event {
+                AudioEvents::MarkerReached(_, name) => name.as_str(),
+                _ => panic!("unexpected event"),
+            })
+            .collect();
+
+        assert_eq!(names, vec!["intro", "drop"]);
+    }
+
+    #[test]
+    fn test_seek_backwards_does_not_emit() {
+        let clock = PlaybackClock::new(1000);
+        let mut track = TrackSync::new("theme", clock.clone(), 120.0, 0);
+
+        clock.seek(1200);
+        assert_eq!(track.poll().len(), 3);
+
+        clock.seek(100);
+        assert!(track.poll().is_empty());
+
+        clock.seek(600);
+        assert_eq!(beats(&track.poll()), vec![(1, 500)]);
+    }
+}
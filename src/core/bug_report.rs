@@ -0,0 +1,450 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt::Debug,
+    fs::{self, File},
+    io::{Seek, Write},
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use log::info;
+use serde::Serialize;
+use thiserror::Error;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::{event_system::event::Event, math::vector::Vec3};
+
+use super::logger::get_recent_logs;
+
+// Bind this action (F8 by default) to capture a report
+pub const BUG_REPORT_ACTION: &str = "CaptureBugReport";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum BugReportErrors {
+    #[error("unable to write bug report: {0}")]
+    Io(String),
+
+    #[error("unable to zip bug report: {0}")]
+    Zip(String),
+
+    #[error("invalid screenshot: {0}")]
+    Screenshot(String),
+}
+
+impl From<std::io::Error> for BugReportErrors {
+    fn from(err: std::io::Error) -> Self {
+        BugReportErrors::Io(err.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for BugReportErrors {
+    fn from(err: zip::result::ZipError) -> Self {
+        BugReportErrors::Zip(err.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JournalEntry {
+    pub frame: u64,
+    // unscaled seconds since the engine started
+    pub time: f64,
+    pub name: String,
+    // the event's debug output
+    pub details: String,
+}
+
+// The latest dispatched events, so a report shows what led up to the bug
+#[derive(Debug, Clone)]
+pub struct EventJournal {
+    entries: VecDeque<JournalEntry>,
+    capacity: usize,
+}
+
+impl Default for EventJournal {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl EventJournal {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, frame: u64, time: Duration, event: &dyn Event) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(JournalEntry {
+            frame,
+            time: time.as_secs_f64(),
+            name: event.get_name(),
+            details: format!("{:?}", event),
+        });
+    }
+
+    // The last `count` entries, oldest first
+    pub fn get_tail(&self, count: usize) -> Vec<JournalEntry> {
+        let skip = self.entries.len().saturating_sub(count);
+        self.entries.iter().skip(skip).cloned().collect()
+    }
+
+    // What was dispatched during the frames, oldest first
+    pub fn get_frames(&self, frames: RangeInclusive<u64>) -> Vec<JournalEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| frames.contains(&entry.frame))
+            .cloned()
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+// 8 bit rgba pixels, top row first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Screenshot {
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+impl Screenshot {
+    pub fn new(width: u32, height: u32, rgba: Vec<u8>) -> Result<Self, BugReportErrors> {
+        if rgba.len() != width as usize * height as usize * 4 {
+            return Err(BugReportErrors::Screenshot(format!(
+                "{} bytes for {}x{} pixels",
+                rgba.len(),
+                width,
+                height
+            )));
+        }
+        Ok(Self {
+            width,
+            height,
+            rgba,
+        })
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn encode_png(&self) -> Result<Vec<u8>, BugReportErrors> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.rgba))
+            .map_err(|err| BugReportErrors::Screenshot(err.to_string()))?;
+        Ok(bytes)
+    }
+}
+
+// manifest.json, the summary a bug tracker or triage script reads first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BugReportManifest {
+    pub title: String,
+    pub description: String,
+    pub created_at: String,
+    pub engine_version: String,
+    pub scene: Option<String>,
+    pub camera_position: Option<Vec3>,
+    pub frame: u64,
+    pub elapsed_seconds: f64,
+    pub stats: BTreeMap<String, String>,
+    // the other files in the zip
+    pub files: Vec<String>,
+}
+
+// Everything a playtester's report carries, written as one zip:
+// manifest.json, screenshot.png, events.json and log.txt
+#[derive(Debug, Clone)]
+pub struct BugReport {
+    manifest: BugReportManifest,
+    screenshot: Option<Screenshot>,
+    events: Vec<JournalEntry>,
+    logs: Vec<String>,
+}
+
+impl BugReport {
+    pub fn new(title: &str) -> Self {
+        Self {
+            manifest: BugReportManifest {
+                title: title.to_string(),
+                description: String::new(),
+                created_at: chrono::Local::now().to_rfc3339(),
+                engine_version: env!("CARGO_PKG_VERSION").to_string(),
+                scene: None,
+                camera_position: None,
+                frame: 0,
+                elapsed_seconds: 0.0,
+                stats: BTreeMap::new(),
+                files: Vec::new(),
+            },
+            screenshot: None,
+            events: Vec::new(),
+            logs: Vec::new(),
+        }
+    }
+
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.manifest.description = description.to_string();
+        self
+    }
+
+    pub fn set_scene(&mut self, scene: Option<&str>) {
+        self.manifest.scene = scene.map(|scene| scene.to_string());
+    }
+
+    pub fn set_camera_position(&mut self, position: Option<Vec3>) {
+        self.manifest.camera_position = position;
+    }
+
+    pub fn set_time(&mut self, frame: u64, elapsed: Duration) {
+        self.manifest.frame = frame;
+        self.manifest.elapsed_seconds = elapsed.as_secs_f64();
+    }
+
+    pub fn add_stat(&mut self, name: &str, value: impl ToString) {
+        self.manifest
+            .stats
+            .insert(name.to_string(), value.to_string());
+    }
+
+    pub fn set_screenshot(&mut self, screenshot: Option<Screenshot>) {
+        self.screenshot = screenshot;
+    }
+
+    pub fn set_events(&mut self, events: Vec<JournalEntry>) {
+        self.events = events;
+    }
+
+    pub fn set_logs(&mut self, logs: Vec<String>) {
+        self.logs = logs;
+    }
+
+    pub fn get_manifest(&self) -> &BugReportManifest {
+        &self.manifest
+    }
+
+    pub fn write_zip<W: Write + Seek>(&self, writer: W) -> Result<W, BugReportErrors> {
+        let mut files: Vec<(&str, Vec<u8>)> = Vec::new();
+        if let Some(screenshot) = self.screenshot.as_ref() {
+            files.push(("screenshot.png", screenshot.encode_png()?));
+        }
+        let events = serde_json::to_vec_pretty(&self.events)
+            .map_err(|err| BugReportErrors::Io(err.to_string()))?;
+        files.push(("events.json", events));
+        files.push(("log.txt", self.logs.join("\n").into_bytes()));
+
+        let mut manifest = self.manifest.clone();
+        manifest.files = files.iter().map(|(name, _)| name.to_string()).collect();
+        let manifest = serde_json::to_vec_pretty(&manifest)
+            .map_err(|err| BugReportErrors::Io(err.to_string()))?;
+
+        let mut zip = ZipWriter::new(writer);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        zip.start_file("manifest.json", options)?;
+        zip.write_all(&manifest)?;
+        for (name, bytes) in files {
+            // pngs are already compressed
+            let options = match name.ends_with(".png") {
+                true => options.compression_method(CompressionMethod::Stored),
+                false => options,
+            };
+            zip.start_file(name, options)?;
+            zip.write_all(&bytes)?;
+        }
+        Ok(zip.finish()?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), BugReportErrors> {
+        self.write_zip(File::create(path)?)?;
+        Ok(())
+    }
+}
+
+pub type ScreenshotProvider = Box<dyn Fn() -> Option<Screenshot> + Send + Sync>;
+
+// Keeps the event journal and the context a report needs, the engine asks
+// it to capture when `BUG_REPORT_ACTION` starts. The game keeps the scene
+// and camera up to date, the renderer provides the screenshot.
+pub struct BugReporter {
+    journal: EventJournal,
+    output_dir: PathBuf,
+    scene: Option<String>,
+    camera_position: Option<Vec3>,
+    screenshot: Option<ScreenshotProvider>,
+    // how many journal entries and log lines go into a report
+    tail: usize,
+}
+
+impl Debug for BugReporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BugReporter")
+            .field("journal", &self.journal.len())
+            .field("output_dir", &self.output_dir)
+            .field("scene", &self.scene)
+            .field("camera_position", &self.camera_position)
+            .field("tail", &self.tail)
+            .finish()
+    }
+}
+
+impl Default for BugReporter {
+    fn default() -> Self {
+        Self::new("bug_reports")
+    }
+}
+
+impl BugReporter {
+    pub fn new(output_dir: impl AsRef<Path>) -> Self {
+        Self {
+            journal: EventJournal::default(),
+            output_dir: output_dir.as_ref().to_path_buf(),
+            scene: None,
+            camera_position: None,
+            screenshot: None,
+            tail: 200,
+        }
+    }
+
+    pub fn set_output_dir(&mut self, output_dir: impl AsRef<Path>) {
+        self.output_dir = output_dir.as_ref().to_path_buf();
+    }
+
+    pub fn get_output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    pub fn set_tail(&mut self, tail: usize) {
+        self.tail = tail;
+    }
+
+    pub fn set_scene(&mut self, scene: Option<&str>) {
+        self.scene = scene.map(|scene| scene.to_string());
+    }
+
+    pub fn set_camera_position(&mut self, position: Option<Vec3>) {
+        self.camera_position = position;
+    }
+
+    pub fn set_screenshot_provider(
+        &mut self,
+        provider: impl Fn() -> Option<Screenshot> + Send + Sync + 'static,
+    ) {
+        self.screenshot = Some(Box::new(provider));
+    }
+
+    pub fn get_journal(&self) -> &EventJournal {
+        &self.journal
+    }
+
+    pub fn get_journal_mut(&mut self) -> &mut EventJournal {
+        &mut self.journal
+    }
+
+    // A report with everything known right now, the engine adds its stats
+    pub fn build(&self, title: &str) -> BugReport {
+        let mut report = BugReport::new(title);
+        report.set_scene(self.scene.as_deref());
+        report.set_camera_position(self.camera_position);
+        report.set_screenshot(self.screenshot.as_ref().and_then(|provider| provider()));
+        report.set_events(self.journal.get_tail(self.tail));
+        let logs = get_recent_logs();
+        let skip = logs.len().saturating_sub(self.tail);
+        report.set_logs(logs.into_iter().skip(skip).collect());
+        report
+    }
+
+    // Writes the report as `<output_dir>/bug-<timestamp>.zip`
+    pub fn save(&self, report: &BugReport) -> Result<PathBuf, BugReportErrors> {
+        fs::create_dir_all(&self.output_dir)?;
+        let name = format!(
+            "bug-{}.zip",
+            chrono::Local::now().format("%Y%m%d-%H%M%S%.3f")
+        );
+        let path = self.output_dir.join(name);
+        report.save(&path)?;
+        info!("bug report written to {}", path.display());
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read};
+
+    use super::*;
+    use crate::event_system::engine_events::input_events::InputEvent;
+
+    #[test]
+    fn test_journal_keeps_the_tail() {
+        let mut journal = EventJournal::new(2);
+        for action in ["a", "b", "c"] {
+            journal.record(
+                1,
+                Duration::ZERO,
+                &InputEvent::ActionStarted(action.to_string()),
+            );
+        }
+        let tail = journal.get_tail(5);
+        assert_eq!(tail.len(), 2);
+        assert_eq!(tail[0].details, "ActionStarted(\"b\")");
+        assert_eq!(journal.get_tail(1)[0].name, "ActionStarted");
+    }
+
+    #[test]
+    fn test_report_zip() {
+        let mut report = BugReport::new("falls through floor").with_description("near the bridge");
+        report.set_scene(Some("level_2"));
+        report.set_camera_position(Some(Vec3::new(1.0, 2.0, 3.0)));
+        report.add_stat("fps", 60);
+        report.set_screenshot(Some(Screenshot::new(1, 1, vec![255, 0, 0, 255]).unwrap()));
+        report.set_logs(vec!["first".to_string(), "second".to_string()]);
+
+        let bytes = report
+            .write_zip(Cursor::new(Vec::new()))
+            .unwrap()
+            .into_inner();
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+        let mut names: Vec<&str> = archive.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec!["events.json", "log.txt", "manifest.json", "screenshot.png"]
+        );
+
+        let mut manifest = String::new();
+        archive
+            .by_name("manifest.json")
+            .unwrap()
+            .read_to_string(&mut manifest)
+            .unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&manifest).unwrap();
+        assert_eq!(manifest["scene"], "level_2");
+        assert_eq!(manifest["camera_position"]["y"], 2.0);
+        assert_eq!(manifest["stats"]["fps"], "60");
+        assert_eq!(manifest["files"].as_array().unwrap().len(), 3);
+    }
+}
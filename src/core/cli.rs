@@ -0,0 +1,154 @@
+use std::{env, path::PathBuf};
+
+use thiserror::Error;
+
+use super::config::ConfigLoader;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CliErrors {
+    #[error("missing value for {0}")]
+    MissingValue(String),
+
+    #[error("invalid value {1} for {0}")]
+    InvalidValue(String, String),
+}
+
+// Engine flags understood on the command line, everything else is handed to
+// the game untouched through `unknown`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CliArgs {
+    pub headless: bool,
+    pub log_level: Option<String>,
+    pub fps_cap: Option<u32>,
+    pub window_size: Option<(u32, u32)>,
+    pub replay: Option<PathBuf>,
+    pub unknown: Vec<String>,
+}
+
+impl CliArgs {
+    // Skips the program name
+    pub fn from_env() -> Result<Self, CliErrors> {
+        Self::parse(env::args().skip(1))
+    }
+
+    // Accepts both `--flag value` and `--flag=value`
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, CliErrors> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) if arg.starts_with("--") => {
+                    (flag.to_string(), Some(value.to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| CliErrors::MissingValue(flag.clone()))
+            };
+
+            match flag.as_str() {
+                "--headless" => parsed.headless = true,
+                "--log-level" => parsed.log_level = Some(value()?),
+                "--fps-cap" => {
+                    let fps_cap = value()?;
+                    parsed.fps_cap = Some(
+                        fps_cap
+                            .parse()
+                            .map_err(|_| CliErrors::InvalidValue(flag.clone(), fps_cap))?,
+                    );
+                }
+                "--window-size" => {
+                    let size = value()?;
+                    let invalid = || CliErrors::InvalidValue(flag.clone(), size.clone());
+                    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+                    parsed.window_size = Some((
+                        width.parse().map_err(|_| invalid())?,
+                        height.parse().map_err(|_| invalid())?,
+                    ));
+                }
+                "--replay" => parsed.replay = Some(PathBuf::from(value()?)),
+                _ => parsed.unknown.push(arg),
+            }
+        }
+        Ok(parsed)
+    }
+
+    // Adds the flags as the command line layer of the config
+    pub fn apply_to(&self, mut loader: ConfigLoader) -> ConfigLoader {
+        if self.headless {
+            loader = loader.with_override("headless", "true");
+        }
+        if let Some(log_level) = self.log_level.as_ref() {
+            loader = loader.with_override("log_level", log_level);
+        }
+        if let Some(fps_cap) = self.fps_cap {
+            loader = loader.with_override("fps_cap", &fps_cap.to_string());
+        }
+        if let Some((width, height)) = self.window_size {
+            loader = loader
+                .with_override("window.width", &width.to_string())
+                .with_override("window.height", &height.to_string());
+        }
+        loader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_engine_flags() {
+        let parsed = CliArgs::parse(args(&[
+            "--headless",
+            "--log-level=debug",
+            "--fps-cap",
+            "30",
+            "--window-size",
+            "800x600",
+            "--replay",
+            "match.rpl",
+            "--level",
+            "forest",
+        ]))
+        .unwrap();
+
+        assert!(parsed.headless);
+        assert_eq!(parsed.log_level.as_deref(), Some("debug"));
+        assert_eq!(parsed.fps_cap, Some(30));
+        assert_eq!(parsed.window_size, Some((800, 600)));
+        assert_eq!(parsed.replay, Some(PathBuf::from("match.rpl")));
+        assert_eq!(parsed.unknown, args(&["--level", "forest"]));
+
+        let config = parsed
+            .apply_to(ConfigLoader::new().without_env())
+            .load()
+            .unwrap();
+        assert!(config.headless);
+        assert_eq!(config.fps_cap, 30);
+        assert_eq!(config.window.height, 600);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            CliArgs::parse(args(&["--fps-cap"])),
+            Err(CliErrors::MissingValue("--fps-cap".to_string()))
+        );
+        assert_eq!(
+            CliArgs::parse(args(&["--window-size", "big"])),
+            Err(CliErrors::InvalidValue(
+                "--window-size".to_string(),
+                "big".to_string()
+            ))
+        );
+    }
+}
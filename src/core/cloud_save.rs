@@ -0,0 +1,435 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::event_system::{
+    engine_events::save_events::{SaveConflict, SaveEvents, SaveVersion},
+    event_queue::EventQueue,
+};
+
+use super::paths::AppPaths;
+
+// Kept next to the saves, remembers what both sides looked like after the
+// last sync
+const SYNC_STATE_FILE: &str = ".cloud_sync.json";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CloudSaveErrors {
+    #[error("unable to access local saves: {0}")]
+    Io(String),
+
+    #[error("cloud save provider failed: {0}")]
+    Provider(String),
+
+    #[error("there is no save {0}")]
+    NotFound(String),
+
+    #[error("save {0} is not in conflict")]
+    NoConflict(String),
+}
+
+impl From<std::io::Error> for CloudSaveErrors {
+    fn from(err: std::io::Error) -> Self {
+        CloudSaveErrors::Io(err.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveInfo {
+    pub name: String,
+    // unix seconds, as the side holding the save sees it
+    pub modified: u64,
+    pub size: u64,
+}
+
+// A storefront's or platform's cloud storage (Steam Cloud, iCloud, a game
+// server...). Remote timestamps only have to be consistent with each other,
+// they are never compared with local ones.
+pub trait CloudSaveProvider {
+    fn list(&mut self) -> Result<Vec<SaveInfo>, CloudSaveErrors>;
+
+    // Returns the save as the cloud stored it, with its new timestamp
+    fn upload(&mut self, name: &str, data: &[u8]) -> Result<SaveInfo, CloudSaveErrors>;
+
+    fn download(&mut self, name: &str) -> Result<SaveVersion, CloudSaveErrors>;
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+// A cloud in memory, for offline builds and tests. `set_remote` plays
+// another device uploading.
+#[derive(Debug, Default)]
+pub struct MemoryCloud {
+    saves: BTreeMap<String, SaveVersion>,
+    clock: u64,
+}
+
+impl MemoryCloud {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_remote(&mut self, name: &str, data: &[u8]) -> SaveInfo {
+        // every upload gets a newer timestamp, even within a second
+        self.clock = (self.clock + 1).max(unix_now());
+        self.saves.insert(
+            name.to_string(),
+            SaveVersion {
+                data: data.to_vec(),
+                modified: self.clock,
+            },
+        );
+        SaveInfo {
+            name: name.to_string(),
+            modified: self.clock,
+            size: data.len() as u64,
+        }
+    }
+}
+
+impl CloudSaveProvider for MemoryCloud {
+    fn list(&mut self) -> Result<Vec<SaveInfo>, CloudSaveErrors> {
+        Ok(self
+            .saves
+            .iter()
+            .map(|(name, save)| SaveInfo {
+                name: name.clone(),
+                modified: save.modified,
+                size: save.data.len() as u64,
+            })
+            .collect())
+    }
+
+    fn upload(&mut self, name: &str, data: &[u8]) -> Result<SaveInfo, CloudSaveErrors> {
+        Ok(self.set_remote(name, data))
+    }
+
+    fn download(&mut self, name: &str) -> Result<SaveVersion, CloudSaveErrors> {
+        self.saves
+            .get(name)
+            .cloned()
+            .ok_or_else(|| CloudSaveErrors::NotFound(name.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct SyncedSlot {
+    local_hash: String,
+    remote_modified: u64,
+}
+
+fn hash(data: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncReport {
+    pub uploaded: Vec<String>,
+    pub downloaded: Vec<String>,
+    pub conflicts: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    KeepLocal,
+    KeepRemote,
+    Merged(Vec<u8>),
+}
+
+// Save slots are files in a local directory, kept in sync with a cloud
+// provider. A slot that changed on one side since the last sync is copied
+// to the other. When both changed it is left alone and a `SaveConflict`
+// event with both versions is emitted until the game resolves it.
+pub struct CloudSync {
+    dir: PathBuf,
+    provider: Box<dyn CloudSaveProvider>,
+    synced: BTreeMap<String, SyncedSlot>,
+    conflicts: BTreeMap<String, SaveConflict>,
+}
+
+impl CloudSync {
+    pub fn new(dir: impl AsRef<Path>, provider: impl CloudSaveProvider + 'static) -> Self {
+        let dir = dir.as_ref().to_path_buf();
+        // a missing or broken state only means the next sync looks at
+        // every slot like it never synced
+        let synced = fs::read_to_string(dir.join(SYNC_STATE_FILE))
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        Self {
+            dir,
+            provider: Box::new(provider),
+            synced,
+            conflicts: BTreeMap::new(),
+        }
+    }
+
+    // Saves in the platform's save directory
+    pub fn from_paths(paths: &AppPaths, provider: impl CloudSaveProvider + 'static) -> Self {
+        Self::new(paths.get_saves_dir(), provider)
+    }
+
+    pub fn get_dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn save(&self, slot: &str, data: &[u8]) -> Result<(), CloudSaveErrors> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.dir.join(slot), data)?;
+        Ok(())
+    }
+
+    pub fn load(&self, slot: &str) -> Result<Vec<u8>, CloudSaveErrors> {
+        fs::read(self.dir.join(slot)).map_err(|_| CloudSaveErrors::NotFound(slot.to_string()))
+    }
+
+    pub fn list_local(&self) -> Result<Vec<SaveInfo>, CloudSaveErrors> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let mut saves = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let metadata = entry.metadata()?;
+            if name.starts_with('.') || !metadata.is_file() {
+                continue;
+            }
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |since| since.as_secs());
+            saves.push(SaveInfo {
+                name,
+                modified,
+                size: metadata.len(),
+            });
+        }
+        saves.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(saves)
+    }
+
+    pub fn get_conflicts(&self) -> Vec<&SaveConflict> {
+        self.conflicts.values().collect()
+    }
+
+    pub fn sync(&mut self, queue: &EventQueue) -> Result<SyncReport, CloudSaveErrors> {
+        let local: BTreeMap<String, SaveInfo> = self
+            .list_local()?
+            .into_iter()
+            .map(|save| (save.name.clone(), save))
+            .collect();
+        let remote: BTreeMap<String, SaveInfo> = self
+            .provider
+            .list()?
+            .into_iter()
+            .map(|save| (save.name.clone(), save))
+            .collect();
+        let slots: BTreeSet<&String> = local.keys().chain(remote.keys()).collect();
+
+        let mut report = SyncReport::default();
+        let mut events = Vec::new();
+        for slot in slots {
+            let data = match local.contains_key(slot) {
+                true => Some(self.load(slot)?),
+                false => None,
+            };
+            let synced = self.synced.get(slot.as_str());
+            let local_changed = match (&data, synced) {
+                (Some(data), Some(synced)) => hash(data) != synced.local_hash,
+                (data, _) => data.is_some(),
+            };
+            let remote_changed = match (remote.get(slot), synced) {
+                (Some(info), Some(synced)) => info.modified != synced.remote_modified,
+                (info, _) => info.is_some(),
+            };
+
+            match (local_changed, remote_changed) {
+                (false, false) => {}
+                (true, false) => {
+                    if let Some(data) = data.as_ref() {
+                        self.upload(slot, data)?;
+                        report.uploaded.push(slot.clone());
+                        events.push(SaveEvents::SaveUploaded(slot.clone()));
+                    }
+                }
+                (false, true) => {
+                    self.download(slot)?;
+                    report.downloaded.push(slot.clone());
+                    events.push(SaveEvents::SaveDownloaded(slot.clone()));
+                }
+                (true, true) => {
+                    let remote = self.provider.download(slot)?;
+                    let local = SaveVersion {
+                        data: data.unwrap_or_default(),
+                        modified: local.get(slot).map_or(0, |info| info.modified),
+                    };
+                    // the same bytes landed on both sides, e.g. a first sync
+                    // after a reinstall
+                    if local.data == remote.data {
+                        self.mark_synced(slot, &local.data, remote.modified);
+                        continue;
+                    }
+                    let conflict = SaveConflict {
+                        slot: slot.clone(),
+                        local,
+                        remote,
+                    };
+                    info!("save {} changed locally and in the cloud", slot);
+                    report.conflicts.push(slot.clone());
+                    events.push(SaveEvents::SaveConflict(conflict.clone()));
+                    self.conflicts.insert(slot.clone(), conflict);
+                }
+            }
+        }
+        self.store_state()?;
+
+        for event in events {
+            if let Err(err) = queue.emit(Box::new(event)) {
+                error!("unable to emit save event {:?}", err);
+            }
+        }
+        Ok(report)
+    }
+
+    pub fn resolve(&mut self, slot: &str, resolution: Resolution) -> Result<(), CloudSaveErrors> {
+        let conflict = self
+            .conflicts
+            .remove(slot)
+            .ok_or_else(|| CloudSaveErrors::NoConflict(slot.to_string()))?;
+        match resolution {
+            Resolution::KeepLocal => self.upload(slot, &conflict.local.data)?,
+            Resolution::KeepRemote => {
+                self.save(slot, &conflict.remote.data)?;
+                self.mark_synced(slot, &conflict.remote.data, conflict.remote.modified);
+            }
+            Resolution::Merged(data) => {
+                self.save(slot, &data)?;
+                self.upload(slot, &data)?;
+            }
+        }
+        self.store_state()
+    }
+
+    fn upload(&mut self, slot: &str, data: &[u8]) -> Result<(), CloudSaveErrors> {
+        let uploaded = self.provider.upload(slot, data)?;
+        self.mark_synced(slot, data, uploaded.modified);
+        Ok(())
+    }
+
+    fn download(&mut self, slot: &str) -> Result<(), CloudSaveErrors> {
+        let remote = self.provider.download(slot)?;
+        self.save(slot, &remote.data)?;
+        self.mark_synced(slot, &remote.data, remote.modified);
+        Ok(())
+    }
+
+    fn mark_synced(&mut self, slot: &str, data: &[u8], remote_modified: u64) {
+        self.synced.insert(
+            slot.to_string(),
+            SyncedSlot {
+                local_hash: hash(data),
+                remote_modified,
+            },
+        );
+    }
+
+    fn store_state(&self) -> Result<(), CloudSaveErrors> {
+        fs::create_dir_all(&self.dir)?;
+        let contents = serde_json::to_string_pretty(&self.synced)
+            .map_err(|err| CloudSaveErrors::Io(err.to_string()))?;
+        fs::write(self.dir.join(SYNC_STATE_FILE), contents)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    // lets the test play another device while the sync owns the provider
+    #[derive(Clone, Default)]
+    struct SharedCloud(Arc<Mutex<MemoryCloud>>);
+
+    impl CloudSaveProvider for SharedCloud {
+        fn list(&mut self) -> Result<Vec<SaveInfo>, CloudSaveErrors> {
+            self.0.lock().unwrap().list()
+        }
+
+        fn upload(&mut self, name: &str, data: &[u8]) -> Result<SaveInfo, CloudSaveErrors> {
+            self.0.lock().unwrap().upload(name, data)
+        }
+
+        fn download(&mut self, name: &str) -> Result<SaveVersion, CloudSaveErrors> {
+            self.0.lock().unwrap().download(name)
+        }
+    }
+
+    #[test]
+    fn test_sync_and_conflicts() {
+        let dir = std::env::temp_dir().join(format!("aloy-cloud-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let cloud = SharedCloud::default();
+        let queue = EventQueue::new();
+        let mut sync = CloudSync::new(&dir, cloud.clone());
+
+        sync.save("slot1", b"level 1").unwrap();
+        cloud.0.lock().unwrap().set_remote("slot2", b"level 9");
+        let report = sync.sync(&queue).unwrap();
+        assert_eq!(report.uploaded, vec!["slot1".to_string()]);
+        assert_eq!(report.downloaded, vec!["slot2".to_string()]);
+        assert_eq!(sync.load("slot2").unwrap(), b"level 9");
+        assert_eq!(sync.sync(&queue).unwrap(), SyncReport::default());
+
+        // both sides move on from the synced version
+        sync.save("slot1", b"level 2").unwrap();
+        cloud.0.lock().unwrap().set_remote("slot1", b"level 3");
+        let report = sync.sync(&queue).unwrap();
+        assert_eq!(report.conflicts, vec!["slot1".to_string()]);
+        let events = queue.get_events().unwrap();
+        let conflict = events
+            .iter()
+            .find(|event| event.get_name() == "SaveConflict")
+            .and_then(|event| event.get_data())
+            .unwrap();
+        let conflict = conflict.get_ref::<SaveConflict>().unwrap();
+        assert_eq!(
+            (&conflict.local.data[..], &conflict.remote.data[..]),
+            (&b"level 2"[..], &b"level 3"[..])
+        );
+
+        sync.resolve("slot1", Resolution::KeepRemote).unwrap();
+        assert_eq!(sync.load("slot1").unwrap(), b"level 3");
+        assert_eq!(
+            sync.resolve("slot1", Resolution::KeepLocal),
+            Err(CloudSaveErrors::NoConflict("slot1".to_string()))
+        );
+        // the state survives a restart
+        let mut restarted = CloudSync::new(&dir, cloud);
+        assert_eq!(restarted.sync(&queue).unwrap(), SyncReport::default());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,331 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use log::{info, LevelFilter};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use super::data_file::{load_data_file, DataFileErrors};
+
+pub const ENV_PREFIX: &str = "ALOY_";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ConfigErrors {
+    #[error("unable to load config file: {0}")]
+    File(#[from] DataFileErrors),
+
+    #[error("unknown config key {0}")]
+    UnknownKey(String),
+
+    #[error("invalid value {1} for config key {0}")]
+    InvalidValue(String, String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    pub title: String,
+    pub fullscreen: bool,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            title: "Aloy".to_string(),
+            fullscreen: false,
+        }
+    }
+}
+
+// Every field has a default so config files only need what they change
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EngineConfig {
+    pub log_level: String,
+    pub vsync: bool,
+    // 0 means uncapped
+    pub fps_cap: u32,
+    pub fixed_hz: u32,
+    pub headless: bool,
+    pub window: WindowConfig,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        Self {
+            log_level: "info".to_string(),
+            vsync: true,
+            fps_cap: 0,
+            fixed_hz: 60,
+            headless: false,
+            window: WindowConfig::default(),
+        }
+    }
+}
+
+fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<T, ConfigErrors> {
+    value
+        .parse()
+        .map_err(|_| ConfigErrors::InvalidValue(key.to_string(), value.to_string()))
+}
+
+impl EngineConfig {
+    pub const KEYS: [&'static str; 9] = [
+        "log_level",
+        "vsync",
+        "fps_cap",
+        "fixed_hz",
+        "headless",
+        "window.width",
+        "window.height",
+        "window.title",
+        "window.fullscreen",
+    ];
+
+    // Sets a single dotted key from its string form, used by the env var and
+    // command line layers
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigErrors> {
+        match key {
+            "log_level" => {
+                parse::<LevelFilter>(key, value)?;
+                self.log_level = value.to_string();
+            }
+            "vsync" => self.vsync = parse(key, value)?,
+            "fps_cap" => self.fps_cap = parse(key, value)?,
+            "fixed_hz" => self.fixed_hz = parse(key, value)?,
+            "headless" => self.headless = parse(key, value)?,
+            "window.width" => self.window.width = parse(key, value)?,
+            "window.height" => self.window.height = parse(key, value)?,
+            "window.title" => self.window.title = value.to_string(),
+            "window.fullscreen" => self.window.fullscreen = parse(key, value)?,
+            _ => return Err(ConfigErrors::UnknownKey(key.to_string())),
+        }
+        Ok(())
+    }
+
+    // Unknown levels fall back to info, `set` already rejects them
+    pub fn get_log_level(&self) -> LevelFilter {
+        self.log_level.parse().unwrap_or(LevelFilter::Info)
+    }
+
+    pub fn get_fixed_delta(&self) -> Duration {
+        Duration::from_secs(1) / self.fixed_hz.max(1)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigErrors> {
+        Ok(load_data_file(path)?)
+    }
+}
+
+// Builds the config from its layers, each one overriding the previous:
+// engine defaults -> config file -> ALOY_* env vars -> command line
+#[derive(Debug, Clone)]
+pub struct ConfigLoader {
+    path: Option<PathBuf>,
+    overrides: Vec<(String, String)>,
+    use_env: bool,
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        Self {
+            path: None,
+            overrides: Vec::new(),
+            use_env: true,
+        }
+    }
+
+    pub fn with_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn without_env(mut self) -> Self {
+        self.use_env = false;
+        self
+    }
+
+    // Command line values, applied last
+    pub fn with_override(mut self, key: &str, value: &str) -> Self {
+        self.overrides.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn get_path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    // A missing file is not an error, the defaults are used instead
+    pub fn load(&self) -> Result<EngineConfig, ConfigErrors> {
+        let mut config = match self.path.as_ref() {
+            Some(path) if path.exists() => EngineConfig::from_file(path)?,
+            _ => EngineConfig::default(),
+        };
+
+        if self.use_env {
+            for key in EngineConfig::KEYS {
+                let var = format!("{}{}", ENV_PREFIX, key.replace('.', "_").to_uppercase());
+                if let Ok(value) = env::var(&var) {
+                    config.set(key, &value)?;
+                }
+            }
+        }
+
+        for (key, value) in self.overrides.iter() {
+            config.set(key, value)?;
+        }
+        Ok(config)
+    }
+}
+
+impl Default for ConfigLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Polls the config file's modification time and reloads it when it changed
+#[derive(Debug)]
+pub struct ConfigWatcher {
+    loader: ConfigLoader,
+    poll_interval: Duration,
+    since_poll: Duration,
+    last_modified: Option<SystemTime>,
+}
+
+impl ConfigWatcher {
+    pub fn new(loader: ConfigLoader) -> Self {
+        let last_modified = Self::modified(&loader);
+        Self {
+            loader,
+            poll_interval: Duration::from_millis(500),
+            since_poll: Duration::ZERO,
+            last_modified,
+        }
+    }
+
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+
+    pub fn get_loader(&self) -> &ConfigLoader {
+        &self.loader
+    }
+
+    fn modified(loader: &ConfigLoader) -> Option<SystemTime> {
+        fs::metadata(loader.get_path()?).ok()?.modified().ok()
+    }
+
+    // Returns the reloaded config if the file changed since the last poll
+    pub fn poll(&mut self, delta: Duration) -> Option<Result<EngineConfig, ConfigErrors>> {
+        self.since_poll += delta;
+        if self.since_poll < self.poll_interval {
+            return None;
+        }
+        self.since_poll = Duration::ZERO;
+
+        let modified = Self::modified(&self.loader);
+        if modified == self.last_modified {
+            return None;
+        }
+        self.last_modified = modified;
+        info!("config file changed, reloading");
+        Some(self.loader.load())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("aloy-config-test-{}-{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_layers_override_in_order() {
+        let path = temp_path("layers.toml");
+        fs::write(
+            &path,
+            "vsync = false\nfps_cap = 144\n[window]\nwidth = 800\n",
+        )
+        .unwrap();
+
+        let config = ConfigLoader::new()
+            .without_env()
+            .with_file(&path)
+            .with_override("fps_cap", "30")
+            .load()
+            .unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert!(!config.vsync);
+        assert_eq!(config.fps_cap, 30);
+        assert_eq!(config.window.width, 800);
+        assert_eq!(config.window.height, 720, "Unset keys keep the default");
+    }
+
+    #[test]
+    fn test_ron_file() {
+        let path = temp_path("config.ron");
+        fs::write(&path, "(log_level: \"debug\", headless: true)").unwrap();
+        let config = EngineConfig::from_file(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        assert_eq!(config.get_log_level(), LevelFilter::Debug);
+        assert!(config.headless);
+    }
+
+    #[test]
+    fn test_set_rejects_bad_values() {
+        let mut config = EngineConfig::default();
+        assert_eq!(
+            config.set("fps_cap", "fast"),
+            Err(ConfigErrors::InvalidValue(
+                "fps_cap".to_string(),
+                "fast".to_string()
+            ))
+        );
+        assert_eq!(
+            config.set("log_level", "loud"),
+            Err(ConfigErrors::InvalidValue(
+                "log_level".to_string(),
+                "loud".to_string()
+            ))
+        );
+        assert!(matches!(
+            config.set("nope", "1"),
+            Err(ConfigErrors::UnknownKey(_))
+        ));
+    }
+
+    #[test]
+    fn test_watcher_reloads_changed_file() {
+        let path = temp_path("watched.toml");
+        fs::write(&path, "fps_cap = 60\n").unwrap();
+        let mut watcher = ConfigWatcher::new(ConfigLoader::new().without_env().with_file(&path));
+        watcher.set_poll_interval(Duration::ZERO);
+        assert!(watcher.poll(Duration::ZERO).is_none());
+
+        // make sure the modification time actually moves
+        let later = SystemTime::now() + Duration::from_secs(5);
+        fs::write(&path, "fps_cap = 120\n").unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        let reloaded = watcher.poll(Duration::ZERO).unwrap().unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(reloaded.fps_cap, 120);
+    }
+}
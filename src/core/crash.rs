@@ -0,0 +1,134 @@
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    cell::RefCell,
+    panic::{self, PanicHookInfo},
+    sync::Once,
+};
+
+use log::error;
+
+// Exit code used when the application goes down because of a panic, same as
+// the one rust uses for a panicking main
+pub const CRASH_EXIT_CODE: i32 = 101;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrashInfo {
+    pub message: String,
+    pub location: Option<String>,
+    pub backtrace: String,
+}
+
+impl CrashInfo {
+    fn from_hook(info: &PanicHookInfo) -> Self {
+        Self {
+            message: payload_message(info.payload()),
+            location: info
+                .location()
+                .map(|location| format!("{}:{}", location.file(), location.line())),
+            backtrace: Backtrace::force_capture().to_string(),
+        }
+    }
+
+    // Used when the hook did not run, e.g. someone replaced it
+    pub fn from_payload(payload: &(dyn Any + Send)) -> Self {
+        Self {
+            message: payload_message(payload),
+            location: None,
+            backtrace: String::new(),
+        }
+    }
+}
+
+thread_local! {
+    // the hook runs on the panicking thread, the one that catches the unwind
+    static LAST_CRASH: RefCell<Option<CrashInfo>> = const { RefCell::new(None) };
+}
+
+pub fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+// Logs every panic with a backtrace through the engine logger and keeps the
+// crash around so the application can report it once it caught the unwind.
+// The hook that was installed before, rust's default output or one of a
+// test harness, still runs after it. Installed once per process.
+pub fn install_panic_hook() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            record_crash(info);
+            previous(info);
+        }));
+    });
+}
+
+fn record_crash(info: &PanicHookInfo) {
+    let crash = CrashInfo::from_hook(info);
+    error!(
+        "panic at {}: {}\n{}",
+        crash.location.as_deref().unwrap_or("<unknown>"),
+        crash.message,
+        crash.backtrace
+    );
+    log::logger().flush();
+
+    LAST_CRASH.with(|last_crash| last_crash.replace(Some(crash)));
+}
+
+pub fn take_last_crash() -> Option<CrashInfo> {
+    LAST_CRASH.with(|last_crash| last_crash.take())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn test_payload_message() {
+        let static_str: Box<dyn Any + Send> = Box::new("static message");
+        let owned: Box<dyn Any + Send> = Box::new("owned message".to_string());
+        let other: Box<dyn Any + Send> = Box::new(42);
+
+        assert_eq!(payload_message(static_str.as_ref()), "static message");
+        assert_eq!(payload_message(owned.as_ref()), "owned message");
+        assert_eq!(payload_message(other.as_ref()), "unknown panic payload");
+    }
+
+    #[test]
+    fn test_previous_hook_still_runs() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        {
+            let seen = Arc::clone(&seen);
+            let default = panic::take_hook();
+            panic::set_hook(Box::new(move |info| {
+                seen.lock().unwrap().push(payload_message(info.payload()));
+                default(info);
+            }));
+        }
+        install_panic_hook();
+
+        let result = std::thread::spawn(|| {
+            let result = panic::catch_unwind(|| panic!("chained hook"));
+            (result.is_err(), take_last_crash())
+        })
+        .join()
+        .unwrap();
+        assert!(result.0);
+        assert_eq!(result.1.unwrap().message, "chained hook");
+        assert!(seen
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|message| message == "chained hook"));
+    }
+}
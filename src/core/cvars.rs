@@ -0,0 +1,497 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    mem,
+    ops::BitOr,
+    path::Path,
+};
+
+use log::{info, warn};
+use thiserror::Error;
+
+use super::settings::{SettingValue, Settings, SettingsErrors};
+
+// Cvars live in the settings as `cvar.<name>`
+pub const CVAR_SETTINGS_PREFIX: &str = "cvar.";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CVarErrors {
+    #[error("unknown cvar {0}")]
+    Unknown(String),
+
+    #[error("cvar {0} is already registered")]
+    AlreadyRegistered(String),
+
+    #[error("invalid value {1} for cvar {0}")]
+    InvalidValue(String, String),
+
+    #[error("cvar {0} is a cheat, enable cheats to change it")]
+    Cheat(String),
+
+    #[error("{0}")]
+    Settings(#[from] SettingsErrors),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CVarFlags(u8);
+
+impl CVarFlags {
+    pub const NONE: Self = Self(0);
+    // only changeable from the console or config while cheats are enabled
+    pub const CHEAT: Self = Self(1);
+    // saved with the user settings
+    pub const ARCHIVE: Self = Self(1 << 1);
+    // the server's value is sent to every client
+    pub const REPLICATED: Self = Self(1 << 2);
+
+    pub fn contains(self, flags: Self) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+}
+
+impl BitOr for CVarFlags {
+    type Output = Self;
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+type ChangeCallback = Box<dyn FnMut(&SettingValue) + Send>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CVar {
+    pub name: String,
+    pub description: String,
+    pub flags: CVarFlags,
+    pub default: SettingValue,
+    pub value: SettingValue,
+}
+
+// Parses `text` as the same kind of value as `like`
+fn parse_like(like: &SettingValue, text: &str) -> Option<SettingValue> {
+    let text = text.trim();
+    match like {
+        SettingValue::Bool(_) => match text {
+            "true" | "1" | "on" => Some(SettingValue::Bool(true)),
+            "false" | "0" | "off" => Some(SettingValue::Bool(false)),
+            _ => None,
+        },
+        SettingValue::Int(_) => text.parse().ok().map(SettingValue::Int),
+        SettingValue::Float(_) => text.parse().ok().map(SettingValue::Float),
+        SettingValue::Text(_) => Some(SettingValue::Text(text.trim_matches('"').to_string())),
+    }
+}
+
+// Ints are accepted for float cvars, anything else has to match
+fn coerce(like: &SettingValue, value: SettingValue) -> Option<SettingValue> {
+    match (like, value) {
+        (SettingValue::Float(_), SettingValue::Int(value)) => {
+            Some(SettingValue::Float(value as f64))
+        }
+        (like, value) if mem::discriminant(like) == mem::discriminant(&value) => Some(value),
+        _ => None,
+    }
+}
+
+fn format_value(value: &SettingValue) -> String {
+    match value {
+        SettingValue::Bool(value) => value.to_string(),
+        SettingValue::Int(value) => value.to_string(),
+        SettingValue::Float(value) => value.to_string(),
+        SettingValue::Text(value) => format!("\"{}\"", value),
+    }
+}
+
+// Console variables: named, typed tweakables registered by engine and game
+// code. They can be set from code, the settings (`cvar.<name>` keys), the
+// command line (`+name value`) and the console. Values that arrive before
+// their cvar is registered are kept and applied on registration, so the
+// command line works for cvars the game registers late.
+#[derive(Default)]
+pub struct CVars {
+    cvars: BTreeMap<String, CVar>,
+    callbacks: HashMap<String, Vec<ChangeCallback>>,
+    pending: HashMap<String, String>,
+    cheats_enabled: bool,
+    // replicated cvars changed since the last `take_replicated_changes`
+    replicated_changes: Vec<String>,
+}
+
+impl CVars {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(
+        &mut self,
+        name: &str,
+        default: SettingValue,
+        flags: CVarFlags,
+        description: &str,
+    ) -> Result<(), CVarErrors> {
+        if self.cvars.contains_key(name) {
+            return Err(CVarErrors::AlreadyRegistered(name.to_string()));
+        }
+        let mut cvar = CVar {
+            name: name.to_string(),
+            description: description.to_string(),
+            flags,
+            value: default.clone(),
+            default,
+        };
+        if let Some(text) = self.pending.remove(name) {
+            match parse_like(&cvar.default, &text) {
+                Some(value) => cvar.value = value,
+                None => warn!("ignoring invalid value {} for cvar {}", text, name),
+            }
+        }
+        self.cvars.insert(name.to_string(), cvar);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SettingValue> {
+        self.cvars.get(name).map(|cvar| &cvar.value)
+    }
+
+    pub fn get_cvar(&self, name: &str) -> Option<&CVar> {
+        self.cvars.get(name)
+    }
+
+    pub fn get_bool(&self, name: &str) -> Option<bool> {
+        match self.get(name)? {
+            SettingValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_int(&self, name: &str) -> Option<i64> {
+        match self.get(name)? {
+            SettingValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_float(&self, name: &str) -> Option<f64> {
+        match self.get(name)? {
+            SettingValue::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_text(&self, name: &str) -> Option<&str> {
+        match self.get(name)? {
+            SettingValue::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    // Sorted by name
+    pub fn iter(&self) -> impl Iterator<Item = &CVar> {
+        self.cvars.values()
+    }
+
+    // Called with the new value every time the cvar changes
+    pub fn on_change(&mut self, name: &str, callback: impl FnMut(&SettingValue) + Send + 'static) {
+        self.callbacks
+            .entry(name.to_string())
+            .or_default()
+            .push(Box::new(callback));
+    }
+
+    pub fn is_cheats_enabled(&self) -> bool {
+        self.cheats_enabled
+    }
+
+    // Disabling cheats puts every cheat cvar back to its default
+    pub fn set_cheats_enabled(&mut self, cheats_enabled: bool) {
+        self.cheats_enabled = cheats_enabled;
+        if cheats_enabled {
+            return;
+        }
+        let cheats: Vec<(String, SettingValue)> = self
+            .cvars
+            .values()
+            .filter(|cvar| cvar.flags.contains(CVarFlags::CHEAT) && cvar.value != cvar.default)
+            .map(|cvar| (cvar.name.clone(), cvar.default.clone()))
+            .collect();
+        for (name, default) in cheats {
+            let _ = self.assign(&name, default, false);
+        }
+    }
+
+    fn assign(&mut self, name: &str, value: SettingValue, checked: bool) -> Result<(), CVarErrors> {
+        let cvar = self
+            .cvars
+            .get_mut(name)
+            .ok_or_else(|| CVarErrors::Unknown(name.to_string()))?;
+        if checked && cvar.flags.contains(CVarFlags::CHEAT) && !self.cheats_enabled {
+            return Err(CVarErrors::Cheat(name.to_string()));
+        }
+        let value = coerce(&cvar.default, value.clone())
+            .ok_or_else(|| CVarErrors::InvalidValue(name.to_string(), format_value(&value)))?;
+        if cvar.value == value {
+            return Ok(());
+        }
+        info!("cvar {} = {}", name, format_value(&value));
+        cvar.value = value;
+        if cvar.flags.contains(CVarFlags::REPLICATED)
+            && !self
+                .replicated_changes
+                .iter()
+                .any(|changed| changed == name)
+        {
+            self.replicated_changes.push(name.to_string());
+        }
+
+        let value = &cvar.value;
+        if let Some(callbacks) = self.callbacks.get_mut(name) {
+            for callback in callbacks.iter_mut() {
+                callback(value);
+            }
+        }
+        Ok(())
+    }
+
+    // From game code, cheat cvars included
+    pub fn set(&mut self, name: &str, value: SettingValue) -> Result<(), CVarErrors> {
+        self.assign(name, value, false)
+    }
+
+    pub fn reset(&mut self, name: &str) -> Result<(), CVarErrors> {
+        let default = self
+            .cvars
+            .get(name)
+            .map(|cvar| cvar.default.clone())
+            .ok_or_else(|| CVarErrors::Unknown(name.to_string()))?;
+        self.assign(name, default, false)
+    }
+
+    // Sets from the string form, parsed as the cvar's type. Cheat cvars need
+    // cheats enabled.
+    pub fn set_from_str(&mut self, name: &str, text: &str) -> Result<(), CVarErrors> {
+        let cvar = self
+            .cvars
+            .get(name)
+            .ok_or_else(|| CVarErrors::Unknown(name.to_string()))?;
+        let value = parse_like(&cvar.default, text)
+            .ok_or_else(|| CVarErrors::InvalidValue(name.to_string(), text.to_string()))?;
+        self.assign(name, value, true)
+    }
+
+    // A console line: `name` prints the cvar, `name value` sets it
+    pub fn execute(&mut self, line: &str) -> Result<String, CVarErrors> {
+        let line = line.trim();
+        let (name, value) = match line.split_once(char::is_whitespace) {
+            Some((name, value)) => (name, Some(value.trim())),
+            None => (line, None),
+        };
+        if let Some(value) = value {
+            self.set_from_str(name, value)?;
+        }
+        let cvar = self
+            .cvars
+            .get(name)
+            .ok_or_else(|| CVarErrors::Unknown(name.to_string()))?;
+        Ok(format!(
+            "{} = {} ({})",
+            cvar.name,
+            format_value(&cvar.value),
+            cvar.description
+        ))
+    }
+
+    fn set_or_defer(&mut self, name: &str, text: &str) {
+        if !self.cvars.contains_key(name) {
+            self.pending.insert(name.to_string(), text.to_string());
+            return;
+        }
+        if let Err(err) = self.set_from_str(name, text) {
+            warn!("{}", err);
+        }
+    }
+
+    // `+name value` pairs from the command line, returns the other arguments
+    pub fn apply_args(&mut self, args: &[String]) -> Vec<String> {
+        let mut rest = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(name) = arg.strip_prefix('+') else {
+                rest.push(arg.clone());
+                continue;
+            };
+            match args.next() {
+                Some(value) => self.set_or_defer(name, value),
+                None => warn!("missing value for cvar {}", name),
+            }
+        }
+        rest
+    }
+
+    // Applies every `cvar.<name>` key of the settings
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        let keys: Vec<String> = settings
+            .keys_with_prefix(CVAR_SETTINGS_PREFIX)
+            .map(|key| key.to_string())
+            .collect();
+        for key in keys {
+            let name = &key[CVAR_SETTINGS_PREFIX.len()..];
+            let text = match settings.get(&key) {
+                Some(SettingValue::Text(text)) => text.clone(),
+                Some(value) => format_value(value),
+                None => continue,
+            };
+            self.set_or_defer(name, &text);
+        }
+    }
+
+    // Writes the archive cvars into the user settings, the ones back at
+    // their default are removed so changed defaults reach the players
+    pub fn store_archived(&self, settings: &mut Settings) {
+        for cvar in self.cvars.values() {
+            if !cvar.flags.contains(CVarFlags::ARCHIVE) {
+                continue;
+            }
+            let key = format!("{}{}", CVAR_SETTINGS_PREFIX, cvar.name);
+            match cvar.value == cvar.default {
+                true => {
+                    settings.remove(&key);
+                }
+                false => {
+                    settings.set(&key, cvar.value.clone());
+                }
+            }
+        }
+    }
+
+    pub fn save_archived(&self, path: impl AsRef<Path>) -> Result<(), CVarErrors> {
+        let mut settings = Settings::new();
+        self.store_archived(&mut settings);
+        Ok(settings.save(path)?)
+    }
+
+    pub fn load_archived(&mut self, path: impl AsRef<Path>) -> Result<(), CVarErrors> {
+        let mut settings = Settings::new();
+        settings.load(path)?;
+        self.apply_settings(&settings);
+        Ok(())
+    }
+
+    // Values of the replicated cvars, for a client joining
+    pub fn get_replicated(&self) -> Vec<(String, SettingValue)> {
+        self.cvars
+            .values()
+            .filter(|cvar| cvar.flags.contains(CVarFlags::REPLICATED))
+            .map(|cvar| (cvar.name.clone(), cvar.value.clone()))
+            .collect()
+    }
+
+    // Replicated cvars changed since the last call, for the server to send
+    pub fn take_replicated_changes(&mut self) -> Vec<(String, SettingValue)> {
+        mem::take(&mut self.replicated_changes)
+            .into_iter()
+            .filter_map(|name| Some((name.clone(), self.get(&name)?.clone())))
+            .collect()
+    }
+
+    // Values received from the server, they skip the cheat check
+    pub fn apply_replicated(&mut self, values: Vec<(String, SettingValue)>) {
+        for (name, value) in values {
+            let replicated = self
+                .cvars
+                .get(&name)
+                .is_some_and(|cvar| cvar.flags.contains(CVarFlags::REPLICATED));
+            if !replicated {
+                warn!("ignoring replicated value for cvar {}", name);
+                continue;
+            }
+            if let Err(err) = self.assign(&name, value, false) {
+                warn!("{}", err);
+            }
+        }
+        self.replicated_changes.clear();
+    }
+}
+
+impl Debug for CVars {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CVars")
+            .field("cvars", &self.cvars)
+            .field("cheats_enabled", &self.cheats_enabled)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[test]
+    fn test_sources_flags_and_persistence() {
+        let mut cvars = CVars::new();
+        let rest = cvars.apply_args(&["+r_fov".to_string(), "75".to_string(), "--map".to_string()]);
+        assert_eq!(rest, vec!["--map".to_string()]);
+
+        cvars
+            .register(
+                "r_fov",
+                SettingValue::Float(90.0),
+                CVarFlags::ARCHIVE,
+                "field of view",
+            )
+            .unwrap();
+        cvars
+            .register(
+                "sv_gravity",
+                SettingValue::Int(800),
+                CVarFlags::CHEAT | CVarFlags::REPLICATED,
+                "world gravity",
+            )
+            .unwrap();
+        assert_eq!(cvars.get_float("r_fov"), Some(75.0));
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        cvars.on_change("sv_gravity", move |value| {
+            sink.lock().unwrap().push(value.clone())
+        });
+
+        assert_eq!(
+            cvars.execute("sv_gravity 100"),
+            Err(CVarErrors::Cheat("sv_gravity".to_string()))
+        );
+        cvars.set_cheats_enabled(true);
+        assert_eq!(
+            cvars.execute("sv_gravity 100").unwrap(),
+            "sv_gravity = 100 (world gravity)"
+        );
+        assert_eq!(
+            cvars.take_replicated_changes(),
+            vec![("sv_gravity".to_string(), SettingValue::Int(100))]
+        );
+        cvars.set_cheats_enabled(false);
+        assert_eq!(cvars.get_int("sv_gravity"), Some(800));
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![SettingValue::Int(100), SettingValue::Int(800)]
+        );
+        assert!(cvars.execute("r_fov wide").is_err());
+
+        let path = std::env::temp_dir().join(format!("aloy-cvars-test-{}.cfg", std::process::id()));
+        cvars.set("r_fov", SettingValue::Int(100)).unwrap();
+        cvars.save_archived(&path).unwrap();
+        let mut loaded = CVars::new();
+        loaded.load_archived(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        loaded
+            .register(
+                "r_fov",
+                SettingValue::Float(90.0),
+                CVarFlags::ARCHIVE,
+                "field of view",
+            )
+            .unwrap();
+        assert_eq!(loaded.get_float("r_fov"), Some(100.0));
+    }
+}
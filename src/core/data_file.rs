@@ -0,0 +1,29 @@
+use std::{fs, path::Path};
+
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum DataFileErrors {
+    #[error("unable to read {0}: {1}")]
+    Io(String, String),
+
+    #[error("unable to parse {0}: {1}")]
+    Parse(String, String),
+}
+
+// Reads a config or content file, `.ron` files as RON and everything else
+// as TOML
+pub fn load_data_file<T: DeserializeOwned>(path: impl AsRef<Path>) -> Result<T, DataFileErrors> {
+    let path = path.as_ref();
+    let name = path.display().to_string();
+    let contents = fs::read_to_string(path)
+        .map_err(|err| DataFileErrors::Io(name.clone(), err.to_string()))?;
+
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("ron") => {
+            ron::from_str(&contents).map_err(|err| DataFileErrors::Parse(name, err.to_string()))
+        }
+        _ => toml::from_str(&contents).map_err(|err| DataFileErrors::Parse(name, err.to_string())),
+    }
+}
@@ -0,0 +1,152 @@
+use log::{error, info};
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::engine_events::world_events::{OriginShift, WorldEvents},
+    math::vector::{DVec3, Vec3},
+};
+
+// Keeps the camera close to (0, 0, 0) in local (f32) coordinates. Absolute
+// positions are kept as f64 so precision only degrades in the local space
+// very far away from the camera, where it is not visible.
+#[derive(Debug)]
+pub struct FloatingOrigin {
+    enabled: bool,
+    threshold: f32,
+    origin: DVec3,
+    camera_position: Vec3,
+    pending: Option<OriginShift>,
+}
+
+impl FloatingOrigin {
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            enabled: true,
+            threshold,
+            origin: DVec3::ZERO,
+            camera_position: Vec3::ZERO,
+            pending: None,
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn get_origin(&self) -> DVec3 {
+        self.origin
+    }
+
+    // Local position of the camera, updated by the game every frame
+    pub fn set_camera_position(&mut self, position: Vec3) {
+        self.camera_position = position;
+    }
+
+    pub fn to_world(&self, local: Vec3) -> DVec3 {
+        self.origin + DVec3::from(local)
+    }
+
+    pub fn to_local(&self, world: DVec3) -> Vec3 {
+        Vec3::from(world - self.origin)
+    }
+
+    // Moves the origin to the camera if it went past the threshold and
+    // returns the shift every system has to apply to its local positions
+    pub fn update(&mut self) -> Option<OriginShift> {
+        if !self.enabled || self.camera_position.length() < self.threshold {
+            return None;
+        }
+
+        let offset = self.camera_position;
+        self.origin += DVec3::from(offset);
+        self.camera_position = Vec3::ZERO;
+        info!("rebasing world origin to {:?}", self.origin);
+
+        Some(OriginShift {
+            offset,
+            new_origin: self.origin,
+        })
+    }
+
+    // A shift that could not be emitted is retried on the next tick, systems
+    // must never miss one or they end up in a different space
+    fn emit_pending(&mut self, ctx: &mut SubsystemContext) {
+        if let Some(shift) = self.pending.take() {
+            if let Err(err) = ctx
+                .event_queue
+                .emit(Box::new(WorldEvents::OriginShifted(shift.clone())))
+            {
+                error!("unable to emit origin shift {:?}", err);
+                self.pending = Some(shift);
+            }
+        }
+    }
+}
+
+impl Default for FloatingOrigin {
+    fn default() -> Self {
+        Self::new(5000.0)
+    }
+}
+
+impl Subsystem for FloatingOrigin {
+    fn get_name(&self) -> &str {
+        "FloatingOrigin"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        if self.threshold <= 0.0 {
+            return Err("threshold has to be positive".to_string());
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        self.emit_pending(ctx);
+        if self.pending.is_none() {
+            self.pending = self.update();
+            self.emit_pending(ctx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_rebase_below_threshold() {
+        let mut origin = FloatingOrigin::new(100.0);
+        origin.set_camera_position(Vec3::new(50.0, 0.0, 0.0));
+        assert!(origin.update().is_none());
+        assert_eq!(origin.get_origin(), DVec3::ZERO);
+    }
+
+    #[test]
+    fn test_rebase_keeps_world_positions() {
+        let mut origin = FloatingOrigin::new(100.0);
+        let entity_world = origin.to_world(Vec3::new(150.0, 0.0, 10.0));
+
+        origin.set_camera_position(Vec3::new(120.0, 0.0, 0.0));
+        let shift = origin.update().expect("should rebase past the threshold");
+
+        assert_eq!(shift.offset, Vec3::new(120.0, 0.0, 0.0));
+        assert_eq!(shift.new_origin, DVec3::new(120.0, 0.0, 0.0));
+
+        let entity_local = Vec3::new(150.0, 0.0, 10.0) - shift.offset;
+        assert_eq!(origin.to_local(entity_world), entity_local);
+        assert_eq!(origin.to_world(entity_local), entity_world);
+    }
+
+    #[test]
+    fn test_disabled_never_rebases() {
+        let mut origin = FloatingOrigin::new(1.0);
+        origin.set_enabled(false);
+        origin.set_camera_position(Vec3::new(1000.0, 0.0, 0.0));
+        assert!(origin.update().is_none());
+    }
+}
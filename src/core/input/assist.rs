@@ -0,0 +1,352 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+use crate::{core::settings::Settings, event_system::engine_events::input_events::InputEvent};
+
+pub const ASSIST_SETTINGS_PREFIX: &str = "input.assist.";
+
+// One switch scanning: the engine walks over `actions`, highlighting each for
+// `interval`. Pressing the single switch triggers the highlighted action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanSettings {
+    pub actions: Vec<String>,
+    pub interval: Duration,
+    pub switch_action: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssistSettings {
+    // a press toggles these on and off, releasing does nothing
+    pub toggle_actions: HashSet<String>,
+    // how long an action has to be held before it counts, filters out
+    // accidental taps
+    pub hold_threshold: Duration,
+    // None disables repeating
+    pub repeat_delay: Option<Duration>,
+    pub repeat_interval: Duration,
+    pub scanning: Option<ScanSettings>,
+}
+
+impl Default for AssistSettings {
+    fn default() -> Self {
+        Self {
+            toggle_actions: HashSet::new(),
+            hold_threshold: Duration::ZERO,
+            repeat_delay: None,
+            repeat_interval: Duration::from_millis(100),
+            scanning: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct HeldAction {
+    pressed_at: Duration,
+    started: bool,
+    next_repeat: Option<Duration>,
+}
+
+// Sits between the raw action presses and the action events gameplay sees
+#[derive(Debug, Clone, Default)]
+pub struct InputAssist {
+    settings: AssistSettings,
+    held: HashMap<String, HeldAction>,
+    toggled: HashSet<String>,
+    scan_index: usize,
+    next_scan: Option<Duration>,
+}
+
+impl InputAssist {
+    pub fn new(settings: AssistSettings) -> Self {
+        Self {
+            settings,
+            ..Default::default()
+        }
+    }
+
+    pub fn get_settings(&self) -> &AssistSettings {
+        &self.settings
+    }
+
+    // Held and toggled actions are kept, scanning starts over
+    pub fn set_settings(&mut self, settings: AssistSettings) {
+        self.settings = settings;
+        self.scan_index = 0;
+        self.next_scan = None;
+    }
+
+    pub fn get_scan_focus(&self) -> Option<&str> {
+        let scanning = self.settings.scanning.as_ref()?;
+        self.next_scan?;
+        scanning
+            .actions
+            .get(self.scan_index)
+            .map(|action| action.as_str())
+    }
+
+    pub fn handle(&mut self, action: &str, pressed: bool, now: Duration) -> Vec<InputEvent> {
+        if let Some(scanning) = self.settings.scanning.as_ref() {
+            if scanning.switch_action == action {
+                return match (pressed, self.get_scan_focus()) {
+                    (true, Some(focused)) => vec![
+                        InputEvent::ActionStarted(focused.to_string()),
+                        InputEvent::ActionEnded(focused.to_string()),
+                    ],
+                    _ => Vec::new(),
+                };
+            }
+        }
+
+        if self.settings.toggle_actions.contains(action) {
+            if !pressed {
+                return Vec::new();
+            }
+            return if self.toggled.remove(action) {
+                vec![InputEvent::ActionEnded(action.to_string())]
+            } else {
+                self.toggled.insert(action.to_string());
+                vec![InputEvent::ActionStarted(action.to_string())]
+            };
+        }
+
+        if pressed {
+            if self.held.contains_key(action) {
+                return Vec::new();
+            }
+            let started = self.settings.hold_threshold.is_zero();
+            self.held.insert(
+                action.to_string(),
+                HeldAction {
+                    pressed_at: now,
+                    started,
+                    next_repeat: self.settings.repeat_delay.map(|delay| now + delay),
+                },
+            );
+            if started {
+                return vec![InputEvent::ActionStarted(action.to_string())];
+            }
+            return Vec::new();
+        }
+
+        match self.held.remove(action) {
+            Some(held) if held.started => vec![InputEvent::ActionEnded(action.to_string())],
+            _ => Vec::new(),
+        }
+    }
+
+    // Hold thresholds, repeats and scanning are time based, call every frame
+    pub fn update(&mut self, now: Duration) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+
+        for (action, held) in self.held.iter_mut() {
+            if !held.started {
+                if now - held.pressed_at < self.settings.hold_threshold {
+                    continue;
+                }
+                held.started = true;
+                held.next_repeat = self.settings.repeat_delay.map(|delay| now + delay);
+                events.push(InputEvent::ActionStarted(action.clone()));
+                continue;
+            }
+            if let Some(next_repeat) = held.next_repeat {
+                if now >= next_repeat {
+                    held.next_repeat = Some(now + self.settings.repeat_interval);
+                    events.push(InputEvent::ActionRepeated(action.clone()));
+                }
+            }
+        }
+
+        if let Some(scanning) = self.settings.scanning.as_ref() {
+            if !scanning.actions.is_empty() {
+                let advance = match self.next_scan {
+                    None => true,
+                    Some(next_scan) if now >= next_scan => {
+                        self.scan_index = (self.scan_index + 1) % scanning.actions.len();
+                        true
+                    }
+                    _ => false,
+                };
+                if advance {
+                    self.next_scan = Some(now + scanning.interval);
+                    events.push(InputEvent::ScanFocused(
+                        scanning.actions[self.scan_index].clone(),
+                    ));
+                }
+            }
+        }
+
+        events
+    }
+
+    // Reads `input.assist.*`, durations are in seconds:
+    //
+    // hold_threshold, repeat_delay (0 disables), repeat_interval,
+    // toggle.<action> = true, scan.enabled, scan.interval,
+    // scan.actions = "Up,Down,Select", scan.switch = "Switch"
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        let key = |field: &str| format!("{}{}", ASSIST_SETTINGS_PREFIX, field);
+        // values that do not fit a Duration, like inf, are ignored
+        let seconds = |field: &str| {
+            settings
+                .get_float(&key(field))
+                .filter(|seconds| seconds.is_finite())
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds.max(0.0)).ok())
+        };
+        let mut assist = self.settings.clone();
+
+        if let Some(threshold) = seconds("hold_threshold") {
+            assist.hold_threshold = threshold;
+        }
+        if let Some(delay) = seconds("repeat_delay") {
+            assist.repeat_delay = (!delay.is_zero()).then_some(delay);
+        }
+        if let Some(interval) = seconds("repeat_interval") {
+            assist.repeat_interval = interval;
+        }
+
+        let toggle_prefix = key("toggle.");
+        for toggle_key in settings.keys_with_prefix(&toggle_prefix) {
+            let action = toggle_key[toggle_prefix.len()..].to_string();
+            if settings.get_bool(toggle_key) == Some(true) {
+                assist.toggle_actions.insert(action);
+            } else {
+                assist.toggle_actions.remove(&action);
+            }
+        }
+
+        match settings.get_bool(&key("scan.enabled")) {
+            Some(true) => {
+                let mut scanning = assist.scanning.take().unwrap_or(ScanSettings {
+                    actions: Vec::new(),
+                    interval: Duration::from_secs(1),
+                    switch_action: "Switch".to_string(),
+                });
+                if let Some(interval) = seconds("scan.interval") {
+                    scanning.interval = interval;
+                }
+                if let Some(actions) = settings.get_text(&key("scan.actions")) {
+                    scanning.actions = actions
+                        .split(',')
+                        .map(|action| action.trim().to_string())
+                        .filter(|action| !action.is_empty())
+                        .collect();
+                }
+                if let Some(switch_action) = settings.get_text(&key("scan.switch")) {
+                    scanning.switch_action = switch_action.to_string();
+                }
+                assist.scanning = Some(scanning);
+            }
+            Some(false) => assist.scanning = None,
+            None => {}
+        }
+
+        if assist != self.settings {
+            self.set_settings(assist);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::settings::SettingValue;
+
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_toggle_to_hold() {
+        let mut assist = InputAssist::default();
+        assist.set_settings(AssistSettings {
+            toggle_actions: HashSet::from(["Sprint".to_string()]),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            assist.handle("Sprint", true, ms(0)),
+            vec![InputEvent::ActionStarted("Sprint".to_string())]
+        );
+        assert!(assist.handle("Sprint", false, ms(10)).is_empty());
+        assert_eq!(
+            assist.handle("Sprint", true, ms(20)),
+            vec![InputEvent::ActionEnded("Sprint".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_hold_threshold_and_repeat() {
+        let mut assist = InputAssist::new(AssistSettings {
+            hold_threshold: ms(100),
+            repeat_delay: Some(ms(200)),
+            repeat_interval: ms(50),
+            ..Default::default()
+        });
+
+        // released before the threshold, nothing happens
+        assist.handle("Jump", true, ms(0));
+        assert!(assist.update(ms(50)).is_empty());
+        assert!(assist.handle("Jump", false, ms(60)).is_empty());
+
+        assist.handle("Jump", true, ms(100));
+        assert_eq!(
+            assist.update(ms(200)),
+            vec![InputEvent::ActionStarted("Jump".to_string())]
+        );
+        assert!(assist.update(ms(300)).is_empty());
+        assert_eq!(
+            assist.update(ms(400)),
+            vec![InputEvent::ActionRepeated("Jump".to_string())]
+        );
+        assert_eq!(
+            assist.handle("Jump", false, ms(420)),
+            vec![InputEvent::ActionEnded("Jump".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_one_switch_scanning() {
+        let mut settings = Settings::new();
+        settings.set("input.assist.scan.enabled", SettingValue::Bool(true));
+        settings.set("input.assist.scan.interval", SettingValue::Float(0.5));
+        settings.set(
+            "input.assist.scan.actions",
+            SettingValue::Text("Left, Right".to_string()),
+        );
+        settings.set(
+            "input.assist.hold_threshold",
+            SettingValue::Float(f64::INFINITY),
+        );
+        settings.set("input.assist.repeat_interval", SettingValue::Float(1e300));
+        let mut assist = InputAssist::default();
+        assist.apply_settings(&settings);
+        assert_eq!(
+            assist.get_settings().hold_threshold,
+            AssistSettings::default().hold_threshold
+        );
+        assert_eq!(
+            assist.get_settings().repeat_interval,
+            AssistSettings::default().repeat_interval
+        );
+
+        assert_eq!(
+            assist.update(ms(0)),
+            vec![InputEvent::ScanFocused("Left".to_string())]
+        );
+        assert_eq!(
+            assist.update(ms(500)),
+            vec![InputEvent::ScanFocused("Right".to_string())]
+        );
+        assert_eq!(
+            assist.handle("Switch", true, ms(600)),
+            vec![
+                InputEvent::ActionStarted("Right".to_string()),
+                InputEvent::ActionEnded("Right".to_string())
+            ]
+        );
+        assert!(assist.handle("Switch", false, ms(650)).is_empty());
+    }
+}
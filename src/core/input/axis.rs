@@ -0,0 +1,126 @@
+use std::{fmt::Debug, sync::Arc};
+
+pub type CurveFn = Arc<dyn Fn(f32) -> f32 + Send + Sync>;
+
+#[derive(Clone, Default)]
+pub enum ResponseCurve {
+    #[default]
+    Linear,
+    // |x|^exponent keeping the sign, > 1 gives finer control near the center
+    Exponential(f32),
+    // Receives the dead zone adjusted value in [-1, 1]
+    Custom(CurveFn),
+}
+
+impl ResponseCurve {
+    pub fn evaluate(&self, value: f32) -> f32 {
+        match self {
+            Self::Linear => value,
+            Self::Exponential(exponent) => value.signum() * value.abs().powf(*exponent),
+            Self::Custom(curve) => curve(value),
+        }
+    }
+}
+
+impl Debug for ResponseCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Linear => write!(f, "Linear"),
+            Self::Exponential(exponent) => write!(f, "Exponential({})", exponent),
+            Self::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AxisSettings {
+    pub dead_zone: f32,
+    pub sensitivity: f32,
+    pub inverted: bool,
+    pub curve: ResponseCurve,
+}
+
+impl AxisSettings {
+    // raw -> dead zone -> response curve -> sensitivity -> inversion
+    pub fn apply(&self, raw: f32) -> f32 {
+        let magnitude = raw.abs();
+        if magnitude <= self.dead_zone {
+            return 0.0;
+        }
+
+        // rescale so the output starts at 0 right outside the dead zone
+        // instead of jumping to the dead zone value
+        let live_range = (1.0 - self.dead_zone).max(f32::EPSILON);
+        let rescaled = raw.signum() * ((magnitude - self.dead_zone) / live_range);
+
+        let value = self.curve.evaluate(rescaled) * self.sensitivity;
+        if self.inverted {
+            -value
+        } else {
+            value
+        }
+    }
+}
+
+impl Default for AxisSettings {
+    fn default() -> Self {
+        Self {
+            dead_zone: 0.0,
+            sensitivity: 1.0,
+            inverted: false,
+            curve: ResponseCurve::Linear,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn approx_eq(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-5
+    }
+
+    #[test]
+    fn test_default_settings_pass_through() {
+        let settings = AxisSettings::default();
+        assert!(approx_eq(settings.apply(0.5), 0.5));
+        assert!(approx_eq(settings.apply(-1.0), -1.0));
+    }
+
+    #[test]
+    fn test_dead_zone_is_removed_and_rescaled() {
+        let settings = AxisSettings {
+            dead_zone: 0.2,
+            ..Default::default()
+        };
+
+        assert_eq!(settings.apply(0.15), 0.0);
+        assert_eq!(settings.apply(-0.2), 0.0);
+        assert!(approx_eq(settings.apply(0.6), 0.5));
+        assert!(approx_eq(settings.apply(-1.0), -1.0));
+    }
+
+    #[test]
+    fn test_curve_sensitivity_and_inversion() {
+        let settings = AxisSettings {
+            dead_zone: 0.0,
+            sensitivity: 2.0,
+            inverted: true,
+            curve: ResponseCurve::Exponential(2.0),
+        };
+
+        assert!(approx_eq(settings.apply(0.5), -0.5));
+        assert!(approx_eq(settings.apply(-0.5), 0.5));
+    }
+
+    #[test]
+    fn test_custom_curve() {
+        let settings = AxisSettings {
+            curve: ResponseCurve::Custom(Arc::new(|value| value / 2.0)),
+            ..Default::default()
+        };
+
+        assert!(approx_eq(settings.apply(0.8), 0.4));
+    }
+}
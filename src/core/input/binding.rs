@@ -0,0 +1,330 @@
+use std::{fmt::Display, str::FromStr};
+
+use thiserror::Error;
+
+use crate::{
+    core::key_code::KeyCode,
+    event_system::engine_events::{
+        gamepad_events::{GamepadAxis, GamepadButton},
+        mouse_events::MouseButton,
+    },
+};
+
+use super::state::Input;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum BindingErrors {
+    #[error("unknown input binding {0}")]
+    Unknown(String),
+}
+
+// How far a gamepad axis has to be pushed to count as a pressed button
+pub const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+const GAMEPAD_AXES: [(GamepadAxis, &str); 6] = [
+    (GamepadAxis::LeftStickX, "LeftStickX"),
+    (GamepadAxis::LeftStickY, "LeftStickY"),
+    (GamepadAxis::RightStickX, "RightStickX"),
+    (GamepadAxis::RightStickY, "RightStickY"),
+    (GamepadAxis::LeftTrigger, "LeftTrigger"),
+    (GamepadAxis::RightTrigger, "RightTrigger"),
+];
+
+// Deltas of the current frame, not clamped: motion is in logical pixels
+// with y growing downwards, scroll in lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseAxis {
+    MotionX,
+    MotionY,
+    ScrollX,
+    ScrollY,
+}
+
+const MOUSE_AXES: [(MouseAxis, &str); 4] = [
+    (MouseAxis::MotionX, "MotionX"),
+    (MouseAxis::MotionY, "MotionY"),
+    (MouseAxis::ScrollX, "ScrollX"),
+    (MouseAxis::ScrollY, "ScrollY"),
+];
+
+const GAMEPAD_BUTTONS: [(GamepadButton, &str); 15] = [
+    (GamepadButton::South, "South"),
+    (GamepadButton::East, "East"),
+    (GamepadButton::West, "West"),
+    (GamepadButton::North, "North"),
+    (GamepadButton::LeftShoulder, "LeftShoulder"),
+    (GamepadButton::RightShoulder, "RightShoulder"),
+    (GamepadButton::LeftStick, "LeftStick"),
+    (GamepadButton::RightStick, "RightStick"),
+    (GamepadButton::Select, "Select"),
+    (GamepadButton::Start, "Start"),
+    (GamepadButton::Guide, "Guide"),
+    (GamepadButton::DPadUp, "DPadUp"),
+    (GamepadButton::DPadDown, "DPadDown"),
+    (GamepadButton::DPadLeft, "DPadLeft"),
+    (GamepadButton::DPadRight, "DPadRight"),
+];
+
+const MOUSE_BUTTONS: [(MouseButton, &str); 5] = [
+    (MouseButton::Left, "Left"),
+    (MouseButton::Right, "Right"),
+    (MouseButton::Middle, "Middle"),
+    (MouseButton::Back, "Back"),
+    (MouseButton::Forward, "Forward"),
+];
+
+fn find_name<T: PartialEq + Copy>(table: &[(T, &'static str)], value: T) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(entry, _)| *entry == value)
+        .map(|(_, name)| *name)
+}
+
+fn find_value<T: Copy>(table: &[(T, &str)], name: &str) -> Option<T> {
+    table
+        .iter()
+        .find(|(_, entry)| *entry == name)
+        .map(|(value, _)| *value)
+}
+
+// One physical input an action can be bound to. Written as `Key.Space`,
+// `Mouse.Left`, `Gamepad.South` or `GamepadAxis.LeftTrigger+` in settings
+// files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InputBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    // on any connected gamepad
+    Gamepad(GamepadButton),
+    // pressed while the axis is pushed past `AXIS_PRESS_THRESHOLD` in the
+    // positive or negative direction
+    GamepadAxis { axis: GamepadAxis, positive: bool },
+}
+
+impl InputBinding {
+    pub fn is_down(&self, input: &Input) -> bool {
+        match self {
+            Self::Key(key) => input.is_key_down(*key),
+            Self::Mouse(button) => input.is_mouse_button_down(*button),
+            Self::Gamepad(button) => input.get_gamepads().any(|gamepad| {
+                input
+                    .get_gamepad_buttons(*gamepad)
+                    .is_some_and(|buttons| buttons.is_down(*button))
+            }),
+            Self::GamepadAxis { axis, positive } => {
+                let value = input.axis(*axis);
+                match positive {
+                    true => value >= AXIS_PRESS_THRESHOLD,
+                    false => value <= -AXIS_PRESS_THRESHOLD,
+                }
+            }
+        }
+    }
+
+    // 0 to 1, buttons are 0 or 1
+    pub fn get_value(&self, input: &Input) -> f32 {
+        match self {
+            Self::GamepadAxis { axis, positive } => {
+                let value = input.axis(*axis);
+                match positive {
+                    true => value.max(0.0),
+                    false => (-value).max(0.0),
+                }
+            }
+            _ => match self.is_down(input) {
+                true => 1.0,
+                false => 0.0,
+            },
+        }
+    }
+}
+
+impl Display for InputBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Key(key) => write!(f, "Key.{}", key.get_name()),
+            Self::Mouse(MouseButton::Other(button)) => write!(f, "Mouse.Button{}", button),
+            Self::Mouse(button) => {
+                write!(
+                    f,
+                    "Mouse.{}",
+                    find_name(&MOUSE_BUTTONS, *button).unwrap_or("?")
+                )
+            }
+            Self::Gamepad(GamepadButton::Other(button)) => write!(f, "Gamepad.Button{}", button),
+            Self::Gamepad(button) => write!(
+                f,
+                "Gamepad.{}",
+                find_name(&GAMEPAD_BUTTONS, *button).unwrap_or("?")
+            ),
+            Self::GamepadAxis { axis, positive } => write!(
+                f,
+                "GamepadAxis.{}{}",
+                find_name(&GAMEPAD_AXES, *axis).unwrap_or("?"),
+                if *positive { '+' } else { '-' }
+            ),
+        }
+    }
+}
+
+impl FromStr for InputBinding {
+    type Err = BindingErrors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let unknown = || BindingErrors::Unknown(s.to_string());
+        let (device, name) = s.trim().split_once('.').ok_or_else(unknown)?;
+        let other = |name: &str| name.strip_prefix("Button")?.parse::<u16>().ok();
+
+        let binding = match device {
+            "Key" => KeyCode::from_name(name).map(Self::Key),
+            "Mouse" => find_value(&MOUSE_BUTTONS, name)
+                .or_else(|| other(name).map(MouseButton::Other))
+                .map(Self::Mouse),
+            "Gamepad" => find_value(&GAMEPAD_BUTTONS, name)
+                .or_else(|| other(name).map(GamepadButton::Other))
+                .map(Self::Gamepad),
+            "GamepadAxis" => {
+                let (axis, positive) = match name.strip_suffix('-') {
+                    Some(axis) => (axis, false),
+                    None => (name.strip_suffix('+').unwrap_or(name), true),
+                };
+                find_value(&GAMEPAD_AXES, axis).map(|axis| Self::GamepadAxis { axis, positive })
+            }
+            _ => None,
+        };
+        binding.ok_or_else(unknown)
+    }
+}
+
+// A source for a logical axis like "MoveX". Written as `Axis.LeftStickX`
+// or `Key.A/Key.D` for a negative and positive button pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AxisBinding {
+    // the axis of whichever gamepad is pushed furthest
+    Gamepad(GamepadAxis),
+    Mouse(MouseAxis),
+    Buttons {
+        negative: InputBinding,
+        positive: InputBinding,
+    },
+}
+
+impl AxisBinding {
+    pub fn buttons(negative: InputBinding, positive: InputBinding) -> Self {
+        Self::Buttons { negative, positive }
+    }
+
+    // Raw value before the axis settings are applied, in [-1, 1] except
+    // for mouse axes
+    pub fn get_value(&self, input: &Input) -> f32 {
+        match self {
+            Self::Gamepad(axis) => input.axis(*axis),
+            Self::Mouse(axis) => match axis {
+                MouseAxis::MotionX => input.mouse_delta().x,
+                MouseAxis::MotionY => input.mouse_delta().y,
+                MouseAxis::ScrollX => input.scroll_delta().x,
+                MouseAxis::ScrollY => input.scroll_delta().y,
+            },
+            Self::Buttons { negative, positive } => {
+                positive.get_value(input) - negative.get_value(input)
+            }
+        }
+    }
+}
+
+impl Display for AxisBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Gamepad(axis) => {
+                write!(f, "Axis.{}", find_name(&GAMEPAD_AXES, *axis).unwrap_or("?"))
+            }
+            Self::Mouse(axis) => {
+                write!(
+                    f,
+                    "MouseAxis.{}",
+                    find_name(&MOUSE_AXES, *axis).unwrap_or("?")
+                )
+            }
+            Self::Buttons { negative, positive } => write!(f, "{}/{}", negative, positive),
+        }
+    }
+}
+
+impl FromStr for AxisBinding {
+    type Err = BindingErrors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some((negative, positive)) = s.split_once('/') {
+            return Ok(Self::buttons(negative.parse()?, positive.parse()?));
+        }
+        if let Some(axis) = s.strip_prefix("MouseAxis.") {
+            return find_value(&MOUSE_AXES, axis)
+                .map(Self::Mouse)
+                .ok_or_else(|| BindingErrors::Unknown(s.to_string()));
+        }
+        s.strip_prefix("Axis.")
+            .and_then(|axis| find_value(&GAMEPAD_AXES, axis))
+            .map(Self::Gamepad)
+            .ok_or_else(|| BindingErrors::Unknown(s.to_string()))
+    }
+}
+
+// Settings files keep every binding of an action in one value, separated by
+// commas
+pub fn format_bindings<T: Display>(bindings: &[T]) -> String {
+    bindings
+        .iter()
+        .map(|binding| binding.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+pub fn parse_bindings<T: FromStr<Err = BindingErrors>>(
+    text: &str,
+) -> Result<Vec<T>, BindingErrors> {
+    text.split(',')
+        .filter(|binding| !binding.trim().is_empty())
+        .map(|binding| binding.parse())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binding_names_round_trip() {
+        let bindings = vec![
+            InputBinding::Key(KeyCode::Space),
+            InputBinding::Key(KeyCode::KP0),
+            InputBinding::Mouse(MouseButton::Other(4)),
+            InputBinding::Gamepad(GamepadButton::South),
+            InputBinding::GamepadAxis {
+                axis: GamepadAxis::RightTrigger,
+                positive: true,
+            },
+        ];
+        let text = format_bindings(&bindings);
+        assert_eq!(
+            text,
+            "Key.Space, Key.KP0, Mouse.Button4, Gamepad.South, GamepadAxis.RightTrigger+"
+        );
+        assert_eq!(parse_bindings::<InputBinding>(&text), Ok(bindings));
+
+        let axis =
+            AxisBinding::buttons(InputBinding::Key(KeyCode::A), InputBinding::Key(KeyCode::D));
+        assert_eq!("Key.A/Key.D".parse(), Ok(axis));
+        assert_eq!(
+            "Axis.LeftStickX".parse(),
+            Ok(AxisBinding::Gamepad(GamepadAxis::LeftStickX))
+        );
+        let look = AxisBinding::Mouse(MouseAxis::MotionY);
+        assert_eq!(look.to_string(), "MouseAxis.MotionY");
+        assert_eq!("MouseAxis.MotionY".parse(), Ok(look));
+        assert_eq!(
+            "Key.Nope".parse::<InputBinding>(),
+            Err(BindingErrors::Unknown("Key.Nope".to_string()))
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::{error, warn};
+use thiserror::Error;
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::{
+        engine_events::gamepad_events::{GamepadEvents, GamepadId},
+        event_queue::EventQueue,
+    },
+};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum GamepadErrors {
+    #[error("there is no gamepad {0:?}")]
+    UnknownGamepad(GamepadId),
+
+    #[error("gamepad {0:?} has no rumble motors")]
+    RumbleUnsupported(GamepadId),
+
+    #[error("gamepad backend failed: {0}")]
+    Backend(String),
+}
+
+// Motor speeds in [0, 1]. The strong motor is the low frequency one in the
+// left grip, the weak one the high frequency motor in the right grip.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rumble {
+    pub strong: f32,
+    pub weak: f32,
+}
+
+impl Rumble {
+    pub const OFF: Self = Self {
+        strong: 0.0,
+        weak: 0.0,
+    };
+
+    pub fn new(strong: f32, weak: f32) -> Self {
+        Self {
+            strong: strong.clamp(0.0, 1.0),
+            weak: weak.clamp(0.0, 1.0),
+        }
+    }
+}
+
+// The platform side of the pads (sdl2, gilrs, a console's pad library...).
+// Connections, buttons and axes are sent as GamepadEvents.
+pub trait GamepadBackend {
+    fn poll_events(&mut self, queue: &Arc<EventQueue>) -> Result<(), GamepadErrors>;
+
+    // Runs the motors until they are set again, the subsystem stops them
+    // when the duration is over
+    fn set_rumble(&mut self, id: GamepadId, rumble: Rumble) -> Result<(), GamepadErrors>;
+}
+
+// Pads driven from code, for headless runs and tests
+#[derive(Debug, Default)]
+pub struct VirtualGamepads {
+    // whether each connected pad has motors, and what they run at
+    pads: BTreeMap<GamepadId, Option<Rumble>>,
+    pending: Vec<GamepadEvents>,
+}
+
+impl VirtualGamepads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn connect(&mut self, id: GamepadId, has_rumble: bool) {
+        self.pads.insert(id, has_rumble.then_some(Rumble::OFF));
+        self.pending.push(GamepadEvents::Connected(id));
+    }
+
+    pub fn disconnect(&mut self, id: GamepadId) {
+        if self.pads.remove(&id).is_some() {
+            self.pending.push(GamepadEvents::Disconnected(id));
+        }
+    }
+
+    // Plays a button or axis change, sent on the next poll
+    pub fn send(&mut self, event: GamepadEvents) {
+        self.pending.push(event);
+    }
+
+    pub fn get_rumble(&self, id: GamepadId) -> Option<Rumble> {
+        self.pads.get(&id).copied().flatten()
+    }
+}
+
+impl GamepadBackend for VirtualGamepads {
+    fn poll_events(&mut self, queue: &Arc<EventQueue>) -> Result<(), GamepadErrors> {
+        for event in self.pending.drain(..) {
+            queue
+                .emit(Box::new(event))
+                .map_err(|err| GamepadErrors::Backend(format!("{:?}", err)))?;
+        }
+        Ok(())
+    }
+
+    fn set_rumble(&mut self, id: GamepadId, rumble: Rumble) -> Result<(), GamepadErrors> {
+        match self.pads.get_mut(&id) {
+            Some(Some(motors)) => {
+                *motors = rumble;
+                Ok(())
+            }
+            Some(None) => Err(GamepadErrors::RumbleUnsupported(id)),
+            None => Err(GamepadErrors::UnknownGamepad(id)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RumbleRequest {
+    id: GamepadId,
+    rumble: Rumble,
+    duration: Duration,
+}
+
+// Lets event handlers, which have no access to the engine, ask for rumble.
+// The requests are played on the next tick of the subsystem.
+//
+//     let rumble = gamepads.get_rumble_handle();
+//     app.on_event("PlayerHit".to_string(), move |_| {
+//         rumble.set_rumble(GamepadId(0), 0.8, 0.3, Duration::from_millis(200));
+//     });
+#[derive(Debug, Clone, Default)]
+pub struct RumbleHandle {
+    requests: Arc<Mutex<Vec<RumbleRequest>>>,
+}
+
+impl RumbleHandle {
+    pub fn set_rumble(&self, id: GamepadId, strong: f32, weak: f32, duration: Duration) {
+        if let Ok(mut requests) = self.requests.lock() {
+            requests.push(RumbleRequest {
+                id,
+                rumble: Rumble::new(strong, weak),
+                duration,
+            });
+        }
+    }
+
+    pub fn stop_rumble(&self, id: GamepadId) {
+        self.set_rumble(id, 0.0, 0.0, Duration::ZERO);
+    }
+
+    fn take(&self) -> Vec<RumbleRequest> {
+        self.requests
+            .lock()
+            .map(|mut requests| std::mem::take(&mut *requests))
+            .unwrap_or_default()
+    }
+}
+
+// Polls the gamepad backend every frame and drives force feedback. A new
+// rumble on a pad replaces the one that is playing. Rumble runs on unscaled
+// time, pausing the game does not stretch it.
+pub struct GamepadSubsystem {
+    backend: Box<dyn GamepadBackend>,
+    // when the rumble of each pad ends
+    rumbling: HashMap<GamepadId, Duration>,
+    handle: RumbleHandle,
+    now: Duration,
+}
+
+impl GamepadSubsystem {
+    pub fn new(backend: impl GamepadBackend + 'static) -> Self {
+        Self {
+            backend: Box::new(backend),
+            rumbling: HashMap::new(),
+            handle: RumbleHandle::default(),
+            now: Duration::ZERO,
+        }
+    }
+
+    pub fn get_rumble_handle(&self) -> RumbleHandle {
+        self.handle.clone()
+    }
+
+    // Strong and weak are clamped to [0, 1], a zero duration stops the pad
+    pub fn set_rumble(
+        &mut self,
+        id: GamepadId,
+        strong: f32,
+        weak: f32,
+        duration: Duration,
+    ) -> Result<(), GamepadErrors> {
+        let rumble = match duration.is_zero() {
+            true => Rumble::OFF,
+            false => Rumble::new(strong, weak),
+        };
+        self.backend.set_rumble(id, rumble)?;
+        match rumble == Rumble::OFF {
+            true => self.rumbling.remove(&id),
+            false => self.rumbling.insert(id, self.now + duration),
+        };
+        Ok(())
+    }
+
+    pub fn stop_rumble(&mut self, id: GamepadId) -> Result<(), GamepadErrors> {
+        self.set_rumble(id, 0.0, 0.0, Duration::ZERO)
+    }
+
+    pub fn is_rumbling(&self, id: GamepadId) -> bool {
+        self.rumbling.contains_key(&id)
+    }
+
+    fn stop_expired(&mut self) {
+        let now = self.now;
+        let expired: Vec<GamepadId> = self
+            .rumbling
+            .iter()
+            .filter(|(_, ends)| **ends <= now)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in expired {
+            self.rumbling.remove(&id);
+            if let Err(err) = self.backend.set_rumble(id, Rumble::OFF) {
+                warn!("unable to stop the rumble of {:?}: {}", id, err);
+            }
+        }
+    }
+}
+
+impl Subsystem for GamepadSubsystem {
+    fn get_name(&self) -> &str {
+        "Gamepad"
+    }
+
+    fn init(&mut self, ctx: &mut SubsystemContext) -> Result<(), String> {
+        self.now = ctx.time.get_unscaled_elapsed();
+        self.backend
+            .poll_events(ctx.event_queue)
+            .map_err(|err| err.to_string())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        self.now = ctx.time.get_unscaled_elapsed();
+        if let Err(err) = self.backend.poll_events(ctx.event_queue) {
+            error!("{}", err);
+        }
+        for request in self.handle.take() {
+            let rumble = request.rumble;
+            let result = self.set_rumble(request.id, rumble.strong, rumble.weak, request.duration);
+            if let Err(err) = result {
+                warn!("unable to rumble: {}", err);
+            }
+        }
+        self.stop_expired();
+    }
+
+    fn shutdown(&mut self, _ctx: &mut SubsystemContext) {
+        let ids: Vec<GamepadId> = self.rumbling.drain().map(|(id, _)| id).collect();
+        for id in ids {
+            let _ = self.backend.set_rumble(id, Rumble::OFF);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{runner::exit_handlers::ExitHandlers, time::Time};
+
+    #[test]
+    fn test_rumble_stops_after_duration() {
+        let mut pads = VirtualGamepads::new();
+        pads.connect(GamepadId(0), true);
+        pads.connect(GamepadId(1), false);
+        let mut gamepads = GamepadSubsystem::new(pads);
+
+        let queue = Arc::new(EventQueue::new());
+        let mut time = Time::default();
+        let exit_handlers = ExitHandlers::default();
+        let mut ctx = SubsystemContext {
+            event_queue: &queue,
+            time: &time,
+            exit_handlers: &exit_handlers,
+        };
+        gamepads.init(&mut ctx).unwrap();
+        assert_eq!(queue.get_events().unwrap().len(), 2);
+
+        assert_eq!(
+            gamepads.set_rumble(GamepadId(1), 1.0, 1.0, Duration::from_secs(1)),
+            Err(GamepadErrors::RumbleUnsupported(GamepadId(1)))
+        );
+        assert_eq!(
+            gamepads.set_rumble(GamepadId(5), 1.0, 1.0, Duration::from_secs(1)),
+            Err(GamepadErrors::UnknownGamepad(GamepadId(5)))
+        );
+
+        gamepads.get_rumble_handle().set_rumble(
+            GamepadId(0),
+            2.0,
+            0.25,
+            Duration::from_millis(100),
+        );
+        gamepads.tick(&mut ctx);
+        assert!(gamepads.is_rumbling(GamepadId(0)));
+
+        time.advance(Duration::from_millis(150));
+        let mut ctx = SubsystemContext {
+            event_queue: &queue,
+            time: &time,
+            exit_handlers: &exit_handlers,
+        };
+        gamepads.tick(&mut ctx);
+        assert!(!gamepads.is_rumbling(GamepadId(0)));
+    }
+}
@@ -0,0 +1,376 @@
+use std::{collections::HashMap, time::Duration};
+
+use log::error;
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::engine_events::{
+        gesture_events::{GestureEvents, PanGesture, PinchGesture, SwipeDirection, SwipeGesture},
+        touch_events::{Touch, TouchPhase},
+    },
+    math::vector::Vec2,
+};
+
+// Distances are in pixels, velocities in pixels per second
+#[derive(Debug, Clone, PartialEq)]
+pub struct GestureConfig {
+    pub tap_max_duration: Duration,
+    // a finger that moved further than this is no tap anymore but a pan
+    pub tap_max_distance: f32,
+    pub double_tap_interval: Duration,
+    pub long_press_duration: Duration,
+    pub swipe_max_duration: Duration,
+    pub swipe_min_velocity: f32,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            tap_max_duration: Duration::from_millis(250),
+            tap_max_distance: 10.0,
+            double_tap_interval: Duration::from_millis(300),
+            long_press_duration: Duration::from_millis(500),
+            swipe_max_duration: Duration::from_millis(300),
+            swipe_min_velocity: 800.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TrackedTouch {
+    start_position: Vec2,
+    start_time: Duration,
+    position: Vec2,
+    last_time: Duration,
+    velocity: Vec2,
+    moved: bool,
+    long_pressed: bool,
+}
+
+// Turns raw touches into tap, double tap, long press, pinch, pan and swipe
+// gestures. The platform layer feeds touches with `push_touch`, the gestures
+// are emitted as GestureEvents on the next tick.
+#[derive(Debug, Default)]
+pub struct GestureRecognizer {
+    config: GestureConfig,
+    touches: HashMap<u64, TrackedTouch>,
+    pending: Vec<Touch>,
+    // a second finger turns the whole interaction into a pinch, no taps or
+    // swipes until every finger is lifted
+    multi_touch: bool,
+    pinch_start_distance: Option<f32>,
+    last_tap: Option<(Duration, Vec2)>,
+}
+
+impl GestureRecognizer {
+    pub fn new(config: GestureConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn get_config(&self) -> &GestureConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: GestureConfig) {
+        self.config = config;
+    }
+
+    pub fn push_touch(&mut self, touch: Touch) {
+        self.pending.push(touch);
+    }
+
+    // `now` is any monotonic timestamp, the engine uses unscaled elapsed time
+    pub fn process(&mut self, touch: &Touch, now: Duration) -> Vec<GestureEvents> {
+        let mut gestures = Vec::new();
+
+        match touch.phase {
+            TouchPhase::Started => {
+                self.touches.insert(
+                    touch.id,
+                    TrackedTouch {
+                        start_position: touch.position,
+                        start_time: now,
+                        position: touch.position,
+                        last_time: now,
+                        velocity: Vec2::ZERO,
+                        moved: false,
+                        long_pressed: false,
+                    },
+                );
+                if self.touches.len() > 1 {
+                    self.multi_touch = true;
+                    self.pinch_start_distance = self.pinch_distance();
+                }
+            }
+            TouchPhase::Moved => {
+                let Some(tracked) = self.touches.get_mut(&touch.id) else {
+                    return gestures;
+                };
+                let delta = touch.position - tracked.position;
+                let dt = (now - tracked.last_time).as_secs_f32();
+                if dt > 0.0 {
+                    tracked.velocity = delta / dt;
+                }
+                tracked.position = touch.position;
+                tracked.last_time = now;
+                if tracked.start_position.distance(touch.position) > self.config.tap_max_distance {
+                    tracked.moved = true;
+                }
+                let is_panning = tracked.moved && !tracked.long_pressed;
+                let velocity = tracked.velocity;
+
+                if self.touches.len() == 2 {
+                    if let (Some(start), Some(current)) =
+                        (self.pinch_start_distance, self.pinch_distance())
+                    {
+                        if start > 0.0 {
+                            gestures.push(GestureEvents::Pinch(PinchGesture {
+                                center: self.pinch_center(),
+                                scale: current / start,
+                            }));
+                        }
+                    }
+                } else if !self.multi_touch && is_panning {
+                    gestures.push(GestureEvents::Pan(PanGesture {
+                        position: touch.position,
+                        delta,
+                        velocity,
+                    }));
+                }
+            }
+            TouchPhase::Ended => {
+                if let Some(tracked) = self.touches.remove(&touch.id) {
+                    if !self.multi_touch && !tracked.long_pressed {
+                        self.finish_single_touch(&tracked, touch.position, now, &mut gestures);
+                    }
+                }
+                self.lifted();
+            }
+            TouchPhase::Cancelled => {
+                self.touches.remove(&touch.id);
+                self.lifted();
+            }
+        }
+
+        gestures
+    }
+
+    // Long presses fire while the finger is still down, so they have to be
+    // checked every frame
+    pub fn update(&mut self, now: Duration) -> Vec<GestureEvents> {
+        if self.multi_touch {
+            return Vec::new();
+        }
+        let long_press = self.config.long_press_duration;
+        self.touches
+            .values_mut()
+            .filter(|tracked| {
+                !tracked.moved && !tracked.long_pressed && now - tracked.start_time >= long_press
+            })
+            .map(|tracked| {
+                tracked.long_pressed = true;
+                GestureEvents::LongPress(tracked.position)
+            })
+            .collect()
+    }
+
+    fn finish_single_touch(
+        &mut self,
+        tracked: &TrackedTouch,
+        position: Vec2,
+        now: Duration,
+        gestures: &mut Vec<GestureEvents>,
+    ) {
+        let duration = now - tracked.start_time;
+
+        if !tracked.moved {
+            if duration > self.config.tap_max_duration {
+                return;
+            }
+            gestures.push(GestureEvents::Tap(position));
+
+            let is_double = self.last_tap.is_some_and(|(time, last_position)| {
+                now - time <= self.config.double_tap_interval
+                    && last_position.distance(position) <= self.config.tap_max_distance
+            });
+            if is_double {
+                gestures.push(GestureEvents::DoubleTap(position));
+                self.last_tap = None;
+            } else {
+                self.last_tap = Some((now, position));
+            }
+            return;
+        }
+
+        let seconds = duration.as_secs_f32();
+        if duration > self.config.swipe_max_duration || seconds <= 0.0 {
+            return;
+        }
+        let velocity = (position - tracked.start_position) / seconds;
+        if velocity.length() < self.config.swipe_min_velocity {
+            return;
+        }
+        // screen space, y grows downwards
+        let direction = if velocity.x.abs() >= velocity.y.abs() {
+            if velocity.x > 0.0 {
+                SwipeDirection::Right
+            } else {
+                SwipeDirection::Left
+            }
+        } else if velocity.y > 0.0 {
+            SwipeDirection::Down
+        } else {
+            SwipeDirection::Up
+        };
+        gestures.push(GestureEvents::Swipe(SwipeGesture {
+            direction,
+            velocity,
+        }));
+    }
+
+    fn lifted(&mut self) {
+        if self.touches.len() < 2 {
+            self.pinch_start_distance = None;
+        }
+        if self.touches.is_empty() {
+            self.multi_touch = false;
+        }
+    }
+
+    fn pinch_positions(&self) -> Option<(Vec2, Vec2)> {
+        let mut positions = self.touches.values().map(|tracked| tracked.position);
+        Some((positions.next()?, positions.next()?))
+    }
+
+    fn pinch_distance(&self) -> Option<f32> {
+        self.pinch_positions().map(|(a, b)| a.distance(b))
+    }
+
+    fn pinch_center(&self) -> Vec2 {
+        self.pinch_positions()
+            .map(|(a, b)| (a + b) / 2.0)
+            .unwrap_or(Vec2::ZERO)
+    }
+}
+
+impl Subsystem for GestureRecognizer {
+    fn get_name(&self) -> &str {
+        "GestureRecognizer"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        let now = ctx.time.get_unscaled_elapsed();
+        let mut gestures = Vec::new();
+        for touch in std::mem::take(&mut self.pending) {
+            gestures.extend(self.process(&touch, now));
+        }
+        gestures.extend(self.update(now));
+
+        for gesture in gestures {
+            if let Err(err) = ctx.event_queue.emit(Box::new(gesture)) {
+                error!("unable to emit gesture event {:?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn touch(id: u64, phase: TouchPhase, x: f32, y: f32) -> Touch {
+        Touch {
+            id,
+            phase,
+            position: Vec2::new(x, y),
+        }
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_tap_and_double_tap() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.process(&touch(1, TouchPhase::Started, 5.0, 5.0), ms(0));
+        let first = recognizer.process(&touch(1, TouchPhase::Ended, 6.0, 5.0), ms(100));
+        assert_eq!(first, vec![GestureEvents::Tap(Vec2::new(6.0, 5.0))]);
+
+        recognizer.process(&touch(2, TouchPhase::Started, 5.0, 5.0), ms(200));
+        let second = recognizer.process(&touch(2, TouchPhase::Ended, 5.0, 5.0), ms(250));
+        assert_eq!(
+            second,
+            vec![
+                GestureEvents::Tap(Vec2::new(5.0, 5.0)),
+                GestureEvents::DoubleTap(Vec2::new(5.0, 5.0))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_long_press_suppresses_tap() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.process(&touch(1, TouchPhase::Started, 0.0, 0.0), ms(0));
+        assert!(recognizer.update(ms(400)).is_empty());
+        assert_eq!(
+            recognizer.update(ms(600)),
+            vec![GestureEvents::LongPress(Vec2::ZERO)]
+        );
+        assert!(recognizer.update(ms(700)).is_empty());
+        assert!(recognizer
+            .process(&touch(1, TouchPhase::Ended, 0.0, 0.0), ms(800))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_pan_then_swipe() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.process(&touch(1, TouchPhase::Started, 0.0, 0.0), ms(0));
+        let pan = recognizer.process(&touch(1, TouchPhase::Moved, 50.0, 0.0), ms(50));
+        assert_eq!(
+            pan,
+            vec![GestureEvents::Pan(PanGesture {
+                position: Vec2::new(50.0, 0.0),
+                delta: Vec2::new(50.0, 0.0),
+                velocity: Vec2::new(1000.0, 0.0),
+            })]
+        );
+
+        let swipe = recognizer.process(&touch(1, TouchPhase::Ended, 100.0, 0.0), ms(100));
+        match swipe.as_slice() {
+            [GestureEvents::Swipe(swipe)] => assert_eq!(swipe.direction, SwipeDirection::Right),
+            other => panic!("expected a swipe, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_pinch_scale() {
+        let mut recognizer = GestureRecognizer::default();
+        recognizer.process(&touch(1, TouchPhase::Started, 0.0, 0.0), ms(0));
+        recognizer.process(&touch(2, TouchPhase::Started, 100.0, 0.0), ms(10));
+        let pinch = recognizer.process(&touch(2, TouchPhase::Moved, 200.0, 0.0), ms(50));
+
+        assert_eq!(
+            pinch,
+            vec![GestureEvents::Pinch(PinchGesture {
+                center: Vec2::new(100.0, 0.0),
+                scale: 2.0,
+            })]
+        );
+        recognizer.process(&touch(2, TouchPhase::Ended, 200.0, 0.0), ms(60));
+        assert!(
+            recognizer
+                .process(&touch(1, TouchPhase::Ended, 0.0, 0.0), ms(70))
+                .is_empty(),
+            "Lifting after a pinch is no tap"
+        );
+    }
+}
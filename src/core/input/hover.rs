@@ -0,0 +1,268 @@
+use std::{collections::HashMap, time::Duration};
+
+use log::error;
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::{
+        engine_events::hover_events::{HoverEvents, Tooltip},
+        event::{EntityId, TargetedEvent},
+    },
+    math::{rect::Rect, vector::Vec2},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HoverConfig {
+    // how long the cursor has to rest on a target before its tooltip shows
+    pub tooltip_delay: Duration,
+    // from the cursor to the tooltip's top left, in window pixels
+    pub tooltip_offset: Vec2,
+}
+
+impl Default for HoverConfig {
+    fn default() -> Self {
+        Self {
+            tooltip_delay: Duration::from_millis(500),
+            tooltip_offset: Vec2::new(12.0, 16.0),
+        }
+    }
+}
+
+// Finds the world entity under the cursor, e.g. a raycast against the
+// scene's spatial index. Only asked when no widget is under the cursor.
+pub trait HoverPicker {
+    fn pick(&self, cursor: Vec2) -> Option<EntityId>;
+}
+
+impl<F: Fn(Vec2) -> Option<EntityId>> HoverPicker for F {
+    fn pick(&self, cursor: Vec2) -> Option<EntityId> {
+        self(cursor)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Widget {
+    rect: Rect,
+    // higher layers are on top
+    layer: i32,
+}
+
+// Tracks what is under the cursor, UI widgets first then world entities,
+// and sends HoverEntered/HoverExited to the target. A target with a tooltip
+// gets it shown once the cursor rested on it for `tooltip_delay`.
+// The platform layer feeds the cursor with `set_cursor`, UI layout keeps the
+// widget rects up to date.
+pub struct HoverService {
+    config: HoverConfig,
+    widgets: HashMap<EntityId, Widget>,
+    tooltips: HashMap<EntityId, String>,
+    picker: Option<Box<dyn HoverPicker>>,
+    cursor: Option<Vec2>,
+    hovered: Option<EntityId>,
+    hovered_since: Duration,
+    tooltip: Option<Tooltip>,
+}
+
+impl Default for HoverService {
+    fn default() -> Self {
+        Self::new(HoverConfig::default())
+    }
+}
+
+impl HoverService {
+    pub fn new(config: HoverConfig) -> Self {
+        Self {
+            config,
+            widgets: HashMap::new(),
+            tooltips: HashMap::new(),
+            picker: None,
+            cursor: None,
+            hovered: None,
+            hovered_since: Duration::ZERO,
+            tooltip: None,
+        }
+    }
+
+    pub fn with_picker(mut self, picker: impl HoverPicker + 'static) -> Self {
+        self.picker = Some(Box::new(picker));
+        self
+    }
+
+    pub fn get_config(&self) -> &HoverConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: HoverConfig) {
+        self.config = config;
+    }
+
+    // Adds or moves a widget, in window pixels
+    pub fn set_widget(&mut self, entity: EntityId, rect: Rect, layer: i32) {
+        self.widgets.insert(entity, Widget { rect, layer });
+    }
+
+    pub fn remove_widget(&mut self, entity: EntityId) {
+        self.widgets.remove(&entity);
+    }
+
+    // None removes the tooltip, works for widgets and world entities
+    pub fn set_tooltip(&mut self, entity: EntityId, text: Option<&str>) {
+        match text {
+            Some(text) => self.tooltips.insert(entity, text.to_string()),
+            None => self.tooltips.remove(&entity),
+        };
+    }
+
+    // None when the cursor left the window
+    pub fn set_cursor(&mut self, cursor: Option<Vec2>) {
+        self.cursor = cursor;
+    }
+
+    pub fn get_hovered(&self) -> Option<EntityId> {
+        self.hovered
+    }
+
+    // The tooltip to draw this frame
+    pub fn get_tooltip(&self) -> Option<&Tooltip> {
+        self.tooltip.as_ref()
+    }
+
+    pub fn hit_test(&self, cursor: Vec2) -> Option<EntityId> {
+        let widget = self
+            .widgets
+            .iter()
+            .filter(|(_, widget)| widget.rect.contains(cursor))
+            // ties go to the lower id so the result does not depend on
+            // hash map order
+            .max_by(|(a_id, a), (b_id, b)| a.layer.cmp(&b.layer).then(b_id.cmp(a_id)))
+            .map(|(entity, _)| *entity);
+        widget.or_else(|| self.picker.as_ref()?.pick(cursor))
+    }
+
+    // `now` is any monotonic timestamp, the engine uses unscaled elapsed time
+    pub fn update(&mut self, now: Duration) -> Vec<(EntityId, HoverEvents)> {
+        let mut events = Vec::new();
+        let hovered = self.cursor.and_then(|cursor| self.hit_test(cursor));
+
+        if hovered != self.hovered {
+            if let Some(tooltip) = self.tooltip.take() {
+                events.push((tooltip.target, HoverEvents::TooltipHidden(tooltip.target)));
+            }
+            if let Some(previous) = self.hovered {
+                events.push((previous, HoverEvents::HoverExited(previous)));
+            }
+            if let Some(current) = hovered {
+                events.push((current, HoverEvents::HoverEntered(current)));
+            }
+            self.hovered = hovered;
+            self.hovered_since = now;
+        }
+
+        // the tooltip text can also go away while it is shown
+        if let Some(tooltip) = self.tooltip.as_ref() {
+            if !self.tooltips.contains_key(&tooltip.target) {
+                let target = tooltip.target;
+                self.tooltip = None;
+                events.push((target, HoverEvents::TooltipHidden(target)));
+            }
+        }
+
+        if let (Some(target), Some(cursor), None) = (self.hovered, self.cursor, &self.tooltip) {
+            let rested = now.saturating_sub(self.hovered_since) >= self.config.tooltip_delay;
+            if let Some(text) = self.tooltips.get(&target).filter(|_| rested) {
+                let tooltip = Tooltip {
+                    target,
+                    text: text.clone(),
+                    position: cursor + self.config.tooltip_offset,
+                };
+                self.tooltip = Some(tooltip.clone());
+                events.push((target, HoverEvents::TooltipShown(tooltip)));
+            }
+        }
+        events
+    }
+}
+
+impl Subsystem for HoverService {
+    fn get_name(&self) -> &str {
+        "HoverService"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        for (target, event) in self.update(ctx.time.get_unscaled_elapsed()) {
+            if let Err(err) = ctx
+                .event_queue
+                .emit(Box::new(TargetedEvent::new(target, event)))
+            {
+                error!("unable to emit hover event {:?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_widgets_over_world() {
+        let crate_entity = EntityId(100);
+        let mut hover = HoverService::default()
+            .with_picker(move |cursor: Vec2| (cursor.x > 50.0).then_some(crate_entity));
+        let panel = EntityId(1);
+        let button = EntityId(2);
+        hover.set_widget(panel, Rect::new(Vec2::ZERO, Vec2::splat(100.0)), 0);
+        hover.set_widget(button, Rect::new(Vec2::splat(10.0), Vec2::splat(20.0)), 1);
+
+        assert_eq!(hover.hit_test(Vec2::splat(15.0)), Some(button));
+        assert_eq!(hover.hit_test(Vec2::splat(60.0)), Some(panel));
+        hover.remove_widget(panel);
+        assert_eq!(hover.hit_test(Vec2::splat(60.0)), Some(crate_entity));
+        assert_eq!(hover.hit_test(Vec2::splat(40.0)), None);
+    }
+
+    #[test]
+    fn test_enter_exit_and_tooltip_delay() {
+        let mut hover = HoverService::default();
+        let button = EntityId(2);
+        hover.set_widget(button, Rect::new(Vec2::ZERO, Vec2::splat(20.0)), 0);
+        hover.set_tooltip(button, Some("Save the game"));
+
+        hover.set_cursor(Some(Vec2::splat(5.0)));
+        assert_eq!(
+            hover.update(ms(0)),
+            vec![(button, HoverEvents::HoverEntered(button))]
+        );
+        // moving inside the widget does not restart the delay
+        hover.set_cursor(Some(Vec2::splat(8.0)));
+        assert!(hover.update(ms(300)).is_empty());
+
+        let shown = hover.update(ms(500));
+        let tooltip = Tooltip {
+            target: button,
+            text: "Save the game".to_string(),
+            position: Vec2::new(20.0, 24.0),
+        };
+        assert_eq!(shown, vec![(button, HoverEvents::TooltipShown(tooltip))]);
+        assert!(hover.get_tooltip().is_some());
+        assert!(hover.update(ms(600)).is_empty());
+
+        hover.set_cursor(None);
+        assert_eq!(
+            hover.update(ms(700)),
+            vec![
+                (button, HoverEvents::TooltipHidden(button)),
+                (button, HoverEvents::HoverExited(button)),
+            ]
+        );
+        assert_eq!(hover.get_hovered(), None);
+    }
+}
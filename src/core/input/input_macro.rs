@@ -0,0 +1,231 @@
+use std::{fs, path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::event_system::{
+    engine_events::input_events::InputEvent,
+    event::{Event, PlayerId},
+};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MacroErrors {
+    #[error("unable to access the macro file: {0}")]
+    Io(String),
+
+    #[error("broken macro file: {0}")]
+    Parse(String),
+}
+
+impl From<std::io::Error> for MacroErrors {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(err.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MacroAction {
+    Started(String),
+    Ended(String),
+    Repeated(String),
+    Axis(String, f32),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MacroStep {
+    // since the recording started, unscaled
+    pub at: Duration,
+    pub action: MacroAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub player: Option<u32>,
+}
+
+impl MacroStep {
+    // Action events only, raw device input is not recorded so a macro keeps
+    // working after the bindings change
+    pub fn from_event(at: Duration, event: &dyn Event) -> Option<Self> {
+        let data = event.get_data()?;
+        let action = match event.get_name().as_str() {
+            "ActionStarted" => MacroAction::Started(data.get_ref::<String>()?.clone()),
+            "ActionEnded" => MacroAction::Ended(data.get_ref::<String>()?.clone()),
+            "ActionRepeated" => MacroAction::Repeated(data.get_ref::<String>()?.clone()),
+            "AxisChanged" => {
+                let (axis, value) = data.get_ref::<(String, f32)>()?;
+                MacroAction::Axis(axis.clone(), *value)
+            }
+            _ => return None,
+        };
+        Some(Self {
+            at,
+            action,
+            player: event.get_player().map(|player| player.0),
+        })
+    }
+
+    pub fn get_event(&self) -> InputEvent {
+        match &self.action {
+            MacroAction::Started(action) => InputEvent::ActionStarted(action.clone()),
+            MacroAction::Ended(action) => InputEvent::ActionEnded(action.clone()),
+            MacroAction::Repeated(action) => InputEvent::ActionRepeated(action.clone()),
+            MacroAction::Axis(axis, value) => InputEvent::AxisChanged(axis.clone(), *value),
+        }
+    }
+}
+
+// Action events with their timing, recorded from a play session and played
+// back to drive menus and gameplay on their own, e.g. a smoke test going
+// main menu -> load level -> quit on every build.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputMacro {
+    steps: Vec<MacroStep>,
+}
+
+impl InputMacro {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Steps are kept sorted by time
+    pub fn push(&mut self, step: MacroStep) {
+        let index = self.steps.partition_point(|other| other.at <= step.at);
+        self.steps.insert(index, step);
+    }
+
+    pub fn get_steps(&self) -> &[MacroStep] {
+        &self.steps
+    }
+
+    pub fn get_duration(&self) -> Duration {
+        self.steps.last().map(|step| step.at).unwrap_or_default()
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), MacroErrors> {
+        let contents = serde_json::to_string_pretty(self)
+            .map_err(|err| MacroErrors::Parse(err.to_string()))?;
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, MacroErrors> {
+        let contents = fs::read_to_string(path)?;
+        let mut input_macro: Self =
+            serde_json::from_str(&contents).map_err(|err| MacroErrors::Parse(err.to_string()))?;
+        // hand edited files may be out of order
+        input_macro.steps.sort_by_key(|step| step.at);
+        Ok(input_macro)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MacroRecorder {
+    started: Duration,
+    recording: InputMacro,
+}
+
+impl MacroRecorder {
+    pub fn new(now: Duration) -> Self {
+        Self {
+            started: now,
+            recording: InputMacro::new(),
+        }
+    }
+
+    pub fn record(&mut self, event: &dyn Event, now: Duration) {
+        let at = now.saturating_sub(self.started);
+        if let Some(step) = MacroStep::from_event(at, event) {
+            self.recording.push(step);
+        }
+    }
+
+    pub fn finish(self) -> InputMacro {
+        self.recording
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MacroPlayer {
+    started: Duration,
+    input_macro: InputMacro,
+    next: usize,
+}
+
+impl MacroPlayer {
+    pub fn new(input_macro: InputMacro, now: Duration) -> Self {
+        Self {
+            started: now,
+            input_macro,
+            next: 0,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.input_macro.steps.len()
+    }
+
+    // The action events that are due, with the player they were for
+    pub fn update(&mut self, now: Duration) -> Vec<(Option<PlayerId>, InputEvent)> {
+        let elapsed = now.saturating_sub(self.started);
+        let mut events = Vec::new();
+        while let Some(step) = self.input_macro.steps.get(self.next) {
+            if step.at > elapsed {
+                break;
+            }
+            events.push((step.player.map(PlayerId), step.get_event()));
+            self.next += 1;
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_system::{
+        engine_events::application_events::ApplicationEvents, event::PlayerEvent,
+    };
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut recorder = MacroRecorder::new(Duration::from_secs(10));
+        let second = Duration::from_secs;
+        recorder.record(
+            &InputEvent::ActionStarted("Confirm".to_string()),
+            second(11),
+        );
+        recorder.record(&ApplicationEvents::Paused, second(11));
+        recorder.record(
+            &PlayerEvent::new(
+                PlayerId(1),
+                InputEvent::AxisChanged("MoveX".to_string(), 0.5),
+            ),
+            second(12),
+        );
+        recorder.record(&InputEvent::ActionEnded("Confirm".to_string()), second(13));
+        let recording = recorder.finish();
+        assert_eq!(recording.get_steps().len(), 3);
+        assert_eq!(recording.get_duration(), second(3));
+
+        let path = std::env::temp_dir().join("aloy_input_macro_test.json");
+        recording.save(&path).unwrap();
+        let loaded = InputMacro::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        assert_eq!(loaded, recording);
+
+        let mut player = MacroPlayer::new(loaded, second(100));
+        assert_eq!(
+            player.update(second(101)),
+            vec![(None, InputEvent::ActionStarted("Confirm".to_string()))]
+        );
+        assert_eq!(
+            player.update(second(104)),
+            vec![
+                (
+                    Some(PlayerId(1)),
+                    InputEvent::AxisChanged("MoveX".to_string(), 0.5)
+                ),
+                (None, InputEvent::ActionEnded("Confirm".to_string()))
+            ]
+        );
+        assert!(player.is_finished());
+    }
+}
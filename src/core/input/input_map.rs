@@ -0,0 +1,598 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+    time::Duration,
+};
+
+use log::warn;
+
+use crate::{
+    core::settings::{SettingValue, Settings, SettingsErrors},
+    event_system::{
+        engine_events::input_events::InputEvent,
+        event::{PlayerEvent, PlayerId},
+    },
+};
+
+use super::{
+    assist::InputAssist,
+    axis::{AxisSettings, ResponseCurve},
+    binding::{format_bindings, parse_bindings, AxisBinding, InputBinding},
+    players::PlayerAssignments,
+    state::Input,
+};
+
+pub const INPUT_SETTINGS_PREFIX: &str = "input.";
+pub const AXIS_SETTINGS_PREFIX: &str = "input.axis.";
+pub const BINDING_SETTINGS_PREFIX: &str = "input.binding.";
+pub const AXIS_BINDING_SETTINGS_PREFIX: &str = "input.axis_binding.";
+
+// The actions and axes of one set of devices
+#[derive(Debug, Default, Clone)]
+struct ActionState {
+    // actions whose bindings are held right now
+    held: HashSet<String>,
+    axis_values: HashMap<String, f32>,
+    assist: InputAssist,
+}
+
+// Logical actions ("Jump") and axes ("MoveX") bound to keys, mouse buttons
+// and gamepads. An action is held while any of its bindings is, an axis
+// follows whichever of its bindings is pushed furthest.
+//
+// In local multiplayer every player has the same bindings on their own
+// devices, see `PlayerAssignments`. Their actions come out of
+// `handle_player_input` tagged with the player.
+#[derive(Debug, Default, Clone)]
+pub struct InputMap {
+    axes: HashMap<String, AxisSettings>,
+    bindings: HashMap<String, Vec<InputBinding>>,
+    axis_bindings: HashMap<String, Vec<AxisBinding>>,
+    // driven by the devices that belong to no player
+    actions: ActionState,
+    players: PlayerAssignments,
+    player_actions: BTreeMap<PlayerId, ActionState>,
+}
+
+impl InputMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_axis_settings(&mut self, axis: &str, settings: AxisSettings) {
+        self.axes.insert(axis.to_string(), settings);
+    }
+
+    pub fn get_axis_settings(&self, axis: &str) -> Option<&AxisSettings> {
+        self.axes.get(axis)
+    }
+
+    // Values below the dead zone read as 0, clamped to [0, 1)
+    pub fn set_dead_zone(&mut self, axis: &str, dead_zone: f32) {
+        self.axes.entry(axis.to_string()).or_default().dead_zone = dead_zone.clamp(0.0, 0.99);
+    }
+
+    pub fn set_sensitivity(&mut self, axis: &str, sensitivity: f32) {
+        self.axes.entry(axis.to_string()).or_default().sensitivity = sensitivity;
+    }
+
+    pub fn set_inverted(&mut self, axis: &str, inverted: bool) {
+        self.axes.entry(axis.to_string()).or_default().inverted = inverted;
+    }
+
+    pub fn set_curve(&mut self, axis: &str, curve: ResponseCurve) {
+        self.axes.entry(axis.to_string()).or_default().curve = curve;
+    }
+
+    pub fn bind(&mut self, action: &str, binding: InputBinding) {
+        let bindings = self.bindings.entry(action.to_string()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    // Player remapping, replaces every binding of the action and returns the
+    // old ones. Persisted through `store_bindings`.
+    pub fn rebind(&mut self, action: &str, bindings: Vec<InputBinding>) -> Vec<InputBinding> {
+        self.bindings
+            .insert(action.to_string(), bindings)
+            .unwrap_or_default()
+    }
+
+    pub fn get_bindings(&self, action: &str) -> &[InputBinding] {
+        self.bindings
+            .get(action)
+            .map(|bindings| bindings.as_slice())
+            .unwrap_or_default()
+    }
+
+    pub fn bind_axis(&mut self, axis: &str, binding: AxisBinding) {
+        let bindings = self.axis_bindings.entry(axis.to_string()).or_default();
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+    }
+
+    pub fn rebind_axis(&mut self, axis: &str, bindings: Vec<AxisBinding>) -> Vec<AxisBinding> {
+        self.axis_bindings
+            .insert(axis.to_string(), bindings)
+            .unwrap_or_default()
+    }
+
+    pub fn get_axis_bindings(&self, axis: &str) -> &[AxisBinding] {
+        self.axis_bindings
+            .get(axis)
+            .map(|bindings| bindings.as_slice())
+            .unwrap_or_default()
+    }
+
+    // The last value sent with AxisChanged, 0 for axes that never moved
+    pub fn get_axis_value(&self, axis: &str) -> f32 {
+        self.actions.axis_values.get(axis).copied().unwrap_or(0.0)
+    }
+
+    pub fn get_player_axis_value(&self, player: PlayerId, axis: &str) -> f32 {
+        self.player_actions
+            .get(&player)
+            .and_then(|actions| actions.axis_values.get(axis).copied())
+            .unwrap_or(0.0)
+    }
+
+    pub fn get_players(&self) -> &PlayerAssignments {
+        &self.players
+    }
+
+    pub fn get_players_mut(&mut self) -> &mut PlayerAssignments {
+        &mut self.players
+    }
+
+    // Writes the bindings as `input.binding.<action>` and
+    // `input.axis_binding.<axis>` so they are saved with the rest of the
+    // settings, along with the axis settings `apply_settings` reads back
+    pub fn store_bindings(&self, settings: &mut Settings) {
+        for (axis, axis_settings) in self.axes.iter() {
+            let key = |field: &str| format!("{}{}.{}", AXIS_SETTINGS_PREFIX, axis, field);
+            settings.set(
+                &key("dead_zone"),
+                SettingValue::Float(axis_settings.dead_zone as f64),
+            );
+            settings.set(
+                &key("sensitivity"),
+                SettingValue::Float(axis_settings.sensitivity as f64),
+            );
+            settings.set(&key("inverted"), SettingValue::Bool(axis_settings.inverted));
+            let exponent = match axis_settings.curve {
+                ResponseCurve::Linear => Some(1.0),
+                ResponseCurve::Exponential(exponent) => Some(exponent),
+                ResponseCurve::Custom(_) => None,
+            };
+            if let Some(exponent) = exponent {
+                settings.set(&key("exponent"), SettingValue::Float(exponent as f64));
+            }
+        }
+        for (action, bindings) in self.bindings.iter() {
+            settings.set(
+                &format!("{}{}", BINDING_SETTINGS_PREFIX, action),
+                SettingValue::Text(format_bindings(bindings)),
+            );
+        }
+        for (axis, bindings) in self.axis_bindings.iter() {
+            settings.set(
+                &format!("{}{}", AXIS_BINDING_SETTINGS_PREFIX, axis),
+                SettingValue::Text(format_bindings(bindings)),
+            );
+        }
+    }
+
+    // A controls file with only the bindings, for games that keep them
+    // apart from the other settings
+    pub fn save_bindings(&self, path: impl AsRef<Path>) -> Result<(), SettingsErrors> {
+        let mut settings = Settings::new();
+        self.store_bindings(&mut settings);
+        settings.save(path)
+    }
+
+    // Bindings in the file replace the ones set for the same action or axis
+    pub fn load_bindings(&mut self, path: impl AsRef<Path>) -> Result<(), SettingsErrors> {
+        let mut settings = Settings::new();
+        settings.load(path)?;
+        self.apply_bindings(&settings);
+        Ok(())
+    }
+
+    fn apply_bindings(&mut self, settings: &Settings) {
+        for key in settings.keys_with_prefix(BINDING_SETTINGS_PREFIX) {
+            let Some(text) = settings.get_text(key) else {
+                continue;
+            };
+            match parse_bindings(text) {
+                Ok(bindings) => {
+                    self.bindings
+                        .insert(key[BINDING_SETTINGS_PREFIX.len()..].to_string(), bindings);
+                }
+                Err(err) => warn!("skipping {}: {}", key, err),
+            }
+        }
+        for key in settings.keys_with_prefix(AXIS_BINDING_SETTINGS_PREFIX) {
+            let Some(text) = settings.get_text(key) else {
+                continue;
+            };
+            match parse_bindings(text) {
+                Ok(bindings) => {
+                    self.axis_bindings.insert(
+                        key[AXIS_BINDING_SETTINGS_PREFIX.len()..].to_string(),
+                        bindings,
+                    );
+                }
+                Err(err) => warn!("skipping {}: {}", key, err),
+            }
+        }
+    }
+
+    // Every player's actions go through the same assistance settings
+    pub fn get_assist(&self) -> &InputAssist {
+        &self.actions.assist
+    }
+
+    pub fn get_assist_mut(&mut self) -> &mut InputAssist {
+        &mut self.actions.assist
+    }
+
+    // Raw presses of an action go through the input assistance before they
+    // become action events
+    pub fn handle_action(&mut self, action: &str, pressed: bool, now: Duration) -> Vec<InputEvent> {
+        self.actions.assist.handle(action, pressed, now)
+    }
+
+    // Turns the polled device state into action presses and axis changes,
+    // called after every input event and once more at the end of the frame
+    // so mouse axes go back to 0 when the mouse stops. Only the devices that
+    // belong to no player count.
+    pub fn handle_input(&mut self, input: &Input, now: Duration) -> Vec<InputEvent> {
+        let input = match self.players.is_empty() {
+            true => Cow::Borrowed(input),
+            false => {
+                Cow::Owned(input.filter_devices(|device| self.players.get_player(device).is_none()))
+            }
+        };
+        let mut actions = std::mem::take(&mut self.actions);
+        let events = self.evaluate(&mut actions, &input, now);
+        self.actions = actions;
+        events
+    }
+
+    // Same as `handle_input` for each player on their own devices. A player
+    // whose devices were taken away releases everything they held.
+    pub fn handle_player_input(
+        &mut self,
+        input: &Input,
+        now: Duration,
+    ) -> Vec<PlayerEvent<InputEvent>> {
+        for player in self.players.get_players() {
+            self.player_actions.entry(player).or_default();
+        }
+        let mut player_actions = std::mem::take(&mut self.player_actions);
+        let mut events = Vec::new();
+        for (player, actions) in player_actions.iter_mut() {
+            if actions.assist.get_settings() != self.actions.assist.get_settings() {
+                actions
+                    .assist
+                    .set_settings(self.actions.assist.get_settings().clone());
+            }
+            let input =
+                input.filter_devices(|device| self.players.get_player(device) == Some(*player));
+            let player_events = self.evaluate(actions, &input, now);
+            events.extend(
+                player_events
+                    .into_iter()
+                    .map(|event| PlayerEvent::new(*player, event)),
+            );
+        }
+        // players that left and let go of everything
+        let players = self.players.get_players();
+        player_actions.retain(|player, actions| {
+            players.contains(player)
+                || !actions.held.is_empty()
+                || actions.axis_values.values().any(|value| *value != 0.0)
+        });
+        self.player_actions = player_actions;
+        events
+    }
+
+    fn evaluate(&self, state: &mut ActionState, input: &Input, now: Duration) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        let mut actions: Vec<&String> = self.bindings.keys().collect();
+        // sorted so actions changing on the same event come out in a stable
+        // order
+        actions.sort();
+        for action in actions {
+            let down = self.bindings[action]
+                .iter()
+                .any(|binding| binding.is_down(input));
+            if down != state.held.contains(action) {
+                match down {
+                    true => state.held.insert(action.clone()),
+                    false => state.held.remove(action),
+                };
+                events.extend(state.assist.handle(action, down, now));
+            }
+        }
+
+        let mut axes: Vec<&String> = self.axis_bindings.keys().collect();
+        axes.sort();
+        for axis in axes {
+            let raw = self.axis_bindings[axis]
+                .iter()
+                .map(|binding| binding.get_value(input))
+                .fold(0.0, |best: f32, value| {
+                    if value.abs() > best.abs() {
+                        value
+                    } else {
+                        best
+                    }
+                });
+            let value = self.apply_axis(axis, raw);
+            if value != state.axis_values.get(axis).copied().unwrap_or(0.0) {
+                state.axis_values.insert(axis.clone(), value);
+                events.push(InputEvent::AxisChanged(axis.clone(), value));
+            }
+        }
+        events
+    }
+
+    pub fn update(&mut self, now: Duration) -> Vec<InputEvent> {
+        self.actions.assist.update(now)
+    }
+
+    pub fn update_players(&mut self, now: Duration) -> Vec<PlayerEvent<InputEvent>> {
+        let mut events = Vec::new();
+        for (player, actions) in self.player_actions.iter_mut() {
+            events.extend(
+                actions
+                    .assist
+                    .update(now)
+                    .into_iter()
+                    .map(|event| PlayerEvent::new(*player, event)),
+            );
+        }
+        events
+    }
+
+    // Every raw axis value has to go through here before it reaches gameplay
+    pub fn apply_axis(&self, axis: &str, raw: f32) -> f32 {
+        match self.axes.get(axis) {
+            Some(settings) => settings.apply(raw),
+            None => raw,
+        }
+    }
+
+    // Picks up `input.axis.<axis>.{dead_zone,sensitivity,inverted,exponent}`,
+    // `input.binding.<action>`, `input.axis_binding.<axis>` and the
+    // `input.assist.*` options from the settings service. Custom curves can
+    // only be set from code and are kept as they are.
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.apply_bindings(settings);
+        self.actions.assist.apply_settings(settings);
+
+        let axes: Vec<String> = settings
+            .keys_with_prefix(AXIS_SETTINGS_PREFIX)
+            .filter_map(|key| key[AXIS_SETTINGS_PREFIX.len()..].split('.').next())
+            .map(|axis| axis.to_string())
+            .collect();
+
+        for axis in axes {
+            let key = |field: &str| format!("{}{}.{}", AXIS_SETTINGS_PREFIX, axis, field);
+            let axis_settings = self.axes.entry(axis.clone()).or_default();
+
+            if let Some(dead_zone) = settings.get_float(&key("dead_zone")) {
+                axis_settings.dead_zone = dead_zone.clamp(0.0, 1.0) as f32;
+            }
+            if let Some(sensitivity) = settings.get_float(&key("sensitivity")) {
+                axis_settings.sensitivity = sensitivity as f32;
+            }
+            if let Some(inverted) = settings.get_bool(&key("inverted")) {
+                axis_settings.inverted = inverted;
+            }
+            if let Some(exponent) = settings.get_float(&key("exponent")) {
+                axis_settings.curve = if exponent == 1.0 {
+                    ResponseCurve::Linear
+                } else {
+                    ResponseCurve::Exponential(exponent as f32)
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        core::{key_code::KeyCode, settings::SettingValue},
+        event_system::{
+            engine_events::{
+                gamepad_events::{GamepadAxis, GamepadButton, GamepadEvents, GamepadId},
+                keyboard_events::KeyboardEvent,
+                mouse_events::MouseEvents,
+            },
+            event::Event,
+        },
+        math::vector::Vec2,
+    };
+
+    use super::{
+        super::{binding::MouseAxis, players::InputDevice},
+        *,
+    };
+
+    #[test]
+    fn test_unknown_axis_passes_through() {
+        let map = InputMap::new();
+        assert_eq!(map.apply_axis("MoveX", 0.05), 0.05);
+    }
+
+    #[test]
+    fn test_apply_settings() {
+        let mut settings = Settings::new();
+        settings.set("input.axis.MoveX.dead_zone", SettingValue::Float(0.5));
+        settings.set("input.axis.MoveX.inverted", SettingValue::Bool(true));
+        settings.set("input.axis.LookY.exponent", SettingValue::Int(2));
+
+        let mut map = InputMap::new();
+        map.apply_settings(&settings);
+
+        assert_eq!(map.apply_axis("MoveX", 0.4), 0.0);
+        assert_eq!(map.apply_axis("MoveX", 1.0), -1.0);
+        assert_eq!(map.apply_axis("LookY", 0.5), 0.25);
+    }
+
+    #[test]
+    fn test_mouse_axis_settings_persist() {
+        let mut map = InputMap::new();
+        map.bind_axis("LookY", AxisBinding::Mouse(MouseAxis::MotionY));
+        map.set_sensitivity("LookY", 0.5);
+        map.set_inverted("LookY", true);
+
+        let mut input = Input::new();
+        input.handle_event(&MouseEvents::RawMotion(Vec2::new(0.0, 8.0)));
+        assert_eq!(
+            map.handle_input(&input, Duration::ZERO),
+            vec![InputEvent::AxisChanged("LookY".to_string(), -4.0)]
+        );
+        input.begin_frame();
+        assert_eq!(
+            map.handle_input(&input, Duration::ZERO),
+            vec![InputEvent::AxisChanged("LookY".to_string(), 0.0)]
+        );
+
+        let mut settings = Settings::new();
+        map.store_bindings(&mut settings);
+        let mut restored = InputMap::new();
+        restored.apply_settings(&settings);
+        assert_eq!(restored.apply_axis("LookY", 2.0), -1.0);
+    }
+
+    #[test]
+    fn test_bindings_round_trip_through_settings() {
+        let mut map = InputMap::new();
+        map.rebind("Jump", vec![InputBinding::Key(KeyCode::Space)]);
+        map.bind("Jump", InputBinding::Gamepad(GamepadButton::South));
+        map.bind_axis("MoveX", AxisBinding::Gamepad(GamepadAxis::LeftStickX));
+        let mut settings = Settings::new();
+        map.store_bindings(&mut settings);
+        assert_eq!(
+            settings.get_text("input.binding.Jump"),
+            Some("Key.Space, Gamepad.South")
+        );
+
+        let mut restored = InputMap::new();
+        restored.apply_settings(&settings);
+        assert_eq!(restored.get_bindings("Jump"), map.get_bindings("Jump"));
+        assert_eq!(
+            restored.get_axis_bindings("MoveX"),
+            &[AxisBinding::Gamepad(GamepadAxis::LeftStickX)]
+        );
+    }
+
+    #[test]
+    fn test_player_devices_drive_player_actions() {
+        let mut map = InputMap::new();
+        map.bind("Jump", InputBinding::Gamepad(GamepadButton::South));
+        map.bind("Jump", InputBinding::Key(KeyCode::Space));
+        let (first, second) = (PlayerId(0), PlayerId(1));
+        let players = map.get_players_mut();
+        players.assign(InputDevice::Gamepad(GamepadId(3)), second);
+        players.assign(InputDevice::KeyboardMouse, first);
+
+        let mut input = Input::new();
+        input.handle_event(&GamepadEvents::ButtonPressed(
+            GamepadId(3),
+            GamepadButton::South,
+        ));
+        assert!(map.handle_input(&input, Duration::ZERO).is_empty());
+        assert_eq!(
+            map.handle_player_input(&input, Duration::ZERO),
+            vec![PlayerEvent::new(
+                second,
+                InputEvent::ActionStarted("Jump".to_string())
+            )]
+        );
+
+        // a pad nobody joined with plays the actions without a player
+        input.handle_event(&GamepadEvents::ButtonPressed(
+            GamepadId(4),
+            GamepadButton::South,
+        ));
+        assert_eq!(
+            map.handle_input(&input, Duration::ZERO),
+            vec![InputEvent::ActionStarted("Jump".to_string())]
+        );
+
+        map.get_players_mut().remove_player(second);
+        assert_eq!(
+            map.handle_player_input(&input, Duration::ZERO),
+            vec![PlayerEvent::new(
+                second,
+                InputEvent::ActionEnded("Jump".to_string())
+            )]
+        );
+        assert!(map.handle_player_input(&input, Duration::ZERO).is_empty());
+    }
+
+    #[test]
+    fn test_bound_inputs_emit_actions_and_axes() {
+        let mut map = InputMap::new();
+        map.bind("Jump", InputBinding::Key(KeyCode::Space));
+        map.bind("Jump", InputBinding::Gamepad(GamepadButton::South));
+        map.bind_axis(
+            "MoveX",
+            AxisBinding::buttons(InputBinding::Key(KeyCode::A), InputBinding::Key(KeyCode::D)),
+        );
+        map.bind_axis("MoveX", AxisBinding::Gamepad(GamepadAxis::LeftStickX));
+
+        let mut input = Input::new();
+        let mut feed = |map: &mut InputMap, event: &dyn Event| {
+            input.handle_event(event);
+            map.handle_input(&input, Duration::ZERO)
+        };
+        let space = KeyboardEvent::KeyPressed {
+            key: KeyCode::Space,
+            repeat: false,
+        };
+        assert_eq!(
+            feed(&mut map, &space),
+            vec![InputEvent::ActionStarted("Jump".to_string())]
+        );
+        // still held through the other binding
+        let pad = GamepadId(0);
+        assert!(feed(
+            &mut map,
+            &GamepadEvents::ButtonPressed(pad, GamepadButton::South)
+        )
+        .is_empty());
+        assert!(feed(&mut map, &KeyboardEvent::KeyReleased(KeyCode::Space)).is_empty());
+        assert_eq!(
+            feed(
+                &mut map,
+                &GamepadEvents::ButtonReleased(pad, GamepadButton::South)
+            ),
+            vec![InputEvent::ActionEnded("Jump".to_string())]
+        );
+
+        let d = KeyboardEvent::KeyPressed {
+            key: KeyCode::D,
+            repeat: false,
+        };
+        assert_eq!(
+            feed(&mut map, &d),
+            vec![InputEvent::AxisChanged("MoveX".to_string(), 1.0)]
+        );
+        // the stick pushed less than the key does not win
+        let stick = GamepadEvents::AxisMoved(pad, GamepadAxis::LeftStickX, -0.5);
+        assert!(feed(&mut map, &stick).is_empty());
+        assert_eq!(
+            feed(&mut map, &KeyboardEvent::KeyReleased(KeyCode::D)),
+            vec![InputEvent::AxisChanged("MoveX".to_string(), -0.5)]
+        );
+    }
+}
@@ -0,0 +1,11 @@
+pub mod assist;
+pub mod axis;
+pub mod binding;
+pub mod gamepad;
+pub mod gestures;
+pub mod hover;
+pub mod input_macro;
+pub mod input_map;
+pub mod players;
+pub mod shortcuts;
+pub mod state;
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+
+use crate::event_system::{
+    engine_events::gamepad_events::{GamepadAxis, GamepadButton, GamepadId},
+    event::{Event, PlayerId},
+};
+
+// What a local player plays with. Keyboard and mouse go together, nobody
+// shares a keyboard with the one on the mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum InputDevice {
+    KeyboardMouse,
+    Gamepad(GamepadId),
+}
+
+impl InputDevice {
+    // The device a raw key, mouse or gamepad event came from
+    pub fn from_event(event: &dyn Event) -> Option<Self> {
+        let name = event.get_name();
+        if name.starts_with("Gamepad") {
+            let data = event.get_data()?;
+            let gamepad = data
+                .get_ref::<GamepadId>()
+                .or_else(|| {
+                    data.get_ref::<(GamepadId, GamepadButton)>()
+                        .map(|(id, _)| id)
+                })
+                .or_else(|| {
+                    data.get_ref::<(GamepadId, GamepadAxis, f32)>()
+                        .map(|(id, ..)| id)
+                })?;
+            return Some(Self::Gamepad(*gamepad));
+        }
+        (name.starts_with("Key") || name.starts_with("Mouse")).then_some(Self::KeyboardMouse)
+    }
+}
+
+// Which device belongs to which local player. Devices nobody was given to
+// drive the actions without a player, with an empty table that is every
+// device, as in a single player game. `join` is the "press start to join"
+// screen: the pad that pressed start becomes the next player.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerAssignments {
+    devices: BTreeMap<InputDevice, PlayerId>,
+}
+
+impl PlayerAssignments {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.devices.is_empty()
+    }
+
+    // A device belongs to one player, assigning it again moves it
+    pub fn assign(&mut self, device: InputDevice, player: PlayerId) -> Option<PlayerId> {
+        self.devices.insert(device, player)
+    }
+
+    pub fn unassign(&mut self, device: InputDevice) -> Option<PlayerId> {
+        self.devices.remove(&device)
+    }
+
+    // Frees every device of the player, e.g. when they leave the game
+    pub fn remove_player(&mut self, player: PlayerId) {
+        self.devices.retain(|_, owner| *owner != player);
+    }
+
+    // Gives the device to the lowest player without a device, or returns
+    // the player it already belongs to
+    pub fn join(&mut self, device: InputDevice) -> PlayerId {
+        if let Some(player) = self.get_player(device) {
+            return player;
+        }
+        let players = self.get_players();
+        let player = (0..)
+            .map(PlayerId)
+            .find(|player| !players.contains(player))
+            .unwrap_or(PlayerId(0));
+        self.devices.insert(device, player);
+        player
+    }
+
+    pub fn get_player(&self, device: InputDevice) -> Option<PlayerId> {
+        self.devices.get(&device).copied()
+    }
+
+    pub fn get_devices(&self, player: PlayerId) -> Vec<InputDevice> {
+        self.devices
+            .iter()
+            .filter(|(_, owner)| **owner == player)
+            .map(|(device, _)| *device)
+            .collect()
+    }
+
+    // Players with at least one device, sorted
+    pub fn get_players(&self) -> Vec<PlayerId> {
+        let mut players: Vec<PlayerId> = self.devices.values().copied().collect();
+        players.sort();
+        players.dedup();
+        players
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_system::engine_events::{
+        gamepad_events::GamepadEvents, keyboard_events::KeyboardEvent,
+    };
+
+    #[test]
+    fn test_join_takes_the_next_free_player() {
+        let mut players = PlayerAssignments::new();
+        let pad = |id| InputDevice::Gamepad(GamepadId(id));
+        assert_eq!(players.join(pad(7)), PlayerId(0));
+        assert_eq!(players.join(pad(2)), PlayerId(1));
+        assert_eq!(players.join(pad(7)), PlayerId(0));
+
+        players.remove_player(PlayerId(0));
+        assert_eq!(players.join(InputDevice::KeyboardMouse), PlayerId(0));
+        assert_eq!(players.get_players(), vec![PlayerId(0), PlayerId(1)]);
+
+        let moved = GamepadEvents::AxisMoved(GamepadId(2), GamepadAxis::LeftStickY, 1.0);
+        assert_eq!(InputDevice::from_event(&moved), Some(pad(2)));
+        let key = KeyboardEvent::KeyReleased(crate::core::key_code::KeyCode::A);
+        assert_eq!(
+            InputDevice::from_event(&key),
+            Some(InputDevice::KeyboardMouse)
+        );
+    }
+}
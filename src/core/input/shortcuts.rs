@@ -0,0 +1,370 @@
+use std::{fmt::Display, str::FromStr, time::Duration};
+
+use thiserror::Error;
+
+use crate::{
+    core::key_code::KeyCode, event_system::engine_events::shortcut_events::ShortcutEvents,
+};
+
+use super::state::Input;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ShortcutErrors {
+    #[error("invalid shortcut {0}")]
+    Invalid(String),
+
+    #[error("shortcut {0} is already registered")]
+    AlreadyRegistered(String),
+
+    #[error("{0} is already used by shortcut {1}")]
+    Conflict(String, String),
+}
+
+// Left and right modifier keys count the same
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub shift: bool,
+    pub alt: bool,
+    // the windows or command key
+    pub logo: bool,
+}
+
+impl Modifiers {
+    pub const NONE: Self = Self {
+        ctrl: false,
+        shift: false,
+        alt: false,
+        logo: false,
+    };
+
+    pub fn from_input(input: &Input) -> Self {
+        let down = |left, right| input.is_key_down(left) || input.is_key_down(right);
+        Self {
+            ctrl: down(KeyCode::LeftControl, KeyCode::RightControl),
+            shift: down(KeyCode::LeftShift, KeyCode::RightShift),
+            alt: down(KeyCode::LeftAlt, KeyCode::RightAlt),
+            logo: down(KeyCode::LeftSuper, KeyCode::RightSuper),
+        }
+    }
+
+    pub fn is_modifier_key(key: KeyCode) -> bool {
+        matches!(
+            key,
+            KeyCode::LeftControl
+                | KeyCode::RightControl
+                | KeyCode::LeftShift
+                | KeyCode::RightShift
+                | KeyCode::LeftAlt
+                | KeyCode::RightAlt
+                | KeyCode::LeftSuper
+                | KeyCode::RightSuper
+        )
+    }
+}
+
+// One key with the modifiers that have to be held, written `Ctrl+S` or
+// `Shift+Alt+F1`. The modifiers have to match exactly, Ctrl+Shift+S does not
+// trigger Ctrl+S.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyCombo {
+    pub modifiers: Modifiers,
+    pub key: KeyCode,
+}
+
+impl KeyCombo {
+    pub fn new(modifiers: Modifiers, key: KeyCode) -> Self {
+        Self { modifiers, key }
+    }
+}
+
+impl Display for KeyCombo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let modifiers = [
+            (self.modifiers.ctrl, "Ctrl+"),
+            (self.modifiers.shift, "Shift+"),
+            (self.modifiers.alt, "Alt+"),
+            (self.modifiers.logo, "Super+"),
+        ];
+        for (held, name) in modifiers {
+            if held {
+                write!(f, "{}", name)?;
+            }
+        }
+        // digits read better without their variant prefix
+        let key = self.key.get_name();
+        match key.strip_prefix('D').filter(|digit| digit.len() == 1) {
+            Some(digit) => write!(f, "{}", digit),
+            None => write!(f, "{}", key),
+        }
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = ShortcutErrors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ShortcutErrors::Invalid(s.to_string());
+        let mut parts: Vec<&str> = s.split('+').map(|part| part.trim()).collect();
+        let key = parts.pop().ok_or_else(invalid)?;
+
+        let mut modifiers = Modifiers::NONE;
+        for part in parts {
+            match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" | "option" => modifiers.alt = true,
+                "super" | "cmd" | "win" | "logo" => modifiers.logo = true,
+                _ => return Err(invalid()),
+            }
+        }
+
+        let key = match key.len() {
+            // single letters and digits, case does not matter
+            1 if key.chars().all(|c| c.is_ascii_digit()) => {
+                KeyCode::from_name(&format!("D{}", key))
+            }
+            1 => KeyCode::from_name(&key.to_ascii_uppercase()),
+            _ => KeyCode::from_name(key),
+        };
+        key.filter(|key| !Modifiers::is_modifier_key(*key))
+            .map(|key| Self::new(modifiers, key))
+            .ok_or_else(invalid)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shortcut {
+    pub name: String,
+    // one combo, or several typed one after another for a chord like
+    // `Ctrl+K Ctrl+C`
+    pub sequence: Vec<KeyCombo>,
+}
+
+fn format_sequence(sequence: &[KeyCombo]) -> String {
+    sequence
+        .iter()
+        .map(|combo| combo.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// Matches key presses against registered shortcuts and chords. Feed it the
+// presses with the modifiers held at the time, `update` ends chords whose
+// next key did not come in time.
+#[derive(Debug, Clone)]
+pub struct ShortcutRegistry {
+    shortcuts: Vec<Shortcut>,
+    chord_timeout: Duration,
+    pending: Vec<KeyCombo>,
+    last_press: Duration,
+}
+
+impl Default for ShortcutRegistry {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(1000))
+    }
+}
+
+impl ShortcutRegistry {
+    pub fn new(chord_timeout: Duration) -> Self {
+        Self {
+            shortcuts: Vec::new(),
+            chord_timeout,
+            pending: Vec::new(),
+            last_press: Duration::ZERO,
+        }
+    }
+
+    pub fn set_chord_timeout(&mut self, chord_timeout: Duration) {
+        self.chord_timeout = chord_timeout;
+    }
+
+    pub fn get_chord_timeout(&self) -> Duration {
+        self.chord_timeout
+    }
+
+    // `keys` is a combo or a chord of combos separated by spaces, e.g.
+    // `Ctrl+S` or `Ctrl+K Ctrl+C`. A chord can not start with a key that is
+    // a shortcut of its own, it could never be finished.
+    pub fn register(&mut self, name: &str, keys: &str) -> Result<(), ShortcutErrors> {
+        if self.shortcuts.iter().any(|shortcut| shortcut.name == name) {
+            return Err(ShortcutErrors::AlreadyRegistered(name.to_string()));
+        }
+        let sequence = keys
+            .split_whitespace()
+            .map(|combo| combo.parse())
+            .collect::<Result<Vec<KeyCombo>, ShortcutErrors>>()?;
+        if sequence.is_empty() {
+            return Err(ShortcutErrors::Invalid(keys.to_string()));
+        }
+
+        let overlapping = self.shortcuts.iter().find(|shortcut| {
+            let shared = shortcut.sequence.len().min(sequence.len());
+            shortcut.sequence[..shared] == sequence[..shared]
+        });
+        if let Some(shortcut) = overlapping {
+            return Err(ShortcutErrors::Conflict(
+                keys.to_string(),
+                shortcut.name.clone(),
+            ));
+        }
+        self.shortcuts.push(Shortcut {
+            name: name.to_string(),
+            sequence,
+        });
+        Ok(())
+    }
+
+    pub fn unregister(&mut self, name: &str) -> bool {
+        let count = self.shortcuts.len();
+        self.shortcuts.retain(|shortcut| shortcut.name != name);
+        self.pending.clear();
+        count != self.shortcuts.len()
+    }
+
+    pub fn get_shortcut(&self, name: &str) -> Option<&Shortcut> {
+        self.shortcuts.iter().find(|shortcut| shortcut.name == name)
+    }
+
+    pub fn get_shortcuts(&self) -> &[Shortcut] {
+        &self.shortcuts
+    }
+
+    // Presses of modifier keys and OS key repeats should not be passed in
+    pub fn handle_key(&mut self, combo: KeyCombo, now: Duration) -> Vec<ShortcutEvents> {
+        let mut events = self.update(now);
+        let was_pending = !self.pending.is_empty();
+        self.last_press = now;
+
+        self.pending.push(combo);
+        if let Some(event) = self.match_pending() {
+            events.push(event);
+            return events;
+        }
+        // the key did not continue the chord, it may start something new
+        if was_pending {
+            self.pending = vec![combo];
+            events.push(ShortcutEvents::ChordCancelled);
+            if let Some(event) = self.match_pending() {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    fn match_pending(&mut self) -> Option<ShortcutEvents> {
+        let pending = &self.pending;
+        if let Some(shortcut) = self
+            .shortcuts
+            .iter()
+            .find(|shortcut| shortcut.sequence == *pending)
+        {
+            let name = shortcut.name.clone();
+            self.pending.clear();
+            return Some(ShortcutEvents::ShortcutTriggered(name));
+        }
+        let is_prefix = self
+            .shortcuts
+            .iter()
+            .any(|shortcut| shortcut.sequence.starts_with(pending));
+        if is_prefix {
+            return Some(ShortcutEvents::ChordPending(format_sequence(pending)));
+        }
+        self.pending.clear();
+        None
+    }
+
+    // Feeds a key press with the modifiers taken from the polled state, the
+    // press must not be in `input` yet so repeats can be told apart
+    pub fn handle_key_press(
+        &mut self,
+        key: KeyCode,
+        input: &Input,
+        now: Duration,
+    ) -> Vec<ShortcutEvents> {
+        if Modifiers::is_modifier_key(key) || input.is_key_down(key) {
+            return self.update(now);
+        }
+        self.handle_key(KeyCombo::new(Modifiers::from_input(input), key), now)
+    }
+
+    pub fn update(&mut self, now: Duration) -> Vec<ShortcutEvents> {
+        if !self.pending.is_empty() && now.saturating_sub(self.last_press) > self.chord_timeout {
+            self.pending.clear();
+            return vec![ShortcutEvents::ChordCancelled];
+        }
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combo(keys: &str) -> KeyCombo {
+        keys.parse().unwrap()
+    }
+
+    fn triggered(name: &str) -> ShortcutEvents {
+        ShortcutEvents::ShortcutTriggered(name.to_string())
+    }
+
+    #[test]
+    fn test_parse_combos() {
+        let save = combo("Ctrl+S");
+        assert!(save.modifiers.ctrl && !save.modifiers.shift);
+        assert_eq!(save.key, KeyCode::S);
+        assert_eq!(combo("shift+alt+F1").to_string(), "Shift+Alt+F1");
+        assert_eq!(combo("Ctrl+1").to_string(), "Ctrl+1");
+        assert!("Ctrl+Shift".parse::<KeyCombo>().is_err());
+        assert!("Hyper+S".parse::<KeyCombo>().is_err());
+    }
+
+    #[test]
+    fn test_combos_and_chords() {
+        let mut shortcuts = ShortcutRegistry::new(Duration::from_millis(500));
+        shortcuts.register("Save", "Ctrl+S").unwrap();
+        shortcuts.register("Comment", "Ctrl+K Ctrl+C").unwrap();
+        shortcuts.register("Uncomment", "Ctrl+K Ctrl+U").unwrap();
+        assert_eq!(
+            shortcuts.register("Kill", "Ctrl+K"),
+            Err(ShortcutErrors::Conflict(
+                "Ctrl+K".to_string(),
+                "Comment".to_string()
+            ))
+        );
+
+        let ms = Duration::from_millis;
+        assert_eq!(
+            shortcuts.handle_key(combo("Ctrl+S"), ms(0)),
+            vec![triggered("Save")]
+        );
+        // modifiers match exactly
+        assert!(shortcuts
+            .handle_key(combo("Ctrl+Shift+S"), ms(10))
+            .is_empty());
+
+        assert_eq!(
+            shortcuts.handle_key(combo("Ctrl+K"), ms(100)),
+            vec![ShortcutEvents::ChordPending("Ctrl+K".to_string())]
+        );
+        assert_eq!(
+            shortcuts.handle_key(combo("Ctrl+U"), ms(400)),
+            vec![triggered("Uncomment")]
+        );
+
+        // too slow, the second key is a fresh start
+        shortcuts.handle_key(combo("Ctrl+K"), ms(1000));
+        assert_eq!(
+            shortcuts.handle_key(combo("Ctrl+S"), ms(1600)),
+            vec![ShortcutEvents::ChordCancelled, triggered("Save")]
+        );
+        // a key that continues nothing cancels and may trigger on its own
+        shortcuts.handle_key(combo("Ctrl+K"), ms(2000));
+        assert_eq!(
+            shortcuts.handle_key(combo("Ctrl+S"), ms(2100)),
+            vec![ShortcutEvents::ChordCancelled, triggered("Save")]
+        );
+    }
+}
@@ -0,0 +1,341 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    core::{key_code::KeyCode, window::dpi::CursorPosition},
+    event_system::{
+        engine_events::{
+            gamepad_events::{GamepadAxis, GamepadButton, GamepadId},
+            mouse_events::MouseButton,
+        },
+        event::Event,
+    },
+    math::vector::Vec2,
+};
+
+use super::players::InputDevice;
+
+// Held buttons of one kind plus the ones that went down or up this frame
+#[derive(Debug, Clone)]
+pub struct ButtonState<T> {
+    down: HashSet<T>,
+    pressed: HashSet<T>,
+    released: HashSet<T>,
+}
+
+impl<T> Default for ButtonState<T> {
+    fn default() -> Self {
+        Self {
+            down: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+        }
+    }
+}
+
+impl<T: Copy + Eq + std::hash::Hash> ButtonState<T> {
+    // OS key repeats arrive as presses of a held key, they are no new edge
+    pub fn press(&mut self, button: T) {
+        if self.down.insert(button) {
+            self.pressed.insert(button);
+        }
+    }
+
+    pub fn release(&mut self, button: T) {
+        if self.down.remove(&button) {
+            self.released.insert(button);
+        }
+    }
+
+    pub fn is_down(&self, button: T) -> bool {
+        self.down.contains(&button)
+    }
+
+    // A press and release within one frame still counts as both
+    pub fn was_pressed(&self, button: T) -> bool {
+        self.pressed.contains(&button)
+    }
+
+    pub fn was_released(&self, button: T) -> bool {
+        self.released.contains(&button)
+    }
+
+    pub fn get_down(&self) -> impl Iterator<Item = &T> {
+        self.down.iter()
+    }
+
+    fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+
+    fn release_all(&mut self) {
+        self.released.extend(self.down.drain());
+    }
+}
+
+// Keyboard, mouse and gamepad state for gameplay code that polls instead of
+// handling events. The engine feeds it every dispatched event and starts a
+// new frame before the frame's events, so the edges are valid for the whole
+// frame's update.
+#[derive(Debug, Clone, Default)]
+pub struct Input {
+    keys: ButtonState<KeyCode>,
+    mouse_buttons: ButtonState<MouseButton>,
+    // logical pixels of the window the cursor was last seen in
+    mouse_position: Option<Vec2>,
+    cursor_delta: Vec2,
+    raw_delta: Vec2,
+    scroll: Vec2,
+    gamepad_buttons: HashMap<GamepadId, ButtonState<GamepadButton>>,
+    gamepad_axes: HashMap<(GamepadId, GamepadAxis), f32>,
+}
+
+impl Input {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Clears the per frame edges and deltas
+    pub fn begin_frame(&mut self) {
+        self.keys.end_frame();
+        self.mouse_buttons.end_frame();
+        self.gamepad_buttons
+            .values_mut()
+            .for_each(ButtonState::end_frame);
+        self.cursor_delta = Vec2::ZERO;
+        self.raw_delta = Vec2::ZERO;
+        self.scroll = Vec2::ZERO;
+    }
+
+    pub fn handle_event(&mut self, event: &dyn Event) {
+        let Some(data) = event.get_data() else {
+            return;
+        };
+        match event.get_name().as_str() {
+            "KeyPressed" => data.get_ref::<KeyCode>().map(|key| self.keys.press(*key)),
+            "KeyReleased" => data.get_ref::<KeyCode>().map(|key| self.keys.release(*key)),
+            "MouseButtonPressed" => data
+                .get_ref::<MouseButton>()
+                .map(|button| self.mouse_buttons.press(*button)),
+            "MouseButtonReleased" => data
+                .get_ref::<MouseButton>()
+                .map(|button| self.mouse_buttons.release(*button)),
+            "MouseMoved" => data.get_ref::<CursorPosition>().map(|cursor| {
+                let position = cursor.get_logical();
+                if let Some(previous) = self.mouse_position {
+                    self.cursor_delta += position - previous;
+                }
+                self.mouse_position = Some(position);
+            }),
+            "MouseRawMotion" => data.get_ref::<Vec2>().map(|delta| self.raw_delta += *delta),
+            "MouseScrolled" => data.get_ref::<Vec2>().map(|delta| self.scroll += *delta),
+            "GamepadConnected" => data.get_ref::<GamepadId>().map(|gamepad| {
+                self.gamepad_buttons.entry(*gamepad).or_default();
+            }),
+            "GamepadDisconnected" => data.get_ref::<GamepadId>().map(|gamepad| {
+                self.gamepad_buttons.remove(gamepad);
+                self.gamepad_axes.retain(|(id, _), _| id != gamepad);
+            }),
+            "GamepadButtonPressed" => {
+                data.get_ref::<(GamepadId, GamepadButton)>()
+                    .map(|(gamepad, button)| {
+                        self.gamepad_buttons
+                            .entry(*gamepad)
+                            .or_default()
+                            .press(*button)
+                    })
+            }
+            "GamepadButtonReleased" => {
+                data.get_ref::<(GamepadId, GamepadButton)>()
+                    .map(|(gamepad, button)| {
+                        self.gamepad_buttons
+                            .entry(*gamepad)
+                            .or_default()
+                            .release(*button)
+                    })
+            }
+            "GamepadAxisMoved" => {
+                data.get_ref::<(GamepadId, GamepadAxis, f32)>()
+                    .map(|(gamepad, axis, value)| {
+                        self.gamepad_axes.insert((*gamepad, *axis), *value);
+                    })
+            }
+            // the releases of anything held while focus is away never arrive
+            "FocusChanged" => data
+                .get_ref::<bool>()
+                .filter(|focused| !**focused)
+                .map(|_| self.release_all()),
+            _ => None,
+        };
+    }
+
+    // The state of some devices only, what one local player sees
+    pub fn filter_devices(&self, keep: impl Fn(InputDevice) -> bool) -> Input {
+        let mut input = self.clone();
+        if !keep(InputDevice::KeyboardMouse) {
+            input.keys = ButtonState::default();
+            input.mouse_buttons = ButtonState::default();
+            input.mouse_position = None;
+            input.cursor_delta = Vec2::ZERO;
+            input.raw_delta = Vec2::ZERO;
+            input.scroll = Vec2::ZERO;
+        }
+        input
+            .gamepad_buttons
+            .retain(|gamepad, _| keep(InputDevice::Gamepad(*gamepad)));
+        input
+            .gamepad_axes
+            .retain(|(gamepad, _), _| keep(InputDevice::Gamepad(*gamepad)));
+        input
+    }
+
+    pub fn release_all(&mut self) {
+        self.keys.release_all();
+        self.mouse_buttons.release_all();
+        self.gamepad_buttons
+            .values_mut()
+            .for_each(ButtonState::release_all);
+        self.gamepad_axes.clear();
+    }
+
+    pub fn is_key_down(&self, key: KeyCode) -> bool {
+        self.keys.is_down(key)
+    }
+
+    pub fn was_key_pressed_this_frame(&self, key: KeyCode) -> bool {
+        self.keys.was_pressed(key)
+    }
+
+    pub fn was_key_released_this_frame(&self, key: KeyCode) -> bool {
+        self.keys.was_released(key)
+    }
+
+    pub fn get_keys(&self) -> &ButtonState<KeyCode> {
+        &self.keys
+    }
+
+    pub fn get_mouse_buttons(&self) -> &ButtonState<MouseButton> {
+        &self.mouse_buttons
+    }
+
+    pub fn is_mouse_button_down(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.is_down(button)
+    }
+
+    // None until the cursor moved over a window
+    pub fn mouse_position(&self) -> Option<Vec2> {
+        self.mouse_position
+    }
+
+    // How far the mouse moved this frame. The unaccelerated device motion
+    // when there is any, so it keeps working while the cursor is locked.
+    pub fn mouse_delta(&self) -> Vec2 {
+        if self.raw_delta != Vec2::ZERO {
+            self.raw_delta
+        } else {
+            self.cursor_delta
+        }
+    }
+
+    pub fn scroll_delta(&self) -> Vec2 {
+        self.scroll
+    }
+
+    pub fn get_gamepads(&self) -> impl Iterator<Item = &GamepadId> {
+        self.gamepad_buttons.keys()
+    }
+
+    pub fn get_gamepad_buttons(&self, gamepad: GamepadId) -> Option<&ButtonState<GamepadButton>> {
+        self.gamepad_buttons.get(&gamepad)
+    }
+
+    pub fn gamepad_axis(&self, gamepad: GamepadId, axis: GamepadAxis) -> f32 {
+        self.gamepad_axes
+            .get(&(gamepad, axis))
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    // The axis over all gamepads, whichever is pushed furthest, for single
+    // player games that do not care which pad is used
+    pub fn axis(&self, axis: GamepadAxis) -> f32 {
+        self.gamepad_axes
+            .iter()
+            .filter(|((_, a), _)| *a == axis)
+            .map(|(_, value)| *value)
+            .fold(0.0, |best, value| {
+                if value.abs() > f32::abs(best) {
+                    value
+                } else {
+                    best
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_system::engine_events::{
+        gamepad_events::GamepadEvents, keyboard_events::KeyboardEvent, mouse_events::MouseEvents,
+    };
+
+    #[test]
+    fn test_key_edges() {
+        let mut input = Input::new();
+        input.handle_event(&KeyboardEvent::KeyPressed {
+            key: KeyCode::Space,
+            repeat: false,
+        });
+        assert!(input.is_key_down(KeyCode::Space));
+        assert!(input.was_key_pressed_this_frame(KeyCode::Space));
+
+        // held, the repeat is no new press
+        input.begin_frame();
+        input.handle_event(&KeyboardEvent::KeyPressed {
+            key: KeyCode::Space,
+            repeat: true,
+        });
+        assert!(input.is_key_down(KeyCode::Space));
+        assert!(!input.was_key_pressed_this_frame(KeyCode::Space));
+
+        input.begin_frame();
+        input.handle_event(&KeyboardEvent::KeyReleased(KeyCode::Space));
+        assert!(!input.is_key_down(KeyCode::Space));
+        assert!(input.was_key_released_this_frame(KeyCode::Space));
+        input.begin_frame();
+        assert!(!input.was_key_released_this_frame(KeyCode::Space));
+    }
+
+    #[test]
+    fn test_mouse_and_gamepad() {
+        let mut input = Input::new();
+        let moved = |x: f32, y: f32| MouseEvents::Moved(CursorPosition::new(Vec2::new(x, y), 2.0));
+        input.handle_event(&moved(20.0, 20.0));
+        input.handle_event(&moved(30.0, 24.0));
+        assert_eq!(input.mouse_position(), Some(Vec2::new(15.0, 12.0)));
+        assert_eq!(input.mouse_delta(), Vec2::new(5.0, 2.0));
+        input.handle_event(&MouseEvents::RawMotion(Vec2::new(-3.0, 0.0)));
+        assert_eq!(input.mouse_delta(), Vec2::new(-3.0, 0.0));
+        input.begin_frame();
+        assert_eq!(input.mouse_delta(), Vec2::ZERO);
+
+        let (first, second) = (GamepadId(0), GamepadId(1));
+        input.handle_event(&GamepadEvents::AxisMoved(
+            first,
+            GamepadAxis::LeftStickX,
+            0.2,
+        ));
+        input.handle_event(&GamepadEvents::AxisMoved(
+            second,
+            GamepadAxis::LeftStickX,
+            -0.7,
+        ));
+        assert_eq!(input.axis(GamepadAxis::LeftStickX), -0.7);
+        assert_eq!(input.gamepad_axis(first, GamepadAxis::LeftStickX), 0.2);
+        input.handle_event(&GamepadEvents::ButtonPressed(first, GamepadButton::South));
+        input.handle_event(&GamepadEvents::Disconnected(first));
+        assert!(input.get_gamepad_buttons(first).is_none());
+        assert_eq!(input.axis(GamepadAxis::LeftStickX), -0.7);
+    }
+}
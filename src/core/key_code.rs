@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum KeyCode {
     Space = 32,
     Apostrophe = 39, /* ' */
@@ -131,3 +131,137 @@ pub enum KeyCode {
     RightSuper = 347,
     Menu = 348,
 }
+
+impl KeyCode {
+    pub const ALL: &'static [KeyCode] = &[
+        Self::Space,
+        Self::Apostrophe,
+        Self::Comma,
+        Self::Minus,
+        Self::Period,
+        Self::Slash,
+        Self::D0,
+        Self::D1,
+        Self::D2,
+        Self::D3,
+        Self::D4,
+        Self::D5,
+        Self::D6,
+        Self::D7,
+        Self::D8,
+        Self::D9,
+        Self::Semicolon,
+        Self::Equal,
+        Self::A,
+        Self::B,
+        Self::C,
+        Self::D,
+        Self::E,
+        Self::F,
+        Self::G,
+        Self::H,
+        Self::I,
+        Self::J,
+        Self::K,
+        Self::L,
+        Self::M,
+        Self::N,
+        Self::O,
+        Self::P,
+        Self::Q,
+        Self::R,
+        Self::S,
+        Self::T,
+        Self::U,
+        Self::V,
+        Self::W,
+        Self::X,
+        Self::Y,
+        Self::Z,
+        Self::LeftBracket,
+        Self::Backslash,
+        Self::RightBracket,
+        Self::GraveAccent,
+        Self::World1,
+        Self::World2,
+        Self::Escape,
+        Self::Enter,
+        Self::Tab,
+        Self::Backspace,
+        Self::Insert,
+        Self::Delete,
+        Self::Right,
+        Self::Left,
+        Self::Down,
+        Self::Up,
+        Self::PageUp,
+        Self::PageDown,
+        Self::Home,
+        Self::End,
+        Self::CapsLock,
+        Self::ScrollLock,
+        Self::NumLock,
+        Self::PrintScreen,
+        Self::Pause,
+        Self::F1,
+        Self::F2,
+        Self::F3,
+        Self::F4,
+        Self::F5,
+        Self::F6,
+        Self::F7,
+        Self::F8,
+        Self::F9,
+        Self::F10,
+        Self::F11,
+        Self::F12,
+        Self::F13,
+        Self::F14,
+        Self::F15,
+        Self::F16,
+        Self::F17,
+        Self::F18,
+        Self::F19,
+        Self::F20,
+        Self::F21,
+        Self::F22,
+        Self::F23,
+        Self::F24,
+        Self::F25,
+        Self::KP0,
+        Self::KP1,
+        Self::KP2,
+        Self::KP3,
+        Self::KP4,
+        Self::KP5,
+        Self::KP6,
+        Self::KP7,
+        Self::KP8,
+        Self::KP9,
+        Self::KPDecimal,
+        Self::KPDivide,
+        Self::KPMultiply,
+        Self::KPSubtract,
+        Self::KPAdd,
+        Self::KPEnter,
+        Self::KPEqual,
+        Self::LeftShift,
+        Self::LeftControl,
+        Self::LeftAlt,
+        Self::LeftSuper,
+        Self::RightShift,
+        Self::RightControl,
+        Self::RightAlt,
+        Self::RightSuper,
+        Self::Menu,
+    ];
+
+    // The variant name, e.g. `Space` or `KP0`, used in binding files
+    pub fn get_name(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|key| key.get_name() == name)
+    }
+}
@@ -1,26 +1,538 @@
-// log4rs
-// log
-// env_logger
-pub fn init_logger() {
-    use std::io::Write;
-    let mut log_builder = env_logger::Builder::new();
-
-    // default format is ugly
-    // tz [LEVEL target] ...args
-    log_builder
-        .format_timestamp_secs()
-        .target(env_logger::Target::Stdout)
-        .filter_level(log::LevelFilter::Trace)
-        .format(|buf, record| {
-            let tz = buf.timestamp();
-            writeln!(
-                buf,
-                "{} - [{} {}] -> {} ",
-                tz,
-                record.level(),
-                record.target(),
-                record.args()
-            )
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    net::{TcpStream, ToSocketAddrs},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+// How many of the latest log lines are kept around for bug reports
+pub const RECENT_LOG_LINES: usize = 500;
+
+static RECENT_LOGS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+thread_local! {
+    static CURRENT_APP: RefCell<Option<AppLogger>> = const { RefCell::new(None) };
+}
+
+// The logging side of one Application. The `log` crate only has one global
+// logger, so it routes every record through the AppLogger that is active on
+// the calling thread: its name is added to the line and its level filters.
+#[derive(Debug, Clone)]
+pub struct AppLogger {
+    name: Arc<str>,
+    level: Arc<AtomicUsize>,
+}
+
+impl AppLogger {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: Arc::from(name),
+            level: Arc::new(AtomicUsize::new(LevelFilter::Trace as usize)),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_level(&self) -> LevelFilter {
+        LevelFilter::iter()
+            .nth(self.level.load(Ordering::Relaxed))
+            .unwrap_or(LevelFilter::Trace)
+    }
+
+    // Also raises the global max level if it is lower, otherwise the records
+    // would never reach the router
+    pub fn set_level(&self, level: LevelFilter) {
+        self.level.store(level as usize, Ordering::Relaxed);
+        if level > log::max_level() {
+            log::set_max_level(level);
+        }
+    }
+
+    // Makes this the active logger of the thread until the scope is dropped
+    pub fn enter(&self) -> LoggerScope {
+        let previous = CURRENT_APP.with(|current| current.replace(Some(self.clone())));
+        LoggerScope { previous }
+    }
+}
+
+impl Default for AppLogger {
+    fn default() -> Self {
+        Self::new("aloy")
+    }
+}
+
+pub struct LoggerScope {
+    previous: Option<AppLogger>,
+}
+
+impl Drop for LoggerScope {
+    fn drop(&mut self) {
+        CURRENT_APP.with(|current| current.replace(self.previous.take()));
+    }
+}
+
+// Name of the application logging on this thread, if any
+pub fn current_app_name() -> Option<String> {
+    CURRENT_APP.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .map(|logger| logger.get_name().to_string())
+    })
+}
+
+fn current_app_level() -> LevelFilter {
+    CURRENT_APP.with(|current| {
+        current
+            .borrow()
+            .as_ref()
+            .map_or(LevelFilter::Trace, |logger| logger.get_level())
+    })
+}
+
+fn remember_log(line: String) {
+    if let Ok(mut logs) = RECENT_LOGS.lock() {
+        if logs.len() == RECENT_LOG_LINES {
+            logs.pop_front();
+        }
+        logs.push_back(line);
+    }
+}
+
+// The latest lines logged by any application, oldest first
+pub fn get_recent_logs() -> Vec<String> {
+    RECENT_LOGS
+        .lock()
+        .map(|logs| logs.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+// One record as the sinks get it
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    pub time: chrono::DateTime<chrono::Local>,
+    pub level: Level,
+    pub target: String,
+    // the application that logged it, when one was active on the thread
+    pub app: Option<String>,
+    pub message: String,
+}
+
+impl LogLine {
+    // tz - app [LEVEL target] -> message
+    pub fn format(&self) -> String {
+        let app = self
+            .app
+            .as_ref()
+            .map(|name| format!("{} ", name))
+            .unwrap_or_default();
+        format!(
+            "{} - {}[{} {}] -> {}",
+            self.time.format("%H:%M:%S%.3f"),
+            app,
+            self.level,
+            self.target,
+            self.message
+        )
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::json!({
+            "time": self.time.to_rfc3339(),
+            "level": self.level.as_str(),
+            "target": self.target,
+            "app": self.app,
+            "message": self.message,
+        })
+        .to_string()
+    }
+}
+
+// Somewhere log lines go. Sinks are attached and detached by name while the
+// game runs, each with its own level.
+pub trait LogSink: Send {
+    fn write(&mut self, line: &LogLine);
+    fn flush(&mut self) {}
+}
+
+struct AttachedSink {
+    name: String,
+    level: LevelFilter,
+    sink: Box<dyn LogSink>,
+}
+
+static SINKS: Mutex<Vec<AttachedSink>> = Mutex::new(Vec::new());
+
+thread_local! {
+    // set while the sinks run, a sink logging itself must not deadlock
+    static IN_SINK: Cell<bool> = const { Cell::new(false) };
+}
+
+// Replaces the sink with the same name, if any
+pub fn attach_sink(name: &str, level: LevelFilter, sink: impl LogSink + 'static) {
+    let Ok(mut sinks) = SINKS.lock() else {
+        return;
+    };
+    let attached = AttachedSink {
+        name: name.to_string(),
+        level,
+        sink: Box::new(sink),
+    };
+    match sinks.iter_mut().find(|other| other.name == name) {
+        Some(existing) => {
+            existing.sink.flush();
+            *existing = attached;
+        }
+        None => sinks.push(attached),
+    }
+}
+
+// Flushes and drops the sink, false if there was none
+pub fn detach_sink(name: &str) -> bool {
+    let Ok(mut sinks) = SINKS.lock() else {
+        return false;
+    };
+    let Some(index) = sinks.iter().position(|sink| sink.name == name) else {
+        return false;
+    };
+    sinks.remove(index).sink.flush();
+    true
+}
+
+pub fn set_sink_level(name: &str, level: LevelFilter) -> bool {
+    let Ok(mut sinks) = SINKS.lock() else {
+        return false;
+    };
+    match sinks.iter_mut().find(|sink| sink.name == name) {
+        Some(sink) => {
+            sink.level = level;
+            true
+        }
+        None => false,
+    }
+}
+
+// In the order they were attached
+pub fn get_sink_names() -> Vec<String> {
+    SINKS
+        .lock()
+        .map(|sinks| sinks.iter().map(|sink| sink.name.clone()).collect())
+        .unwrap_or_default()
+}
+
+fn write_to_sinks(line: &LogLine) {
+    if IN_SINK.with(|in_sink| in_sink.replace(true)) {
+        return;
+    }
+    if let Ok(mut sinks) = SINKS.lock() {
+        for attached in sinks.iter_mut() {
+            if line.level <= attached.level {
+                attached.sink.write(line);
+            }
+        }
+    }
+    IN_SINK.with(|in_sink| in_sink.set(false));
+}
+
+fn flush_sinks() {
+    if let Ok(mut sinks) = SINKS.lock() {
+        sinks.iter_mut().for_each(|attached| attached.sink.flush());
+    }
+}
+
+// Standard output, what `init_logger` attaches as "console"
+#[derive(Debug, Default)]
+pub struct ConsoleSink;
+
+impl LogSink for ConsoleSink {
+    fn write(&mut self, line: &LogLine) {
+        let _ = writeln!(std::io::stdout(), "{}", line.format());
+    }
+
+    fn flush(&mut self) {
+        let _ = std::io::stdout().flush();
+    }
+}
+
+// Formatted lines appended to a file
+pub struct FileSink {
+    writer: BufWriter<File>,
+}
+
+impl FileSink {
+    pub fn new(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            writer: BufWriter::new(file),
+        })
+    }
+}
+
+impl LogSink for FileSink {
+    fn write(&mut self, line: &LogLine) {
+        let _ = writeln!(self.writer, "{}", line.format());
+    }
+
+    fn flush(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+// One JSON object per line, for log collectors
+pub struct JsonSink {
+    writer: Box<dyn Write + Send>,
+}
+
+impl JsonSink {
+    pub fn new(writer: impl Write + Send + 'static) -> Self {
+        Self {
+            writer: Box::new(writer),
+        }
+    }
+
+    pub fn to_file(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::new(BufWriter::new(file)))
+    }
+}
+
+impl LogSink for JsonSink {
+    fn write(&mut self, line: &LogLine) {
+        let _ = writeln!(self.writer, "{}", line.to_json());
+    }
+
+    fn flush(&mut self) {
+        let _ = self.writer.flush();
+    }
+}
+
+// Keeps the latest lines in memory, e.g. for an in game console. Clones
+// share the lines, keep one to read them after attaching the other.
+#[derive(Debug, Clone)]
+pub struct RingBufferSink {
+    capacity: usize,
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl RingBufferSink {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    // Oldest first
+    pub fn get_lines(&self) -> Vec<String> {
+        self.lines
+            .lock()
+            .map(|lines| lines.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut lines) = self.lines.lock() {
+            lines.clear();
+        }
+    }
+}
+
+impl LogSink for RingBufferSink {
+    fn write(&mut self, line: &LogLine) {
+        if let Ok(mut lines) = self.lines.lock() {
+            if lines.len() == self.capacity {
+                lines.pop_front();
+            }
+            if self.capacity > 0 {
+                lines.push_back(line.format());
+            }
+        }
+    }
+}
+
+// Streams formatted lines to a remote console over TCP, e.g. a tool on the
+// developer's machine watching a game on a device. Stops sending once the
+// connection breaks, attach a new one to reconnect.
+//
+// The socket is written on a thread of its own, so a slow console never
+// stalls the game while it logs. Lines that do not fit the queue are dropped.
+pub struct RemoteConsoleSink {
+    lines: Option<SyncSender<String>>,
+    connected: Arc<AtomicBool>,
+    dropped: u64,
+}
+
+impl RemoteConsoleSink {
+    pub const QUEUE_LINES: usize = 1024;
+
+    pub fn connect(address: impl ToSocketAddrs) -> std::io::Result<Self> {
+        let mut stream = TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let (lines, receiver) = mpsc::sync_channel::<String>(Self::QUEUE_LINES);
+        let connected = Arc::new(AtomicBool::new(true));
+        let writer_connected = Arc::clone(&connected);
+        thread::Builder::new()
+            .name("aloy remote console".to_string())
+            .spawn(move || {
+                for line in receiver {
+                    if writeln!(stream, "{}", line).is_err() {
+                        break;
+                    }
+                }
+                writer_connected.store(false, Ordering::Relaxed);
+            })?;
+        Ok(Self {
+            lines: Some(lines),
+            connected,
+            dropped: 0,
         })
-        .init();
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.lines.is_some() && self.connected.load(Ordering::Relaxed)
+    }
+
+    // Lines dropped because the connection could not keep up
+    pub fn get_dropped_lines(&self) -> u64 {
+        self.dropped
+    }
+}
+
+impl LogSink for RemoteConsoleSink {
+    fn write(&mut self, line: &LogLine) {
+        let Some(lines) = self.lines.as_ref() else {
+            return;
+        };
+        match lines.try_send(line.format()) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => self.dropped += 1,
+            Err(TrySendError::Disconnected(_)) => self.lines = None,
+        }
+    }
+}
+
+struct AppRouter;
+
+static ROUTER: AppRouter = AppRouter;
+
+impl Log for AppRouter {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= current_app_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let line = LogLine {
+                time: chrono::Local::now(),
+                level: record.level(),
+                target: record.target().to_string(),
+                app: current_app_name(),
+                message: record.args().to_string(),
+            };
+            remember_log(line.format());
+            write_to_sinks(&line);
+        }
+    }
+
+    fn flush(&self) {
+        flush_sinks();
+    }
+}
+
+// Installs the logger with the console sink, more sinks can be attached
+// before or after
+pub fn init_logger() {
+    if log::set_logger(&ROUTER).is_ok() {
+        log::set_max_level(LevelFilter::Trace);
+        attach_sink("console", LevelFilter::Trace, ConsoleSink);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scopes_nest_per_thread() {
+        let editor = AppLogger::new("editor");
+        let preview = AppLogger::new("preview");
+        preview.set_level(LevelFilter::Warn);
+
+        {
+            let _editor = editor.enter();
+            {
+                let _preview = preview.enter();
+                assert_eq!(current_app_name().as_deref(), Some("preview"));
+                assert_eq!(current_app_level(), LevelFilter::Warn);
+            }
+            assert_eq!(current_app_name().as_deref(), Some("editor"));
+            let other_thread = std::thread::spawn(current_app_name).join().unwrap();
+            assert_eq!(other_thread, None);
+        }
+        assert_eq!(current_app_name(), None);
+    }
+
+    #[test]
+    fn test_sinks_attach_and_detach() {
+        init_logger();
+        let console = RingBufferSink::new(1000);
+        attach_sink("test console", LevelFilter::Warn, console.clone());
+        assert!(get_sink_names().contains(&"test console".to_string()));
+
+        log::info!(target: "sink_test", "quiet line");
+        log::warn!(target: "sink_test", "loud line");
+        let lines = console.get_lines();
+        assert!(lines
+            .iter()
+            .any(|line| line.ends_with("[WARN sink_test] -> loud line")));
+        assert!(!lines.iter().any(|line| line.contains("quiet line")));
+
+        assert!(set_sink_level("test console", LevelFilter::Info));
+        log::info!(target: "sink_test", "now heard");
+        assert!(console
+            .get_lines()
+            .iter()
+            .any(|line| line.contains("now heard")));
+
+        assert!(detach_sink("test console"));
+        assert!(!detach_sink("test console"));
+        log::warn!(target: "sink_test", "after detach");
+        assert!(!console
+            .get_lines()
+            .iter()
+            .any(|line| line.contains("after detach")));
+    }
+
+    #[test]
+    fn test_remote_console_writes_on_its_own_thread() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut sink = RemoteConsoleSink::connect(listener.local_addr().unwrap()).unwrap();
+        let (console, _) = listener.accept().unwrap();
+
+        let line = LogLine {
+            time: chrono::Local::now(),
+            level: Level::Info,
+            target: "remote".to_string(),
+            app: None,
+            message: "hello".to_string(),
+        };
+        sink.write(&line);
+        let mut received = String::new();
+        std::io::BufRead::read_line(&mut std::io::BufReader::new(&console), &mut received).unwrap();
+        assert_eq!(received.trim_end(), line.format());
+        assert!(sink.is_connected());
+        assert_eq!(sink.get_dropped_lines(), 0);
+    }
 }
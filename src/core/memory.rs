@@ -0,0 +1,380 @@
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    collections::BTreeMap,
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use log::{info, warn};
+
+// Tags past this share the last one
+pub const MAX_TAGS: usize = 64;
+
+const UNTAGGED_NAME: &str = "untagged";
+const OVERFLOW_NAME: &str = "other";
+
+// What an allocation is attributed to, usually a subsystem. The engine tags
+// everything a subsystem allocates while it is initalized or ticked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MemoryTag(u8);
+
+impl MemoryTag {
+    pub const UNTAGGED: Self = Self(0);
+
+    pub fn get_name(&self) -> String {
+        match self.0 {
+            0 => UNTAGGED_NAME.to_string(),
+            index => TAG_NAMES
+                .lock()
+                .map(|names| names[index as usize - 1].clone())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+struct TagCounters {
+    live_bytes: AtomicU64,
+    live_allocations: AtomicU64,
+    total_allocations: AtomicU64,
+}
+
+impl TagCounters {
+    const fn new() -> Self {
+        Self {
+            live_bytes: AtomicU64::new(0),
+            live_allocations: AtomicU64::new(0),
+            total_allocations: AtomicU64::new(0),
+        }
+    }
+}
+
+static COUNTERS: [TagCounters; MAX_TAGS] = [const { TagCounters::new() }; MAX_TAGS];
+// names of the tags after the untagged one
+static TAG_NAMES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static CURRENT_TAG: Cell<u8> = const { Cell::new(0) };
+}
+
+// The same name always gives the same tag
+pub fn register_tag(name: &str) -> MemoryTag {
+    let Ok(mut names) = TAG_NAMES.lock() else {
+        return MemoryTag::UNTAGGED;
+    };
+    if let Some(index) = names.iter().position(|other| other == name) {
+        return MemoryTag(index as u8 + 1);
+    }
+    if names.len() + 1 == MAX_TAGS - 1 {
+        warn!(
+            "out of memory tags, {} is counted as {}",
+            name, OVERFLOW_NAME
+        );
+        names.push(OVERFLOW_NAME.to_string());
+    }
+    if names.len() + 1 >= MAX_TAGS {
+        return MemoryTag(MAX_TAGS as u8 - 1);
+    }
+    names.push(name.to_string());
+    MemoryTag(names.len() as u8)
+}
+
+pub fn get_current_tag() -> MemoryTag {
+    MemoryTag(CURRENT_TAG.try_with(|tag| tag.get()).unwrap_or(0))
+}
+
+// Allocations on this thread go to `tag` until the scope is dropped
+pub fn scope(tag: MemoryTag) -> TagScope {
+    let previous = CURRENT_TAG.with(|current| current.replace(tag.0));
+    TagScope { previous }
+}
+
+#[must_use = "the tag only applies while the scope is alive"]
+pub struct TagScope {
+    previous: u8,
+}
+
+impl Drop for TagScope {
+    fn drop(&mut self) {
+        let _ = CURRENT_TAG.try_with(|current| current.set(self.previous));
+    }
+}
+
+// Wraps the system allocator and counts live memory per tag. Only counts when
+// the game installs it:
+//
+//     #[global_allocator]
+//     static ALLOCATOR: TrackingAllocator = TrackingAllocator;
+//
+// Every allocation carries its tag in a small header in front of it, so
+// frees are counted against the tag that allocated.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrackingAllocator;
+
+impl TrackingAllocator {
+    fn header(layout: Layout) -> usize {
+        layout.align().max(std::mem::size_of::<usize>())
+    }
+
+    unsafe fn allocate(&self, layout: Layout, zeroed: bool) -> *mut u8 {
+        let header = Self::header(layout);
+        let Ok(full) = Layout::from_size_align(layout.size() + header, layout.align()) else {
+            return std::ptr::null_mut();
+        };
+        let base = match zeroed {
+            true => System.alloc_zeroed(full),
+            false => System.alloc(full),
+        };
+        if base.is_null() {
+            return base;
+        }
+        let tag = get_current_tag().0;
+        base.add(header - 1).write(tag);
+        let counters = &COUNTERS[tag as usize];
+        counters
+            .live_bytes
+            .fetch_add(layout.size() as u64, Ordering::Relaxed);
+        counters.live_allocations.fetch_add(1, Ordering::Relaxed);
+        counters.total_allocations.fetch_add(1, Ordering::Relaxed);
+        INSTALLED.store(true, Ordering::Relaxed);
+        base.add(header)
+    }
+}
+
+unsafe impl GlobalAlloc for TrackingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.allocate(layout, false)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        self.allocate(layout, true)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let header = Self::header(layout);
+        let tag = ptr.sub(1).read();
+        let counters = &COUNTERS[tag as usize];
+        counters
+            .live_bytes
+            .fetch_sub(layout.size() as u64, Ordering::Relaxed);
+        counters.live_allocations.fetch_sub(1, Ordering::Relaxed);
+        System.dealloc(
+            ptr.sub(header),
+            Layout::from_size_align_unchecked(layout.size() + header, layout.align()),
+        );
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TagUsage {
+    pub live_bytes: u64,
+    pub live_allocations: u64,
+    // every allocation made so far, freed or not
+    pub total_allocations: u64,
+}
+
+// The live memory of every tag at one point in time
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemorySnapshot {
+    label: String,
+    usage: BTreeMap<String, TagUsage>,
+}
+
+impl MemorySnapshot {
+    pub fn capture(label: &str) -> Self {
+        if !is_tracking() {
+            warn!("memory snapshot without the TrackingAllocator installed");
+        }
+        let mut names = vec![UNTAGGED_NAME.to_string()];
+        if let Ok(registered) = TAG_NAMES.lock() {
+            names.extend(registered.iter().cloned());
+        }
+        let usage = names
+            .into_iter()
+            .zip(COUNTERS.iter())
+            .map(|(name, counters)| {
+                let usage = TagUsage {
+                    live_bytes: counters.live_bytes.load(Ordering::Relaxed),
+                    live_allocations: counters.live_allocations.load(Ordering::Relaxed),
+                    total_allocations: counters.total_allocations.load(Ordering::Relaxed),
+                };
+                (name, usage)
+            })
+            .collect();
+        Self {
+            label: label.to_string(),
+            usage,
+        }
+    }
+
+    pub fn get_label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn get_usage(&self, tag: &str) -> Option<TagUsage> {
+        self.usage.get(tag).copied()
+    }
+
+    pub fn get_live_bytes(&self) -> u64 {
+        self.usage.values().map(|usage| usage.live_bytes).sum()
+    }
+
+    // What changed from this snapshot to a later one
+    pub fn diff(&self, later: &MemorySnapshot) -> MemoryDiff {
+        let mut entries: Vec<TagDiff> = later
+            .usage
+            .iter()
+            .map(|(tag, after)| TagDiff {
+                tag: tag.clone(),
+                before: self.get_usage(tag).unwrap_or_default(),
+                after: *after,
+            })
+            .collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.get_byte_growth()));
+        MemoryDiff {
+            from: self.label.clone(),
+            to: later.label.clone(),
+            entries,
+        }
+    }
+}
+
+// True once the TrackingAllocator has served an allocation
+pub fn is_tracking() -> bool {
+    INSTALLED.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagDiff {
+    pub tag: String,
+    pub before: TagUsage,
+    pub after: TagUsage,
+}
+
+impl TagDiff {
+    pub fn get_byte_growth(&self) -> i64 {
+        self.after.live_bytes as i64 - self.before.live_bytes as i64
+    }
+
+    pub fn get_allocation_growth(&self) -> i64 {
+        self.after.live_allocations as i64 - self.before.live_allocations as i64
+    }
+
+    // Allocations made between the snapshots, freed or not
+    pub fn get_allocations_made(&self) -> u64 {
+        self.after.total_allocations - self.before.total_allocations
+    }
+}
+
+// Per tag growth between two snapshots, biggest growth first. Taken around
+// something that should not leave anything behind, e.g. loading and leaving a
+// level, whatever still grew is the leak.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryDiff {
+    from: String,
+    to: String,
+    entries: Vec<TagDiff>,
+}
+
+impl MemoryDiff {
+    pub fn get_entries(&self) -> &[TagDiff] {
+        &self.entries
+    }
+
+    pub fn get(&self, tag: &str) -> Option<&TagDiff> {
+        self.entries.iter().find(|entry| entry.tag == tag)
+    }
+
+    pub fn get_byte_growth(&self) -> i64 {
+        self.entries.iter().map(TagDiff::get_byte_growth).sum()
+    }
+
+    // Tags that grew by more than `threshold` bytes
+    pub fn get_growing(&self, threshold: u64) -> Vec<&TagDiff> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.get_byte_growth() > threshold as i64)
+            .collect()
+    }
+
+    // Writes the report to the log, tags growing past `threshold` as warnings
+    pub fn log_report(&self, threshold: u64) {
+        info!("memory from {} to {}", self.from, self.to);
+        for entry in self.entries.iter() {
+            let growth = entry.get_byte_growth();
+            match growth > threshold as i64 {
+                true => warn!(
+                    "{} grew by {} bytes in {} allocations",
+                    entry.tag,
+                    growth,
+                    entry.get_allocation_growth()
+                ),
+                false => info!("{}: {:+} bytes", entry.tag, growth),
+            }
+        }
+    }
+}
+
+impl Display for MemoryDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "memory from {} to {}", self.from, self.to)?;
+        writeln!(
+            f,
+            "{:<24} {:>14} {:>14} {:>12} {:>10}",
+            "tag", "before", "after", "growth", "allocs"
+        )?;
+        for entry in self.entries.iter() {
+            writeln!(
+                f,
+                "{:<24} {:>14} {:>14} {:>+12} {:>+10}",
+                entry.tag,
+                entry.before.live_bytes,
+                entry.after.live_bytes,
+                entry.get_byte_growth(),
+                entry.get_allocation_growth()
+            )?;
+        }
+        write!(f, "total {:+} bytes", self.get_byte_growth())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_by_tag() {
+        let allocator = TrackingAllocator;
+        let tag = register_tag("memory test");
+        assert_eq!(register_tag("memory test"), tag);
+        assert_eq!(tag.get_name(), "memory test");
+
+        let before = MemorySnapshot::capture("before");
+        let layout = Layout::from_size_align(64, 32).unwrap();
+        let (leaked, freed) = {
+            let _scope = scope(tag);
+            assert_eq!(get_current_tag(), tag);
+            unsafe { (allocator.alloc(layout), allocator.alloc_zeroed(layout)) }
+        };
+        assert_eq!(get_current_tag(), MemoryTag::UNTAGGED);
+        assert_eq!(leaked as usize % 32, 0);
+        unsafe { allocator.dealloc(freed, layout) };
+        let after = MemorySnapshot::capture("after");
+
+        let diff = before.diff(&after);
+        let entry = diff.get("memory test").unwrap();
+        assert_eq!(entry.get_byte_growth(), 64);
+        assert_eq!(entry.get_allocation_growth(), 1);
+        assert_eq!(entry.get_allocations_made(), 2);
+        assert!(diff.get_growing(32).iter().any(|e| e.tag == "memory test"));
+        assert!(diff.to_string().contains("memory test"));
+
+        unsafe { allocator.dealloc(leaked, layout) };
+        let cleaned = before.diff(&MemorySnapshot::capture("cleaned"));
+        assert_eq!(cleaned.get("memory test").unwrap().get_byte_growth(), 0);
+    }
+}
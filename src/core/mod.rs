@@ -1,3 +1,23 @@
+pub mod bug_report;
+pub mod cli;
+pub mod cloud_save;
+pub mod config;
+pub mod crash;
+pub mod cvars;
+pub mod data_file;
+pub mod floating_origin;
+pub mod input;
 pub mod key_code;
 pub mod logger;
+pub mod memory;
+pub mod net;
+pub mod paths;
 pub mod runner;
+pub mod scene_loading;
+pub mod settings;
+pub mod spawn_director;
+pub mod time;
+pub mod time_slicing;
+pub mod time_travel;
+pub mod timer;
+pub mod window;
@@ -0,0 +1,452 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+use log::warn;
+
+use super::{
+    transport::{NetErrors, Transport},
+    wire::ByteReader,
+};
+
+const KIND_DATA: u8 = 0;
+const KIND_ACK: u8 = 1;
+
+const INITIAL_RTT: Duration = Duration::from_millis(100);
+const MIN_RETRANSMIT_TIMEOUT: Duration = Duration::from_millis(20);
+
+// How far ahead of the next expected sequence reliable messages are kept,
+// and how many bytes of them. Anything past that is dropped unacked so the
+// sender resends it once the gap is filled.
+pub const RECEIVE_WINDOW: u32 = 1024;
+pub const MAX_BUFFERED_BYTES: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind {
+    // fire and forget, may arrive out of order or not at all
+    Unreliable,
+    // may get lost, but anything older than the newest delivered is dropped
+    UnreliableSequenced,
+    // every message arrives exactly once and in order
+    ReliableOrdered,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChannelMetrics {
+    pub sent: u64,
+    pub received: u64,
+    pub retransmissions: u64,
+    pub acked: u64,
+    // duplicates, stale sequenced messages and reliable ones past the
+    // receive window
+    pub discarded: u64,
+    pub in_flight: usize,
+    pub smoothed_rtt: Duration,
+}
+
+#[derive(Debug)]
+struct PendingMessage {
+    payload: Vec<u8>,
+    first_sent: Instant,
+    last_sent: Instant,
+    retransmitted: bool,
+}
+
+#[derive(Debug)]
+struct Channel {
+    kind: ChannelKind,
+    next_send_sequence: u32,
+    // next sequence to deliver for reliable, newest delivered for sequenced
+    next_receive_sequence: u32,
+    pending: HashMap<u32, PendingMessage>,
+    out_of_order: BTreeMap<u32, Vec<u8>>,
+    out_of_order_bytes: usize,
+    metrics: ChannelMetrics,
+}
+
+impl Channel {
+    fn new(kind: ChannelKind) -> Self {
+        Self {
+            kind,
+            next_send_sequence: 0,
+            next_receive_sequence: 0,
+            pending: HashMap::new(),
+            out_of_order: BTreeMap::new(),
+            out_of_order_bytes: 0,
+            metrics: ChannelMetrics {
+                smoothed_rtt: INITIAL_RTT,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn retransmit_timeout(&self) -> Duration {
+        (self.metrics.smoothed_rtt * 2).max(MIN_RETRANSMIT_TIMEOUT)
+    }
+}
+
+// Sequences wrap, one is newer when it is less than half the range ahead
+fn is_newer(sequence: u32, than: u32) -> bool {
+    (sequence.wrapping_sub(than) as i32) > 0
+}
+
+// [channel: u8][kind: u8][sequence: u32 LE][payload...]
+fn encode_packet(channel: u8, kind: u8, sequence: u32, payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(6 + payload.len());
+    packet.push(channel);
+    packet.push(kind);
+    packet.extend_from_slice(&sequence.to_le_bytes());
+    packet.extend_from_slice(payload);
+    packet
+}
+
+fn decode_packet(packet: &[u8]) -> Result<(u8, u8, u32, &[u8]), NetErrors> {
+    let mut reader = ByteReader::new(packet);
+    let [channel] = reader.take_array::<1>()?;
+    let [kind] = reader.take_array::<1>()?;
+    let sequence = u32::from_le_bytes(reader.take_array()?);
+    let payload = reader.take(packet.len() - 6)?;
+    Ok((channel, kind, sequence, payload))
+}
+
+// Multiplexes several channels with different delivery guarantees over one
+// unreliable packet transport (usually UDP). Call `update` every frame so lost
+// reliable messages get retransmitted.
+#[derive(Debug)]
+pub struct ChannelEndpoint<T: Transport> {
+    transport: T,
+    channels: Vec<Channel>,
+}
+
+impl<T: Transport> ChannelEndpoint<T> {
+    // Channel ids are the indices into `kinds`, both peers must agree on them
+    pub fn new(transport: T, kinds: &[ChannelKind]) -> Self {
+        Self {
+            transport,
+            channels: kinds.iter().map(|kind| Channel::new(*kind)).collect(),
+        }
+    }
+
+    pub fn get_transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn get_channel_kind(&self, channel: u8) -> Option<ChannelKind> {
+        self.channels.get(channel as usize).map(|c| c.kind)
+    }
+
+    pub fn get_metrics(&self, channel: u8) -> Option<&ChannelMetrics> {
+        self.channels.get(channel as usize).map(|c| &c.metrics)
+    }
+
+    fn get_channel_mut(&mut self, channel: u8) -> Result<&mut Channel, NetErrors> {
+        self.channels
+            .get_mut(channel as usize)
+            .ok_or_else(|| NetErrors::UnableToSend(format!("unknown channel {}", channel)))
+    }
+
+    pub fn send(&mut self, channel: u8, payload: &[u8]) -> Result<(), NetErrors> {
+        self.send_at(channel, payload, Instant::now())
+    }
+
+    pub fn send_at(&mut self, channel: u8, payload: &[u8], now: Instant) -> Result<(), NetErrors> {
+        let state = self.get_channel_mut(channel)?;
+        let sequence = state.next_send_sequence;
+        state.next_send_sequence = state.next_send_sequence.wrapping_add(1);
+        state.metrics.sent += 1;
+
+        if state.kind == ChannelKind::ReliableOrdered {
+            state.pending.insert(
+                sequence,
+                PendingMessage {
+                    payload: payload.to_vec(),
+                    first_sent: now,
+                    last_sent: now,
+                    retransmitted: false,
+                },
+            );
+            state.metrics.in_flight = state.pending.len();
+        }
+
+        self.transport
+            .send(&encode_packet(channel, KIND_DATA, sequence, payload))
+    }
+
+    pub fn update(&mut self) -> Result<(), NetErrors> {
+        self.update_at(Instant::now())
+    }
+
+    // Resends every reliable message that was not acked within the timeout
+    pub fn update_at(&mut self, now: Instant) -> Result<(), NetErrors> {
+        for (id, channel) in self.channels.iter_mut().enumerate() {
+            let timeout = channel.retransmit_timeout();
+            for (sequence, message) in channel.pending.iter_mut() {
+                if now.duration_since(message.last_sent) < timeout {
+                    continue;
+                }
+                message.last_sent = now;
+                message.retransmitted = true;
+                channel.metrics.retransmissions += 1;
+                self.transport.send(&encode_packet(
+                    id as u8,
+                    KIND_DATA,
+                    *sequence,
+                    &message.payload,
+                ))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn receive(&mut self) -> Result<Vec<(u8, Vec<u8>)>, NetErrors> {
+        self.receive_at(Instant::now())
+    }
+
+    // Returns (channel, payload) pairs ready for the game, acks are handled
+    // internally
+    pub fn receive_at(&mut self, now: Instant) -> Result<Vec<(u8, Vec<u8>)>, NetErrors> {
+        let mut delivered = Vec::new();
+
+        for packet in self.transport.receive()? {
+            let (channel_id, kind, sequence, payload) = match decode_packet(&packet) {
+                Ok(decoded) => decoded,
+                Err(err) => {
+                    warn!("dropping malformed channel packet: {}", err);
+                    continue;
+                }
+            };
+            let Some(channel) = self.channels.get_mut(channel_id as usize) else {
+                warn!("dropping packet for unknown channel {}", channel_id);
+                continue;
+            };
+
+            if kind == KIND_ACK {
+                if let Some(message) = channel.pending.remove(&sequence) {
+                    // Karn's rule, retransmitted messages give ambiguous rtts
+                    if !message.retransmitted {
+                        let sample = now.duration_since(message.first_sent);
+                        channel.metrics.smoothed_rtt =
+                            channel.metrics.smoothed_rtt.mul_f32(0.875) + sample.mul_f32(0.125);
+                    }
+                    channel.metrics.acked += 1;
+                    channel.metrics.in_flight = channel.pending.len();
+                }
+                continue;
+            }
+
+            match channel.kind {
+                ChannelKind::Unreliable => {
+                    channel.metrics.received += 1;
+                    delivered.push((channel_id, payload.to_vec()));
+                }
+                ChannelKind::UnreliableSequenced => {
+                    let is_first = channel.metrics.received == 0;
+                    if is_first || is_newer(sequence, channel.next_receive_sequence) {
+                        channel.next_receive_sequence = sequence;
+                        channel.metrics.received += 1;
+                        delivered.push((channel_id, payload.to_vec()));
+                    } else {
+                        channel.metrics.discarded += 1;
+                    }
+                }
+                ChannelKind::ReliableOrdered => {
+                    let ahead = sequence.wrapping_sub(channel.next_receive_sequence);
+                    if is_newer(sequence, channel.next_receive_sequence)
+                        && (ahead >= RECEIVE_WINDOW
+                            || channel.out_of_order_bytes + payload.len() > MAX_BUFFERED_BYTES)
+                    {
+                        channel.metrics.discarded += 1;
+                        continue;
+                    }
+
+                    // always ack, our previous ack might have been lost. A
+                    // failed ack is acked again once the message is resent,
+                    // the messages received so far are delivered anyway.
+                    if let Err(err) =
+                        self.transport
+                            .send(&encode_packet(channel_id, KIND_ACK, sequence, &[]))
+                    {
+                        warn!("unable to ack message {}: {}", sequence, err);
+                    }
+
+                    if is_newer(channel.next_receive_sequence, sequence)
+                        || channel.out_of_order.contains_key(&sequence)
+                    {
+                        channel.metrics.discarded += 1;
+                        continue;
+                    }
+                    channel.out_of_order_bytes += payload.len();
+                    channel.out_of_order.insert(sequence, payload.to_vec());
+                    while let Some(payload) =
+                        channel.out_of_order.remove(&channel.next_receive_sequence)
+                    {
+                        channel.out_of_order_bytes -= payload.len();
+                        channel.next_receive_sequence =
+                            channel.next_receive_sequence.wrapping_add(1);
+                        channel.metrics.received += 1;
+                        delivered.push((channel_id, payload));
+                    }
+                }
+            }
+        }
+
+        Ok(delivered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::net::{
+        conditioner::{ConditionedTransport, ConditionerConfig},
+        transport::InProcessTransport,
+    };
+
+    use super::*;
+
+    const UNRELIABLE: u8 = 0;
+    const SEQUENCED: u8 = 1;
+    const RELIABLE: u8 = 2;
+    const KINDS: [ChannelKind; 3] = [
+        ChannelKind::Unreliable,
+        ChannelKind::UnreliableSequenced,
+        ChannelKind::ReliableOrdered,
+    ];
+
+    #[test]
+    fn test_reliable_ordered_over_lossy_link() {
+        let lossy = ConditionerConfig {
+            packet_loss: 0.3,
+            ..Default::default()
+        };
+        let (a, b) = InProcessTransport::pair();
+        let mut sender = ChannelEndpoint::new(
+            ConditionedTransport::with_seed(a, lossy.clone(), 11),
+            &KINDS,
+        );
+        let mut reciever =
+            ChannelEndpoint::new(ConditionedTransport::with_seed(b, lossy, 21), &KINDS);
+
+        let start = Instant::now();
+        for i in 0..50u8 {
+            sender.send_at(RELIABLE, &[i], start).unwrap();
+        }
+
+        let mut received = Vec::new();
+        for round in 1..200u32 {
+            let now = start + Duration::from_secs(round as u64);
+            received.extend(
+                reciever
+                    .receive_at(now)
+                    .unwrap()
+                    .into_iter()
+                    .map(|(_, payload)| payload[0]),
+            );
+            sender.receive_at(now).unwrap();
+            sender.update_at(now).unwrap();
+            if received.len() == 50 && sender.get_metrics(RELIABLE).unwrap().in_flight == 0 {
+                break;
+            }
+        }
+
+        assert_eq!(received, (0..50).collect::<Vec<u8>>());
+        let metrics = sender.get_metrics(RELIABLE).unwrap();
+        assert_eq!(metrics.in_flight, 0);
+        assert_eq!(metrics.acked, 50);
+        assert!(metrics.retransmissions > 0);
+    }
+
+    #[test]
+    fn test_sequenced_drops_stale_messages() {
+        let (a, b) = InProcessTransport::pair();
+        let mut a = a;
+        let mut reciever = ChannelEndpoint::new(b, &KINDS);
+
+        // deliver sequence 2 before 1
+        a.send(&encode_packet(SEQUENCED, KIND_DATA, 2, b"new"))
+            .unwrap();
+        a.send(&encode_packet(SEQUENCED, KIND_DATA, 1, b"old"))
+            .unwrap();
+
+        let delivered = reciever.receive().unwrap();
+        assert_eq!(delivered, vec![(SEQUENCED, b"new".to_vec())]);
+        assert_eq!(reciever.get_metrics(SEQUENCED).unwrap().discarded, 1);
+    }
+
+    #[test]
+    fn test_unreliable_is_delivered_as_is() {
+        let (a, b) = InProcessTransport::pair();
+        let mut sender = ChannelEndpoint::new(a, &KINDS);
+        let mut reciever = ChannelEndpoint::new(b, &KINDS);
+
+        sender.send(UNRELIABLE, b"ping").unwrap();
+        assert_eq!(
+            reciever.receive().unwrap(),
+            vec![(UNRELIABLE, b"ping".to_vec())]
+        );
+        assert_eq!(sender.get_metrics(UNRELIABLE).unwrap().in_flight, 0);
+        assert!(sender.send(9, b"nope").is_err());
+    }
+
+    // A socket that would block on every send
+    struct ReceiveOnly(InProcessTransport);
+
+    impl Transport for ReceiveOnly {
+        fn send(&mut self, _packet: &[u8]) -> Result<(), NetErrors> {
+            Err(NetErrors::UnableToSend("would block".to_string()))
+        }
+
+        fn receive(&mut self) -> Result<Vec<Vec<u8>>, NetErrors> {
+            self.0.receive()
+        }
+    }
+
+    #[test]
+    fn test_failed_acks_keep_messages_and_sequences_wrap() {
+        let (mut a, b) = InProcessTransport::pair();
+        let mut reciever = ChannelEndpoint::new(ReceiveOnly(b), &KINDS);
+        reciever.channels[RELIABLE as usize].next_receive_sequence = u32::MAX;
+
+        for (sequence, payload) in [(u32::MAX, b"last"), (0, b"wrap")] {
+            a.send(&encode_packet(RELIABLE, KIND_DATA, sequence, payload))
+                .unwrap();
+        }
+        assert_eq!(
+            reciever.receive().unwrap(),
+            vec![(RELIABLE, b"last".to_vec()), (RELIABLE, b"wrap".to_vec())]
+        );
+
+        // a resend of the wrapped message is a duplicate
+        a.send(&encode_packet(RELIABLE, KIND_DATA, u32::MAX, b"last"))
+            .unwrap();
+        assert!(reciever.receive().unwrap().is_empty());
+        assert_eq!(reciever.get_metrics(RELIABLE).unwrap().discarded, 1);
+    }
+
+    #[test]
+    fn test_reliable_messages_past_the_window_are_dropped() {
+        let (mut a, b) = InProcessTransport::pair();
+        let mut reciever = ChannelEndpoint::new(b, &KINDS);
+
+        let large = vec![0; MAX_BUFFERED_BYTES / 2 + 1];
+        for (sequence, payload) in [
+            (RECEIVE_WINDOW, &b"far"[..]),
+            (u32::MAX / 2, &b"farther"[..]),
+            (1, &large[..]),
+            (2, &large[..]),
+        ] {
+            a.send(&encode_packet(RELIABLE, KIND_DATA, sequence, payload))
+                .unwrap();
+        }
+        assert!(reciever.receive().unwrap().is_empty());
+        let channel = &reciever.channels[RELIABLE as usize];
+        assert_eq!(channel.out_of_order.len(), 1);
+        assert_eq!(channel.out_of_order_bytes, large.len());
+        assert_eq!(channel.metrics.discarded, 3);
+
+        a.send(&encode_packet(RELIABLE, KIND_DATA, 0, b"first"))
+            .unwrap();
+        assert_eq!(reciever.receive().unwrap().len(), 2);
+        assert_eq!(reciever.channels[RELIABLE as usize].out_of_order_bytes, 0);
+    }
+}
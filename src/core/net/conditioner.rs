@@ -0,0 +1,295 @@
+use std::time::{Duration, Instant};
+
+use log::info;
+
+use crate::math::random::Random;
+
+use super::transport::{NetErrors, Transport};
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ConditionerConfig {
+    pub latency: Duration,
+    // extra random delay in [0, jitter] added on top of the latency
+    pub jitter: Duration,
+    // 0.0 - 1.0
+    pub packet_loss: f32,
+    // 0.0 - 1.0, a reordered packet is held back so later packets overtake it
+    pub reorder_chance: f32,
+}
+
+impl ConditionerConfig {
+    pub fn is_disabled(&self) -> bool {
+        self.latency.is_zero()
+            && self.jitter.is_zero()
+            && self.packet_loss <= 0.0
+            && self.reorder_chance <= 0.0
+    }
+}
+
+#[derive(Debug)]
+struct InFlight {
+    deliver_at: Instant,
+    sequence: u64,
+    packet: Vec<u8>,
+}
+
+// Simulates one direction of a network link
+#[derive(Debug)]
+pub struct LinkSimulator {
+    config: ConditionerConfig,
+    random: Random,
+    in_flight: Vec<InFlight>,
+    last_delivery: Option<Instant>,
+    sequence: u64,
+}
+
+impl LinkSimulator {
+    pub fn new(config: ConditionerConfig, random: Random) -> Self {
+        Self {
+            config,
+            random,
+            in_flight: Vec::new(),
+            last_delivery: None,
+            sequence: 0,
+        }
+    }
+
+    pub fn set_config(&mut self, config: ConditionerConfig) {
+        self.config = config;
+    }
+
+    pub fn get_config(&self) -> &ConditionerConfig {
+        &self.config
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    // Returns false if the packet got "lost"
+    pub fn push(&mut self, packet: Vec<u8>, now: Instant) -> bool {
+        if self.random.chance(self.config.packet_loss) {
+            return false;
+        }
+
+        let jitter = self.config.jitter.mul_f32(self.random.next_f32());
+        let mut deliver_at = now + self.config.latency + jitter;
+
+        if self.random.chance(self.config.reorder_chance) {
+            deliver_at += self.config.latency.max(self.config.jitter);
+        } else {
+            // without reordering jitter must not let a packet overtake an
+            // earlier one
+            if let Some(last_delivery) = self.last_delivery {
+                deliver_at = deliver_at.max(last_delivery);
+            }
+            self.last_delivery = Some(deliver_at);
+        }
+
+        self.in_flight.push(InFlight {
+            deliver_at,
+            sequence: self.sequence,
+            packet,
+        });
+        self.sequence += 1;
+        true
+    }
+
+    pub fn pop_ready(&mut self, now: Instant) -> Vec<Vec<u8>> {
+        let (mut ready, waiting): (Vec<InFlight>, Vec<InFlight>) = self
+            .in_flight
+            .drain(..)
+            .partition(|in_flight| in_flight.deliver_at <= now);
+        self.in_flight = waiting;
+
+        ready.sort_by_key(|in_flight| (in_flight.deliver_at, in_flight.sequence));
+        ready
+            .into_iter()
+            .map(|in_flight| in_flight.packet)
+            .collect()
+    }
+}
+
+// Wraps a transport and applies latency, jitter, loss and reordering to the
+// packets going in both directions. Only meant for testing on localhost.
+#[derive(Debug)]
+pub struct ConditionedTransport<T: Transport> {
+    inner: T,
+    outbound: LinkSimulator,
+    inbound: LinkSimulator,
+}
+
+impl<T: Transport> ConditionedTransport<T> {
+    pub fn new(inner: T, config: ConditionerConfig) -> Self {
+        info!("network conditioner enabled with {:?}", config);
+        Self {
+            inner,
+            outbound: LinkSimulator::new(config.clone(), Random::from_entropy()),
+            inbound: LinkSimulator::new(config, Random::from_entropy()),
+        }
+    }
+
+    pub fn with_seed(inner: T, config: ConditionerConfig, seed: u64) -> Self {
+        Self {
+            inner,
+            outbound: LinkSimulator::new(config.clone(), Random::new(seed)),
+            inbound: LinkSimulator::new(config, Random::new(seed.wrapping_add(1))),
+        }
+    }
+
+    pub fn set_config(&mut self, config: ConditionerConfig) {
+        info!("network conditioner changed to {:?}", config);
+        self.outbound.set_config(config.clone());
+        self.inbound.set_config(config);
+    }
+
+    pub fn get_config(&self) -> &ConditionerConfig {
+        self.outbound.get_config()
+    }
+
+    pub fn get_inner(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    // Sends the outbound packets whose simulated delay has passed. Called on
+    // every send and receive, so polling the transport keeps it flowing.
+    pub fn flush(&mut self, now: Instant) -> Result<(), NetErrors> {
+        for packet in self.outbound.pop_ready(now) {
+            self.inner.send(&packet)?;
+        }
+        Ok(())
+    }
+
+    pub fn send_at(&mut self, packet: &[u8], now: Instant) -> Result<(), NetErrors> {
+        self.outbound.push(packet.to_vec(), now);
+        self.flush(now)
+    }
+
+    pub fn receive_at(&mut self, now: Instant) -> Result<Vec<Vec<u8>>, NetErrors> {
+        self.flush(now)?;
+        for packet in self.inner.receive()? {
+            self.inbound.push(packet, now);
+        }
+        Ok(self.inbound.pop_ready(now))
+    }
+}
+
+impl<T: Transport> Transport for ConditionedTransport<T> {
+    fn send(&mut self, packet: &[u8]) -> Result<(), NetErrors> {
+        self.send_at(packet, Instant::now())
+    }
+
+    fn receive(&mut self) -> Result<Vec<Vec<u8>>, NetErrors> {
+        self.receive_at(Instant::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::net::transport::InProcessTransport;
+
+    use super::*;
+
+    #[test]
+    fn test_latency_delays_delivery() {
+        let config = ConditionerConfig {
+            latency: Duration::from_millis(100),
+            ..Default::default()
+        };
+        let mut link = LinkSimulator::new(config, Random::new(1));
+        let now = Instant::now();
+
+        assert!(link.push(vec![1], now));
+        assert!(link.pop_ready(now + Duration::from_millis(99)).is_empty());
+        assert_eq!(
+            link.pop_ready(now + Duration::from_millis(100)),
+            vec![vec![1]]
+        );
+    }
+
+    #[test]
+    fn test_full_packet_loss() {
+        let config = ConditionerConfig {
+            packet_loss: 1.0,
+            ..Default::default()
+        };
+        let mut link = LinkSimulator::new(config, Random::new(1));
+        assert!(!link.push(vec![1], Instant::now()));
+        assert_eq!(link.in_flight(), 0);
+    }
+
+    #[test]
+    fn test_jitter_without_reordering_keeps_order() {
+        let config = ConditionerConfig {
+            latency: Duration::from_millis(10),
+            jitter: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let mut link = LinkSimulator::new(config, Random::new(3));
+        let now = Instant::now();
+        for i in 0..20u8 {
+            link.push(vec![i], now + Duration::from_millis(i as u64));
+        }
+
+        let delivered: Vec<u8> = link
+            .pop_ready(now + Duration::from_secs(1))
+            .into_iter()
+            .map(|packet| packet[0])
+            .collect();
+        assert_eq!(delivered, (0..20).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn test_reordered_packet_is_overtaken() {
+        let mut link = LinkSimulator::new(
+            ConditionerConfig {
+                latency: Duration::from_millis(10),
+                reorder_chance: 1.0,
+                ..Default::default()
+            },
+            Random::new(5),
+        );
+        let now = Instant::now();
+        link.push(vec![0], now);
+        link.set_config(ConditionerConfig {
+            latency: Duration::from_millis(10),
+            ..Default::default()
+        });
+        link.push(vec![1], now);
+
+        assert_eq!(
+            link.pop_ready(now + Duration::from_secs(1)),
+            vec![vec![1], vec![0]]
+        );
+    }
+
+    #[test]
+    fn test_conditioned_transport_both_directions() {
+        let config = ConditionerConfig {
+            latency: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let (a, b) = InProcessTransport::pair();
+        let mut a = ConditionedTransport::with_seed(a, config.clone(), 1);
+        let mut b = ConditionedTransport::with_seed(b, config, 2);
+        let now = Instant::now();
+
+        a.send_at(b"hello", now).unwrap();
+        assert!(b.receive_at(now).unwrap().is_empty());
+
+        // a's outbound delay has passed, b still holds it for its inbound delay
+        a.flush(now + Duration::from_millis(50)).unwrap();
+        assert!(b
+            .receive_at(now + Duration::from_millis(50))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            b.receive_at(now + Duration::from_millis(100)).unwrap(),
+            vec![b"hello".to_vec()]
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::path::Path;
+
+use log::warn;
+
+use crate::event_system::{
+    event_queue::{EventQueue, EventQueueErrors},
+    replay::ReplayErrors,
+    schema::EventSchemas,
+};
+
+use super::{
+    session_recording::SessionRecorder,
+    transport::{NetErrors, Transport},
+    wire::WireEvent,
+};
+
+// Sends and receives engine events over any packet transport
+#[derive(Debug)]
+pub struct EventTransport<T: Transport> {
+    transport: T,
+    recorder: Option<SessionRecorder>,
+    schemas: Option<EventSchemas>,
+}
+
+impl<T: Transport> EventTransport<T> {
+    pub fn new(transport: T) -> Self {
+        Self {
+            transport,
+            recorder: None,
+            schemas: None,
+        }
+    }
+
+    // Sent events are tagged with their schema version, received ones are
+    // migrated up to it
+    pub fn set_schemas(&mut self, schemas: EventSchemas) {
+        self.schemas = Some(schemas);
+    }
+
+    pub fn get_schemas(&self) -> Option<&EventSchemas> {
+        self.schemas.as_ref()
+    }
+
+    pub fn get_transport(&self) -> &T {
+        &self.transport
+    }
+
+    pub fn get_transport_mut(&mut self) -> &mut T {
+        &mut self.transport
+    }
+
+    // Records every event sent or received from now on into a replay file,
+    // replaces a recording that is already running
+    pub fn start_recording(&mut self, path: impl AsRef<Path>) -> Result<(), ReplayErrors> {
+        self.stop_recording();
+        self.recorder = Some(SessionRecorder::create(path)?);
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> Option<SessionRecorder> {
+        let mut recorder = self.recorder.take()?;
+        if let Err(err) = recorder.flush() {
+            warn!("unable to flush session recording: {}", err);
+        }
+        Some(recorder)
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    // Snapshots are not sent through here, the game hands them over so
+    // playback can resync from them
+    pub fn record_snapshot(&mut self, snapshot: &[u8]) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(err) = recorder.record_snapshot(snapshot) {
+                warn!("unable to record snapshot: {}", err);
+            }
+        }
+    }
+
+    // A failing recording is logged but never breaks the match
+    pub fn send_event(&mut self, event: &WireEvent) -> Result<(), NetErrors> {
+        let event = match self.schemas.as_ref() {
+            Some(schemas) => schemas.stamp(event.clone()),
+            None => event.clone(),
+        };
+        self.transport.send(&event.encode()?)?;
+        if let Some(recorder) = self.recorder.as_mut() {
+            if let Err(err) = recorder.record_outbound(&event) {
+                warn!("unable to record outbound event: {}", err);
+            }
+        }
+        Ok(())
+    }
+
+    // Malformed packets and events that cannot be migrated are logged and
+    // dropped, they must not take the whole connection down. Recordings keep
+    // the events as they were received.
+    pub fn receive_events(&mut self) -> Result<Vec<WireEvent>, NetErrors> {
+        let events: Vec<WireEvent> = self
+            .transport
+            .receive()?
+            .iter()
+            .filter_map(|packet| match WireEvent::decode(packet) {
+                Ok(event) => Some(event),
+                Err(err) => {
+                    warn!("dropping malformed event packet: {}", err);
+                    None
+                }
+            })
+            .collect();
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            for event in &events {
+                if let Err(err) = recorder.record_inbound(event) {
+                    warn!("unable to record inbound event: {}", err);
+                }
+            }
+        }
+        let Some(schemas) = self.schemas.as_ref() else {
+            return Ok(events);
+        };
+        Ok(events
+            .into_iter()
+            .filter_map(|event| match schemas.upgrade(event) {
+                Ok(event) => Some(event),
+                Err(err) => {
+                    warn!("dropping event: {}", err);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    // Pushes every received event onto the local queue, returns how many
+    pub fn pump_into(&mut self, queue: &EventQueue) -> Result<usize, NetErrors> {
+        let events = self.receive_events()?;
+        let count = events.len();
+        for event in events {
+            if let Err(EventQueueErrors::UnableToEmitToEventQueue(err)) =
+                queue.emit(Box::new(event))
+            {
+                return Err(NetErrors::UnableToReceive(err.to_string()));
+            }
+        }
+        Ok(count)
+    }
+}
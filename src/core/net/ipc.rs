@@ -0,0 +1,377 @@
+use std::{
+    io::{ErrorKind, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+use log::{info, warn};
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::event::Event,
+};
+
+use super::{
+    event_transport::EventTransport,
+    transport::{NetErrors, Transport},
+    wire::WireEvent,
+};
+
+// Frames bigger than this are treated as garbage instead of allocated
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+// Packets over a byte stream, each one prefixed with its length:
+//
+// [len: u32 LE][packet]
+//
+// The stream has to be non blocking. Writes that would block are kept and
+// retried on the next send or receive.
+#[derive(Debug)]
+pub struct StreamTransport<S: Read + Write> {
+    stream: S,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl<S: Read + Write> StreamTransport<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        }
+    }
+
+    pub fn get_stream(&self) -> &S {
+        &self.stream
+    }
+
+    fn flush(&mut self) -> Result<(), NetErrors> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(NetErrors::Disconnected),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) if err.kind() == ErrorKind::BrokenPipe => {
+                    return Err(NetErrors::Disconnected)
+                }
+                Err(err) => return Err(NetErrors::UnableToSend(err.to_string())),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S: Read + Write> Transport for StreamTransport<S> {
+    fn send(&mut self, packet: &[u8]) -> Result<(), NetErrors> {
+        if packet.len() > MAX_FRAME_SIZE {
+            return Err(NetErrors::UnableToSend(format!(
+                "packet of {} bytes is too big",
+                packet.len()
+            )));
+        }
+        self.outgoing
+            .extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        self.outgoing.extend_from_slice(packet);
+        self.flush()
+    }
+
+    fn receive(&mut self) -> Result<Vec<Vec<u8>>, NetErrors> {
+        self.flush()?;
+
+        let mut closed = false;
+        let mut buffer = [0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => {
+                    closed = true;
+                    break;
+                }
+                Ok(len) => self.incoming.extend_from_slice(&buffer[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => {
+                    closed = true;
+                    break;
+                }
+                Err(err) => return Err(NetErrors::UnableToReceive(err.to_string())),
+            }
+        }
+
+        let mut packets = Vec::new();
+        while self.incoming.len() >= 4 {
+            let len = u32::from_le_bytes([
+                self.incoming[0],
+                self.incoming[1],
+                self.incoming[2],
+                self.incoming[3],
+            ]) as usize;
+            if len > MAX_FRAME_SIZE {
+                return Err(NetErrors::MalformedPacket(format!(
+                    "frame of {} bytes is too big",
+                    len
+                )));
+            }
+            if self.incoming.len() < 4 + len {
+                break;
+            }
+            packets.push(self.incoming[4..4 + len].to_vec());
+            self.incoming.drain(..4 + len);
+        }
+
+        if closed && packets.is_empty() {
+            return Err(NetErrors::Disconnected);
+        }
+        Ok(packets)
+    }
+}
+
+// Unix domain sockets where there are some, a loopback TCP port everywhere
+// else (std has no named pipes). The name is the socket path or the
+// `127.0.0.1:<port>` address.
+#[cfg(unix)]
+pub type IpcStream = std::os::unix::net::UnixStream;
+#[cfg(not(unix))]
+pub type IpcStream = std::net::TcpStream;
+
+#[cfg(unix)]
+type IpcSocket = std::os::unix::net::UnixListener;
+#[cfg(not(unix))]
+type IpcSocket = std::net::TcpListener;
+
+pub type IpcTransport = StreamTransport<IpcStream>;
+
+impl IpcTransport {
+    pub fn connect(name: &str) -> Result<Self, NetErrors> {
+        let stream =
+            IpcStream::connect(name).map_err(|err| NetErrors::UnableToSend(err.to_string()))?;
+        stream
+            .set_nonblocking(true)
+            .map_err(|err| NetErrors::UnableToSend(err.to_string()))?;
+        Ok(Self::new(stream))
+    }
+}
+
+#[derive(Debug)]
+pub struct IpcListener {
+    name: String,
+    socket: IpcSocket,
+}
+
+impl IpcListener {
+    pub fn bind(name: &str) -> Result<Self, NetErrors> {
+        // a socket file left behind by a crashed run would make bind fail
+        #[cfg(unix)]
+        if std::os::unix::net::UnixStream::connect(name).is_err() {
+            let _ = std::fs::remove_file(name);
+        }
+        let socket =
+            IpcSocket::bind(name).map_err(|err| NetErrors::UnableToReceive(err.to_string()))?;
+        socket
+            .set_nonblocking(true)
+            .map_err(|err| NetErrors::UnableToReceive(err.to_string()))?;
+        Ok(Self {
+            name: name.to_string(),
+            socket,
+        })
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    // Non blocking, None when nobody is waiting to connect
+    pub fn accept(&self) -> Result<Option<IpcTransport>, NetErrors> {
+        match self.socket.accept() {
+            Ok((stream, _)) => {
+                stream
+                    .set_nonblocking(true)
+                    .map_err(|err| NetErrors::UnableToReceive(err.to_string()))?;
+                Ok(Some(IpcTransport::new(stream)))
+            }
+            Err(err) if err.kind() == ErrorKind::WouldBlock => Ok(None),
+            Err(err) => Err(NetErrors::UnableToReceive(err.to_string())),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Drop for IpcListener {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.name);
+    }
+}
+
+// WireEvents keep their payload, other events are sent with their debug
+// output so tools can at least show them
+pub fn to_wire_event(event: &dyn Event) -> WireEvent {
+    let payload = event
+        .get_data()
+        .and_then(|data| data.get_ref::<Vec<u8>>().cloned())
+        .unwrap_or_else(|| format!("{:?}", event).into_bytes());
+    WireEvent::new(&event.get_name(), payload)
+}
+
+// Exposes the event queue to other processes on the same machine, e.g. a
+// level editor or a test driver. Every event a tool sends is emitted on the
+// queue as a WireEvent, events handed to the observer go out to every
+// connected tool. Same framing as the network transport, so tools can
+// share its code.
+//
+//     let bridge = IpcBridge::bind("/tmp/game.sock")?;
+//     app.on_event("PlayerDied".to_string(), bridge.observer());
+//     app.get_engine().add_subsystem(bridge)?;
+pub struct IpcBridge {
+    listener: IpcListener,
+    clients: Vec<EventTransport<IpcTransport>>,
+    outbox: Arc<Mutex<Vec<WireEvent>>>,
+}
+
+impl IpcBridge {
+    pub fn bind(name: &str) -> Result<Self, NetErrors> {
+        Ok(Self {
+            listener: IpcListener::bind(name)?,
+            clients: Vec::new(),
+            outbox: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    pub fn get_name(&self) -> &str {
+        self.listener.get_name()
+    }
+
+    pub fn get_client_count(&self) -> usize {
+        self.clients.len()
+    }
+
+    // An event handler that forwards the events it sees to the tools on the
+    // next tick
+    pub fn observer(&self) -> impl Fn(&dyn Event) + Send + Sync + 'static {
+        let outbox = Arc::clone(&self.outbox);
+        move |event| {
+            if let Ok(mut outbox) = outbox.lock() {
+                outbox.push(to_wire_event(event));
+            }
+        }
+    }
+
+    pub fn send(&mut self, event: &WireEvent) {
+        self.clients
+            .retain_mut(|client| match client.send_event(event) {
+                Ok(()) => true,
+                Err(err) => {
+                    info!("dropping ipc client: {}", err);
+                    false
+                }
+            });
+    }
+}
+
+impl Subsystem for IpcBridge {
+    fn get_name(&self) -> &str {
+        "IpcBridge"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        info!("ipc bridge listening on {}", self.listener.get_name());
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        loop {
+            match self.listener.accept() {
+                Ok(Some(client)) => {
+                    info!("ipc client connected");
+                    self.clients.push(EventTransport::new(client));
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    warn!("unable to accept ipc client: {}", err);
+                    break;
+                }
+            }
+        }
+
+        self.clients
+            .retain_mut(|client| match client.pump_into(ctx.event_queue) {
+                Ok(_) => true,
+                Err(err) => {
+                    info!("dropping ipc client: {}", err);
+                    false
+                }
+            });
+
+        let outgoing: Vec<WireEvent> = match self.outbox.lock() {
+            Ok(mut outbox) => outbox.drain(..).collect(),
+            Err(_) => Vec::new(),
+        };
+        for event in outgoing {
+            self.send(&event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        core::{runner::exit_handlers::ExitHandlers, time::Time},
+        event_system::event_queue::EventQueue,
+    };
+
+    #[test]
+    fn test_frames_split_across_reads() {
+        let mut bytes = Vec::new();
+        {
+            let mut writer = StreamTransport::new(std::io::Cursor::new(&mut bytes));
+            writer.send(b"hello").unwrap();
+            writer.send(b"").unwrap();
+        }
+        // only the first frame and part of the next
+        let mut reader = StreamTransport::new(std::io::Cursor::new(bytes[..11].to_vec()));
+        assert_eq!(reader.receive().unwrap(), vec![b"hello".to_vec()]);
+        assert_eq!(reader.receive(), Err(NetErrors::Disconnected));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_bridge_round_trip() {
+        let path = std::env::temp_dir().join(format!("aloy-ipc-test-{}.sock", std::process::id()));
+        let name = path.to_str().unwrap();
+        let mut bridge = IpcBridge::bind(name).unwrap();
+        let mut tool = EventTransport::new(IpcTransport::connect(name).unwrap());
+
+        let queue = Arc::new(EventQueue::new());
+        let time = Time::default();
+        let exit_handlers = ExitHandlers::default();
+        let mut ctx = SubsystemContext {
+            event_queue: &queue,
+            time: &time,
+            exit_handlers: &exit_handlers,
+        };
+
+        tool.send_event(&WireEvent::new("SpawnEnemy", vec![7]))
+            .unwrap();
+        bridge.tick(&mut ctx);
+        assert_eq!(bridge.get_client_count(), 1);
+        let events = queue.get_events().unwrap();
+        assert_eq!(events[0].get_name(), "SpawnEnemy");
+
+        let observer = bridge.observer();
+        observer(&WireEvent::new("PlayerDied", vec![1, 2]));
+        bridge.tick(&mut ctx);
+        let mut received = Vec::new();
+        for _ in 0..100 {
+            received.extend(tool.receive_events().unwrap());
+            if !received.is_empty() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(received, vec![WireEvent::new("PlayerDied", vec![1, 2])]);
+    }
+}
@@ -0,0 +1,10 @@
+pub mod channels;
+pub mod conditioner;
+pub mod event_transport;
+pub mod ipc;
+pub mod session_recording;
+#[cfg(feature = "debug_http")]
+pub mod status_server;
+pub mod transport;
+pub mod udp;
+pub mod wire;
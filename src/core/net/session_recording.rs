@@ -0,0 +1,206 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::Duration,
+};
+
+use log::{error, warn};
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::replay::{ReplayErrors, ReplayPlayer, ReplayWriter},
+};
+
+use super::wire::WireEvent;
+
+// Replay channels used by session recordings
+pub const INBOUND_CHANNEL: u8 = 0;
+pub const OUTBOUND_CHANNEL: u8 = 1;
+pub const SNAPSHOT_CHANNEL: u8 = 2;
+
+// Name snapshots are emitted under during playback, the payload is the raw
+// snapshot as the game handed it to the recorder
+pub const SNAPSHOT_EVENT_NAME: &str = "NetSnapshot";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionRecord {
+    Inbound(WireEvent),
+    Outbound(WireEvent),
+    Snapshot(Vec<u8>),
+}
+
+impl SessionRecord {
+    // Every record is replayed as an event, snapshots included
+    pub fn into_event(self) -> WireEvent {
+        match self {
+            SessionRecord::Inbound(event) | SessionRecord::Outbound(event) => event,
+            SessionRecord::Snapshot(snapshot) => WireEvent::new(SNAPSHOT_EVENT_NAME, snapshot),
+        }
+    }
+}
+
+// Writes the replicated traffic of one networked session into a replay file
+#[derive(Debug)]
+pub struct SessionRecorder<W: Write = BufWriter<File>> {
+    writer: ReplayWriter<W>,
+}
+
+impl SessionRecorder {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, ReplayErrors> {
+        Ok(Self {
+            writer: ReplayWriter::create(path)?,
+        })
+    }
+}
+
+impl<W: Write> SessionRecorder<W> {
+    pub fn new(writer: W) -> Result<Self, ReplayErrors> {
+        Ok(Self {
+            writer: ReplayWriter::new(writer)?,
+        })
+    }
+
+    pub fn record_inbound(&mut self, event: &WireEvent) -> Result<(), ReplayErrors> {
+        self.writer.record(INBOUND_CHANNEL, event)
+    }
+
+    pub fn record_outbound(&mut self, event: &WireEvent) -> Result<(), ReplayErrors> {
+        self.writer.record(OUTBOUND_CHANNEL, event)
+    }
+
+    pub fn record_snapshot(&mut self, snapshot: &[u8]) -> Result<(), ReplayErrors> {
+        self.writer.record(
+            SNAPSHOT_CHANNEL,
+            &WireEvent::new(SNAPSHOT_EVENT_NAME, snapshot.to_vec()),
+        )
+    }
+
+    pub fn get_record_count(&self) -> u64 {
+        self.writer.get_entry_count()
+    }
+
+    pub fn flush(&mut self) -> Result<(), ReplayErrors> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> Result<W, ReplayErrors> {
+        self.writer.into_inner()
+    }
+}
+
+// Plays a recorded session back on its original timeline. Registered as a
+// subsystem it pushes every record onto the event queue, so the match can be
+// reconstructed locally without a connection, for spectating or bug analysis.
+#[derive(Debug)]
+pub struct SessionPlayback {
+    player: ReplayPlayer,
+}
+
+impl SessionPlayback {
+    pub fn new(player: ReplayPlayer) -> Self {
+        Self { player }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ReplayErrors> {
+        Ok(Self::new(ReplayPlayer::open(path)?))
+    }
+
+    pub fn get_position(&self) -> Duration {
+        self.player.get_position()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.player.is_finished()
+    }
+
+    pub fn advance(&mut self, delta: Duration) -> Vec<SessionRecord> {
+        self.player
+            .advance(delta)
+            .into_iter()
+            .filter_map(|entry| match entry.channel {
+                INBOUND_CHANNEL => Some(SessionRecord::Inbound(entry.event)),
+                OUTBOUND_CHANNEL => Some(SessionRecord::Outbound(entry.event)),
+                SNAPSHOT_CHANNEL => Some(SessionRecord::Snapshot(entry.event.payload)),
+                channel => {
+                    warn!("skipping record on unknown channel {}", channel);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+impl Subsystem for SessionPlayback {
+    fn get_name(&self) -> &str {
+        "SessionPlayback"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        for record in self.advance(ctx.time.get_delta()) {
+            if let Err(err) = ctx.event_queue.emit(Box::new(record.into_event())) {
+                error!("unable to emit recorded event {:?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        core::net::{event_transport::EventTransport, transport::InProcessTransport},
+        event_system::replay::read_replay,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_recorded_session_plays_back() {
+        let path = std::env::temp_dir().join(format!(
+            "aloy-session-recording-test-{}.rpl",
+            std::process::id()
+        ));
+        let (a, b) = InProcessTransport::pair();
+        let mut local = EventTransport::new(a);
+        let mut remote = EventTransport::new(b);
+        local.start_recording(&path).unwrap();
+
+        local.send_event(&WireEvent::new("Move", vec![1])).unwrap();
+        remote.send_event(&WireEvent::new("Hit", vec![2])).unwrap();
+        local.receive_events().unwrap();
+        local.record_snapshot(&[7, 7]);
+        let recorder = local.stop_recording().unwrap();
+        assert_eq!(recorder.get_record_count(), 3);
+        drop(recorder);
+
+        let mut playback = SessionPlayback::open(&path).unwrap();
+        let records = playback.advance(Duration::from_secs(60));
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(
+            records,
+            vec![
+                SessionRecord::Outbound(WireEvent::new("Move", vec![1])),
+                SessionRecord::Inbound(WireEvent::new("Hit", vec![2])),
+                SessionRecord::Snapshot(vec![7, 7]),
+            ]
+        );
+        assert!(playback.is_finished());
+    }
+
+    #[test]
+    fn test_recorder_writes_a_replay() {
+        let mut recorder = SessionRecorder::new(Vec::new()).unwrap();
+        recorder
+            .record_outbound(&WireEvent::new("Move", vec![]))
+            .unwrap();
+        let entries = read_replay(recorder.into_inner().unwrap().as_slice()).unwrap();
+
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].channel, OUTBOUND_CHANNEL);
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    collections::BTreeMap,
+    io::{ErrorKind, Read, Write},
+    net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    core::bug_report::{EventJournal, JournalEntry},
+    event_system::event::Event,
+};
+
+// Events kept for /events/recent
+const RECENT_EVENTS: usize = 100;
+// Requests are one line and a few headers, anything bigger is dropped
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum StatusServerErrors {
+    #[error("unable to listen on port {0}: {1}")]
+    Bind(u16, String),
+
+    #[error("unable to start the status server: {0}")]
+    Spawn(String),
+}
+
+// What /stats.json reports, published by the engine every frame
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct EngineStatus {
+    pub frame: u64,
+    pub uptime_seconds: f64,
+    pub fps: f64,
+    pub frame_time_ms: f64,
+    pub paused: bool,
+    pub run_mode: String,
+    pub subsystems: Vec<String>,
+    // set by the game, e.g. connected players or the current map's tick
+    pub stats: BTreeMap<String, f64>,
+}
+
+#[derive(Debug)]
+struct StatusShared {
+    status: EngineStatus,
+    stats: BTreeMap<String, f64>,
+    journal: EventJournal,
+    last_publish: Option<Instant>,
+    stall_timeout: Duration,
+}
+
+impl StatusShared {
+    fn respond(&self, path: &str) -> (u16, &'static str, String) {
+        match path {
+            "/healthz" => match self.last_publish {
+                Some(at) if at.elapsed() <= self.stall_timeout => {
+                    (200, "text/plain", "ok\n".to_string())
+                }
+                // the loop stopped publishing, most likely stuck in a frame
+                Some(_) => (503, "text/plain", "stalled\n".to_string()),
+                None => (503, "text/plain", "starting\n".to_string()),
+            },
+            "/stats.json" => {
+                let mut status = self.status.clone();
+                status.stats.extend(self.stats.clone());
+                (200, "application/json", to_json(&status))
+            }
+            "/events/recent" => {
+                let events: Vec<JournalEntry> = self.journal.get_tail(RECENT_EVENTS);
+                (200, "application/json", to_json(&events))
+            }
+            _ => (404, "text/plain", "not found\n".to_string()),
+        }
+    }
+}
+
+fn to_json(value: &impl Serialize) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "null".to_string())
+}
+
+// A tiny HTTP endpoint on localhost for health checks of headless servers:
+//
+//     GET /healthz        200 while the loop runs, 503 once it stalls
+//     GET /stats.json     the last published EngineStatus
+//     GET /events/recent  the latest dispatched events, oldest first
+//
+// Requests are answered on a thread of its own so a check never waits for
+// a frame, the engine only publishes its status into it.
+#[derive(Debug)]
+pub struct StatusServer {
+    address: SocketAddr,
+    shared: Arc<Mutex<StatusShared>>,
+    stopping: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl StatusServer {
+    // Listens on 127.0.0.1, port 0 picks a free one
+    pub fn start(port: u16) -> Result<Self, StatusServerErrors> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))
+            .map_err(|err| StatusServerErrors::Bind(port, err.to_string()))?;
+        let address = listener
+            .local_addr()
+            .map_err(|err| StatusServerErrors::Bind(port, err.to_string()))?;
+        // polled so the thread notices when it has to stop
+        listener
+            .set_nonblocking(true)
+            .map_err(|err| StatusServerErrors::Bind(port, err.to_string()))?;
+
+        let shared = Arc::new(Mutex::new(StatusShared {
+            status: EngineStatus::default(),
+            stats: BTreeMap::new(),
+            journal: EventJournal::new(RECENT_EVENTS),
+            last_publish: None,
+            stall_timeout: Duration::from_secs(5),
+        }));
+        let stopping = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let shared = shared.clone();
+            let stopping = stopping.clone();
+            thread::Builder::new()
+                .name("status server".to_string())
+                .spawn(move || Self::serve(listener, shared, stopping))
+                .map_err(|err| StatusServerErrors::Spawn(err.to_string()))?
+        };
+        info!("status server listening on http://{}", address);
+
+        Ok(Self {
+            address,
+            shared,
+            stopping,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn get_address(&self) -> SocketAddr {
+        self.address
+    }
+
+    // How long the engine can go without publishing before /healthz fails
+    pub fn set_stall_timeout(&mut self, stall_timeout: Duration) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.stall_timeout = stall_timeout;
+        }
+    }
+
+    pub fn publish(&self, status: EngineStatus) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.status = status;
+            shared.last_publish = Some(Instant::now());
+        }
+    }
+
+    pub fn set_stat(&self, name: &str, value: f64) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.stats.insert(name.to_string(), value);
+        }
+    }
+
+    pub fn remove_stat(&self, name: &str) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.stats.remove(name);
+        }
+    }
+
+    pub fn record_event(&self, frame: u64, time: Duration, event: &dyn Event) {
+        if let Ok(mut shared) = self.shared.lock() {
+            shared.journal.record(frame, time, event);
+        }
+    }
+
+    fn serve(listener: TcpListener, shared: Arc<Mutex<StatusShared>>, stopping: Arc<AtomicBool>) {
+        while !stopping.load(Ordering::Relaxed) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Err(err) = Self::answer(stream, &shared) {
+                        warn!("unable to answer status request {}", err);
+                    }
+                }
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(err) => {
+                    warn!("unable to accept status request {}", err);
+                    thread::sleep(Duration::from_millis(10));
+                }
+            }
+        }
+    }
+
+    fn answer(mut stream: TcpStream, shared: &Mutex<StatusShared>) -> std::io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+        stream.set_write_timeout(Some(Duration::from_secs(1)))?;
+
+        let mut request = Vec::new();
+        let mut chunk = [0; 1024];
+        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+            let read = stream.read(&mut chunk)?;
+            if read == 0 || request.len() + read > MAX_REQUEST_SIZE {
+                return Ok(());
+            }
+            request.extend_from_slice(&chunk[..read]);
+        }
+
+        let request = String::from_utf8_lossy(&request);
+        let mut parts = request.lines().next().unwrap_or("").split_whitespace();
+        let (status, content_type, body) = match (parts.next(), parts.next()) {
+            (Some("GET"), Some(target)) => {
+                let path = target.split('?').next().unwrap_or(target);
+                match shared.lock() {
+                    Ok(shared) => shared.respond(path),
+                    Err(_) => (500, "text/plain", "poisoned\n".to_string()),
+                }
+            }
+            _ => (405, "text/plain", "only GET is supported\n".to_string()),
+        };
+        let reason = match status {
+            200 => "OK",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            503 => "Service Unavailable",
+            _ => "Internal Server Error",
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            reason,
+            content_type,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+
+    pub fn stop(&mut self) {
+        self.stopping.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                warn!("status server thread panicked");
+            }
+            info!("status server on {} stopped", self.address);
+        }
+    }
+}
+
+impl Drop for StatusServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::net::wire::WireEvent;
+
+    fn get(address: SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serves_health_and_stats() {
+        let mut server = StatusServer::start(0).unwrap();
+        let address = server.get_address();
+        assert!(get(address, "/healthz").starts_with("HTTP/1.1 503"));
+
+        server.publish(EngineStatus {
+            frame: 42,
+            run_mode: "Headless".to_string(),
+            ..Default::default()
+        });
+        server.set_stat("players", 3.0);
+        server.record_event(
+            42,
+            Duration::from_secs(1),
+            &WireEvent::new("PlayerJoined", vec![]),
+        );
+        let health = get(address, "/healthz");
+        assert!(health.starts_with("HTTP/1.1 200 OK"));
+        assert!(health.ends_with("\r\n\r\nok\n"));
+
+        let stats = get(address, "/stats.json");
+        let body = stats.split("\r\n\r\n").nth(1).unwrap();
+        let stats: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(stats["frame"], 42);
+        assert_eq!(stats["run_mode"], "Headless");
+        assert_eq!(stats["stats"]["players"], 3.0);
+
+        let events = get(address, "/events/recent");
+        assert!(events.contains("\"name\":\"PlayerJoined\""));
+        assert!(get(address, "/metrics").starts_with("HTTP/1.1 404"));
+
+        // nothing published within the timeout, the loop looks stuck
+        server.set_stall_timeout(Duration::ZERO);
+        thread::sleep(Duration::from_millis(5));
+        assert!(get(address, "/healthz").starts_with("HTTP/1.1 503"));
+
+        server.stop();
+        assert!(TcpStream::connect(address).is_err());
+    }
+}
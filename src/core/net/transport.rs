@@ -0,0 +1,93 @@
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum NetErrors {
+    #[error("the remote end of the transport is disconnected")]
+    Disconnected,
+
+    #[error("unable to send packet: {0}")]
+    UnableToSend(String),
+
+    #[error("unable to receive packets: {0}")]
+    UnableToReceive(String),
+
+    #[error("malformed packet: {0}")]
+    MalformedPacket(String),
+
+    #[error("packet too large: {0}")]
+    TooLarge(String),
+}
+
+// A packet transport between this engine instance and one peer. Both calls
+// are non blocking, `receive` returns whatever arrived since the last call.
+pub trait Transport {
+    fn send(&mut self, packet: &[u8]) -> Result<(), NetErrors>;
+    fn receive(&mut self) -> Result<Vec<Vec<u8>>, NetErrors>;
+}
+
+// Both ends live in the same process, used for tests and for running several
+// engine instances side by side without sockets.
+#[derive(Debug)]
+pub struct InProcessTransport {
+    sender: Sender<Vec<u8>>,
+    reciever: Receiver<Vec<u8>>,
+}
+
+impl InProcessTransport {
+    pub fn pair() -> (Self, Self) {
+        let (a_sender, b_reciever) = mpsc::channel();
+        let (b_sender, a_reciever) = mpsc::channel();
+        (
+            Self {
+                sender: a_sender,
+                reciever: a_reciever,
+            },
+            Self {
+                sender: b_sender,
+                reciever: b_reciever,
+            },
+        )
+    }
+}
+
+impl Transport for InProcessTransport {
+    fn send(&mut self, packet: &[u8]) -> Result<(), NetErrors> {
+        self.sender
+            .send(packet.to_vec())
+            .map_err(|_| NetErrors::Disconnected)
+    }
+
+    fn receive(&mut self) -> Result<Vec<Vec<u8>>, NetErrors> {
+        let mut packets = Vec::new();
+        loop {
+            match self.reciever.try_recv() {
+                Ok(packet) => packets.push(packet),
+                Err(TryRecvError::Empty) => return Ok(packets),
+                Err(TryRecvError::Disconnected) if !packets.is_empty() => return Ok(packets),
+                Err(TryRecvError::Disconnected) => return Err(NetErrors::Disconnected),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_in_process_pair() {
+        let (mut a, mut b) = InProcessTransport::pair();
+        a.send(b"ping").unwrap();
+        b.send(b"pong").unwrap();
+
+        assert_eq!(b.receive().unwrap(), vec![b"ping".to_vec()]);
+        assert_eq!(a.receive().unwrap(), vec![b"pong".to_vec()]);
+        assert!(a.receive().unwrap().is_empty());
+
+        drop(b);
+        assert_eq!(a.send(b"lost"), Err(NetErrors::Disconnected));
+        assert_eq!(a.receive(), Err(NetErrors::Disconnected));
+    }
+}
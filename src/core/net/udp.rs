@@ -0,0 +1,75 @@
+use std::{
+    io::ErrorKind,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+};
+
+use log::warn;
+
+use super::transport::{NetErrors, Transport};
+
+// Largest payload that fits a single UDP datagram
+pub const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+// Non blocking UDP connection to a single peer. Datagrams from any other
+// address are ignored.
+#[derive(Debug)]
+pub struct UdpTransport {
+    socket: UdpSocket,
+    peer: SocketAddr,
+}
+
+impl UdpTransport {
+    pub fn bind(local: impl ToSocketAddrs, peer: SocketAddr) -> Result<Self, NetErrors> {
+        let socket =
+            UdpSocket::bind(local).map_err(|err| NetErrors::UnableToSend(err.to_string()))?;
+        socket
+            .set_nonblocking(true)
+            .map_err(|err| NetErrors::UnableToSend(err.to_string()))?;
+        Ok(Self { socket, peer })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, NetErrors> {
+        self.socket
+            .local_addr()
+            .map_err(|err| NetErrors::UnableToReceive(err.to_string()))
+    }
+
+    pub fn get_peer(&self) -> SocketAddr {
+        self.peer
+    }
+
+    pub fn set_peer(&mut self, peer: SocketAddr) {
+        self.peer = peer;
+    }
+}
+
+impl Transport for UdpTransport {
+    fn send(&mut self, packet: &[u8]) -> Result<(), NetErrors> {
+        if packet.len() > MAX_DATAGRAM_SIZE {
+            return Err(NetErrors::UnableToSend(format!(
+                "packet of {} bytes does not fit a datagram",
+                packet.len()
+            )));
+        }
+        self.socket
+            .send_to(packet, self.peer)
+            .map(|_| ())
+            .map_err(|err| NetErrors::UnableToSend(err.to_string()))
+    }
+
+    fn receive(&mut self) -> Result<Vec<Vec<u8>>, NetErrors> {
+        let mut packets = Vec::new();
+        let mut buffer = vec![0; MAX_DATAGRAM_SIZE];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, from)) if from == self.peer => packets.push(buffer[..len].to_vec()),
+                Ok((_, from)) => warn!("ignoring datagram from unknown peer {}", from),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => return Ok(packets),
+                // ICMP port unreachable shows up here on some platforms, the
+                // peer might just not be up yet
+                Err(err) if err.kind() == ErrorKind::ConnectionReset => continue,
+                Err(err) => return Err(NetErrors::UnableToReceive(err.to_string())),
+            }
+        }
+    }
+}
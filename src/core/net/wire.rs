@@ -0,0 +1,162 @@
+use std::any::Any;
+
+use crate::event_system::event::{DynamicStore, Event};
+
+use super::transport::NetErrors;
+
+pub const VERSION_SEPARATOR: char = '@';
+
+// An event as it travels over the network. The payload is encoded by the
+// game, the engine only frames it:
+//
+// [name len: u16 LE][name: utf8][payload len: u32 LE][payload]
+#[derive(Debug, Clone, PartialEq)]
+pub struct WireEvent {
+    pub name: String,
+    pub payload: Vec<u8>,
+}
+
+impl WireEvent {
+    pub fn new(name: &str, payload: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            payload,
+        }
+    }
+
+    // Events whose payload layout changed carry their schema version in the
+    // name, "PlayerMoved@2". Builds from before versioning send the bare
+    // name, which reads as version 1.
+    pub fn versioned(name: &str, version: u16, payload: Vec<u8>) -> Self {
+        match version {
+            0 | 1 => Self::new(name, payload),
+            _ => Self::new(
+                &format!("{}{}{}", name, VERSION_SEPARATOR, version),
+                payload,
+            ),
+        }
+    }
+
+    pub fn get_base_name(&self) -> &str {
+        self.split_version().0
+    }
+
+    pub fn get_version(&self) -> u16 {
+        self.split_version().1
+    }
+
+    fn split_version(&self) -> (&str, u16) {
+        match self.name.rsplit_once(VERSION_SEPARATOR) {
+            Some((name, version)) => match version.parse() {
+                Ok(version) => (name, version),
+                Err(_) => (&self.name, 1),
+            },
+            None => (&self.name, 1),
+        }
+    }
+
+    // Fails for names and payloads longer than their length prefix can say
+    pub fn encode(&self) -> Result<Vec<u8>, NetErrors> {
+        let name_len = u16::try_from(self.name.len())
+            .map_err(|_| NetErrors::TooLarge(format!("event name of {} bytes", self.name.len())))?;
+        let payload_len = u32::try_from(self.payload.len()).map_err(|_| {
+            NetErrors::TooLarge(format!(
+                "{} payload of {} bytes",
+                self.name,
+                self.payload.len()
+            ))
+        })?;
+
+        let mut bytes = Vec::with_capacity(6 + self.name.len() + self.payload.len());
+        bytes.extend_from_slice(&name_len.to_le_bytes());
+        bytes.extend_from_slice(self.name.as_bytes());
+        bytes.extend_from_slice(&payload_len.to_le_bytes());
+        bytes.extend_from_slice(&self.payload);
+        Ok(bytes)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, NetErrors> {
+        let mut reader = ByteReader::new(bytes);
+        let name_len = u16::from_le_bytes(reader.take_array()?) as usize;
+        let name = String::from_utf8(reader.take(name_len)?.to_vec())
+            .map_err(|err| NetErrors::MalformedPacket(err.to_string()))?;
+        let payload_len = u32::from_le_bytes(reader.take_array()?) as usize;
+        let payload = reader.take(payload_len)?.to_vec();
+
+        if !reader.is_empty() {
+            return Err(NetErrors::MalformedPacket("trailing bytes".to_string()));
+        }
+        Ok(Self { name, payload })
+    }
+}
+
+impl Event for WireEvent {
+    fn get_name(&self) -> String {
+        self.name.clone()
+    }
+
+    // Handlers get the raw payload bytes and decode them themselves
+    fn get_data(&self) -> Option<DynamicStore> {
+        let wrapped = Box::new(self.payload.clone()) as Box<dyn Any>;
+        Some(DynamicStore::new(wrapped))
+    }
+}
+
+pub(crate) struct ByteReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    pub(crate) fn take(&mut self, len: usize) -> Result<&'a [u8], NetErrors> {
+        if self.bytes.len() < len {
+            return Err(NetErrors::MalformedPacket(format!(
+                "expected {} more bytes, got {}",
+                len,
+                self.bytes.len()
+            )));
+        }
+        let (taken, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub(crate) fn take_array<const N: usize>(&mut self) -> Result<[u8; N], NetErrors> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.take(N)?);
+        Ok(array)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_roundtrip() {
+        let event = WireEvent::new("PlayerMoved", vec![1, 2, 3]);
+        assert_eq!(WireEvent::decode(&event.encode().unwrap()).unwrap(), event);
+
+        let long_name = WireEvent::new(&"a".repeat(u16::MAX as usize + 1), Vec::new());
+        assert!(matches!(long_name.encode(), Err(NetErrors::TooLarge(_))));
+    }
+
+    #[test]
+    fn test_decode_truncated_packet() {
+        let mut bytes = WireEvent::new("PlayerMoved", vec![1, 2, 3])
+            .encode()
+            .unwrap();
+        bytes.pop();
+        assert!(matches!(
+            WireEvent::decode(&bytes),
+            Err(NetErrors::MalformedPacket(_))
+        ));
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::warn;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PathErrors {
+    #[error("unable to create {0}: {1}")]
+    CreateFailed(PathBuf, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PathKind {
+    Saves,
+    Settings,
+    Logs,
+    // anything that can be thrown away and rebuilt, e.g. compiled shaders
+    Cache,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    // and the BSDs, anything following the XDG base directories
+    Linux,
+    Windows,
+    MacOs,
+    // the browser's storage, paths are the keys the storage backend files
+    // things under
+    Web,
+}
+
+impl Platform {
+    pub fn current() -> Self {
+        if cfg!(target_arch = "wasm32") {
+            Self::Web
+        } else if cfg!(target_os = "windows") {
+            Self::Windows
+        } else if cfg!(target_os = "macos") {
+            Self::MacOs
+        } else {
+            Self::Linux
+        }
+    }
+}
+
+// Where a game keeps its files on the platform it runs on:
+//
+// Linux    ~/.local/share/<app>             saves
+//          ~/.config/<app>                  settings
+//          ~/.local/state/<app>/logs        logs
+//          ~/.cache/<app>                   cache
+// Windows  %APPDATA%\<app>\Saves, Settings
+//          %LOCALAPPDATA%\<app>\Logs, Cache
+// macOS    ~/Library/Application Support/<app>/Saves, Settings
+//          ~/Library/Logs/<app>, ~/Library/Caches/<app>
+// Web      /<app>/saves, /<app>/settings...
+//
+// The XDG variables are honoured. Without a home directory, or with a root
+// set for portable installs and tests, everything goes in one directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppPaths {
+    app_name: String,
+    platform: Platform,
+    root: Option<PathBuf>,
+}
+
+impl AppPaths {
+    pub fn new(app_name: &str) -> Self {
+        Self {
+            app_name: app_name.to_string(),
+            platform: Platform::current(),
+            root: None,
+        }
+    }
+
+    pub fn with_platform(mut self, platform: Platform) -> Self {
+        self.platform = platform;
+        self
+    }
+
+    // Keeps every directory under `root`, e.g. next to the executable
+    pub fn with_root(mut self, root: impl AsRef<Path>) -> Self {
+        self.root = Some(root.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn get_app_name(&self) -> &str {
+        &self.app_name
+    }
+
+    pub fn get_platform(&self) -> Platform {
+        self.platform
+    }
+
+    pub fn get(&self, kind: PathKind) -> PathBuf {
+        self.resolve(kind, |name| std::env::var_os(name).map(PathBuf::from))
+    }
+
+    pub fn get_saves_dir(&self) -> PathBuf {
+        self.get(PathKind::Saves)
+    }
+
+    pub fn get_settings_dir(&self) -> PathBuf {
+        self.get(PathKind::Settings)
+    }
+
+    pub fn get_logs_dir(&self) -> PathBuf {
+        self.get(PathKind::Logs)
+    }
+
+    pub fn get_cache_dir(&self) -> PathBuf {
+        self.get(PathKind::Cache)
+    }
+
+    // The directory, created if it is not there yet
+    pub fn ensure(&self, kind: PathKind) -> Result<PathBuf, PathErrors> {
+        let dir = self.get(kind);
+        fs::create_dir_all(&dir)
+            .map_err(|err| PathErrors::CreateFailed(dir.clone(), err.to_string()))?;
+        Ok(dir)
+    }
+
+    fn resolve(&self, kind: PathKind, env: impl Fn(&str) -> Option<PathBuf>) -> PathBuf {
+        let name = match kind {
+            PathKind::Saves => "saves",
+            PathKind::Settings => "settings",
+            PathKind::Logs => "logs",
+            PathKind::Cache => "cache",
+        };
+        if let Some(root) = self.root.as_ref() {
+            return root.join(name);
+        }
+        if self.platform == Platform::Web {
+            return PathBuf::from("/").join(&self.app_name).join(name);
+        }
+
+        let home = env(match self.platform {
+            Platform::Windows => "USERPROFILE",
+            _ => "HOME",
+        });
+        let base = match (self.platform, kind) {
+            (Platform::Linux, PathKind::Saves) => {
+                env("XDG_DATA_HOME").or_else(|| home.map(|home| home.join(".local/share")))
+            }
+            (Platform::Linux, PathKind::Settings) => {
+                env("XDG_CONFIG_HOME").or_else(|| home.map(|home| home.join(".config")))
+            }
+            (Platform::Linux, PathKind::Logs) => {
+                env("XDG_STATE_HOME").or_else(|| home.map(|home| home.join(".local/state")))
+            }
+            (Platform::Linux, PathKind::Cache) => {
+                env("XDG_CACHE_HOME").or_else(|| home.map(|home| home.join(".cache")))
+            }
+            (Platform::Windows, PathKind::Saves | PathKind::Settings) => {
+                env("APPDATA").or_else(|| home.map(|home| home.join("AppData").join("Roaming")))
+            }
+            (Platform::Windows, PathKind::Logs | PathKind::Cache) => {
+                env("LOCALAPPDATA").or_else(|| home.map(|home| home.join("AppData").join("Local")))
+            }
+            (Platform::MacOs, PathKind::Saves | PathKind::Settings) => {
+                home.map(|home| home.join("Library/Application Support"))
+            }
+            (Platform::MacOs, PathKind::Logs) => home.map(|home| home.join("Library/Logs")),
+            (Platform::MacOs, PathKind::Cache) => home.map(|home| home.join("Library/Caches")),
+            (Platform::Web, _) => None,
+        };
+        let Some(base) = base else {
+            warn!(
+                "no home directory, keeping {} in the working directory",
+                name
+            );
+            return PathBuf::from(&self.app_name).join(name);
+        };
+
+        let app = base.join(&self.app_name);
+        match (self.platform, kind) {
+            (Platform::Linux, PathKind::Logs) => app.join("logs"),
+            (Platform::Linux, _) | (Platform::MacOs, PathKind::Logs | PathKind::Cache) => app,
+            (Platform::Windows | Platform::MacOs, PathKind::Saves) => app.join("Saves"),
+            (Platform::Windows | Platform::MacOs, PathKind::Settings) => app.join("Settings"),
+            (Platform::Windows, PathKind::Logs) => app.join("Logs"),
+            (Platform::Windows, PathKind::Cache) => app.join("Cache"),
+            (Platform::Web, _) => app,
+        }
+    }
+}
+
+impl Default for AppPaths {
+    fn default() -> Self {
+        Self::new("aloy")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platform_locations() {
+        let env = |name: &str| match name {
+            "HOME" | "USERPROFILE" => Some(PathBuf::from("/home/ada")),
+            "XDG_CONFIG_HOME" => Some(PathBuf::from("/etc/ada")),
+            "LOCALAPPDATA" => Some(PathBuf::from("C:/Users/ada/AppData/Local")),
+            _ => None,
+        };
+        let linux = AppPaths::new("crate").with_platform(Platform::Linux);
+        assert_eq!(
+            linux.resolve(PathKind::Saves, env),
+            PathBuf::from("/home/ada/.local/share/crate")
+        );
+        assert_eq!(
+            linux.resolve(PathKind::Settings, env),
+            PathBuf::from("/etc/ada/crate")
+        );
+
+        let windows = linux.clone().with_platform(Platform::Windows);
+        assert_eq!(
+            windows.resolve(PathKind::Saves, env),
+            PathBuf::from("/home/ada/AppData/Roaming/crate/Saves")
+        );
+        assert_eq!(
+            windows.resolve(PathKind::Cache, env),
+            PathBuf::from("C:/Users/ada/AppData/Local/crate/Cache")
+        );
+
+        let mac = linux.clone().with_platform(Platform::MacOs);
+        assert_eq!(
+            mac.resolve(PathKind::Logs, env),
+            PathBuf::from("/home/ada/Library/Logs/crate")
+        );
+        let web = linux.clone().with_platform(Platform::Web);
+        assert_eq!(
+            web.resolve(PathKind::Saves, env),
+            PathBuf::from("/crate/saves")
+        );
+        let portable = linux.with_root("/games/crate/data");
+        assert_eq!(
+            portable.resolve(PathKind::Logs, env),
+            PathBuf::from("/games/crate/data/logs")
+        );
+    }
+}
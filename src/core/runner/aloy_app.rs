@@ -0,0 +1,117 @@
+use crate::{core::window::WindowId, event_system::event::Event};
+
+use super::engine::Engine;
+
+// Implemented by game crates to plug their logic into the engine loop. Every
+// callback has an empty default so a game only overrides what it needs.
+pub trait AloyApp {
+    fn on_init(&mut self, _engine: &mut Engine) {}
+
+    // Called once per frame after all queued events have been dispatched
+    fn on_update(&mut self, _engine: &mut Engine) {}
+
+    // Called zero or more times per frame at the fixed timestep, never while
+    // the application is paused
+    fn on_fixed_update(&mut self, _engine: &mut Engine) {}
+
+    // Called after on_update once for every open window, applications with
+    // more than one window (an editor with detached panels) draw each of
+    // them here
+    fn on_render(&mut self, _engine: &mut Engine, _window: WindowId) {}
+
+    fn on_event(&mut self, _engine: &mut Engine, _event: &dyn Event) {}
+
+    fn on_shutdown(&mut self, _engine: &mut Engine) {}
+}
+
+// Generates the `main` of a game crate from an expression returning an
+// AloyApp, evaluated when the game starts:
+//
+// aloy_main!(MyGame);                // a unit struct
+// aloy_main!(MyGame::default());
+// aloy_main!(MyGame::new("level1"));
+#[macro_export]
+macro_rules! aloy_main {
+    ($app:expr) => {
+        fn main() {
+            $crate::run_app($app);
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::{
+        core::runner::{applications::Application, exit_handlers::ExitReason},
+        event_system::engine_events::application_events::ApplicationEvents,
+    };
+
+    #[derive(Default)]
+    struct RecordingGame {
+        calls: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl AloyApp for RecordingGame {
+        fn on_init(&mut self, _engine: &mut Engine) {
+            self.calls.lock().unwrap().push("init");
+        }
+
+        fn on_update(&mut self, _engine: &mut Engine) {
+            self.calls.lock().unwrap().push("update");
+        }
+
+        fn on_event(&mut self, _engine: &mut Engine, event: &dyn Event) {
+            if event.get_name() == "Paused" {
+                self.calls.lock().unwrap().push("event");
+            }
+        }
+
+        fn on_shutdown(&mut self, _engine: &mut Engine) {
+            self.calls.lock().unwrap().push("shutdown");
+        }
+    }
+
+    // any expression names the game, constants included
+    #[allow(dead_code)]
+    mod generated {
+        struct Game;
+
+        impl super::AloyApp for Game {}
+
+        const GAME: Game = Game;
+
+        crate::aloy_main!(GAME);
+    }
+
+    #[test]
+    fn test_lifecycle_order() {
+        let game = RecordingGame::default();
+        let calls = Arc::clone(&game.calls);
+        let mut app = Application::builder().with_app(game).build();
+
+        app.get_engine()
+            .emit(Box::new(ApplicationEvents::Paused))
+            .unwrap();
+        app.step(Duration::from_millis(16));
+        app.get_engine().exit(ExitReason::NORMAL);
+        let mut reason = None;
+        for _ in 0..4 {
+            reason = app.step(Duration::from_millis(16));
+            if reason.is_some() {
+                break;
+            }
+        }
+
+        assert_eq!(reason, Some(ExitReason::NORMAL));
+        let calls = calls.lock().unwrap();
+        assert_eq!(&calls[..3], &["init", "event", "update"]);
+        assert_eq!(calls.last(), Some(&"shutdown"));
+        assert_eq!(calls.iter().filter(|call| **call == "init").count(), 1);
+    }
+}
@@ -1,39 +1,125 @@
 use core::panic;
 use std::{
+    any::Any,
+    fmt::Debug,
+    panic::{catch_unwind, AssertUnwindSafe},
     process::exit,
-    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
 };
 
 use log::{error, info, trace};
 
-use crate::event_system::{
-    event::Event,
-    event_dispatcher::{EventDispatcher, EventDispatcherErrors},
-    event_queue::{self, EventQueueErrors},
+use crate::{
+    core::{
+        crash::{install_panic_hook, take_last_crash, CrashInfo, CRASH_EXIT_CODE},
+        window::{create_default_window, WindowProps, WindowSubsystem},
+    },
+    ecs::schedule::Stage,
+    event_system::{
+        engine_events::application_events::ApplicationEvents,
+        event::{EntityId, Event},
+        event_dispatcher::EventDispatcherErrors,
+        event_queue::EventQueueErrors,
+    },
 };
 
-use super::exit_handlers::ExitReason;
+use super::{
+    aloy_app::AloyApp,
+    builder::ApplicationBuilder,
+    engine::Engine,
+    exit_handlers::ExitReason,
+    plugin::{Plugin, PluginErrors},
+};
 
-#[derive(Debug, Default)]
+#[derive(Default)]
 pub struct Application {
-    exit_flag: Arc<Mutex<Option<ExitReason>>>,
-    dispatchers: Vec<EventDispatcher>,
+    engine: Engine,
+    app: Option<Box<dyn AloyApp>>,
+    plugins: Vec<String>,
+    initalized: bool,
+    // the exit that was announced with WillExit and happens next frame
+    exiting: Option<ExitReason>,
 }
 
 impl Application {
+    pub fn new(app: impl AloyApp + 'static) -> Self {
+        Self::from_parts(Engine::default(), Some(Box::new(app)))
+    }
+
+    pub(crate) fn from_parts(engine: Engine, app: Option<Box<dyn AloyApp>>) -> Self {
+        Self {
+            engine,
+            app,
+            plugins: Vec::new(),
+            initalized: false,
+            exiting: None,
+        }
+    }
+
+    pub fn builder() -> ApplicationBuilder {
+        ApplicationBuilder::new()
+    }
+
     fn initalize(&mut self) {
-        let exit_event = "Exit".to_string();
-        let exit_flag = Arc::clone(&self.exit_flag);
-        if let Some(err) = self.on_event(exit_event, move |e| {
-            if let Some(exit) = e.get_data().unwrap().get_ref::<ExitReason>() {
-                if let Ok(mut exit_flag) = exit_flag.try_lock() {
-                    exit_flag.replace(exit.clone());
-                }
-            }
-        }) {
+        let _logger = self.engine.get_logger().enter();
+        if let Some(err) = self.engine.initalize() {
+            error!("error during initalization {:?}", err);
+            panic!("error in initalization");
+        }
+
+        if let Err(err) = self.engine.init_subsystems() {
             error!("error during initalization {:?}", err);
             panic!("error in initalization");
         }
+
+        if let Some(app) = self.app.as_mut() {
+            app.on_init(&mut self.engine);
+        }
+
+        if let Err(err) = self.engine.init_subsystems() {
+            error!("error during initalization {:?}", err);
+            panic!("error in initalization");
+        }
+        self.initalized = true;
+    }
+
+    pub fn add_plugin(&mut self, plugin: impl Plugin) -> Result<(), PluginErrors> {
+        let name = plugin.get_name().to_string();
+        if plugin.is_unique() && self.has_plugin(&name) {
+            return Err(PluginErrors::AlreadyAdded(name));
+        }
+
+        info!("adding plugin {}", name);
+        plugin.build(self);
+        self.plugins.push(name);
+        Ok(())
+    }
+
+    pub fn has_plugin(&self, name: &str) -> bool {
+        self.plugins.iter().any(|plugin| plugin == name)
+    }
+
+    pub fn pause(&mut self) {
+        self.engine.pause();
+    }
+
+    pub fn resume(&mut self) {
+        self.engine.resume();
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.engine.is_paused()
+    }
+
+    // Runs the task on the main thread at the start of the next frame, worker
+    // threads use `Engine::get_main_thread_handle` instead
+    pub fn run_on_main_thread(&self, task: impl FnOnce(&mut Engine) + Send + 'static) {
+        self.engine.run_on_main_thread(task);
+    }
+
+    pub fn get_engine(&mut self) -> &mut Engine {
+        &mut self.engine
     }
 
     pub fn on_event(
@@ -41,59 +127,596 @@ impl Application {
         event_name: String,
         cb: impl Fn(&dyn Event) + Send + Sync + 'static,
     ) -> Option<EventDispatcherErrors> {
-        let mut dispatcher = EventDispatcher::new(event_name);
-        if let Err(err) = dispatcher.add_handlers(Arc::new(cb)) {
-            return Some(err);
-        }
-        self.dispatchers.push(dispatcher);
-        None
+        self.engine.on_event(event_name, cb)
+    }
+
+    // Handlers registered here only receive events targeted at the entity
+    pub fn on_entity_event(
+        &mut self,
+        entity: EntityId,
+        event_name: String,
+        cb: impl Fn(&dyn Event) + Send + Sync + 'static,
+    ) {
+        self.engine.on_entity_event(entity, event_name, cb);
+    }
+
+    pub fn remove_entity_handlers(&mut self, entity: EntityId) -> bool {
+        self.engine.remove_entity_handlers(entity)
     }
 
     // For immdidate dispatching events
-    pub fn dispatch(&self, event: &dyn Event) {
-        for dispatcher in &self.dispatchers {
-            if let Err(err) = dispatcher.dispatch(event) {
-                error!("error in dispatch::{:?}", err);
-            }
+    pub fn dispatch(&mut self, event: &dyn Event) {
+        // handlers already see the state including their own event
+        self.engine.record_event(event);
+        self.engine.dispatch(event);
+        if let Some(app) = self.app.as_mut() {
+            app.on_event(&mut self.engine, event);
+        }
+    }
+
+    fn teardown(&mut self, reason: &ExitReason) {
+        let _logger = self.engine.get_logger().enter();
+        self.engine.run_exit_handlers(reason);
+        if let Some(app) = self.app.as_mut() {
+            app.on_shutdown(&mut self.engine);
+        }
+        self.engine.shutdown_subsystems();
+        self.initalized = false;
+        self.exiting = None;
+    }
+
+    fn shutdown(&mut self, reason: ExitReason) -> ! {
+        info!("Shutting down with {:?}", reason);
+        self.teardown(&reason);
+
+        match reason {
+            ExitReason::NORMAL => exit(0),
+            ExitReason::ERROR(code) => exit(code),
+        }
+    }
+
+    // Gives the Crash handlers (autosave...) a last chance to run. A panic
+    // inside one of them must not take the rest of the crash handling down.
+    fn report_crash(&mut self, payload: &(dyn Any + Send)) -> CrashInfo {
+        let crash = take_last_crash().unwrap_or_else(|| CrashInfo::from_payload(payload));
+        let event = ApplicationEvents::Crash(crash.clone());
+        if catch_unwind(AssertUnwindSafe(|| self.dispatch(&event))).is_err() {
+            error!("a crash handler panicked");
         }
+        crash
     }
 
+    // Panics are caught here instead of unwinding through the FFI boundary and
+    // are turned into an ExitReason::ERROR
     pub fn run(&mut self) {
-        info!("Start");
+        install_panic_hook();
 
-        let event_loop = event_queue::EventQueue::initalize();
+        if let Err(payload) = catch_unwind(AssertUnwindSafe(|| self.run_loop())) {
+            let crash = self.report_crash(payload.as_ref());
+            error!("application crashed: {}", crash.message);
 
-        self.initalize();
-        loop {
-            // At every event cycle we will fetch all the events
-            match event_loop.get_events() {
-                Ok(events) => {
-                    for event in events.iter() {
-                        let e = event.as_ref();
-                        self.dispatch(e);
-                    }
+            let reason = ExitReason::ERROR(CRASH_EXIT_CODE);
+            if catch_unwind(AssertUnwindSafe(|| self.teardown(&reason))).is_err() {
+                error!("shutdown after crash panicked");
+            }
+            log::logger().flush();
+            exit(CRASH_EXIT_CODE);
+        }
+    }
+
+    // Runs exactly one frame that is `delta` long and returns, for tests and
+    // editors that drive the engine themselves. Initalizes on the first call,
+    // when the frame requested an exit the application is torn down and the
+    // reason returned instead of exiting the process.
+    pub fn step(&mut self, delta: Duration) -> Option<ExitReason> {
+        if !self.initalized {
+            self.initalize();
+        }
+        self.engine.advance_time(delta);
+
+        let reason = self.run_frame();
+        if let Some(reason) = reason.as_ref() {
+            info!("Stepped into exit with {:?}", reason);
+            self.teardown(reason);
+        }
+        reason
+    }
+
+    fn run_frame(&mut self) -> Option<ExitReason> {
+        let _logger = self.engine.get_logger().enter();
+        self.engine.run_main_thread_tasks();
+        self.engine.poll_config();
+        self.engine.begin_input_frame();
+
+        // At every event cycle we will fetch all the events
+        match self.engine.get_event_queue().get_events() {
+            Ok(events) => {
+                for event in events.iter() {
+                    let e = event.as_ref();
+                    self.dispatch(e);
                 }
-                Err(EventQueueErrors::EmptyQueue) => {
-                    info!("No events in the global queue");
+            }
+            Err(EventQueueErrors::EmptyQueue) => {
+                info!("No events in the global queue");
+            }
+            Err(EventQueueErrors::UnableToFetchEventsFromQueue) => {}
+            _ => {}
+        }
+
+        self.engine.run_stage(Stage::PreUpdate);
+
+        while self.engine.get_time_mut().consume_fixed_step() {
+            if let Some(app) = self.app.as_mut() {
+                app.on_fixed_update(&mut self.engine);
+            }
+            self.engine.run_stage(Stage::FixedUpdate);
+        }
+
+        self.engine.tick_subsystems();
+        self.engine.update_input();
+        self.engine.run_stage(Stage::Update);
+
+        // Headless runs have nothing to draw, only the simulation counts
+        if !self.engine.is_headless() {
+            if let Some(app) = self.app.as_mut() {
+                app.on_update(&mut self.engine);
+            }
+        }
+        self.engine.run_stage(Stage::PostUpdate);
+        if !self.engine.is_headless() {
+            self.engine.run_stage(Stage::Render);
+            if let Some(app) = self.app.as_mut() {
+                let windows = self
+                    .engine
+                    .get_subsystem::<WindowSubsystem>()
+                    .map(|windows| windows.get_ids())
+                    .unwrap_or_default();
+                for window in windows {
+                    app.on_render(&mut self.engine, window);
                 }
-                Err(EventQueueErrors::UnableToFetchEventsFromQueue) => {}
-                _ => {}
             }
+        }
 
+        // an exit request is announced with WillExit first, the frame after
+        // it is the last one
+        if let Some(reason) = self.exiting.take() {
+            return Some(reason);
+        }
+        if let Some(reason) = self.engine.take_exit_reason() {
+            info!("Exit requested with {:?}", reason);
+            if let Err(err) = self
+                .engine
+                .emit(Box::new(ApplicationEvents::WillExit(reason.clone())))
             {
-                let exit_flag = Arc::clone(&self.exit_flag);
-                if let Ok(mut exit_reason) = exit_flag.try_lock() {
-                    if let Some(flag) = exit_reason.take() {
-                        match flag {
-                            ExitReason::NORMAL => {
-                                exit(0);
-                            }
-                            ExitReason::ERROR(code) => exit(code),
-                        }
-                    }
-                };
+                error!("unable to emit will exit event {:?}", err);
+            }
+            self.exiting = Some(reason);
+        }
+        None
+    }
+
+    fn start(&mut self) {
+        info!("Start");
+
+        if !self.engine.is_headless() && self.engine.get_subsystem::<WindowSubsystem>().is_none() {
+            let props = WindowProps::from_config(self.engine.get_config());
+            let window = WindowSubsystem::new(props, create_default_window());
+            if let Err(err) = self.engine.add_subsystem(window) {
+                error!("unable to add the window {}", err);
+            }
+        }
+
+        self.initalize();
+    }
+
+    // How long to wait before the next frame. Without vsync to pace us a
+    // headless server would spin a core. Under Fifo the renderer already
+    // waits for the display, the cap only matters below its refresh rate.
+    fn get_frame_pause(&self, frame_start: Instant) -> Duration {
+        if self.engine.is_headless() {
+            self.engine.get_time().get_time_until_fixed_step()
+        } else if self.engine.get_config().fps_cap > 0 {
+            let frame_time = Duration::from_secs(1) / self.engine.get_config().fps_cap;
+            frame_time.saturating_sub(frame_start.elapsed())
+        } else {
+            Duration::ZERO
+        }
+    }
+
+    fn run_loop(&mut self) {
+        self.start();
+        loop {
+            let frame_start = Instant::now();
+            self.engine.update_time();
+
+            if let Some(reason) = self.run_frame() {
+                self.shutdown(reason);
             }
             trace!("working");
+
+            let pause = self.get_frame_pause(frame_start);
+            if !pause.is_zero() {
+                thread::sleep(pause);
+            }
+        }
+    }
+
+    // `run` for programs that already live in a tokio runtime, e.g. a game
+    // server next to its web API. Frames are paced with tokio's timer so the
+    // other tasks run between them, and the exit reason is returned instead
+    // of exiting the process. The engine is not Send, run this on a current
+    // thread runtime or in a LocalSet. Panics are not caught.
+    #[cfg(feature = "tokio")]
+    pub async fn run_async(&mut self) -> ExitReason {
+        self.start();
+        loop {
+            let frame_start = Instant::now();
+            self.engine.update_time();
+
+            if let Some(reason) = self.run_frame() {
+                info!("Shutting down with {:?}", reason);
+                self.teardown(&reason);
+                return reason;
+            }
+
+            let pause = self.get_frame_pause(frame_start);
+            if pause.is_zero() {
+                tokio::task::yield_now().await;
+            } else {
+                tokio::time::sleep(pause).await;
+            }
+        }
+    }
+}
+
+impl Debug for Application {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Application")
+            .field("engine", &self.engine)
+            .field("has_app", &self.app.is_some())
+            .field("plugins", &self.plugins)
+            .field("initalized", &self.initalized)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    };
+
+    use super::*;
+    use crate::{
+        core::time::Time,
+        core::{config::EngineConfig, net::wire::WireEvent},
+        ecs::world::{EcsErrors, World},
+        event_system::event::TargetedEvent,
+        math::{
+            transform::{Mat4, Transform},
+            vector::Vec4,
+        },
+        renderer::{
+            api::{headless::HeadlessRenderer, PresentMode, RenderPass, RenderTarget, RendererAPI},
+            gpu_memory::GpuMemoryCategory,
+            render_stats::{RenderStat, RenderStats},
+            renderer2d::Renderer2D,
+        },
+    };
+
+    #[derive(Default)]
+    struct CountingApp {
+        fixed_updates: Arc<AtomicU32>,
+        updates: Arc<AtomicU32>,
+    }
+
+    impl AloyApp for CountingApp {
+        fn on_update(&mut self, _engine: &mut Engine) {
+            self.updates.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn on_fixed_update(&mut self, engine: &mut Engine) {
+            if self.fixed_updates.fetch_add(1, Ordering::SeqCst) == 2 {
+                engine.exit(ExitReason::NORMAL);
+            }
+        }
+    }
+
+    #[test]
+    fn test_step_runs_one_frame() {
+        let game = CountingApp::default();
+        let fixed_updates = Arc::clone(&game.fixed_updates);
+        let updates = Arc::clone(&game.updates);
+        let mut app = Application::builder()
+            .with_app(game)
+            .with_fixed_delta(Duration::from_millis(10))
+            .build();
+
+        assert_eq!(app.step(Duration::from_millis(25)), None);
+        assert_eq!(fixed_updates.load(Ordering::SeqCst), 2);
+        assert_eq!(updates.load(Ordering::SeqCst), 1);
+
+        // the exit requested in the third fixed update is handled next frame,
+        // announced with WillExit and done the frame after
+        assert_eq!(app.step(Duration::from_millis(10)), None);
+        assert_eq!(app.step(Duration::from_millis(10)), None);
+        assert_eq!(
+            app.step(Duration::from_millis(10)),
+            Some(ExitReason::NORMAL)
+        );
+    }
+
+    #[test]
+    fn test_will_exit_and_exit_handlers() {
+        let mut app = Application::builder().build();
+        let journal = Arc::new(Mutex::new(Vec::new()));
+        {
+            let journal = Arc::clone(&journal);
+            app.on_event("WillExit".to_string(), move |_| {
+                journal.lock().unwrap().push("will exit".to_string());
+            });
+        }
+        for (priority, name) in [(0, "autosave"), (1, "flush")] {
+            let journal = Arc::clone(&journal);
+            app.get_engine().on_exit(priority, move |reason| {
+                journal
+                    .lock()
+                    .unwrap()
+                    .push(format!("{} {:?}", name, reason));
+            });
         }
+
+        app.step(Duration::from_millis(16));
+        app.get_engine().exit(ExitReason::ERROR(4));
+        let mut reason = None;
+        for _ in 0..4 {
+            reason = app.step(Duration::from_millis(16));
+            if reason.is_some() {
+                break;
+            }
+        }
+
+        assert_eq!(reason, Some(ExitReason::ERROR(4)));
+        assert_eq!(
+            *journal.lock().unwrap(),
+            vec!["will exit", "flush ERROR(4)", "autosave ERROR(4)"]
+        );
+    }
+
+    #[test]
+    fn test_applications_do_not_share_events() {
+        let mut editor = Application::builder().with_name("editor").build();
+        let mut preview = Application::builder().with_name("preview").build();
+        let received = Arc::new(AtomicU32::new(0));
+        for app in [&mut editor, &mut preview] {
+            let received = Arc::clone(&received);
+            app.on_event("Paused".to_string(), move |_| {
+                received.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        editor
+            .get_engine()
+            .emit(Box::new(ApplicationEvents::Paused))
+            .unwrap();
+        preview.step(Duration::from_millis(16));
+        assert_eq!(received.load(Ordering::SeqCst), 0);
+        editor.step(Duration::from_millis(16));
+        assert_eq!(received.load(Ordering::SeqCst), 1);
+        assert_eq!(preview.get_engine().get_logger().get_name(), "preview");
+    }
+
+    #[test]
+    fn test_main_thread_tasks_run_during_step() {
+        let mut app = Application::builder().build();
+        let main_thread = thread::current().id();
+        let ran_on = Arc::new(Mutex::new(None));
+
+        let handle = app.get_engine().get_main_thread_handle();
+        {
+            let ran_on = Arc::clone(&ran_on);
+            thread::spawn(move || {
+                handle
+                    .run(move |_| {
+                        ran_on.lock().unwrap().replace(thread::current().id());
+                    })
+                    .unwrap();
+            })
+            .join()
+            .unwrap();
+        }
+        assert!(ran_on.lock().unwrap().is_none());
+
+        app.step(Duration::from_millis(16));
+        assert_eq!(*ran_on.lock().unwrap(), Some(main_thread));
+    }
+
+    #[test]
+    fn test_headless_step_skips_on_update() {
+        let game = CountingApp::default();
+        let updates = Arc::clone(&game.updates);
+        let mut app = Application::builder().with_app(game).headless().build();
+
+        app.step(Duration::from_millis(16));
+        assert_eq!(updates.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_step_runs_the_schedule() {
+        struct Ran(Vec<Stage>);
+
+        let mut app = Application::builder()
+            .with_fixed_delta(Duration::from_millis(10))
+            .headless()
+            .build();
+        let world = app.get_engine().get_world_mut();
+        let ran = world.spawn((Ran(Vec::new()),));
+        for stage in Stage::ALL {
+            app.get_engine()
+                .add_system(stage, "record", move |world: &mut World| {
+                    world.get_mut::<Ran>(ran).unwrap().0.push(stage);
+                });
+        }
+
+        app.step(Duration::from_millis(25));
+        // nothing renders headless
+        assert_eq!(
+            app.get_engine().get_world().get::<Ran>(ran).unwrap().0,
+            vec![
+                Stage::PreUpdate,
+                Stage::FixedUpdate,
+                Stage::FixedUpdate,
+                Stage::Update,
+                Stage::PostUpdate
+            ]
+        );
+    }
+
+    #[test]
+    fn test_engine_resources_stay_in_the_world() {
+        let mut app = Application::builder().headless().build();
+        let world = app.get_engine().get_world_mut();
+        assert!(matches!(
+            world.remove_resource::<Time>(),
+            Err(EcsErrors::ProtectedResource(_))
+        ));
+        assert!(world.insert_resource(Time::new()).is_err());
+        let stats = world.get_resource_mut::<RenderStats>().unwrap();
+        stats.record_draw_call(3);
+
+        app.step(Duration::from_millis(16));
+        let stats = app
+            .get_engine()
+            .get_world()
+            .get_resource::<RenderStats>()
+            .unwrap();
+        assert_eq!(stats.get_last_frame().get(RenderStat::DrawCalls), 1);
+        assert_eq!(stats.get_current_frame().get(RenderStat::DrawCalls), 0);
+    }
+
+    #[test]
+    fn test_submitted_frames_are_counted() {
+        let mut app = Application::builder().headless().build();
+        let mut api = HeadlessRenderer::new();
+        let mut renderer = Renderer2D::new(&mut api).unwrap();
+        renderer.begin_scene(&Mat4::IDENTITY);
+        renderer.draw_quad(&Transform::IDENTITY, Vec4::splat(1.0));
+        let mut pass = RenderPass::new("world", RenderTarget::Surface);
+        renderer.end_scene(&mut api, &mut pass).unwrap();
+        let over_budget = Arc::new(AtomicU32::new(0));
+        {
+            let over_budget = Arc::clone(&over_budget);
+            app.on_event("MemoryBudgetExceeded".to_string(), move |_| {
+                over_budget.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        api.get_memory_mut()
+            .set_budget(GpuMemoryCategory::Buffer, 1);
+
+        app.get_engine()
+            .submit_frame(&mut api, &[pass.clone()])
+            .unwrap();
+        app.step(Duration::from_millis(16));
+        let stats = app
+            .get_engine()
+            .get_world()
+            .get_resource::<RenderStats>()
+            .unwrap();
+        assert_eq!(stats.get_last_frame().get(RenderStat::DrawCalls), 1);
+        assert_eq!(stats.get_last_frame().get(RenderStat::Triangles), 2);
+        // the headless backend has no timestamps
+        assert!(stats.get_last_frame().pass_timings.is_empty());
+
+        // the renderer's buffers are over budget, reported once per overrun
+        app.get_engine().submit_frame(&mut api, &[pass]).unwrap();
+        app.step(Duration::from_millis(16));
+        assert_eq!(over_budget.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_targeted_events_skip_global_handlers() {
+        let mut app = Application::builder().build();
+        let global = Arc::new(AtomicU32::new(0));
+        let targeted = Arc::new(AtomicU32::new(0));
+        {
+            let global = Arc::clone(&global);
+            app.on_event("DamageTaken".to_string(), move |_| {
+                global.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        {
+            let targeted = Arc::clone(&targeted);
+            app.on_entity_event(EntityId(4), "DamageTaken".to_string(), move |_| {
+                targeted.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        let damage = || WireEvent::new("DamageTaken", Vec::new());
+        app.dispatch(&TargetedEvent::new(EntityId(4), damage()));
+        assert_eq!(targeted.load(Ordering::SeqCst), 1);
+        assert_eq!(global.load(Ordering::SeqCst), 0);
+        app.dispatch(&damage());
+        assert_eq!(global.load(Ordering::SeqCst), 1);
+        assert_eq!(targeted.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_report_crash_dispatches_crash_event() {
+        let mut app = Application::default();
+        let reported = Arc::new(Mutex::new(None));
+        {
+            let reported = Arc::clone(&reported);
+            app.on_event("Crash".to_string(), move |event| {
+                let data = event.get_data().unwrap();
+                let crash = data.get_ref::<CrashInfo>().unwrap();
+                reported.lock().unwrap().replace(crash.message.clone());
+            });
+        }
+
+        let payload: Box<dyn Any + Send> = Box::new("out of cheese");
+        let crash = app.report_crash(payload.as_ref());
+
+        assert_eq!(crash.message, "out of cheese");
+        assert_eq!(
+            reported.lock().unwrap().as_deref(),
+            Some("out of cheese"),
+            "Crash handlers should get the crash info"
+        );
+    }
+
+    #[test]
+    fn test_present_mode_reaches_the_renderer() {
+        let mut app = Application::builder().build();
+        let renderer = Arc::new(Mutex::new(HeadlessRenderer::new()));
+        {
+            let renderer = Arc::clone(&renderer);
+            app.on_event("PresentModeChanged".to_string(), move |event| {
+                let data = event.get_data().unwrap();
+                let mode = *data.get_ref::<PresentMode>().unwrap();
+                renderer.lock().unwrap().set_present_mode(mode);
+            });
+        }
+
+        app.get_engine().set_present_mode(PresentMode::Immediate);
+        assert!(!app.get_engine().get_config().vsync);
+        app.step(Duration::from_millis(16));
+        assert_eq!(
+            renderer.lock().unwrap().get_present_mode(),
+            PresentMode::Immediate
+        );
+
+        // turning vsync back on in the config switches to Fifo
+        let config = app.get_engine().get_config().clone();
+        app.get_engine().set_config(EngineConfig {
+            vsync: true,
+            ..config
+        });
+        app.step(Duration::from_millis(16));
+        assert_eq!(app.get_engine().get_present_mode(), PresentMode::Fifo);
+        assert_eq!(
+            renderer.lock().unwrap().get_present_mode(),
+            PresentMode::Fifo
+        );
     }
 }
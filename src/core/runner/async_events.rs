@@ -0,0 +1,137 @@
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    core::window::WindowId,
+    event_system::{
+        engine_events::application_events::ApplicationEvents,
+        event::{EntityId, Event},
+        event_queue::{EventQueue, EventQueueErrors},
+    },
+};
+
+use super::exit_handlers::ExitReason;
+
+// An event as async code sees it. The events themselves stay on the engine
+// thread, the envelope keeps their name, where they were sent to, their
+// payload when it is bytes (WireEvents) and their debug output otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventEnvelope {
+    pub name: String,
+    pub window: Option<WindowId>,
+    pub target: Option<EntityId>,
+    pub payload: Option<Vec<u8>>,
+    pub details: String,
+}
+
+impl EventEnvelope {
+    pub fn from_event(event: &dyn Event) -> Self {
+        Self {
+            name: event.get_name(),
+            window: event.get_window(),
+            target: event.get_target(),
+            payload: event
+                .get_data()
+                .and_then(|data| data.get_ref::<Vec<u8>>().cloned()),
+            details: format!("{:?}", event),
+        }
+    }
+}
+
+// The events of one name, in the order they were dispatched. Ends when the
+// engine is dropped, dropping the stream unsubscribes.
+//
+//     let mut deaths = app.get_engine().subscribe("PlayerDied");
+//     tokio::task::spawn_local(async move {
+//         while let Some(death) = deaths.next().await {
+//             leaderboard.report(death.details).await;
+//         }
+//     });
+#[derive(Debug)]
+pub struct EventStream {
+    receiver: UnboundedReceiver<EventEnvelope>,
+}
+
+impl EventStream {
+    pub(crate) fn channel() -> (UnboundedSender<EventEnvelope>, Self) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (sender, Self { receiver })
+    }
+
+    pub async fn next(&mut self) -> Option<EventEnvelope> {
+        self.receiver.recv().await
+    }
+
+    // The next event if one was already dispatched
+    pub fn try_next(&mut self) -> Option<EventEnvelope> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Stream for EventStream {
+    type Item = EventEnvelope;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.poll_recv(cx)
+    }
+}
+
+// Emits onto the engine's queue from any task or thread, the events are
+// dispatched on the next frame. Cheap to clone into every task.
+#[derive(Debug, Clone)]
+pub struct AsyncEmitter {
+    queue: Arc<EventQueue>,
+}
+
+impl AsyncEmitter {
+    pub fn new(queue: Arc<EventQueue>) -> Self {
+        Self { queue }
+    }
+
+    pub fn emit(&self, event: impl Event + 'static) -> Result<(), EventQueueErrors> {
+        self.queue.emit(Box::new(event))
+    }
+
+    // Same as Engine::exit, `run_async` returns the reason once the WillExit
+    // frame is over
+    pub fn exit(&self, reason: ExitReason) -> Result<(), EventQueueErrors> {
+        self.emit(ApplicationEvents::Exit(reason))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::runner::applications::Application;
+
+    #[test]
+    fn test_run_async_streams_events() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let tasks = tokio::task::LocalSet::new();
+
+        let mut app = Application::builder().headless().build();
+        let mut paused = app.get_engine().subscribe("Paused");
+        let emitter = app.get_engine().get_async_emitter();
+        let received = tasks.spawn_local(async move {
+            emitter.emit(ApplicationEvents::Paused).unwrap();
+            let event = paused.next().await;
+            emitter.exit(ExitReason::ERROR(3)).unwrap();
+            event
+        });
+
+        let reason = tasks.block_on(&runtime, app.run_async());
+        assert_eq!(reason, ExitReason::ERROR(3));
+        let event = tasks.block_on(&runtime, received).unwrap().unwrap();
+        assert_eq!(event.name, "Paused");
+        assert_eq!(event.payload, None);
+    }
+}
@@ -0,0 +1,214 @@
+use std::{sync::Arc, time::Duration};
+
+use log::error;
+
+use crate::{
+    core::{
+        cli::CliArgs,
+        config::ConfigLoader,
+        logger::AppLogger,
+        net::session_recording::SessionPlayback,
+        window::{Window, WindowProps, WindowSubsystem},
+    },
+    event_system::event_queue::EventQueue,
+};
+
+use super::{aloy_app::AloyApp, applications::Application, engine::Engine};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RunMode {
+    #[default]
+    Windowed,
+    // No window and no renderer, the loop only runs events and fixed updates.
+    // Meant for dedicated servers and tests.
+    Headless,
+}
+
+#[derive(Default)]
+pub struct ApplicationBuilder {
+    app: Option<Box<dyn AloyApp>>,
+    run_mode: RunMode,
+    fixed_delta: Option<Duration>,
+    event_queue: Option<Arc<EventQueue>>,
+    config: Option<ConfigLoader>,
+    args: Option<CliArgs>,
+    name: Option<String>,
+    window: Option<Box<dyn Window>>,
+}
+
+impl ApplicationBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_app(mut self, app: impl AloyApp + 'static) -> Self {
+        self.app = Some(Box::new(app));
+        self
+    }
+
+    pub fn with_run_mode(mut self, run_mode: RunMode) -> Self {
+        self.run_mode = run_mode;
+        self
+    }
+
+    pub fn headless(self) -> Self {
+        self.with_run_mode(RunMode::Headless)
+    }
+
+    // Rate of on_fixed_update, the server tick rate in headless mode
+    pub fn with_fixed_delta(mut self, fixed_delta: Duration) -> Self {
+        self.fixed_delta = Some(fixed_delta);
+        self
+    }
+
+    // Every application gets its own queue unless one is given here
+    pub fn with_event_queue(mut self, event_queue: Arc<EventQueue>) -> Self {
+        self.event_queue = Some(event_queue);
+        self
+    }
+
+    // Shares the process wide queue, for games that emit from places without
+    // access to the engine
+    pub fn with_global_event_queue(self) -> Self {
+        self.with_event_queue(EventQueue::initalize())
+    }
+
+    // Shown in every log line of this application, tells an editor and its
+    // game preview apart
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn with_config(mut self, loader: ConfigLoader) -> Self {
+        self.config = Some(loader);
+        self
+    }
+
+    // Command line flags override the config, `+name value` sets a cvar and
+    // unknown args are kept for the game in `Engine::get_extra_args`
+    pub fn with_args(mut self, args: CliArgs) -> Self {
+        self.args = Some(args);
+        self
+    }
+
+    // Window backend to use instead of the default one, ignored when headless
+    pub fn with_window(mut self, window: impl Window + 'static) -> Self {
+        self.window = Some(Box::new(window));
+        self
+    }
+
+    // A broken config or replay is logged and the application starts without
+    // it, the builder itself never fails
+    pub fn build(self) -> Application {
+        let mut engine = Engine::new(
+            self.event_queue
+                .unwrap_or_else(|| Arc::new(EventQueue::new())),
+        );
+        engine.set_run_mode(self.run_mode);
+        if let Some(name) = self.name.as_ref() {
+            engine.set_logger(AppLogger::new(name));
+        }
+
+        let loader = match (self.config, self.args.as_ref()) {
+            (loader, Some(args)) => Some(args.apply_to(loader.unwrap_or_default())),
+            (loader, None) => loader,
+        };
+        if let Some(loader) = loader {
+            if let Err(err) = engine.load_config(loader) {
+                error!("unable to load config {}", err);
+            }
+        }
+        if engine.get_config().headless {
+            engine.set_run_mode(RunMode::Headless);
+        }
+
+        if let Some(args) = self.args {
+            if let Some(replay) = args.replay.as_ref() {
+                match SessionPlayback::open(replay) {
+                    Ok(playback) => {
+                        if let Err(err) = engine.add_subsystem(playback) {
+                            error!("unable to start replay {}", err);
+                        }
+                    }
+                    Err(err) => error!("unable to open replay {}: {}", replay.display(), err),
+                }
+            }
+            let rest = engine.get_cvars_mut().apply_args(&args.unknown);
+            engine.set_extra_args(rest);
+        }
+
+        if let Some(window) = self.window.filter(|_| !engine.is_headless()) {
+            let props = WindowProps::from_config(engine.get_config());
+            if let Err(err) = engine.add_subsystem(WindowSubsystem::new(props, window)) {
+                error!("unable to add the window {}", err);
+            }
+        }
+
+        if let Some(fixed_delta) = self.fixed_delta {
+            engine.get_time_mut().set_fixed_delta(fixed_delta);
+        }
+        Application::from_parts(engine, self.app)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{cvars::CVarFlags, settings::SettingValue};
+
+    #[test]
+    fn test_builder_selects_headless() {
+        let mut app = ApplicationBuilder::new()
+            .headless()
+            .with_fixed_delta(Duration::from_millis(50))
+            .build();
+
+        assert!(app.get_engine().is_headless());
+        assert_eq!(
+            app.get_engine().get_time().get_fixed_delta(),
+            Duration::from_millis(50)
+        );
+        assert_eq!(
+            ApplicationBuilder::new()
+                .build()
+                .get_engine()
+                .get_run_mode(),
+            RunMode::Windowed
+        );
+    }
+
+    #[test]
+    fn test_builder_applies_args() {
+        let args = CliArgs::parse(
+            [
+                "--headless",
+                "--fps-cap",
+                "20",
+                "+r_fov",
+                "80",
+                "--map",
+                "dust",
+            ]
+            .iter()
+            .map(|arg| arg.to_string()),
+        )
+        .unwrap();
+        let mut app = ApplicationBuilder::new()
+            .with_config(ConfigLoader::new().without_env())
+            .with_args(args)
+            .build();
+
+        let engine = app.get_engine();
+        assert!(engine.is_headless());
+        assert_eq!(engine.get_config().fps_cap, 20);
+        assert_eq!(engine.get_extra_args(), ["--map", "dust"]);
+
+        // the cvar is registered after the command line was read
+        engine
+            .get_cvars_mut()
+            .register("r_fov", SettingValue::Float(90.0), CVarFlags::NONE, "fov")
+            .unwrap();
+        assert_eq!(engine.get_cvars().get_float("r_fov"), Some(80.0));
+    }
+}
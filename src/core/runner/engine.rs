@@ -0,0 +1,928 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::{error, info};
+
+use crate::{
+    core::{
+        bug_report::{BugReportErrors, BugReporter, BUG_REPORT_ACTION},
+        config::{ConfigErrors, ConfigLoader, ConfigWatcher, EngineConfig},
+        cvars::{CVars, CVAR_SETTINGS_PREFIX},
+        input::{
+            binding::InputBinding,
+            input_macro::{InputMacro, MacroPlayer, MacroRecorder},
+            input_map::{InputMap, INPUT_SETTINGS_PREFIX},
+            players::InputDevice,
+            shortcuts::ShortcutRegistry,
+            state::Input,
+        },
+        key_code::KeyCode,
+        logger::AppLogger,
+        paths::{AppPaths, PathKind},
+        settings::{SettingValue, Settings, SettingsErrors, SettingsLayer},
+        time::Time,
+        time_slicing::TimeSlicer,
+        timer::TimerManager,
+        window::{WindowErrors, WindowSubsystem, PRIMARY_WINDOW},
+    },
+    ecs::{
+        schedule::{Schedule, Stage, SystemEntry},
+        system::IntoSystem,
+        world::World,
+    },
+    event_system::{
+        engine_events::{application_events::ApplicationEvents, renderer_events::RendererEvents},
+        entity_dispatcher::EntityDispatcher,
+        event::{EntityId, Event, PlayerEvent, PlayerId},
+        event_dispatcher::{EventDispatcher, EventDispatcherErrors},
+        event_queue::{EventQueue, EventQueueErrors},
+    },
+    math::vector::Vec2,
+    renderer::{
+        api::{PresentMode, RenderPass, RendererAPI, RendererErrors},
+        capture::SCREENSHOT_ACTION,
+        render_stats::RenderStats,
+    },
+};
+
+#[cfg(feature = "debug_http")]
+use std::net::SocketAddr;
+
+#[cfg(feature = "debug_http")]
+use crate::core::net::status_server::{EngineStatus, StatusServer, StatusServerErrors};
+
+#[cfg(feature = "tokio")]
+use super::async_events::{AsyncEmitter, EventEnvelope, EventStream};
+use super::{
+    builder::RunMode,
+    exit_handlers::{ExitHandlers, ExitReason},
+    main_thread::{MainThreadHandle, MainThreadQueue},
+    subsystem::{Subsystem, SubsystemContext, SubsystemErrors, SubsystemManager},
+};
+
+// Engine is the part of the runtime that game code gets to talk to. The
+// Application owns it and drives the loop, the game only receives &mut Engine
+// in its callbacks.
+#[derive(Debug)]
+pub struct Engine {
+    event_queue: Arc<EventQueue>,
+    exit_flag: Arc<Mutex<Option<ExitReason>>>,
+    dispatchers: Vec<EventDispatcher>,
+    entity_dispatcher: EntityDispatcher,
+    settings: Settings,
+    cvars: CVars,
+    input_map: InputMap,
+    shortcuts: ShortcutRegistry,
+    bug_reporter: BugReporter,
+    macro_recorder: Option<MacroRecorder>,
+    macro_player: Option<MacroPlayer>,
+    subsystems: SubsystemManager,
+    // also keeps the engine's Time, Input and RenderStats as resources for
+    // systems, protected from removal
+    world: World,
+    schedule: Schedule,
+    run_mode: RunMode,
+    main_thread: MainThreadQueue,
+    config: EngineConfig,
+    config_watcher: Option<ConfigWatcher>,
+    present_mode: PresentMode,
+    extra_args: Vec<String>,
+    exit_handlers: ExitHandlers,
+    logger: AppLogger,
+    paths: AppPaths,
+    #[cfg(feature = "debug_http")]
+    status_server: Option<StatusServer>,
+}
+
+// The engine's Time and Input live in the world, borrowed through these
+// next to the other fields
+fn get_time(world: &World) -> &Time {
+    world
+        .get_resource::<Time>()
+        .expect("the world keeps the engine's Time")
+}
+
+fn get_input(world: &World) -> &Input {
+    world
+        .get_resource::<Input>()
+        .expect("the world keeps the engine's Input")
+}
+
+fn get_input_mut(world: &mut World) -> &mut Input {
+    world
+        .get_resource_mut::<Input>()
+        .expect("the world keeps the engine's Input")
+}
+
+// Name of the user settings file in the settings directory
+pub const USER_SETTINGS_FILE: &str = "settings.cfg";
+
+impl Engine {
+    pub fn new(event_queue: Arc<EventQueue>) -> Self {
+        let mut subsystems = SubsystemManager::new();
+        subsystems
+            .register(Box::new(TimerManager::new()))
+            .expect("built in subsystems are registered once");
+        subsystems
+            .register(Box::new(TimeSlicer::new()))
+            .expect("built in subsystems are registered once");
+        let mut input_map = InputMap::new();
+        input_map.bind(BUG_REPORT_ACTION, InputBinding::Key(KeyCode::F8));
+        input_map.bind(SCREENSHOT_ACTION, InputBinding::Key(KeyCode::F12));
+        let paths = AppPaths::default();
+        let mut world = World::new();
+        let expect_new = "a new world has no protected resources";
+        world.insert_resource(Time::new()).expect(expect_new);
+        world.insert_resource(Input::new()).expect(expect_new);
+        world.insert_resource(RenderStats::new()).expect(expect_new);
+        world.protect_resource::<Time>();
+        world.protect_resource::<Input>();
+        world.protect_resource::<RenderStats>();
+
+        Self {
+            event_queue,
+            exit_flag: Arc::new(Mutex::new(None)),
+            dispatchers: Vec::new(),
+            entity_dispatcher: EntityDispatcher::new(),
+            settings: Settings::new(),
+            cvars: CVars::new(),
+            input_map,
+            shortcuts: ShortcutRegistry::default(),
+            bug_reporter: BugReporter::new(Self::bug_report_dir(&paths)),
+            macro_recorder: None,
+            macro_player: None,
+            subsystems,
+            world,
+            schedule: Schedule::new(),
+            run_mode: RunMode::default(),
+            main_thread: MainThreadQueue::new(),
+            config: EngineConfig::default(),
+            config_watcher: None,
+            present_mode: PresentMode::default(),
+            extra_args: Vec::new(),
+            exit_handlers: ExitHandlers::new(),
+            logger: AppLogger::default(),
+            paths,
+            #[cfg(feature = "debug_http")]
+            status_server: None,
+        }
+    }
+
+    pub(crate) fn initalize(&mut self) -> Option<EventDispatcherErrors> {
+        let exit_flag = Arc::clone(&self.exit_flag);
+        self.on_event("Exit".to_string(), move |e| {
+            if let Some(exit) = e.get_data().unwrap().get_ref::<ExitReason>() {
+                if let Ok(mut exit_flag) = exit_flag.try_lock() {
+                    exit_flag.replace(exit.clone());
+                }
+            }
+        })
+    }
+
+    pub fn get_run_mode(&self) -> RunMode {
+        self.run_mode
+    }
+
+    pub fn is_headless(&self) -> bool {
+        self.run_mode == RunMode::Headless
+    }
+
+    pub(crate) fn set_run_mode(&mut self, run_mode: RunMode) {
+        self.run_mode = run_mode;
+    }
+
+    // Command line arguments the engine did not understand, for the game
+    pub fn get_extra_args(&self) -> &[String] {
+        &self.extra_args
+    }
+
+    pub(crate) fn set_extra_args(&mut self, extra_args: Vec<String>) {
+        self.extra_args = extra_args;
+    }
+
+    pub fn get_logger(&self) -> &AppLogger {
+        &self.logger
+    }
+
+    // Also names the directories the application keeps its files in
+    pub(crate) fn set_logger(&mut self, logger: AppLogger) {
+        self.set_paths(AppPaths::new(logger.get_name()));
+        self.logger = logger;
+    }
+
+    pub fn get_paths(&self) -> &AppPaths {
+        &self.paths
+    }
+
+    // E.g. `AppPaths::new(name).with_root(dir)` for a portable install
+    pub fn set_paths(&mut self, paths: AppPaths) {
+        self.bug_reporter
+            .set_output_dir(Self::bug_report_dir(&paths));
+        self.paths = paths;
+    }
+
+    fn bug_report_dir(paths: &AppPaths) -> PathBuf {
+        paths.get_logs_dir().join("bug_reports")
+    }
+
+    pub fn get_config(&self) -> &EngineConfig {
+        &self.config
+    }
+
+    // Loads the layered config and keeps watching its file for changes
+    pub fn load_config(&mut self, loader: ConfigLoader) -> Result<(), ConfigErrors> {
+        let config = loader.load()?;
+        self.config_watcher = loader
+            .get_path()
+            .is_some()
+            .then(|| ConfigWatcher::new(loader));
+        self.set_config(config);
+        Ok(())
+    }
+
+    // Applies the config to the engine and every subsystem, then lets the
+    // game know through ConfigReloaded
+    pub fn set_config(&mut self, config: EngineConfig) {
+        self.logger.set_level(config.get_log_level());
+        if get_time(&self.world).get_fixed_delta() != config.get_fixed_delta() {
+            self.get_time_mut()
+                .set_fixed_delta(config.get_fixed_delta());
+        }
+        self.subsystems.apply_config_all(&config);
+        // a vsync change in the config replaces the present mode, keeping
+        // Mailbox or Immediate when vsync stays off
+        let present_mode = match config.vsync {
+            true => PresentMode::Fifo,
+            false if self.present_mode.is_vsync() => PresentMode::Mailbox,
+            false => self.present_mode,
+        };
+        self.config = config.clone();
+        self.set_present_mode(present_mode);
+
+        if let Err(err) = self.emit(Box::new(ApplicationEvents::ConfigReloaded(config))) {
+            error!("unable to emit config reloaded event {:?}", err);
+        }
+    }
+
+    pub fn get_present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
+
+    // Switches vsync at runtime. The config follows so the frame limiter and
+    // saved configs agree, and PresentModeChanged tells whoever owns the
+    // renderer to call `set_present_mode` on it.
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) {
+        if present_mode == self.present_mode {
+            return;
+        }
+        info!("present mode {:?}", present_mode);
+        self.present_mode = present_mode;
+        self.config.vsync = present_mode.is_vsync();
+        let event = RendererEvents::PresentModeChanged(present_mode);
+        if let Err(err) = self.emit(Box::new(event)) {
+            error!("unable to emit present mode changed event {:?}", err);
+        }
+    }
+
+    // Submits the passes of a frame to the game's renderer. They are counted
+    // in the RenderStats resource together with the GPU timings that came
+    // back since the last frame, and the backend's memory budgets are
+    // checked, so call this instead of `api.submit`.
+    pub fn submit_frame(
+        &mut self,
+        api: &mut dyn RendererAPI,
+        passes: &[RenderPass],
+    ) -> Result<(), RendererErrors> {
+        api.submit(passes)?;
+        api.get_memory_mut().report_budgets(&self.event_queue);
+        let timings = api.take_pass_timings();
+        if let Some(stats) = self.world.get_resource_mut::<RenderStats>() {
+            stats.record_passes(passes);
+            stats.record_pass_timings(timings);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn poll_config(&mut self) {
+        let delta = get_time(&self.world).get_unscaled_delta();
+        let Some(reloaded) = self
+            .config_watcher
+            .as_mut()
+            .and_then(|watcher| watcher.poll(delta))
+        else {
+            return;
+        };
+        match reloaded {
+            Ok(config) if config != self.config => self.set_config(config),
+            Ok(_) => {}
+            // keep running on the old config, the file might be half written
+            Err(err) => error!("unable to reload config {}", err),
+        }
+    }
+
+    pub fn add_subsystem(&mut self, subsystem: impl Subsystem) -> Result<(), SubsystemErrors> {
+        self.subsystems.register(Box::new(subsystem))
+    }
+
+    pub fn get_subsystem<T: Subsystem>(&self) -> Option<&T> {
+        self.subsystems.get::<T>()
+    }
+
+    pub fn get_subsystem_mut<T: Subsystem>(&mut self) -> Option<&mut T> {
+        self.subsystems.get_mut::<T>()
+    }
+
+    pub fn get_world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn get_world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn get_schedule_mut(&mut self) -> &mut Schedule {
+        &mut self.schedule
+    }
+
+    // The system runs on the world every frame in its stage
+    pub fn add_system<Marker>(
+        &mut self,
+        stage: Stage,
+        name: &str,
+        system: impl IntoSystem<Marker>,
+    ) -> &mut SystemEntry {
+        self.schedule.add_system(stage, name, system)
+    }
+
+    pub(crate) fn run_stage(&mut self, stage: Stage) {
+        self.schedule.run_stage(stage, &mut self.world);
+    }
+
+    // Also picks up subsystems that were added after the first call, e.g. by
+    // the game inside on_init
+    pub(crate) fn init_subsystems(&mut self) -> Result<(), SubsystemErrors> {
+        let mut ctx = SubsystemContext {
+            event_queue: &self.event_queue,
+            time: get_time(&self.world),
+            exit_handlers: &self.exit_handlers,
+        };
+        self.subsystems.init_all(&mut ctx)
+    }
+
+    pub(crate) fn tick_subsystems(&mut self) {
+        let mut ctx = SubsystemContext {
+            event_queue: &self.event_queue,
+            time: get_time(&self.world),
+            exit_handlers: &self.exit_handlers,
+        };
+        self.subsystems.tick_all(&mut ctx);
+        if let Some(stats) = self.world.get_resource_mut::<RenderStats>() {
+            stats.report_frame(&self.event_queue);
+        }
+        #[cfg(feature = "debug_http")]
+        self.publish_status();
+    }
+
+    // Serves the engine's status on localhost for health checks, port 0
+    // picks a free one. Returns the address it listens on.
+    #[cfg(feature = "debug_http")]
+    pub fn start_status_server(&mut self, port: u16) -> Result<SocketAddr, StatusServerErrors> {
+        let server = StatusServer::start(port)?;
+        let address = server.get_address();
+        self.status_server = Some(server);
+        self.publish_status();
+        Ok(address)
+    }
+
+    #[cfg(feature = "debug_http")]
+    pub fn get_status_server_mut(&mut self) -> Option<&mut StatusServer> {
+        self.status_server.as_mut()
+    }
+
+    #[cfg(feature = "debug_http")]
+    pub fn stop_status_server(&mut self) {
+        self.status_server = None;
+    }
+
+    #[cfg(feature = "debug_http")]
+    fn publish_status(&self) {
+        let Some(server) = self.status_server.as_ref() else {
+            return;
+        };
+        let frame_time = get_time(&self.world).get_unscaled_delta().as_secs_f64();
+        server.publish(EngineStatus {
+            frame: get_time(&self.world).get_frame_count(),
+            uptime_seconds: get_time(&self.world).get_unscaled_elapsed().as_secs_f64(),
+            fps: if frame_time > 0.0 {
+                1.0 / frame_time
+            } else {
+                0.0
+            },
+            frame_time_ms: frame_time * 1000.0,
+            paused: get_time(&self.world).is_paused(),
+            run_mode: format!("{:?}", self.run_mode),
+            subsystems: self
+                .subsystems
+                .get_names()
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+            stats: Default::default(),
+        });
+    }
+
+    pub(crate) fn shutdown_subsystems(&mut self) {
+        let mut ctx = SubsystemContext {
+            event_queue: &self.event_queue,
+            time: get_time(&self.world),
+            exit_handlers: &self.exit_handlers,
+        };
+        self.subsystems.shutdown_all(&mut ctx);
+    }
+
+    pub fn get_timers_mut(&mut self) -> &mut TimerManager {
+        self.subsystems
+            .get_mut::<TimerManager>()
+            .expect("timer manager is always registered")
+    }
+
+    // Time sliced tasks, see `TimeSlicer`
+    pub fn get_time_slicer_mut(&mut self) -> &mut TimeSlicer {
+        self.subsystems
+            .get_mut::<TimeSlicer>()
+            .expect("time slicer is always registered")
+    }
+
+    pub fn get_time(&self) -> &Time {
+        get_time(&self.world)
+    }
+
+    pub fn get_time_mut(&mut self) -> &mut Time {
+        self.world
+            .get_resource_mut::<Time>()
+            .expect("the world keeps the engine's Time")
+    }
+
+    // Stops fixed updates and scaled time, events and on_update keep running
+    // so pause menus still work
+    pub fn pause(&mut self) {
+        if get_time(&self.world).is_paused() {
+            return;
+        }
+        self.get_time_mut().set_paused(true);
+        if let Err(err) = self.emit(Box::new(ApplicationEvents::Paused)) {
+            error!("unable to emit paused event {:?}", err);
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if !get_time(&self.world).is_paused() {
+            return;
+        }
+        self.get_time_mut().set_paused(false);
+        if let Err(err) = self.emit(Box::new(ApplicationEvents::Resumed)) {
+            error!("unable to emit resumed event {:?}", err);
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        get_time(&self.world).is_paused()
+    }
+
+    pub(crate) fn update_time(&mut self) {
+        self.get_time_mut().update();
+    }
+
+    pub(crate) fn advance_time(&mut self, delta: Duration) {
+        self.get_time_mut().advance(delta);
+    }
+
+    pub fn get_main_thread_handle(&self) -> MainThreadHandle {
+        self.main_thread.get_handle()
+    }
+
+    pub fn run_on_main_thread(&self, task: impl FnOnce(&mut Engine) + Send + 'static) {
+        // the queue lives as long as the engine, sending can not fail here
+        let _ = self.main_thread.get_handle().run(task);
+    }
+
+    pub(crate) fn run_main_thread_tasks(&mut self) {
+        for task in self.main_thread.take_tasks() {
+            task(self);
+        }
+    }
+
+    pub fn get_event_queue(&self) -> Arc<EventQueue> {
+        Arc::clone(&self.event_queue)
+    }
+
+    pub fn emit(&self, event: Box<impl Event + 'static>) -> Result<(), EventQueueErrors> {
+        self.event_queue.emit(event)
+    }
+
+    // Requests the application to exit at the end of the current frame
+    pub fn exit(&self, reason: ExitReason) {
+        if let Err(err) = self.emit(Box::new(ApplicationEvents::Exit(reason))) {
+            error!("unable to emit exit event {:?}", err);
+        }
+    }
+
+    // The callback runs once the application shuts down, after the WillExit
+    // frame and before the game and the subsystems are shut down. Higher
+    // priorities run first.
+    pub fn on_exit(&self, priority: i32, callback: impl FnOnce(ExitReason) + Send + 'static) {
+        self.exit_handlers.register(priority, callback);
+    }
+
+    pub(crate) fn run_exit_handlers(&self, reason: &ExitReason) {
+        self.exit_handlers.run_all(reason);
+    }
+
+    pub fn get_settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    // Settings changed at runtime are pushed to the subsystems that read them
+    pub fn set_setting(&mut self, key: &str, value: SettingValue) -> Option<SettingValue> {
+        self.set_setting_in(SettingsLayer::User, key, value)
+    }
+
+    pub fn set_setting_in(
+        &mut self,
+        layer: SettingsLayer,
+        key: &str,
+        value: SettingValue,
+    ) -> Option<SettingValue> {
+        let previous = self.settings.set_in(layer, key, value);
+        if key.starts_with(INPUT_SETTINGS_PREFIX) {
+            self.input_map.apply_settings(&self.settings);
+        }
+        if key.starts_with(CVAR_SETTINGS_PREFIX) {
+            self.cvars.apply_settings(&self.settings);
+        }
+        previous
+    }
+
+    // "Restore defaults" is resetting the user layer
+    pub fn reset_settings(&mut self, layer: SettingsLayer) {
+        self.settings.reset_layer(layer);
+        self.input_map.apply_settings(&self.settings);
+        self.cvars.apply_settings(&self.settings);
+    }
+
+    pub fn load_settings_layer(
+        &mut self,
+        layer: SettingsLayer,
+        path: impl AsRef<Path>,
+    ) -> Result<(), SettingsErrors> {
+        self.settings.load_layer(layer, path)?;
+        self.input_map.apply_settings(&self.settings);
+        self.cvars.apply_settings(&self.settings);
+        Ok(())
+    }
+
+    // Bindings and archive cvars are written into the settings first so
+    // remaps and tweaks persist
+    pub fn save_settings(&mut self, path: impl AsRef<Path>) -> Result<(), SettingsErrors> {
+        self.input_map.store_bindings(&mut self.settings);
+        self.cvars.store_archived(&mut self.settings);
+        self.settings.save(path)
+    }
+
+    pub fn load_settings(&mut self, path: impl AsRef<Path>) -> Result<(), SettingsErrors> {
+        self.settings.load(path)?;
+        self.input_map.apply_settings(&self.settings);
+        self.cvars.apply_settings(&self.settings);
+        Ok(())
+    }
+
+    pub fn get_cvars(&self) -> &CVars {
+        &self.cvars
+    }
+
+    pub fn get_cvars_mut(&mut self) -> &mut CVars {
+        &mut self.cvars
+    }
+
+    // Saves the user layer to the platform's settings directory
+    pub fn save_user_settings(&mut self) -> Result<PathBuf, SettingsErrors> {
+        let dir = self
+            .paths
+            .ensure(PathKind::Settings)
+            .map_err(|err| SettingsErrors::Io(err.to_string()))?;
+        let path = dir.join(USER_SETTINGS_FILE);
+        self.save_settings(&path)?;
+        Ok(path)
+    }
+
+    // Err on the first run, before anything was saved
+    pub fn load_user_settings(&mut self) -> Result<(), SettingsErrors> {
+        let path = self.paths.get_settings_dir().join(USER_SETTINGS_FILE);
+        self.load_settings(path)
+    }
+
+    // Entry point for raw action presses, they go through the input
+    // assistance and come out as action events
+    pub fn press_action(&mut self, action: &str) {
+        let now = get_time(&self.world).get_unscaled_elapsed();
+        let events = self.input_map.handle_action(action, true, now);
+        self.emit_input_events(events);
+    }
+
+    pub fn release_action(&mut self, action: &str) {
+        let now = get_time(&self.world).get_unscaled_elapsed();
+        let events = self.input_map.handle_action(action, false, now);
+        self.emit_input_events(events);
+    }
+
+    pub(crate) fn update_input(&mut self) {
+        let now = get_time(&self.world).get_unscaled_elapsed();
+        let mut events = self.input_map.handle_input(get_input(&self.world), now);
+        events.extend(self.input_map.update(now));
+        self.emit_input_events(events);
+        let mut events = self
+            .input_map
+            .handle_player_input(get_input(&self.world), now);
+        events.extend(self.input_map.update_players(now));
+        self.emit_input_events(events);
+        let events = self.shortcuts.update(now);
+        self.emit_input_events(events);
+        self.play_macro_steps(now);
+    }
+
+    // Starts recording the action events, raw device input is left out.
+    // A recording that was running is thrown away.
+    pub fn record_macro(&mut self) {
+        self.macro_recorder = Some(MacroRecorder::new(
+            get_time(&self.world).get_unscaled_elapsed(),
+        ));
+    }
+
+    pub fn is_recording_macro(&self) -> bool {
+        self.macro_recorder.is_some()
+    }
+
+    pub fn stop_recording_macro(&mut self) -> Option<InputMacro> {
+        self.macro_recorder.take().map(MacroRecorder::finish)
+    }
+
+    // Plays the action events back from the next frame on, in place of the
+    // macro that was playing
+    pub fn play_macro(&mut self, input_macro: InputMacro) {
+        self.macro_player = Some(MacroPlayer::new(
+            input_macro,
+            get_time(&self.world).get_unscaled_elapsed(),
+        ));
+    }
+
+    pub fn is_playing_macro(&self) -> bool {
+        self.macro_player.is_some()
+    }
+
+    pub fn stop_macro(&mut self) {
+        self.macro_player = None;
+    }
+
+    fn play_macro_steps(&mut self, now: Duration) {
+        let Some(player) = self.macro_player.as_mut() else {
+            return;
+        };
+        for (player, event) in player.update(now) {
+            let result = match player {
+                Some(player) => self.emit(Box::new(PlayerEvent::new(player, event))),
+                None => self.emit(Box::new(event)),
+            };
+            if let Err(err) = result {
+                error!("unable to emit macro event {:?}", err);
+            }
+        }
+        if self
+            .macro_player
+            .as_ref()
+            .is_some_and(MacroPlayer::is_finished)
+        {
+            info!("input macro finished");
+            self.macro_player = None;
+        }
+    }
+
+    fn emit_input_events(&self, events: Vec<impl Event + 'static>) {
+        for event in events {
+            if let Err(err) = self.emit(Box::new(event)) {
+                error!("unable to emit input event {:?}", err);
+            }
+        }
+    }
+
+    // The local player an action was for or whose device sent a raw input
+    // event, None in single player games
+    pub fn get_player(&self, event: &dyn Event) -> Option<PlayerId> {
+        event.get_player().or_else(|| {
+            InputDevice::from_event(event)
+                .and_then(|device| self.input_map.get_players().get_player(device))
+        })
+    }
+
+    // Polled key, mouse and gamepad state, see `Input`
+    pub fn get_input(&self) -> &Input {
+        get_input(&self.world)
+    }
+
+    // Text input mode of the primary window, on while a text field has focus
+    // so TextInput and IME events come in
+    pub fn set_text_input(&mut self, enabled: bool) -> Result<(), WindowErrors> {
+        self.get_subsystem_mut::<WindowSubsystem>()
+            .ok_or(WindowErrors::UnknownWindow(PRIMARY_WINDOW))?
+            .set_text_input(PRIMARY_WINDOW, enabled)
+    }
+
+    pub fn is_text_input(&self) -> bool {
+        self.get_subsystem::<WindowSubsystem>()
+            .is_some_and(|windows| windows.is_text_input(PRIMARY_WINDOW))
+    }
+
+    // Puts the IME candidate window next to the text cursor, in physical
+    // pixels from the top left of the primary window
+    pub fn set_ime_cursor_area(&mut self, position: Vec2, size: Vec2) -> Result<(), WindowErrors> {
+        self.get_subsystem_mut::<WindowSubsystem>()
+            .ok_or(WindowErrors::UnknownWindow(PRIMARY_WINDOW))?
+            .set_ime_cursor_area(PRIMARY_WINDOW, position, size)
+    }
+
+    pub(crate) fn begin_input_frame(&mut self) {
+        get_input_mut(&mut self.world).begin_frame();
+    }
+
+    // Every dispatched event goes through here: it is journaled for bug
+    // reports, updates the polled state and the bound inputs turn into
+    // action and axis events
+    pub(crate) fn record_event(&mut self, event: &dyn Event) {
+        let now = get_time(&self.world).get_unscaled_elapsed();
+        self.bug_reporter.get_journal_mut().record(
+            get_time(&self.world).get_frame_count(),
+            now,
+            event,
+        );
+        #[cfg(feature = "debug_http")]
+        if let Some(server) = self.status_server.as_ref() {
+            server.record_event(get_time(&self.world).get_frame_count(), now, event);
+        }
+
+        // before the input sees the press, so held keys tell repeats apart
+        if event.get_name() == "KeyPressed" {
+            if let Some(key) = event
+                .get_data()
+                .and_then(|data| data.get_ref::<KeyCode>().copied())
+            {
+                let events = self
+                    .shortcuts
+                    .handle_key_press(key, get_input(&self.world), now);
+                self.emit_input_events(events);
+            }
+        }
+
+        if let Some(recorder) = self.macro_recorder.as_mut() {
+            recorder.record(event, now);
+        }
+        get_input_mut(&mut self.world).handle_event(event);
+        let events = self.input_map.handle_input(get_input(&self.world), now);
+        self.emit_input_events(events);
+        let events = self
+            .input_map
+            .handle_player_input(get_input(&self.world), now);
+        self.emit_input_events(events);
+
+        if event.get_name() == "ActionStarted"
+            && event.get_data().is_some_and(|data| {
+                data.get_ref::<String>().map(|action| action.as_str()) == Some(BUG_REPORT_ACTION)
+            })
+        {
+            if let Err(err) = self.capture_bug_report("Playtest report") {
+                error!("unable to capture bug report {}", err);
+            }
+        }
+    }
+
+    pub fn get_bug_reporter(&self) -> &BugReporter {
+        &self.bug_reporter
+    }
+
+    pub fn get_bug_reporter_mut(&mut self) -> &mut BugReporter {
+        &mut self.bug_reporter
+    }
+
+    // Bundles the screenshot, event journal, logs and engine stats into a zip
+    // in the reporter's output directory
+    pub fn capture_bug_report(&self, title: &str) -> Result<PathBuf, BugReportErrors> {
+        let mut report = self.bug_reporter.build(title);
+        report.set_time(
+            get_time(&self.world).get_frame_count(),
+            get_time(&self.world).get_unscaled_elapsed(),
+        );
+        report.add_stat("app", self.logger.get_name());
+        report.add_stat("run_mode", format!("{:?}", self.run_mode));
+        report.add_stat("paused", get_time(&self.world).is_paused());
+        report.add_stat("time_scale", get_time(&self.world).get_time_scale());
+        report.add_stat(
+            "frame_ms",
+            get_time(&self.world).get_unscaled_delta().as_secs_f64() * 1000.0,
+        );
+        report.add_stat("subsystems", self.subsystems.get_names().join(", "));
+        self.bug_reporter.save(&report)
+    }
+
+    pub fn get_shortcuts(&self) -> &ShortcutRegistry {
+        &self.shortcuts
+    }
+
+    pub fn get_shortcuts_mut(&mut self) -> &mut ShortcutRegistry {
+        &mut self.shortcuts
+    }
+
+    pub fn get_input_map(&self) -> &InputMap {
+        &self.input_map
+    }
+
+    pub fn get_input_map_mut(&mut self) -> &mut InputMap {
+        &mut self.input_map
+    }
+
+    pub(crate) fn take_exit_reason(&self) -> Option<ExitReason> {
+        match self.exit_flag.try_lock() {
+            Ok(mut exit_reason) => exit_reason.take(),
+            Err(_) => None,
+        }
+    }
+
+    pub fn on_event(
+        &mut self,
+        event_name: String,
+        cb: impl Fn(&dyn Event) + Send + Sync + 'static,
+    ) -> Option<EventDispatcherErrors> {
+        let mut dispatcher = EventDispatcher::new(event_name);
+        if let Err(err) = dispatcher.add_handlers(Arc::new(cb)) {
+            return Some(err);
+        }
+        self.dispatchers.push(dispatcher);
+        None
+    }
+
+    // Every `event_name` event dispatched from now on, for async code
+    #[cfg(feature = "tokio")]
+    pub fn subscribe(&mut self, event_name: &str) -> EventStream {
+        let (sender, stream) = EventStream::channel();
+        let handler = move |event: &dyn Event| {
+            // the stream was dropped
+            let _ = sender.send(EventEnvelope::from_event(event));
+        };
+        if let Some(err) = self.on_event(event_name.to_string(), handler) {
+            error!("unable to subscribe to {}: {:?}", event_name, err);
+        }
+        stream
+    }
+
+    #[cfg(feature = "tokio")]
+    pub fn get_async_emitter(&self) -> AsyncEmitter {
+        AsyncEmitter::new(self.get_event_queue())
+    }
+
+    // Handlers registered here only receive events targeted at the entity
+    pub fn on_entity_event(
+        &mut self,
+        entity: EntityId,
+        event_name: String,
+        cb: impl Fn(&dyn Event) + Send + Sync + 'static,
+    ) {
+        self.entity_dispatcher
+            .add_handler(entity, event_name, Arc::new(cb));
+    }
+
+    pub fn remove_entity_handlers(&mut self, entity: EntityId) -> bool {
+        self.entity_dispatcher.remove_entity(entity)
+    }
+
+    // For immdidate dispatching events. Targeted events only reach the
+    // handlers of their entity, the others go to every global handler.
+    pub fn dispatch(&self, event: &dyn Event) {
+        if event.get_target().is_some() {
+            self.entity_dispatcher.dispatch(event);
+            return;
+        }
+        for dispatcher in &self.dispatchers {
+            if let Err(err) = dispatcher.dispatch(event) {
+                error!("error in dispatch::{:?}", err);
+            }
+        }
+    }
+}
+
+// Every engine gets its own queue, so several applications can live in one
+// process. Use `EventQueue::initalize` explicitly to share the global one.
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new(Arc::new(EventQueue::new()))
+    }
+}
@@ -1,5 +1,107 @@
-#[derive(Debug, Clone)]
+use std::{
+    fmt::Debug,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+};
+
+use log::{error, info};
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum ExitReason {
     NORMAL,
     ERROR(i32),
 }
+
+pub type ExitCallback = Box<dyn FnOnce(ExitReason) + Send>;
+
+struct ExitHandler {
+    priority: i32,
+    callback: ExitCallback,
+}
+
+// Shutdown callbacks registered by subsystems and game code. Cloning shares
+// the same list, so subsystems can register through their context.
+#[derive(Clone, Default)]
+pub struct ExitHandlers {
+    handlers: Arc<Mutex<Vec<ExitHandler>>>,
+}
+
+impl ExitHandlers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Higher priorities run first, equal priorities in registration order
+    pub fn register(&self, priority: i32, callback: impl FnOnce(ExitReason) + Send + 'static) {
+        match self.handlers.lock() {
+            Ok(mut handlers) => handlers.push(ExitHandler {
+                priority,
+                callback: Box::new(callback),
+            }),
+            Err(_) => error!("unable to register exit handler, the list is poisoned"),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.handlers.lock().map_or(0, |handlers| handlers.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Every handler runs once, a panicking handler is logged and does not
+    // stop the ones after it
+    pub fn run_all(&self, reason: &ExitReason) {
+        let mut handlers = match self.handlers.lock() {
+            Ok(mut handlers) => std::mem::take(&mut *handlers),
+            Err(poisoned) => std::mem::take(&mut *poisoned.into_inner()),
+        };
+        // stable sort keeps the registration order for equal priorities
+        handlers.sort_by_key(|handler| -(handler.priority as i64));
+
+        info!("running {} exit handlers", handlers.len());
+        for handler in handlers {
+            let reason = reason.clone();
+            if catch_unwind(AssertUnwindSafe(|| (handler.callback)(reason))).is_err() {
+                error!("exit handler with priority {} panicked", handler.priority);
+            }
+        }
+    }
+}
+
+impl Debug for ExitHandlers {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ExitHandlers")
+            .field("handlers", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_handlers_run_by_priority_once() {
+        let handlers = ExitHandlers::new();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        for (priority, name) in [(0, "save"), (10, "flush"), (0, "close"), (-5, "log")] {
+            let order = Arc::clone(&order);
+            handlers.register(priority, move |reason| {
+                order.lock().unwrap().push((name, reason));
+            });
+        }
+        handlers.register(5, |_| panic!("broken handler"));
+
+        handlers.run_all(&ExitReason::ERROR(3));
+        handlers.run_all(&ExitReason::NORMAL);
+
+        let order = order.lock().unwrap();
+        let names: Vec<&str> = order.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, vec!["flush", "save", "close", "log"]);
+        assert!(order
+            .iter()
+            .all(|(_, reason)| *reason == ExitReason::ERROR(3)));
+    }
+}
@@ -0,0 +1,359 @@
+use log::info;
+
+use crate::{core::window::WindowId, event_system::event::Event};
+
+use super::{aloy_app::AloyApp, engine::Engine};
+
+// What happens to the states below while this one is on top
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LowerStates {
+    // no updates and no rendering, e.g. a loading screen
+    #[default]
+    Paused,
+    // frozen but still drawn, e.g. a pause menu over the game
+    Rendering,
+    // keep running, e.g. a HUD or chat overlay
+    Updating,
+}
+
+pub enum StateTransition {
+    None,
+    Push(Box<dyn GameState>),
+    Pop,
+    // replaces the top state
+    Switch(Box<dyn GameState>),
+}
+
+// One screen of the game: menu, loading screen, gameplay... Updates and
+// events can ask the stack for a transition, it is applied once every state
+// got its callback for the frame.
+pub trait GameState {
+    fn get_name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    fn on_enter(&mut self, _engine: &mut Engine) {}
+    fn on_exit(&mut self, _engine: &mut Engine) {}
+
+    // Another state was pushed on top of / popped off this one
+    fn on_pause(&mut self, _engine: &mut Engine) {}
+    fn on_resume(&mut self, _engine: &mut Engine) {}
+
+    fn on_update(&mut self, _engine: &mut Engine) -> StateTransition {
+        StateTransition::None
+    }
+
+    // Called for every open window, like AloyApp::on_render
+    fn on_render(&mut self, _engine: &mut Engine, _window: WindowId) {}
+
+    fn on_event(&mut self, _engine: &mut Engine, _event: &dyn Event) -> StateTransition {
+        StateTransition::None
+    }
+
+    fn lower_states(&self) -> LowerStates {
+        LowerStates::Paused
+    }
+}
+
+// Works as the AloyApp of a game, `Application::new(StateStack::new(Menu))`
+#[derive(Default)]
+pub struct StateStack {
+    states: Vec<Box<dyn GameState>>,
+    pending: Vec<StateTransition>,
+}
+
+impl StateStack {
+    pub fn new(initial: impl GameState + 'static) -> Self {
+        Self {
+            states: Vec::new(),
+            pending: vec![StateTransition::Push(Box::new(initial))],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+
+    pub fn get_top_name(&self) -> Option<&str> {
+        self.states.last().map(|state| state.get_name())
+    }
+
+    pub fn push(&mut self, engine: &mut Engine, mut state: Box<dyn GameState>) {
+        if let Some(top) = self.states.last_mut() {
+            top.on_pause(engine);
+        }
+        info!("entering state {}", state.get_name());
+        state.on_enter(engine);
+        self.states.push(state);
+    }
+
+    pub fn pop(&mut self, engine: &mut Engine) -> Option<Box<dyn GameState>> {
+        let mut state = self.states.pop()?;
+        info!("leaving state {}", state.get_name());
+        state.on_exit(engine);
+        if let Some(top) = self.states.last_mut() {
+            top.on_resume(engine);
+        }
+        Some(state)
+    }
+
+    // The state below is not resumed, it stays covered by the new one
+    pub fn switch(&mut self, engine: &mut Engine, mut state: Box<dyn GameState>) {
+        if let Some(mut old) = self.states.pop() {
+            info!("leaving state {}", old.get_name());
+            old.on_exit(engine);
+        }
+        info!("entering state {}", state.get_name());
+        state.on_enter(engine);
+        self.states.push(state);
+    }
+
+    pub fn clear(&mut self, engine: &mut Engine) {
+        while self.pop(engine).is_some() {}
+    }
+
+    // Index of the lowest state that gets the given treatment
+    fn lowest(&self, reaches: impl Fn(LowerStates) -> bool) -> usize {
+        let mut lowest = self.states.len().saturating_sub(1);
+        while lowest > 0 && reaches(self.states[lowest].lower_states()) {
+            lowest -= 1;
+        }
+        lowest
+    }
+
+    fn apply_transitions(&mut self, engine: &mut Engine) {
+        for transition in std::mem::take(&mut self.pending) {
+            match transition {
+                StateTransition::None => {}
+                StateTransition::Push(state) => self.push(engine, state),
+                StateTransition::Pop => {
+                    self.pop(engine);
+                }
+                StateTransition::Switch(state) => self.switch(engine, state),
+            }
+        }
+    }
+
+    // Updates from the top down
+    pub fn update(&mut self, engine: &mut Engine) {
+        self.apply_transitions(engine);
+        if self.states.is_empty() {
+            return;
+        }
+
+        let lowest_updated = self.lowest(|lower| lower == LowerStates::Updating);
+        for state in self.states[lowest_updated..].iter_mut().rev() {
+            let transition = state.on_update(engine);
+            self.pending.push(transition);
+        }
+
+        self.apply_transitions(engine);
+    }
+
+    // Renders bottom up so overlays are drawn over what they cover
+    pub fn render(&mut self, engine: &mut Engine, window: WindowId) {
+        if self.states.is_empty() {
+            return;
+        }
+        let lowest_rendered = self.lowest(|lower| lower != LowerStates::Paused);
+        for state in self.states[lowest_rendered..].iter_mut() {
+            state.on_render(engine, window);
+        }
+    }
+
+    // Events reach the same states as updates do
+    pub fn handle_event(&mut self, engine: &mut Engine, event: &dyn Event) {
+        if self.states.is_empty() {
+            return;
+        }
+        let lowest_updated = self.lowest(|lower| lower == LowerStates::Updating);
+        for state in self.states[lowest_updated..].iter_mut().rev() {
+            let transition = state.on_event(engine, event);
+            self.pending.push(transition);
+        }
+    }
+}
+
+impl AloyApp for StateStack {
+    fn on_init(&mut self, engine: &mut Engine) {
+        self.apply_transitions(engine);
+    }
+
+    fn on_update(&mut self, engine: &mut Engine) {
+        self.update(engine);
+    }
+
+    fn on_render(&mut self, engine: &mut Engine, window: WindowId) {
+        self.render(engine, window);
+    }
+
+    fn on_event(&mut self, engine: &mut Engine, event: &dyn Event) {
+        self.handle_event(engine, event);
+    }
+
+    fn on_shutdown(&mut self, engine: &mut Engine) {
+        self.clear(engine);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::core::window::PRIMARY_WINDOW;
+
+    type Log = Arc<Mutex<Vec<String>>>;
+
+    struct Recording {
+        name: &'static str,
+        lower: LowerStates,
+        log: Log,
+        next: Option<StateTransition>,
+    }
+
+    impl Recording {
+        fn new(name: &'static str, lower: LowerStates, log: &Log) -> Self {
+            Self {
+                name,
+                lower,
+                log: Arc::clone(log),
+                next: None,
+            }
+        }
+
+        fn record(&self, what: &str) {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", self.name, what));
+        }
+    }
+
+    impl GameState for Recording {
+        fn get_name(&self) -> &str {
+            self.name
+        }
+
+        fn on_enter(&mut self, _engine: &mut Engine) {
+            self.record("enter");
+        }
+
+        fn on_exit(&mut self, _engine: &mut Engine) {
+            self.record("exit");
+        }
+
+        fn on_pause(&mut self, _engine: &mut Engine) {
+            self.record("pause");
+        }
+
+        fn on_resume(&mut self, _engine: &mut Engine) {
+            self.record("resume");
+        }
+
+        fn on_update(&mut self, _engine: &mut Engine) -> StateTransition {
+            self.record("update");
+            self.next.take().unwrap_or(StateTransition::None)
+        }
+
+        fn on_render(&mut self, _engine: &mut Engine, _window: WindowId) {
+            self.record("render");
+        }
+
+        fn lower_states(&self) -> LowerStates {
+            self.lower
+        }
+    }
+
+    fn take(log: &Log) -> Vec<String> {
+        std::mem::take(&mut *log.lock().unwrap())
+    }
+
+    fn frame(stack: &mut StateStack, engine: &mut Engine) {
+        stack.on_update(engine);
+        stack.on_render(engine, PRIMARY_WINDOW);
+    }
+
+    #[test]
+    fn test_push_and_pop_from_update() {
+        let log = Log::default();
+        let mut engine = Engine::default();
+        let mut game = Recording::new("game", LowerStates::Paused, &log);
+        game.next = Some(StateTransition::Push(Box::new(Recording::new(
+            "pause_menu",
+            LowerStates::Rendering,
+            &log,
+        ))));
+        let mut stack = StateStack::new(game);
+        stack.on_init(&mut engine);
+        assert_eq!(take(&log), vec!["game enter"]);
+
+        frame(&mut stack, &mut engine);
+        assert_eq!(
+            take(&log),
+            vec![
+                "game update",
+                "game pause",
+                "pause_menu enter",
+                "game render",
+                "pause_menu render"
+            ]
+        );
+
+        // the pause menu freezes the game but keeps it on screen
+        frame(&mut stack, &mut engine);
+        assert_eq!(
+            take(&log),
+            vec!["pause_menu update", "game render", "pause_menu render"]
+        );
+
+        stack.pop(&mut engine);
+        assert_eq!(take(&log), vec!["pause_menu exit", "game resume"]);
+        assert_eq!(stack.get_top_name(), Some("game"));
+    }
+
+    #[test]
+    fn test_lower_states_policies() {
+        let log = Log::default();
+        let mut engine = Engine::default();
+        let mut stack = StateStack::default();
+        stack.push(
+            &mut engine,
+            Box::new(Recording::new("game", LowerStates::Paused, &log)),
+        );
+        stack.push(
+            &mut engine,
+            Box::new(Recording::new("hud", LowerStates::Updating, &log)),
+        );
+        stack.push(
+            &mut engine,
+            Box::new(Recording::new("loading", LowerStates::Paused, &log)),
+        );
+        take(&log);
+
+        frame(&mut stack, &mut engine);
+        assert_eq!(take(&log), vec!["loading update", "loading render"]);
+
+        stack.switch(
+            &mut engine,
+            Box::new(Recording::new("chat", LowerStates::Updating, &log)),
+        );
+        take(&log);
+        frame(&mut stack, &mut engine);
+        assert_eq!(
+            take(&log),
+            vec![
+                "chat update",
+                "hud update",
+                "game update",
+                "game render",
+                "hud render",
+                "chat render"
+            ]
+        );
+    }
+}
@@ -0,0 +1,464 @@
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use thiserror::Error;
+
+use crate::{
+    core::net::{
+        event_transport::EventTransport, ipc::to_wire_event, transport::InProcessTransport,
+        wire::WireEvent,
+    },
+    event_system::event::Event,
+};
+
+use super::{
+    aloy_app::AloyApp,
+    applications::Application,
+    engine::Engine,
+    exit_handlers::ExitReason,
+    subsystem::{Subsystem, SubsystemContext},
+};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum HarnessErrors {
+    #[error("there is no instance {0}")]
+    UnknownInstance(usize),
+
+    #[error("instances {0} and {1} are already connected")]
+    AlreadyConnected(usize, usize),
+
+    #[error("instances {0} and {1} are not connected")]
+    NotConnected(usize, usize),
+
+    #[error("an instance cannot connect to itself")]
+    SelfConnection,
+
+    #[error("unable to forward {0}: {1}")]
+    Forward(String, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InstanceId(pub usize);
+
+type Outbox = Arc<Mutex<Vec<(InstanceId, WireEvent)>>>;
+
+// The in process connections of one instance. Forwarded events collect in
+// the outbox while they are dispatched and go out on the next tick, what
+// the peers sent is emitted on the instance's queue.
+struct HarnessPeers {
+    peers: Vec<(InstanceId, EventTransport<InProcessTransport>)>,
+    outbox: Outbox,
+}
+
+impl Subsystem for HarnessPeers {
+    fn get_name(&self) -> &str {
+        "HarnessPeers"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        let outgoing: Vec<(InstanceId, WireEvent)> = match self.outbox.lock() {
+            Ok(mut outbox) => outbox.drain(..).collect(),
+            Err(_) => Vec::new(),
+        };
+        for (to, event) in outgoing {
+            let Some((_, peer)) = self.peers.iter_mut().find(|(id, _)| *id == to) else {
+                warn!(
+                    "dropping {}, not connected to instance {}",
+                    event.name, to.0
+                );
+                continue;
+            };
+            if let Err(err) = peer.send_event(&event) {
+                warn!(
+                    "unable to send {} to instance {}: {}",
+                    event.name, to.0, err
+                );
+            }
+        }
+
+        self.peers
+            .retain_mut(|(id, peer)| match peer.pump_into(ctx.event_queue) {
+                Ok(_) => true,
+                Err(err) => {
+                    info!("instance {} disconnected: {}", id.0, err);
+                    false
+                }
+            });
+    }
+}
+
+struct Instance {
+    name: String,
+    app: Application,
+    outbox: Outbox,
+    exit_reason: Option<ExitReason>,
+}
+
+// Runs several headless engine instances side by side in one process for
+// multiplayer integration tests. Instances are connected over in process
+// transports, scripted inputs are emitted on the frame they are due and
+// every `step` runs one frame of each instance in the order they were
+// spawned, so runs are deterministic:
+//
+//     let mut harness = TestHarness::new(Duration::from_millis(16));
+//     let server = harness.spawn("server", Server::new(state.clone()));
+//     let client = harness.spawn("client", Client::default());
+//     harness.connect(server, client)?;
+//     harness.forward(client, server, "MoveInput")?;
+//     harness.forward(server, client, "Snapshot")?;
+//     harness.input_at(10, client, WireEvent::new("MoveInput", vec![1]))?;
+//     assert!(harness.run_until(100, |_| state.lock().unwrap().moved));
+//
+// Instances never share events, each one has its own queue (see
+// `ApplicationBuilder::with_event_queue`), the forwarded events are the
+// only traffic between them.
+pub struct TestHarness {
+    instances: Vec<Instance>,
+    // (frame, instance, event), in the order they were scheduled
+    scripted: Vec<(u64, InstanceId, Box<dyn Event>)>,
+    delta: Duration,
+    frame: u64,
+}
+
+impl TestHarness {
+    // Every frame is `delta` long, it is also the fixed timestep
+    pub fn new(delta: Duration) -> Self {
+        Self {
+            instances: Vec::new(),
+            scripted: Vec::new(),
+            delta,
+            frame: 0,
+        }
+    }
+
+    pub fn spawn(&mut self, name: &str, app: impl AloyApp + 'static) -> InstanceId {
+        let mut app = Application::builder()
+            .with_app(app)
+            .with_name(name)
+            .with_fixed_delta(self.delta)
+            .headless()
+            .build();
+        let outbox = Outbox::default();
+        let peers = HarnessPeers {
+            peers: Vec::new(),
+            outbox: outbox.clone(),
+        };
+        if let Err(err) = app.get_engine().add_subsystem(peers) {
+            error!("unable to add the harness peers to {}: {}", name, err);
+        }
+        self.instances.push(Instance {
+            name: name.to_string(),
+            app,
+            outbox,
+            exit_reason: None,
+        });
+        InstanceId(self.instances.len() - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    // Frames stepped so far
+    pub fn get_frame(&self) -> u64 {
+        self.frame
+    }
+
+    fn get_instance_mut(&mut self, id: InstanceId) -> Result<&mut Instance, HarnessErrors> {
+        self.instances
+            .get_mut(id.0)
+            .ok_or(HarnessErrors::UnknownInstance(id.0))
+    }
+
+    pub fn get_name(&self, id: InstanceId) -> Option<&str> {
+        self.instances
+            .get(id.0)
+            .map(|instance| instance.name.as_str())
+    }
+
+    pub fn get_engine(&mut self, id: InstanceId) -> Option<&mut Engine> {
+        self.instances
+            .get_mut(id.0)
+            .map(|instance| instance.app.get_engine())
+    }
+
+    pub fn get_app(&mut self, id: InstanceId) -> Option<&mut Application> {
+        self.instances
+            .get_mut(id.0)
+            .map(|instance| &mut instance.app)
+    }
+
+    // Set once the instance exited, it is not stepped anymore
+    pub fn get_exit_reason(&self, id: InstanceId) -> Option<&ExitReason> {
+        self.instances
+            .get(id.0)
+            .and_then(|instance| instance.exit_reason.as_ref())
+    }
+
+    fn peers_mut(&mut self, id: InstanceId) -> Result<&mut HarnessPeers, HarnessErrors> {
+        self.get_instance_mut(id)?
+            .app
+            .get_engine()
+            .get_subsystem_mut::<HarnessPeers>()
+            .ok_or(HarnessErrors::UnknownInstance(id.0))
+    }
+
+    pub fn is_connected(&mut self, a: InstanceId, b: InstanceId) -> bool {
+        self.peers_mut(a)
+            .is_ok_and(|peers| peers.peers.iter().any(|(id, _)| *id == b))
+    }
+
+    pub fn connect(&mut self, a: InstanceId, b: InstanceId) -> Result<(), HarnessErrors> {
+        if a == b {
+            return Err(HarnessErrors::SelfConnection);
+        }
+        self.get_instance_mut(b)?;
+        if self.is_connected(a, b) {
+            return Err(HarnessErrors::AlreadyConnected(a.0, b.0));
+        }
+        let (a_end, b_end) = InProcessTransport::pair();
+        self.peers_mut(a)?
+            .peers
+            .push((b, EventTransport::new(a_end)));
+        self.peers_mut(b)?
+            .peers
+            .push((a, EventTransport::new(b_end)));
+        Ok(())
+    }
+
+    // Drops the connection on both ends, like a client losing its network
+    pub fn disconnect(&mut self, a: InstanceId, b: InstanceId) -> Result<(), HarnessErrors> {
+        if !self.is_connected(a, b) {
+            return Err(HarnessErrors::NotConnected(a.0, b.0));
+        }
+        self.peers_mut(a)?.peers.retain(|(id, _)| *id != b);
+        self.peers_mut(b)?.peers.retain(|(id, _)| *id != a);
+        Ok(())
+    }
+
+    // Every `event_name` event dispatched on `from` is sent to `to` and
+    // emitted there. Forwarding is one way, forwarding the same name back
+    // would echo the events forever.
+    pub fn forward(
+        &mut self,
+        from: InstanceId,
+        to: InstanceId,
+        event_name: &str,
+    ) -> Result<(), HarnessErrors> {
+        if !self.is_connected(from, to) {
+            return Err(HarnessErrors::NotConnected(from.0, to.0));
+        }
+        let instance = self.get_instance_mut(from)?;
+        let outbox = instance.outbox.clone();
+        let observer = move |event: &dyn Event| {
+            if let Ok(mut outbox) = outbox.lock() {
+                outbox.push((to, to_wire_event(event)));
+            }
+        };
+        match instance.app.on_event(event_name.to_string(), observer) {
+            Some(err) => Err(HarnessErrors::Forward(
+                event_name.to_string(),
+                format!("{:?}", err),
+            )),
+            None => Ok(()),
+        }
+    }
+
+    // Emits the event on the instance's queue, it is dispatched during the
+    // instance's next frame
+    pub fn input(
+        &mut self,
+        id: InstanceId,
+        event: impl Event + 'static,
+    ) -> Result<(), HarnessErrors> {
+        let instance = self.get_instance_mut(id)?;
+        if let Err(err) = instance.app.get_engine().emit(Box::new(event)) {
+            error!("unable to emit input for {}: {:?}", instance.name, err);
+        }
+        Ok(())
+    }
+
+    // Like `input`, on the given frame. Frames that already ran get it on
+    // the next step.
+    pub fn input_at(
+        &mut self,
+        frame: u64,
+        id: InstanceId,
+        event: impl Event + 'static,
+    ) -> Result<(), HarnessErrors> {
+        self.get_instance_mut(id)?;
+        self.scripted.push((frame, id, Box::new(event)));
+        Ok(())
+    }
+
+    // Runs one frame of every instance that did not exit
+    pub fn step(&mut self) {
+        let frame = self.frame;
+        let (due, later) = std::mem::take(&mut self.scripted)
+            .into_iter()
+            .partition(|(at, _, _)| *at <= frame);
+        self.scripted = later;
+        for (_, id, event) in due {
+            let instance = &mut self.instances[id.0];
+            if let Err(err) = instance
+                .app
+                .get_engine()
+                .get_event_queue()
+                .emit_boxed(event)
+            {
+                error!("unable to emit input for {}: {:?}", instance.name, err);
+            }
+        }
+
+        for instance in self.instances.iter_mut() {
+            if instance.exit_reason.is_some() {
+                continue;
+            }
+            if let Some(reason) = instance.app.step(self.delta) {
+                info!("instance {} exited with {:?}", instance.name, reason);
+                instance.exit_reason = Some(reason);
+            }
+        }
+        self.frame += 1;
+    }
+
+    pub fn run(&mut self, frames: u64) {
+        for _ in 0..frames {
+            self.step();
+        }
+    }
+
+    // Steps until `condition` holds, checked before every frame. False when
+    // it still did not after `max_frames`.
+    pub fn run_until(
+        &mut self,
+        max_frames: u64,
+        mut condition: impl FnMut(&mut TestHarness) -> bool,
+    ) -> bool {
+        for _ in 0..max_frames {
+            if condition(self) {
+                return true;
+            }
+            self.step();
+        }
+        condition(self)
+    }
+}
+
+impl Debug for TestHarness {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TestHarness")
+            .field(
+                "instances",
+                &self
+                    .instances
+                    .iter()
+                    .map(|instance| instance.name.as_str())
+                    .collect::<Vec<_>>(),
+            )
+            .field("frame", &self.frame)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The server owns the position, clients send moves and draw what the
+    // snapshots say
+    struct Server {
+        position: i32,
+    }
+
+    impl AloyApp for Server {
+        fn on_event(&mut self, engine: &mut Engine, event: &dyn Event) {
+            if event.get_name() != "MoveInput" {
+                return;
+            }
+            if let Some(payload) = event
+                .get_data()
+                .and_then(|data| data.get_ref::<Vec<u8>>().cloned())
+            {
+                self.position += payload[0] as i8 as i32;
+                let snapshot = WireEvent::new("Snapshot", self.position.to_le_bytes().to_vec());
+                engine.emit(Box::new(snapshot)).unwrap();
+            }
+        }
+    }
+
+    struct Client {
+        seen: Arc<Mutex<Option<i32>>>,
+    }
+
+    impl AloyApp for Client {
+        fn on_event(&mut self, _engine: &mut Engine, event: &dyn Event) {
+            if event.get_name() != "Snapshot" {
+                return;
+            }
+            if let Some(payload) = event
+                .get_data()
+                .and_then(|data| data.get_ref::<Vec<u8>>().cloned())
+            {
+                let position = i32::from_le_bytes(payload[..4].try_into().unwrap());
+                self.seen.lock().unwrap().replace(position);
+            }
+        }
+    }
+
+    #[test]
+    fn test_replicates_between_instances() {
+        let mut harness = TestHarness::new(Duration::from_millis(16));
+        let server = harness.spawn("server", Server { position: 0 });
+        let mut clients = Vec::new();
+        for name in ["alice", "bob"] {
+            let seen = Arc::new(Mutex::new(None));
+            let client = harness.spawn(name, Client { seen: seen.clone() });
+            harness.connect(server, client).unwrap();
+            harness.forward(client, server, "MoveInput").unwrap();
+            harness.forward(server, client, "Snapshot").unwrap();
+            clients.push((client, seen));
+        }
+        assert_eq!(
+            harness.connect(server, clients[0].0),
+            Err(HarnessErrors::AlreadyConnected(0, 1))
+        );
+
+        let (alice, alice_seen) = clients[0].clone();
+        let (bob, bob_seen) = clients[1].clone();
+        harness
+            .input_at(5, alice, WireEvent::new("MoveInput", vec![3]))
+            .unwrap();
+        harness.run(5);
+        assert_eq!(*alice_seen.lock().unwrap(), None);
+
+        // the move travels to the server and its snapshot to every client
+        assert!(harness.run_until(10, |_| *bob_seen.lock().unwrap() == Some(3)));
+        assert_eq!(*alice_seen.lock().unwrap(), Some(3));
+
+        // bob drops, alice keeps playing
+        harness.disconnect(server, bob).unwrap();
+        harness
+            .input(alice, WireEvent::new("MoveInput", vec![-1i8 as u8]))
+            .unwrap();
+        assert!(harness.run_until(10, |_| *alice_seen.lock().unwrap() == Some(2)));
+        assert_eq!(*bob_seen.lock().unwrap(), Some(3));
+
+        harness.get_engine(bob).unwrap().exit(ExitReason::NORMAL);
+        harness.run(3);
+        assert_eq!(harness.get_exit_reason(bob), Some(&ExitReason::NORMAL));
+        assert_eq!(harness.get_exit_reason(server), None);
+    }
+}
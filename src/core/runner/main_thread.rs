@@ -0,0 +1,70 @@
+use std::{
+    fmt::Debug,
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+use thiserror::Error;
+
+use super::engine::Engine;
+
+pub type MainThreadTask = Box<dyn FnOnce(&mut Engine) + Send>;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MainThreadErrors {
+    #[error("the application owning the main thread queue is gone")]
+    Closed,
+}
+
+// Cheap to clone and Send, hand it to worker threads or async handlers that
+// need something done on the main thread
+#[derive(Clone)]
+pub struct MainThreadHandle {
+    sender: Sender<MainThreadTask>,
+}
+
+impl MainThreadHandle {
+    pub fn run(
+        &self,
+        task: impl FnOnce(&mut Engine) + Send + 'static,
+    ) -> Result<(), MainThreadErrors> {
+        self.sender
+            .send(Box::new(task))
+            .map_err(|_| MainThreadErrors::Closed)
+    }
+}
+
+impl Debug for MainThreadHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MainThreadHandle").finish()
+    }
+}
+
+// Tasks are drained once per frame, right before the queued events are
+// dispatched. Tasks scheduled while draining run on the next frame.
+pub(crate) struct MainThreadQueue {
+    sender: Sender<MainThreadTask>,
+    reciever: Receiver<MainThreadTask>,
+}
+
+impl MainThreadQueue {
+    pub(crate) fn new() -> Self {
+        let (sender, reciever) = mpsc::channel();
+        Self { sender, reciever }
+    }
+
+    pub(crate) fn get_handle(&self) -> MainThreadHandle {
+        MainThreadHandle {
+            sender: self.sender.clone(),
+        }
+    }
+
+    pub(crate) fn take_tasks(&self) -> Vec<MainThreadTask> {
+        self.reciever.try_iter().collect()
+    }
+}
+
+impl Debug for MainThreadQueue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MainThreadQueue").finish()
+    }
+}
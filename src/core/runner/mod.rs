@@ -1,2 +1,12 @@
+pub mod aloy_app;
 pub mod applications;
+#[cfg(feature = "tokio")]
+pub mod async_events;
+pub mod builder;
+pub mod engine;
 pub mod exit_handlers;
+pub mod game_state;
+pub mod harness;
+pub mod main_thread;
+pub mod plugin;
+pub mod subsystem;
@@ -0,0 +1,103 @@
+use thiserror::Error;
+
+use super::applications::Application;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PluginErrors {
+    #[error("plugin {0} was already added")]
+    AlreadyAdded(String),
+}
+
+// A plugin bundles the handlers and subsystems of one feature (physics,
+// audio, networking...) so it can be added to an application in one call.
+pub trait Plugin {
+    fn build(&self, app: &mut Application);
+
+    fn get_name(&self) -> &str {
+        std::any::type_name::<Self>()
+    }
+
+    // Unique plugins can only be added once per application
+    fn is_unique(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    };
+
+    use crate::{
+        core::runner::subsystem::{Subsystem, SubsystemContext},
+        event_system::{engine_events::application_events::ApplicationEvents, event::Event},
+    };
+
+    use super::*;
+
+    struct PhysicsSubsystem;
+
+    impl Subsystem for PhysicsSubsystem {
+        fn get_name(&self) -> &str {
+            "Physics"
+        }
+
+        fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    struct PhysicsPlugin {
+        collisions: Arc<AtomicU8>,
+    }
+
+    impl Plugin for PhysicsPlugin {
+        fn build(&self, app: &mut Application) {
+            let collisions = Arc::clone(&self.collisions);
+            app.on_event("ExampleEvent".to_string(), move |_event: &dyn Event| {
+                collisions.fetch_add(1, Ordering::SeqCst);
+            });
+            app.get_engine()
+                .add_subsystem(PhysicsSubsystem)
+                .expect("subsystem should be registered");
+        }
+    }
+
+    #[test]
+    fn test_plugin_registers_handlers_and_subsystems() {
+        let collisions = Arc::new(AtomicU8::new(0));
+        let mut app = Application::default();
+
+        app.add_plugin(PhysicsPlugin {
+            collisions: Arc::clone(&collisions),
+        })
+        .expect("plugin should be added");
+
+        assert!(app.has_plugin(std::any::type_name::<PhysicsPlugin>()));
+        assert!(app
+            .get_engine()
+            .get_subsystem::<PhysicsSubsystem>()
+            .is_some());
+
+        app.dispatch(&ApplicationEvents::ExampleEvent);
+        assert_eq!(collisions.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_unique_plugin_can_only_be_added_once() {
+        let mut app = Application::default();
+        let plugin = || PhysicsPlugin {
+            collisions: Arc::new(AtomicU8::new(0)),
+        };
+
+        assert!(app.add_plugin(plugin()).is_ok());
+        assert_eq!(
+            app.add_plugin(plugin()),
+            Err(PluginErrors::AlreadyAdded(
+                std::any::type_name::<PhysicsPlugin>().to_string()
+            ))
+        );
+    }
+}
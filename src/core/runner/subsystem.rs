@@ -0,0 +1,297 @@
+use std::{any::Any, fmt::Debug, sync::Arc};
+
+use log::{error, info};
+use thiserror::Error;
+
+use crate::{
+    core::{
+        config::EngineConfig,
+        memory::{self, MemoryTag},
+        time::Time,
+    },
+    event_system::event_queue::EventQueue,
+};
+
+use super::exit_handlers::ExitHandlers;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SubsystemErrors {
+    #[error("subsystem {0} failed to initalize: {1}")]
+    InitFailed(String, String),
+
+    #[error("subsystem {0} is already registered")]
+    AlreadyRegistered(String),
+}
+
+// Everything a subsystem is allowed to touch while the engine drives it
+pub struct SubsystemContext<'a> {
+    pub event_queue: &'a Arc<EventQueue>,
+    pub time: &'a Time,
+    // for shutdown callbacks that have to run before the loop ends
+    pub exit_handlers: &'a ExitHandlers,
+}
+
+pub trait Subsystem: Any {
+    fn get_name(&self) -> &str;
+    fn init(&mut self, ctx: &mut SubsystemContext) -> Result<(), String>;
+    fn tick(&mut self, _ctx: &mut SubsystemContext) {}
+    fn shutdown(&mut self, _ctx: &mut SubsystemContext) {}
+
+    // Called when the engine config changed, e.g. the renderer re-applies
+    // vsync here
+    fn on_config_reloaded(&mut self, _config: &EngineConfig) {}
+}
+
+// Subsystems are initalized in the order they were registered and shut down
+// in the reverse order, so a subsystem can always rely on the ones registered
+// before it (renderer after window, audio after assets...).
+#[derive(Default)]
+pub struct SubsystemManager {
+    subsystems: Vec<Box<dyn Subsystem>>,
+    // what the subsystems allocate is counted under their name
+    memory_tags: Vec<MemoryTag>,
+    initalized: usize,
+}
+
+impl SubsystemManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, subsystem: Box<dyn Subsystem>) -> Result<(), SubsystemErrors> {
+        let name = subsystem.get_name().to_string();
+        if self.subsystems.iter().any(|s| s.get_name() == name) {
+            return Err(SubsystemErrors::AlreadyRegistered(name));
+        }
+        info!("registering subsystem {}", name);
+        self.memory_tags.push(memory::register_tag(&name));
+        self.subsystems.push(subsystem);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.subsystems.len()
+    }
+
+    // In registration order
+    pub fn get_names(&self) -> Vec<&str> {
+        self.subsystems
+            .iter()
+            .map(|subsystem| subsystem.get_name())
+            .collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subsystems.is_empty()
+    }
+
+    pub fn get<T: Subsystem>(&self) -> Option<&T> {
+        self.subsystems
+            .iter()
+            .find_map(|s| (s.as_ref() as &dyn Any).downcast_ref::<T>())
+    }
+
+    pub fn get_mut<T: Subsystem>(&mut self) -> Option<&mut T> {
+        self.subsystems
+            .iter_mut()
+            .find_map(|s| (s.as_mut() as &mut dyn Any).downcast_mut::<T>())
+    }
+
+    // Initalizes every subsystem that is not running yet. If one fails the
+    // ones that were already started are shut down again before returning.
+    pub fn init_all(&mut self, ctx: &mut SubsystemContext) -> Result<(), SubsystemErrors> {
+        while self.initalized < self.subsystems.len() {
+            let subsystem = &mut self.subsystems[self.initalized];
+            info!("initalizing subsystem {}", subsystem.get_name());
+            let _scope = memory::scope(self.memory_tags[self.initalized]);
+            if let Err(err) = subsystem.init(ctx) {
+                let name = subsystem.get_name().to_string();
+                error!("subsystem {} failed to initalize: {}", name, err);
+                self.shutdown_all(ctx);
+                return Err(SubsystemErrors::InitFailed(name, err));
+            }
+            self.initalized += 1;
+        }
+        Ok(())
+    }
+
+    pub fn tick_all(&mut self, ctx: &mut SubsystemContext) {
+        let tagged = self.subsystems.iter_mut().zip(self.memory_tags.iter());
+        for (subsystem, tag) in tagged.take(self.initalized) {
+            let _scope = memory::scope(*tag);
+            subsystem.tick(ctx);
+        }
+    }
+
+    pub fn apply_config_all(&mut self, config: &EngineConfig) {
+        for subsystem in self.subsystems.iter_mut() {
+            subsystem.on_config_reloaded(config);
+        }
+    }
+
+    pub fn shutdown_all(&mut self, ctx: &mut SubsystemContext) {
+        while self.initalized > 0 {
+            self.initalized -= 1;
+            let subsystem = &mut self.subsystems[self.initalized];
+            info!("shutting down subsystem {}", subsystem.get_name());
+            subsystem.shutdown(ctx);
+        }
+    }
+}
+
+impl Debug for SubsystemManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SubsystemManager")
+            .field(
+                "subsystems",
+                &self
+                    .subsystems
+                    .iter()
+                    .map(|s| s.get_name())
+                    .collect::<Vec<_>>(),
+            )
+            .field("initalized", &self.initalized)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    struct TestSubsystem {
+        name: String,
+        fail_init: bool,
+        ticks: u32,
+        journal: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl TestSubsystem {
+        fn new(name: &str, journal: &Arc<Mutex<Vec<String>>>) -> Self {
+            Self {
+                name: name.to_string(),
+                fail_init: false,
+                ticks: 0,
+                journal: Arc::clone(journal),
+            }
+        }
+    }
+
+    impl Subsystem for TestSubsystem {
+        fn get_name(&self) -> &str {
+            &self.name
+        }
+
+        fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+            if self.fail_init {
+                return Err("boom".to_string());
+            }
+            self.journal
+                .lock()
+                .unwrap()
+                .push(format!("init {}", self.name));
+            Ok(())
+        }
+
+        fn tick(&mut self, _ctx: &mut SubsystemContext) {
+            self.ticks += 1;
+        }
+
+        fn shutdown(&mut self, _ctx: &mut SubsystemContext) {
+            self.journal
+                .lock()
+                .unwrap()
+                .push(format!("shutdown {}", self.name));
+        }
+    }
+
+    #[test]
+    fn test_init_in_order_and_shutdown_in_reverse() {
+        let queue = Arc::new(EventQueue::new());
+        let time = Time::new();
+        let exit_handlers = ExitHandlers::new();
+        let mut ctx = SubsystemContext {
+            event_queue: &queue,
+            time: &time,
+            exit_handlers: &exit_handlers,
+        };
+        let journal = Arc::new(Mutex::new(Vec::new()));
+
+        let mut manager = SubsystemManager::new();
+        for name in ["window", "renderer", "audio"] {
+            manager
+                .register(Box::new(TestSubsystem::new(name, &journal)))
+                .unwrap();
+        }
+
+        manager.init_all(&mut ctx).expect("init should succeed");
+        manager.tick_all(&mut ctx);
+        manager.shutdown_all(&mut ctx);
+
+        assert_eq!(
+            *journal.lock().unwrap(),
+            vec![
+                "init window",
+                "init renderer",
+                "init audio",
+                "shutdown audio",
+                "shutdown renderer",
+                "shutdown window",
+            ]
+        );
+        assert_eq!(manager.get::<TestSubsystem>().unwrap().ticks, 1);
+    }
+
+    #[test]
+    fn test_failed_init_rolls_back() {
+        let queue = Arc::new(EventQueue::new());
+        let time = Time::new();
+        let exit_handlers = ExitHandlers::new();
+        let mut ctx = SubsystemContext {
+            event_queue: &queue,
+            time: &time,
+            exit_handlers: &exit_handlers,
+        };
+        let journal = Arc::new(Mutex::new(Vec::new()));
+
+        let mut failing = TestSubsystem::new("renderer", &journal);
+        failing.fail_init = true;
+
+        let mut manager = SubsystemManager::new();
+        manager
+            .register(Box::new(TestSubsystem::new("window", &journal)))
+            .unwrap();
+        manager.register(Box::new(failing)).unwrap();
+
+        let result = manager.init_all(&mut ctx);
+
+        assert_eq!(
+            result,
+            Err(SubsystemErrors::InitFailed(
+                "renderer".to_string(),
+                "boom".to_string()
+            ))
+        );
+        assert_eq!(
+            *journal.lock().unwrap(),
+            vec!["init window", "shutdown window"]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_registration() {
+        let journal = Arc::new(Mutex::new(Vec::new()));
+        let mut manager = SubsystemManager::new();
+        manager
+            .register(Box::new(TestSubsystem::new("audio", &journal)))
+            .unwrap();
+
+        let result = manager.register(Box::new(TestSubsystem::new("audio", &journal)));
+        assert_eq!(
+            result,
+            Err(SubsystemErrors::AlreadyRegistered("audio".to_string()))
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+};
+
+use log::{error, info, warn};
+use thiserror::Error;
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::engine_events::world_events::{SceneLoadFailed, SceneLoadProgress, WorldEvents},
+};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SceneLoadErrors {
+    #[error("scene {0} is already loading")]
+    AlreadyLoading(String),
+
+    #[error("unable to start loading scene {0}: {1}")]
+    Spawn(String, String),
+}
+
+// Handed to a preload job to report how far it got and to notice when the
+// load was cancelled
+#[derive(Debug, Clone)]
+pub struct LoadProgress {
+    sender: Sender<(String, f32)>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl LoadProgress {
+    // `progress` is of the whole load from 0 to 1, `stage` says what is
+    // being done, e.g. "textures" or "entities"
+    pub fn report(&self, stage: &str, progress: f32) {
+        // the loader is gone when this fails, the job finds out by polling
+        // `is_cancelled`
+        let _ = self
+            .sender
+            .send((stage.to_string(), progress.clamp(0.0, 1.0)));
+    }
+
+    // Jobs should check between steps and give up when true
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+struct Loading<S> {
+    stage: String,
+    progress: f32,
+    receiver: Receiver<(String, f32)>,
+    cancelled: Arc<AtomicBool>,
+    thread: JoinHandle<Result<S, String>>,
+}
+
+// Builds scenes on background threads while the current one keeps running.
+// A preload job loads the assets, bakes what it needs and instantiates the
+// entities into a world of its own that nothing updates yet, reporting its
+// progress on the way. Every report becomes a SceneLoadProgress event for
+// loading bars and a finished scene waits, announced by SceneLoaded, until
+// the game takes it and swaps it in within one frame:
+//
+//     loader.preload("level_2", |progress| {
+//         let assets = load_assets(&progress)?;
+//         progress.report("entities", 0.8);
+//         Ok(Level::instantiate(assets))
+//     })?;
+//     // later, in on_update
+//     if let Some(level) = loader.take("level_2") {
+//         return StateTransition::Switch(Box::new(Gameplay::new(level)));
+//     }
+pub struct SceneLoader<S> {
+    loading: HashMap<String, Loading<S>>,
+    ready: HashMap<String, S>,
+}
+
+impl<S: Send + 'static> SceneLoader<S> {
+    pub fn new() -> Self {
+        Self {
+            loading: HashMap::new(),
+            ready: HashMap::new(),
+        }
+    }
+
+    // Starts loading `name`, a scene that is already ready is replaced once
+    // the new one finishes
+    pub fn preload(
+        &mut self,
+        name: &str,
+        job: impl FnOnce(&LoadProgress) -> Result<S, String> + Send + 'static,
+    ) -> Result<(), SceneLoadErrors> {
+        if self.loading.contains_key(name) {
+            return Err(SceneLoadErrors::AlreadyLoading(name.to_string()));
+        }
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let progress = LoadProgress {
+            sender,
+            cancelled: cancelled.clone(),
+        };
+        let thread = thread::Builder::new()
+            .name(format!("scene loader {}", name))
+            .spawn(move || job(&progress))
+            .map_err(|err| SceneLoadErrors::Spawn(name.to_string(), err.to_string()))?;
+        info!("preloading scene {}", name);
+        self.loading.insert(
+            name.to_string(),
+            Loading {
+                stage: String::new(),
+                progress: 0.0,
+                receiver,
+                cancelled,
+                thread,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn is_loading(&self, name: &str) -> bool {
+        self.loading.contains_key(name)
+    }
+
+    pub fn is_ready(&self, name: &str) -> bool {
+        self.ready.contains_key(name)
+    }
+
+    // The last reported progress, 1 once the scene is ready
+    pub fn get_progress(&self, name: &str) -> Option<f32> {
+        match self.loading.get(name) {
+            Some(loading) => Some(loading.progress),
+            None => self.ready.contains_key(name).then_some(1.0),
+        }
+    }
+
+    pub fn get_stage(&self, name: &str) -> Option<&str> {
+        self.loading.get(name).map(|loading| loading.stage.as_str())
+    }
+
+    // Hands over a ready scene, None while it is still loading
+    pub fn take(&mut self, name: &str) -> Option<S> {
+        self.ready.remove(name)
+    }
+
+    // Stops waiting for `name` and drops it if it was ready. The job keeps
+    // running until it checks `is_cancelled`, what it returns is dropped.
+    pub fn cancel(&mut self, name: &str) -> bool {
+        if let Some(loading) = self.loading.remove(name) {
+            loading.cancelled.store(true, Ordering::Relaxed);
+            info!("cancelled loading scene {}", name);
+            return true;
+        }
+        self.ready.remove(name).is_some()
+    }
+
+    // Collects the progress and the finished jobs, returns the events to
+    // emit for them
+    pub fn poll(&mut self) -> Vec<WorldEvents> {
+        let mut events = Vec::new();
+        let mut finished = Vec::new();
+        for (name, loading) in self.loading.iter_mut() {
+            // checked first so the reports of a finished job are all in
+            let done = loading.thread.is_finished();
+            for (stage, progress) in loading.receiver.try_iter() {
+                // reports never go backwards so loading bars do not jump
+                loading.progress = loading.progress.max(progress);
+                loading.stage = stage;
+                events.push(WorldEvents::SceneLoadProgress(SceneLoadProgress {
+                    scene: name.clone(),
+                    stage: loading.stage.clone(),
+                    progress: loading.progress,
+                }));
+            }
+            if done {
+                finished.push(name.clone());
+            }
+        }
+
+        for name in finished {
+            let Some(loading) = self.loading.remove(&name) else {
+                continue;
+            };
+            let result = loading
+                .thread
+                .join()
+                .unwrap_or_else(|_| Err("the preload job panicked".to_string()));
+            match result {
+                Ok(scene) => {
+                    info!("scene {} is ready", name);
+                    self.ready.insert(name.clone(), scene);
+                    events.push(WorldEvents::SceneLoaded(name));
+                }
+                Err(err) => {
+                    warn!("unable to load scene {}: {}", name, err);
+                    events.push(WorldEvents::SceneLoadFailed(SceneLoadFailed {
+                        scene: name,
+                        error: err,
+                    }));
+                }
+            }
+        }
+        events
+    }
+}
+
+impl<S: Send + 'static> Default for SceneLoader<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Debug for SceneLoader<S> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SceneLoader")
+            .field("loading", &self.loading.keys().collect::<Vec<_>>())
+            .field("ready", &self.ready.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl<S: Send + 'static> Subsystem for SceneLoader<S> {
+    fn get_name(&self) -> &str {
+        "SceneLoader"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        for event in self.poll() {
+            if let Err(err) = ctx.event_queue.emit(Box::new(event)) {
+                error!("unable to emit scene loading event {:?}", err);
+            }
+        }
+    }
+
+    fn shutdown(&mut self, _ctx: &mut SubsystemContext) {
+        for (_, loading) in self.loading.drain() {
+            loading.cancelled.store(true, Ordering::Relaxed);
+        }
+        self.ready.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    fn poll_until(loader: &mut SceneLoader<Vec<u32>>, name: &str) -> Vec<WorldEvents> {
+        let started = Instant::now();
+        let mut events = Vec::new();
+        while loader.is_loading(name) && started.elapsed() < Duration::from_secs(5) {
+            events.extend(loader.poll());
+            thread::sleep(Duration::from_millis(1));
+        }
+        events
+    }
+
+    #[test]
+    fn test_preload_in_background() {
+        let mut loader = SceneLoader::new();
+        let (go, wait) = mpsc::channel::<()>();
+        loader
+            .preload("level_2", move |progress| {
+                progress.report("assets", 0.5);
+                wait.recv().map_err(|err| err.to_string())?;
+                progress.report("entities", 0.9);
+                Ok(vec![1, 2, 3])
+            })
+            .unwrap();
+        assert_eq!(
+            loader.preload("level_2", |_| Ok(Vec::new())),
+            Err(SceneLoadErrors::AlreadyLoading("level_2".to_string()))
+        );
+
+        // the job is still waiting, the current scene keeps running
+        let started = Instant::now();
+        while loader.get_progress("level_2") != Some(0.5) {
+            loader.poll();
+            assert!(started.elapsed() < Duration::from_secs(5));
+        }
+        assert_eq!(loader.get_stage("level_2"), Some("assets"));
+        assert_eq!(loader.take("level_2"), None);
+
+        go.send(()).unwrap();
+        let events = poll_until(&mut loader, "level_2");
+        assert_eq!(
+            events,
+            vec![
+                WorldEvents::SceneLoadProgress(SceneLoadProgress {
+                    scene: "level_2".to_string(),
+                    stage: "entities".to_string(),
+                    progress: 0.9,
+                }),
+                WorldEvents::SceneLoaded("level_2".to_string()),
+            ]
+        );
+        assert_eq!(loader.get_progress("level_2"), Some(1.0));
+        assert_eq!(loader.take("level_2"), Some(vec![1, 2, 3]));
+        assert!(!loader.is_ready("level_2"));
+
+        loader
+            .preload("broken", |_| Err("missing map".to_string()))
+            .unwrap();
+        let events = poll_until(&mut loader, "broken");
+        assert_eq!(
+            events.last(),
+            Some(&WorldEvents::SceneLoadFailed(SceneLoadFailed {
+                scene: "broken".to_string(),
+                error: "missing map".to_string(),
+            }))
+        );
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use log::{info, warn};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SettingsErrors {
+    #[error("unable to read or write settings file: {0}")]
+    Io(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SettingValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+}
+
+// Where a value comes from. Later layers win, so a session override beats
+// the player's settings, which beat the platform profile and the defaults
+// the engine and game ship with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SettingsLayer {
+    Defaults,
+    Platform,
+    User,
+    // command line or console overrides, never saved
+    Session,
+}
+
+impl SettingsLayer {
+    // Lowest to highest priority
+    pub const ALL: [SettingsLayer; 4] = [
+        SettingsLayer::Defaults,
+        SettingsLayer::Platform,
+        SettingsLayer::User,
+        SettingsLayer::Session,
+    ];
+}
+
+// Runtime key/value settings. Keys are dotted paths like
+// `input.axis.MoveX.dead_zone` so subsystems can look up their own section.
+// Every key can be set in each layer, reads see the highest one. The plain
+// `set`, `remove`, `save` and `load` work on the user layer.
+#[derive(Debug, Default, Clone)]
+pub struct Settings {
+    layers: [HashMap<String, SettingValue>; 4],
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns the previous user value of the key, if any
+    pub fn set(&mut self, key: &str, value: SettingValue) -> Option<SettingValue> {
+        self.set_in(SettingsLayer::User, key, value)
+    }
+
+    pub fn set_in(
+        &mut self,
+        layer: SettingsLayer,
+        key: &str,
+        value: SettingValue,
+    ) -> Option<SettingValue> {
+        info!("setting {} to {:?} in {:?}", key, value, layer);
+        self.layers[layer as usize].insert(key.to_string(), value)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&SettingValue> {
+        self.layers.iter().rev().find_map(|values| values.get(key))
+    }
+
+    // The layer the value of `get` comes from
+    pub fn get_layer(&self, key: &str) -> Option<SettingsLayer> {
+        self.resolve(key).first().map(|(layer, _)| *layer)
+    }
+
+    // Every layer that sets the key, the one that wins first
+    pub fn resolve(&self, key: &str) -> Vec<(SettingsLayer, &SettingValue)> {
+        SettingsLayer::ALL
+            .iter()
+            .rev()
+            .filter_map(|layer| Some((*layer, self.layers[*layer as usize].get(key)?)))
+            .collect()
+    }
+
+    // Removes the user value, a value from a lower layer shows through again
+    pub fn remove(&mut self, key: &str) -> Option<SettingValue> {
+        self.remove_in(SettingsLayer::User, key)
+    }
+
+    pub fn remove_in(&mut self, layer: SettingsLayer, key: &str) -> Option<SettingValue> {
+        self.layers[layer as usize].remove(key)
+    }
+
+    // Drops every value of the layer, resetting `User` is "restore defaults"
+    pub fn reset_layer(&mut self, layer: SettingsLayer) {
+        info!("resetting the {:?} settings", layer);
+        self.layers[layer as usize].clear();
+    }
+
+    pub fn get_layer_values(&self, layer: SettingsLayer) -> &HashMap<String, SettingValue> {
+        &self.layers[layer as usize]
+    }
+
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)? {
+            SettingValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        match self.get(key)? {
+            SettingValue::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    // Ints are accepted as floats so `dead_zone = 0` works as expected
+    pub fn get_float(&self, key: &str) -> Option<f64> {
+        match self.get(key)? {
+            SettingValue::Float(value) => Some(*value),
+            SettingValue::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn get_text(&self, key: &str) -> Option<&str> {
+        match self.get(key)? {
+            SettingValue::Text(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SettingsErrors> {
+        self.save_layer(SettingsLayer::User, path)
+    }
+
+    // One `key = value` per line, sorted so the file diffs nicely. Text is
+    // quoted, floats always carry a dot so they load back as floats.
+    pub fn save_layer(
+        &self,
+        layer: SettingsLayer,
+        path: impl AsRef<Path>,
+    ) -> Result<(), SettingsErrors> {
+        let values = &self.layers[layer as usize];
+        let mut keys: Vec<&String> = values.keys().collect();
+        keys.sort();
+
+        let mut contents = String::new();
+        for key in keys {
+            let value = match &values[key] {
+                SettingValue::Bool(value) => value.to_string(),
+                SettingValue::Int(value) => value.to_string(),
+                SettingValue::Float(value) => format!("{:?}", value),
+                SettingValue::Text(value) => {
+                    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+                }
+            };
+            contents.push_str(&format!("{} = {}\n", key, value));
+        }
+        fs::write(path, contents).map_err(|err| SettingsErrors::Io(err.to_string()))
+    }
+
+    pub fn load(&mut self, path: impl AsRef<Path>) -> Result<(), SettingsErrors> {
+        self.load_layer(SettingsLayer::User, path)
+    }
+
+    // Loaded values override the ones already in the layer, broken lines are
+    // skipped
+    pub fn load_layer(
+        &mut self,
+        layer: SettingsLayer,
+        path: impl AsRef<Path>,
+    ) -> Result<(), SettingsErrors> {
+        let contents =
+            fs::read_to_string(path).map_err(|err| SettingsErrors::Io(err.to_string()))?;
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                warn!("skipping malformed settings line {}", line);
+                continue;
+            };
+            match parse_value(value.trim()) {
+                Some(value) => {
+                    self.layers[layer as usize].insert(key.trim().to_string(), value);
+                }
+                None => warn!("skipping malformed settings line {}", line),
+            }
+        }
+        Ok(())
+    }
+
+    // Loads `<dir>/<os>.cfg` (`windows.cfg`, `linux.cfg`, `macos.cfg`...)
+    // into the platform layer, a missing file just means no platform tuning
+    pub fn load_platform_profile(&mut self, dir: impl AsRef<Path>) -> Result<(), SettingsErrors> {
+        let path = dir.as_ref().join(format!("{}.cfg", std::env::consts::OS));
+        if !path.exists() {
+            info!("no platform settings at {}", path.display());
+            return Ok(());
+        }
+        self.load_layer(SettingsLayer::Platform, path)
+    }
+
+    // Keys set in any layer, each once
+    pub fn keys_with_prefix<'a>(&'a self, prefix: &'a str) -> impl Iterator<Item = &'a str> {
+        let mut seen = HashSet::new();
+        self.layers
+            .iter()
+            .flat_map(|values| values.keys())
+            .filter(move |key| key.starts_with(prefix) && seen.insert(key.as_str()))
+            .map(|key| key.as_str())
+    }
+}
+
+fn parse_value(value: &str) -> Option<SettingValue> {
+    if let Some(text) = value.strip_prefix('"') {
+        let text = text.strip_suffix('"')?;
+        let mut unescaped = String::with_capacity(text.len());
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            unescaped.push(if c == '\\' { chars.next()? } else { c });
+        }
+        return Some(SettingValue::Text(unescaped));
+    }
+    match value {
+        "true" => Some(SettingValue::Bool(true)),
+        "false" => Some(SettingValue::Bool(false)),
+        _ => value
+            .parse::<i64>()
+            .map(SettingValue::Int)
+            .or_else(|_| value.parse::<f64>().map(SettingValue::Float))
+            .ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let path =
+            std::env::temp_dir().join(format!("aloy-settings-test-{}.cfg", std::process::id()));
+        let mut settings = Settings::new();
+        settings.set("a.bool", SettingValue::Bool(true));
+        settings.set("a.int", SettingValue::Int(-3));
+        settings.set("a.float", SettingValue::Float(1.0));
+        settings.set("a.text", SettingValue::Text("say \"hi\" \\o/".to_string()));
+        settings.save(&path).unwrap();
+
+        let mut loaded = Settings::new();
+        loaded.load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        for key in ["a.bool", "a.int", "a.float", "a.text"] {
+            assert_eq!(loaded.get(key), settings.get(key), "{} should survive", key);
+        }
+    }
+
+    #[test]
+    fn test_layers() {
+        let mut settings = Settings::new();
+        settings.set_in(
+            SettingsLayer::Defaults,
+            "video.vsync",
+            SettingValue::Bool(true),
+        );
+        settings.set_in(
+            SettingsLayer::Platform,
+            "video.vsync",
+            SettingValue::Bool(false),
+        );
+        settings.set("video.vsync", SettingValue::Bool(true));
+        settings.set_in(
+            SettingsLayer::Session,
+            "video.vsync",
+            SettingValue::Bool(false),
+        );
+
+        assert_eq!(
+            settings.get_layer("video.vsync"),
+            Some(SettingsLayer::Session)
+        );
+        let layers: Vec<SettingsLayer> = settings
+            .resolve("video.vsync")
+            .iter()
+            .map(|(layer, _)| *layer)
+            .collect();
+        assert_eq!(
+            layers,
+            vec![
+                SettingsLayer::Session,
+                SettingsLayer::User,
+                SettingsLayer::Platform,
+                SettingsLayer::Defaults
+            ]
+        );
+
+        settings.reset_layer(SettingsLayer::Session);
+        assert_eq!(settings.get_bool("video.vsync"), Some(true));
+        // restore defaults, the platform profile shows through
+        settings.reset_layer(SettingsLayer::User);
+        assert_eq!(settings.get_bool("video.vsync"), Some(false));
+        assert_eq!(
+            settings.get_layer("video.vsync"),
+            Some(SettingsLayer::Platform)
+        );
+        assert_eq!(settings.keys_with_prefix("video.").count(), 1);
+    }
+}
@@ -0,0 +1,410 @@
+use std::{collections::HashMap, fmt::Debug, path::Path, time::Duration};
+
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    core::{
+        data_file::{load_data_file, DataFileErrors},
+        runner::subsystem::{Subsystem, SubsystemContext},
+    },
+    event_system::engine_events::world_events::{SpawnRequest, WorldEvents},
+    math::{random::Random, vector::Vec3},
+};
+
+// Random points tried per spawn before the attempt is given up
+const PLACEMENT_ATTEMPTS: u32 = 8;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SpawnErrors {
+    #[error("unable to load spawn table: {0}")]
+    File(#[from] DataFileErrors),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpawnEntry {
+    // what gameplay spawns, e.g. a prefab name
+    pub name: String,
+    // relative to the other entries of the table
+    pub weight: f32,
+    // seconds before this entry can be picked again
+    pub cooldown: f32,
+    // counted against the table's budget while the spawn is alive
+    pub cost: u32,
+}
+
+impl Default for SpawnEntry {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            weight: 1.0,
+            cooldown: 0.0,
+            cost: 1,
+        }
+    }
+}
+
+// A box spawns are placed in, in world units
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct SpawnArea {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpawnTable {
+    pub name: String,
+    pub entries: Vec<SpawnEntry>,
+    // seconds between spawns
+    pub interval: f32,
+    // the most cost alive at once
+    pub budget: u32,
+    pub areas: Vec<SpawnArea>,
+    // radius around a spawn point that has to be empty by the SpawnQuery,
+    // keeps spawns off the players and off each other
+    pub clearance: f32,
+}
+
+impl Default for SpawnTable {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            entries: Vec::new(),
+            interval: 1.0,
+            budget: 10,
+            areas: Vec::new(),
+            clearance: 0.0,
+        }
+    }
+}
+
+impl SpawnTable {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, SpawnErrors> {
+        Ok(load_data_file(path)?)
+    }
+}
+
+// Asks the game's spatial index how crowded a place is, e.g. how many
+// players and enemies are within the radius
+pub trait SpawnQuery {
+    fn count_within(&self, position: Vec3, radius: f32) -> usize;
+}
+
+impl<F: Fn(Vec3, f32) -> usize> SpawnQuery for F {
+    fn count_within(&self, position: Vec3, radius: f32) -> usize {
+        self(position, radius)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SpawnHandle(u64);
+
+struct TableState {
+    table: SpawnTable,
+    enabled: bool,
+    until_spawn: f32,
+    used_budget: u32,
+    // seconds left per entry
+    cooldowns: Vec<f32>,
+}
+
+// Decides what spawns where from data defined spawn tables. Every interval
+// a table picks an entry by weight among the ones off cooldown that fit its
+// remaining budget, finds a clear point in one of its areas and emits a
+// SpawnRequested event; gameplay spawns it and calls `release` with the
+// handle once it is gone so its cost returns to the budget.
+pub struct SpawnDirector {
+    tables: Vec<TableState>,
+    query: Option<Box<dyn SpawnQuery>>,
+    // the table name and cost of every live spawn
+    alive: HashMap<SpawnHandle, (String, u32)>,
+    random: Random,
+    next_handle: u64,
+}
+
+impl SpawnDirector {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            tables: Vec::new(),
+            query: None,
+            alive: HashMap::new(),
+            random: Random::new(seed),
+            next_handle: 0,
+        }
+    }
+
+    // Without a query every point is clear
+    pub fn set_query(&mut self, query: impl SpawnQuery + 'static) {
+        self.query = Some(Box::new(query));
+    }
+
+    // Replaces the table with the same name, its live spawns keep counting
+    pub fn add_table(&mut self, table: SpawnTable) {
+        let used_budget = self
+            .alive
+            .values()
+            .filter(|(name, _)| *name == table.name)
+            .map(|(_, cost)| cost)
+            .sum();
+        let state = TableState {
+            enabled: true,
+            until_spawn: table.interval,
+            used_budget,
+            cooldowns: vec![0.0; table.entries.len()],
+            table,
+        };
+        match self
+            .tables
+            .iter_mut()
+            .find(|existing| existing.table.name == state.table.name)
+        {
+            Some(existing) => *existing = state,
+            None => self.tables.push(state),
+        }
+    }
+
+    pub fn remove_table(&mut self, name: &str) -> bool {
+        let count = self.tables.len();
+        self.tables.retain(|state| state.table.name != name);
+        count != self.tables.len()
+    }
+
+    pub fn get_table(&self, name: &str) -> Option<&SpawnTable> {
+        self.find(name).map(|state| &state.table)
+    }
+
+    fn find(&self, name: &str) -> Option<&TableState> {
+        self.tables.iter().find(|state| state.table.name == name)
+    }
+
+    // A disabled table keeps its live spawns but spawns no more
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) {
+        if let Some(state) = self.tables.iter_mut().find(|s| s.table.name == name) {
+            state.enabled = enabled;
+        }
+    }
+
+    pub fn get_used_budget(&self, name: &str) -> Option<u32> {
+        self.find(name).map(|state| state.used_budget)
+    }
+
+    pub fn get_alive_count(&self) -> usize {
+        self.alive.len()
+    }
+
+    // The spawn is gone, its cost is free again
+    pub fn release(&mut self, handle: SpawnHandle) -> bool {
+        let Some((table, cost)) = self.alive.remove(&handle) else {
+            return false;
+        };
+        if let Some(state) = self.tables.iter_mut().find(|s| s.table.name == table) {
+            state.used_budget = state.used_budget.saturating_sub(cost);
+        }
+        true
+    }
+
+    fn pick_entry(&mut self, table: usize) -> Option<usize> {
+        let state = &self.tables[table];
+        let left = state.table.budget.saturating_sub(state.used_budget);
+        let candidates: Vec<usize> = (0..state.table.entries.len())
+            .filter(|&entry| {
+                let spawn = &state.table.entries[entry];
+                state.cooldowns[entry] <= 0.0 && spawn.cost <= left && spawn.weight > 0.0
+            })
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        let total: f32 = candidates
+            .iter()
+            .map(|&entry| state.table.entries[entry].weight)
+            .sum();
+        let mut roll = self.random.range_f32(0.0, total);
+        for &entry in candidates.iter() {
+            roll -= self.tables[table].table.entries[entry].weight;
+            if roll < 0.0 {
+                return Some(entry);
+            }
+        }
+        candidates.last().copied()
+    }
+
+    fn place(&mut self, table: usize) -> Option<Vec3> {
+        let areas = &self.tables[table].table.areas;
+        if areas.is_empty() {
+            return None;
+        }
+        let clearance = self.tables[table].table.clearance;
+        for _ in 0..PLACEMENT_ATTEMPTS {
+            let area = areas[self.random.range_u64(0, areas.len() as u64) as usize];
+            let position = Vec3::new(
+                self.random.range_f32(area.min.x, area.max.x),
+                self.random.range_f32(area.min.y, area.max.y),
+                self.random.range_f32(area.min.z, area.max.z),
+            );
+            let clear = match &self.query {
+                Some(query) => query.count_within(position, clearance) == 0,
+                None => true,
+            };
+            if clear {
+                return Some(position);
+            }
+        }
+        None
+    }
+
+    // Runs the tables for `delta` of game time, returns the spawns to make
+    pub fn advance(&mut self, delta: Duration) -> Vec<SpawnRequest> {
+        let seconds = delta.as_secs_f32();
+        let mut requests = Vec::new();
+        for table in 0..self.tables.len() {
+            let state = &mut self.tables[table];
+            for cooldown in state.cooldowns.iter_mut() {
+                *cooldown -= seconds;
+            }
+            if !state.enabled {
+                continue;
+            }
+            state.until_spawn -= seconds;
+            // at most one spawn per table and frame, a long frame does not
+            // flood the level
+            if state.until_spawn > 0.0 {
+                continue;
+            }
+            state.until_spawn = state.table.interval.max(0.0);
+
+            let Some(entry) = self.pick_entry(table) else {
+                continue;
+            };
+            let Some(position) = self.place(table) else {
+                warn!(
+                    "no clear spawn point for table {}",
+                    self.tables[table].table.name
+                );
+                continue;
+            };
+
+            let state = &mut self.tables[table];
+            let spawn = &state.table.entries[entry];
+            state.cooldowns[entry] = spawn.cooldown;
+            state.used_budget += spawn.cost;
+            let handle = SpawnHandle(self.next_handle);
+            self.next_handle += 1;
+            self.alive
+                .insert(handle, (state.table.name.clone(), spawn.cost));
+            requests.push(SpawnRequest {
+                handle,
+                table: state.table.name.clone(),
+                entry: spawn.name.clone(),
+                position,
+            });
+        }
+        requests
+    }
+}
+
+impl Default for SpawnDirector {
+    fn default() -> Self {
+        Self::new(Random::from_entropy().next_u64())
+    }
+}
+
+impl Debug for SpawnDirector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpawnDirector")
+            .field(
+                "tables",
+                &self
+                    .tables
+                    .iter()
+                    .map(|state| &state.table.name)
+                    .collect::<Vec<_>>(),
+            )
+            .field("alive", &self.alive.len())
+            .finish()
+    }
+}
+
+impl Subsystem for SpawnDirector {
+    fn get_name(&self) -> &str {
+        "SpawnDirector"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        for request in self.advance(ctx.time.get_delta()) {
+            if let Err(err) = ctx
+                .event_queue
+                .emit(Box::new(WorldEvents::SpawnRequested(request)))
+            {
+                error!("unable to emit spawn event {:?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spawns_within_budget_cooldowns_and_areas() {
+        let table: SpawnTable = toml::from_str(
+            r#"
+            name = "cave"
+            interval = 1.0
+            budget = 3
+            clearance = 2.0
+            areas = [{ min = { x = 0.0, y = 0.0, z = 0.0 }, max = { x = 10.0, y = 0.0, z = 10.0 } }]
+
+            [[entries]]
+            name = "bat"
+            weight = 3.0
+
+            [[entries]]
+            name = "troll"
+            cost = 2
+            cooldown = 5.0
+            "#,
+        )
+        .unwrap();
+        assert_eq!(table.entries[0].cost, 1);
+
+        let mut director = SpawnDirector::new(7);
+        // the player stands in the left half of the cave
+        director.set_query(|position: Vec3, radius: f32| usize::from(position.x < 5.0 + radius));
+        director.add_table(table);
+
+        let second = Duration::from_secs(1);
+        let mut requests = Vec::new();
+        for _ in 0..6 {
+            requests.extend(director.advance(second));
+        }
+        // the budget of 3 stops the spawning
+        let cost: u32 = requests
+            .iter()
+            .map(|request| if request.entry == "troll" { 2 } else { 1 })
+            .sum();
+        assert_eq!(cost, 3);
+        assert_eq!(director.get_used_budget("cave"), Some(3));
+        assert!(requests.iter().all(|request| request.position.x >= 7.0));
+        assert!(requests.iter().filter(|r| r.entry == "troll").count() <= 1);
+
+        // a released spawn frees its cost for the next interval
+        assert!(director.release(requests[0].handle));
+        assert!(!director.release(requests[0].handle));
+        let more = director.advance(second);
+        assert_eq!(more.len(), 1);
+        assert_eq!(more[0].entry, "bat");
+
+        director.set_enabled("cave", false);
+        director.release(more[0].handle);
+        assert!(director.advance(second * 3).is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+use std::time::{Duration, Instant};
+
+// Fast enough for any fast forward, small enough that scaling a frame's
+// delta never overflows a Duration
+pub const MAX_TIME_SCALE: f64 = 100.0;
+
+#[derive(Debug, Clone)]
+pub struct Time {
+    delta: Duration,
+    unscaled_delta: Duration,
+    elapsed: Duration,
+    unscaled_elapsed: Duration,
+    frame_count: u64,
+    // 0 = frozen, 0.5 = slow motion, 2 = fast forward
+    time_scale: f64,
+    // pausing keeps the time scale so resume restores it
+    paused: bool,
+    fixed_delta: Duration,
+    fixed_accumulator: Duration,
+    // caps how many fixed steps a single slow frame can queue up
+    max_fixed_steps: u32,
+    last_frame: Option<Instant>,
+}
+
+impl Time {
+    pub fn new() -> Self {
+        Self {
+            delta: Duration::ZERO,
+            unscaled_delta: Duration::ZERO,
+            elapsed: Duration::ZERO,
+            unscaled_elapsed: Duration::ZERO,
+            frame_count: 0,
+            time_scale: 1.0,
+            paused: false,
+            fixed_delta: Duration::from_secs(1) / 60,
+            fixed_accumulator: Duration::ZERO,
+            max_fixed_steps: 5,
+            last_frame: None,
+        }
+    }
+
+    // Called by the run loop at the start of every frame
+    pub fn update(&mut self) {
+        let now = Instant::now();
+        let unscaled_delta = match self.last_frame {
+            Some(last_frame) => now - last_frame,
+            None => Duration::ZERO,
+        };
+        self.last_frame = Some(now);
+        self.advance(unscaled_delta);
+    }
+
+    // Advances by a fixed amount instead of the wall clock
+    pub fn advance(&mut self, unscaled_delta: Duration) {
+        self.unscaled_delta = unscaled_delta;
+        self.delta = if self.paused {
+            Duration::ZERO
+        } else {
+            unscaled_delta.mul_f64(self.time_scale)
+        };
+        self.unscaled_elapsed += self.unscaled_delta;
+        self.elapsed += self.delta;
+        self.frame_count += 1;
+
+        self.fixed_accumulator =
+            (self.fixed_accumulator + self.delta).min(self.fixed_delta * self.max_fixed_steps);
+    }
+
+    // Returns true and consumes one fixed step if enough scaled time has
+    // accumulated, the run loop calls this until it returns false
+    pub fn consume_fixed_step(&mut self) -> bool {
+        if self.fixed_delta.is_zero() || self.fixed_accumulator < self.fixed_delta {
+            return false;
+        }
+        self.fixed_accumulator -= self.fixed_delta;
+        true
+    }
+
+    // Scaled time left until the next fixed step is due
+    pub fn get_time_until_fixed_step(&self) -> Duration {
+        self.fixed_delta.saturating_sub(self.fixed_accumulator)
+    }
+
+    pub fn get_fixed_delta(&self) -> Duration {
+        self.fixed_delta
+    }
+
+    pub fn set_fixed_delta(&mut self, fixed_delta: Duration) {
+        self.fixed_delta = fixed_delta;
+        self.fixed_accumulator = Duration::ZERO;
+    }
+
+    pub fn set_max_fixed_steps(&mut self, max_fixed_steps: u32) {
+        self.max_fixed_steps = max_fixed_steps.max(1);
+    }
+
+    // How far we are into the next fixed step, for interpolating rendering
+    pub fn fixed_overstep(&self) -> f32 {
+        if self.fixed_delta.is_zero() {
+            return 0.0;
+        }
+        self.fixed_accumulator.as_secs_f32() / self.fixed_delta.as_secs_f32()
+    }
+
+    pub fn get_delta(&self) -> Duration {
+        self.delta
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta.as_secs_f32()
+    }
+
+    pub fn get_unscaled_delta(&self) -> Duration {
+        self.unscaled_delta
+    }
+
+    pub fn unscaled_delta_seconds(&self) -> f32 {
+        self.unscaled_delta.as_secs_f32()
+    }
+
+    pub fn get_elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn get_unscaled_elapsed(&self) -> Duration {
+        self.unscaled_elapsed
+    }
+
+    pub fn get_frame_count(&self) -> u64 {
+        self.frame_count
+    }
+
+    pub fn get_time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    // Clamped to 0..=MAX_TIME_SCALE, time never runs backwards. NaN keeps
+    // the current scale.
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        if !time_scale.is_nan() {
+            self.time_scale = time_scale.clamp(0.0, MAX_TIME_SCALE);
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    // A time scale of 0 freezes scaled time too, but only an explicit pause
+    // counts as paused
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance_accumulates() {
+        let mut time = Time::new();
+        time.advance(Duration::from_millis(16));
+        time.advance(Duration::from_millis(16));
+
+        assert_eq!(time.get_frame_count(), 2);
+        assert_eq!(time.get_delta(), Duration::from_millis(16));
+        assert_eq!(time.get_elapsed(), Duration::from_millis(32));
+    }
+
+    #[test]
+    fn test_time_scale() {
+        let mut time = Time::new();
+        time.set_time_scale(0.5);
+        time.advance(Duration::from_millis(20));
+
+        assert_eq!(time.get_delta(), Duration::from_millis(10));
+        assert_eq!(time.get_unscaled_delta(), Duration::from_millis(20));
+        assert_eq!(time.get_elapsed(), Duration::from_millis(10));
+        assert_eq!(time.get_unscaled_elapsed(), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_paused_time_still_counts_frames() {
+        let mut time = Time::new();
+        time.set_time_scale(-1.0);
+        assert_eq!(time.get_time_scale(), 0.0);
+        time.set_time_scale(f64::NAN);
+        assert_eq!(time.get_time_scale(), 0.0);
+        time.set_time_scale(f64::INFINITY);
+        assert_eq!(time.get_time_scale(), MAX_TIME_SCALE);
+        time.set_time_scale(0.0);
+
+        time.advance(Duration::from_millis(16));
+        assert_eq!(time.get_delta(), Duration::ZERO);
+        assert_eq!(time.get_frame_count(), 1);
+    }
+
+    #[test]
+    fn test_pause_keeps_time_scale() {
+        let mut time = Time::new();
+        time.set_time_scale(2.0);
+        time.set_paused(true);
+        time.advance(Duration::from_millis(10));
+
+        assert!(time.is_paused());
+        assert_eq!(time.get_delta(), Duration::ZERO);
+        assert_eq!(time.get_unscaled_elapsed(), Duration::from_millis(10));
+
+        time.set_paused(false);
+        time.advance(Duration::from_millis(10));
+        assert_eq!(time.get_delta(), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_fixed_steps() {
+        let mut time = Time::new();
+        time.set_fixed_delta(Duration::from_millis(10));
+
+        time.advance(Duration::from_millis(25));
+        assert!(time.consume_fixed_step());
+        assert!(time.consume_fixed_step());
+        assert!(!time.consume_fixed_step());
+        assert!((time.fixed_overstep() - 0.5).abs() < 1e-4);
+        assert_eq!(time.get_time_until_fixed_step(), Duration::from_millis(5));
+
+        time.set_paused(true);
+        time.advance(Duration::from_millis(100));
+        assert!(!time.consume_fixed_step(), "No fixed steps while paused");
+    }
+
+    #[test]
+    fn test_fixed_steps_are_capped() {
+        let mut time = Time::new();
+        time.set_fixed_delta(Duration::from_millis(10));
+        time.set_max_fixed_steps(3);
+        time.advance(Duration::from_secs(1));
+
+        let mut steps = 0;
+        while time.consume_fixed_step() {
+            steps += 1;
+        }
+        assert_eq!(steps, 3);
+    }
+
+    #[test]
+    fn test_first_update_has_no_delta() {
+        let mut time = Time::new();
+        time.update();
+        assert_eq!(time.get_unscaled_delta(), Duration::ZERO);
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+
+use log::error;
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::engine_events::timer_events::{SliceCompleted, TimerEvents},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceStep {
+    Continue,
+    Done,
+}
+
+// Expensive work split into small steps, e.g. replanning one AI agent or
+// serializing one chunk of a save per step. The task keeps its own position
+// between steps so the work resumes where the last frame stopped.
+pub trait SlicedTask: Send {
+    fn step(&mut self) -> SliceStep;
+
+    // 0 to 1 through the current run, when the task can tell
+    fn get_progress(&self) -> Option<f32> {
+        None
+    }
+
+    // Called before a periodic task runs again
+    fn restart(&mut self) {}
+}
+
+// How much of a task runs per frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SliceBudget {
+    Steps(u32),
+    // checked between steps, a slow step still runs to its end
+    Time(Duration),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SliceHandle(u64);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceStatus {
+    pub name: String,
+    pub running: bool,
+    pub progress: Option<f32>,
+    // steps and frames of the current run, or of the last one when idle
+    pub steps: u64,
+    pub frames: u32,
+    pub completed_runs: u64,
+}
+
+struct Sliced {
+    handle: SliceHandle,
+    name: String,
+    budget: SliceBudget,
+    // periodic tasks start over this long after finishing
+    interval: Option<Duration>,
+    // time left until a periodic task starts over
+    waiting: Option<Duration>,
+    steps: u64,
+    frames: u32,
+    completed_runs: u64,
+    task: Box<dyn SlicedTask>,
+}
+
+// Runs time sliced tasks a bounded chunk per frame. A finished task emits a
+// SliceCompleted event, one shot tasks are removed then and periodic ones
+// wait for their interval (in scaled time) to run again.
+#[derive(Default)]
+pub struct TimeSlicer {
+    tasks: Vec<Sliced>,
+    next_handle: u64,
+}
+
+impl TimeSlicer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(
+        &mut self,
+        name: &str,
+        budget: SliceBudget,
+        interval: Option<Duration>,
+        task: Box<dyn SlicedTask>,
+    ) -> SliceHandle {
+        let handle = SliceHandle(self.next_handle);
+        self.next_handle += 1;
+        self.tasks.push(Sliced {
+            handle,
+            name: name.to_string(),
+            budget,
+            interval,
+            waiting: None,
+            steps: 0,
+            frames: 0,
+            completed_runs: 0,
+            task,
+        });
+        handle
+    }
+
+    // Runs once, starting next frame
+    pub fn add(
+        &mut self,
+        name: &str,
+        budget: SliceBudget,
+        task: impl SlicedTask + 'static,
+    ) -> SliceHandle {
+        self.push(name, budget, None, Box::new(task))
+    }
+
+    // Runs again `interval` after every completed run
+    pub fn add_periodic(
+        &mut self,
+        name: &str,
+        budget: SliceBudget,
+        interval: Duration,
+        task: impl SlicedTask + 'static,
+    ) -> SliceHandle {
+        self.push(name, budget, Some(interval), Box::new(task))
+    }
+
+    pub fn remove(&mut self, handle: SliceHandle) -> bool {
+        let count = self.tasks.len();
+        self.tasks.retain(|sliced| sliced.handle != handle);
+        self.tasks.len() != count
+    }
+
+    pub fn set_budget(&mut self, handle: SliceHandle, budget: SliceBudget) {
+        if let Some(sliced) = self.tasks.iter_mut().find(|s| s.handle == handle) {
+            sliced.budget = budget;
+        }
+    }
+
+    pub fn get_status(&self, handle: SliceHandle) -> Option<SliceStatus> {
+        let sliced = self.tasks.iter().find(|s| s.handle == handle)?;
+        Some(SliceStatus {
+            name: sliced.name.clone(),
+            running: sliced.waiting.is_none(),
+            progress: sliced.task.get_progress(),
+            steps: sliced.steps,
+            frames: sliced.frames,
+            completed_runs: sliced.completed_runs,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    // One frame of every task, returns the runs that finished
+    pub fn advance(&mut self, delta: Duration) -> Vec<SliceCompleted> {
+        let mut completed = Vec::new();
+        for sliced in self.tasks.iter_mut() {
+            if let Some(waiting) = sliced.waiting {
+                if waiting > delta {
+                    sliced.waiting = Some(waiting - delta);
+                    continue;
+                }
+                sliced.waiting = None;
+                sliced.steps = 0;
+                sliced.frames = 0;
+                sliced.task.restart();
+            }
+
+            sliced.frames += 1;
+            let started = Instant::now();
+            let mut steps = 0;
+            let done = loop {
+                let within = match sliced.budget {
+                    SliceBudget::Steps(max) => steps < max.max(1),
+                    // at least one step so a task always moves
+                    SliceBudget::Time(budget) => steps == 0 || started.elapsed() < budget,
+                };
+                if !within {
+                    break false;
+                }
+                steps += 1;
+                if sliced.task.step() == SliceStep::Done {
+                    break true;
+                }
+            };
+            sliced.steps += steps as u64;
+
+            if done {
+                sliced.completed_runs += 1;
+                completed.push(SliceCompleted {
+                    handle: sliced.handle,
+                    name: sliced.name.clone(),
+                    frames: sliced.frames,
+                });
+                sliced.waiting = Some(sliced.interval.unwrap_or(Duration::MAX));
+            }
+        }
+        self.tasks
+            .retain(|sliced| sliced.interval.is_some() || sliced.waiting.is_none());
+        completed
+    }
+}
+
+impl Debug for TimeSlicer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TimeSlicer")
+            .field(
+                "tasks",
+                &self.tasks.iter().map(|s| &s.name).collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Subsystem for TimeSlicer {
+    fn get_name(&self) -> &str {
+        "TimeSlicer"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        for completed in self.advance(ctx.time.get_delta()) {
+            if let Err(err) = ctx
+                .event_queue
+                .emit(Box::new(TimerEvents::SliceCompleted(completed)))
+            {
+                error!("unable to emit slice event {:?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Countdown {
+        left: u32,
+        total: u32,
+    }
+
+    impl SlicedTask for Countdown {
+        fn step(&mut self) -> SliceStep {
+            self.left -= 1;
+            match self.left {
+                0 => SliceStep::Done,
+                _ => SliceStep::Continue,
+            }
+        }
+
+        fn get_progress(&self) -> Option<f32> {
+            Some((self.total - self.left) as f32 / self.total as f32)
+        }
+
+        fn restart(&mut self) {
+            self.left = self.total;
+        }
+    }
+
+    #[test]
+    fn test_spreads_work_over_frames() {
+        let mut slicer = TimeSlicer::new();
+        let frame = Duration::from_millis(16);
+        let once = slicer.add(
+            "save",
+            SliceBudget::Steps(4),
+            Countdown {
+                left: 10,
+                total: 10,
+            },
+        );
+        let periodic = slicer.add_periodic(
+            "replan",
+            SliceBudget::Steps(5),
+            Duration::from_millis(20),
+            Countdown { left: 5, total: 5 },
+        );
+
+        let completed = slicer.advance(frame);
+        assert_eq!(completed.len(), 1);
+        assert_eq!(completed[0].handle, periodic);
+        let status = slicer.get_status(once).unwrap();
+        assert_eq!((status.steps, status.frames), (4, 1));
+        assert_eq!(status.progress, Some(0.4));
+        assert!(!slicer.get_status(periodic).unwrap().running);
+
+        slicer.advance(frame);
+        let completed = slicer.advance(frame);
+        assert_eq!(completed[0].name, "save");
+        assert_eq!(completed[0].frames, 3);
+        assert_eq!(slicer.get_status(once), None);
+
+        // the periodic task waited 32ms of its 20ms and ran again
+        assert_eq!(completed.len(), 2);
+        assert_eq!(slicer.get_status(periodic).unwrap().completed_runs, 2);
+        assert_eq!(slicer.len(), 1);
+    }
+}
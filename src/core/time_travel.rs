@@ -0,0 +1,217 @@
+use std::{collections::VecDeque, fmt::Debug, time::Duration};
+
+use thiserror::Error;
+
+use super::bug_report::{EventJournal, JournalEntry};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TimeTravelErrors {
+    #[error("frame {0} is not recorded")]
+    NotRecorded(u64),
+
+    #[error("nothing is recorded yet")]
+    Empty,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorldSnapshot<W> {
+    pub frame: u64,
+    // unscaled time since the engine started
+    pub time: Duration,
+    pub world: W,
+}
+
+// Keeps copies of the world of the last frames so a debugger can scrub back
+// through them. The game records its world at the end of every frame, the
+// engine's event journal tells what was dispatched in each of them.
+//
+// Scrubbing only moves a cursor, the live world is never touched. `branch`
+// hands out a copy to play around with in a sandbox, `rewind` throws the
+// newer frames away and returns the world to resume the game from.
+#[derive(Debug, Clone)]
+pub struct TimeTravel<W: Clone> {
+    snapshots: VecDeque<WorldSnapshot<W>>,
+    capacity: usize,
+    // frame being looked at, None while following the live game
+    cursor: Option<u64>,
+}
+
+impl<W: Clone> Default for TimeTravel<W> {
+    // 5 seconds at 60 fps
+    fn default() -> Self {
+        Self::new(300)
+    }
+}
+
+impl<W: Clone> TimeTravel<W> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+            cursor: None,
+        }
+    }
+
+    pub fn record(&mut self, frame: u64, time: Duration, world: &W) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(WorldSnapshot {
+            frame,
+            time,
+            world: world.clone(),
+        });
+        // the frame being looked at may have just been dropped
+        if let (Some(cursor), Some(oldest)) = (self.cursor, self.get_oldest_frame()) {
+            self.cursor = Some(cursor.max(oldest));
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+        self.cursor = None;
+    }
+
+    pub fn get_oldest_frame(&self) -> Option<u64> {
+        self.snapshots.front().map(|snapshot| snapshot.frame)
+    }
+
+    pub fn get_latest_frame(&self) -> Option<u64> {
+        self.snapshots.back().map(|snapshot| snapshot.frame)
+    }
+
+    pub fn is_scrubbing(&self) -> bool {
+        self.cursor.is_some()
+    }
+
+    fn position(&self, frame: u64) -> Option<usize> {
+        self.snapshots
+            .binary_search_by_key(&frame, |snapshot| snapshot.frame)
+            .ok()
+    }
+
+    // The snapshot under the cursor, the latest one while live
+    pub fn get_current(&self) -> Option<&WorldSnapshot<W>> {
+        match self.cursor {
+            Some(frame) => self.get_snapshot(frame),
+            None => self.snapshots.back(),
+        }
+    }
+
+    pub fn get_snapshot(&self, frame: u64) -> Option<&WorldSnapshot<W>> {
+        self.position(frame).map(|index| &self.snapshots[index])
+    }
+
+    pub fn seek(&mut self, frame: u64) -> Result<&WorldSnapshot<W>, TimeTravelErrors> {
+        let index = self
+            .position(frame)
+            .ok_or(TimeTravelErrors::NotRecorded(frame))?;
+        self.cursor = Some(frame);
+        Ok(&self.snapshots[index])
+    }
+
+    // Moves `frames` recorded frames back, stops at the oldest one
+    pub fn scrub_back(&mut self, frames: usize) -> Result<&WorldSnapshot<W>, TimeTravelErrors> {
+        let current = self.current_index().ok_or(TimeTravelErrors::Empty)?;
+        let frame = self.snapshots[current.saturating_sub(frames)].frame;
+        self.seek(frame)
+    }
+
+    // Moves `frames` recorded frames forward, stops at the latest one
+    pub fn scrub_forward(&mut self, frames: usize) -> Result<&WorldSnapshot<W>, TimeTravelErrors> {
+        let current = self.current_index().ok_or(TimeTravelErrors::Empty)?;
+        let index = (current + frames).min(self.snapshots.len() - 1);
+        let frame = self.snapshots[index].frame;
+        self.seek(frame)
+    }
+
+    fn current_index(&self) -> Option<usize> {
+        match self.cursor {
+            Some(frame) => self.position(frame),
+            None => self.snapshots.len().checked_sub(1),
+        }
+    }
+
+    // Back to following the live game
+    pub fn resume_live(&mut self) {
+        self.cursor = None;
+    }
+
+    // The events dispatched during the frame under the cursor
+    pub fn get_events(&self, journal: &EventJournal) -> Vec<JournalEntry> {
+        self.get_current()
+            .map(|snapshot| journal.get_frames(snapshot.frame..=snapshot.frame))
+            .unwrap_or_default()
+    }
+
+    // A copy of the world under the cursor to simulate on without touching
+    // the recording or the live game
+    pub fn branch(&self) -> Option<W> {
+        self.get_current().map(|snapshot| snapshot.world.clone())
+    }
+
+    // Drops the frames after the cursor and stops scrubbing. The game swaps
+    // its world for the returned one to resume from that point.
+    pub fn rewind(&mut self) -> Option<WorldSnapshot<W>> {
+        let index = self.current_index()?;
+        self.snapshots.truncate(index + 1);
+        self.cursor = None;
+        self.snapshots.back().cloned()
+    }
+}
+
+impl<W: Clone + Debug> TimeTravel<W> {
+    // The component values of the world under the cursor, for the overlay
+    pub fn inspect(&self) -> Option<String> {
+        self.get_current()
+            .map(|snapshot| format!("{:#?}", snapshot.world))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct World {
+        health: Vec<i32>,
+    }
+
+    #[test]
+    fn test_scrub_branch_and_rewind() {
+        let mut history = TimeTravel::new(4);
+        let mut world = World { health: vec![100] };
+        for frame in 0..6 {
+            world.health[0] -= 10;
+            history.record(frame, Duration::from_millis(frame * 16), &world);
+        }
+        assert_eq!(history.get_oldest_frame(), Some(2));
+
+        let snapshot = history.scrub_back(2).unwrap();
+        assert_eq!((snapshot.frame, snapshot.world.health[0]), (3, 60));
+        assert!(history.inspect().unwrap().contains("60"));
+        assert_eq!(history.scrub_back(10).unwrap().frame, 2);
+        assert_eq!(history.scrub_forward(1).unwrap().frame, 3);
+        assert_eq!(history.seek(0), Err(TimeTravelErrors::NotRecorded(0)));
+
+        let mut sandbox = history.branch().unwrap();
+        sandbox.health[0] = 0;
+        assert_eq!(history.get_current().unwrap().world.health[0], 60);
+
+        let resumed = history.rewind().unwrap();
+        assert_eq!(resumed.world, World { health: vec![60] });
+        assert!(!history.is_scrubbing());
+        assert_eq!(history.get_latest_frame(), Some(3));
+    }
+}
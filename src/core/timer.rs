@@ -0,0 +1,295 @@
+use std::{fmt::Debug, time::Duration};
+
+use log::error;
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::engine_events::timer_events::{TimerEvents, TimerFired},
+};
+
+pub type TimerCallback = Box<dyn FnMut() + Send>;
+
+// Shortest interval a timer repeats at
+pub const MIN_INTERVAL: Duration = Duration::from_millis(1);
+
+// How often one interval may catch up in a single advance, after a long
+// hitch the missed ones beyond this are dropped
+pub const MAX_FIRES_PER_ADVANCE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerHandle(u64);
+
+struct Timer {
+    handle: TimerHandle,
+    name: String,
+    remaining: Duration,
+    interval: Option<Duration>,
+    callback: TimerCallback,
+}
+
+// Timers run on scaled time, so they stop while the game is paused. Every
+// time a timer fires its callback is called and a TimerFired event is
+// emitted, use whichever style fits.
+#[derive(Default)]
+pub struct TimerManager {
+    timers: Vec<Timer>,
+    next_handle: u64,
+}
+
+impl TimerManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn add(
+        &mut self,
+        name: &str,
+        duration: Duration,
+        interval: Option<Duration>,
+        callback: TimerCallback,
+    ) -> TimerHandle {
+        let handle = TimerHandle(self.next_handle);
+        self.next_handle += 1;
+        self.timers.push(Timer {
+            handle,
+            name: name.to_string(),
+            remaining: duration,
+            interval,
+            callback,
+        });
+        handle
+    }
+
+    pub fn set_timeout(
+        &mut self,
+        name: &str,
+        duration: Duration,
+        callback: impl FnMut() + Send + 'static,
+    ) -> TimerHandle {
+        self.add(name, duration, None, Box::new(callback))
+    }
+
+    // Intervals shorter than MIN_INTERVAL are raised to it
+    pub fn set_interval(
+        &mut self,
+        name: &str,
+        interval: Duration,
+        callback: impl FnMut() + Send + 'static,
+    ) -> TimerHandle {
+        let interval = interval.max(MIN_INTERVAL);
+        self.add(name, interval, Some(interval), Box::new(callback))
+    }
+
+    pub fn cancel(&mut self, handle: TimerHandle) -> bool {
+        let count = self.timers.len();
+        self.timers.retain(|timer| timer.handle != handle);
+        count != self.timers.len()
+    }
+
+    // Returns how many timers were cancelled
+    pub fn cancel_by_name(&mut self, name: &str) -> usize {
+        let count = self.timers.len();
+        self.timers.retain(|timer| timer.name != name);
+        count - self.timers.len()
+    }
+
+    pub fn is_active(&self, handle: TimerHandle) -> bool {
+        self.timers.iter().any(|timer| timer.handle == handle)
+    }
+
+    pub fn get_remaining(&self, handle: TimerHandle) -> Option<Duration> {
+        self.timers
+            .iter()
+            .find(|timer| timer.handle == handle)
+            .map(|timer| timer.remaining)
+    }
+
+    pub fn len(&self) -> usize {
+        self.timers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timers.is_empty()
+    }
+
+    // Fires every timer that became due within `delta`, in the order they
+    // became due. Intervals that were due more than once fire more than
+    // once, up to MAX_FIRES_PER_ADVANCE times.
+    pub fn advance(&mut self, delta: Duration) -> Vec<TimerFired> {
+        let mut due: Vec<(Duration, usize)> = Vec::new();
+
+        for (index, timer) in self.timers.iter_mut().enumerate() {
+            let mut elapsed = Duration::ZERO;
+            let mut budget = delta;
+            let mut fires = 0;
+            while budget >= timer.remaining {
+                if fires == MAX_FIRES_PER_ADVANCE {
+                    // skips the missed ones, keeping the timer's phase
+                    let interval = timer.interval.unwrap_or(MIN_INTERVAL);
+                    let skipped = (budget - timer.remaining).as_nanos() % interval.as_nanos();
+                    budget = Duration::from_nanos(skipped as u64);
+                    timer.remaining = interval;
+                    break;
+                }
+                fires += 1;
+                elapsed += timer.remaining;
+                budget -= timer.remaining;
+                due.push((elapsed, index));
+                match timer.interval {
+                    Some(interval) => timer.remaining = interval,
+                    None => {
+                        timer.remaining = Duration::ZERO;
+                        break;
+                    }
+                }
+            }
+            if budget < timer.remaining {
+                timer.remaining -= budget;
+            }
+        }
+
+        due.sort_by_key(|(elapsed, _)| *elapsed);
+        let fired = due
+            .into_iter()
+            .map(|(_, index)| {
+                let timer = &mut self.timers[index];
+                (timer.callback)();
+                TimerFired {
+                    handle: timer.handle,
+                    name: timer.name.clone(),
+                }
+            })
+            .collect();
+
+        self.timers
+            .retain(|timer| timer.interval.is_some() || !timer.remaining.is_zero());
+        fired
+    }
+}
+
+impl Debug for TimerManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TimerManager")
+            .field(
+                "timers",
+                &self
+                    .timers
+                    .iter()
+                    .map(|timer| &timer.name)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Subsystem for TimerManager {
+    fn get_name(&self) -> &str {
+        "TimerManager"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        for fired in self.advance(ctx.time.get_delta()) {
+            if let Err(err) = ctx
+                .event_queue
+                .emit(Box::new(TimerEvents::TimerFired(fired)))
+            {
+                error!("unable to emit timer event {:?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    fn counter() -> (Arc<AtomicU8>, impl FnMut() + Send + 'static) {
+        let count = Arc::new(AtomicU8::new(0));
+        let cb = {
+            let count = Arc::clone(&count);
+            move || {
+                count.fetch_add(1, Ordering::SeqCst);
+            }
+        };
+        (count, cb)
+    }
+
+    #[test]
+    fn test_timeout_fires_once() {
+        let mut timers = TimerManager::new();
+        let (count, cb) = counter();
+        let handle = timers.set_timeout("respawn", Duration::from_millis(100), cb);
+
+        assert!(timers.advance(Duration::from_millis(60)).is_empty());
+        let fired = timers.advance(Duration::from_millis(60));
+
+        assert_eq!(
+            fired,
+            vec![TimerFired {
+                handle,
+                name: "respawn".to_string()
+            }]
+        );
+        assert_eq!(count.load(Ordering::SeqCst), 1);
+        assert!(!timers.is_active(handle));
+        assert!(timers.advance(Duration::from_secs(1)).is_empty());
+    }
+
+    #[test]
+    fn test_interval_catches_up() {
+        let mut timers = TimerManager::new();
+        let (count, cb) = counter();
+        let handle = timers.set_interval("tick", Duration::from_millis(10), cb);
+
+        assert_eq!(timers.advance(Duration::from_millis(35)).len(), 3);
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+        assert_eq!(timers.get_remaining(handle), Some(Duration::from_millis(5)));
+        assert!(timers.is_active(handle));
+
+        // a zero interval can not flood a long frame
+        let (count, cb) = counter();
+        let handle = timers.set_interval("busy", Duration::ZERO, cb);
+        timers.advance(Duration::from_millis(100) + Duration::from_micros(300));
+        assert_eq!(count.load(Ordering::SeqCst) as usize, MAX_FIRES_PER_ADVANCE);
+        assert_eq!(
+            timers.get_remaining(handle),
+            Some(Duration::from_micros(700))
+        );
+    }
+
+    #[test]
+    fn test_cancel_by_handle_and_name() {
+        let mut timers = TimerManager::new();
+        let first = timers.set_timeout("a", Duration::from_millis(10), || {});
+        timers.set_interval("b", Duration::from_millis(10), || {});
+        timers.set_interval("b", Duration::from_millis(20), || {});
+
+        assert!(timers.cancel(first));
+        assert!(!timers.cancel(first));
+        assert_eq!(timers.cancel_by_name("b"), 2);
+        assert!(timers.is_empty());
+    }
+
+    #[test]
+    fn test_fired_in_due_order() {
+        let mut timers = TimerManager::new();
+        timers.set_timeout("late", Duration::from_millis(30), || {});
+        timers.set_timeout("early", Duration::from_millis(10), || {});
+
+        let names: Vec<String> = timers
+            .advance(Duration::from_millis(50))
+            .into_iter()
+            .map(|fired| fired.name)
+            .collect();
+        assert_eq!(names, vec!["early", "late"]);
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+use log::warn;
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ClipboardErrors {
+    #[error("clipboard is not available: {0}")]
+    Unavailable(String),
+
+    #[error("clipboard does not hold text")]
+    NotText,
+}
+
+pub trait Clipboard {
+    fn get_text(&mut self) -> Result<String, ClipboardErrors>;
+
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardErrors>;
+}
+
+// A clipboard private to the process, for headless runs and tests
+#[derive(Debug, Default)]
+pub struct MemoryClipboard {
+    text: String,
+}
+
+impl MemoryClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Clipboard for MemoryClipboard {
+    fn get_text(&mut self) -> Result<String, ClipboardErrors> {
+        Ok(self.text.clone())
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardErrors> {
+        self.text = text.to_string();
+        Ok(())
+    }
+}
+
+// The OS clipboard through the tools every desktop ships with (pbcopy,
+// wl-copy, xclip, PowerShell). Copies are also kept in memory so copy and
+// paste inside the game keep working where none of them is installed.
+#[derive(Debug, Default)]
+pub struct SystemClipboard {
+    fallback: MemoryClipboard,
+}
+
+impl SystemClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn get_commands() -> (Vec<&'static str>, Vec<&'static str>) {
+        if cfg!(target_os = "macos") {
+            (vec!["pbpaste"], vec!["pbcopy"])
+        } else if cfg!(target_os = "windows") {
+            (
+                vec!["powershell", "-NoProfile", "-Command", "Get-Clipboard -Raw"],
+                vec![
+                    "powershell",
+                    "-NoProfile",
+                    "-Command",
+                    "$input | Set-Clipboard",
+                ],
+            )
+        } else if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            (vec!["wl-paste", "--no-newline"], vec!["wl-copy"])
+        } else {
+            (
+                vec!["xclip", "-selection", "clipboard", "-out"],
+                vec!["xclip", "-selection", "clipboard", "-in"],
+            )
+        }
+    }
+
+    fn read() -> Result<String, ClipboardErrors> {
+        let (paste, _) = Self::get_commands();
+        let output = Command::new(paste[0])
+            .args(&paste[1..])
+            .stderr(Stdio::null())
+            .output()
+            .map_err(|err| ClipboardErrors::Unavailable(format!("{}: {}", paste[0], err)))?;
+        if !output.status.success() {
+            return Err(ClipboardErrors::Unavailable(format!(
+                "{} exited with {}",
+                paste[0], output.status
+            )));
+        }
+        String::from_utf8(output.stdout).map_err(|_| ClipboardErrors::NotText)
+    }
+
+    fn write(text: &str) -> Result<(), ClipboardErrors> {
+        let (_, copy) = Self::get_commands();
+        let unavailable =
+            |err: std::io::Error| ClipboardErrors::Unavailable(format!("{}: {}", copy[0], err));
+        let mut child = Command::new(copy[0])
+            .args(&copy[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(unavailable)?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes()).map_err(unavailable)?;
+        }
+        let status = child.wait().map_err(unavailable)?;
+        match status.success() {
+            true => Ok(()),
+            false => Err(ClipboardErrors::Unavailable(format!(
+                "{} exited with {}",
+                copy[0], status
+            ))),
+        }
+    }
+}
+
+impl Clipboard for SystemClipboard {
+    fn get_text(&mut self) -> Result<String, ClipboardErrors> {
+        match Self::read() {
+            Ok(text) => Ok(text),
+            Err(ClipboardErrors::NotText) => Err(ClipboardErrors::NotText),
+            Err(err) => {
+                warn!("{}, using the in game clipboard", err);
+                self.fallback.get_text()
+            }
+        }
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<(), ClipboardErrors> {
+        self.fallback.set_text(text)?;
+        Self::write(text)
+    }
+}
+
+// The OS clipboard when there is an OS window to go with it
+pub fn create_default_clipboard() -> Box<dyn Clipboard> {
+    #[cfg(feature = "winit")]
+    return Box::new(SystemClipboard::new());
+
+    #[cfg(not(feature = "winit"))]
+    return Box::new(MemoryClipboard::new());
+}
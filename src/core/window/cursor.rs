@@ -0,0 +1,102 @@
+use super::taskbar::IconErrors;
+
+// How the cursor is held by the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CursorGrab {
+    #[default]
+    None,
+    // kept inside the window
+    Confined,
+    // kept at its position, only relative motion comes through
+    Locked,
+}
+
+// System cursor shapes, named like the CSS cursors most platforms follow
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CursorIcon {
+    #[default]
+    Default,
+    Pointer,
+    Text,
+    Crosshair,
+    Move,
+    Grab,
+    Grabbing,
+    NotAllowed,
+    Wait,
+    Progress,
+    Help,
+    EwResize,
+    NsResize,
+    NeswResize,
+    NwseResize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorState {
+    pub visible: bool,
+    pub grab: CursorGrab,
+    pub icon: CursorIcon,
+    // a CursorImage shows instead of the icon
+    pub custom: bool,
+    // raw mouse deltas are sent as MouseEvents::RawMotion, the cursor is
+    // hidden and locked (or confined where locking is not supported)
+    pub relative: bool,
+}
+
+impl Default for CursorState {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            grab: CursorGrab::None,
+            icon: CursorIcon::Default,
+            custom: false,
+            relative: false,
+        }
+    }
+}
+
+// A cursor drawn by the OS from pixels, `hotspot` is the pixel that points,
+// from the top left. Platforms cap the size, 32x32 to 128x128 is safe.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CursorImage {
+    width: u32,
+    height: u32,
+    // 8 bit rgba, not premultiplied
+    rgba: Vec<u8>,
+    hotspot: (u32, u32),
+}
+
+impl CursorImage {
+    pub fn from_rgba(
+        width: u32,
+        height: u32,
+        rgba: Vec<u8>,
+        hotspot: (u32, u32),
+    ) -> Result<Self, IconErrors> {
+        if width == 0 || height == 0 || rgba.len() != width as usize * height as usize * 4 {
+            return Err(IconErrors::InvalidSize(width, height, rgba.len()));
+        }
+        if hotspot.0 >= width || hotspot.1 >= height {
+            return Err(IconErrors::InvalidHotspot(hotspot.0, hotspot.1));
+        }
+        Ok(Self {
+            width,
+            height,
+            rgba,
+            hotspot,
+        })
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn get_rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+
+    pub fn get_hotspot(&self) -> (u32, u32) {
+        self.hotspot
+    }
+}
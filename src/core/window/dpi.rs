@@ -0,0 +1,115 @@
+use crate::math::vector::Vec2;
+
+// Physical pixels are what the framebuffer has, logical pixels are physical
+// pixels divided by the scale factor of the monitor. UI layouts in logical
+// pixels look the same size on a 4K or retina display as on a 1080p one,
+// the renderer and picking against the framebuffer want physical pixels.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PhysicalSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PhysicalSize {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+
+    pub fn to_logical(self, scale_factor: f64) -> LogicalSize {
+        LogicalSize::new(
+            self.width as f64 / scale_factor,
+            self.height as f64 / scale_factor,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct LogicalSize {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl LogicalSize {
+    pub fn new(width: f64, height: f64) -> Self {
+        Self { width, height }
+    }
+
+    pub fn to_physical(self, scale_factor: f64) -> PhysicalSize {
+        PhysicalSize::new(
+            (self.width * scale_factor).round() as u32,
+            (self.height * scale_factor).round() as u32,
+        )
+    }
+}
+
+pub fn to_logical(physical: Vec2, scale_factor: f64) -> Vec2 {
+    physical / scale_factor as f32
+}
+
+pub fn to_physical(logical: Vec2, scale_factor: f64) -> Vec2 {
+    logical * scale_factor as f32
+}
+
+// Size of the drawable area together with the scale factor it was measured
+// at, sent with `WindowEvents::Resized`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowSize {
+    pub physical: PhysicalSize,
+    pub scale_factor: f64,
+}
+
+impl WindowSize {
+    pub fn new(width: u32, height: u32, scale_factor: f64) -> Self {
+        Self {
+            physical: PhysicalSize::new(width, height),
+            scale_factor,
+        }
+    }
+
+    pub fn get_logical(&self) -> LogicalSize {
+        self.physical.to_logical(self.scale_factor)
+    }
+}
+
+// Cursor position from the top left of the window, sent with
+// `MouseEvents::Moved`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CursorPosition {
+    pub physical: Vec2,
+    pub scale_factor: f64,
+}
+
+impl CursorPosition {
+    pub fn new(physical: Vec2, scale_factor: f64) -> Self {
+        Self {
+            physical,
+            scale_factor,
+        }
+    }
+
+    pub fn get_logical(&self) -> Vec2 {
+        to_logical(self.physical, self.scale_factor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trips_between_spaces() {
+        let size = WindowSize::new(3840, 2160, 2.0);
+        assert_eq!(size.get_logical(), LogicalSize::new(1920.0, 1080.0));
+        assert_eq!(size.get_logical().to_physical(2.0), size.physical);
+        // 125% scaling does not divide evenly, physical sizes round
+        assert_eq!(
+            LogicalSize::new(1001.0, 10.0).to_physical(1.25),
+            PhysicalSize::new(1251, 13)
+        );
+
+        let cursor = CursorPosition::new(Vec2::new(300.0, 150.0), 1.5);
+        assert_eq!(cursor.get_logical(), Vec2::new(200.0, 100.0));
+        assert_eq!(to_physical(cursor.get_logical(), 1.5), cursor.physical);
+    }
+}
@@ -0,0 +1,322 @@
+use std::sync::Arc;
+
+use log::error;
+use raw_window_handle::{DisplayHandle, HandleError, WindowHandle};
+
+use crate::{
+    event_system::{
+        engine_events::{
+            keyboard_events::{ImePreedit, KeyboardEvent},
+            mouse_events::MouseEvents,
+        },
+        event::WindowedEvent,
+        event_queue::EventQueue,
+    },
+    math::vector::Vec2,
+};
+
+use super::{
+    cursor::{CursorGrab, CursorIcon, CursorImage, CursorState},
+    dpi::LogicalSize,
+    taskbar::{AttentionRequest, TaskbarProgress, WindowIcon},
+    Monitor, NativeHandle, VideoMode, Window, WindowErrors, WindowId, WindowMode, WindowProps,
+};
+
+// A window without an OS window behind it, for platforms that draw
+// offscreen and for tests. `close` plays the user closing it. It has one
+// 1920x1080 monitor unless others are given.
+#[derive(Debug)]
+pub struct HeadlessWindow {
+    title: String,
+    size: (u32, u32),
+    windowed_size: (u32, u32),
+    scale_factor: f64,
+    monitors: Vec<Monitor>,
+    close_requested: bool,
+    frames: u64,
+    cursor: CursorState,
+    custom_cursor: Option<CursorImage>,
+    cursor_position: Vec2,
+    raw_motion: Vec<Vec2>,
+    paste_requested: bool,
+    text_input: bool,
+    ime_cursor_area: (Vec2, Vec2),
+    typed: Vec<KeyboardEvent>,
+    icon: Option<WindowIcon>,
+    attention: Option<AttentionRequest>,
+    progress: TaskbarProgress,
+}
+
+impl HeadlessWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_scale_factor(mut self, scale_factor: f64) -> Self {
+        self.scale_factor = scale_factor;
+        self
+    }
+
+    pub fn with_monitors(mut self, monitors: Vec<Monitor>) -> Self {
+        self.monitors = monitors;
+        self
+    }
+
+    pub fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    // Frames presented so far
+    pub fn get_frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn close(&mut self) {
+        self.close_requested = true;
+    }
+
+    // Plays the user pressing the paste shortcut
+    pub fn paste(&mut self) {
+        self.paste_requested = true;
+    }
+
+    pub fn is_text_input(&self) -> bool {
+        self.text_input
+    }
+
+    // Position and size of the last area set
+    pub fn get_ime_cursor_area(&self) -> (Vec2, Vec2) {
+        self.ime_cursor_area
+    }
+
+    // Plays the user typing, sent on the next poll in text input mode
+    pub fn type_text(&mut self, text: &str) {
+        if self.text_input {
+            self.typed.push(KeyboardEvent::TextInput(text.to_string()));
+        }
+    }
+
+    // Plays the IME composing `text` and then committing `committed`
+    pub fn compose(&mut self, text: &str, committed: &str) {
+        if !self.text_input {
+            return;
+        }
+        let cursor = Some((text.len(), text.len()));
+        for preedit in [text, ""] {
+            self.typed.push(KeyboardEvent::ImePreedit(ImePreedit {
+                text: preedit.to_string(),
+                cursor: cursor.filter(|_| !preedit.is_empty()),
+            }));
+        }
+        self.typed
+            .push(KeyboardEvent::ImeCommit(committed.to_string()));
+        self.typed
+            .push(KeyboardEvent::TextInput(committed.to_string()));
+    }
+
+    pub fn get_custom_cursor(&self) -> Option<&CursorImage> {
+        self.custom_cursor.as_ref()
+    }
+
+    pub fn get_cursor_position(&self) -> Vec2 {
+        self.cursor_position
+    }
+
+    pub fn get_icon(&self) -> Option<&WindowIcon> {
+        self.icon.as_ref()
+    }
+
+    pub fn get_attention(&self) -> Option<AttentionRequest> {
+        self.attention
+    }
+
+    pub fn get_taskbar_progress(&self) -> TaskbarProgress {
+        self.progress
+    }
+
+    // Plays the mouse moving, sent on the next poll in relative mode
+    pub fn move_mouse(&mut self, delta: Vec2) {
+        if self.cursor.relative {
+            self.raw_motion.push(delta);
+        }
+    }
+}
+
+impl Default for HeadlessWindow {
+    fn default() -> Self {
+        let video_mode = VideoMode {
+            width: 1920,
+            height: 1080,
+            refresh_rate_millihertz: 60_000,
+            bit_depth: 32,
+        };
+        Self {
+            title: String::new(),
+            size: (0, 0),
+            windowed_size: (0, 0),
+            scale_factor: 1.0,
+            monitors: vec![Monitor {
+                name: "headless".to_string(),
+                position: (0, 0),
+                size: (video_mode.width, video_mode.height),
+                video_modes: vec![video_mode],
+            }],
+            close_requested: false,
+            frames: 0,
+            cursor: CursorState::default(),
+            custom_cursor: None,
+            cursor_position: Vec2::ZERO,
+            raw_motion: Vec::new(),
+            paste_requested: false,
+            text_input: false,
+            ime_cursor_area: (Vec2::ZERO, Vec2::ZERO),
+            typed: Vec::new(),
+            icon: None,
+            attention: None,
+            progress: TaskbarProgress::None,
+        }
+    }
+}
+
+impl Window for HeadlessWindow {
+    fn create(&mut self, props: &WindowProps) -> Result<(), WindowErrors> {
+        self.title = props.title.clone();
+        // props are logical like on a real display
+        let size = LogicalSize::new(props.width as f64, props.height as f64)
+            .to_physical(self.scale_factor);
+        self.size = (size.width, size.height);
+        self.windowed_size = self.size;
+        self.icon = props.icon.clone();
+        Ok(())
+    }
+
+    fn get_size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    fn get_scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+    }
+
+    fn set_icon(&mut self, icon: Option<&WindowIcon>) {
+        self.icon = icon.cloned();
+    }
+
+    fn request_attention(&mut self, request: Option<AttentionRequest>) {
+        self.attention = request;
+    }
+
+    fn set_taskbar_progress(&mut self, progress: TaskbarProgress) -> Result<(), WindowErrors> {
+        self.progress = progress;
+        Ok(())
+    }
+
+    fn get_monitors(&self) -> Vec<Monitor> {
+        self.monitors.clone()
+    }
+
+    fn set_mode(&mut self, mode: &WindowMode) -> Result<(u32, u32), WindowErrors> {
+        mode.validate(&self.monitors)?;
+        self.size = match mode {
+            WindowMode::Windowed => self.windowed_size,
+            WindowMode::BorderlessFullscreen { monitor } => {
+                let monitor = self
+                    .monitors
+                    .get(monitor.unwrap_or(0))
+                    .ok_or(WindowErrors::UnknownMonitor(0))?;
+                monitor.size
+            }
+            WindowMode::ExclusiveFullscreen { video_mode, .. } => {
+                (video_mode.width, video_mode.height)
+            }
+        };
+        Ok(self.size)
+    }
+
+    fn get_cursor_state(&self) -> CursorState {
+        self.cursor
+    }
+
+    fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor.visible = visible;
+    }
+
+    fn set_cursor_grab(&mut self, grab: CursorGrab) -> Result<(), WindowErrors> {
+        self.cursor.grab = grab;
+        Ok(())
+    }
+
+    fn set_cursor_icon(&mut self, icon: CursorIcon) {
+        self.cursor.icon = icon;
+        self.cursor.custom = false;
+        self.custom_cursor = None;
+    }
+
+    fn set_custom_cursor(&mut self, cursor: Option<&CursorImage>) -> Result<(), WindowErrors> {
+        self.cursor.custom = cursor.is_some();
+        self.custom_cursor = cursor.cloned();
+        Ok(())
+    }
+
+    fn set_cursor_position(&mut self, position: Vec2) -> Result<(), WindowErrors> {
+        self.cursor_position = position;
+        Ok(())
+    }
+
+    fn set_relative_mouse(&mut self, relative: bool) -> Result<(), WindowErrors> {
+        self.cursor.relative = relative;
+        self.raw_motion.clear();
+        Ok(())
+    }
+
+    fn set_text_input(&mut self, enabled: bool) {
+        self.text_input = enabled;
+        if !enabled {
+            self.typed.clear();
+        }
+    }
+
+    fn set_ime_cursor_area(&mut self, position: Vec2, size: Vec2) {
+        self.ime_cursor_area = (position, size);
+    }
+
+    fn take_paste_request(&mut self) -> bool {
+        std::mem::take(&mut self.paste_requested)
+    }
+
+    fn poll_events(&mut self, id: WindowId, queue: &Arc<EventQueue>) -> Result<bool, WindowErrors> {
+        for typed in self.typed.drain(..) {
+            let event = WindowedEvent::new(id, typed);
+            if let Err(err) = queue.emit(Box::new(event)) {
+                error!("unable to emit window event {:?}", err);
+            }
+        }
+        for delta in self.raw_motion.drain(..) {
+            let event = WindowedEvent::new(id, MouseEvents::RawMotion(delta));
+            if let Err(err) = queue.emit(Box::new(event)) {
+                error!("unable to emit window event {:?}", err);
+            }
+        }
+        Ok(self.close_requested)
+    }
+
+    fn swap_buffers(&mut self) {
+        self.frames += 1;
+    }
+
+    fn get_native_handle(&self) -> NativeHandle {
+        NativeHandle::None
+    }
+
+    fn get_window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
+        Err(HandleError::Unavailable)
+    }
+
+    fn get_display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
+        Err(HandleError::Unavailable)
+    }
+}
@@ -0,0 +1,914 @@
+pub mod clipboard;
+pub mod cursor;
+pub mod dpi;
+pub mod headless_window;
+pub mod taskbar;
+#[cfg(feature = "winit")]
+pub mod winit_window;
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use log::{error, info};
+pub use raw_window_handle;
+use raw_window_handle::{
+    DisplayHandle, HandleError, HasDisplayHandle, HasWindowHandle, WindowHandle,
+};
+use thiserror::Error;
+
+use crate::{
+    core::runner::{
+        exit_handlers::ExitReason,
+        subsystem::{Subsystem, SubsystemContext},
+    },
+    event_system::{
+        engine_events::{
+            application_events::ApplicationEvents,
+            keyboard_events::KeyboardEvent,
+            window_events::{ModeChanged, WindowEvents},
+        },
+        event::WindowedEvent,
+        event_queue::EventQueue,
+    },
+    math::vector::Vec2,
+};
+
+use self::{
+    clipboard::{create_default_clipboard, Clipboard, ClipboardErrors},
+    cursor::{CursorGrab, CursorIcon, CursorImage, CursorState},
+    dpi::WindowSize,
+    taskbar::{AttentionRequest, TaskbarProgress, WindowIcon},
+};
+use super::config::EngineConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WindowId(pub u32);
+
+// The window the application starts with
+pub const PRIMARY_WINDOW: WindowId = WindowId(0);
+
+#[derive(Debug, Error, PartialEq)]
+pub enum WindowErrors {
+    #[error("unable to create window: {0}")]
+    CreateFailed(String),
+
+    #[error("window backend failed: {0}")]
+    Backend(String),
+
+    #[error("there is no window {0:?}")]
+    UnknownWindow(WindowId),
+
+    #[error("there is no monitor {0}")]
+    UnknownMonitor(usize),
+
+    #[error("monitor {0} does not support {1:?}")]
+    UnsupportedVideoMode(usize, VideoMode),
+
+    #[error("{0} is not supported by this platform")]
+    Unsupported(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VideoMode {
+    pub width: u32,
+    pub height: u32,
+    pub refresh_rate_millihertz: u32,
+    pub bit_depth: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Monitor {
+    pub name: String,
+    pub position: (i32, i32),
+    pub size: (u32, u32),
+    pub video_modes: Vec<VideoMode>,
+}
+
+// Monitors are indices into `Window::get_monitors`, None is the monitor the
+// window is on
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum WindowMode {
+    #[default]
+    Windowed,
+    BorderlessFullscreen {
+        monitor: Option<usize>,
+    },
+    // Switches the monitor to the video mode, it has to be one of the modes
+    // the monitor lists
+    ExclusiveFullscreen {
+        monitor: usize,
+        video_mode: VideoMode,
+    },
+}
+
+impl WindowMode {
+    pub fn is_fullscreen(&self) -> bool {
+        *self != Self::Windowed
+    }
+
+    pub fn validate(&self, monitors: &[Monitor]) -> Result<(), WindowErrors> {
+        match self {
+            Self::Windowed | Self::BorderlessFullscreen { monitor: None } => Ok(()),
+            Self::BorderlessFullscreen {
+                monitor: Some(monitor),
+            } => monitors
+                .get(*monitor)
+                .map(|_| ())
+                .ok_or(WindowErrors::UnknownMonitor(*monitor)),
+            Self::ExclusiveFullscreen {
+                monitor,
+                video_mode,
+            } => {
+                let found = monitors
+                    .get(*monitor)
+                    .ok_or(WindowErrors::UnknownMonitor(*monitor))?;
+                if !found.video_modes.contains(video_mode) {
+                    return Err(WindowErrors::UnsupportedVideoMode(*monitor, *video_mode));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// What the renderer needs to create a surface on the window, depends on the
+// backend that made it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NativeHandle {
+    // not created yet or a backend without an OS window
+    None,
+    Winit(u64),
+}
+
+// A platform window. Backends (winit, sdl2, a console's platform layer...)
+// live behind their feature flag, the engine only talks to this trait.
+pub trait Window {
+    fn create(&mut self, props: &WindowProps) -> Result<(), WindowErrors>;
+
+    // Size of the drawable area in physical pixels
+    fn get_size(&self) -> (u32, u32);
+
+    // Physical pixels per logical pixel of the monitor the window is on
+    fn get_scale_factor(&self) -> f64;
+
+    fn set_title(&mut self, title: &str);
+
+    // None goes back to the platform's default icon
+    fn set_icon(&mut self, icon: Option<&WindowIcon>);
+
+    // None cancels a pending request
+    fn request_attention(&mut self, request: Option<AttentionRequest>);
+
+    fn set_taskbar_progress(&mut self, progress: TaskbarProgress) -> Result<(), WindowErrors>;
+
+    fn get_monitors(&self) -> Vec<Monitor>;
+
+    // Returns the size of the drawable area in the new mode
+    fn set_mode(&mut self, mode: &WindowMode) -> Result<(u32, u32), WindowErrors>;
+
+    fn get_cursor_state(&self) -> CursorState;
+
+    fn set_cursor_visible(&mut self, visible: bool);
+
+    fn set_cursor_grab(&mut self, grab: CursorGrab) -> Result<(), WindowErrors>;
+
+    // Replaces a custom cursor
+    fn set_cursor_icon(&mut self, icon: CursorIcon);
+
+    // None goes back to the icon
+    fn set_custom_cursor(&mut self, cursor: Option<&CursorImage>) -> Result<(), WindowErrors>;
+
+    // Warps the cursor, in physical pixels from the top left of the window
+    fn set_cursor_position(&mut self, position: Vec2) -> Result<(), WindowErrors>;
+
+    // Relative mouse mode for camera controls, see CursorState::relative
+    fn set_relative_mouse(&mut self, relative: bool) -> Result<(), WindowErrors>;
+
+    // Text input mode, for text fields: the IME may open and TextInput and
+    // IME events are sent. Off for gameplay so keys stay plain keys.
+    fn set_text_input(&mut self, enabled: bool);
+
+    // Where the IME places its candidate window, the area of the text cursor
+    // in physical pixels from the top left of the window
+    fn set_ime_cursor_area(&mut self, position: Vec2, size: Vec2);
+
+    // True once after the paste shortcut (Ctrl+V, Cmd+V, Shift+Insert) was
+    // pressed, the subsystem then sends the clipboard text
+    fn take_paste_request(&mut self) -> bool;
+
+    // Turns the pending OS events into engine events, sent as WindowedEvents
+    // of `id`. Returns true once the window was closed.
+    fn poll_events(&mut self, id: WindowId, queue: &Arc<EventQueue>) -> Result<bool, WindowErrors>;
+
+    // Presents the frame the renderer finished
+    fn swap_buffers(&mut self);
+
+    fn get_native_handle(&self) -> NativeHandle;
+
+    // For renderers outside the engine (wgpu, hand written Vulkan...), both
+    // are unavailable until the window is created and on backends without an
+    // OS window
+    fn get_window_handle(&self) -> Result<WindowHandle<'_>, HandleError>;
+
+    fn get_display_handle(&self) -> Result<DisplayHandle<'_>, HandleError>;
+}
+
+// Lets `WindowSubsystem::get_window` be handed straight to anything that
+// takes raw-window-handle types
+impl HasWindowHandle for dyn Window + '_ {
+    fn window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
+        self.get_window_handle()
+    }
+}
+
+impl HasDisplayHandle for dyn Window + '_ {
+    fn display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
+        self.get_display_handle()
+    }
+}
+
+// The window of the default backend, winit when it is enabled
+pub fn create_default_window() -> Box<dyn Window> {
+    #[cfg(feature = "winit")]
+    return Box::new(winit_window::WinitWindow::new());
+
+    #[cfg(not(feature = "winit"))]
+    return Box::new(headless_window::HeadlessWindow::new());
+}
+
+// What the window is created with, sizes are logical pixels so the window
+// has the same size on screen at any scale factor
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowProps {
+    pub title: String,
+    pub width: u32,
+    pub height: u32,
+    // read by the renderer when it creates its swapchain
+    pub vsync: bool,
+    pub resizable: bool,
+    pub mode: WindowMode,
+    pub icon: Option<WindowIcon>,
+}
+
+impl WindowProps {
+    pub fn new(title: &str, width: u32, height: u32) -> Self {
+        Self {
+            title: title.to_string(),
+            width,
+            height,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_config(config: &EngineConfig) -> Self {
+        Self {
+            title: config.window.title.clone(),
+            width: config.window.width,
+            height: config.window.height,
+            vsync: config.vsync,
+            resizable: true,
+            mode: Self::mode_from_config(config),
+            icon: None,
+        }
+    }
+}
+
+impl WindowProps {
+    fn mode_from_config(config: &EngineConfig) -> WindowMode {
+        match config.window.fullscreen {
+            true => WindowMode::BorderlessFullscreen { monitor: None },
+            false => WindowMode::Windowed,
+        }
+    }
+}
+
+impl Default for WindowProps {
+    fn default() -> Self {
+        Self::from_config(&EngineConfig::default())
+    }
+}
+
+struct OpenWindow {
+    props: WindowProps,
+    window: Box<dyn Window>,
+    created: bool,
+    close_requested: bool,
+    pending_mode: Option<WindowMode>,
+    text_input: bool,
+}
+
+// Drives the windows of the application from the main loop: created on the
+// next tick after they were opened, then polled and presented every frame.
+// Closing the primary window exits the application, other windows are just
+// dropped. Mode changes are applied on the next tick and announced with
+// `WindowEvents::ModeChanged`. The clipboard is shared by every window, a
+// paste shortcut in one of them sends `KeyboardEvent::ClipboardPasted`.
+pub struct WindowSubsystem {
+    windows: BTreeMap<WindowId, OpenWindow>,
+    next_id: u32,
+    clipboard: Box<dyn Clipboard>,
+}
+
+impl WindowSubsystem {
+    pub fn new(props: WindowProps, window: Box<dyn Window>) -> Self {
+        let mut subsystem = Self {
+            windows: BTreeMap::new(),
+            next_id: PRIMARY_WINDOW.0,
+            clipboard: create_default_clipboard(),
+        };
+        subsystem.open(props, window);
+        subsystem
+    }
+
+    pub fn with_clipboard(mut self, clipboard: impl Clipboard + 'static) -> Self {
+        self.clipboard = Box::new(clipboard);
+        self
+    }
+
+    pub fn get_clipboard_text(&mut self) -> Result<String, ClipboardErrors> {
+        self.clipboard.get_text()
+    }
+
+    pub fn set_clipboard_text(&mut self, text: &str) -> Result<(), ClipboardErrors> {
+        self.clipboard.set_text(text)
+    }
+
+    pub fn open(&mut self, props: WindowProps, window: Box<dyn Window>) -> WindowId {
+        let id = WindowId(self.next_id);
+        self.next_id += 1;
+        self.windows.insert(
+            id,
+            OpenWindow {
+                props,
+                window,
+                created: false,
+                close_requested: false,
+                pending_mode: None,
+                text_input: false,
+            },
+        );
+        id
+    }
+
+    // A window of the default backend, e.g. a detached editor panel
+    pub fn open_default(&mut self, props: WindowProps) -> WindowId {
+        self.open(props, create_default_window())
+    }
+
+    // The window goes away on the next tick like when the user closed it
+    pub fn close(&mut self, id: WindowId) -> Result<(), WindowErrors> {
+        self.get_open_mut(id)?.close_requested = true;
+        Ok(())
+    }
+
+    pub fn get_ids(&self) -> Vec<WindowId> {
+        self.windows.keys().copied().collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    fn get_open_mut(&mut self, id: WindowId) -> Result<&mut OpenWindow, WindowErrors> {
+        self.windows
+            .get_mut(&id)
+            .ok_or(WindowErrors::UnknownWindow(id))
+    }
+
+    pub fn get_window(&self, id: WindowId) -> Option<&dyn Window> {
+        self.windows.get(&id).map(|open| open.window.as_ref())
+    }
+
+    pub fn get_window_mut(&mut self, id: WindowId) -> Option<&mut (dyn Window + 'static)> {
+        self.windows.get_mut(&id).map(|open| open.window.as_mut())
+    }
+
+    pub fn get_props(&self, id: WindowId) -> Option<&WindowProps> {
+        self.windows.get(&id).map(|open| &open.props)
+    }
+
+    pub fn set_title(&mut self, id: WindowId, title: &str) -> Result<(), WindowErrors> {
+        let open = self.get_open_mut(id)?;
+        open.props.title = title.to_string();
+        open.window.set_title(title);
+        Ok(())
+    }
+
+    // Kept for windows that are not created yet
+    pub fn set_icon(&mut self, id: WindowId, icon: Option<WindowIcon>) -> Result<(), WindowErrors> {
+        let open = self.get_open_mut(id)?;
+        if open.created {
+            open.window.set_icon(icon.as_ref());
+        }
+        open.props.icon = icon;
+        Ok(())
+    }
+
+    pub fn request_attention(
+        &mut self,
+        id: WindowId,
+        request: Option<AttentionRequest>,
+    ) -> Result<(), WindowErrors> {
+        self.get_open_mut(id)?.window.request_attention(request);
+        Ok(())
+    }
+
+    // Err(Unsupported) where the platform has no progress on its taskbar,
+    // it is fine to ignore
+    pub fn set_taskbar_progress(
+        &mut self,
+        id: WindowId,
+        progress: TaskbarProgress,
+    ) -> Result<(), WindowErrors> {
+        self.get_open_mut(id)?.window.set_taskbar_progress(progress)
+    }
+
+    // Kept for windows that are not created yet
+    pub fn set_text_input(&mut self, id: WindowId, enabled: bool) -> Result<(), WindowErrors> {
+        let open = self.get_open_mut(id)?;
+        if open.created {
+            open.window.set_text_input(enabled);
+        }
+        open.text_input = enabled;
+        Ok(())
+    }
+
+    pub fn is_text_input(&self, id: WindowId) -> bool {
+        self.windows.get(&id).is_some_and(|open| open.text_input)
+    }
+
+    pub fn set_custom_cursor(
+        &mut self,
+        id: WindowId,
+        cursor: Option<&CursorImage>,
+    ) -> Result<(), WindowErrors> {
+        self.get_open_mut(id)?.window.set_custom_cursor(cursor)
+    }
+
+    pub fn set_ime_cursor_area(
+        &mut self,
+        id: WindowId,
+        position: Vec2,
+        size: Vec2,
+    ) -> Result<(), WindowErrors> {
+        self.get_open_mut(id)?
+            .window
+            .set_ime_cursor_area(position, size);
+        Ok(())
+    }
+
+    pub fn get_size(&self, id: WindowId) -> Option<WindowSize> {
+        let window = self.get_window(id)?;
+        let (width, height) = window.get_size();
+        Some(WindowSize::new(width, height, window.get_scale_factor()))
+    }
+
+    // Monitors are the same for every window, the primary one is asked
+    pub fn get_monitors(&self) -> Vec<Monitor> {
+        self.windows
+            .values()
+            .next()
+            .map(|open| open.window.get_monitors())
+            .unwrap_or_default()
+    }
+
+    pub fn get_mode(&self, id: WindowId) -> Option<&WindowMode> {
+        self.get_props(id).map(|props| &props.mode)
+    }
+
+    // Checked right away so a bad video mode is reported to the caller
+    pub fn request_mode(&mut self, id: WindowId, mode: WindowMode) -> Result<(), WindowErrors> {
+        let open = self.get_open_mut(id)?;
+        mode.validate(&open.window.get_monitors())?;
+        open.pending_mode = Some(mode);
+        Ok(())
+    }
+
+    fn emit(queue: &Arc<EventQueue>, id: WindowId, event: WindowEvents) {
+        if let Err(err) = queue.emit(Box::new(WindowedEvent::new(id, event))) {
+            error!("unable to emit window event {:?}", err);
+        }
+    }
+
+    fn create_window(id: WindowId, open: &mut OpenWindow) -> Result<(), WindowErrors> {
+        open.window.create(&open.props)?;
+        info!("created window {:?} {}", id, open.props.title);
+        open.created = true;
+        if open.text_input {
+            open.window.set_text_input(true);
+        }
+        if open.props.mode.is_fullscreen() {
+            open.pending_mode = Some(std::mem::take(&mut open.props.mode));
+        }
+        Ok(())
+    }
+
+    fn apply_mode(id: WindowId, open: &mut OpenWindow, mode: WindowMode, queue: &Arc<EventQueue>) {
+        let (width, height) = match open.window.set_mode(&mode) {
+            Ok(size) => size,
+            Err(err) => {
+                error!("unable to change the window mode {}", err);
+                return;
+            }
+        };
+        info!("window {:?} mode changed to {:?}", id, mode);
+        open.props.mode = mode.clone();
+        let changed = ModeChanged {
+            mode,
+            width,
+            height,
+        };
+        Self::emit(queue, id, WindowEvents::ModeChanged(changed));
+    }
+}
+
+impl Subsystem for WindowSubsystem {
+    fn get_name(&self) -> &str {
+        "Window"
+    }
+
+    // Only the primary window has to work for the application to start
+    fn init(&mut self, ctx: &mut SubsystemContext) -> Result<(), String> {
+        for (id, open) in self.windows.iter_mut() {
+            let created = Self::create_window(*id, open)
+                .and_then(|_| open.window.poll_events(*id, ctx.event_queue));
+            match created {
+                Ok(_) => {}
+                Err(err) if *id == PRIMARY_WINDOW => return Err(err.to_string()),
+                Err(err) => error!("unable to create window {:?}: {}", id, err),
+            }
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        let mut closed = Vec::new();
+        for (id, open) in self.windows.iter_mut() {
+            if !open.created {
+                if let Err(err) = Self::create_window(*id, open) {
+                    error!("unable to create window {:?}: {}", id, err);
+                    closed.push(*id);
+                    continue;
+                }
+            }
+            let close = open
+                .window
+                .poll_events(*id, ctx.event_queue)
+                .unwrap_or_else(|err| {
+                    error!("{}", err);
+                    true
+                });
+            if close || open.close_requested {
+                closed.push(*id);
+                continue;
+            }
+            if open.window.take_paste_request() {
+                match self.clipboard.get_text() {
+                    Ok(text) if !text.is_empty() => {
+                        let pasted = WindowedEvent::new(*id, KeyboardEvent::ClipboardPasted(text));
+                        if let Err(err) = ctx.event_queue.emit(Box::new(pasted)) {
+                            error!("unable to emit paste event {:?}", err);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(err) => error!("unable to paste: {}", err),
+                }
+            }
+            if let Some(mode) = open.pending_mode.take() {
+                Self::apply_mode(*id, open, mode, ctx.event_queue);
+            }
+            open.window.swap_buffers();
+        }
+
+        for id in closed {
+            if id == PRIMARY_WINDOW {
+                let exit = ApplicationEvents::Exit(ExitReason::NORMAL);
+                if let Err(err) = ctx.event_queue.emit(Box::new(exit)) {
+                    error!("unable to emit exit event {:?}", err);
+                }
+                continue;
+            }
+            info!("closed window {:?}", id);
+            self.windows.remove(&id);
+            Self::emit(ctx.event_queue, id, WindowEvents::Closed);
+        }
+    }
+
+    // Only the primary window follows the fullscreen switch of the config,
+    // an exclusive mode picked in game is kept while it still says fullscreen
+    fn on_config_reloaded(&mut self, config: &EngineConfig) {
+        let Some(open) = self.windows.get_mut(&PRIMARY_WINDOW) else {
+            return;
+        };
+        if config.window.fullscreen != open.props.mode.is_fullscreen() {
+            open.pending_mode = Some(WindowProps::mode_from_config(config));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::{runner::exit_handlers::ExitHandlers, time::Time};
+
+    use super::{clipboard::MemoryClipboard, headless_window::HeadlessWindow, *};
+
+    #[test]
+    fn test_subsystem_drives_window() {
+        let queue = Arc::new(EventQueue::new());
+        let time = Time::default();
+        let exit_handlers = ExitHandlers::default();
+        let mut ctx = SubsystemContext {
+            event_queue: &queue,
+            time: &time,
+            exit_handlers: &exit_handlers,
+        };
+
+        let mut subsystem = WindowSubsystem::new(
+            WindowProps::new("test", 320, 200),
+            Box::new(HeadlessWindow::new()),
+        );
+        subsystem.init(&mut ctx).unwrap();
+        let window = subsystem.get_window(PRIMARY_WINDOW).unwrap();
+        assert_eq!(window.get_size(), (320, 200));
+        let size = subsystem.get_size(PRIMARY_WINDOW).unwrap();
+        assert_eq!(size.get_logical().width, 320.0);
+        subsystem.set_title(PRIMARY_WINDOW, "renamed").unwrap();
+        assert_eq!(
+            subsystem.get_props(PRIMARY_WINDOW).unwrap().title,
+            "renamed"
+        );
+
+        subsystem.tick(&mut ctx);
+        assert!(queue.get_events().is_err());
+
+        let mut window = HeadlessWindow::new();
+        window.close();
+        let mut closed = WindowSubsystem::new(WindowProps::default(), Box::new(window));
+        closed.init(&mut ctx).unwrap();
+        closed.tick(&mut ctx);
+        let events = queue.get_events().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].get_name(), "Exit");
+    }
+
+    #[test]
+    fn test_mode_changes() {
+        let queue = Arc::new(EventQueue::new());
+        let time = Time::default();
+        let exit_handlers = ExitHandlers::default();
+        let mut ctx = SubsystemContext {
+            event_queue: &queue,
+            time: &time,
+            exit_handlers: &exit_handlers,
+        };
+        let mut subsystem = WindowSubsystem::new(
+            WindowProps::new("test", 800, 600),
+            Box::new(HeadlessWindow::new()),
+        );
+        subsystem.init(&mut ctx).unwrap();
+
+        let monitors = subsystem.get_monitors();
+        let video_mode = monitors[0].video_modes[0];
+        let unsupported = VideoMode {
+            width: 640,
+            ..video_mode
+        };
+        assert_eq!(
+            subsystem.request_mode(
+                PRIMARY_WINDOW,
+                WindowMode::ExclusiveFullscreen {
+                    monitor: 0,
+                    video_mode: unsupported,
+                }
+            ),
+            Err(WindowErrors::UnsupportedVideoMode(0, unsupported))
+        );
+        assert_eq!(
+            subsystem.request_mode(
+                PRIMARY_WINDOW,
+                WindowMode::BorderlessFullscreen { monitor: Some(3) }
+            ),
+            Err(WindowErrors::UnknownMonitor(3))
+        );
+
+        let exclusive = WindowMode::ExclusiveFullscreen {
+            monitor: 0,
+            video_mode,
+        };
+        subsystem
+            .request_mode(PRIMARY_WINDOW, exclusive.clone())
+            .unwrap();
+        subsystem.tick(&mut ctx);
+        assert_eq!(subsystem.get_mode(PRIMARY_WINDOW).unwrap(), &exclusive);
+        assert_eq!(
+            subsystem.get_window(PRIMARY_WINDOW).unwrap().get_size(),
+            (1920, 1080)
+        );
+        let data = queue.get_events().unwrap()[0].get_data().unwrap();
+        let changed = data.get_ref::<ModeChanged>().unwrap();
+        assert_eq!((changed.width, changed.height), (1920, 1080));
+
+        let mut config = EngineConfig::default();
+        config.window.fullscreen = false;
+        subsystem.on_config_reloaded(&config);
+        subsystem.tick(&mut ctx);
+        assert_eq!(
+            subsystem.get_mode(PRIMARY_WINDOW).unwrap(),
+            &WindowMode::Windowed
+        );
+        assert_eq!(
+            subsystem.get_window(PRIMARY_WINDOW).unwrap().get_size(),
+            (800, 600)
+        );
+    }
+
+    #[test]
+    fn test_secondary_windows() {
+        let queue = Arc::new(EventQueue::new());
+        let time = Time::default();
+        let exit_handlers = ExitHandlers::default();
+        let mut ctx = SubsystemContext {
+            event_queue: &queue,
+            time: &time,
+            exit_handlers: &exit_handlers,
+        };
+        let mut subsystem =
+            WindowSubsystem::new(WindowProps::default(), Box::new(HeadlessWindow::new()));
+        subsystem.init(&mut ctx).unwrap();
+
+        let panel = subsystem.open(
+            WindowProps::new("inspector", 300, 500),
+            Box::new(HeadlessWindow::new()),
+        );
+        assert_eq!(subsystem.get_ids(), vec![PRIMARY_WINDOW, panel]);
+        subsystem.tick(&mut ctx);
+        assert_eq!(subsystem.get_window(panel).unwrap().get_size(), (300, 500));
+
+        subsystem.close(panel).unwrap();
+        subsystem.tick(&mut ctx);
+        assert_eq!(subsystem.len(), 1);
+        let events = queue.get_events().unwrap();
+        assert_eq!(events[0].get_name(), "Closed");
+        assert_eq!(events[0].get_window(), Some(panel));
+        assert_eq!(
+            subsystem.close(panel),
+            Err(WindowErrors::UnknownWindow(panel))
+        );
+    }
+
+    #[test]
+    fn test_paste_shortcut_sends_clipboard_text() {
+        let queue = Arc::new(EventQueue::new());
+        let time = Time::default();
+        let exit_handlers = ExitHandlers::default();
+        let mut ctx = SubsystemContext {
+            event_queue: &queue,
+            time: &time,
+            exit_handlers: &exit_handlers,
+        };
+        let mut window = HeadlessWindow::new();
+        window.paste();
+        let mut subsystem = WindowSubsystem::new(WindowProps::default(), Box::new(window))
+            .with_clipboard(MemoryClipboard::new());
+        subsystem.init(&mut ctx).unwrap();
+        subsystem.set_clipboard_text("/spawn crate").unwrap();
+
+        subsystem.tick(&mut ctx);
+        let events = queue.get_events().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].get_name(), "ClipboardPasted");
+        let data = events[0].get_data().unwrap();
+        assert_eq!(
+            data.get_ref::<String>().map(String::as_str),
+            Some("/spawn crate")
+        );
+
+        subsystem.tick(&mut ctx);
+        assert!(queue.get_events().is_err());
+    }
+
+    #[test]
+    fn test_relative_mouse() {
+        let queue = Arc::new(EventQueue::new());
+        let mut window = HeadlessWindow::new();
+        window.create(&WindowProps::default()).unwrap();
+        window.set_cursor_icon(CursorIcon::Crosshair);
+        window.set_cursor_grab(CursorGrab::Confined).unwrap();
+
+        // only relative mode reports raw motion
+        window.move_mouse(Vec2::new(3.0, 0.0));
+        window.set_relative_mouse(true).unwrap();
+        window.move_mouse(Vec2::new(-4.0, 2.0));
+        window.poll_events(PRIMARY_WINDOW, &queue).unwrap();
+
+        let events = queue.get_events().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].get_name(), "MouseRawMotion");
+        let data = events[0].get_data().unwrap();
+        assert_eq!(data.get_ref::<Vec2>(), Some(&Vec2::new(-4.0, 2.0)));
+        let cursor = window.get_cursor_state();
+        assert!(cursor.relative && cursor.visible);
+        assert_eq!(
+            (cursor.grab, cursor.icon),
+            (CursorGrab::Confined, CursorIcon::Crosshair)
+        );
+
+        window.set_cursor_position(Vec2::new(10.0, 20.0)).unwrap();
+        assert_eq!(window.get_cursor_position(), Vec2::new(10.0, 20.0));
+    }
+
+    #[test]
+    fn test_text_input_and_ime() {
+        let queue = Arc::new(EventQueue::new());
+        let time = Time::default();
+        let exit_handlers = ExitHandlers::default();
+        let mut ctx = SubsystemContext {
+            event_queue: &queue,
+            time: &time,
+            exit_handlers: &exit_handlers,
+        };
+        let mut subsystem =
+            WindowSubsystem::new(WindowProps::default(), Box::new(HeadlessWindow::new()));
+        // enabled before the window exists, it is created with it
+        subsystem.set_text_input(PRIMARY_WINDOW, true).unwrap();
+        subsystem.init(&mut ctx).unwrap();
+        assert!(subsystem.is_text_input(PRIMARY_WINDOW));
+
+        let mut window = HeadlessWindow::new();
+        window.type_text("ignored");
+        window.set_text_input(true);
+        window.set_ime_cursor_area(Vec2::new(40.0, 60.0), Vec2::new(2.0, 16.0));
+        assert_eq!(
+            window.get_ime_cursor_area(),
+            (Vec2::new(40.0, 60.0), Vec2::new(2.0, 16.0))
+        );
+        window.type_text("hi");
+        window.compose("nihon", "日本");
+        window.poll_events(PRIMARY_WINDOW, &queue).unwrap();
+
+        let names: Vec<String> = queue
+            .get_events()
+            .unwrap()
+            .iter()
+            .map(|event| event.get_name())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "TextInput",
+                "ImePreedit",
+                "ImePreedit",
+                "ImeCommit",
+                "TextInput"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_raw_handles() {
+        fn has_handles(target: &(impl HasWindowHandle + HasDisplayHandle + ?Sized)) -> bool {
+            target.window_handle().is_ok() && target.display_handle().is_ok()
+        }
+
+        let subsystem =
+            WindowSubsystem::new(WindowProps::default(), Box::new(HeadlessWindow::new()));
+        let window = subsystem.get_window(PRIMARY_WINDOW).unwrap();
+        assert!(!has_handles(window));
+        assert!(matches!(
+            window.get_window_handle(),
+            Err(HandleError::Unavailable)
+        ));
+    }
+
+    #[test]
+    fn test_icon_and_taskbar() {
+        let icon = WindowIcon::from_rgba(1, 1, vec![255, 0, 0, 255]).unwrap();
+        let mut subsystem =
+            WindowSubsystem::new(WindowProps::default(), Box::new(HeadlessWindow::new()));
+        // set before the window exists, it is created with it
+        subsystem
+            .set_icon(PRIMARY_WINDOW, Some(icon.clone()))
+            .unwrap();
+        let props = subsystem.get_props(PRIMARY_WINDOW).unwrap().clone();
+        assert_eq!(props.icon.as_ref(), Some(&icon));
+        assert_eq!(
+            subsystem.set_icon(WindowId(7), None),
+            Err(WindowErrors::UnknownWindow(WindowId(7)))
+        );
+
+        let mut window = HeadlessWindow::new();
+        window.create(&props).unwrap();
+        assert_eq!(window.get_icon(), Some(&icon));
+        window.set_icon(None);
+        assert_eq!(window.get_icon(), None);
+        window.request_attention(Some(AttentionRequest::Critical));
+        assert_eq!(window.get_attention(), Some(AttentionRequest::Critical));
+        window
+            .set_taskbar_progress(TaskbarProgress::Paused(1.5))
+            .unwrap();
+        assert_eq!(window.get_taskbar_progress().get_value(), Some(1.0));
+    }
+}
@@ -0,0 +1,151 @@
+use std::{fs, path::Path};
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum IconErrors {
+    #[error("unable to read icon: {0}")]
+    Io(String),
+
+    #[error("unable to decode icon {0}: {1}")]
+    Decode(String, String),
+
+    #[error("{2} bytes of pixels do not make a {0}x{1} rgba icon")]
+    InvalidSize(u32, u32, usize),
+
+    #[error("hotspot {0},{1} is outside of the cursor")]
+    InvalidHotspot(u32, u32),
+}
+
+// The picture in the title bar, taskbar and app switcher. Small sizes like
+// 32x32 or 64x64 work everywhere.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowIcon {
+    width: u32,
+    height: u32,
+    // 8 bit rgba, not premultiplied
+    rgba: Vec<u8>,
+}
+
+impl WindowIcon {
+    pub fn from_rgba(width: u32, height: u32, rgba: Vec<u8>) -> Result<Self, IconErrors> {
+        if width == 0 || height == 0 || rgba.len() != width as usize * height as usize * 4 {
+            return Err(IconErrors::InvalidSize(width, height, rgba.len()));
+        }
+        Ok(Self {
+            width,
+            height,
+            rgba,
+        })
+    }
+
+    pub fn from_png(bytes: &[u8]) -> Result<Self, IconErrors> {
+        Self::decode_png(bytes).map_err(|err| IconErrors::Decode("png".to_string(), err))
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, IconErrors> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|err| IconErrors::Io(err.to_string()))?;
+        Self::decode_png(&bytes).map_err(|err| IconErrors::Decode(path.display().to_string(), err))
+    }
+
+    fn decode_png(bytes: &[u8]) -> Result<Self, String> {
+        let mut decoder = png::Decoder::new(bytes);
+        // palettes and 16 bit channels come out as plain 8 bit
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(|err| err.to_string())?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut pixels)
+            .map_err(|err| err.to_string())?;
+        pixels.truncate(info.buffer_size());
+
+        let rgba = match info.color_type {
+            png::ColorType::Rgba => pixels,
+            png::ColorType::Rgb => pixels
+                .chunks_exact(3)
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => pixels
+                .chunks_exact(2)
+                .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+                .collect(),
+            png::ColorType::Grayscale => pixels
+                .iter()
+                .flat_map(|gray| [*gray, *gray, *gray, 255])
+                .collect(),
+            other => return Err(format!("unsupported color type {:?}", other)),
+        };
+        Self::from_rgba(info.width, info.height, rgba).map_err(|err| err.to_string())
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn get_rgba(&self) -> &[u8] {
+        &self.rgba
+    }
+}
+
+// How hard the taskbar entry asks to be looked at, it stops once the window
+// gets focus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttentionRequest {
+    // flashes once or bounces the dock icon once
+    Informational,
+    // keeps flashing until the window is focused
+    Critical,
+}
+
+// The progress shown over the taskbar entry, e.g. while a level loads or
+// shaders compile. Values are between 0 and 1.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum TaskbarProgress {
+    #[default]
+    None,
+    Indeterminate,
+    Normal(f32),
+    Paused(f32),
+    Error(f32),
+}
+
+impl TaskbarProgress {
+    pub fn get_value(&self) -> Option<f32> {
+        match self {
+            Self::Normal(value) | Self::Paused(value) | Self::Error(value) => {
+                Some(value.clamp(0.0, 1.0))
+            }
+            Self::None | Self::Indeterminate => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_icon_from_png() {
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut bytes, 2, 1);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[255, 0, 0, 0, 0, 255]).unwrap();
+        }
+        let icon = WindowIcon::from_png(&bytes).unwrap();
+        assert_eq!(icon.get_size(), (2, 1));
+        assert_eq!(icon.get_rgba(), &[255, 0, 0, 255, 0, 0, 255, 255]);
+
+        assert_eq!(
+            WindowIcon::from_rgba(2, 2, vec![0; 4]),
+            Err(IconErrors::InvalidSize(2, 2, 4))
+        );
+        assert!(matches!(
+            WindowIcon::from_png(b"not a png"),
+            Err(IconErrors::Decode(..))
+        ));
+    }
+}
@@ -0,0 +1,879 @@
+use std::{cell::RefCell, collections::HashMap, mem, sync::Arc, time::Duration};
+
+use log::{error, info, warn};
+use raw_window_handle::{
+    DisplayHandle, HandleError, HasDisplayHandle, HasWindowHandle, WindowHandle,
+};
+use winit::{
+    application::ApplicationHandler,
+    dpi::{LogicalSize, PhysicalPosition, PhysicalSize},
+    event::{DeviceEvent, DeviceId, ElementState, Ime, KeyEvent, MouseScrollDelta, WindowEvent},
+    event_loop::{ActiveEventLoop, EventLoop},
+    keyboard::{Key, KeyCode as WinitKey, ModifiersState, NamedKey, PhysicalKey},
+    monitor::{MonitorHandle, VideoModeHandle},
+    platform::pump_events::{EventLoopExtPumpEvents, PumpStatus},
+    window::{
+        CursorGrabMode, CursorIcon as WinitCursorIcon, CustomCursor, Fullscreen, Icon,
+        UserAttentionType, WindowAttributes, WindowId,
+    },
+};
+
+use crate::{
+    core::key_code::KeyCode,
+    event_system::{
+        engine_events::{
+            keyboard_events::{ImePreedit, KeyboardEvent},
+            mouse_events::{MouseButton, MouseEvents},
+            window_events::WindowEvents,
+        },
+        event::WindowedEvent,
+        event_queue::EventQueue,
+    },
+    math::vector::Vec2,
+};
+
+use super::{
+    cursor::{CursorGrab, CursorIcon, CursorImage, CursorState},
+    dpi::{CursorPosition, WindowSize},
+    taskbar::{AttentionRequest, TaskbarProgress, WindowIcon},
+    Monitor, NativeHandle, VideoMode, Window, WindowErrors, WindowId as EngineWindowId, WindowMode,
+    WindowProps,
+};
+
+// Lines scrolled per pixel of touchpad scrolling
+const PIXELS_PER_LINE: f32 = 20.0;
+
+#[derive(Debug, PartialEq)]
+enum Translated {
+    Window(WindowEvents),
+    Keyboard(KeyboardEvent),
+    Mouse(MouseEvents),
+}
+
+impl Translated {
+    fn emit(self, window: EngineWindowId, queue: &EventQueue) {
+        let result = match self {
+            Self::Window(event) => queue.emit(Box::new(WindowedEvent::new(window, event))),
+            Self::Keyboard(event) => queue.emit(Box::new(WindowedEvent::new(window, event))),
+            Self::Mouse(event) => queue.emit(Box::new(WindowedEvent::new(window, event))),
+        };
+        if let Err(err) = result {
+            error!("unable to emit window event {:?}", err);
+        }
+    }
+}
+
+fn translate_button(button: winit::event::MouseButton) -> MouseButton {
+    match button {
+        winit::event::MouseButton::Left => MouseButton::Left,
+        winit::event::MouseButton::Right => MouseButton::Right,
+        winit::event::MouseButton::Middle => MouseButton::Middle,
+        winit::event::MouseButton::Back => MouseButton::Back,
+        winit::event::MouseButton::Forward => MouseButton::Forward,
+        winit::event::MouseButton::Other(button) => MouseButton::Other(button),
+    }
+}
+
+fn translate_cursor_icon(icon: CursorIcon) -> WinitCursorIcon {
+    match icon {
+        CursorIcon::Default => WinitCursorIcon::Default,
+        CursorIcon::Pointer => WinitCursorIcon::Pointer,
+        CursorIcon::Text => WinitCursorIcon::Text,
+        CursorIcon::Crosshair => WinitCursorIcon::Crosshair,
+        CursorIcon::Move => WinitCursorIcon::Move,
+        CursorIcon::Grab => WinitCursorIcon::Grab,
+        CursorIcon::Grabbing => WinitCursorIcon::Grabbing,
+        CursorIcon::NotAllowed => WinitCursorIcon::NotAllowed,
+        CursorIcon::Wait => WinitCursorIcon::Wait,
+        CursorIcon::Progress => WinitCursorIcon::Progress,
+        CursorIcon::Help => WinitCursorIcon::Help,
+        CursorIcon::EwResize => WinitCursorIcon::EwResize,
+        CursorIcon::NsResize => WinitCursorIcon::NsResize,
+        CursorIcon::NeswResize => WinitCursorIcon::NeswResize,
+        CursorIcon::NwseResize => WinitCursorIcon::NwseResize,
+    }
+}
+
+fn translate_key(key: WinitKey) -> Option<KeyCode> {
+    let key = match key {
+        WinitKey::Space => KeyCode::Space,
+        WinitKey::Quote => KeyCode::Apostrophe,
+        WinitKey::Comma => KeyCode::Comma,
+        WinitKey::Minus => KeyCode::Minus,
+        WinitKey::Period => KeyCode::Period,
+        WinitKey::Slash => KeyCode::Slash,
+        WinitKey::Digit0 => KeyCode::D0,
+        WinitKey::Digit1 => KeyCode::D1,
+        WinitKey::Digit2 => KeyCode::D2,
+        WinitKey::Digit3 => KeyCode::D3,
+        WinitKey::Digit4 => KeyCode::D4,
+        WinitKey::Digit5 => KeyCode::D5,
+        WinitKey::Digit6 => KeyCode::D6,
+        WinitKey::Digit7 => KeyCode::D7,
+        WinitKey::Digit8 => KeyCode::D8,
+        WinitKey::Digit9 => KeyCode::D9,
+        WinitKey::Semicolon => KeyCode::Semicolon,
+        WinitKey::Equal => KeyCode::Equal,
+        WinitKey::KeyA => KeyCode::A,
+        WinitKey::KeyB => KeyCode::B,
+        WinitKey::KeyC => KeyCode::C,
+        WinitKey::KeyD => KeyCode::D,
+        WinitKey::KeyE => KeyCode::E,
+        WinitKey::KeyF => KeyCode::F,
+        WinitKey::KeyG => KeyCode::G,
+        WinitKey::KeyH => KeyCode::H,
+        WinitKey::KeyI => KeyCode::I,
+        WinitKey::KeyJ => KeyCode::J,
+        WinitKey::KeyK => KeyCode::K,
+        WinitKey::KeyL => KeyCode::L,
+        WinitKey::KeyM => KeyCode::M,
+        WinitKey::KeyN => KeyCode::N,
+        WinitKey::KeyO => KeyCode::O,
+        WinitKey::KeyP => KeyCode::P,
+        WinitKey::KeyQ => KeyCode::Q,
+        WinitKey::KeyR => KeyCode::R,
+        WinitKey::KeyS => KeyCode::S,
+        WinitKey::KeyT => KeyCode::T,
+        WinitKey::KeyU => KeyCode::U,
+        WinitKey::KeyV => KeyCode::V,
+        WinitKey::KeyW => KeyCode::W,
+        WinitKey::KeyX => KeyCode::X,
+        WinitKey::KeyY => KeyCode::Y,
+        WinitKey::KeyZ => KeyCode::Z,
+        WinitKey::BracketLeft => KeyCode::LeftBracket,
+        WinitKey::Backslash => KeyCode::Backslash,
+        WinitKey::BracketRight => KeyCode::RightBracket,
+        WinitKey::Backquote => KeyCode::GraveAccent,
+        WinitKey::IntlBackslash => KeyCode::World1,
+        WinitKey::IntlRo => KeyCode::World2,
+        WinitKey::Escape => KeyCode::Escape,
+        WinitKey::Enter => KeyCode::Enter,
+        WinitKey::Tab => KeyCode::Tab,
+        WinitKey::Backspace => KeyCode::Backspace,
+        WinitKey::Insert => KeyCode::Insert,
+        WinitKey::Delete => KeyCode::Delete,
+        WinitKey::ArrowRight => KeyCode::Right,
+        WinitKey::ArrowLeft => KeyCode::Left,
+        WinitKey::ArrowDown => KeyCode::Down,
+        WinitKey::ArrowUp => KeyCode::Up,
+        WinitKey::PageUp => KeyCode::PageUp,
+        WinitKey::PageDown => KeyCode::PageDown,
+        WinitKey::Home => KeyCode::Home,
+        WinitKey::End => KeyCode::End,
+        WinitKey::CapsLock => KeyCode::CapsLock,
+        WinitKey::ScrollLock => KeyCode::ScrollLock,
+        WinitKey::NumLock => KeyCode::NumLock,
+        WinitKey::PrintScreen => KeyCode::PrintScreen,
+        WinitKey::Pause => KeyCode::Pause,
+        WinitKey::F1 => KeyCode::F1,
+        WinitKey::F2 => KeyCode::F2,
+        WinitKey::F3 => KeyCode::F3,
+        WinitKey::F4 => KeyCode::F4,
+        WinitKey::F5 => KeyCode::F5,
+        WinitKey::F6 => KeyCode::F6,
+        WinitKey::F7 => KeyCode::F7,
+        WinitKey::F8 => KeyCode::F8,
+        WinitKey::F9 => KeyCode::F9,
+        WinitKey::F10 => KeyCode::F10,
+        WinitKey::F11 => KeyCode::F11,
+        WinitKey::F12 => KeyCode::F12,
+        WinitKey::F13 => KeyCode::F13,
+        WinitKey::F14 => KeyCode::F14,
+        WinitKey::F15 => KeyCode::F15,
+        WinitKey::F16 => KeyCode::F16,
+        WinitKey::F17 => KeyCode::F17,
+        WinitKey::F18 => KeyCode::F18,
+        WinitKey::F19 => KeyCode::F19,
+        WinitKey::F20 => KeyCode::F20,
+        WinitKey::F21 => KeyCode::F21,
+        WinitKey::F22 => KeyCode::F22,
+        WinitKey::F23 => KeyCode::F23,
+        WinitKey::F24 => KeyCode::F24,
+        WinitKey::F25 => KeyCode::F25,
+        WinitKey::Numpad0 => KeyCode::KP0,
+        WinitKey::Numpad1 => KeyCode::KP1,
+        WinitKey::Numpad2 => KeyCode::KP2,
+        WinitKey::Numpad3 => KeyCode::KP3,
+        WinitKey::Numpad4 => KeyCode::KP4,
+        WinitKey::Numpad5 => KeyCode::KP5,
+        WinitKey::Numpad6 => KeyCode::KP6,
+        WinitKey::Numpad7 => KeyCode::KP7,
+        WinitKey::Numpad8 => KeyCode::KP8,
+        WinitKey::Numpad9 => KeyCode::KP9,
+        WinitKey::NumpadDecimal => KeyCode::KPDecimal,
+        WinitKey::NumpadDivide => KeyCode::KPDivide,
+        WinitKey::NumpadMultiply => KeyCode::KPMultiply,
+        WinitKey::NumpadSubtract => KeyCode::KPSubtract,
+        WinitKey::NumpadAdd => KeyCode::KPAdd,
+        WinitKey::NumpadEnter => KeyCode::KPEnter,
+        WinitKey::NumpadEqual => KeyCode::KPEqual,
+        WinitKey::ShiftLeft => KeyCode::LeftShift,
+        WinitKey::ControlLeft => KeyCode::LeftControl,
+        WinitKey::AltLeft => KeyCode::LeftAlt,
+        WinitKey::SuperLeft => KeyCode::LeftSuper,
+        WinitKey::ShiftRight => KeyCode::RightShift,
+        WinitKey::ControlRight => KeyCode::RightControl,
+        WinitKey::AltRight => KeyCode::RightAlt,
+        WinitKey::SuperRight => KeyCode::RightSuper,
+        WinitKey::ContextMenu => KeyCode::Menu,
+        _ => return None,
+    };
+    Some(key)
+}
+
+// Ctrl+V (Cmd+V on macOS) by the layout's V, or Shift+Insert
+fn is_paste(event: &KeyEvent, modifiers: ModifiersState) -> bool {
+    if event.state != ElementState::Pressed || event.repeat {
+        return false;
+    }
+    let command = match cfg!(target_os = "macos") {
+        true => modifiers.super_key(),
+        false => modifiers.control_key(),
+    };
+    match &event.logical_key {
+        Key::Character(character) => command && character.eq_ignore_ascii_case("v"),
+        Key::Named(NamedKey::Insert) => modifiers.shift_key(),
+        _ => false,
+    }
+}
+
+// Keys without an engine KeyCode still produce their text. TextInput and
+// IME events are only made in text input mode.
+fn translate(event: WindowEvent, scale_factor: f64, text_input: bool) -> Vec<Translated> {
+    match event {
+        WindowEvent::Resized(size) => vec![Translated::Window(WindowEvents::Resized(
+            WindowSize::new(size.width, size.height, scale_factor),
+        ))],
+        WindowEvent::ScaleFactorChanged { scale_factor, .. } => vec![Translated::Window(
+            WindowEvents::ScaleFactorChanged(scale_factor),
+        )],
+        WindowEvent::Moved(position) => vec![Translated::Window(WindowEvents::Moved(
+            position.x, position.y,
+        ))],
+        WindowEvent::CloseRequested => vec![Translated::Window(WindowEvents::CloseRequested)],
+        WindowEvent::Focused(focused) => {
+            vec![Translated::Window(WindowEvents::FocusChanged(focused))]
+        }
+        WindowEvent::KeyboardInput { event, .. } => {
+            let mut translated = Vec::new();
+            let key = match event.physical_key {
+                PhysicalKey::Code(code) => translate_key(code),
+                PhysicalKey::Unidentified(_) => None,
+            };
+            if let Some(key) = key {
+                translated.push(Translated::Keyboard(match event.state {
+                    ElementState::Pressed => KeyboardEvent::KeyPressed {
+                        key,
+                        repeat: event.repeat,
+                    },
+                    ElementState::Released => KeyboardEvent::KeyReleased(key),
+                }));
+            }
+            if event.state == ElementState::Pressed {
+                if let Some(text) = event.text.as_ref() {
+                    let printable: String = text
+                        .chars()
+                        .filter(|character| !character.is_control())
+                        .collect();
+                    if text_input && !printable.is_empty() {
+                        translated.push(Translated::Keyboard(KeyboardEvent::TextInput(printable)));
+                    }
+                    translated.extend(
+                        text.chars()
+                            .filter(|character| !character.is_control())
+                            .map(|character| {
+                                Translated::Keyboard(KeyboardEvent::CharTyped(character))
+                            }),
+                    );
+                }
+            }
+            translated
+        }
+        WindowEvent::Ime(Ime::Preedit(text, cursor)) if text_input => {
+            vec![Translated::Keyboard(KeyboardEvent::ImePreedit(
+                ImePreedit { text, cursor },
+            ))]
+        }
+        WindowEvent::Ime(Ime::Commit(text)) if text_input => vec![
+            Translated::Keyboard(KeyboardEvent::ImeCommit(text.clone())),
+            Translated::Keyboard(KeyboardEvent::TextInput(text)),
+        ],
+        WindowEvent::CursorMoved { position, .. } => {
+            let physical = Vec2::new(position.x as f32, position.y as f32);
+            vec![Translated::Mouse(MouseEvents::Moved(CursorPosition::new(
+                physical,
+                scale_factor,
+            )))]
+        }
+        WindowEvent::MouseInput { state, button, .. } => {
+            let button = translate_button(button);
+            vec![Translated::Mouse(match state {
+                ElementState::Pressed => MouseEvents::ButtonPressed(button),
+                ElementState::Released => MouseEvents::ButtonReleased(button),
+            })]
+        }
+        WindowEvent::MouseWheel { delta, .. } => {
+            let lines = match delta {
+                MouseScrollDelta::LineDelta(x, y) => Vec2::new(x, y),
+                MouseScrollDelta::PixelDelta(pixels) => {
+                    Vec2::new(pixels.x as f32, pixels.y as f32) / PIXELS_PER_LINE
+                }
+            };
+            vec![Translated::Mouse(MouseEvents::Scrolled(lines))]
+        }
+        _ => Vec::new(),
+    }
+}
+
+// winit allows a single event loop per process, so every window of the main
+// thread shares this one. Whichever window polls first pumps it and the
+// events are kept per window until that window polls.
+thread_local! {
+    static SHARED_LOOP: RefCell<Option<SharedLoop>> = const { RefCell::new(None) };
+}
+
+struct SharedLoop {
+    event_loop: EventLoop<()>,
+    state: LoopState,
+}
+
+#[derive(Default)]
+struct LoopState {
+    resumed: bool,
+    exited: bool,
+    next_request: u64,
+    // windows can only be made while the loop is active, during a pump
+    requested: Vec<(u64, WindowAttributes)>,
+    created: HashMap<u64, Result<winit::window::Window, String>>,
+    inboxes: HashMap<WindowId, Vec<WindowEvent>>,
+    // mouse motion of every device since a window in relative mode took it
+    raw_motion: (f64, f64),
+}
+
+impl LoopState {
+    fn create_requested(&mut self, event_loop: &ActiveEventLoop) {
+        if !self.resumed {
+            return;
+        }
+        for (request, attributes) in self.requested.drain(..) {
+            let window = event_loop
+                .create_window(attributes)
+                .map_err(|err| err.to_string());
+            self.created.insert(request, window);
+        }
+    }
+}
+
+impl ApplicationHandler for LoopState {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        self.resumed = true;
+        self.create_requested(event_loop);
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.create_requested(event_loop);
+    }
+
+    fn window_event(&mut self, _event_loop: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        self.inboxes.entry(id).or_default().push(event);
+    }
+
+    fn device_event(
+        &mut self,
+        _event_loop: &ActiveEventLoop,
+        _device_id: DeviceId,
+        event: DeviceEvent,
+    ) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            self.raw_motion.0 += delta.0;
+            self.raw_motion.1 += delta.1;
+        }
+    }
+}
+
+fn with_shared_loop<T>(
+    f: impl FnOnce(&mut SharedLoop) -> Result<T, WindowErrors>,
+) -> Result<T, WindowErrors> {
+    SHARED_LOOP.with(|shared| {
+        let mut shared = shared.borrow_mut();
+        if shared.is_none() {
+            let event_loop =
+                EventLoop::new().map_err(|err| WindowErrors::CreateFailed(err.to_string()))?;
+            shared.replace(SharedLoop {
+                event_loop,
+                state: LoopState::default(),
+            });
+        }
+        match shared.as_mut() {
+            Some(shared) => f(shared),
+            None => Err(WindowErrors::Backend("event loop is gone".to_string())),
+        }
+    })
+}
+
+// The winit backend. The event loop is pumped from `poll_events` once per
+// frame, so the engine keeps owning the main loop.
+#[derive(Default)]
+pub struct WinitWindow {
+    props: WindowProps,
+    request: Option<u64>,
+    window: Option<winit::window::Window>,
+    cursor: CursorState,
+    custom_cursor: Option<CustomCursor>,
+    modifiers: ModifiersState,
+    paste_requested: bool,
+    text_input: bool,
+    ime_cursor_area: Option<(Vec2, Vec2)>,
+}
+
+impl WinitWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_window(&self) -> Option<&winit::window::Window> {
+        self.window.as_ref()
+    }
+
+    // Returns true once the window is gone or asked to close
+    fn pump(&mut self, id: EngineWindowId, queue: &Arc<EventQueue>) -> Result<bool, WindowErrors> {
+        let (events, raw_motion, exited) = with_shared_loop(|shared| {
+            let status = shared
+                .event_loop
+                .pump_app_events(Some(Duration::ZERO), &mut shared.state);
+            if let PumpStatus::Exit(code) = status {
+                info!("window event loop exited with {}", code);
+                shared.state.exited = true;
+            }
+
+            if self.window.is_none() {
+                let created = self
+                    .request
+                    .and_then(|request| shared.state.created.remove(&request));
+                match created {
+                    Some(Ok(window)) => {
+                        self.request = None;
+                        self.window = Some(window);
+                        self.apply_settings();
+                    }
+                    Some(Err(err)) => return Err(WindowErrors::CreateFailed(err)),
+                    None => {}
+                }
+            }
+            let events = self
+                .window
+                .as_ref()
+                .and_then(|window| shared.state.inboxes.remove(&window.id()))
+                .unwrap_or_default();
+            let raw_motion = match self.cursor.relative {
+                true => mem::take(&mut shared.state.raw_motion),
+                false => (0.0, 0.0),
+            };
+            Ok((events, raw_motion, shared.state.exited))
+        })?;
+
+        let scale_factor = self.get_scale_factor();
+        let mut close_requested = exited;
+        for event in events {
+            close_requested |= event == WindowEvent::CloseRequested;
+            match &event {
+                WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+                WindowEvent::KeyboardInput { event, .. } => {
+                    self.paste_requested |= is_paste(event, self.modifiers);
+                }
+                _ => {}
+            }
+            for translated in translate(event, scale_factor, self.text_input) {
+                translated.emit(id, queue);
+            }
+        }
+        if raw_motion != (0.0, 0.0) {
+            let delta = Vec2::new(raw_motion.0 as f32, raw_motion.1 as f32);
+            Translated::Mouse(MouseEvents::RawMotion(delta)).emit(id, queue);
+        }
+        Ok(close_requested)
+    }
+
+    // Locked falls back to confined, Windows and X11 can not lock
+    fn grab(window: &winit::window::Window, grab: CursorGrab) -> Result<(), WindowErrors> {
+        let result = match grab {
+            CursorGrab::None => window.set_cursor_grab(CursorGrabMode::None),
+            CursorGrab::Confined => window.set_cursor_grab(CursorGrabMode::Confined),
+            CursorGrab::Locked => window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| window.set_cursor_grab(CursorGrabMode::Confined)),
+        };
+        result.map_err(|err| WindowErrors::Backend(err.to_string()))
+    }
+
+    // Settings made before the OS window existed
+    fn apply_settings(&self) {
+        let Some(window) = self.window.as_ref() else {
+            return;
+        };
+        match self.custom_cursor.as_ref() {
+            Some(cursor) => window.set_cursor(cursor.clone()),
+            None => window.set_cursor(translate_cursor_icon(self.cursor.icon)),
+        }
+        window.set_cursor_visible(self.cursor.visible && !self.cursor.relative);
+        let grab = match self.cursor.relative {
+            true => CursorGrab::Locked,
+            false => self.cursor.grab,
+        };
+        if let Err(err) = Self::grab(window, grab) {
+            warn!("unable to grab the cursor: {}", err);
+        }
+        window.set_ime_allowed(self.text_input);
+        if let Some((position, size)) = self.ime_cursor_area {
+            Self::set_ime_area(window, position, size);
+        }
+    }
+
+    fn set_ime_area(window: &winit::window::Window, position: Vec2, size: Vec2) {
+        window.set_ime_cursor_area(
+            PhysicalPosition::new(position.x, position.y),
+            PhysicalSize::new(size.x, size.y),
+        );
+    }
+}
+
+fn translate_icon(icon: &WindowIcon) -> Option<Icon> {
+    let (width, height) = icon.get_size();
+    Icon::from_rgba(icon.get_rgba().to_vec(), width, height)
+        .map_err(|err| warn!("unable to use the window icon: {}", err))
+        .ok()
+}
+
+fn translate_video_mode(video_mode: &VideoModeHandle) -> VideoMode {
+    VideoMode {
+        width: video_mode.size().width,
+        height: video_mode.size().height,
+        refresh_rate_millihertz: video_mode.refresh_rate_millihertz(),
+        bit_depth: video_mode.bit_depth(),
+    }
+}
+
+impl WinitWindow {
+    fn get_monitor_handles(&self) -> Vec<MonitorHandle> {
+        self.window
+            .as_ref()
+            .map(|window| window.available_monitors().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl Window for WinitWindow {
+    // The OS window is made by the next pump, once the loop is resumed
+    fn create(&mut self, props: &WindowProps) -> Result<(), WindowErrors> {
+        self.props = props.clone();
+        let attributes = winit::window::Window::default_attributes()
+            .with_title(props.title.clone())
+            .with_inner_size(LogicalSize::new(props.width, props.height))
+            .with_resizable(props.resizable)
+            .with_window_icon(props.icon.as_ref().and_then(translate_icon));
+        let request = with_shared_loop(|shared| {
+            let request = shared.state.next_request;
+            shared.state.next_request += 1;
+            shared.state.requested.push((request, attributes));
+            Ok(request)
+        })?;
+        self.request = Some(request);
+        Ok(())
+    }
+
+    fn get_size(&self) -> (u32, u32) {
+        match self.window.as_ref() {
+            Some(window) => {
+                let size = window.inner_size();
+                (size.width, size.height)
+            }
+            None => (self.props.width, self.props.height),
+        }
+    }
+
+    fn get_scale_factor(&self) -> f64 {
+        self.window
+            .as_ref()
+            .map_or(1.0, |window| window.scale_factor())
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.props.title = title.to_string();
+        if let Some(window) = self.window.as_ref() {
+            window.set_title(title);
+        }
+    }
+
+    fn set_icon(&mut self, icon: Option<&WindowIcon>) {
+        self.props.icon = icon.cloned();
+        if let Some(window) = self.window.as_ref() {
+            window.set_window_icon(icon.and_then(translate_icon));
+        }
+    }
+
+    fn request_attention(&mut self, request: Option<AttentionRequest>) {
+        let Some(window) = self.window.as_ref() else {
+            return;
+        };
+        window.request_user_attention(request.map(|request| match request {
+            AttentionRequest::Informational => UserAttentionType::Informational,
+            AttentionRequest::Critical => UserAttentionType::Critical,
+        }));
+    }
+
+    // winit has no taskbar progress, it needs ITaskbarList3 on Windows and
+    // the Unity launcher API on Linux desktops
+    fn set_taskbar_progress(&mut self, _progress: TaskbarProgress) -> Result<(), WindowErrors> {
+        Err(WindowErrors::Unsupported("taskbar progress".to_string()))
+    }
+
+    fn get_monitors(&self) -> Vec<Monitor> {
+        self.get_monitor_handles()
+            .iter()
+            .map(|monitor| Monitor {
+                name: monitor.name().unwrap_or_default(),
+                position: (monitor.position().x, monitor.position().y),
+                size: (monitor.size().width, monitor.size().height),
+                video_modes: monitor
+                    .video_modes()
+                    .map(|video_mode| translate_video_mode(&video_mode))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    fn set_mode(&mut self, mode: &WindowMode) -> Result<(u32, u32), WindowErrors> {
+        let Some(window) = self.window.as_ref() else {
+            return Err(WindowErrors::Backend(
+                "window is not created yet".to_string(),
+            ));
+        };
+        let monitors = self.get_monitor_handles();
+        let size = match mode {
+            WindowMode::Windowed => {
+                window.set_fullscreen(None);
+                let size = LogicalSize::new(self.props.width, self.props.height)
+                    .to_physical(window.scale_factor());
+                (size.width, size.height)
+            }
+            WindowMode::BorderlessFullscreen { monitor } => {
+                let handle = match monitor {
+                    Some(index) => Some(
+                        monitors
+                            .get(*index)
+                            .cloned()
+                            .ok_or(WindowErrors::UnknownMonitor(*index))?,
+                    ),
+                    None => window.current_monitor(),
+                };
+                let size = handle
+                    .as_ref()
+                    .map_or((0, 0), |handle| (handle.size().width, handle.size().height));
+                window.set_fullscreen(Some(Fullscreen::Borderless(handle)));
+                size
+            }
+            WindowMode::ExclusiveFullscreen {
+                monitor,
+                video_mode,
+            } => {
+                let handle = monitors
+                    .get(*monitor)
+                    .ok_or(WindowErrors::UnknownMonitor(*monitor))?
+                    .video_modes()
+                    .find(|handle| translate_video_mode(handle) == *video_mode)
+                    .ok_or(WindowErrors::UnsupportedVideoMode(*monitor, *video_mode))?;
+                window.set_fullscreen(Some(Fullscreen::Exclusive(handle)));
+                (video_mode.width, video_mode.height)
+            }
+        };
+        Ok(size)
+    }
+
+    fn get_cursor_state(&self) -> CursorState {
+        self.cursor
+    }
+
+    fn set_cursor_visible(&mut self, visible: bool) {
+        self.cursor.visible = visible;
+        if let Some(window) = self.window.as_ref() {
+            window.set_cursor_visible(visible && !self.cursor.relative);
+        }
+    }
+
+    fn set_cursor_grab(&mut self, grab: CursorGrab) -> Result<(), WindowErrors> {
+        if let Some(window) = self.window.as_ref().filter(|_| !self.cursor.relative) {
+            Self::grab(window, grab)?;
+        }
+        self.cursor.grab = grab;
+        Ok(())
+    }
+
+    fn set_cursor_icon(&mut self, icon: CursorIcon) {
+        self.cursor.icon = icon;
+        self.cursor.custom = false;
+        self.custom_cursor = None;
+        if let Some(window) = self.window.as_ref() {
+            window.set_cursor(translate_cursor_icon(icon));
+        }
+    }
+
+    fn set_custom_cursor(&mut self, cursor: Option<&CursorImage>) -> Result<(), WindowErrors> {
+        let Some(cursor) = cursor else {
+            self.set_cursor_icon(self.cursor.icon);
+            return Ok(());
+        };
+        let (width, height) = cursor.get_size();
+        let (hotspot_x, hotspot_y) = cursor.get_hotspot();
+        let too_large =
+            |_| WindowErrors::Backend(format!("{}x{} cursor is too large", width, height));
+        let source = CustomCursor::from_rgba(
+            cursor.get_rgba(),
+            u16::try_from(width).map_err(too_large)?,
+            u16::try_from(height).map_err(too_large)?,
+            hotspot_x as u16,
+            hotspot_y as u16,
+        )
+        .map_err(|err| WindowErrors::Backend(err.to_string()))?;
+        let custom = with_shared_loop(|shared| Ok(shared.event_loop.create_custom_cursor(source)))?;
+        if let Some(window) = self.window.as_ref() {
+            window.set_cursor(custom.clone());
+        }
+        self.cursor.custom = true;
+        self.custom_cursor = Some(custom);
+        Ok(())
+    }
+
+    fn set_cursor_position(&mut self, position: Vec2) -> Result<(), WindowErrors> {
+        let Some(window) = self.window.as_ref() else {
+            return Err(WindowErrors::Backend(
+                "window is not created yet".to_string(),
+            ));
+        };
+        window
+            .set_cursor_position(PhysicalPosition::new(position.x, position.y))
+            .map_err(|err| WindowErrors::Backend(err.to_string()))
+    }
+
+    fn set_relative_mouse(&mut self, relative: bool) -> Result<(), WindowErrors> {
+        if let Some(window) = self.window.as_ref() {
+            let grab = match relative {
+                true => CursorGrab::Locked,
+                false => self.cursor.grab,
+            };
+            Self::grab(window, grab)?;
+            window.set_cursor_visible(self.cursor.visible && !relative);
+        }
+        self.cursor.relative = relative;
+        // motion from before the switch is not camera input
+        with_shared_loop(|shared| {
+            shared.state.raw_motion = (0.0, 0.0);
+            Ok(())
+        })
+    }
+
+    fn set_text_input(&mut self, enabled: bool) {
+        self.text_input = enabled;
+        if let Some(window) = self.window.as_ref() {
+            window.set_ime_allowed(enabled);
+        }
+    }
+
+    fn set_ime_cursor_area(&mut self, position: Vec2, size: Vec2) {
+        self.ime_cursor_area = Some((position, size));
+        if let Some(window) = self.window.as_ref() {
+            Self::set_ime_area(window, position, size);
+        }
+    }
+
+    fn take_paste_request(&mut self) -> bool {
+        mem::take(&mut self.paste_requested)
+    }
+
+    fn poll_events(
+        &mut self,
+        id: EngineWindowId,
+        queue: &Arc<EventQueue>,
+    ) -> Result<bool, WindowErrors> {
+        self.pump(id, queue)
+    }
+
+    // The renderer's surface does the actual present, winit only wants to
+    // know it is about to happen
+    fn swap_buffers(&mut self) {
+        if let Some(window) = self.window.as_ref() {
+            window.pre_present_notify();
+        }
+    }
+
+    fn get_native_handle(&self) -> NativeHandle {
+        self.window.as_ref().map_or(NativeHandle::None, |window| {
+            NativeHandle::Winit(window.id().into())
+        })
+    }
+
+    fn get_window_handle(&self) -> Result<WindowHandle<'_>, HandleError> {
+        self.window
+            .as_ref()
+            .ok_or(HandleError::Unavailable)?
+            .window_handle()
+    }
+
+    fn get_display_handle(&self) -> Result<DisplayHandle<'_>, HandleError> {
+        self.window
+            .as_ref()
+            .ok_or(HandleError::Unavailable)?
+            .display_handle()
+    }
+}
+
+impl Drop for WinitWindow {
+    fn drop(&mut self) {
+        if let Some(window) = self.window.take() {
+            let id = window.id();
+            drop(window);
+            SHARED_LOOP.with(|shared| {
+                if let Some(shared) = shared.borrow_mut().as_mut() {
+                    shared.state.inboxes.remove(&id);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_window_and_keys() {
+        assert_eq!(
+            translate(
+                WindowEvent::Resized(PhysicalSize::new(800, 600)),
+                2.0,
+                false
+            ),
+            vec![Translated::Window(WindowEvents::Resized(WindowSize::new(
+                800, 600, 2.0
+            )))]
+        );
+        assert_eq!(
+            translate(WindowEvent::Focused(false), 1.0, false),
+            vec![Translated::Window(WindowEvents::FocusChanged(false))]
+        );
+        assert_eq!(
+            translate(WindowEvent::Ime(Ime::Commit("日本".to_string())), 1.0, true),
+            vec![
+                Translated::Keyboard(KeyboardEvent::ImeCommit("日本".to_string())),
+                Translated::Keyboard(KeyboardEvent::TextInput("日本".to_string())),
+            ]
+        );
+        assert!(translate(WindowEvent::Ime(Ime::Commit("x".to_string())), 1.0, false).is_empty());
+        assert_eq!(translate_key(WinitKey::KeyW), Some(KeyCode::W));
+        assert_eq!(translate_key(WinitKey::NumpadEnter), Some(KeyCode::KPEnter));
+        assert_eq!(translate_key(WinitKey::F35), None);
+        assert_eq!(
+            translate_button(winit::event::MouseButton::Other(7)),
+            MouseButton::Other(7)
+        );
+    }
+}
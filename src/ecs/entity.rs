@@ -0,0 +1,130 @@
+use std::fmt;
+
+use crate::event_system::event::EntityId;
+
+// A game object. The index is reused after a despawn, the generation tells
+// the new entity from stale handles to the old one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+impl Entity {
+    pub fn get_index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn get_generation(&self) -> u32 {
+        self.generation
+    }
+
+    // For targeted events, the generation in the high bits
+    pub fn to_id(self) -> EntityId {
+        EntityId((self.generation as u64) << 32 | self.index as u64)
+    }
+
+    pub fn from_id(id: EntityId) -> Self {
+        Self {
+            index: id.0 as u32,
+            generation: (id.0 >> 32) as u32,
+        }
+    }
+}
+
+impl From<Entity> for EntityId {
+    fn from(entity: Entity) -> Self {
+        entity.to_id()
+    }
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+// Hands out entities and recycles the indices of despawned ones
+#[derive(Debug, Clone, Default)]
+pub struct Entities {
+    // current generation of every index ever handed out
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl Entities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn alloc(&mut self) -> Entity {
+        self.len += 1;
+        if let Some(index) = self.free.pop() {
+            self.alive[index as usize] = true;
+            return Entity {
+                index,
+                generation: self.generations[index as usize],
+            };
+        }
+        let index = self.generations.len() as u32;
+        self.generations.push(0);
+        self.alive.push(true);
+        Entity {
+            index,
+            generation: 0,
+        }
+    }
+
+    // Room for `additional` more entities, recycled indices are used first
+    pub fn reserve(&mut self, additional: usize) {
+        let new = additional.saturating_sub(self.free.len());
+        self.generations.reserve(new);
+        self.alive.reserve(new);
+    }
+
+    // Returns false for entities already freed
+    pub fn free(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        let index = entity.index as usize;
+        self.alive[index] = false;
+        self.generations[index] = self.generations[index].wrapping_add(1);
+        self.free.push(entity.index);
+        self.len -= 1;
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        let index = entity.index as usize;
+        self.alive.get(index).copied().unwrap_or(false)
+            && self.generations[index] == entity.generation
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // One past the highest index handed out so far
+    pub fn get_capacity(&self) -> usize {
+        self.generations.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.alive
+            .iter()
+            .zip(self.generations.iter())
+            .enumerate()
+            .filter(|(_, (alive, _))| **alive)
+            .map(|(index, (_, generation))| Entity {
+                index: index as u32,
+                generation: *generation,
+            })
+    }
+}
@@ -0,0 +1,7 @@
+pub mod entity;
+pub mod query;
+pub mod schedule;
+pub mod storage;
+pub mod system;
+mod worker_pool;
+pub mod world;
@@ -0,0 +1,624 @@
+use std::{
+    any::{type_name, TypeId},
+    collections::HashMap,
+    marker::PhantomData,
+};
+
+use super::{
+    entity::{Entities, Entity},
+    storage::{AnyStorage, Component, Members, ResourceCell, SparseSet},
+};
+
+// The component and resource types something reads and writes, and the
+// ones its filters only check entities against. Two accesses conflict when
+// one writes a type the other uses in any way, a filter on a type the same
+// access writes is fine.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Access {
+    reads: Vec<(TypeId, &'static str)>,
+    writes: Vec<(TypeId, &'static str)>,
+    filters: Vec<(TypeId, &'static str)>,
+}
+
+impl Access {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_read<T: Component>(&mut self) {
+        self.reads.push((TypeId::of::<T>(), type_name::<T>()));
+    }
+
+    pub fn add_write<T: Component>(&mut self) {
+        self.writes.push((TypeId::of::<T>(), type_name::<T>()));
+    }
+
+    pub fn add_filter<T: Component>(&mut self) {
+        self.filters.push((TypeId::of::<T>(), type_name::<T>()));
+    }
+
+    pub fn add_resource_read<T: Component>(&mut self) {
+        self.reads
+            .push((TypeId::of::<ResourceCell<T>>(), type_name::<T>()));
+    }
+
+    pub fn add_resource_write<T: Component>(&mut self) {
+        self.writes
+            .push((TypeId::of::<ResourceCell<T>>(), type_name::<T>()));
+    }
+
+    pub fn extend(&mut self, other: &Access) {
+        self.reads.extend_from_slice(&other.reads);
+        self.writes.extend_from_slice(&other.writes);
+        self.filters.extend_from_slice(&other.filters);
+    }
+
+    pub fn is_read(&self, type_id: TypeId) -> bool {
+        self.reads.iter().any(|(read, _)| *read == type_id)
+    }
+
+    pub fn is_written(&self, type_id: TypeId) -> bool {
+        self.writes.iter().any(|(written, _)| *written == type_id)
+    }
+
+    pub fn is_filtered(&self, type_id: TypeId) -> bool {
+        self.filters
+            .iter()
+            .any(|(filtered, _)| *filtered == type_id)
+    }
+
+    fn is_used(&self, type_id: TypeId) -> bool {
+        self.is_read(type_id) || self.is_written(type_id) || self.is_filtered(type_id)
+    }
+
+    // A type written twice, or written and read, by this access alone
+    pub fn get_conflict(&self) -> Option<&'static str> {
+        self.writes
+            .iter()
+            .enumerate()
+            .find(|(index, (written, _))| {
+                self.is_read(*written)
+                    || self.writes[index + 1..]
+                        .iter()
+                        .any(|(other, _)| other == written)
+            })
+            .map(|(_, (_, name))| *name)
+    }
+
+    // A type one of the two writes and the other uses
+    pub fn get_conflict_with(&self, other: &Access) -> Option<&'static str> {
+        self.writes
+            .iter()
+            .find(|(written, _)| other.is_used(*written))
+            .or_else(|| {
+                other
+                    .writes
+                    .iter()
+                    .find(|(written, _)| self.is_used(*written))
+            })
+            .map(|(_, name)| *name)
+    }
+
+    pub fn is_compatible(&self, other: &Access) -> bool {
+        self.get_conflict_with(other).is_none()
+    }
+}
+
+enum StorageRef<'w> {
+    Read(&'w dyn AnyStorage),
+    Write(&'w mut dyn AnyStorage),
+}
+
+// The storages one access may touch, borrowed from the world
+pub struct StorageView<'w> {
+    entities: &'w Entities,
+    storages: HashMap<TypeId, StorageRef<'w>>,
+    // who has the components of the storages taken for writing, for filters
+    members: HashMap<TypeId, Members<'w>>,
+}
+
+impl<'w> StorageView<'w> {
+    pub(crate) fn new(
+        entities: &'w Entities,
+        storages: impl Iterator<Item = (&'w TypeId, &'w mut Box<dyn AnyStorage>)>,
+        access: &Access,
+    ) -> Self {
+        let storages = storages
+            .filter_map(|(type_id, storage)| {
+                let storage = match (access.is_written(*type_id), access.is_used(*type_id)) {
+                    (true, _) => StorageRef::Write(storage.as_mut()),
+                    (false, true) => StorageRef::Read(&**storage),
+                    (false, false) => return None,
+                };
+                Some((*type_id, storage))
+            })
+            .collect();
+        Self {
+            entities,
+            storages,
+            members: HashMap::new(),
+        }
+    }
+
+    // One view per access for systems running side by side, each type
+    // written by one of them goes to that one alone. The accesses have to
+    // be compatible with each other.
+    pub(crate) fn split_all(
+        entities: &'w Entities,
+        storages: impl Iterator<Item = (&'w TypeId, &'w mut Box<dyn AnyStorage>)>,
+        accesses: &[Access],
+    ) -> Vec<Self> {
+        let mut views: Vec<Self> = accesses
+            .iter()
+            .map(|_| Self {
+                entities,
+                storages: HashMap::new(),
+                members: HashMap::new(),
+            })
+            .collect();
+        for (type_id, storage) in storages {
+            let writer = accesses
+                .iter()
+                .position(|access| access.is_written(*type_id));
+            if let Some(writer) = writer {
+                views[writer]
+                    .storages
+                    .insert(*type_id, StorageRef::Write(storage.as_mut()));
+                continue;
+            }
+            let storage: &'w dyn AnyStorage = &**storage;
+            for (view, access) in views.iter_mut().zip(accesses) {
+                if access.is_used(*type_id) {
+                    view.storages.insert(*type_id, StorageRef::Read(storage));
+                }
+            }
+        }
+        views
+    }
+
+    // Moves the storages `access` writes into a view of their own and
+    // shares the ones it reads, for the queries of one system
+    pub(crate) fn split(&mut self, access: &Access) -> StorageView<'w> {
+        let mut storages = HashMap::new();
+        for (type_id, _) in access.writes.iter() {
+            if let Some(storage @ StorageRef::Write(_)) = self.storages.remove(type_id) {
+                storages.insert(*type_id, storage);
+            }
+        }
+        for (type_id, _) in access.reads.iter().chain(access.filters.iter()) {
+            if let Some(StorageRef::Read(storage)) = self.storages.get(type_id) {
+                storages.insert(*type_id, StorageRef::Read(*storage));
+            }
+        }
+        StorageView {
+            entities: self.entities,
+            storages,
+            members: HashMap::new(),
+        }
+    }
+
+    fn reborrow(&mut self) -> StorageView<'_> {
+        let storages = self
+            .storages
+            .iter_mut()
+            .map(|(type_id, storage)| {
+                let storage = match storage {
+                    StorageRef::Read(storage) => StorageRef::Read(*storage),
+                    StorageRef::Write(storage) => StorageRef::Write(&mut **storage),
+                };
+                (*type_id, storage)
+            })
+            .collect();
+        StorageView {
+            entities: self.entities,
+            storages,
+            members: self.members.clone(),
+        }
+    }
+
+    fn read<T: Component>(&self) -> Option<&'w SparseSet<T>> {
+        match self.storages.get(&TypeId::of::<T>())? {
+            StorageRef::Read(storage) => storage.as_any().downcast_ref(),
+            StorageRef::Write(_) => None,
+        }
+    }
+
+    // Also works on storages taken for writing, the values stay with the
+    // write
+    fn get_members<T: Component>(&self) -> Option<Members<'w>> {
+        match self.storages.get(&TypeId::of::<T>()) {
+            Some(StorageRef::Read(storage)) => storage
+                .as_any()
+                .downcast_ref::<SparseSet<T>>()
+                .map(|storage| storage.get_members()),
+            _ => self.members.get(&TypeId::of::<T>()).copied(),
+        }
+    }
+
+    fn take_write<T: Component>(&mut self) -> Option<&'w mut SparseSet<T>> {
+        match self.storages.remove(&TypeId::of::<T>())? {
+            StorageRef::Write(storage) => storage.as_any_mut().downcast_mut(),
+            StorageRef::Read(_) => None,
+        }
+    }
+
+    pub(crate) fn read_resource<T: Component>(&self) -> Option<&'w T> {
+        match self.storages.get(&TypeId::of::<ResourceCell<T>>())? {
+            StorageRef::Read(cell) => cell
+                .as_any()
+                .downcast_ref::<ResourceCell<T>>()
+                .map(|cell| &cell.0),
+            StorageRef::Write(_) => None,
+        }
+    }
+
+    pub(crate) fn take_resource<T: Component>(&mut self) -> Option<&'w mut T> {
+        match self.storages.remove(&TypeId::of::<ResourceCell<T>>())? {
+            StorageRef::Write(cell) => cell
+                .as_any_mut()
+                .downcast_mut::<ResourceCell<T>>()
+                .map(|cell| &mut cell.0),
+            StorageRef::Read(_) => None,
+        }
+    }
+}
+
+// What a query yields per entity: `&T`, `&mut T`, `Option<&T>`,
+// `Option<&mut T>`, `Entity` or a tuple of up to eight of them
+pub trait QueryData {
+    type Item<'a>;
+    type Fetch<'a>;
+
+    fn add_access(access: &mut Access);
+
+    // None when a required component has no storage yet, nothing matches
+    fn fetch<'a>(view: &mut StorageView<'a>) -> Option<Self::Fetch<'a>>;
+
+    // Entities having the required components, None when nothing is required
+    fn get_candidates<'a>(fetch: &Self::Fetch<'a>) -> Option<&'a [Entity]>;
+
+    // Each entity is handed out at most once per fetch
+    fn get<'a>(fetch: &mut Self::Fetch<'a>, entity: Entity) -> Option<Self::Item<'a>>;
+}
+
+impl<T: Component> QueryData for &T {
+    type Item<'a> = &'a T;
+    type Fetch<'a> = &'a SparseSet<T>;
+
+    fn add_access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn fetch<'a>(view: &mut StorageView<'a>) -> Option<Self::Fetch<'a>> {
+        view.read::<T>()
+    }
+
+    fn get_candidates<'a>(fetch: &Self::Fetch<'a>) -> Option<&'a [Entity]> {
+        Some(fetch.get_entities())
+    }
+
+    fn get<'a>(fetch: &mut Self::Fetch<'a>, entity: Entity) -> Option<Self::Item<'a>> {
+        fetch.get(entity)
+    }
+}
+
+// The mutable components of a storage, found through its members. Building
+// it does not touch the values, only the entities handed out are marked.
+pub struct WriteFetch<'a, T> {
+    members: Members<'a>,
+    values: *mut T,
+    // one bit per value position, set once its value was handed out
+    taken: Vec<u64>,
+    marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> WriteFetch<'a, T> {
+    fn new(members: Members<'a>, values: &'a mut [T]) -> Self {
+        Self {
+            members,
+            taken: vec![0; values.len().div_ceil(64)],
+            values: values.as_mut_ptr(),
+            marker: PhantomData,
+        }
+    }
+
+    fn take(&mut self, entity: Entity) -> Option<&'a mut T> {
+        let position = self.members.get_position(entity)?;
+        let (word, bit) = (position / 64, 1 << (position % 64));
+        if self.taken[word] & bit != 0 {
+            return None;
+        }
+        self.taken[word] |= bit;
+        // SAFETY: `values` is borrowed mutably for 'a, the position is in
+        // bounds as the members index it, and `taken` makes sure no value
+        // is handed out twice
+        Some(unsafe { &mut *self.values.add(position) })
+    }
+}
+
+impl<T: Component> QueryData for &mut T {
+    type Item<'a> = &'a mut T;
+    type Fetch<'a> = WriteFetch<'a, T>;
+
+    fn add_access(access: &mut Access) {
+        access.add_write::<T>();
+    }
+
+    fn fetch<'a>(view: &mut StorageView<'a>) -> Option<Self::Fetch<'a>> {
+        let (members, values) = view.take_write::<T>()?.split_mut();
+        view.members.insert(TypeId::of::<T>(), members);
+        Some(WriteFetch::new(members, values))
+    }
+
+    fn get_candidates<'a>(fetch: &Self::Fetch<'a>) -> Option<&'a [Entity]> {
+        Some(fetch.members.get_entities())
+    }
+
+    fn get<'a>(fetch: &mut Self::Fetch<'a>, entity: Entity) -> Option<Self::Item<'a>> {
+        fetch.take(entity)
+    }
+}
+
+impl<Q: QueryData> QueryData for Option<Q> {
+    type Item<'a> = Option<Q::Item<'a>>;
+    type Fetch<'a> = Option<Q::Fetch<'a>>;
+
+    fn add_access(access: &mut Access) {
+        Q::add_access(access);
+    }
+
+    fn fetch<'a>(view: &mut StorageView<'a>) -> Option<Self::Fetch<'a>> {
+        Some(Q::fetch(view))
+    }
+
+    fn get_candidates<'a>(_fetch: &Self::Fetch<'a>) -> Option<&'a [Entity]> {
+        None
+    }
+
+    fn get<'a>(fetch: &mut Self::Fetch<'a>, entity: Entity) -> Option<Self::Item<'a>> {
+        Some(fetch.as_mut().and_then(|fetch| Q::get(fetch, entity)))
+    }
+}
+
+impl QueryData for Entity {
+    type Item<'a> = Entity;
+    type Fetch<'a> = ();
+
+    fn add_access(_access: &mut Access) {}
+
+    fn fetch<'a>(_view: &mut StorageView<'a>) -> Option<Self::Fetch<'a>> {
+        Some(())
+    }
+
+    fn get_candidates<'a>(_fetch: &Self::Fetch<'a>) -> Option<&'a [Entity]> {
+        None
+    }
+
+    fn get<'a>(_fetch: &mut Self::Fetch<'a>, entity: Entity) -> Option<Self::Item<'a>> {
+        Some(entity)
+    }
+}
+
+macro_rules! impl_query_data_tuple {
+    ($($data:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($data: QueryData),*> QueryData for ($($data,)*) {
+            type Item<'a> = ($($data::Item<'a>,)*);
+            type Fetch<'a> = ($($data::Fetch<'a>,)*);
+
+            fn add_access(access: &mut Access) {
+                $($data::add_access(access);)*
+            }
+
+            fn fetch<'a>(view: &mut StorageView<'a>) -> Option<Self::Fetch<'a>> {
+                Some(($($data::fetch(view)?,)*))
+            }
+
+            // the smallest storage has the fewest entities to try
+            fn get_candidates<'a>(fetch: &Self::Fetch<'a>) -> Option<&'a [Entity]> {
+                let ($($data,)*) = fetch;
+                [$($data::get_candidates($data)),*]
+                    .into_iter()
+                    .flatten()
+                    .min_by_key(|candidates| candidates.len())
+            }
+
+            fn get<'a>(fetch: &mut Self::Fetch<'a>, entity: Entity) -> Option<Self::Item<'a>> {
+                let ($($data,)*) = fetch;
+                Some(($($data::get($data, entity)?,)*))
+            }
+        }
+    };
+}
+
+impl_query_data_tuple!(A);
+impl_query_data_tuple!(A, B);
+impl_query_data_tuple!(A, B, C);
+impl_query_data_tuple!(A, B, C, D);
+impl_query_data_tuple!(A, B, C, D, E);
+impl_query_data_tuple!(A, B, C, D, E, F);
+impl_query_data_tuple!(A, B, C, D, E, F, G);
+impl_query_data_tuple!(A, B, C, D, E, F, G, H);
+
+// Narrows a query down without fetching anything: `With<T>`,
+// `Without<T>`, `()` or a tuple of up to eight of them. Filters only look at
+// who has a component, so `(&mut T, With<T>)` is fine.
+pub trait QueryFilter {
+    type Fetch<'a>;
+
+    fn add_access(access: &mut Access);
+
+    fn fetch<'a>(view: &StorageView<'a>) -> Self::Fetch<'a>;
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: Entity) -> bool;
+}
+
+// Only entities that have a T
+pub struct With<T>(PhantomData<T>);
+
+// Only entities that have no T
+pub struct Without<T>(PhantomData<T>);
+
+impl<T: Component> QueryFilter for With<T> {
+    type Fetch<'a> = Option<Members<'a>>;
+
+    fn add_access(access: &mut Access) {
+        access.add_filter::<T>();
+    }
+
+    fn fetch<'a>(view: &StorageView<'a>) -> Self::Fetch<'a> {
+        view.get_members::<T>()
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: Entity) -> bool {
+        fetch.is_some_and(|members| members.contains(entity))
+    }
+}
+
+impl<T: Component> QueryFilter for Without<T> {
+    type Fetch<'a> = Option<Members<'a>>;
+
+    fn add_access(access: &mut Access) {
+        access.add_filter::<T>();
+    }
+
+    fn fetch<'a>(view: &StorageView<'a>) -> Self::Fetch<'a> {
+        view.get_members::<T>()
+    }
+
+    fn matches(fetch: &Self::Fetch<'_>, entity: Entity) -> bool {
+        !fetch.is_some_and(|members| members.contains(entity))
+    }
+}
+
+macro_rules! impl_query_filter_tuple {
+    ($($filter:ident),*) => {
+        #[allow(non_snake_case, unused_variables, clippy::unused_unit)]
+        impl<$($filter: QueryFilter),*> QueryFilter for ($($filter,)*) {
+            type Fetch<'a> = ($($filter::Fetch<'a>,)*);
+
+            fn add_access(access: &mut Access) {
+                $($filter::add_access(access);)*
+            }
+
+            fn fetch<'a>(view: &StorageView<'a>) -> Self::Fetch<'a> {
+                ($($filter::fetch(view),)*)
+            }
+
+            fn matches(fetch: &Self::Fetch<'_>, entity: Entity) -> bool {
+                let ($($filter,)*) = fetch;
+                true $(&& $filter::matches($filter, entity))*
+            }
+        }
+    };
+}
+
+impl_query_filter_tuple!();
+impl_query_filter_tuple!(A);
+impl_query_filter_tuple!(A, B);
+impl_query_filter_tuple!(A, B, C);
+impl_query_filter_tuple!(A, B, C, D);
+impl_query_filter_tuple!(A, B, C, D, E);
+impl_query_filter_tuple!(A, B, C, D, E, F);
+impl_query_filter_tuple!(A, B, C, D, E, F, G);
+impl_query_filter_tuple!(A, B, C, D, E, F, G, H);
+
+// The entities with the components `Q` asks for that pass `F`. Items borrow
+// the query, so the borrow checker keeps them from outliving it or from
+// aliasing a second iteration.
+//
+//     let mut query = world.query_filtered::<(&Velocity, &mut Position), Without<Frozen>>();
+//     for (velocity, position) in query.iter_mut() {
+//         position.0 += velocity.0 * delta;
+//     }
+pub struct Query<'w, Q: QueryData, F: QueryFilter = ()> {
+    view: StorageView<'w>,
+    marker: PhantomData<fn() -> (Q, F)>,
+}
+
+impl<'w, Q: QueryData, F: QueryFilter> Query<'w, Q, F> {
+    // Panics when the query reads and writes, or writes twice, the same
+    // component type, like `(&mut Position, &Position)`. Stable Rust cannot
+    // compare types while compiling, so this runs when the query is built
+    // or its system added, before anything is borrowed.
+    pub fn get_access() -> Access {
+        let mut access = Access::new();
+        Q::add_access(&mut access);
+        F::add_access(&mut access);
+        if let Some(conflict) = access.get_conflict() {
+            panic!(
+                "query {} borrows {} mutably more than once",
+                type_name::<Self>(),
+                conflict
+            );
+        }
+        access
+    }
+
+    pub(crate) fn new(view: StorageView<'w>) -> Self {
+        Self {
+            view,
+            marker: PhantomData,
+        }
+    }
+
+    pub fn iter_mut(&mut self) -> QueryIter<'_, Q, F> {
+        let mut view = self.view.reborrow();
+        let Some(fetch) = Q::fetch(&mut view) else {
+            return QueryIter {
+                fetch: None,
+                candidates: Vec::new().into_iter(),
+            };
+        };
+        // after the fetch, filters see the members of what it writes
+        let filter = F::fetch(&view);
+        let candidates: Vec<Entity> = match Q::get_candidates(&fetch) {
+            Some(candidates) => candidates.to_vec(),
+            None => view.entities.iter().collect(),
+        };
+        QueryIter {
+            fetch: Some((fetch, filter)),
+            candidates: candidates.into_iter(),
+        }
+    }
+
+    // The items of one entity, None when it does not match
+    pub fn get_mut(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+        let mut view = self.view.reborrow();
+        let mut fetch = Q::fetch(&mut view)?;
+        if !view.entities.is_alive(entity) || !F::matches(&F::fetch(&view), entity) {
+            return None;
+        }
+        Q::get(&mut fetch, entity)
+    }
+
+    pub fn count(&mut self) -> usize {
+        self.iter_mut().count()
+    }
+}
+
+impl<'a, 'w, Q: QueryData, F: QueryFilter> IntoIterator for &'a mut Query<'w, Q, F> {
+    type Item = Q::Item<'a>;
+    type IntoIter = QueryIter<'a, Q, F>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+pub struct QueryIter<'a, Q: QueryData, F: QueryFilter> {
+    fetch: Option<(Q::Fetch<'a>, F::Fetch<'a>)>,
+    candidates: std::vec::IntoIter<Entity>,
+}
+
+impl<'a, Q: QueryData, F: QueryFilter> Iterator for QueryIter<'a, Q, F> {
+    type Item = Q::Item<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (fetch, filter) = self.fetch.as_mut()?;
+        self.candidates
+            .by_ref()
+            .filter(|entity| F::matches(filter, *entity))
+            .find_map(|entity| Q::get(fetch, entity))
+    }
+}
@@ -0,0 +1,459 @@
+use std::{collections::BTreeMap, fmt};
+
+use log::error;
+use thiserror::Error;
+
+use super::{
+    query::Access,
+    system::{IntoSystem, System},
+    worker_pool::WorkerPool,
+    world::World,
+};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ScheduleErrors {
+    #[error("systems of {0:?} are ordered in a cycle: {1:?}")]
+    Cycle(Stage, Vec<String>),
+}
+
+// Where in the frame a system runs, in this order. PreUpdate sees the
+// frame's events dispatched, FixedUpdate runs zero or more times per frame
+// after `AloyApp::on_fixed_update` and Render is skipped by headless runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Stage {
+    PreUpdate,
+    FixedUpdate,
+    Update,
+    PostUpdate,
+    Render,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::PreUpdate,
+        Stage::FixedUpdate,
+        Stage::Update,
+        Stage::PostUpdate,
+        Stage::Render,
+    ];
+}
+
+// A system with its place in the stage, `before` and `after` name other
+// systems of the same stage
+pub struct SystemEntry {
+    name: String,
+    before: Vec<String>,
+    after: Vec<String>,
+    system: Box<dyn System>,
+}
+
+impl SystemEntry {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    // Runs this system ahead of the named one
+    pub fn before(&mut self, name: &str) -> &mut Self {
+        self.before.push(name.to_string());
+        self
+    }
+
+    pub fn after(&mut self, name: &str) -> &mut Self {
+        self.after.push(name.to_string());
+        self
+    }
+}
+
+impl fmt::Debug for SystemEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SystemEntry")
+            .field("name", &self.name)
+            .field("before", &self.before)
+            .field("after", &self.after)
+            .finish()
+    }
+}
+
+#[derive(Debug, Default)]
+struct StageSystems {
+    systems: Vec<SystemEntry>,
+    // indices into `systems` in running order, the systems of a batch run
+    // side by side. None when systems changed.
+    batches: Option<Vec<Vec<usize>>>,
+}
+
+impl StageSystems {
+    // Kahn's algorithm, systems without constraints between them keep the
+    // order they were added in
+    fn sort(&self, stage: Stage) -> Result<Vec<usize>, ScheduleErrors> {
+        let count = self.systems.len();
+        let mut followers: Vec<Vec<usize>> = vec![Vec::new(); count];
+        let mut waiting_on = vec![0; count];
+        for (index, entry) in self.systems.iter().enumerate() {
+            for (other, named) in self.systems.iter().enumerate() {
+                let edge = if entry.before.contains(&named.name) {
+                    (index, other)
+                } else if entry.after.contains(&named.name) {
+                    (other, index)
+                } else {
+                    continue;
+                };
+                followers[edge.0].push(edge.1);
+                waiting_on[edge.1] += 1;
+            }
+        }
+
+        let mut order = Vec::with_capacity(count);
+        let mut done = vec![false; count];
+        while order.len() < count {
+            let Some(next) = (0..count).find(|index| !done[*index] && waiting_on[*index] == 0)
+            else {
+                let cycle = (0..count)
+                    .filter(|index| !done[*index])
+                    .map(|index| self.systems[index].name.clone())
+                    .collect();
+                return Err(ScheduleErrors::Cycle(stage, cycle));
+            };
+            done[next] = true;
+            for then in followers[next].iter() {
+                waiting_on[*then] -= 1;
+            }
+            order.push(next);
+        }
+        Ok(order)
+    }
+
+    fn is_ordered(&self, first: usize, then: usize) -> bool {
+        let (first, then) = (&self.systems[first], &self.systems[then]);
+        first.before.contains(&then.name)
+            || first.after.contains(&then.name)
+            || then.before.contains(&first.name)
+            || then.after.contains(&first.name)
+    }
+
+    // Groups neighbours in the order that have an access, do not conflict
+    // and are not ordered against each other
+    fn get_batches(&self, order: Vec<usize>, parallel: bool) -> Vec<Vec<usize>> {
+        let mut batches: Vec<Vec<usize>> = Vec::new();
+        for index in order {
+            let access = self.systems[index].system.get_access();
+            let joins = parallel
+                && batches.last().is_some_and(|batch| {
+                    batch.iter().all(|other| {
+                        let other_access = self.systems[*other].system.get_access();
+                        match (access, other_access) {
+                            (Some(access), Some(other_access)) => {
+                                access.is_compatible(other_access)
+                                    && !self.is_ordered(index, *other)
+                            }
+                            _ => false,
+                        }
+                    })
+                });
+            match batches.last_mut() {
+                Some(batch) if joins => batch.push(index),
+                _ => batches.push(vec![index]),
+            }
+        }
+        // the views are handed out in the order of the systems
+        for batch in batches.iter_mut() {
+            batch.sort();
+        }
+        batches
+    }
+
+    fn run_batch(&mut self, batch: &[usize], world: &mut World, workers: &mut WorkerPool) {
+        if let [index] = batch {
+            self.systems[*index].system.run(world);
+            return;
+        }
+        let accesses: Vec<Access> = batch
+            .iter()
+            .filter_map(|index| self.systems[*index].system.get_access().cloned())
+            .collect();
+        let views = world.get_views(&accesses);
+        let jobs = self
+            .systems
+            .iter_mut()
+            .enumerate()
+            .filter(|(index, _)| batch.contains(index))
+            .map(|(_, entry)| &mut entry.system)
+            .zip(views)
+            .map(|(system, view)| {
+                Box::new(move || system.run_view(view)) as Box<dyn FnOnce() + Send + '_>
+            })
+            .collect();
+        workers.run_all(jobs);
+    }
+}
+
+// The systems of every stage, the Application runs them each frame:
+//
+//     engine.add_system(Stage::Update, "movement", movement);
+//     engine.add_system(Stage::Update, "collisions", collisions).after("movement");
+#[derive(Debug)]
+pub struct Schedule {
+    stages: BTreeMap<Stage, StageSystems>,
+    parallel: bool,
+    workers: WorkerPool,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self {
+            stages: BTreeMap::new(),
+            parallel: true,
+            workers: WorkerPool::new(),
+        }
+    }
+
+    // Worker threads started so far, batches of n systems need n - 1
+    pub fn get_worker_count(&self) -> usize {
+        self.workers.get_worker_count()
+    }
+
+    pub fn is_parallel(&self) -> bool {
+        self.parallel
+    }
+
+    // Runs every system on the main thread when false, e.g. to debug a
+    // race between systems that should have been ordered
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel;
+        for systems in self.stages.values_mut() {
+            systems.batches = None;
+        }
+    }
+
+    pub fn add_system<Marker>(
+        &mut self,
+        stage: Stage,
+        name: &str,
+        system: impl IntoSystem<Marker>,
+    ) -> &mut SystemEntry {
+        let systems = self.stages.entry(stage).or_default();
+        systems.batches = None;
+        systems.systems.push(SystemEntry {
+            name: name.to_string(),
+            before: Vec::new(),
+            after: Vec::new(),
+            system: Box::new(system.into_system()),
+        });
+        systems
+            .systems
+            .last_mut()
+            .expect("the system was just added")
+    }
+
+    // Removes every system of the stage with the name
+    pub fn remove_system(&mut self, stage: Stage, name: &str) -> bool {
+        let Some(systems) = self.stages.get_mut(&stage) else {
+            return false;
+        };
+        let count = systems.systems.len();
+        systems.systems.retain(|entry| entry.name != name);
+        systems.batches = None;
+        systems.systems.len() != count
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages
+            .values()
+            .map(|systems| systems.systems.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Names of the stage's systems in the order they run
+    pub fn get_order(&self, stage: Stage) -> Result<Vec<&str>, ScheduleErrors> {
+        let Some(systems) = self.stages.get(&stage) else {
+            return Ok(Vec::new());
+        };
+        Ok(systems
+            .sort(stage)?
+            .into_iter()
+            .map(|index| systems.systems[index].get_name())
+            .collect())
+    }
+
+    // The systems of the stage that run side by side, in the order the
+    // groups run
+    pub fn get_batches(&self, stage: Stage) -> Result<Vec<Vec<&str>>, ScheduleErrors> {
+        let Some(systems) = self.stages.get(&stage) else {
+            return Ok(Vec::new());
+        };
+        let batches = systems.get_batches(systems.sort(stage)?, self.parallel);
+        Ok(batches
+            .into_iter()
+            .map(|batch| {
+                batch
+                    .into_iter()
+                    .map(|index| systems.systems[index].get_name())
+                    .collect()
+            })
+            .collect())
+    }
+
+    // Runs the systems of the stage, the ones that do not conflict on the
+    // schedule's worker threads. A cycle in their ordering is logged once and they run
+    // in the order they were added.
+    pub fn run_stage(&mut self, stage: Stage, world: &mut World) {
+        let Some(systems) = self.stages.get_mut(&stage) else {
+            return;
+        };
+        let batches = match systems.batches.take() {
+            Some(batches) => batches,
+            None => {
+                let order = systems.sort(stage).unwrap_or_else(|err| {
+                    error!("{}", err);
+                    (0..systems.systems.len()).collect()
+                });
+                systems.get_batches(order, self.parallel)
+            }
+        };
+        for batch in batches.iter() {
+            systems.run_batch(batch, world, &mut self.workers);
+        }
+        systems.batches = Some(batches);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::query::Query;
+
+    struct Journal(Vec<&'static str>);
+
+    fn log(name: &'static str) -> impl FnMut(&mut World) + Send {
+        move |world: &mut World| {
+            for journal in world.query::<&mut Journal>().iter_mut() {
+                journal.0.push(name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_systems_run_in_order() {
+        let mut world = World::new();
+        let journal = world.spawn((Journal(Vec::new()),));
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(Stage::Update, "render", log("render"))
+            .after("physics");
+        schedule.add_system(Stage::Update, "physics", log("physics"));
+        schedule
+            .add_system(Stage::Update, "input", log("input"))
+            .before("physics");
+        schedule.add_system(Stage::PreUpdate, "events", log("events"));
+
+        assert_eq!(
+            schedule.get_order(Stage::Update),
+            Ok(vec!["input", "physics", "render"])
+        );
+        for stage in Stage::ALL {
+            schedule.run_stage(stage, &mut world);
+        }
+        assert_eq!(
+            world.get::<Journal>(journal).unwrap().0,
+            vec!["events", "input", "physics", "render"]
+        );
+
+        // a cycle still runs every system once
+        schedule
+            .add_system(Stage::Update, "late", log("late"))
+            .before("input")
+            .after("render");
+        assert!(matches!(
+            schedule.get_order(Stage::Update),
+            Err(ScheduleErrors::Cycle(Stage::Update, _))
+        ));
+        world.get_mut::<Journal>(journal).unwrap().0.clear();
+        schedule.run_stage(Stage::Update, &mut world);
+        assert_eq!(world.get::<Journal>(journal).unwrap().0.len(), 4);
+        assert!(schedule.remove_system(Stage::Update, "late"));
+        assert_eq!(schedule.len(), 4);
+    }
+
+    struct Position(f32);
+
+    struct Velocity(f32);
+
+    struct Health(u32);
+
+    fn movement(mut query: Query<(&Velocity, &mut Position)>) {
+        for (velocity, position) in &mut query {
+            position.0 += velocity.0;
+        }
+    }
+
+    fn regenerate(mut healths: Query<&mut Health>, mut journals: Query<&mut Journal>) {
+        for health in &mut healths {
+            health.0 += 1;
+        }
+        for journal in &mut journals {
+            journal.0.push("regenerate");
+        }
+    }
+
+    fn report(mut positions: Query<&Position>, mut journals: Query<&mut Journal>) {
+        let moved = positions
+            .iter_mut()
+            .filter(|position| position.0 > 0.0)
+            .count();
+        for journal in &mut journals {
+            journal.0.push(if moved == 2 { "moved" } else { "still" });
+        }
+    }
+
+    #[test]
+    fn test_disjoint_systems_run_side_by_side() {
+        let mut world = World::new();
+        let journal = world.spawn((Journal(Vec::new()),));
+        for speed in [1.0, 2.0] {
+            world.spawn((Position(0.0), Velocity(speed), Health(1)));
+        }
+        let mut schedule = Schedule::new();
+        schedule.add_system(Stage::Update, "movement", movement);
+        schedule.add_system(Stage::Update, "regenerate", regenerate);
+        schedule.add_system(Stage::Update, "report", report);
+        schedule.add_system(Stage::Update, "log", log("log"));
+
+        // report reads what movement writes, log takes the whole world
+        assert_eq!(
+            schedule.get_batches(Stage::Update),
+            Ok(vec![
+                vec!["movement", "regenerate"],
+                vec!["report"],
+                vec!["log"]
+            ])
+        );
+        schedule.run_stage(Stage::Update, &mut world);
+        assert_eq!(
+            world.get::<Journal>(journal).unwrap().0,
+            vec!["regenerate", "moved", "log"]
+        );
+        let healths: Vec<u32> = world
+            .query::<&Health>()
+            .iter_mut()
+            .map(|health| health.0)
+            .collect();
+        assert_eq!(healths, vec![2, 2]);
+
+        // the workers stay around for the next frames
+        schedule.run_stage(Stage::Update, &mut world);
+        assert_eq!(schedule.get_worker_count(), 1);
+
+        schedule.set_parallel(false);
+        assert_eq!(schedule.get_batches(Stage::Update).unwrap().len(), 4);
+    }
+}
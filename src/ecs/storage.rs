@@ -0,0 +1,213 @@
+use std::any::Any;
+
+use super::entity::Entity;
+
+// Anything Send and Sync can be a component, systems may run on other
+// threads
+pub trait Component: Any + Send + Sync {}
+
+impl<T: Any + Send + Sync> Component for T {}
+
+// The components of one type. Values are packed for iteration and found by
+// entity index through the sparse array.
+#[derive(Debug)]
+pub struct SparseSet<T> {
+    // position in `dense` of every entity index
+    sparse: Vec<Option<usize>>,
+    dense: Vec<T>,
+    owners: Vec<Entity>,
+}
+
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> SparseSet<T> {
+    pub fn new() -> Self {
+        Self {
+            sparse: Vec::new(),
+            dense: Vec::new(),
+            owners: Vec::new(),
+        }
+    }
+
+    // Returns the component the entity had
+    pub fn insert(&mut self, entity: Entity, value: T) -> Option<T> {
+        let index = entity.get_index() as usize;
+        if let Some(position) = self.get_position(entity) {
+            return Some(std::mem::replace(&mut self.dense[position], value));
+        }
+        if index >= self.sparse.len() {
+            self.sparse.resize(index + 1, None);
+        }
+        // a stale owner of the index is replaced
+        if let Some(position) = self.sparse[index] {
+            self.swap_remove(position);
+        }
+        self.sparse[index] = Some(self.dense.len());
+        self.dense.push(value);
+        self.owners.push(entity);
+        None
+    }
+
+    // Inserts a value per entity, growing the storage once for all of them
+    pub fn insert_batch(&mut self, entities: &[Entity], values: Vec<T>) {
+        self.dense.reserve(values.len());
+        self.owners.reserve(values.len());
+        let size = entities
+            .iter()
+            .map(|entity| entity.get_index() as usize + 1)
+            .max()
+            .unwrap_or(0);
+        if size > self.sparse.len() {
+            self.sparse.resize(size, None);
+        }
+        for (entity, value) in entities.iter().zip(values) {
+            self.insert(*entity, value);
+        }
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let position = self.get_position(entity)?;
+        Some(self.swap_remove(position))
+    }
+
+    fn swap_remove(&mut self, position: usize) -> T {
+        let owner = self.owners[position];
+        self.sparse[owner.get_index() as usize] = None;
+        let value = self.dense.swap_remove(position);
+        self.owners.swap_remove(position);
+        if let Some(moved) = self.owners.get(position) {
+            self.sparse[moved.get_index() as usize] = Some(position);
+        }
+        value
+    }
+
+    fn get_position(&self, entity: Entity) -> Option<usize> {
+        self.get_members().get_position(entity)
+    }
+
+    pub(crate) fn get_members(&self) -> Members<'_> {
+        Members {
+            sparse: &self.sparse,
+            owners: &self.owners,
+        }
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.get_position(entity).is_some()
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        self.get_position(entity)
+            .map(|position| &self.dense[position])
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        self.get_position(entity)
+            .map(|position| &mut self.dense[position])
+    }
+
+    pub fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dense.is_empty()
+    }
+
+    pub fn get_entities(&self) -> &[Entity] {
+        &self.owners
+    }
+
+    // Who has a component next to the values, to hand out every value
+    // mutably while filters still look up entities
+    pub(crate) fn split_mut(&mut self) -> (Members<'_>, &mut [T]) {
+        let members = Members {
+            sparse: &self.sparse,
+            owners: &self.owners,
+        };
+        (members, &mut self.dense)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.owners.iter().copied().zip(self.dense.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.owners.iter().copied().zip(self.dense.iter_mut())
+    }
+}
+
+// The entities of a storage without its values, positions index the values
+#[derive(Debug, Clone, Copy)]
+pub struct Members<'a> {
+    sparse: &'a [Option<usize>],
+    owners: &'a [Entity],
+}
+
+impl<'a> Members<'a> {
+    pub(crate) fn get_position(&self, entity: Entity) -> Option<usize> {
+        let position = (*self.sparse.get(entity.get_index() as usize)?)?;
+        (self.owners[position] == entity).then_some(position)
+    }
+
+    pub(crate) fn contains(&self, entity: Entity) -> bool {
+        self.get_position(entity).is_some()
+    }
+
+    pub(crate) fn get_entities(&self) -> &'a [Entity] {
+        self.owners
+    }
+}
+
+// A resource kept next to the storages, so systems borrow it the same way
+#[derive(Debug)]
+pub(crate) struct ResourceCell<T>(pub(crate) T);
+
+// A SparseSet of any component type, what the world keeps
+pub trait AnyStorage: Any + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    fn remove_entity(&mut self, entity: Entity);
+
+    fn contains(&self, entity: Entity) -> bool;
+}
+
+impl<T: Component> AnyStorage for SparseSet<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn remove_entity(&mut self, entity: Entity) {
+        self.remove(entity);
+    }
+
+    fn contains(&self, entity: Entity) -> bool {
+        SparseSet::contains(self, entity)
+    }
+}
+
+impl<T: Component> AnyStorage for ResourceCell<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn remove_entity(&mut self, _entity: Entity) {}
+
+    fn contains(&self, _entity: Entity) -> bool {
+        false
+    }
+}
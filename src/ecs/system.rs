@@ -0,0 +1,304 @@
+use std::{
+    any::type_name,
+    marker::PhantomData,
+    ops::{Deref, DerefMut},
+};
+
+use super::{
+    query::{Access, Query, QueryData, QueryFilter, StorageView},
+    storage::Component,
+    world::World,
+};
+
+// Game logic that runs on the world every time its stage does. Systems
+// without an access take the whole world and run alone, the others only
+// touch the storages of their access and run next to systems they do not
+// conflict with.
+pub trait System: Send {
+    fn run(&mut self, world: &mut World);
+
+    fn get_access(&self) -> Option<&Access> {
+        None
+    }
+
+    // Runs on the storages of the access, only called when there is one
+    fn run_view(&mut self, _view: StorageView<'_>) {
+        unreachable!("systems without an access run on the world")
+    }
+}
+
+impl<F: FnMut(&mut World) + Send> System for F {
+    fn run(&mut self, world: &mut World) {
+        self(world)
+    }
+}
+
+// What a system function takes, a Query, Res or ResMut
+pub trait SystemParam {
+    type Item<'w>;
+
+    fn add_access(access: &mut Access);
+
+    fn fetch<'w>(view: &mut StorageView<'w>) -> Self::Item<'w>;
+}
+
+impl<Q: QueryData, F: QueryFilter> SystemParam for Query<'_, Q, F> {
+    type Item<'w> = Query<'w, Q, F>;
+
+    fn add_access(access: &mut Access) {
+        access.extend(&Query::<Q, F>::get_access());
+    }
+
+    fn fetch<'w>(view: &mut StorageView<'w>) -> Self::Item<'w> {
+        Query::new(view.split(&Query::<Q, F>::get_access()))
+    }
+}
+
+// A resource a system reads, e.g. `time: Res<Time>`. Systems taking one
+// panic when the world has no such resource, `Option<Res<T>>` does not.
+#[derive(Debug)]
+pub struct Res<'w, T> {
+    value: &'w T,
+}
+
+impl<T> Deref for Res<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+// A resource a system writes, only one system at a time gets it
+#[derive(Debug)]
+pub struct ResMut<'w, T> {
+    value: &'w mut T,
+}
+
+impl<T> Deref for ResMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for ResMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.value
+    }
+}
+
+impl<T: Component> SystemParam for Option<Res<'_, T>> {
+    type Item<'w> = Option<Res<'w, T>>;
+
+    fn add_access(access: &mut Access) {
+        access.add_resource_read::<T>();
+    }
+
+    fn fetch<'w>(view: &mut StorageView<'w>) -> Self::Item<'w> {
+        view.read_resource::<T>().map(|value| Res { value })
+    }
+}
+
+impl<T: Component> SystemParam for Option<ResMut<'_, T>> {
+    type Item<'w> = Option<ResMut<'w, T>>;
+
+    fn add_access(access: &mut Access) {
+        access.add_resource_write::<T>();
+    }
+
+    fn fetch<'w>(view: &mut StorageView<'w>) -> Self::Item<'w> {
+        view.take_resource::<T>().map(|value| ResMut { value })
+    }
+}
+
+impl<T: Component> SystemParam for Res<'_, T> {
+    type Item<'w> = Res<'w, T>;
+
+    fn add_access(access: &mut Access) {
+        access.add_resource_read::<T>();
+    }
+
+    fn fetch<'w>(view: &mut StorageView<'w>) -> Self::Item<'w> {
+        Option::<Res<T>>::fetch(view)
+            .unwrap_or_else(|| panic!("the world has no {} resource", type_name::<T>()))
+    }
+}
+
+impl<T: Component> SystemParam for ResMut<'_, T> {
+    type Item<'w> = ResMut<'w, T>;
+
+    fn add_access(access: &mut Access) {
+        access.add_resource_write::<T>();
+    }
+
+    fn fetch<'w>(view: &mut StorageView<'w>) -> Self::Item<'w> {
+        Option::<ResMut<T>>::fetch(view)
+            .unwrap_or_else(|| panic!("the world has no {} resource", type_name::<T>()))
+    }
+}
+
+// Turns functions into systems. `Marker` only tells the impls apart.
+pub trait IntoSystem<Marker> {
+    type System: System + 'static;
+
+    fn into_system(self) -> Self::System;
+}
+
+impl<S: System + 'static> IntoSystem<()> for S {
+    type System = S;
+
+    fn into_system(self) -> S {
+        self
+    }
+}
+
+type RunFn = Box<dyn FnMut(StorageView<'_>) + Send>;
+
+// A function of SystemParams, its access is the one of its parameters
+pub struct FunctionSystem {
+    access: Access,
+    run: RunFn,
+}
+
+impl System for FunctionSystem {
+    fn run(&mut self, world: &mut World) {
+        let view = world
+            .get_views(std::slice::from_ref(&self.access))
+            .remove(0);
+        (self.run)(view);
+    }
+
+    fn get_access(&self) -> Option<&Access> {
+        Some(&self.access)
+    }
+
+    fn run_view(&mut self, view: StorageView<'_>) {
+        (self.run)(view);
+    }
+}
+
+macro_rules! impl_function_system {
+    ($($param:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<Func, $($param: SystemParam),*> IntoSystem<PhantomData<fn($($param,)*)>> for Func
+        where
+            Func: Send + 'static,
+            for<'a> &'a mut Func: FnMut($($param),*) + FnMut($($param::Item<'_>),*),
+        {
+            type System = FunctionSystem;
+
+            // Panics when two parameters conflict, like two queries writing
+            // the same component or one filtering what another writes
+            fn into_system(mut self) -> FunctionSystem {
+                let mut access = Access::new();
+                $(
+                    let mut param = Access::new();
+                    $param::add_access(&mut param);
+                    if let Some(conflict) = access.get_conflict_with(&param) {
+                        panic!(
+                            "system {} borrows {} mutably more than once",
+                            type_name::<Func>(),
+                            conflict
+                        );
+                    }
+                    access.extend(&param);
+                )*
+                // the params are handed over through a function, so the
+                // borrow checker sees their lifetimes as those of the view
+                #[allow(clippy::too_many_arguments)]
+                fn call<$($param),*>(mut func: impl FnMut($($param),*), $($param: $param),*) {
+                    func($($param),*)
+                }
+                FunctionSystem {
+                    access,
+                    run: Box::new(move |mut view: StorageView<'_>| {
+                        $(let $param = $param::fetch(&mut view);)*
+                        call(&mut self, $($param),*);
+                    }),
+                }
+            }
+        }
+    };
+}
+
+impl_function_system!(A);
+impl_function_system!(A, B);
+impl_function_system!(A, B, C);
+impl_function_system!(A, B, C, D);
+impl_function_system!(A, B, C, D, E);
+impl_function_system!(A, B, C, D, E, F);
+impl_function_system!(A, B, C, D, E, F, G);
+impl_function_system!(A, B, C, D, E, F, G, H);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        core::time::Time,
+        ecs::{query::With, world::EcsErrors},
+    };
+
+    #[derive(Debug, PartialEq)]
+    struct Score(u32);
+
+    struct Coin(u32);
+
+    struct Missing;
+
+    fn collect(
+        mut score: ResMut<Score>,
+        time: Res<Time>,
+        missing: Option<Res<Missing>>,
+        mut coins: Query<&Coin>,
+    ) {
+        assert!(missing.is_none());
+        score.0 += coins.iter_mut().map(|coin| coin.0).sum::<u32>() * time.get_time_scale() as u32;
+    }
+
+    #[test]
+    fn test_resources_in_systems() {
+        let mut world = World::new();
+        assert_eq!(world.insert_resource(Score(0)), Ok(None));
+        world.insert_resource(Time::new()).unwrap();
+        world.spawn((Coin(2),));
+        world.spawn((Coin(3),));
+
+        let mut system = collect.into_system();
+        let access = system.get_access().unwrap().clone();
+        let mut only_time = Access::new();
+        only_time.add_resource_read::<Time>();
+        assert!(access.is_compatible(&only_time));
+        let mut score = Access::new();
+        score.add_resource_read::<Score>();
+        assert!(!access.is_compatible(&score));
+
+        system.run(&mut world);
+        system.run(&mut world);
+        assert_eq!(world.get_resource::<Score>(), Some(&Score(10)));
+        world.get_resource_mut::<Score>().unwrap().0 = 1;
+        assert_eq!(world.remove_resource::<Score>(), Ok(Some(Score(1))));
+        assert!(!world.has_resource::<Score>() && world.has_resource::<Time>());
+
+        world.protect_resource::<Time>();
+        assert!(matches!(
+            world.insert_resource(Time::new()),
+            Err(EcsErrors::ProtectedResource(_))
+        ));
+        assert!(world.remove_resource::<Time>().is_err());
+        assert!(world.has_resource::<Time>());
+    }
+
+    fn drain(mut coins: Query<&mut Coin>, mut scores: Query<&Score, With<Coin>>) {
+        for coin in &mut coins {
+            coin.0 = scores.count() as u32;
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "more than once")]
+    fn test_filtering_what_another_query_writes_panics() {
+        drain.into_system();
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    any::Any,
+    fmt, mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+use log::error;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// Threads the schedule keeps for its whole life, so running a batch of
+// systems does not spawn any. Workers are started the first time a batch
+// needs them and wait for jobs in between.
+pub(crate) struct WorkerPool {
+    sender: Option<Sender<Job>>,
+    receiver: Arc<Mutex<Receiver<Job>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl WorkerPool {
+    pub(crate) fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            sender: Some(sender),
+            receiver: Arc::new(Mutex::new(receiver)),
+            workers: Vec::new(),
+        }
+    }
+
+    pub(crate) fn get_worker_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    fn spawn_workers(&mut self, count: usize) {
+        while self.workers.len() < count {
+            let receiver = Arc::clone(&self.receiver);
+            let spawned = thread::Builder::new()
+                .name(format!("aloy system worker {}", self.workers.len()))
+                .spawn(move || loop {
+                    // the lock is only held while waiting for the next job
+                    let job = match receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(_) => return,
+                    };
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => return,
+                    }
+                });
+            match spawned {
+                Ok(worker) => self.workers.push(worker),
+                Err(err) => {
+                    error!("unable to start a system worker: {}", err);
+                    return;
+                }
+            }
+        }
+    }
+
+    // Runs the first job on this thread and the others on the workers, and
+    // returns once every one of them finished. A panicking job is resumed
+    // here after the others are done.
+    pub(crate) fn run_all<'a>(&mut self, mut jobs: Vec<Box<dyn FnOnce() + Send + 'a>>) {
+        if jobs.is_empty() {
+            return;
+        }
+        let first = jobs.remove(0);
+        self.spawn_workers(jobs.len());
+
+        let (done, finished) = mpsc::channel::<Result<(), Box<dyn Any + Send>>>();
+        for job in jobs {
+            let done = done.clone();
+            let job: Box<dyn FnOnce() + Send + 'a> = Box::new(move || {
+                let _ = done.send(panic::catch_unwind(AssertUnwindSafe(job)));
+            });
+            // SAFETY: the job only lives until it ran or was dropped, which
+            // drops its `done` sender. We do not return before every sender
+            // is gone, so nothing it borrows for 'a is used after 'a.
+            let job: Job = unsafe { mem::transmute(job) };
+            let job = match self.sender.as_ref() {
+                Some(sender) if !self.workers.is_empty() => match sender.send(job) {
+                    Ok(()) => continue,
+                    Err(err) => err.0,
+                },
+                _ => job,
+            };
+            job();
+        }
+        drop(done);
+
+        let mut panicked = panic::catch_unwind(AssertUnwindSafe(first)).err();
+        for result in finished.iter() {
+            if let Err(payload) = result {
+                panicked.get_or_insert(payload);
+            }
+        }
+        if let Some(payload) = panicked {
+            panic::resume_unwind(payload);
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        // closing the channel ends the workers' loop
+        self.sender = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl fmt::Debug for WorkerPool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WorkerPool")
+            .field("workers", &self.workers.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, sync::Mutex, thread::ThreadId};
+
+    use super::*;
+
+    #[test]
+    fn test_workers_are_reused_and_jobs_borrow() {
+        let mut pool = WorkerPool::new();
+        let threads = Mutex::new(HashSet::<ThreadId>::new());
+        let mut sums = [0u32; 3];
+        for round in 1..=4 {
+            let jobs = sums
+                .iter_mut()
+                .map(|sum| {
+                    let threads = &threads;
+                    Box::new(move || {
+                        threads.lock().unwrap().insert(thread::current().id());
+                        *sum += round;
+                    }) as Box<dyn FnOnce() + Send + '_>
+                })
+                .collect();
+            pool.run_all(jobs);
+        }
+
+        assert_eq!(sums, [10, 10, 10]);
+        assert_eq!(pool.get_worker_count(), 2);
+        // this thread and the two workers, never a new one per round
+        assert!(threads.lock().unwrap().len() <= 3);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.run_all(vec![Box::new(|| {}), Box::new(|| panic!("system failed"))]);
+        }));
+        assert!(result.is_err());
+        // the worker survived the panic
+        let mut ran = false;
+        pool.run_all(vec![Box::new(|| {}), Box::new(|| ran = true)]);
+        assert!(ran);
+    }
+}
@@ -0,0 +1,414 @@
+use std::{
+    any::{type_name, Any, TypeId},
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use thiserror::Error;
+
+use super::{
+    entity::{Entities, Entity},
+    query::{Access, Query, QueryData, QueryFilter, StorageView},
+    storage::{AnyStorage, Component, ResourceCell, SparseSet},
+};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum EcsErrors {
+    #[error("entity {0} was despawned")]
+    DeadEntity(Entity),
+    #[error("resource {0} is owned by the engine and cannot be removed or replaced")]
+    ProtectedResource(&'static str),
+}
+
+// Components added together, a tuple of up to eight. Tuples are components
+// too, so a single one is written `(Health(100),)`.
+//
+//     let player = world.spawn((Transform::IDENTITY, Health(100)));
+pub trait Bundle: Send + Sync + 'static {
+    fn insert_into(self, world: &mut World, entity: Entity);
+
+    // Inserts one bundle per entity, storage by storage
+    fn insert_batch(world: &mut World, entities: &[Entity], bundles: Vec<Self>)
+    where
+        Self: Sized,
+    {
+        for (entity, bundle) in entities.iter().zip(bundles) {
+            bundle.insert_into(world, *entity);
+        }
+    }
+}
+
+macro_rules! impl_bundle_tuple {
+    ($($component:ident $column:ident),*) => {
+        impl<$($component: Component),*> Bundle for ($($component,)*) {
+            #[allow(non_snake_case, unused_variables)]
+            fn insert_into(self, world: &mut World, entity: Entity) {
+                let ($($component,)*) = self;
+                $(world.get_storage_mut::<$component>().insert(entity, $component);)*
+            }
+
+            #[allow(non_snake_case, unused_variables, unused_mut)]
+            fn insert_batch(world: &mut World, entities: &[Entity], bundles: Vec<Self>) {
+                $(let mut $column = Vec::with_capacity(bundles.len());)*
+                for ($($component,)*) in bundles {
+                    $($column.push($component);)*
+                }
+                $(world.get_storage_mut::<$component>().insert_batch(entities, $column);)*
+            }
+        }
+    };
+}
+
+impl_bundle_tuple!();
+impl_bundle_tuple!(A a);
+impl_bundle_tuple!(A a, B b);
+impl_bundle_tuple!(A a, B b, C c);
+impl_bundle_tuple!(A a, B b, C c, D d);
+impl_bundle_tuple!(A a, B b, C c, D d, E e);
+impl_bundle_tuple!(A a, B b, C c, D d, E e, F f);
+impl_bundle_tuple!(A a, B b, C c, D d, E e, F f, G g);
+impl_bundle_tuple!(A a, B b, C c, D d, E e, F f, G g, H h);
+
+// The entities of a game and their components, one sparse set per
+// component type, and the resources, values the world has one of like Time
+#[derive(Default)]
+pub struct World {
+    entities: Entities,
+    storages: HashMap<TypeId, Box<dyn AnyStorage>>,
+    resources: HashMap<TypeId, Box<dyn AnyStorage>>,
+    protected_resources: HashSet<TypeId>,
+}
+
+impl fmt::Debug for World {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("World")
+            .field("entities", &self.entities.len())
+            .field("component_types", &self.storages.len())
+            .field("resources", &self.resources.len())
+            .finish()
+    }
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self, bundle: impl Bundle) -> Entity {
+        let entity = self.entities.alloc();
+        bundle.insert_into(self, entity);
+        entity
+    }
+
+    // Spawns `count` copies of the prefab with the components `overrides`
+    // gives for each one. Every storage grows once and takes the whole
+    // batch in one go, so its components end up next to each other.
+    //
+    //     let bullets = world.spawn_batch((Bullet, Velocity(0.0, 9.0)), 500, |i| {
+    //         (Position(i as f32, 0.0),)
+    //     });
+    pub fn spawn_batch<P: Bundle + Clone, O: Bundle>(
+        &mut self,
+        prefab: P,
+        count: usize,
+        mut overrides: impl FnMut(usize) -> O,
+    ) -> Vec<Entity> {
+        self.entities.reserve(count);
+        let entities: Vec<Entity> = (0..count).map(|_| self.entities.alloc()).collect();
+        let prefabs = vec![prefab; count];
+        P::insert_batch(self, &entities, prefabs);
+        O::insert_batch(self, &entities, (0..count).map(&mut overrides).collect());
+        entities
+    }
+
+    // Removes the entity with all its components, false if it was gone
+    // already
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.entities.free(entity) {
+            return false;
+        }
+        for storage in self.storages.values_mut() {
+            storage.remove_entity(entity);
+        }
+        true
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.is_alive(entity)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn get_entities(&self) -> &Entities {
+        &self.entities
+    }
+
+    // Adds components to the entity, replacing the ones of the same type
+    pub fn insert(&mut self, entity: Entity, bundle: impl Bundle) -> Result<(), EcsErrors> {
+        if !self.is_alive(entity) {
+            return Err(EcsErrors::DeadEntity(entity));
+        }
+        bundle.insert_into(self, entity);
+        Ok(())
+    }
+
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        self.storages
+            .get_mut(&TypeId::of::<T>())?
+            .as_any_mut()
+            .downcast_mut::<SparseSet<T>>()?
+            .remove(entity)
+    }
+
+    pub fn has<T: Component>(&self, entity: Entity) -> bool {
+        self.get_storage::<T>()
+            .is_some_and(|storage| storage.contains(entity))
+    }
+
+    pub fn get<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.get_storage::<T>()?.get(entity)
+    }
+
+    pub fn get_mut<T: Component>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.storages
+            .get_mut(&TypeId::of::<T>())?
+            .as_any_mut()
+            .downcast_mut::<SparseSet<T>>()?
+            .get_mut(entity)
+    }
+
+    // Iterates the entities having the components of `Q`, see Query
+    //
+    //     for (entity, health) in world.query::<(Entity, &mut Health)>().iter_mut() {}
+    pub fn query<Q: QueryData>(&mut self) -> Query<'_, Q> {
+        self.query_filtered::<Q, ()>()
+    }
+
+    // A query narrowed down by `F`, e.g. `Without<Frozen>`
+    pub fn query_filtered<Q: QueryData, F: QueryFilter>(&mut self) -> Query<'_, Q, F> {
+        let access = Query::<Q, F>::get_access();
+        Query::new(StorageView::new(
+            &self.entities,
+            self.storages.iter_mut(),
+            &access,
+        ))
+    }
+
+    // The storages of each access, for systems running side by side
+    pub(crate) fn get_views(&mut self, accesses: &[Access]) -> Vec<StorageView<'_>> {
+        let storages = self.storages.iter_mut().chain(self.resources.iter_mut());
+        StorageView::split_all(&self.entities, storages, accesses)
+    }
+
+    // Returns the resource of the type the world had, protected resources
+    // are not replaced
+    pub fn insert_resource<T: Component>(&mut self, resource: T) -> Result<Option<T>, EcsErrors> {
+        let old = self.remove_resource::<T>()?;
+        self.resources.insert(
+            TypeId::of::<ResourceCell<T>>(),
+            Box::new(ResourceCell(resource)),
+        );
+        Ok(old)
+    }
+
+    pub fn remove_resource<T: Component>(&mut self) -> Result<Option<T>, EcsErrors> {
+        let id = TypeId::of::<ResourceCell<T>>();
+        if self.protected_resources.contains(&id) && self.resources.contains_key(&id) {
+            return Err(EcsErrors::ProtectedResource(type_name::<T>()));
+        }
+        let Some(cell) = self.resources.remove(&id) else {
+            return Ok(None);
+        };
+        match (cell as Box<dyn Any>).downcast::<ResourceCell<T>>() {
+            Ok(cell) => Ok(Some(cell.0)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    // Keeps the resource in the world for good, like the engine's Time and
+    // Input, it can still be changed through get_resource_mut
+    pub fn protect_resource<T: Component>(&mut self) {
+        self.protected_resources
+            .insert(TypeId::of::<ResourceCell<T>>());
+    }
+
+    pub fn is_resource_protected<T: Component>(&self) -> bool {
+        self.protected_resources
+            .contains(&TypeId::of::<ResourceCell<T>>())
+    }
+
+    pub fn has_resource<T: Component>(&self) -> bool {
+        self.resources
+            .contains_key(&TypeId::of::<ResourceCell<T>>())
+    }
+
+    pub fn get_resource<T: Component>(&self) -> Option<&T> {
+        self.resources
+            .get(&TypeId::of::<ResourceCell<T>>())?
+            .as_any()
+            .downcast_ref::<ResourceCell<T>>()
+            .map(|cell| &cell.0)
+    }
+
+    pub fn get_resource_mut<T: Component>(&mut self) -> Option<&mut T> {
+        self.resources
+            .get_mut(&TypeId::of::<ResourceCell<T>>())?
+            .as_any_mut()
+            .downcast_mut::<ResourceCell<T>>()
+            .map(|cell| &mut cell.0)
+    }
+
+    // None until a component of the type was inserted
+    pub fn get_storage<T: Component>(&self) -> Option<&SparseSet<T>> {
+        self.storages
+            .get(&TypeId::of::<T>())?
+            .as_any()
+            .downcast_ref::<SparseSet<T>>()
+    }
+
+    fn get_storage_mut<T: Component>(&mut self) -> &mut SparseSet<T> {
+        self.storages
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(SparseSet::<T>::new()))
+            .as_any_mut()
+            .downcast_mut::<SparseSet<T>>()
+            .unwrap_or_else(|| panic!("storage of {} has another type", type_name::<T>()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ecs::query::{With, Without},
+        event_system::event::EntityId,
+    };
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Position(f32, f32);
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Health(u32);
+
+    #[test]
+    fn test_spawn_despawn_and_recycle() {
+        let mut world = World::new();
+        let player = world.spawn((Position(1.0, 2.0), Health(100)));
+        let rock = world.spawn((Position(5.0, 0.0),));
+        assert_eq!(world.len(), 2);
+        assert_eq!(world.get::<Position>(player), Some(&Position(1.0, 2.0)));
+        assert!(!world.has::<Health>(rock));
+
+        world.get_mut::<Health>(player).unwrap().0 -= 30;
+        assert_eq!(world.get::<Health>(player), Some(&Health(70)));
+        world.insert(rock, (Health(10),)).unwrap();
+        assert_eq!(world.remove::<Health>(rock), Some(Health(10)));
+
+        // the index is reused, the old handle stays dead
+        assert!(world.despawn(player));
+        assert!(!world.despawn(player));
+        let enemy = world.spawn((Health(5),));
+        assert_eq!(enemy.get_index(), player.get_index());
+        assert_ne!(enemy, player);
+        assert_eq!(world.get::<Health>(player), None);
+        assert_eq!(world.get::<Position>(enemy), None);
+        assert_eq!(
+            world.insert(player, (Health(1),)),
+            Err(EcsErrors::DeadEntity(player))
+        );
+        assert_eq!(world.get_storage::<Position>().unwrap().len(), 1);
+        assert_eq!(Entity::from_id(EntityId::from(enemy)), enemy);
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Velocity(f32, f32);
+
+    struct Frozen;
+
+    #[test]
+    fn test_queries_and_filters() {
+        let mut world = World::new();
+        let moving = world.spawn((Position(0.0, 0.0), Velocity(1.0, 2.0)));
+        let frozen = world.spawn((Position(0.0, 0.0), Velocity(5.0, 5.0), Frozen));
+        let still = world.spawn((Position(3.0, 3.0), Health(10)));
+
+        let mut query = world.query_filtered::<(&Velocity, &mut Position), Without<Frozen>>();
+        for (velocity, position) in &mut query {
+            position.0 += velocity.0;
+            position.1 += velocity.1;
+        }
+        assert_eq!(query.count(), 1);
+        assert!(query.get_mut(frozen).is_none());
+        assert_eq!(query.get_mut(moving).unwrap().1, &Position(1.0, 2.0));
+        assert_eq!(world.get::<Position>(frozen), Some(&Position(0.0, 0.0)));
+
+        let mut query = world.query::<(Entity, &Position, Option<&mut Health>)>();
+        let mut found: Vec<(Entity, Option<u32>)> = query
+            .iter_mut()
+            .map(|(entity, _, health)| (entity, health.map(|health| health.0)))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![(moving, None), (frozen, None), (still, Some(10))]
+        );
+        assert_eq!(world.query_filtered::<Entity, With<Frozen>>().count(), 1);
+        assert_eq!(world.query::<&Velocity>().count(), 2);
+
+        // filters on what the query writes only look at who has it
+        let access = Query::<&mut Health, With<Health>>::get_access();
+        assert!(!access.is_read(TypeId::of::<Health>()));
+        let mut query = world.query_filtered::<&mut Health, With<Health>>();
+        for health in &mut query {
+            health.0 += 1;
+        }
+        assert_eq!(query.get_mut(still), Some(&mut Health(11)));
+        assert_eq!(
+            world
+                .query_filtered::<&mut Velocity, Without<Velocity>>()
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_spawn_batch_applies_overrides() {
+        let mut world = World::new();
+        let old = world.spawn((Health(1),));
+        world.despawn(old);
+
+        let spawned = world.spawn_batch((Health(100), Velocity(0.0, 1.0)), 4, |index| {
+            (Position(index as f32, 0.0), Velocity(0.0, index as f32))
+        });
+        assert_eq!(spawned.len(), 4);
+        assert_eq!(world.len(), 4);
+        // the despawned index is recycled first
+        assert_eq!(spawned[0].get_index(), old.get_index());
+        for (index, entity) in spawned.iter().enumerate() {
+            assert_eq!(world.get::<Health>(*entity), Some(&Health(100)));
+            assert_eq!(
+                world.get::<Position>(*entity),
+                Some(&Position(index as f32, 0.0))
+            );
+            assert_eq!(
+                world.get::<Velocity>(*entity),
+                Some(&Velocity(0.0, index as f32))
+            );
+        }
+        assert_eq!(world.get_storage::<Velocity>().unwrap().len(), 4);
+        assert!(world.spawn_batch((Health(1),), 0, |_| ()).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "more than once")]
+    fn test_conflicting_query_panics() {
+        let mut world = World::new();
+        world.query::<(&mut Position, &Position)>();
+    }
+}
@@ -0,0 +1,66 @@
+use std::any::Any;
+
+use super::engine_events::EngineEvent;
+use crate::{
+    animation::{animator::AnimatorId, sprite::SpritePlayerId},
+    event_system::event::{DynamicStore, Event},
+};
+
+// A clip marker passed during playback, e.g. a footstep to play a sound
+// or spawn dust at
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationMarker {
+    pub animator: AnimatorId,
+    pub clip: String,
+    pub name: String,
+}
+
+// A sprite clip that does not loop reached its last frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnimationFinished {
+    pub player: SpritePlayerId,
+    pub clip: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnimationEvents {
+    Marker(AnimationMarker),
+    Finished(AnimationFinished),
+}
+
+impl EngineEvent for AnimationEvents {
+    fn get_category(&self) -> super::engine_events::EngineEventCategory {
+        super::engine_events::EngineEventCategory::Animation
+    }
+
+    fn get_parent_category(&self) -> Option<super::engine_events::EngineEventCategory> {
+        None
+    }
+
+    fn has_event(name: String) -> bool {
+        let n: &str = &name;
+        matches!(n, "Marker" | "AnimationFinished")
+    }
+}
+
+impl Event for AnimationEvents {
+    fn get_name(&self) -> String {
+        match self {
+            Self::Marker(_) => "Marker".to_string(),
+            Self::Finished(_) => "AnimationFinished".to_string(),
+        }
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        match self {
+            Self::Marker(marker) => {
+                let wrapped = Box::new(marker.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::Finished(finished) => {
+                let wrapped = Box::new(finished.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+        }
+    }
+}
@@ -2,13 +2,19 @@ use std::any::Any;
 
 use super::engine_events::EngineEvent;
 use crate::{
-    core::runner::exit_handlers::ExitReason,
+    core::{config::EngineConfig, crash::CrashInfo, runner::exit_handlers::ExitReason},
     event_system::event::{DynamicStore, Event},
 };
 
 #[derive(Debug)]
 pub enum ApplicationEvents {
     Exit(ExitReason),
+    // sent one frame before the shutdown, e.g. for autosave prompts
+    WillExit(ExitReason),
+    Paused,
+    Resumed,
+    Crash(CrashInfo),
+    ConfigReloaded(EngineConfig),
     ExampleEvent,
     ExampleEventWithData(i128, i128),
 }
@@ -19,14 +25,22 @@ impl EngineEvent for ApplicationEvents {
     }
 
     fn get_parent_category(&self) -> Option<super::engine_events::EngineEventCategory> {
-        match self {
-            _ => None,
-        }
+        None
     }
 
     fn has_event(name: String) -> bool {
         let n: &str = &name;
-        matches!(n, "ExampleEvent" | "ExampleEventWithData" | "Exit")
+        matches!(
+            n,
+            "ExampleEvent"
+                | "ExampleEventWithData"
+                | "Exit"
+                | "WillExit"
+                | "Paused"
+                | "Resumed"
+                | "Crash"
+                | "ConfigReloaded"
+        )
     }
 }
 
@@ -36,6 +50,11 @@ impl Event for ApplicationEvents {
             Self::ExampleEvent => "ExampleEvent".to_string(),
             Self::ExampleEventWithData(_, _) => "ExampleEventWithData".to_string(),
             Self::Exit(_) => "Exit".to_string(),
+            Self::WillExit(_) => "WillExit".to_string(),
+            Self::Paused => "Paused".to_string(),
+            Self::Resumed => "Resumed".to_string(),
+            Self::Crash(_) => "Crash".to_string(),
+            Self::ConfigReloaded(_) => "ConfigReloaded".to_string(),
         }
     }
 
@@ -46,11 +65,19 @@ impl Event for ApplicationEvents {
                 let wrapped = coords as Box<dyn Any>;
                 Some(DynamicStore::new(wrapped))
             }
-            Self::Exit(exit) => {
+            Self::Exit(exit) | Self::WillExit(exit) => {
                 let exit_enum = Box::new(exit.clone());
                 let wrapped = exit_enum as Box<dyn Any>;
                 Some(DynamicStore::new(wrapped))
             }
+            Self::Crash(crash) => {
+                let wrapped = Box::new(crash.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::ConfigReloaded(config) => {
+                let wrapped = Box::new(config.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
             _ => None,
         }
     }
@@ -0,0 +1,48 @@
+use std::any::Any;
+
+use super::engine_events::EngineEvent;
+use crate::event_system::event::{DynamicStore, Event};
+
+// Position of a sync point inside a track. `sample` is the exact sample the
+// beat/marker falls on, not the sample the engine happened to notice it at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncPoint {
+    pub track: String,
+    pub sample: u64,
+    pub seconds: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioEvents {
+    BeatReached(SyncPoint, u64),
+    MarkerReached(SyncPoint, String),
+}
+
+impl EngineEvent for AudioEvents {
+    fn get_category(&self) -> super::engine_events::EngineEventCategory {
+        super::engine_events::EngineEventCategory::Audio
+    }
+
+    fn get_parent_category(&self) -> Option<super::engine_events::EngineEventCategory> {
+        None
+    }
+
+    fn has_event(name: String) -> bool {
+        let n: &str = &name;
+        matches!(n, "BeatReached" | "MarkerReached")
+    }
+}
+
+impl Event for AudioEvents {
+    fn get_name(&self) -> String {
+        match self {
+            Self::BeatReached(_, _) => "BeatReached".to_string(),
+            Self::MarkerReached(_, _) => "MarkerReached".to_string(),
+        }
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        let wrapped = Box::new(self.clone()) as Box<dyn Any>;
+        Some(DynamicStore::new(wrapped))
+    }
+}
@@ -6,6 +6,17 @@ pub enum EngineEventCategory {
     Input,
     Keyboard,
     Mouse,
+    Gamepad,
+    Audio,
+    World,
+    Timer,
+    Renderer,
+    Touch,
+    Gesture,
+    Animation,
+    Hover,
+    Shortcut,
+    Save,
 }
 
 pub trait EngineEvent: Event {
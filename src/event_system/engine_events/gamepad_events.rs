@@ -0,0 +1,102 @@
+use std::any::Any;
+
+use super::engine_events::{EngineEvent, EngineEventCategory};
+use crate::event_system::event::{DynamicStore, Event};
+
+// Stays the same while the pad is connected, a reconnected pad may get a new
+// one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GamepadId(pub u32);
+
+// Sticks are in [-1, 1] with positive y up, triggers in [0, 1]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+// Named by position so the layout does not depend on the vendor, South is A
+// on xbox and cross on playstation pads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftShoulder,
+    RightShoulder,
+    LeftStick,
+    RightStick,
+    Select,
+    Start,
+    Guide,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    Other(u16),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GamepadEvents {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+    ButtonPressed(GamepadId, GamepadButton),
+    ButtonReleased(GamepadId, GamepadButton),
+    AxisMoved(GamepadId, GamepadAxis, f32),
+}
+
+impl Event for GamepadEvents {
+    fn get_name(&self) -> String {
+        match self {
+            Self::Connected(_) => "GamepadConnected".to_string(),
+            Self::Disconnected(_) => "GamepadDisconnected".to_string(),
+            Self::ButtonPressed(..) => "GamepadButtonPressed".to_string(),
+            Self::ButtonReleased(..) => "GamepadButtonReleased".to_string(),
+            Self::AxisMoved(..) => "GamepadAxisMoved".to_string(),
+        }
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        match self {
+            Self::Connected(gamepad) | Self::Disconnected(gamepad) => {
+                let wrapped = Box::new(*gamepad) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::ButtonPressed(gamepad, button) | Self::ButtonReleased(gamepad, button) => {
+                let wrapped = Box::new((*gamepad, *button)) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::AxisMoved(gamepad, axis, value) => {
+                let wrapped = Box::new((*gamepad, *axis, *value)) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+        }
+    }
+}
+
+impl EngineEvent for GamepadEvents {
+    fn get_category(&self) -> EngineEventCategory {
+        EngineEventCategory::Gamepad
+    }
+
+    fn get_parent_category(&self) -> Option<EngineEventCategory> {
+        Some(EngineEventCategory::Input)
+    }
+
+    fn has_event(name: String) -> bool {
+        let n: &str = &name;
+        matches!(
+            n,
+            "GamepadConnected"
+                | "GamepadDisconnected"
+                | "GamepadButtonPressed"
+                | "GamepadButtonReleased"
+                | "GamepadAxisMoved"
+        )
+    }
+}
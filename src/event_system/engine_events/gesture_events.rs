@@ -0,0 +1,89 @@
+use std::any::Any;
+
+use super::engine_events::{EngineEvent, EngineEventCategory};
+use crate::{
+    event_system::event::{DynamicStore, Event},
+    math::vector::Vec2,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwipeDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PinchGesture {
+    pub center: Vec2,
+    // distance between the fingers relative to when the pinch started
+    pub scale: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanGesture {
+    pub position: Vec2,
+    pub delta: Vec2,
+    // pixels per second
+    pub velocity: Vec2,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwipeGesture {
+    pub direction: SwipeDirection,
+    pub velocity: Vec2,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GestureEvents {
+    Tap(Vec2),
+    DoubleTap(Vec2),
+    LongPress(Vec2),
+    Pinch(PinchGesture),
+    Pan(PanGesture),
+    Swipe(SwipeGesture),
+}
+
+impl EngineEvent for GestureEvents {
+    fn get_category(&self) -> EngineEventCategory {
+        EngineEventCategory::Gesture
+    }
+
+    fn get_parent_category(&self) -> Option<EngineEventCategory> {
+        Some(EngineEventCategory::Input)
+    }
+
+    fn has_event(name: String) -> bool {
+        let n: &str = &name;
+        matches!(
+            n,
+            "Tap" | "DoubleTap" | "LongPress" | "Pinch" | "Pan" | "Swipe"
+        )
+    }
+}
+
+impl Event for GestureEvents {
+    fn get_name(&self) -> String {
+        match self {
+            Self::Tap(_) => "Tap".to_string(),
+            Self::DoubleTap(_) => "DoubleTap".to_string(),
+            Self::LongPress(_) => "LongPress".to_string(),
+            Self::Pinch(_) => "Pinch".to_string(),
+            Self::Pan(_) => "Pan".to_string(),
+            Self::Swipe(_) => "Swipe".to_string(),
+        }
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        let wrapped = match self {
+            Self::Tap(position) | Self::DoubleTap(position) | Self::LongPress(position) => {
+                Box::new(*position) as Box<dyn Any>
+            }
+            Self::Pinch(pinch) => Box::new(pinch.clone()) as Box<dyn Any>,
+            Self::Pan(pan) => Box::new(pan.clone()) as Box<dyn Any>,
+            Self::Swipe(swipe) => Box::new(swipe.clone()) as Box<dyn Any>,
+        };
+        Some(DynamicStore::new(wrapped))
+    }
+}
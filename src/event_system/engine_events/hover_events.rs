@@ -0,0 +1,63 @@
+use std::any::Any;
+
+use super::engine_events::{EngineEvent, EngineEventCategory};
+use crate::{
+    event_system::event::{DynamicStore, EntityId, Event},
+    math::vector::Vec2,
+};
+
+// A tooltip the hover service decided to show, the UI draws it at `position`
+// in window pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tooltip {
+    pub target: EntityId,
+    pub text: String,
+    pub position: Vec2,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HoverEvents {
+    HoverEntered(EntityId),
+    HoverExited(EntityId),
+    TooltipShown(Tooltip),
+    TooltipHidden(EntityId),
+}
+
+impl EngineEvent for HoverEvents {
+    fn get_category(&self) -> EngineEventCategory {
+        EngineEventCategory::Hover
+    }
+
+    fn get_parent_category(&self) -> Option<EngineEventCategory> {
+        Some(EngineEventCategory::Input)
+    }
+
+    fn has_event(name: String) -> bool {
+        let n: &str = &name;
+        matches!(
+            n,
+            "HoverEntered" | "HoverExited" | "TooltipShown" | "TooltipHidden"
+        )
+    }
+}
+
+impl Event for HoverEvents {
+    fn get_name(&self) -> String {
+        match self {
+            Self::HoverEntered(_) => "HoverEntered".to_string(),
+            Self::HoverExited(_) => "HoverExited".to_string(),
+            Self::TooltipShown(_) => "TooltipShown".to_string(),
+            Self::TooltipHidden(_) => "TooltipHidden".to_string(),
+        }
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        let wrapped = match self {
+            Self::HoverEntered(target)
+            | Self::HoverExited(target)
+            | Self::TooltipHidden(target) => Box::new(*target) as Box<dyn Any>,
+            Self::TooltipShown(tooltip) => Box::new(tooltip.clone()) as Box<dyn Any>,
+        };
+        Some(DynamicStore::new(wrapped))
+    }
+}
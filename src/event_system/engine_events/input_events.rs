@@ -1,19 +1,47 @@
-use crate::event_system::event::Event;
+use std::any::Any;
+
+use crate::event_system::event::{DynamicStore, Event};
 
 use super::engine_events::EngineEvent;
 
-#[derive(Debug)]
-pub enum InputEvent {}
+// Logical actions, the payload is the action name
+#[derive(Debug, Clone, PartialEq)]
+pub enum InputEvent {
+    ActionStarted(String),
+    ActionEnded(String),
+    // sent while an action is held, after the repeat delay
+    ActionRepeated(String),
+    // one switch scanning moved its highlight to this action
+    ScanFocused(String),
+    // a logical axis moved, the value already went through its axis settings
+    AxisChanged(String, f32),
+}
 
 impl Event for InputEvent {
     fn get_name(&self) -> String {
         match self {
-            _ => "InputEvent".to_string(),
+            Self::ActionStarted(_) => "ActionStarted".to_string(),
+            Self::ActionEnded(_) => "ActionEnded".to_string(),
+            Self::ActionRepeated(_) => "ActionRepeated".to_string(),
+            Self::ScanFocused(_) => "ScanFocused".to_string(),
+            Self::AxisChanged(..) => "AxisChanged".to_string(),
         }
     }
 
-    fn get_data(&self) -> Option<crate::event_system::event::DynamicStore> {
-        None
+    fn get_data(&self) -> Option<DynamicStore> {
+        match self {
+            Self::ActionStarted(action)
+            | Self::ActionEnded(action)
+            | Self::ActionRepeated(action)
+            | Self::ScanFocused(action) => {
+                let wrapped = Box::new(action.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::AxisChanged(axis, value) => {
+                let wrapped = Box::new((axis.clone(), *value)) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+        }
     }
 }
 
@@ -23,14 +51,14 @@ impl EngineEvent for InputEvent {
     }
 
     fn get_parent_category(&self) -> Option<super::engine_events::EngineEventCategory> {
-        match self {
-            _ => None,
-        }
+        None
     }
 
     fn has_event(name: String) -> bool {
-        match name {
-            _ => false,
-        }
+        let n: &str = &name;
+        matches!(
+            n,
+            "ActionStarted" | "ActionEnded" | "ActionRepeated" | "ScanFocused" | "AxisChanged"
+        )
     }
 }
@@ -1,36 +1,92 @@
-use crate::event_system::event::Event;
+use std::any::Any;
 
-use super::engine_events::EngineEvent;
+use super::engine_events::{EngineEvent, EngineEventCategory};
+use crate::{
+    core::key_code::KeyCode,
+    event_system::event::{DynamicStore, Event},
+};
 
-#[derive(Debug)]
-pub enum KeyboardEvent {}
+// Text the IME is still composing, shown at the text cursor until it is
+// committed. `cursor` is the selected byte range in `text`, None hides it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImePreedit {
+    pub text: String,
+    pub cursor: Option<(usize, usize)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum KeyboardEvent {
+    // `repeat` is set for the presses the OS generates while the key is held
+    KeyPressed { key: KeyCode, repeat: bool },
+    KeyReleased(KeyCode),
+    // text input, already layout and modifier aware
+    CharTyped(char),
+    // the text of the clipboard when the paste shortcut was pressed
+    ClipboardPasted(String),
+    // Only sent while text input is enabled on the window, for text fields
+    // and chat boxes. Typed or committed text, a whole string at a time.
+    TextInput(String),
+    // the composition changed, an empty text ends it
+    ImePreedit(ImePreedit),
+    // the IME finished the composition, a TextInput with it follows
+    ImeCommit(String),
+}
 
 impl Event for KeyboardEvent {
     fn get_name(&self) -> String {
         match self {
-            _ => "KeyboardEvent".to_string(),
+            Self::KeyPressed { .. } => "KeyPressed".to_string(),
+            Self::KeyReleased(_) => "KeyReleased".to_string(),
+            Self::CharTyped(_) => "CharTyped".to_string(),
+            Self::ClipboardPasted(_) => "ClipboardPasted".to_string(),
+            Self::TextInput(_) => "TextInput".to_string(),
+            Self::ImePreedit(_) => "ImePreedit".to_string(),
+            Self::ImeCommit(_) => "ImeCommit".to_string(),
         }
     }
 
-    fn get_data(&self) -> Option<crate::event_system::event::DynamicStore> {
-        None
+    fn get_data(&self) -> Option<DynamicStore> {
+        match self {
+            Self::KeyPressed { key, .. } | Self::KeyReleased(key) => {
+                let wrapped = Box::new(*key) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::CharTyped(character) => {
+                let wrapped = Box::new(*character) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::ClipboardPasted(text) | Self::TextInput(text) | Self::ImeCommit(text) => {
+                let wrapped = Box::new(text.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::ImePreedit(preedit) => {
+                let wrapped = Box::new(preedit.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+        }
     }
 }
 
 impl EngineEvent for KeyboardEvent {
-    fn get_category(&self) -> super::engine_events::EngineEventCategory {
-        super::engine_events::EngineEventCategory::Keyboard
+    fn get_category(&self) -> EngineEventCategory {
+        EngineEventCategory::Keyboard
     }
 
-    fn get_parent_category(&self) -> Option<super::engine_events::EngineEventCategory> {
-        match self {
-            _ => None,
-        }
+    fn get_parent_category(&self) -> Option<EngineEventCategory> {
+        Some(EngineEventCategory::Input)
     }
 
     fn has_event(name: String) -> bool {
-        match name {
-            _ => false,
-        }
+        let n: &str = &name;
+        matches!(
+            n,
+            "KeyPressed"
+                | "KeyReleased"
+                | "CharTyped"
+                | "ClipboardPasted"
+                | "TextInput"
+                | "ImePreedit"
+                | "ImeCommit"
+        )
     }
 }
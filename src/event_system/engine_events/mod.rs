@@ -1,6 +1,18 @@
+pub mod animation_events;
 pub mod application_events;
+pub mod audio_events;
+#[allow(clippy::module_inception)]
 pub mod engine_events;
+pub mod gamepad_events;
+pub mod gesture_events;
+pub mod hover_events;
 pub mod input_events;
 pub mod keyboard_events;
 pub mod mouse_events;
+pub mod renderer_events;
+pub mod save_events;
+pub mod shortcut_events;
+pub mod timer_events;
+pub mod touch_events;
 pub mod window_events;
+pub mod world_events;
@@ -1,36 +1,80 @@
-use crate::event_system::event::Event;
+use std::any::Any;
 
-use super::engine_events::EngineEvent;
+use super::engine_events::{EngineEvent, EngineEventCategory};
+use crate::{
+    core::window::dpi::CursorPosition,
+    event_system::event::{DynamicStore, Event},
+    math::vector::Vec2,
+};
 
-#[derive(Debug)]
-pub enum MouseEvents {}
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Back,
+    Forward,
+    Other(u16),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MouseEvents {
+    Moved(CursorPosition),
+    ButtonPressed(MouseButton),
+    ButtonReleased(MouseButton),
+    // in lines, positive y scrolls up
+    Scrolled(Vec2),
+    // unaccelerated device motion in relative mouse mode
+    RawMotion(Vec2),
+}
 
 impl Event for MouseEvents {
     fn get_name(&self) -> String {
         match self {
-            _ => "MouseEvents".to_string(),
+            Self::Moved(_) => "MouseMoved".to_string(),
+            Self::ButtonPressed(_) => "MouseButtonPressed".to_string(),
+            Self::ButtonReleased(_) => "MouseButtonReleased".to_string(),
+            Self::Scrolled(_) => "MouseScrolled".to_string(),
+            Self::RawMotion(_) => "MouseRawMotion".to_string(),
         }
     }
 
-    fn get_data(&self) -> Option<crate::event_system::event::DynamicStore> {
-        None
+    fn get_data(&self) -> Option<DynamicStore> {
+        match self {
+            Self::Moved(position) => {
+                let wrapped = Box::new(*position) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::Scrolled(delta) | Self::RawMotion(delta) => {
+                let wrapped = Box::new(*delta) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::ButtonPressed(button) | Self::ButtonReleased(button) => {
+                let wrapped = Box::new(*button) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+        }
     }
 }
 
 impl EngineEvent for MouseEvents {
-    fn get_category(&self) -> super::engine_events::EngineEventCategory {
-        super::engine_events::EngineEventCategory::Mouse
+    fn get_category(&self) -> EngineEventCategory {
+        EngineEventCategory::Mouse
     }
 
-    fn get_parent_category(&self) -> Option<super::engine_events::EngineEventCategory> {
-        match self {
-            _ => None,
-        }
+    fn get_parent_category(&self) -> Option<EngineEventCategory> {
+        Some(EngineEventCategory::Input)
     }
 
     fn has_event(name: String) -> bool {
-        match name {
-            _ => false,
-        }
+        let n: &str = &name;
+        matches!(
+            n,
+            "MouseMoved"
+                | "MouseButtonPressed"
+                | "MouseButtonReleased"
+                | "MouseScrolled"
+                | "MouseRawMotion"
+        )
     }
 }
@@ -0,0 +1,116 @@
+use std::{any::Any, time::Duration};
+
+use super::engine_events::EngineEvent;
+use crate::{
+    event_system::event::{DynamicStore, Event},
+    renderer::{api::PresentMode, gpu_memory::GpuMemoryCategory, render_stats::RenderStat},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryBudgetExceeded {
+    pub category: GpuMemoryCategory,
+    pub used: u64,
+    pub budget: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderBudgetExceeded {
+    pub stat: RenderStat,
+    pub value: u64,
+    pub threshold: u64,
+}
+
+// Asks the color grading to fade to a LUT, None fades back to no grading
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorGradingBlend {
+    pub lut: Option<String>,
+    pub duration: Duration,
+}
+
+// How far the import of a large model file got, from 0 to 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelLoadProgress {
+    pub path: String,
+    pub progress: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum RendererEvents {
+    MemoryBudgetExceeded(MemoryBudgetExceeded),
+    RenderBudgetExceeded(RenderBudgetExceeded),
+    // name of the environment whose files changed on disk
+    EnvironmentReloaded(String),
+    // name of the shader in the ShaderLibrary that was recompiled
+    ShaderReloaded(String),
+    ColorGradingBlend(ColorGradingBlend),
+    ModelLoadProgress(ModelLoadProgress),
+    // the renderer reconfigures its swapchain with the mode
+    PresentModeChanged(PresentMode),
+}
+
+impl EngineEvent for RendererEvents {
+    fn get_category(&self) -> super::engine_events::EngineEventCategory {
+        super::engine_events::EngineEventCategory::Renderer
+    }
+
+    fn get_parent_category(&self) -> Option<super::engine_events::EngineEventCategory> {
+        None
+    }
+
+    fn has_event(name: String) -> bool {
+        let n: &str = &name;
+        matches!(
+            n,
+            "MemoryBudgetExceeded"
+                | "RenderBudgetExceeded"
+                | "EnvironmentReloaded"
+                | "ShaderReloaded"
+                | "ColorGradingBlend"
+                | "ModelLoadProgress"
+                | "PresentModeChanged"
+        )
+    }
+}
+
+impl Event for RendererEvents {
+    fn get_name(&self) -> String {
+        match self {
+            Self::MemoryBudgetExceeded(_) => "MemoryBudgetExceeded".to_string(),
+            Self::RenderBudgetExceeded(_) => "RenderBudgetExceeded".to_string(),
+            Self::EnvironmentReloaded(_) => "EnvironmentReloaded".to_string(),
+            Self::ShaderReloaded(_) => "ShaderReloaded".to_string(),
+            Self::ColorGradingBlend(_) => "ColorGradingBlend".to_string(),
+            Self::ModelLoadProgress(_) => "ModelLoadProgress".to_string(),
+            Self::PresentModeChanged(_) => "PresentModeChanged".to_string(),
+        }
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        match self {
+            Self::MemoryBudgetExceeded(exceeded) => {
+                let wrapped = Box::new(exceeded.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::RenderBudgetExceeded(exceeded) => {
+                let wrapped = Box::new(exceeded.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::ColorGradingBlend(blend) => {
+                let wrapped = Box::new(blend.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::ModelLoadProgress(progress) => {
+                let wrapped = Box::new(progress.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::PresentModeChanged(mode) => {
+                let wrapped = Box::new(*mode) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::EnvironmentReloaded(name) | Self::ShaderReloaded(name) => {
+                let wrapped = Box::new(name.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+        }
+    }
+}
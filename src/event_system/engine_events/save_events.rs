@@ -0,0 +1,66 @@
+use std::any::Any;
+
+use super::engine_events::{EngineEvent, EngineEventCategory};
+use crate::event_system::event::{DynamicStore, Event};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveVersion {
+    pub data: Vec<u8>,
+    // unix seconds
+    pub modified: u64,
+}
+
+// Both copies of a slot changed since the last sync, the game picks one or
+// merges them and hands the result to `CloudSync::resolve`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SaveConflict {
+    pub slot: String,
+    pub local: SaveVersion,
+    pub remote: SaveVersion,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveEvents {
+    SaveConflict(SaveConflict),
+    // the payload is the slot name
+    SaveUploaded(String),
+    SaveDownloaded(String),
+}
+
+impl Event for SaveEvents {
+    fn get_name(&self) -> String {
+        match self {
+            Self::SaveConflict(_) => "SaveConflict".to_string(),
+            Self::SaveUploaded(_) => "SaveUploaded".to_string(),
+            Self::SaveDownloaded(_) => "SaveDownloaded".to_string(),
+        }
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        match self {
+            Self::SaveConflict(conflict) => {
+                let wrapped = Box::new(conflict.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::SaveUploaded(slot) | Self::SaveDownloaded(slot) => {
+                let wrapped = Box::new(slot.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+        }
+    }
+}
+
+impl EngineEvent for SaveEvents {
+    fn get_category(&self) -> EngineEventCategory {
+        EngineEventCategory::Save
+    }
+
+    fn get_parent_category(&self) -> Option<EngineEventCategory> {
+        None
+    }
+
+    fn has_event(name: String) -> bool {
+        let n: &str = &name;
+        matches!(n, "SaveConflict" | "SaveUploaded" | "SaveDownloaded")
+    }
+}
@@ -0,0 +1,50 @@
+use std::any::Any;
+
+use super::engine_events::{EngineEvent, EngineEventCategory};
+use crate::event_system::event::{DynamicStore, Event};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShortcutEvents {
+    // the payload is the name the shortcut was registered with
+    ShortcutTriggered(String),
+    // the first keys of a chord were typed, e.g. "Ctrl+K", editors show it
+    // while waiting for the rest
+    ChordPending(String),
+    // the chord timed out or a key that continues no chord was pressed
+    ChordCancelled,
+}
+
+impl Event for ShortcutEvents {
+    fn get_name(&self) -> String {
+        match self {
+            Self::ShortcutTriggered(_) => "ShortcutTriggered".to_string(),
+            Self::ChordPending(_) => "ChordPending".to_string(),
+            Self::ChordCancelled => "ChordCancelled".to_string(),
+        }
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        match self {
+            Self::ShortcutTriggered(text) | Self::ChordPending(text) => {
+                let wrapped = Box::new(text.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::ChordCancelled => None,
+        }
+    }
+}
+
+impl EngineEvent for ShortcutEvents {
+    fn get_category(&self) -> EngineEventCategory {
+        EngineEventCategory::Shortcut
+    }
+
+    fn get_parent_category(&self) -> Option<EngineEventCategory> {
+        Some(EngineEventCategory::Input)
+    }
+
+    fn has_event(name: String) -> bool {
+        let n: &str = &name;
+        matches!(n, "ShortcutTriggered" | "ChordPending" | "ChordCancelled")
+    }
+}
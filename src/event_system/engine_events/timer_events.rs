@@ -0,0 +1,65 @@
+use std::any::Any;
+
+use super::engine_events::EngineEvent;
+use crate::{
+    core::{time_slicing::SliceHandle, timer::TimerHandle},
+    event_system::event::{DynamicStore, Event},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimerFired {
+    pub handle: TimerHandle,
+    pub name: String,
+}
+
+// A time sliced task finished a run
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceCompleted {
+    pub handle: SliceHandle,
+    pub name: String,
+    // how many frames the run was spread over
+    pub frames: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimerEvents {
+    TimerFired(TimerFired),
+    SliceCompleted(SliceCompleted),
+}
+
+impl EngineEvent for TimerEvents {
+    fn get_category(&self) -> super::engine_events::EngineEventCategory {
+        super::engine_events::EngineEventCategory::Timer
+    }
+
+    fn get_parent_category(&self) -> Option<super::engine_events::EngineEventCategory> {
+        None
+    }
+
+    fn has_event(name: String) -> bool {
+        let n: &str = &name;
+        matches!(n, "TimerFired" | "SliceCompleted")
+    }
+}
+
+impl Event for TimerEvents {
+    fn get_name(&self) -> String {
+        match self {
+            Self::TimerFired(_) => "TimerFired".to_string(),
+            Self::SliceCompleted(_) => "SliceCompleted".to_string(),
+        }
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        match self {
+            Self::TimerFired(fired) => {
+                let wrapped = Box::new(fired.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::SliceCompleted(completed) => {
+                let wrapped = Box::new(completed.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+        }
+    }
+}
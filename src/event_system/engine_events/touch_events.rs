@@ -0,0 +1,60 @@
+use std::any::Any;
+
+use super::engine_events::{EngineEvent, EngineEventCategory};
+use crate::{
+    event_system::event::{DynamicStore, Event},
+    math::vector::Vec2,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchPhase {
+    Started,
+    Moved,
+    Ended,
+    Cancelled,
+}
+
+// One finger, `id` stays the same from Started until Ended/Cancelled
+#[derive(Debug, Clone, PartialEq)]
+pub struct Touch {
+    pub id: u64,
+    pub phase: TouchPhase,
+    pub position: Vec2,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TouchEvents {
+    Touch(Touch),
+}
+
+impl EngineEvent for TouchEvents {
+    fn get_category(&self) -> EngineEventCategory {
+        EngineEventCategory::Touch
+    }
+
+    fn get_parent_category(&self) -> Option<EngineEventCategory> {
+        Some(EngineEventCategory::Input)
+    }
+
+    fn has_event(name: String) -> bool {
+        let n: &str = &name;
+        matches!(n, "Touch")
+    }
+}
+
+impl Event for TouchEvents {
+    fn get_name(&self) -> String {
+        match self {
+            Self::Touch(_) => "Touch".to_string(),
+        }
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        match self {
+            Self::Touch(touch) => {
+                let wrapped = Box::new(touch.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+        }
+    }
+}
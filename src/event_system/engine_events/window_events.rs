@@ -1,35 +1,94 @@
-use crate::event_system::event::Event;
+use std::any::Any;
 
-use super::engine_events::EngineEvent;
+use super::engine_events::{EngineEvent, EngineEventCategory};
+use crate::{
+    core::window::{dpi::WindowSize, WindowMode},
+    event_system::event::{DynamicStore, Event},
+};
 
-#[derive(Debug)]
-pub enum WindowEvents {}
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeChanged {
+    pub mode: WindowMode,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Sizes are in physical pixels
+#[derive(Debug, Clone, PartialEq)]
+pub enum WindowEvents {
+    Resized(WindowSize),
+    Moved(i32, i32),
+    CloseRequested,
+    // a secondary window was closed and dropped
+    Closed,
+    FocusChanged(bool),
+    // the renderer recreates its swapchain with the new size
+    ModeChanged(ModeChanged),
+    // the window moved to a monitor with a different scale, a Resized with
+    // the new physical size follows
+    ScaleFactorChanged(f64),
+}
 
 impl EngineEvent for WindowEvents {
-    fn get_category(&self) -> super::engine_events::EngineEventCategory {
-        super::engine_events::EngineEventCategory::Window
+    fn get_category(&self) -> EngineEventCategory {
+        EngineEventCategory::Window
     }
 
-    fn get_parent_category(&self) -> Option<super::engine_events::EngineEventCategory> {
-        match self {
-            _ => None,
-        }
+    fn get_parent_category(&self) -> Option<EngineEventCategory> {
+        None
     }
+
     fn has_event(name: String) -> bool {
-        match name {
-            _ => false,
-        }
+        let n: &str = &name;
+        matches!(
+            n,
+            "Resized"
+                | "Moved"
+                | "CloseRequested"
+                | "Closed"
+                | "FocusChanged"
+                | "ModeChanged"
+                | "ScaleFactorChanged"
+        )
     }
 }
 
 impl Event for WindowEvents {
     fn get_name(&self) -> String {
         match self {
-            _ => "WindowEvents".to_string(),
+            Self::Resized(_) => "Resized".to_string(),
+            Self::Moved(_, _) => "Moved".to_string(),
+            Self::CloseRequested => "CloseRequested".to_string(),
+            Self::Closed => "Closed".to_string(),
+            Self::FocusChanged(_) => "FocusChanged".to_string(),
+            Self::ModeChanged(_) => "ModeChanged".to_string(),
+            Self::ScaleFactorChanged(_) => "ScaleFactorChanged".to_string(),
         }
     }
 
-    fn get_data(&self) -> Option<crate::event_system::event::DynamicStore> {
-        None
+    fn get_data(&self) -> Option<DynamicStore> {
+        match self {
+            Self::Resized(size) => {
+                let wrapped = Box::new(*size) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::Moved(x, y) => {
+                let wrapped = Box::new((*x, *y)) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::FocusChanged(focused) => {
+                let wrapped = Box::new(*focused) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::ModeChanged(changed) => {
+                let wrapped = Box::new(changed.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::ScaleFactorChanged(scale_factor) => {
+                let wrapped = Box::new(*scale_factor) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::CloseRequested | Self::Closed => None,
+        }
     }
 }
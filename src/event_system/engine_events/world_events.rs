@@ -0,0 +1,108 @@
+use std::any::Any;
+
+use super::engine_events::EngineEvent;
+use crate::{
+    core::spawn_director::SpawnHandle,
+    event_system::event::{DynamicStore, Event},
+    math::vector::{DVec3, Vec3},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OriginShift {
+    // subtract this from every local position to stay in sync
+    pub offset: Vec3,
+    // absolute world position of the new local origin
+    pub new_origin: DVec3,
+}
+
+// Reported by a scene preload job, progress is of the whole load from 0 to 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneLoadProgress {
+    pub scene: String,
+    pub stage: String,
+    pub progress: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneLoadFailed {
+    pub scene: String,
+    pub error: String,
+}
+
+// The SpawnDirector picked `entry` of `table` to spawn at `position`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpawnRequest {
+    pub handle: SpawnHandle,
+    pub table: String,
+    pub entry: String,
+    pub position: Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum WorldEvents {
+    OriginShifted(OriginShift),
+    SceneLoadProgress(SceneLoadProgress),
+    // the scene with this name is ready to be taken from the SceneLoader
+    SceneLoaded(String),
+    SceneLoadFailed(SceneLoadFailed),
+    SpawnRequested(SpawnRequest),
+}
+
+impl EngineEvent for WorldEvents {
+    fn get_category(&self) -> super::engine_events::EngineEventCategory {
+        super::engine_events::EngineEventCategory::World
+    }
+
+    fn get_parent_category(&self) -> Option<super::engine_events::EngineEventCategory> {
+        None
+    }
+
+    fn has_event(name: String) -> bool {
+        let n: &str = &name;
+        matches!(
+            n,
+            "OriginShifted"
+                | "SceneLoadProgress"
+                | "SceneLoaded"
+                | "SceneLoadFailed"
+                | "SpawnRequested"
+        )
+    }
+}
+
+impl Event for WorldEvents {
+    fn get_name(&self) -> String {
+        match self {
+            Self::OriginShifted(_) => "OriginShifted".to_string(),
+            Self::SceneLoadProgress(_) => "SceneLoadProgress".to_string(),
+            Self::SceneLoaded(_) => "SceneLoaded".to_string(),
+            Self::SceneLoadFailed(_) => "SceneLoadFailed".to_string(),
+            Self::SpawnRequested(_) => "SpawnRequested".to_string(),
+        }
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        match self {
+            Self::OriginShifted(shift) => {
+                let wrapped = Box::new(shift.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::SceneLoadProgress(progress) => {
+                let wrapped = Box::new(progress.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::SceneLoaded(scene) => {
+                let wrapped = Box::new(scene.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::SceneLoadFailed(failed) => {
+                let wrapped = Box::new(failed.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+            Self::SpawnRequested(request) => {
+                let wrapped = Box::new(request.clone()) as Box<dyn Any>;
+                Some(DynamicStore::new(wrapped))
+            }
+        }
+    }
+}
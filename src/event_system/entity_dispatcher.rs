@@ -0,0 +1,146 @@
+use std::{collections::HashMap, fmt::Debug};
+
+use log::info;
+
+use super::{
+    event::{EntityId, Event},
+    event_dispatcher::DispatcherCallback,
+};
+
+// Routes targeted events only to the handlers that were registered for the
+// event's target entity, so handlers don't have to filter by id themselves.
+#[derive(Default)]
+pub struct EntityDispatcher {
+    handlers: HashMap<EntityId, HashMap<String, Vec<DispatcherCallback>>>,
+}
+
+impl EntityDispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_handler(&mut self, entity: EntityId, event_name: String, cb: DispatcherCallback) {
+        info!(
+            "adding new handler for {} on entity {:?}",
+            event_name, entity
+        );
+        self.handlers
+            .entry(entity)
+            .or_default()
+            .entry(event_name)
+            .or_default()
+            .push(cb);
+    }
+
+    pub fn remove_entity(&mut self, entity: EntityId) -> bool {
+        self.handlers.remove(&entity).is_some()
+    }
+
+    pub fn has_handlers(&self, entity: EntityId) -> bool {
+        self.handlers.contains_key(&entity)
+    }
+
+    // Returns true if at least one handler was called
+    pub fn dispatch(&self, event: &dyn Event) -> bool {
+        let Some(target) = event.get_target() else {
+            return false;
+        };
+
+        let handlers = match self
+            .handlers
+            .get(&target)
+            .and_then(|events| events.get(&event.get_name()))
+        {
+            Some(handlers) if !handlers.is_empty() => handlers,
+            _ => return false,
+        };
+
+        handlers.iter().for_each(|handler| handler(event));
+        true
+    }
+}
+
+impl Debug for EntityDispatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EntityDispatcher")
+            .field("entities", &self.handlers.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    };
+
+    use crate::event_system::event::{DynamicStore, TargetedEvent};
+
+    use super::*;
+
+    #[derive(Debug)]
+    struct Damage;
+
+    impl Event for Damage {
+        fn get_name(&self) -> String {
+            "Damage".to_string()
+        }
+
+        fn get_data(&self) -> Option<DynamicStore> {
+            None
+        }
+    }
+
+    fn counting_handler(counter: &Arc<AtomicU8>) -> DispatcherCallback {
+        let counter = Arc::clone(counter);
+        Arc::new(move |_event: &dyn Event| {
+            counter.fetch_add(1, Ordering::SeqCst);
+        })
+    }
+
+    #[test]
+    fn test_targeted_event_only_reaches_target() {
+        let mut dispatcher = EntityDispatcher::new();
+        let player_hits = Arc::new(AtomicU8::new(0));
+        let enemy_hits = Arc::new(AtomicU8::new(0));
+
+        dispatcher.add_handler(
+            EntityId(1),
+            "Damage".to_string(),
+            counting_handler(&player_hits),
+        );
+        dispatcher.add_handler(
+            EntityId(2),
+            "Damage".to_string(),
+            counting_handler(&enemy_hits),
+        );
+
+        assert!(dispatcher.dispatch(&TargetedEvent::new(EntityId(2), Damage)));
+
+        assert_eq!(player_hits.load(Ordering::SeqCst), 0);
+        assert_eq!(enemy_hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_untargeted_event_is_ignored() {
+        let mut dispatcher = EntityDispatcher::new();
+        let hits = Arc::new(AtomicU8::new(0));
+        dispatcher.add_handler(EntityId(1), "Damage".to_string(), counting_handler(&hits));
+
+        assert!(!dispatcher.dispatch(&Damage));
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn test_remove_entity_handlers() {
+        let mut dispatcher = EntityDispatcher::new();
+        let hits = Arc::new(AtomicU8::new(0));
+        dispatcher.add_handler(EntityId(1), "Damage".to_string(), counting_handler(&hits));
+
+        assert!(dispatcher.remove_entity(EntityId(1)));
+        assert!(!dispatcher.has_handlers(EntityId(1)));
+        assert!(!dispatcher.dispatch(&TargetedEvent::new(EntityId(1), Damage)));
+        assert_eq!(hits.load(Ordering::SeqCst), 0);
+    }
+}
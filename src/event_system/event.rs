@@ -1,5 +1,7 @@
 use std::{any::Any, fmt::Debug};
 
+use crate::core::window::WindowId;
+
 #[derive(Debug)]
 pub struct DynamicStore {
     value: Box<dyn Any>,
@@ -15,7 +17,140 @@ impl DynamicStore {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId(pub u64);
+
+// A local player, for split screen and couch co-op. Player 0 is the first
+// one to join.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PlayerId(pub u32);
+
 pub trait Event: Debug + Send + Sync {
     fn get_name(&self) -> String;
     fn get_data(&self) -> Option<DynamicStore>;
+
+    // Events without a target are broadcast to every handler of the event
+    fn get_target(&self) -> Option<EntityId> {
+        None
+    }
+
+    // Window and input events carry the window they came from
+    fn get_window(&self) -> Option<WindowId> {
+        None
+    }
+
+    // Actions of local multiplayer games carry the player whose devices
+    // triggered them
+    fn get_player(&self) -> Option<PlayerId> {
+        None
+    }
+}
+
+// Wraps any event so that it is only routed to the handlers of one entity
+#[derive(Debug)]
+pub struct TargetedEvent<E: Event> {
+    target: EntityId,
+    event: E,
+}
+
+impl<E: Event> TargetedEvent<E> {
+    pub fn new(target: EntityId, event: E) -> Self {
+        Self { target, event }
+    }
+
+    pub fn get_event(&self) -> &E {
+        &self.event
+    }
+}
+
+impl<E: Event> Event for TargetedEvent<E> {
+    fn get_name(&self) -> String {
+        self.event.get_name()
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        self.event.get_data()
+    }
+
+    fn get_target(&self) -> Option<EntityId> {
+        Some(self.target)
+    }
+}
+
+// Tags an event with the window it came from
+#[derive(Debug)]
+pub struct WindowedEvent<E: Event> {
+    window: WindowId,
+    event: E,
+}
+
+impl<E: Event> WindowedEvent<E> {
+    pub fn new(window: WindowId, event: E) -> Self {
+        Self { window, event }
+    }
+
+    pub fn get_event(&self) -> &E {
+        &self.event
+    }
+}
+
+impl<E: Event> Event for WindowedEvent<E> {
+    fn get_name(&self) -> String {
+        self.event.get_name()
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        self.event.get_data()
+    }
+
+    fn get_target(&self) -> Option<EntityId> {
+        self.event.get_target()
+    }
+
+    fn get_window(&self) -> Option<WindowId> {
+        Some(self.window)
+    }
+
+    fn get_player(&self) -> Option<PlayerId> {
+        self.event.get_player()
+    }
+}
+
+// Tags an event with the local player it belongs to
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerEvent<E: Event> {
+    player: PlayerId,
+    event: E,
+}
+
+impl<E: Event> PlayerEvent<E> {
+    pub fn new(player: PlayerId, event: E) -> Self {
+        Self { player, event }
+    }
+
+    pub fn get_event(&self) -> &E {
+        &self.event
+    }
+}
+
+impl<E: Event> Event for PlayerEvent<E> {
+    fn get_name(&self) -> String {
+        self.event.get_name()
+    }
+
+    fn get_data(&self) -> Option<DynamicStore> {
+        self.event.get_data()
+    }
+
+    fn get_target(&self) -> Option<EntityId> {
+        self.event.get_target()
+    }
+
+    fn get_window(&self) -> Option<WindowId> {
+        self.event.get_window()
+    }
+
+    fn get_player(&self) -> Option<PlayerId> {
+        Some(self.player)
+    }
 }
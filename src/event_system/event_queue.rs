@@ -29,6 +29,7 @@ pub enum EventQueueErrors {
     EmptyQueue,
 }
 
+#[derive(Debug)]
 pub struct EventQueue {
     sender: Sender<BoxedEvent>,
     reciever: Arc<Mutex<Receiver<BoxedEvent>>>,
@@ -58,6 +59,14 @@ impl EventQueue {
         Ok(())
     }
 
+    // For events that are already boxed, e.g. kept to be emitted later
+    pub fn emit_boxed(&self, event: BoxedEvent) -> Result<(), EventQueueErrors> {
+        if let Err(e) = self.sender.send(event) {
+            return Err(EventQueueErrors::UnableToEmitToEventQueue(e));
+        }
+        Ok(())
+    }
+
     pub fn get_events(&self) -> Result<Vec<BoxedEvent>, EventQueueErrors> {
         let mut events = Vec::new();
         match self.reciever.try_lock() {
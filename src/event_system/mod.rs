@@ -1,4 +1,7 @@
 pub mod engine_events;
+pub mod entity_dispatcher;
 pub mod event;
 pub mod event_dispatcher;
 pub mod event_queue;
+pub mod replay;
+pub mod schema;
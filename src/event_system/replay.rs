@@ -0,0 +1,266 @@
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use thiserror::Error;
+
+use crate::core::net::{
+    transport::NetErrors,
+    wire::{ByteReader, WireEvent},
+};
+
+use super::schema::EventSchemas;
+
+const REPLAY_MAGIC: &[u8; 8] = b"ALOYRPL\0";
+const REPLAY_VERSION: u16 = 1;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ReplayErrors {
+    #[error("replay io error: {0}")]
+    Io(String),
+
+    #[error("not a replay file or unsupported version")]
+    InvalidHeader,
+
+    #[error("malformed replay entry: {0}")]
+    Malformed(String),
+}
+
+impl From<std::io::Error> for ReplayErrors {
+    fn from(err: std::io::Error) -> Self {
+        ReplayErrors::Io(err.to_string())
+    }
+}
+
+// One recorded event. `channel` is free for the recorder to use, e.g. to tell
+// inbound from outbound network traffic apart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayEntry {
+    pub timestamp: Duration,
+    pub channel: u8,
+    pub event: WireEvent,
+}
+
+// Replay file layout:
+//
+// header: [magic: 8 bytes][version: u16 LE]
+// entry:  [timestamp micros: u64 LE][channel: u8][len: u32 LE][wire event]
+#[derive(Debug)]
+pub struct ReplayWriter<W: Write> {
+    writer: W,
+    started: Instant,
+    entries: u64,
+}
+
+impl ReplayWriter<BufWriter<File>> {
+    pub fn create(path: impl AsRef<Path>) -> Result<Self, ReplayErrors> {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> ReplayWriter<W> {
+    pub fn new(mut writer: W) -> Result<Self, ReplayErrors> {
+        writer.write_all(REPLAY_MAGIC)?;
+        writer.write_all(&REPLAY_VERSION.to_le_bytes())?;
+        Ok(Self {
+            writer,
+            started: Instant::now(),
+            entries: 0,
+        })
+    }
+
+    pub fn get_entry_count(&self) -> u64 {
+        self.entries
+    }
+
+    pub fn record(&mut self, channel: u8, event: &WireEvent) -> Result<(), ReplayErrors> {
+        self.record_at(self.started.elapsed(), channel, event)
+    }
+
+    pub fn record_at(
+        &mut self,
+        timestamp: Duration,
+        channel: u8,
+        event: &WireEvent,
+    ) -> Result<(), ReplayErrors> {
+        let encoded = event
+            .encode()
+            .map_err(|err| ReplayErrors::Malformed(err.to_string()))?;
+        self.writer
+            .write_all(&(timestamp.as_micros() as u64).to_le_bytes())?;
+        self.writer.write_all(&[channel])?;
+        self.writer
+            .write_all(&(encoded.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&encoded)?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), ReplayErrors> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(mut self) -> Result<W, ReplayErrors> {
+        self.flush()?;
+        Ok(self.writer)
+    }
+}
+
+pub fn read_replay(mut reader: impl Read) -> Result<Vec<ReplayEntry>, ReplayErrors> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    if bytes.len() < 10 || &bytes[..8] != REPLAY_MAGIC {
+        return Err(ReplayErrors::InvalidHeader);
+    }
+    if u16::from_le_bytes([bytes[8], bytes[9]]) != REPLAY_VERSION {
+        return Err(ReplayErrors::InvalidHeader);
+    }
+
+    let mut entries = Vec::new();
+    let mut reader = ByteReader::new(&bytes[10..]);
+    while !reader.is_empty() {
+        let entry =
+            read_entry(&mut reader).map_err(|err| ReplayErrors::Malformed(err.to_string()))?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+fn read_entry(reader: &mut ByteReader) -> Result<ReplayEntry, NetErrors> {
+    let timestamp = u64::from_le_bytes(reader.take_array()?);
+    let [channel] = reader.take_array::<1>()?;
+    let len = u32::from_le_bytes(reader.take_array()?) as usize;
+    Ok(ReplayEntry {
+        timestamp: Duration::from_micros(timestamp),
+        channel,
+        event: WireEvent::decode(reader.take(len)?)?,
+    })
+}
+
+pub fn open_replay(path: impl AsRef<Path>) -> Result<Vec<ReplayEntry>, ReplayErrors> {
+    read_replay(BufReader::new(File::open(path)?))
+}
+
+// Hands recorded entries back out on the original timeline
+#[derive(Debug, Default)]
+pub struct ReplayPlayer {
+    entries: VecDeque<ReplayEntry>,
+    position: Duration,
+}
+
+impl ReplayPlayer {
+    pub fn new(mut entries: Vec<ReplayEntry>) -> Self {
+        entries.sort_by_key(|entry| entry.timestamp);
+        Self {
+            entries: entries.into(),
+            position: Duration::ZERO,
+        }
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ReplayErrors> {
+        Ok(Self::new(open_replay(path)?))
+    }
+
+    pub fn get_position(&self) -> Duration {
+        self.position
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Brings events recorded by older builds up to the current schemas,
+    // the ones that cannot be migrated are dropped
+    pub fn upgrade_events(&mut self, schemas: &EventSchemas) {
+        let entries = std::mem::take(&mut self.entries);
+        self.entries = entries
+            .into_iter()
+            .filter_map(|mut entry| match schemas.upgrade(entry.event) {
+                Ok(event) => {
+                    entry.event = event;
+                    Some(entry)
+                }
+                Err(err) => {
+                    warn!("dropping recorded event: {}", err);
+                    None
+                }
+            })
+            .collect();
+    }
+
+    // Returns every entry recorded up to the new position
+    pub fn advance(&mut self, delta: Duration) -> Vec<ReplayEntry> {
+        self.position += delta;
+        let mut due = Vec::new();
+        while self
+            .entries
+            .front()
+            .is_some_and(|entry| entry.timestamp <= self.position)
+        {
+            due.extend(self.entries.pop_front());
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_read_back() {
+        let mut writer = ReplayWriter::new(Vec::new()).unwrap();
+        writer
+            .record_at(
+                Duration::from_millis(5),
+                1,
+                &WireEvent::new("Jump", vec![1]),
+            )
+            .unwrap();
+        writer
+            .record_at(Duration::from_millis(9), 2, &WireEvent::new("Fire", vec![]))
+            .unwrap();
+        let bytes = writer.into_inner().unwrap();
+
+        let entries = read_replay(bytes.as_slice()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].timestamp, Duration::from_millis(5));
+        assert_eq!(entries[0].channel, 1);
+        assert_eq!(entries[0].event, WireEvent::new("Jump", vec![1]));
+        assert_eq!(entries[1].event.name, "Fire");
+    }
+
+    #[test]
+    fn test_invalid_header() {
+        assert_eq!(
+            read_replay(&b"not a replay"[..]),
+            Err(ReplayErrors::InvalidHeader)
+        );
+    }
+
+    #[test]
+    fn test_player_follows_timeline() {
+        let entry = |millis, name: &str| ReplayEntry {
+            timestamp: Duration::from_millis(millis),
+            channel: 0,
+            event: WireEvent::new(name, vec![]),
+        };
+        let mut player = ReplayPlayer::new(vec![entry(20, "b"), entry(10, "a")]);
+
+        assert!(player.advance(Duration::from_millis(5)).is_empty());
+        assert_eq!(player.advance(Duration::from_millis(5))[0].event.name, "a");
+        assert_eq!(player.advance(Duration::from_millis(50))[0].event.name, "b");
+        assert!(player.is_finished());
+    }
+}
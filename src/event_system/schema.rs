@@ -0,0 +1,162 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    sync::Arc,
+};
+
+use thiserror::Error;
+
+use crate::core::net::wire::WireEvent;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SchemaErrors {
+    #[error("{0} version {1} is newer than version {2} this build knows")]
+    TooNew(String, u16, u16),
+
+    #[error("no migration of {0} from version {1}")]
+    MissingMigration(String, u16),
+
+    #[error("unable to migrate {0} from version {1}: {2}")]
+    MigrationFailed(String, u16, String),
+}
+
+// Turns the payload of one version into the payload of the next
+pub type Migration = Arc<dyn Fn(Vec<u8>) -> Result<Vec<u8>, String> + Send + Sync>;
+
+#[derive(Clone, Default)]
+struct EventSchema {
+    version: u16,
+    // keyed by the version they migrate from
+    migrations: BTreeMap<u16, Migration>,
+}
+
+// The payload versions of the serialized events this build sends, and how
+// to bring the payloads of older builds up to them. Replays and peers on a
+// slightly older build keep working instead of failing to decode.
+//
+//     let mut schemas = EventSchemas::new();
+//     schemas.register("PlayerMoved", 2);
+//     // version 1 had no z
+//     schemas.add_migration("PlayerMoved", 1, |mut payload| {
+//         payload.extend_from_slice(&0f32.to_le_bytes());
+//         Ok(payload)
+//     });
+//     transport.set_schemas(schemas);
+//
+// Events that are not registered go through as they are.
+#[derive(Clone, Default)]
+pub struct EventSchemas {
+    schemas: HashMap<String, EventSchema>,
+}
+
+impl EventSchemas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // The version this build sends `name` with, starting at 1
+    pub fn register(&mut self, name: &str, version: u16) {
+        self.schemas.entry(name.to_string()).or_default().version = version.max(1);
+    }
+
+    pub fn add_migration(
+        &mut self,
+        name: &str,
+        from_version: u16,
+        migration: impl Fn(Vec<u8>) -> Result<Vec<u8>, String> + Send + Sync + 'static,
+    ) {
+        let schema = self.schemas.entry(name.to_string()).or_default();
+        schema.version = schema.version.max(from_version + 1);
+        schema.migrations.insert(from_version, Arc::new(migration));
+    }
+
+    pub fn get_version(&self, name: &str) -> Option<u16> {
+        self.schemas.get(name).map(|schema| schema.version)
+    }
+
+    // Tags an outgoing event with the version of its payload
+    pub fn stamp(&self, event: WireEvent) -> WireEvent {
+        let name = event.get_base_name().to_string();
+        match self.get_version(&name) {
+            Some(version) => WireEvent::versioned(&name, version, event.payload),
+            None => event,
+        }
+    }
+
+    // Runs the migrations from the version the event was sent with up to the
+    // current one. The upgraded event has the bare name local handlers know.
+    pub fn upgrade(&self, event: WireEvent) -> Result<WireEvent, SchemaErrors> {
+        let name = event.get_base_name().to_string();
+        let Some(schema) = self.schemas.get(&name) else {
+            return Ok(WireEvent::new(&name, event.payload));
+        };
+        let mut version = event.get_version();
+        if version > schema.version {
+            return Err(SchemaErrors::TooNew(name, version, schema.version));
+        }
+
+        let mut payload = event.payload;
+        while version < schema.version {
+            let migration = schema
+                .migrations
+                .get(&version)
+                .ok_or_else(|| SchemaErrors::MissingMigration(name.clone(), version))?;
+            payload = migration(payload)
+                .map_err(|err| SchemaErrors::MigrationFailed(name.clone(), version, err))?;
+            version += 1;
+        }
+        Ok(WireEvent::new(&name, payload))
+    }
+}
+
+impl Debug for EventSchemas {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let versions: BTreeMap<&String, u16> = self
+            .schemas
+            .iter()
+            .map(|(name, schema)| (name, schema.version))
+            .collect();
+        f.debug_struct("EventSchemas")
+            .field("versions", &versions)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_upgrade_through_every_version() {
+        let mut schemas = EventSchemas::new();
+        schemas.add_migration("PlayerMoved", 1, |mut payload| {
+            payload.push(0);
+            Ok(payload)
+        });
+        schemas.add_migration("PlayerMoved", 2, |payload| {
+            payload.into_iter().map(|byte| Ok(byte * 2)).collect()
+        });
+        assert_eq!(schemas.get_version("PlayerMoved"), Some(3));
+
+        let old_build = WireEvent::new("PlayerMoved", vec![1, 2]);
+        assert_eq!(
+            schemas.upgrade(old_build).unwrap(),
+            WireEvent::new("PlayerMoved", vec![2, 4, 0])
+        );
+        let stamped = schemas.stamp(WireEvent::new("PlayerMoved", vec![7]));
+        assert_eq!(stamped.name, "PlayerMoved@3");
+        assert_eq!(
+            schemas.upgrade(stamped).unwrap(),
+            WireEvent::new("PlayerMoved", vec![7])
+        );
+
+        assert_eq!(
+            schemas.upgrade(WireEvent::versioned("PlayerMoved", 4, vec![])),
+            Err(SchemaErrors::TooNew("PlayerMoved".to_string(), 4, 3))
+        );
+        assert_eq!(
+            schemas.upgrade(WireEvent::new("Chat@2", vec![1])).unwrap(),
+            WireEvent::new("Chat", vec![1])
+        );
+    }
+}
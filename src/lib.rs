@@ -1,11 +1,44 @@
+pub mod animation;
+pub mod assets;
+pub mod audio;
 pub mod core;
+pub mod ecs;
 pub mod event_system;
+pub mod math;
+pub mod renderer;
 
-use core::{logger::init_logger, runner::applications::Application};
+use core::{
+    cli::CliArgs,
+    logger::init_logger,
+    runner::{aloy_app::AloyApp, applications::Application, builder::ApplicationBuilder},
+};
+use std::process::exit;
+
+use log::error;
+
+// Exit code for unusable command line arguments
+const USAGE_EXIT_CODE: i32 = 2;
+
+fn builder_from_env() -> ApplicationBuilder {
+    match CliArgs::from_env() {
+        Ok(args) => Application::builder().with_args(args),
+        Err(err) => {
+            error!("{}", err);
+            exit(USAGE_EXIT_CODE);
+        }
+    }
+}
 
 #[no_mangle]
 pub extern "C" fn run() {
     init_logger();
-    let mut app = Application::default();
+    let mut app = builder_from_env().build();
+    app.run();
+}
+
+// Entry point for game crates, usually called through `aloy_main!`
+pub fn run_app(game: impl AloyApp + 'static) {
+    init_logger();
+    let mut app = builder_from_env().with_app(game).build();
     app.run();
 }
@@ -0,0 +1,5 @@
+pub mod random;
+pub mod rect;
+pub mod smoothing;
+pub mod transform;
+pub mod vector;
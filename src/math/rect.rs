@@ -0,0 +1,52 @@
+use super::vector::Vec2;
+
+// Axis aligned rectangle, y grows downwards like the rest of the 2D code
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl Rect {
+    pub const UNIT: Self = Self {
+        min: Vec2::ZERO,
+        max: Vec2::new(1.0, 1.0),
+    };
+
+    pub fn new(min: Vec2, max: Vec2) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_size(position: Vec2, size: Vec2) -> Self {
+        Self::new(position, position + size)
+    }
+
+    pub fn get_size(&self) -> Vec2 {
+        self.max - self.min
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.max.x <= self.min.x || self.max.y <= self.min.y
+    }
+
+    // Min edges are inside, max edges are not, so touching rects never both
+    // contain a point
+    pub fn contains(&self, point: Vec2) -> bool {
+        point.x >= self.min.x
+            && point.x < self.max.x
+            && point.y >= self.min.y
+            && point.y < self.max.y
+    }
+
+    // The point at `fraction` of the way across, (0, 0) is min
+    pub fn lerp(&self, fraction: Vec2) -> Vec2 {
+        let size = self.get_size();
+        self.min + Vec2::new(size.x * fraction.x, size.y * fraction.y)
+    }
+
+    // The part of the rect between two fractions, e.g. the filled part of a
+    // progress bar is `sub(Vec2::ZERO, Vec2::new(progress, 1.0))`
+    pub fn sub(&self, from: Vec2, to: Vec2) -> Self {
+        Self::new(self.lerp(from), self.lerp(to))
+    }
+}
@@ -0,0 +1,178 @@
+use std::ops::{Add, Mul, Sub};
+
+use super::vector::{Vec2, Vec3, Vec4};
+
+// Values that can be smoothed, the scalar and vector types
+pub trait Smoothable:
+    Copy + Add<Output = Self> + Sub<Output = Self> + Mul<f32, Output = Self>
+{
+    const ZERO: Self;
+}
+
+impl Smoothable for f32 {
+    const ZERO: Self = 0.0;
+}
+
+impl Smoothable for Vec2 {
+    const ZERO: Self = Vec2::ZERO;
+}
+
+impl Smoothable for Vec3 {
+    const ZERO: Self = Vec3::ZERO;
+}
+
+impl Smoothable for Vec4 {
+    const ZERO: Self = Vec4::ZERO;
+}
+
+// Moves `current` towards `target` so that half of the distance is left
+// after `half_life` seconds. Unlike `lerp(current, target, 0.1)` every frame
+// it ends up in the same place at 30 and at 144 fps.
+pub fn damp<T: Smoothable>(current: T, target: T, half_life: f32, delta: f32) -> T {
+    if half_life <= 0.0 {
+        return target;
+    }
+    let remaining = (-std::f32::consts::LN_2 * delta / half_life).exp();
+    target + (current - target) * remaining
+}
+
+// Exact step of a critically damped spring, stable for any delta
+fn critical_step<T: Smoothable>(
+    current: T,
+    target: T,
+    velocity: &mut T,
+    stiffness: f32,
+    delta: f32,
+) -> T {
+    let offset = current - target;
+    let slope = *velocity + offset * stiffness;
+    let decay = (-stiffness * delta).exp();
+    *velocity = (*velocity - slope * (stiffness * delta)) * decay;
+    target + (offset + slope * delta) * decay
+}
+
+// Follows `target` like a critically damped spring, reaching it in roughly
+// `smooth_time` seconds without overshooting. `velocity` carries over between
+// calls, start it at zero. Good for camera follow.
+pub fn smooth_damp<T: Smoothable>(
+    current: T,
+    target: T,
+    velocity: &mut T,
+    smooth_time: f32,
+    delta: f32,
+) -> T {
+    if smooth_time <= 0.0 {
+        *velocity = T::ZERO;
+        return target;
+    }
+    critical_step(current, target, velocity, 2.0 / smooth_time, delta)
+}
+
+// A critically damped spring keeping its own state, for values that keep
+// chasing a moving target like UI elements sliding into place or filtered
+// analog input
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Spring<T> {
+    value: T,
+    velocity: T,
+    target: T,
+    half_life: f32,
+}
+
+impl<T: Smoothable> Spring<T> {
+    // At rest on `value`. After `half_life` seconds about half of any jump
+    // of the target is covered.
+    pub fn new(value: T, half_life: f32) -> Self {
+        Self {
+            value,
+            velocity: T::ZERO,
+            target: value,
+            half_life,
+        }
+    }
+
+    pub fn set_target(&mut self, target: T) {
+        self.target = target;
+    }
+
+    pub fn get_target(&self) -> T {
+        self.target
+    }
+
+    pub fn set_half_life(&mut self, half_life: f32) {
+        self.half_life = half_life;
+    }
+
+    // Jumps to `value` and stops there
+    pub fn reset(&mut self, value: T) {
+        self.value = value;
+        self.target = value;
+        self.velocity = T::ZERO;
+    }
+
+    pub fn update(&mut self, delta: f32) -> T {
+        if self.half_life <= 0.0 {
+            self.reset(self.target);
+            return self.value;
+        }
+        // the stiffness that halves a jump of the target after half_life
+        let stiffness = 1.678_347 / self.half_life;
+        self.value = critical_step(
+            self.value,
+            self.target,
+            &mut self.velocity,
+            stiffness,
+            delta,
+        );
+        self.value
+    }
+
+    pub fn get_value(&self) -> T {
+        self.value
+    }
+
+    pub fn get_velocity(&self) -> T {
+        self.velocity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_independent_of_frame_rate() {
+        let one_step = damp(0.0, 10.0, 0.5, 1.0);
+        let mut stepped = 0.0;
+        for _ in 0..144 {
+            stepped = damp(stepped, 10.0, 0.5, 1.0 / 144.0);
+        }
+        assert!((one_step - 7.5_f32).abs() < 1e-4);
+        assert!((stepped - one_step).abs() < 1e-3);
+
+        let mut velocity = Vec2::ZERO;
+        let slow = smooth_damp(Vec2::ZERO, Vec2::new(4.0, 0.0), &mut velocity, 0.3, 0.5);
+        let mut stepped_velocity = Vec2::ZERO;
+        let mut stepped = Vec2::ZERO;
+        for _ in 0..60 {
+            stepped = smooth_damp(
+                stepped,
+                Vec2::new(4.0, 0.0),
+                &mut stepped_velocity,
+                0.3,
+                0.5 / 60.0,
+            );
+        }
+        assert!(stepped.distance(slow) < 1e-3);
+        assert!(velocity.distance(stepped_velocity) < 1e-3);
+        assert!(slow.x < 4.0);
+
+        let mut spring = Spring::new(0.0, 0.25);
+        spring.set_target(1.0);
+        assert!((spring.update(0.25) - 0.5).abs() < 1e-3);
+        for _ in 0..100 {
+            spring.update(1.0 / 30.0);
+        }
+        assert!((spring.get_value() - 1.0).abs() < 1e-3);
+    }
+}
@@ -0,0 +1,316 @@
+use std::ops::Mul;
+
+use super::vector::{Vec3, Vec4};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quat {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub w: f32,
+}
+
+impl Quat {
+    pub const IDENTITY: Self = Self::new(0.0, 0.0, 0.0, 1.0);
+
+    pub const fn new(x: f32, y: f32, z: f32, w: f32) -> Self {
+        Self { x, y, z, w }
+    }
+
+    // `angle` in radians around a normalized axis
+    pub fn from_axis_angle(axis: Vec3, angle: f32) -> Self {
+        let (sin, cos) = (angle / 2.0).sin_cos();
+        Self::new(axis.x * sin, axis.y * sin, axis.z * sin, cos)
+    }
+
+    // The shortest rotation turning the normalized `from` into `to`
+    pub fn from_rotation_arc(from: Vec3, to: Vec3) -> Self {
+        let dot = from.dot(to);
+        if dot < -1.0 + 1e-6 {
+            // opposite, any perpendicular axis does
+            let axis = match from.cross(Vec3::X).length() > 1e-6 {
+                true => from.cross(Vec3::X),
+                false => from.cross(Vec3::Y),
+            };
+            return Self::from_axis_angle(axis.normalize(), std::f32::consts::PI);
+        }
+        let axis = from.cross(to);
+        Self::new(axis.x, axis.y, axis.z, 1.0 + dot).normalize()
+    }
+
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z + self.w * other.w
+    }
+
+    pub fn normalize(self) -> Self {
+        let length = self.dot(self).sqrt();
+        if length == 0.0 {
+            return Self::IDENTITY;
+        }
+        Self::new(
+            self.x / length,
+            self.y / length,
+            self.z / length,
+            self.w / length,
+        )
+    }
+
+    pub fn conjugate(self) -> Self {
+        Self::new(-self.x, -self.y, -self.z, self.w)
+    }
+
+    pub fn rotate(self, v: Vec3) -> Vec3 {
+        let axis = Vec3::new(self.x, self.y, self.z);
+        let t = axis.cross(v) * 2.0;
+        v + t * self.w + axis.cross(t)
+    }
+
+    // Normalized lerp along the shortest arc, good enough between keyframes
+    // and much cheaper than slerp
+    pub fn nlerp(self, other: Self, t: f32) -> Self {
+        let other = match self.dot(other) < 0.0 {
+            true => Self::new(-other.x, -other.y, -other.z, -other.w),
+            false => other,
+        };
+        Self::new(
+            self.x + (other.x - self.x) * t,
+            self.y + (other.y - self.y) * t,
+            self.z + (other.z - self.z) * t,
+            self.w + (other.w - self.w) * t,
+        )
+        .normalize()
+    }
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Quat {
+    type Output = Self;
+    fn mul(self, o: Self) -> Self {
+        Self::new(
+            self.w * o.x + self.x * o.w + self.y * o.z - self.z * o.y,
+            self.w * o.y - self.x * o.z + self.y * o.w + self.z * o.x,
+            self.w * o.z + self.x * o.y - self.y * o.x + self.z * o.w,
+            self.w * o.w - self.x * o.x - self.y * o.y - self.z * o.z,
+        )
+    }
+}
+
+// Column major like glTF and the shaders expect
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub cols: [Vec4; 4],
+}
+
+impl Mat4 {
+    pub const IDENTITY: Self = Self {
+        cols: [
+            Vec4::new(1.0, 0.0, 0.0, 0.0),
+            Vec4::new(0.0, 1.0, 0.0, 0.0),
+            Vec4::new(0.0, 0.0, 1.0, 0.0),
+            Vec4::new(0.0, 0.0, 0.0, 1.0),
+        ],
+    };
+
+    pub fn from_cols_array(values: [[f32; 4]; 4]) -> Self {
+        Self {
+            cols: values.map(|[x, y, z, w]| Vec4::new(x, y, z, w)),
+        }
+    }
+
+    pub fn to_cols_array(&self) -> [[f32; 4]; 4] {
+        self.cols.map(|col| [col.x, col.y, col.z, col.w])
+    }
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        let mut matrix = Self::IDENTITY;
+        matrix.cols[3] = translation.extend(1.0);
+        matrix
+    }
+
+    // Right handed, looking down -z, depth from 0 at `near` to 1 at `far`
+    // like wgpu wants it
+    pub fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Self {
+        let width = right - left;
+        let height = top - bottom;
+        let depth = near - far;
+        Self {
+            cols: [
+                Vec4::new(2.0 / width, 0.0, 0.0, 0.0),
+                Vec4::new(0.0, 2.0 / height, 0.0, 0.0),
+                Vec4::new(0.0, 0.0, 1.0 / depth, 0.0),
+                Vec4::new(
+                    -(right + left) / width,
+                    -(top + bottom) / height,
+                    near / depth,
+                    1.0,
+                ),
+            ],
+        }
+    }
+
+    // Right handed, `fov_y` in radians, depth from 0 at `near` to 1 at `far`
+    pub fn perspective(fov_y: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        let focal = 1.0 / (fov_y / 2.0).tan();
+        let depth = near - far;
+        Self {
+            cols: [
+                Vec4::new(focal / aspect_ratio, 0.0, 0.0, 0.0),
+                Vec4::new(0.0, focal, 0.0, 0.0),
+                Vec4::new(0.0, 0.0, far / depth, -1.0),
+                Vec4::new(0.0, 0.0, near * far / depth, 0.0),
+            ],
+        }
+    }
+
+    // View matrix of an eye looking along `forward`
+    pub fn look_to(eye: Vec3, forward: Vec3, up: Vec3) -> Self {
+        let forward = forward.normalize();
+        let right = forward.cross(up).normalize();
+        let up = right.cross(forward);
+        Self {
+            cols: [
+                Vec4::new(right.x, up.x, -forward.x, 0.0),
+                Vec4::new(right.y, up.y, -forward.y, 0.0),
+                Vec4::new(right.z, up.z, -forward.z, 0.0),
+                Vec4::new(-right.dot(eye), -up.dot(eye), forward.dot(eye), 1.0),
+            ],
+        }
+    }
+
+    pub fn transform_vec4(&self, v: Vec4) -> Vec4 {
+        self.cols[0] * v.x + self.cols[1] * v.y + self.cols[2] * v.z + self.cols[3] * v.w
+    }
+
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        self.transform_vec4(point.extend(1.0)).truncate()
+    }
+
+    pub fn transform_vector(&self, vector: Vec3) -> Vec3 {
+        self.transform_vec4(vector.extend(0.0)).truncate()
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        Self {
+            cols: other.cols.map(|col| self.transform_vec4(col)),
+        }
+    }
+}
+
+impl Mul<f32> for Mat4 {
+    type Output = Self;
+    fn mul(self, scalar: f32) -> Self {
+        Self {
+            cols: self.cols.map(|col| col * scalar),
+        }
+    }
+}
+
+impl std::ops::Add for Mat4 {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Self {
+            cols: [0, 1, 2, 3].map(|index| self.cols[index] + other.cols[index]),
+        }
+    }
+}
+
+// Translation, rotation and scale, applied scale first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::new(1.0, 1.0, 1.0),
+    };
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn to_matrix(&self) -> Mat4 {
+        let x = self.rotation.rotate(Vec3::X) * self.scale.x;
+        let y = self.rotation.rotate(Vec3::Y) * self.scale.y;
+        let z = self.rotation.rotate(Vec3::Z) * self.scale.z;
+        Mat4 {
+            cols: [
+                x.extend(0.0),
+                y.extend(0.0),
+                z.extend(0.0),
+                self.translation.extend(1.0),
+            ],
+        }
+    }
+
+    pub fn lerp(&self, other: &Self, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.nlerp(other.rotation, t),
+            scale: self.scale.lerp(other.scale, t),
+        }
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        a.distance(b) < 1e-5
+    }
+
+    #[test]
+    fn test_rotation_and_matrices_agree() {
+        let quarter = Quat::from_axis_angle(Vec3::Y, std::f32::consts::FRAC_PI_2);
+        assert!(close(quarter.rotate(Vec3::X), -Vec3::Z));
+        assert!(close((quarter * quarter).rotate(Vec3::X), -Vec3::X));
+
+        let transform = Transform {
+            translation: Vec3::new(1.0, 2.0, 3.0),
+            rotation: quarter,
+            scale: Vec3::splat(2.0),
+        };
+        let matrix = transform.to_matrix();
+        assert!(close(
+            matrix.transform_point(Vec3::X),
+            Vec3::new(1.0, 2.0, 1.0)
+        ));
+        let moved = Mat4::IDENTITY * Transform::from_translation(Vec3::Y).to_matrix() * matrix;
+        assert!(close(
+            moved.transform_point(Vec3::X),
+            Vec3::new(1.0, 3.0, 1.0)
+        ));
+
+        let halfway = Quat::IDENTITY.nlerp(quarter, 0.5);
+        let eighth = Quat::from_axis_angle(Vec3::Y, std::f32::consts::FRAC_PI_4);
+        assert!((halfway.dot(eighth).abs() - 1.0).abs() < 1e-5);
+    }
+}
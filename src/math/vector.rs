@@ -0,0 +1,150 @@
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use serde::{Deserialize, Serialize};
+
+macro_rules! impl_vector {
+    ($name:ident, $scalar:ty, $($field:ident),+) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+        pub struct $name {
+            $(pub $field: $scalar),+
+        }
+
+        impl $name {
+            pub const ZERO: Self = Self { $($field: 0.0),+ };
+
+            pub const fn new($($field: $scalar),+) -> Self {
+                Self { $($field),+ }
+            }
+
+            pub fn splat(value: $scalar) -> Self {
+                Self { $($field: value),+ }
+            }
+
+            pub fn dot(self, other: Self) -> $scalar {
+                0.0 $(+ self.$field * other.$field)+
+            }
+
+            pub fn length_squared(self) -> $scalar {
+                self.dot(self)
+            }
+
+            pub fn length(self) -> $scalar {
+                self.length_squared().sqrt()
+            }
+
+            pub fn distance(self, other: Self) -> $scalar {
+                (self - other).length()
+            }
+
+            // Returns zero for a zero length vector instead of NaNs
+            pub fn normalize(self) -> Self {
+                let length = self.length();
+                if length == 0.0 {
+                    return Self::ZERO;
+                }
+                self / length
+            }
+
+            pub fn lerp(self, other: Self, t: $scalar) -> Self {
+                self + (other - self) * t
+            }
+
+            pub fn min(self, other: Self) -> Self {
+                Self { $($field: self.$field.min(other.$field)),+ }
+            }
+
+            pub fn max(self, other: Self) -> Self {
+                Self { $($field: self.$field.max(other.$field)),+ }
+            }
+        }
+
+        impl Add for $name {
+            type Output = Self;
+            fn add(self, other: Self) -> Self {
+                Self { $($field: self.$field + other.$field),+ }
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: Self) {
+                $(self.$field += other.$field;)+
+            }
+        }
+
+        impl Sub for $name {
+            type Output = Self;
+            fn sub(self, other: Self) -> Self {
+                Self { $($field: self.$field - other.$field),+ }
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: Self) {
+                $(self.$field -= other.$field;)+
+            }
+        }
+
+        impl Mul<$scalar> for $name {
+            type Output = Self;
+            fn mul(self, scalar: $scalar) -> Self {
+                Self { $($field: self.$field * scalar),+ }
+            }
+        }
+
+        impl Div<$scalar> for $name {
+            type Output = Self;
+            fn div(self, scalar: $scalar) -> Self {
+                Self { $($field: self.$field / scalar),+ }
+            }
+        }
+
+        impl Neg for $name {
+            type Output = Self;
+            fn neg(self) -> Self {
+                Self { $($field: -self.$field),+ }
+            }
+        }
+    };
+}
+
+impl_vector!(Vec2, f32, x, y);
+impl_vector!(Vec3, f32, x, y, z);
+impl_vector!(Vec4, f32, x, y, z, w);
+// Double precision, used where f32 runs out of precision (large worlds)
+impl_vector!(DVec3, f64, x, y, z);
+
+impl Vec3 {
+    pub const X: Self = Self::new(1.0, 0.0, 0.0);
+    pub const Y: Self = Self::new(0.0, 1.0, 0.0);
+    pub const Z: Self = Self::new(0.0, 0.0, 1.0);
+
+    pub fn cross(self, other: Self) -> Self {
+        Self::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    pub fn extend(self, w: f32) -> Vec4 {
+        Vec4::new(self.x, self.y, self.z, w)
+    }
+}
+
+impl Vec4 {
+    pub fn truncate(self) -> Vec3 {
+        Vec3::new(self.x, self.y, self.z)
+    }
+}
+
+impl From<Vec3> for DVec3 {
+    fn from(value: Vec3) -> Self {
+        Self::new(value.x as f64, value.y as f64, value.z as f64)
+    }
+}
+
+impl From<DVec3> for Vec3 {
+    fn from(value: DVec3) -> Self {
+        Self::new(value.x as f32, value.y as f32, value.z as f32)
+    }
+}
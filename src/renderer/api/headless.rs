@@ -0,0 +1,552 @@
+use std::collections::HashMap;
+
+use crate::{
+    core::window::Window,
+    math::vector::Vec4,
+    renderer::gpu_memory::{AllocationId, GpuMemoryCategory, GpuMemoryTracker},
+};
+
+use super::{
+    Binding, Buffer, BufferDescriptor, DrawCommand, PassTiming, Pipeline, PipelineDescriptor,
+    PresentMode, Readback, ReadbackImage, RenderPass, RenderTarget, RendererAPI, RendererBackend,
+    RendererErrors, Resource, Shader, ShaderSource, Texture, TextureDescriptor, TextureFormat,
+};
+
+// A renderer without a GPU behind it, for servers and tests. It checks what
+// it is given like a real backend would, keeps buffer and texture contents
+// in memory and remembers the passes of the last frame.
+#[derive(Debug, Default)]
+pub struct HeadlessRenderer {
+    next_id: u64,
+    surface_size: (u32, u32),
+    present_mode: PresentMode,
+    buffers: HashMap<Buffer, (BufferDescriptor, Vec<u8>)>,
+    textures: HashMap<Texture, (TextureDescriptor, Vec<u8>)>,
+    shaders: HashMap<Shader, ShaderSource>,
+    pipelines: HashMap<Pipeline, PipelineDescriptor>,
+    submitted: Vec<RenderPass>,
+    frames: u64,
+    // rgba, only filled by clears like the textures
+    surface_pixels: Vec<u8>,
+    readbacks: HashMap<Readback, Result<ReadbackImage, RendererErrors>>,
+    // finished when the frame is presented
+    surface_readbacks: Vec<Readback>,
+    memory: GpuMemoryTracker,
+    allocations: HashMap<Resource, AllocationId>,
+    // textures whose larger mips were dropped with `set_base_mip`
+    base_mips: HashMap<Texture, u32>,
+}
+
+impl HeadlessRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Frames presented so far
+    pub fn get_frames(&self) -> u64 {
+        self.frames
+    }
+
+    // The passes submitted since the last present
+    pub fn get_submitted(&self) -> &[RenderPass] {
+        &self.submitted
+    }
+
+    pub fn get_buffer_contents(&self, buffer: Buffer) -> Option<&[u8]> {
+        self.buffers
+            .get(&buffer)
+            .map(|(_, contents)| contents.as_slice())
+    }
+
+    pub fn get_base_mip(&self, texture: Texture) -> Option<u32> {
+        self.textures
+            .contains_key(&texture)
+            .then(|| self.base_mips.get(&texture).copied().unwrap_or(0))
+    }
+
+    pub fn get_texture_pixels(&self, texture: Texture) -> Option<&[u8]> {
+        self.textures
+            .get(&texture)
+            .map(|(_, pixels)| pixels.as_slice())
+    }
+
+    pub fn get_resource_count(&self) -> usize {
+        self.buffers.len() + self.textures.len() + self.shaders.len() + self.pipelines.len()
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn check(&self, resource: Resource) -> Result<(), RendererErrors> {
+        let exists = match resource {
+            Resource::Buffer(buffer) => self.buffers.contains_key(&buffer),
+            Resource::Texture(texture) => self.textures.contains_key(&texture),
+            Resource::Shader(shader) => self.shaders.contains_key(&shader),
+            Resource::Pipeline(pipeline) => self.pipelines.contains_key(&pipeline),
+        };
+        match exists {
+            true => Ok(()),
+            false => Err(RendererErrors::UnknownResource(resource)),
+        }
+    }
+
+    fn check_pass(&self, pass: &RenderPass) -> Result<(), RendererErrors> {
+        if let RenderTarget::Texture(texture) = pass.target {
+            self.check(Resource::Texture(texture))?;
+        }
+        if let Some(depth) = pass.depth {
+            self.check(Resource::Texture(depth))?;
+        }
+        for command in pass.commands.iter() {
+            match command {
+                DrawCommand::SetPipeline(pipeline) => self.check(Resource::Pipeline(*pipeline))?,
+                DrawCommand::SetBindings(bindings) => {
+                    for binding in bindings {
+                        match binding {
+                            Binding::Buffer(buffer) => self.check(Resource::Buffer(*buffer))?,
+                            Binding::Texture(texture) => self.check(Resource::Texture(*texture))?,
+                            Binding::Sampler(_) => {}
+                        }
+                    }
+                }
+                DrawCommand::SetVertexBuffer { buffer, .. }
+                | DrawCommand::SetIndexBuffer(buffer) => self.check(Resource::Buffer(*buffer))?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+// RGBA8 clear color, the only formats filled on clear
+fn to_rgba8(color: Vec4) -> [u8; 4] {
+    [color.x, color.y, color.z, color.w]
+        .map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
+
+impl RendererAPI for HeadlessRenderer {
+    fn get_backend(&self) -> RendererBackend {
+        RendererBackend::Headless
+    }
+
+    fn attach(
+        &mut self,
+        window: &dyn Window,
+        size: (u32, u32),
+        vsync: bool,
+    ) -> Result<(), RendererErrors> {
+        self.present_mode = PresentMode::from_vsync(vsync);
+        self.surface_size = size;
+        if size == (0, 0) {
+            self.surface_size = window.get_size();
+        }
+        Ok(())
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        if width > 0 && height > 0 {
+            self.surface_size = (width, height);
+        }
+    }
+
+    // Every mode is supported without a display
+    fn set_present_mode(&mut self, mode: PresentMode) -> PresentMode {
+        self.present_mode = mode;
+        mode
+    }
+
+    fn get_present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
+
+    fn get_surface_size(&self) -> (u32, u32) {
+        self.surface_size
+    }
+
+    fn get_surface_format(&self) -> TextureFormat {
+        TextureFormat::Bgra8UnormSrgb
+    }
+
+    fn create_buffer(
+        &mut self,
+        desc: &BufferDescriptor,
+        contents: Option<&[u8]>,
+    ) -> Result<Buffer, RendererErrors> {
+        let buffer = Buffer(self.next_id());
+        let mut data = vec![0; desc.size as usize];
+        if let Some(contents) = contents {
+            if contents.len() as u64 > desc.size {
+                return Err(RendererErrors::OutOfBounds {
+                    resource: Resource::Buffer(buffer),
+                    offset: 0,
+                    len: contents.len() as u64,
+                    size: desc.size,
+                });
+            }
+            data[..contents.len()].copy_from_slice(contents);
+        }
+        self.buffers.insert(buffer, (desc.clone(), data));
+        let allocation = self.memory.allocate(GpuMemoryCategory::Buffer, desc.size);
+        self.allocations
+            .insert(Resource::Buffer(buffer), allocation);
+        Ok(buffer)
+    }
+
+    fn write_buffer(
+        &mut self,
+        buffer: Buffer,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), RendererErrors> {
+        let (desc, contents) = self
+            .buffers
+            .get_mut(&buffer)
+            .ok_or(RendererErrors::UnknownResource(Resource::Buffer(buffer)))?;
+        let end = offset + data.len() as u64;
+        if end > desc.size {
+            return Err(RendererErrors::OutOfBounds {
+                resource: Resource::Buffer(buffer),
+                offset,
+                len: data.len() as u64,
+                size: desc.size,
+            });
+        }
+        contents[offset as usize..end as usize].copy_from_slice(data);
+        Ok(())
+    }
+
+    fn create_texture(&mut self, desc: &TextureDescriptor) -> Result<Texture, RendererErrors> {
+        let texture = Texture(self.next_id());
+        let pixels = vec![0; desc.get_byte_size() as usize];
+        self.textures.insert(texture, (desc.clone(), pixels));
+        let allocation = self.memory.allocate(
+            desc.get_memory_category(),
+            desc.get_mip_byte_sizes().iter().sum(),
+        );
+        self.allocations
+            .insert(Resource::Texture(texture), allocation);
+        Ok(texture)
+    }
+
+    // Only level 0 is kept, the other levels are checked and dropped
+    fn write_texture_mip(
+        &mut self,
+        texture: Texture,
+        level: u32,
+        pixels: &[u8],
+    ) -> Result<(), RendererErrors> {
+        let (desc, contents) = self
+            .textures
+            .get_mut(&texture)
+            .ok_or(RendererErrors::UnknownResource(Resource::Texture(texture)))?;
+        let size = match level < desc.mip_levels.max(1) {
+            true => desc.get_mip_byte_size(level),
+            false => 0,
+        };
+        if pixels.len() as u64 != size || size == 0 {
+            return Err(RendererErrors::OutOfBounds {
+                resource: Resource::Texture(texture),
+                offset: level as u64,
+                len: pixels.len() as u64,
+                size,
+            });
+        }
+        if level == 0 {
+            contents.copy_from_slice(pixels);
+        }
+        Ok(())
+    }
+
+    fn create_shader(&mut self, source: &ShaderSource) -> Result<Shader, RendererErrors> {
+        if source.get_code().trim().is_empty() {
+            return Err(RendererErrors::ShaderCompile(
+                source.get_label().to_string(),
+                "empty shader".to_string(),
+            ));
+        }
+        let shader = Shader(self.next_id());
+        self.shaders.insert(shader, source.clone());
+        Ok(shader)
+    }
+
+    fn create_pipeline(&mut self, desc: &PipelineDescriptor) -> Result<Pipeline, RendererErrors> {
+        self.check(Resource::Shader(desc.shader))?;
+        if let Some(fragment) = desc.fragment_shader {
+            self.check(Resource::Shader(fragment))?;
+        }
+        let pipeline = Pipeline(self.next_id());
+        self.pipelines.insert(pipeline, desc.clone());
+        Ok(pipeline)
+    }
+
+    fn destroy(&mut self, resource: Resource) {
+        match resource {
+            Resource::Buffer(buffer) => {
+                self.buffers.remove(&buffer);
+            }
+            Resource::Texture(texture) => {
+                self.textures.remove(&texture);
+                self.base_mips.remove(&texture);
+            }
+            Resource::Shader(shader) => {
+                self.shaders.remove(&shader);
+            }
+            Resource::Pipeline(pipeline) => {
+                self.pipelines.remove(&pipeline);
+            }
+        }
+        if let Some(allocation) = self.allocations.remove(&resource) {
+            self.memory.free(allocation);
+        }
+    }
+
+    fn submit(&mut self, passes: &[RenderPass]) -> Result<(), RendererErrors> {
+        for pass in passes {
+            self.check_pass(pass)?;
+        }
+        let (width, height) = self.surface_size;
+        self.surface_pixels
+            .resize(width as usize * height as usize * 4, 0);
+        for pass in passes {
+            let Some(color) = pass.clear_color else {
+                continue;
+            };
+            let RenderTarget::Texture(texture) = pass.target else {
+                let rgba = to_rgba8(color);
+                self.surface_pixels
+                    .chunks_exact_mut(4)
+                    .for_each(|chunk| chunk.copy_from_slice(&rgba));
+                continue;
+            };
+            if let Some((desc, pixels)) = self.textures.get_mut(&texture) {
+                let rgba = to_rgba8(color);
+                let pixel = match desc.format {
+                    TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => rgba,
+                    TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => {
+                        [rgba[2], rgba[1], rgba[0], rgba[3]]
+                    }
+                    _ => continue,
+                };
+                pixels
+                    .chunks_exact_mut(4)
+                    .for_each(|chunk| chunk.copy_from_slice(&pixel));
+            }
+        }
+        self.submitted.extend(passes.iter().cloned());
+        Ok(())
+    }
+
+    fn present(&mut self) -> Result<(), RendererErrors> {
+        self.submitted.clear();
+        self.frames += 1;
+        let (width, height) = self.surface_size;
+        for readback in self.surface_readbacks.drain(..) {
+            let mut rgba = self.surface_pixels.clone();
+            rgba.resize(width as usize * height as usize * 4, 0);
+            let image = ReadbackImage::from_pixels(width, height, TextureFormat::Rgba8Unorm, rgba);
+            self.readbacks.insert(readback, image);
+        }
+        Ok(())
+    }
+
+    // Textures are copied right away, the surface when it is presented
+    fn request_readback(&mut self, target: RenderTarget) -> Result<Readback, RendererErrors> {
+        let readback = Readback(self.next_id());
+        match target {
+            RenderTarget::Surface => self.surface_readbacks.push(readback),
+            RenderTarget::Texture(texture) => {
+                let (desc, pixels) = self
+                    .textures
+                    .get(&texture)
+                    .ok_or(RendererErrors::UnknownResource(Resource::Texture(texture)))?;
+                let image = ReadbackImage::from_pixels(
+                    desc.width,
+                    desc.height,
+                    desc.format,
+                    pixels[..desc.get_byte_size() as usize].to_vec(),
+                )?;
+                self.readbacks.insert(readback, Ok(image));
+            }
+        }
+        Ok(readback)
+    }
+
+    fn take_readback(
+        &mut self,
+        readback: Readback,
+    ) -> Option<Result<ReadbackImage, RendererErrors>> {
+        self.readbacks.remove(&readback)
+    }
+
+    fn get_memory(&self) -> &GpuMemoryTracker {
+        &self.memory
+    }
+
+    fn get_memory_mut(&mut self) -> &mut GpuMemoryTracker {
+        &mut self.memory
+    }
+
+    fn get_allocation(&self, resource: Resource) -> Option<AllocationId> {
+        self.allocations.get(&resource).copied()
+    }
+
+    fn set_base_mip(&mut self, texture: Texture, base_mip: u32) -> Result<(), RendererErrors> {
+        let (desc, _) = self
+            .textures
+            .get(&texture)
+            .ok_or(RendererErrors::UnknownResource(Resource::Texture(texture)))?;
+        if desc.render_target || desc.format.is_depth() {
+            return Err(RendererErrors::Backend(format!(
+                "{} is a render target, it keeps all of its mips",
+                desc.label
+            )));
+        }
+        let base_mip = base_mip.min(desc.mip_levels.max(1) - 1);
+        let resident = desc.get_mip_byte_sizes()[base_mip as usize..].iter().sum();
+        if let Some(allocation) = self.allocations.get(&Resource::Texture(texture)) {
+            self.memory.resize(*allocation, resident);
+        }
+        self.base_mips.insert(texture, base_mip);
+        Ok(())
+    }
+
+    // There is no GPU to time
+    fn take_pass_timings(&mut self) -> Vec<PassTiming> {
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::{
+        api::{BufferUsage, TextureFormat},
+        gpu_memory::{TextureId, TextureResidency},
+    };
+
+    #[test]
+    fn test_checks_resources_and_clears_targets() {
+        let mut api = HeadlessRenderer::new();
+        let desc = BufferDescriptor {
+            label: "quads".to_string(),
+            usage: BufferUsage::Vertex,
+            size: 8,
+        };
+        let quads = api.create_buffer(&desc, Some(&[1, 2])).unwrap();
+        api.write_buffer(quads, 6, &[9, 9]).unwrap();
+        assert_eq!(
+            api.get_buffer_contents(quads),
+            Some(&[1, 2, 0, 0, 0, 0, 9, 9][..])
+        );
+        assert!(matches!(
+            api.write_buffer(quads, 7, &[1, 1]),
+            Err(RendererErrors::OutOfBounds { .. })
+        ));
+
+        let shader = api
+            .create_shader(&ShaderSource::wgsl("sprite", "@vertex fn vs_main() {}"))
+            .unwrap();
+        let pipeline = api
+            .create_pipeline(&PipelineDescriptor::new(
+                "sprite",
+                shader,
+                api.get_surface_format(),
+            ))
+            .unwrap();
+        let target = api
+            .create_texture(&TextureDescriptor {
+                label: "minimap".to_string(),
+                width: 2,
+                height: 1,
+                format: TextureFormat::Rgba8Unorm,
+                render_target: true,
+                mip_levels: 1,
+            })
+            .unwrap();
+
+        let mut pass = RenderPass::new("minimap", RenderTarget::Texture(target));
+        pass.clear(Vec4::new(1.0, 0.0, 0.0, 1.0));
+        pass.set_pipeline(pipeline);
+        pass.set_vertex_buffer(0, quads);
+        pass.draw(0..6, 0..1);
+        api.submit(&[pass.clone()]).unwrap();
+        assert_eq!(
+            api.get_texture_pixels(target),
+            Some(&[255, 0, 0, 255, 255, 0, 0, 255][..])
+        );
+        assert_eq!(api.get_submitted()[0].get_draw_count(), 1);
+        api.present().unwrap();
+        assert!(api.get_submitted().is_empty());
+
+        api.destroy(Resource::Buffer(quads));
+        assert_eq!(
+            api.submit(&[pass]),
+            Err(RendererErrors::UnknownResource(Resource::Buffer(quads)))
+        );
+    }
+
+    #[test]
+    fn test_reports_allocations_to_the_memory_tracker() {
+        let mut api = HeadlessRenderer::new();
+        let buffer = api
+            .create_buffer(
+                &BufferDescriptor {
+                    label: "instances".to_string(),
+                    usage: BufferUsage::Storage,
+                    size: 256,
+                },
+                None,
+            )
+            .unwrap();
+        let mut desc = TextureDescriptor {
+            label: "albedo".to_string(),
+            width: 4,
+            height: 4,
+            format: TextureFormat::Rgba8Unorm,
+            render_target: false,
+            mip_levels: 3,
+        };
+        let albedo = api.create_texture(&desc).unwrap();
+        desc.render_target = true;
+        desc.mip_levels = 1;
+        let target = api.create_texture(&desc).unwrap();
+
+        let memory = api.get_memory();
+        assert_eq!(memory.get_usage(GpuMemoryCategory::Buffer), 256);
+        assert_eq!(memory.get_usage(GpuMemoryCategory::Texture), 64 + 16 + 4);
+        assert_eq!(memory.get_usage(GpuMemoryCategory::RenderTarget), 64);
+
+        // residency drops mips of the backend's own allocation
+        let mut residency = TextureResidency::new();
+        let allocation = api.get_allocation(Resource::Texture(albedo)).unwrap();
+        residency.add_allocation(
+            api.get_memory_mut(),
+            TextureId(albedo.0),
+            allocation,
+            vec![64, 16, 4],
+        );
+        api.get_memory_mut()
+            .set_budget(GpuMemoryCategory::Texture, 20);
+        for change in residency.enforce_budget(api.get_memory_mut()) {
+            api.apply_mip_change(&change).unwrap();
+        }
+        assert_eq!(residency.get_base_mip(TextureId(albedo.0)), Some(1));
+        assert_eq!(api.get_base_mip(albedo), Some(1));
+        assert_eq!(api.get_memory().get_usage(GpuMemoryCategory::Texture), 20);
+
+        // the backend frees the dropped mips on its own too
+        api.set_base_mip(albedo, 5).unwrap();
+        assert_eq!(api.get_base_mip(albedo), Some(2));
+        assert_eq!(api.get_memory().get_usage(GpuMemoryCategory::Texture), 4);
+        assert!(api.set_base_mip(target, 1).is_err());
+
+        api.destroy(Resource::Buffer(buffer));
+        api.destroy(Resource::Texture(albedo));
+        api.destroy(Resource::Texture(target));
+        residency.remove_texture(api.get_memory_mut(), TextureId(albedo.0));
+        assert_eq!(api.get_memory().get_total_usage(), 0);
+        assert_eq!(api.get_allocation(Resource::Texture(albedo)), None);
+    }
+}
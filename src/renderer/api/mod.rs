@@ -0,0 +1,760 @@
+pub mod headless;
+#[cfg(feature = "wgpu")]
+pub mod wgpu_renderer;
+
+use std::{ops::Range, time::Duration};
+
+use thiserror::Error;
+
+use crate::{
+    core::window::Window,
+    math::vector::{Vec2, Vec4},
+    renderer::gpu_memory::{AllocationId, GpuMemoryCategory, GpuMemoryTracker, MipChange},
+};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RendererErrors {
+    #[error("no usable GPU: {0}")]
+    NoAdapter(String),
+
+    #[error("unable to draw into the window: {0}")]
+    Surface(String),
+
+    #[error("the renderer is not attached to a window")]
+    NotAttached,
+
+    #[error("{0:?} does not exist")]
+    UnknownResource(Resource),
+
+    #[error("unable to compile shader {0}: {1}")]
+    ShaderCompile(String, String),
+
+    #[error("unable to create pipeline {0}: {1}")]
+    PipelineCreation(String, String),
+
+    #[error("writing {len} bytes at {offset} overflows the {size} bytes of {resource:?}")]
+    OutOfBounds {
+        resource: Resource,
+        offset: u64,
+        len: u64,
+        size: u64,
+    },
+
+    #[error("renderer backend failed: {0}")]
+    Backend(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RendererBackend {
+    // draws nothing, for servers and tests
+    Headless,
+    // Vulkan, Metal, DX12 or OpenGL, whichever wgpu picks for the platform
+    Wgpu,
+}
+
+// How finished frames reach the screen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PresentMode {
+    // vsync, frames wait for the display and never tear
+    #[default]
+    Fifo,
+    // no tearing, newer frames replace waiting ones so the game is not
+    // held back by the display
+    Mailbox,
+    // no waiting at all, frames can tear
+    Immediate,
+}
+
+impl PresentMode {
+    pub fn from_vsync(vsync: bool) -> Self {
+        match vsync {
+            true => Self::Fifo,
+            false => Self::Mailbox,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "fifo" => Some(Self::Fifo),
+            "mailbox" => Some(Self::Mailbox),
+            "immediate" => Some(Self::Immediate),
+            _ => None,
+        }
+    }
+
+    // Only Fifo paces the loop to the display
+    pub fn is_vsync(&self) -> bool {
+        *self == Self::Fifo
+    }
+
+    // The closest mode the surface supports. Mailbox and Immediate stand in
+    // for each other, every surface supports Fifo.
+    pub fn resolve(self, supported: &[PresentMode]) -> Self {
+        let fallback = match self {
+            Self::Fifo => Self::Fifo,
+            Self::Mailbox => Self::Immediate,
+            Self::Immediate => Self::Mailbox,
+        };
+        [self, fallback]
+            .into_iter()
+            .find(|mode| supported.contains(mode))
+            .unwrap_or(Self::Fifo)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Buffer(pub u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Texture(pub u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Shader(pub u64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Pipeline(pub u64);
+
+// A copy of a target's pixels on its way back from the GPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Readback(pub u64);
+
+// 8 bit rgba pixels, top row first
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadbackImage {
+    pub width: u32,
+    pub height: u32,
+    pub rgba: Vec<u8>,
+}
+
+impl ReadbackImage {
+    // Tightly packed rows of an rgba8 or bgra8 format
+    pub(crate) fn from_pixels(
+        width: u32,
+        height: u32,
+        format: TextureFormat,
+        mut pixels: Vec<u8>,
+    ) -> Result<Self, RendererErrors> {
+        match format {
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {}
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => {
+                pixels
+                    .chunks_exact_mut(4)
+                    .for_each(|pixel| pixel.swap(0, 2));
+            }
+            _ => {
+                return Err(RendererErrors::Backend(format!(
+                    "unable to read back {:?} pixels",
+                    format
+                )))
+            }
+        }
+        Ok(Self {
+            width,
+            height,
+            rgba: pixels,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resource {
+    Buffer(Buffer),
+    Texture(Texture),
+    Shader(Shader),
+    Pipeline(Pipeline),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BufferUsage {
+    Vertex,
+    Index,
+    Uniform,
+    Storage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BufferDescriptor {
+    pub label: String,
+    pub usage: BufferUsage,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureFormat {
+    Rgba8Unorm,
+    Rgba8UnormSrgb,
+    Bgra8Unorm,
+    Bgra8UnormSrgb,
+    R8Unorm,
+    Rgba16Float,
+    Depth32Float,
+}
+
+impl TextureFormat {
+    pub fn get_bytes_per_pixel(&self) -> u32 {
+        match self {
+            Self::R8Unorm => 1,
+            Self::Rgba8Unorm
+            | Self::Rgba8UnormSrgb
+            | Self::Bgra8Unorm
+            | Self::Bgra8UnormSrgb
+            | Self::Depth32Float => 4,
+            Self::Rgba16Float => 8,
+        }
+    }
+
+    pub fn is_depth(&self) -> bool {
+        matches!(self, Self::Depth32Float)
+    }
+
+    // Sampling converts to linear and writing back to sRGB
+    pub fn is_srgb(&self) -> bool {
+        matches!(self, Self::Rgba8UnormSrgb | Self::Bgra8UnormSrgb)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureDescriptor {
+    pub label: String,
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    // can be drawn into by a render pass
+    pub render_target: bool,
+    // 1 for no mipmaps
+    pub mip_levels: u32,
+}
+
+impl TextureDescriptor {
+    // Bytes of the full size image, mip level 0
+    pub fn get_byte_size(&self) -> u64 {
+        self.get_mip_byte_size(0)
+    }
+
+    // Every level halves the size, down to 1x1
+    pub fn get_mip_size(&self, level: u32) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
+    pub fn get_mip_byte_size(&self, level: u32) -> u64 {
+        let (width, height) = self.get_mip_size(level);
+        width as u64 * height as u64 * self.format.get_bytes_per_pixel() as u64
+    }
+
+    // Size of every mip level, largest first
+    pub fn get_mip_byte_sizes(&self) -> Vec<u64> {
+        (0..self.mip_levels.max(1))
+            .map(|level| self.get_mip_byte_size(level))
+            .collect()
+    }
+
+    // Depth buffers count as render targets
+    pub fn get_memory_category(&self) -> GpuMemoryCategory {
+        match self.render_target || self.format.is_depth() {
+            true => GpuMemoryCategory::RenderTarget,
+            false => GpuMemoryCategory::Texture,
+        }
+    }
+
+    // Levels of a full mip chain for the size
+    pub fn get_max_mip_levels(width: u32, height: u32) -> u32 {
+        32 - width.max(height).max(1).leading_zeros()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShaderStage {
+    Vertex,
+    Fragment,
+}
+
+// A WGSL module has every entry point of a pipeline, GLSL has one stage per
+// module with `main` as its entry. Backends without WGSL translate it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ShaderSource {
+    Wgsl {
+        label: String,
+        code: String,
+    },
+    Glsl {
+        label: String,
+        code: String,
+        stage: ShaderStage,
+    },
+}
+
+impl ShaderSource {
+    pub fn wgsl(label: &str, code: &str) -> Self {
+        Self::Wgsl {
+            label: label.to_string(),
+            code: code.to_string(),
+        }
+    }
+
+    pub fn glsl(label: &str, code: &str, stage: ShaderStage) -> Self {
+        Self::Glsl {
+            label: label.to_string(),
+            code: code.to_string(),
+            stage,
+        }
+    }
+
+    pub fn get_label(&self) -> &str {
+        match self {
+            Self::Wgsl { label, .. } | Self::Glsl { label, .. } => label,
+        }
+    }
+
+    pub fn get_code(&self) -> &str {
+        match self {
+            Self::Wgsl { code, .. } | Self::Glsl { code, .. } => code,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VertexFormat {
+    Float32,
+    Float32x2,
+    Float32x3,
+    Float32x4,
+    Unorm8x4,
+    Uint32,
+}
+
+impl VertexFormat {
+    pub fn get_size(&self) -> u64 {
+        match self {
+            Self::Float32 | Self::Unorm8x4 | Self::Uint32 => 4,
+            Self::Float32x2 => 8,
+            Self::Float32x3 => 12,
+            Self::Float32x4 => 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexAttribute {
+    pub location: u32,
+    pub format: VertexFormat,
+    pub offset: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VertexLayout {
+    pub stride: u64,
+    // advances per instance instead of per vertex
+    pub per_instance: bool,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+impl VertexLayout {
+    // Attributes packed one after the other at locations 0, 1, 2...
+    pub fn packed(formats: &[VertexFormat]) -> Self {
+        let mut offset = 0;
+        let attributes = formats
+            .iter()
+            .enumerate()
+            .map(|(location, format)| {
+                let attribute = VertexAttribute {
+                    location: location as u32,
+                    format: *format,
+                    offset,
+                };
+                offset += format.get_size();
+                attribute
+            })
+            .collect();
+        Self {
+            stride: offset,
+            per_instance: false,
+            attributes,
+        }
+    }
+}
+
+// What the shader expects at each binding of group 0, in binding order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BindingKind {
+    UniformBuffer,
+    StorageBuffer,
+    Texture,
+    Sampler,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Filter {
+    Nearest,
+    Linear,
+}
+
+// What happens to texture coordinates outside 0..1
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressMode {
+    ClampToEdge,
+    Repeat,
+    MirrorRepeat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sampler {
+    pub filter: Filter,
+    // between mip levels
+    pub mipmap_filter: Filter,
+    pub address_mode: AddressMode,
+}
+
+impl Sampler {
+    pub const ADDRESS_MODES: [AddressMode; 3] = [
+        AddressMode::ClampToEdge,
+        AddressMode::Repeat,
+        AddressMode::MirrorRepeat,
+    ];
+
+    // Clamped, mipmaps filtered like the texture
+    pub fn new(filter: Filter) -> Self {
+        Self {
+            filter,
+            mipmap_filter: filter,
+            address_mode: AddressMode::ClampToEdge,
+        }
+    }
+
+    pub fn with_address_mode(mut self, address_mode: AddressMode) -> Self {
+        self.address_mode = address_mode;
+        self
+    }
+
+    pub fn with_mipmap_filter(mut self, mipmap_filter: Filter) -> Self {
+        self.mipmap_filter = mipmap_filter;
+        self
+    }
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Self::new(Filter::Linear)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Buffer(Buffer),
+    Texture(Texture),
+    Sampler(Sampler),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    Opaque,
+    Alpha,
+    // the colors are already multiplied by their alpha, e.g. egui's
+    PremultipliedAlpha,
+    Additive,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PrimitiveTopology {
+    TriangleList,
+    TriangleStrip,
+    LineList,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PipelineDescriptor {
+    pub label: String,
+    pub shader: Shader,
+    // the fragment stage from another module, e.g. with GLSL
+    pub fragment_shader: Option<Shader>,
+    pub vertex_entry: String,
+    pub fragment_entry: String,
+    pub vertex_layouts: Vec<VertexLayout>,
+    pub bindings: Vec<BindingKind>,
+    pub target_format: TextureFormat,
+    pub depth_format: Option<TextureFormat>,
+    pub blend: BlendMode,
+    pub topology: PrimitiveTopology,
+}
+
+impl PipelineDescriptor {
+    pub fn new(label: &str, shader: Shader, target_format: TextureFormat) -> Self {
+        Self {
+            label: label.to_string(),
+            shader,
+            fragment_shader: None,
+            vertex_entry: "vs_main".to_string(),
+            fragment_entry: "fs_main".to_string(),
+            vertex_layouts: Vec::new(),
+            bindings: Vec::new(),
+            target_format,
+            depth_format: None,
+            blend: BlendMode::Alpha,
+            topology: PrimitiveTopology::TriangleList,
+        }
+    }
+
+    // Separate vertex and fragment modules, both entered at `main`
+    pub fn from_stages(
+        label: &str,
+        vertex: Shader,
+        fragment: Shader,
+        target_format: TextureFormat,
+    ) -> Self {
+        let mut desc = Self::new(label, vertex, target_format);
+        desc.fragment_shader = Some(fragment);
+        desc.vertex_entry = "main".to_string();
+        desc.fragment_entry = "main".to_string();
+        desc
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderTarget {
+    // the window's swapchain image of this frame
+    Surface,
+    Texture(Texture),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommand {
+    SetPipeline(Pipeline),
+    SetBindings(Vec<Binding>),
+    SetVertexBuffer {
+        slot: u32,
+        buffer: Buffer,
+    },
+    SetIndexBuffer(Buffer),
+    // physical pixels from the top left of the target
+    SetViewport {
+        position: Vec2,
+        size: Vec2,
+    },
+    SetScissor {
+        position: (u32, u32),
+        size: (u32, u32),
+    },
+    Draw {
+        vertices: Range<u32>,
+        instances: Range<u32>,
+    },
+    // 32 bit indices
+    DrawIndexed {
+        indices: Range<u32>,
+        base_vertex: i32,
+        instances: Range<u32>,
+    },
+}
+
+// How long the GPU spent on one pass
+#[derive(Debug, Clone, PartialEq)]
+pub struct PassTiming {
+    pub label: String,
+    pub gpu_time: Duration,
+}
+
+// Draws into one target, recorded up front and handed to `submit`
+//
+//     let mut pass = RenderPass::new("sprites", RenderTarget::Surface);
+//     pass.clear(Vec4::new(0.1, 0.1, 0.1, 1.0));
+//     pass.set_pipeline(pipeline);
+//     pass.set_bindings(vec![Binding::Buffer(camera)]);
+//     pass.set_vertex_buffer(0, quads);
+//     pass.draw(0..6, 0..1);
+//     api.submit(&[pass])?;
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderPass {
+    pub label: String,
+    pub target: RenderTarget,
+    // None keeps what the target already holds
+    pub clear_color: Option<Vec4>,
+    pub depth: Option<Texture>,
+    pub commands: Vec<DrawCommand>,
+}
+
+impl RenderPass {
+    pub fn new(label: &str, target: RenderTarget) -> Self {
+        Self {
+            label: label.to_string(),
+            target,
+            clear_color: None,
+            depth: None,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self, color: Vec4) {
+        self.clear_color = Some(color);
+    }
+
+    // Cleared to the far plane at the start of the pass
+    pub fn set_depth(&mut self, depth: Texture) {
+        self.depth = Some(depth);
+    }
+
+    pub fn set_pipeline(&mut self, pipeline: Pipeline) {
+        self.commands.push(DrawCommand::SetPipeline(pipeline));
+    }
+
+    pub fn set_bindings(&mut self, bindings: Vec<Binding>) {
+        self.commands.push(DrawCommand::SetBindings(bindings));
+    }
+
+    pub fn set_vertex_buffer(&mut self, slot: u32, buffer: Buffer) {
+        self.commands
+            .push(DrawCommand::SetVertexBuffer { slot, buffer });
+    }
+
+    pub fn set_index_buffer(&mut self, buffer: Buffer) {
+        self.commands.push(DrawCommand::SetIndexBuffer(buffer));
+    }
+
+    pub fn set_viewport(&mut self, position: Vec2, size: Vec2) {
+        self.commands
+            .push(DrawCommand::SetViewport { position, size });
+    }
+
+    pub fn set_scissor(&mut self, position: (u32, u32), size: (u32, u32)) {
+        self.commands
+            .push(DrawCommand::SetScissor { position, size });
+    }
+
+    pub fn draw(&mut self, vertices: Range<u32>, instances: Range<u32>) {
+        self.commands.push(DrawCommand::Draw {
+            vertices,
+            instances,
+        });
+    }
+
+    pub fn draw_indexed(&mut self, indices: Range<u32>, base_vertex: i32, instances: Range<u32>) {
+        self.commands.push(DrawCommand::DrawIndexed {
+            indices,
+            base_vertex,
+            instances,
+        });
+    }
+
+    pub fn get_draw_count(&self) -> usize {
+        self.commands
+            .iter()
+            .filter(|command| {
+                matches!(
+                    command,
+                    DrawCommand::Draw { .. } | DrawCommand::DrawIndexed { .. }
+                )
+            })
+            .count()
+    }
+}
+
+// The graphics API behind the renderers. Higher level renderers only talk
+// to this, so the backend can be switched with a feature flag without
+// touching them. Resources are handles, they stay valid until destroyed.
+pub trait RendererAPI {
+    fn get_backend(&self) -> RendererBackend;
+
+    // Connects to the window to draw into, `size` in physical pixels
+    fn attach(
+        &mut self,
+        window: &dyn Window,
+        size: (u32, u32),
+        vsync: bool,
+    ) -> Result<(), RendererErrors>;
+
+    // Call on every window resize, sizes of 0 are ignored
+    fn resize(&mut self, width: u32, height: u32);
+
+    // Reconfigures the swapchain, returns the mode in use which falls back
+    // to a supported one. Before `attach` it replaces the vsync choice.
+    fn set_present_mode(&mut self, mode: PresentMode) -> PresentMode;
+
+    fn get_present_mode(&self) -> PresentMode;
+
+    fn get_surface_size(&self) -> (u32, u32);
+
+    // Format of the window's images, pipelines drawing to the surface use it
+    fn get_surface_format(&self) -> TextureFormat;
+
+    fn create_buffer(
+        &mut self,
+        desc: &BufferDescriptor,
+        contents: Option<&[u8]>,
+    ) -> Result<Buffer, RendererErrors>;
+
+    fn write_buffer(
+        &mut self,
+        buffer: Buffer,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), RendererErrors>;
+
+    fn create_texture(&mut self, desc: &TextureDescriptor) -> Result<Texture, RendererErrors>;
+
+    // Replaces every pixel, rows are tightly packed
+    fn write_texture(&mut self, texture: Texture, pixels: &[u8]) -> Result<(), RendererErrors> {
+        self.write_texture_mip(texture, 0, pixels)
+    }
+
+    // Replaces every pixel of one mip level
+    fn write_texture_mip(
+        &mut self,
+        texture: Texture,
+        level: u32,
+        pixels: &[u8],
+    ) -> Result<(), RendererErrors>;
+
+    fn create_shader(&mut self, source: &ShaderSource) -> Result<Shader, RendererErrors>;
+
+    fn create_pipeline(&mut self, desc: &PipelineDescriptor) -> Result<Pipeline, RendererErrors>;
+
+    fn destroy(&mut self, resource: Resource);
+
+    // Runs the passes in order
+    fn submit(&mut self, passes: &[RenderPass]) -> Result<(), RendererErrors>;
+
+    // Shows what was drawn to the surface this frame
+    fn present(&mut self) -> Result<(), RendererErrors>;
+
+    // Starts copying an rgba8 or bgra8 target back to the CPU without
+    // waiting for the GPU. Reading the surface copies the frame presented
+    // next, so request it before `present`.
+    fn request_readback(&mut self, target: RenderTarget) -> Result<Readback, RendererErrors>;
+
+    // The pixels once the copy finished, None while it is in flight
+    fn take_readback(
+        &mut self,
+        readback: Readback,
+    ) -> Option<Result<ReadbackImage, RendererErrors>>;
+
+    // GPU times of the passes whose results came back since the last call,
+    // usually a frame or two late. Empty where timestamps are unsupported.
+    fn take_pass_timings(&mut self) -> Vec<PassTiming>;
+
+    // Every buffer and texture the backend created and has not destroyed
+    // yet, budgets are set here too
+    fn get_memory(&self) -> &GpuMemoryTracker;
+
+    fn get_memory_mut(&mut self) -> &mut GpuMemoryTracker;
+
+    // The tracker's entry of a buffer or texture, for TextureResidency
+    fn get_allocation(&self, resource: Resource) -> Option<AllocationId>;
+
+    // Keeps only the mip levels from `base_mip` on and frees the larger ones,
+    // the smallest level always stays. Levels that come back have to be
+    // written again with `write_texture_mip`. Render targets keep all mips.
+    fn set_base_mip(&mut self, texture: Texture, base_mip: u32) -> Result<(), RendererErrors>;
+
+    // Applies what TextureResidency::enforce_budget decided for a texture
+    // added as `TextureId(texture.0)`
+    fn apply_mip_change(&mut self, change: &MipChange) -> Result<(), RendererErrors> {
+        self.set_base_mip(Texture(change.texture.0), change.base_mip as u32)
+    }
+}
+
+// The renderer of the default backend, wgpu when it is enabled
+pub fn create_default_renderer() -> Box<dyn RendererAPI> {
+    #[cfg(feature = "wgpu")]
+    return Box::new(wgpu_renderer::WgpuRenderer::new());
+
+    #[cfg(not(feature = "wgpu"))]
+    return Box::new(headless::HeadlessRenderer::new());
+}
@@ -0,0 +1,1332 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use log::{info, warn};
+use wgpu::util::DeviceExt;
+
+use crate::{
+    core::window::Window,
+    renderer::gpu_memory::{AllocationId, GpuMemoryCategory, GpuMemoryTracker},
+};
+
+use super::{
+    AddressMode, Binding, BindingKind, BlendMode, Buffer, BufferDescriptor, BufferUsage,
+    DrawCommand, Filter, PassTiming, Pipeline, PipelineDescriptor, PresentMode, PrimitiveTopology,
+    Readback, ReadbackImage, RenderPass, RenderTarget, RendererAPI, RendererBackend,
+    RendererErrors, Resource, Sampler, Shader, ShaderSource, ShaderStage, Texture,
+    TextureDescriptor, TextureFormat, VertexFormat,
+};
+
+struct GpuContext {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    surface: wgpu::Surface<'static>,
+    config: wgpu::SurfaceConfiguration,
+    present_modes: Vec<PresentMode>,
+    // whether passes are timed, and nanoseconds per timestamp tick
+    timestamps: bool,
+    timestamp_period: f32,
+    // every combination is made up front, there are only a dozen
+    samplers: HashMap<Sampler, wgpu::Sampler>,
+}
+
+type MapState = Arc<Mutex<Option<Result<(), String>>>>;
+
+// Maps the buffer for reading once the GPU is done with it, `mapped` is set
+// when that happened
+fn map_read(buffer: &wgpu::Buffer, mapped: &MapState) {
+    let mapped = mapped.clone();
+    buffer
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, move |result| {
+            if let Ok(mut mapped) = mapped.lock() {
+                *mapped = Some(result.map_err(|err| err.to_string()));
+            }
+        });
+}
+
+// Submits timed at once, later ones go untimed until the GPU caught up
+const TIMING_SLOTS: usize = 3;
+
+// Queries for the begin and end timestamps of every pass of one submit, and
+// the buffers they are read back through. Slots are reused once read.
+struct GpuTimings {
+    query_set: wgpu::QuerySet,
+    resolve: wgpu::Buffer,
+    buffer: wgpu::Buffer,
+    // how many queries the slot has room for
+    capacity: u32,
+    // the passes of the submit being timed, None while the slot is free
+    labels: Option<Vec<String>>,
+    mapped: MapState,
+}
+
+impl GpuTimings {
+    fn new(device: &wgpu::Device, capacity: u32) -> Self {
+        let size = capacity as u64 * 8;
+        Self {
+            query_set: device.create_query_set(&wgpu::QuerySetDescriptor {
+                label: Some("pass timings"),
+                ty: wgpu::QueryType::Timestamp,
+                count: capacity,
+            }),
+            resolve: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("pass timings resolve"),
+                size,
+                usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("pass timings"),
+                size,
+                usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            capacity,
+            labels: None,
+            mapped: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    // Frees the slot, reading its timings if they arrived
+    fn read(&mut self, period: f32) -> Vec<PassTiming> {
+        let labels = self.labels.take().unwrap_or_default();
+        let mapped = self.mapped.lock().ok().and_then(|mut mapped| mapped.take());
+        if let Some(Err(err)) = mapped {
+            warn!("unable to read pass timings {}", err);
+            return Vec::new();
+        }
+
+        let mut timings = Vec::with_capacity(labels.len());
+        {
+            let data = self.buffer.slice(..).get_mapped_range();
+            let ticks: Vec<u64> = data
+                .chunks_exact(8)
+                .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap_or_default()))
+                .collect();
+            for (label, pair) in labels.into_iter().zip(ticks.chunks_exact(2)) {
+                let nanos = pair[1].saturating_sub(pair[0]) as f64 * period as f64;
+                timings.push(PassTiming {
+                    label,
+                    gpu_time: Duration::from_nanos(nanos as u64),
+                });
+            }
+        }
+        self.buffer.unmap();
+        timings
+    }
+
+    fn is_mapped(&self) -> bool {
+        self.mapped
+            .lock()
+            .map(|mapped| mapped.is_some())
+            .unwrap_or(false)
+    }
+}
+
+// A copy into a mappable buffer, rows padded to what wgpu copies
+struct GpuReadback {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+    padded_row: u32,
+    format: TextureFormat,
+    mapped: MapState,
+}
+
+impl GpuReadback {
+    fn map(&self) {
+        map_read(&self.buffer, &self.mapped);
+    }
+
+    fn read(self) -> Result<ReadbackImage, RendererErrors> {
+        let row = (self.width * 4) as usize;
+        let mut pixels = Vec::with_capacity(row * self.height as usize);
+        {
+            let data = self.buffer.slice(..).get_mapped_range();
+            for padded in data.chunks(self.padded_row as usize) {
+                pixels.extend_from_slice(&padded[..row]);
+            }
+        }
+        self.buffer.unmap();
+        ReadbackImage::from_pixels(self.width, self.height, self.format, pixels)
+    }
+}
+
+enum ReadbackState {
+    // copied when the next frame is presented
+    Surface,
+    Copying(GpuReadback),
+    Failed(String),
+}
+
+struct GpuTexture {
+    desc: TextureDescriptor,
+    // holds the mips from `base_mip` on, its level 0 is that mip
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    base_mip: u32,
+}
+
+fn create_gpu_texture(
+    device: &wgpu::Device,
+    desc: &TextureDescriptor,
+    base_mip: u32,
+) -> wgpu::Texture {
+    let mut usage = wgpu::TextureUsages::TEXTURE_BINDING
+        | wgpu::TextureUsages::COPY_DST
+        | wgpu::TextureUsages::COPY_SRC;
+    if desc.render_target || desc.format.is_depth() {
+        usage |= wgpu::TextureUsages::RENDER_ATTACHMENT;
+    }
+    let (width, height) = desc.get_mip_size(base_mip);
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some(&desc.label),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: desc.mip_levels.max(1) - base_mip,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: to_wgpu_format(desc.format),
+        usage,
+        view_formats: &[],
+    })
+}
+
+struct GpuPipeline {
+    pipeline: wgpu::RenderPipeline,
+    layout: wgpu::BindGroupLayout,
+}
+
+// Draws through wgpu, which runs on Vulkan, Metal, DX12 or OpenGL. Nothing
+// can be created before `attach`, the device has to match the window.
+pub struct WgpuRenderer {
+    backends: wgpu::Backends,
+    gpu: Option<GpuContext>,
+    // the swapchain image of this frame, taken on the first pass drawing
+    // to the surface
+    frame: Option<wgpu::SurfaceTexture>,
+    // asked for before `attach`, overrides its vsync flag
+    present_mode: Option<PresentMode>,
+    readbacks: HashMap<Readback, ReadbackState>,
+    timings: Vec<GpuTimings>,
+    next_id: u64,
+    buffers: HashMap<Buffer, (BufferDescriptor, wgpu::Buffer)>,
+    textures: HashMap<Texture, GpuTexture>,
+    shaders: HashMap<Shader, wgpu::ShaderModule>,
+    pipelines: HashMap<Pipeline, GpuPipeline>,
+    // the swapchain images are not counted, wgpu owns them
+    memory: GpuMemoryTracker,
+    allocations: HashMap<Resource, AllocationId>,
+}
+
+impl WgpuRenderer {
+    pub fn new() -> Self {
+        Self::with_backends(wgpu::Backends::all())
+    }
+
+    // Forces the native API, e.g. `wgpu::Backends::GL` or `VULKAN`
+    pub fn with_backends(backends: wgpu::Backends) -> Self {
+        Self {
+            backends,
+            gpu: None,
+            frame: None,
+            present_mode: None,
+            readbacks: HashMap::new(),
+            timings: Vec::new(),
+            next_id: 0,
+            buffers: HashMap::new(),
+            textures: HashMap::new(),
+            shaders: HashMap::new(),
+            pipelines: HashMap::new(),
+            memory: GpuMemoryTracker::new(),
+            allocations: HashMap::new(),
+        }
+    }
+
+    // For renderers that need wgpu features the API does not wrap
+    pub fn get_device(&self) -> Option<&wgpu::Device> {
+        self.gpu.as_ref().map(|gpu| &gpu.device)
+    }
+
+    pub fn get_queue(&self) -> Option<&wgpu::Queue> {
+        self.gpu.as_ref().map(|gpu| &gpu.queue)
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn get_gpu(&self) -> Result<&GpuContext, RendererErrors> {
+        self.gpu.as_ref().ok_or(RendererErrors::NotAttached)
+    }
+
+    fn get_texture(&self, texture: Texture) -> Result<&GpuTexture, RendererErrors> {
+        self.textures
+            .get(&texture)
+            .ok_or(RendererErrors::UnknownResource(Resource::Texture(texture)))
+    }
+
+    fn get_buffer(&self, buffer: Buffer) -> Result<&wgpu::Buffer, RendererErrors> {
+        self.buffers
+            .get(&buffer)
+            .map(|(_, buffer)| buffer)
+            .ok_or(RendererErrors::UnknownResource(Resource::Buffer(buffer)))
+    }
+
+    fn get_pipeline(&self, pipeline: Pipeline) -> Result<&GpuPipeline, RendererErrors> {
+        self.pipelines
+            .get(&pipeline)
+            .ok_or(RendererErrors::UnknownResource(Resource::Pipeline(
+                pipeline,
+            )))
+    }
+
+    // A free timing slot with room for the queries, None when timestamps
+    // are not supported or every slot is still waiting for the GPU
+    fn take_timing_slot(&mut self, query_count: u32) -> Option<usize> {
+        let gpu = self.gpu.as_ref()?;
+        if !gpu.timestamps || query_count == 0 {
+            return None;
+        }
+        let slot = match self
+            .timings
+            .iter()
+            .position(|timings| timings.labels.is_none())
+        {
+            Some(slot) => slot,
+            None if self.timings.len() < TIMING_SLOTS => {
+                self.timings.push(GpuTimings::new(&gpu.device, query_count));
+                self.timings.len() - 1
+            }
+            None => return None,
+        };
+        if self.timings[slot].capacity < query_count {
+            self.timings[slot] = GpuTimings::new(&gpu.device, query_count);
+        }
+        Some(slot)
+    }
+
+    fn acquire_frame(&mut self) -> Result<(), RendererErrors> {
+        if self.frame.is_some() {
+            return Ok(());
+        }
+        let gpu = self.get_gpu()?;
+        let frame = match gpu.surface.get_current_texture() {
+            Ok(frame) => frame,
+            // the window changed under us, configuring again recovers
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                gpu.surface.configure(&gpu.device, &gpu.config);
+                gpu.surface
+                    .get_current_texture()
+                    .map_err(|err| RendererErrors::Surface(err.to_string()))?
+            }
+            Err(err) => return Err(RendererErrors::Surface(err.to_string())),
+        };
+        self.frame = Some(frame);
+        Ok(())
+    }
+
+    fn encode_readback(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+        format: TextureFormat,
+    ) -> Result<GpuReadback, RendererErrors> {
+        if format.get_bytes_per_pixel() != 4 || format.is_depth() {
+            return Err(RendererErrors::Backend(format!(
+                "unable to read back {:?} pixels",
+                format
+            )));
+        }
+        let gpu = self.get_gpu()?;
+        let (width, height) = (texture.width(), texture.height());
+        let padded_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: padded_row as u64 * height as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        Ok(GpuReadback {
+            buffer,
+            width,
+            height,
+            padded_row,
+            format,
+            mapped: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    fn encode_pass(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        pass: &RenderPass,
+        surface_view: Option<&wgpu::TextureView>,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) -> Result<(), RendererErrors> {
+        let gpu = self.get_gpu()?;
+        let view = match pass.target {
+            RenderTarget::Surface => surface_view.ok_or(RendererErrors::NotAttached)?,
+            RenderTarget::Texture(texture) => &self.get_texture(texture)?.view,
+        };
+        let load = match pass.clear_color {
+            Some(color) => wgpu::LoadOp::Clear(wgpu::Color {
+                r: color.x as f64,
+                g: color.y as f64,
+                b: color.z as f64,
+                a: color.w as f64,
+            }),
+            None => wgpu::LoadOp::Load,
+        };
+        let depth_view = match pass.depth {
+            Some(depth) => Some(&self.get_texture(depth)?.view),
+            None => None,
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(&pass.label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: depth_view.map(|view| {
+                wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }
+            }),
+            timestamp_writes,
+            occlusion_query_set: None,
+        });
+
+        let mut current: Option<&GpuPipeline> = None;
+        for command in pass.commands.iter() {
+            match command {
+                DrawCommand::SetPipeline(pipeline) => {
+                    let pipeline = self.get_pipeline(*pipeline)?;
+                    render_pass.set_pipeline(&pipeline.pipeline);
+                    current = Some(pipeline);
+                }
+                DrawCommand::SetBindings(bindings) => {
+                    let pipeline = current.ok_or_else(|| {
+                        RendererErrors::Backend("bindings set before a pipeline".to_string())
+                    })?;
+                    let mut entries = Vec::with_capacity(bindings.len());
+                    for (index, binding) in bindings.iter().enumerate() {
+                        let resource = match binding {
+                            Binding::Buffer(buffer) => {
+                                self.get_buffer(*buffer)?.as_entire_binding()
+                            }
+                            Binding::Texture(texture) => wgpu::BindingResource::TextureView(
+                                &self.get_texture(*texture)?.view,
+                            ),
+                            Binding::Sampler(sampler) => {
+                                wgpu::BindingResource::Sampler(&gpu.samplers[sampler])
+                            }
+                        };
+                        entries.push(wgpu::BindGroupEntry {
+                            binding: index as u32,
+                            resource,
+                        });
+                    }
+                    // made per pass, cheap next to the draws they feed
+                    let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                        label: Some(&pass.label),
+                        layout: &pipeline.layout,
+                        entries: &entries,
+                    });
+                    render_pass.set_bind_group(0, &bind_group, &[]);
+                }
+                DrawCommand::SetVertexBuffer { slot, buffer } => {
+                    render_pass.set_vertex_buffer(*slot, self.get_buffer(*buffer)?.slice(..));
+                }
+                DrawCommand::SetIndexBuffer(buffer) => {
+                    render_pass.set_index_buffer(
+                        self.get_buffer(*buffer)?.slice(..),
+                        wgpu::IndexFormat::Uint32,
+                    );
+                }
+                DrawCommand::SetViewport { position, size } => {
+                    render_pass.set_viewport(position.x, position.y, size.x, size.y, 0.0, 1.0);
+                }
+                DrawCommand::SetScissor { position, size } => {
+                    render_pass.set_scissor_rect(position.0, position.1, size.0, size.1);
+                }
+                DrawCommand::Draw {
+                    vertices,
+                    instances,
+                } => render_pass.draw(vertices.clone(), instances.clone()),
+                DrawCommand::DrawIndexed {
+                    indices,
+                    base_vertex,
+                    instances,
+                } => render_pass.draw_indexed(indices.clone(), *base_vertex, instances.clone()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for WgpuRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn to_wgpu_filter(filter: Filter) -> wgpu::FilterMode {
+    match filter {
+        Filter::Linear => wgpu::FilterMode::Linear,
+        Filter::Nearest => wgpu::FilterMode::Nearest,
+    }
+}
+
+fn to_wgpu_address_mode(address_mode: AddressMode) -> wgpu::AddressMode {
+    match address_mode {
+        AddressMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        AddressMode::Repeat => wgpu::AddressMode::Repeat,
+        AddressMode::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+    }
+}
+
+fn to_wgpu_format(format: TextureFormat) -> wgpu::TextureFormat {
+    match format {
+        TextureFormat::Rgba8Unorm => wgpu::TextureFormat::Rgba8Unorm,
+        TextureFormat::Rgba8UnormSrgb => wgpu::TextureFormat::Rgba8UnormSrgb,
+        TextureFormat::Bgra8Unorm => wgpu::TextureFormat::Bgra8Unorm,
+        TextureFormat::Bgra8UnormSrgb => wgpu::TextureFormat::Bgra8UnormSrgb,
+        TextureFormat::R8Unorm => wgpu::TextureFormat::R8Unorm,
+        TextureFormat::Rgba16Float => wgpu::TextureFormat::Rgba16Float,
+        TextureFormat::Depth32Float => wgpu::TextureFormat::Depth32Float,
+    }
+}
+
+fn from_wgpu_format(format: wgpu::TextureFormat) -> Option<TextureFormat> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm => Some(TextureFormat::Rgba8Unorm),
+        wgpu::TextureFormat::Rgba8UnormSrgb => Some(TextureFormat::Rgba8UnormSrgb),
+        wgpu::TextureFormat::Bgra8Unorm => Some(TextureFormat::Bgra8Unorm),
+        wgpu::TextureFormat::Bgra8UnormSrgb => Some(TextureFormat::Bgra8UnormSrgb),
+        wgpu::TextureFormat::Rgba16Float => Some(TextureFormat::Rgba16Float),
+        _ => None,
+    }
+}
+
+fn to_vertex_format(format: VertexFormat) -> wgpu::VertexFormat {
+    match format {
+        VertexFormat::Float32 => wgpu::VertexFormat::Float32,
+        VertexFormat::Float32x2 => wgpu::VertexFormat::Float32x2,
+        VertexFormat::Float32x3 => wgpu::VertexFormat::Float32x3,
+        VertexFormat::Float32x4 => wgpu::VertexFormat::Float32x4,
+        VertexFormat::Unorm8x4 => wgpu::VertexFormat::Unorm8x4,
+        VertexFormat::Uint32 => wgpu::VertexFormat::Uint32,
+    }
+}
+
+fn to_blend(blend: BlendMode) -> Option<wgpu::BlendState> {
+    match blend {
+        BlendMode::Opaque => None,
+        BlendMode::Alpha => Some(wgpu::BlendState::ALPHA_BLENDING),
+        BlendMode::PremultipliedAlpha => Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+        BlendMode::Additive => Some(wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::OVER,
+        }),
+    }
+}
+
+fn to_wgpu_present_mode(mode: PresentMode) -> wgpu::PresentMode {
+    match mode {
+        PresentMode::Fifo => wgpu::PresentMode::Fifo,
+        PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+        PresentMode::Immediate => wgpu::PresentMode::Immediate,
+    }
+}
+
+fn from_wgpu_present_mode(mode: wgpu::PresentMode) -> Option<PresentMode> {
+    match mode {
+        wgpu::PresentMode::Fifo | wgpu::PresentMode::FifoRelaxed => Some(PresentMode::Fifo),
+        wgpu::PresentMode::Mailbox => Some(PresentMode::Mailbox),
+        wgpu::PresentMode::Immediate => Some(PresentMode::Immediate),
+        _ => None,
+    }
+}
+
+impl RendererAPI for WgpuRenderer {
+    fn get_backend(&self) -> RendererBackend {
+        RendererBackend::Wgpu
+    }
+
+    fn attach(
+        &mut self,
+        window: &dyn Window,
+        size: (u32, u32),
+        vsync: bool,
+    ) -> Result<(), RendererErrors> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: self.backends,
+            ..Default::default()
+        });
+        let target = wgpu::SurfaceTargetUnsafe::RawHandle {
+            raw_display_handle: window
+                .get_display_handle()
+                .map_err(|err| RendererErrors::Surface(err.to_string()))?
+                .as_raw(),
+            raw_window_handle: window
+                .get_window_handle()
+                .map_err(|err| RendererErrors::Surface(err.to_string()))?
+                .as_raw(),
+        };
+        // SAFETY: the window subsystem keeps its windows until shutdown, the
+        // renderer has to be dropped before the window it is attached to
+        let surface = unsafe { instance.create_surface_unsafe(target) }
+            .map_err(|err| RendererErrors::Surface(err.to_string()))?;
+
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            compatible_surface: Some(&surface),
+            force_fallback_adapter: false,
+        }))
+        .ok_or_else(|| {
+            RendererErrors::NoAdapter("no adapter can draw to the window".to_string())
+        })?;
+        let info = adapter.get_info();
+        info!("rendering with {} on {:?}", info.name, info.backend);
+
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("aloy"),
+                // pass timings where the adapter can do them
+                required_features: adapter.features() & wgpu::Features::TIMESTAMP_QUERY,
+                required_limits:
+                    wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+                memory_hints: wgpu::MemoryHints::default(),
+            },
+            None,
+        ))
+        .map_err(|err| RendererErrors::NoAdapter(err.to_string()))?;
+
+        let capabilities = surface.get_capabilities(&adapter);
+        let format = capabilities
+            .formats
+            .iter()
+            .copied()
+            .find(|format| format.is_srgb() && from_wgpu_format(*format).is_some())
+            .or_else(|| capabilities.formats.first().copied())
+            .ok_or_else(|| RendererErrors::Surface("no supported format".to_string()))?;
+        let present_modes: Vec<PresentMode> = capabilities
+            .present_modes
+            .iter()
+            .filter_map(|mode| from_wgpu_present_mode(*mode))
+            .collect();
+        let present_mode = self
+            .present_mode
+            .unwrap_or(PresentMode::from_vsync(vsync))
+            .resolve(&present_modes);
+        info!("presenting with {:?}", present_mode);
+        let mut usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        // for screenshots, where the platform allows it
+        if capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+            usage |= wgpu::TextureUsages::COPY_SRC;
+        }
+        let config = wgpu::SurfaceConfiguration {
+            usage,
+            format,
+            width: size.0.max(1),
+            height: size.1.max(1),
+            present_mode: to_wgpu_present_mode(present_mode),
+            alpha_mode: capabilities.alpha_modes[0],
+            view_formats: Vec::new(),
+            desired_maximum_frame_latency: 2,
+        };
+        surface.configure(&device, &config);
+
+        let mut samplers = HashMap::new();
+        for filter in [Filter::Linear, Filter::Nearest] {
+            for mipmap_filter in [Filter::Linear, Filter::Nearest] {
+                for address_mode in Sampler::ADDRESS_MODES {
+                    let sampler = Sampler {
+                        filter,
+                        mipmap_filter,
+                        address_mode,
+                    };
+                    let address_mode = to_wgpu_address_mode(address_mode);
+                    let wgpu_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+                        address_mode_u: address_mode,
+                        address_mode_v: address_mode,
+                        address_mode_w: address_mode,
+                        mag_filter: to_wgpu_filter(filter),
+                        min_filter: to_wgpu_filter(filter),
+                        mipmap_filter: to_wgpu_filter(mipmap_filter),
+                        ..Default::default()
+                    });
+                    samplers.insert(sampler, wgpu_sampler);
+                }
+            }
+        }
+        let timestamps = device.features().contains(wgpu::Features::TIMESTAMP_QUERY);
+        let timestamp_period = queue.get_timestamp_period();
+        self.gpu = Some(GpuContext {
+            device,
+            queue,
+            surface,
+            config,
+            present_modes,
+            timestamps,
+            timestamp_period,
+            samplers,
+        });
+        Ok(())
+    }
+
+    fn resize(&mut self, width: u32, height: u32) {
+        let Some(gpu) = self.gpu.as_mut() else {
+            return;
+        };
+        if width == 0 || height == 0 {
+            return;
+        }
+        gpu.config.width = width;
+        gpu.config.height = height;
+        gpu.surface.configure(&gpu.device, &gpu.config);
+    }
+
+    fn set_present_mode(&mut self, mode: PresentMode) -> PresentMode {
+        let Some(gpu) = self.gpu.as_mut() else {
+            self.present_mode = Some(mode);
+            return mode;
+        };
+        let resolved = mode.resolve(&gpu.present_modes);
+        if resolved != mode {
+            warn!(
+                "{:?} is not supported, presenting with {:?}",
+                mode, resolved
+            );
+        }
+        self.present_mode = Some(mode);
+        gpu.config.present_mode = to_wgpu_present_mode(resolved);
+        gpu.surface.configure(&gpu.device, &gpu.config);
+        resolved
+    }
+
+    fn get_present_mode(&self) -> PresentMode {
+        match self.gpu.as_ref() {
+            Some(gpu) => from_wgpu_present_mode(gpu.config.present_mode).unwrap_or_default(),
+            None => self.present_mode.unwrap_or_default(),
+        }
+    }
+
+    fn get_surface_size(&self) -> (u32, u32) {
+        self.gpu
+            .as_ref()
+            .map(|gpu| (gpu.config.width, gpu.config.height))
+            .unwrap_or((0, 0))
+    }
+
+    fn get_surface_format(&self) -> TextureFormat {
+        self.gpu
+            .as_ref()
+            .and_then(|gpu| from_wgpu_format(gpu.config.format))
+            .unwrap_or(TextureFormat::Bgra8UnormSrgb)
+    }
+
+    fn create_buffer(
+        &mut self,
+        desc: &BufferDescriptor,
+        contents: Option<&[u8]>,
+    ) -> Result<Buffer, RendererErrors> {
+        let gpu = self.get_gpu()?;
+        let usage = wgpu::BufferUsages::COPY_DST
+            | match desc.usage {
+                BufferUsage::Vertex => wgpu::BufferUsages::VERTEX,
+                BufferUsage::Index => wgpu::BufferUsages::INDEX,
+                BufferUsage::Uniform => wgpu::BufferUsages::UNIFORM,
+                BufferUsage::Storage => wgpu::BufferUsages::STORAGE,
+            };
+        let buffer = match contents {
+            Some(contents) if contents.len() as u64 > desc.size => {
+                return Err(RendererErrors::OutOfBounds {
+                    resource: Resource::Buffer(Buffer(0)),
+                    offset: 0,
+                    len: contents.len() as u64,
+                    size: desc.size,
+                })
+            }
+            Some(contents) => {
+                // sized to the descriptor, the rest stays zeroed
+                let mut data = contents.to_vec();
+                data.resize(desc.size as usize, 0);
+                gpu.device
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: Some(&desc.label),
+                        contents: &data,
+                        usage,
+                    })
+            }
+            None => gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(&desc.label),
+                size: desc.size,
+                usage,
+                mapped_at_creation: false,
+            }),
+        };
+        let handle = Buffer(self.next_id());
+        self.buffers.insert(handle, (desc.clone(), buffer));
+        let allocation = self.memory.allocate(GpuMemoryCategory::Buffer, desc.size);
+        self.allocations
+            .insert(Resource::Buffer(handle), allocation);
+        Ok(handle)
+    }
+
+    fn write_buffer(
+        &mut self,
+        buffer: Buffer,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<(), RendererErrors> {
+        let gpu = self.get_gpu()?;
+        let (desc, gpu_buffer) = self
+            .buffers
+            .get(&buffer)
+            .ok_or(RendererErrors::UnknownResource(Resource::Buffer(buffer)))?;
+        if offset + data.len() as u64 > desc.size {
+            return Err(RendererErrors::OutOfBounds {
+                resource: Resource::Buffer(buffer),
+                offset,
+                len: data.len() as u64,
+                size: desc.size,
+            });
+        }
+        gpu.queue.write_buffer(gpu_buffer, offset, data);
+        Ok(())
+    }
+
+    fn create_texture(&mut self, desc: &TextureDescriptor) -> Result<Texture, RendererErrors> {
+        let gpu = self.get_gpu()?;
+        let texture = create_gpu_texture(&gpu.device, desc, 0);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let handle = Texture(self.next_id());
+        self.textures.insert(
+            handle,
+            GpuTexture {
+                desc: desc.clone(),
+                texture,
+                view,
+                base_mip: 0,
+            },
+        );
+        let allocation = self.memory.allocate(
+            desc.get_memory_category(),
+            desc.get_mip_byte_sizes().iter().sum(),
+        );
+        self.allocations
+            .insert(Resource::Texture(handle), allocation);
+        Ok(handle)
+    }
+
+    fn write_texture_mip(
+        &mut self,
+        texture: Texture,
+        level: u32,
+        pixels: &[u8],
+    ) -> Result<(), RendererErrors> {
+        let gpu = self.get_gpu()?;
+        let gpu_texture = self.get_texture(texture)?;
+        let desc = &gpu_texture.desc;
+        let size = match level < desc.mip_levels.max(1) {
+            true => desc.get_mip_byte_size(level),
+            false => 0,
+        };
+        if pixels.len() as u64 != size || size == 0 {
+            return Err(RendererErrors::OutOfBounds {
+                resource: Resource::Texture(texture),
+                offset: level as u64,
+                len: pixels.len() as u64,
+                size,
+            });
+        }
+        // the level was dropped, there is nothing to write it to
+        if level < gpu_texture.base_mip {
+            return Ok(());
+        }
+        let (width, height) = desc.get_mip_size(level);
+        gpu.queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &gpu_texture.texture,
+                mip_level: level - gpu_texture.base_mip,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * desc.format.get_bytes_per_pixel()),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        Ok(())
+    }
+
+    fn create_shader(&mut self, source: &ShaderSource) -> Result<Shader, RendererErrors> {
+        let gpu = self.get_gpu()?;
+        let label = source.get_label();
+        let wgpu_source = match source {
+            ShaderSource::Wgsl { code, .. } => wgpu::ShaderSource::Wgsl(code.as_str().into()),
+            ShaderSource::Glsl { code, stage, .. } => wgpu::ShaderSource::Glsl {
+                shader: code.as_str().into(),
+                stage: match stage {
+                    ShaderStage::Vertex => wgpu::naga::ShaderStage::Vertex,
+                    ShaderStage::Fragment => wgpu::naga::ShaderStage::Fragment,
+                },
+                defines: Default::default(),
+            },
+        };
+        gpu.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some(label),
+                source: wgpu_source,
+            });
+        if let Some(err) = pollster::block_on(gpu.device.pop_error_scope()) {
+            return Err(RendererErrors::ShaderCompile(
+                label.to_string(),
+                err.to_string(),
+            ));
+        }
+        let handle = Shader(self.next_id());
+        self.shaders.insert(handle, module);
+        Ok(handle)
+    }
+
+    fn create_pipeline(&mut self, desc: &PipelineDescriptor) -> Result<Pipeline, RendererErrors> {
+        let gpu = self.get_gpu()?;
+        let module = self
+            .shaders
+            .get(&desc.shader)
+            .ok_or(RendererErrors::UnknownResource(Resource::Shader(
+                desc.shader,
+            )))?;
+        let fragment_shader = desc.fragment_shader.unwrap_or(desc.shader);
+        let fragment_module =
+            self.shaders
+                .get(&fragment_shader)
+                .ok_or(RendererErrors::UnknownResource(Resource::Shader(
+                    fragment_shader,
+                )))?;
+
+        let visibility = wgpu::ShaderStages::VERTEX_FRAGMENT;
+        let entries: Vec<wgpu::BindGroupLayoutEntry> = desc
+            .bindings
+            .iter()
+            .enumerate()
+            .map(|(index, kind)| wgpu::BindGroupLayoutEntry {
+                binding: index as u32,
+                visibility,
+                ty: match kind {
+                    BindingKind::UniformBuffer => wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    BindingKind::StorageBuffer => wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    BindingKind::Texture => wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    BindingKind::Sampler => {
+                        wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering)
+                    }
+                },
+                count: None,
+            })
+            .collect();
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some(&desc.label),
+                entries: &entries,
+            });
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(&desc.label),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            });
+
+        let attributes: Vec<Vec<wgpu::VertexAttribute>> = desc
+            .vertex_layouts
+            .iter()
+            .map(|layout| {
+                layout
+                    .attributes
+                    .iter()
+                    .map(|attribute| wgpu::VertexAttribute {
+                        format: to_vertex_format(attribute.format),
+                        offset: attribute.offset,
+                        shader_location: attribute.location,
+                    })
+                    .collect()
+            })
+            .collect();
+        let buffers: Vec<wgpu::VertexBufferLayout> = desc
+            .vertex_layouts
+            .iter()
+            .zip(attributes.iter())
+            .map(|(layout, attributes)| wgpu::VertexBufferLayout {
+                array_stride: layout.stride,
+                step_mode: match layout.per_instance {
+                    true => wgpu::VertexStepMode::Instance,
+                    false => wgpu::VertexStepMode::Vertex,
+                },
+                attributes,
+            })
+            .collect();
+
+        gpu.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = gpu
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(&desc.label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module,
+                    entry_point: Some(&desc.vertex_entry),
+                    compilation_options: Default::default(),
+                    buffers: &buffers,
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology: match desc.topology {
+                        PrimitiveTopology::TriangleList => wgpu::PrimitiveTopology::TriangleList,
+                        PrimitiveTopology::TriangleStrip => wgpu::PrimitiveTopology::TriangleStrip,
+                        PrimitiveTopology::LineList => wgpu::PrimitiveTopology::LineList,
+                    },
+                    ..Default::default()
+                },
+                depth_stencil: desc.depth_format.map(|format| wgpu::DepthStencilState {
+                    format: to_wgpu_format(format),
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multisample: Default::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: fragment_module,
+                    entry_point: Some(&desc.fragment_entry),
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: to_wgpu_format(desc.target_format),
+                        blend: to_blend(desc.blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                multiview: None,
+                cache: None,
+            });
+        if let Some(err) = pollster::block_on(gpu.device.pop_error_scope()) {
+            return Err(RendererErrors::PipelineCreation(
+                desc.label.clone(),
+                err.to_string(),
+            ));
+        }
+        let handle = Pipeline(self.next_id());
+        self.pipelines
+            .insert(handle, GpuPipeline { pipeline, layout });
+        Ok(handle)
+    }
+
+    fn destroy(&mut self, resource: Resource) {
+        match resource {
+            Resource::Buffer(buffer) => {
+                if let Some((_, buffer)) = self.buffers.remove(&buffer) {
+                    buffer.destroy();
+                }
+            }
+            Resource::Texture(texture) => {
+                if let Some(texture) = self.textures.remove(&texture) {
+                    texture.texture.destroy();
+                }
+            }
+            Resource::Shader(shader) => {
+                self.shaders.remove(&shader);
+            }
+            Resource::Pipeline(pipeline) => {
+                self.pipelines.remove(&pipeline);
+            }
+        }
+        if let Some(allocation) = self.allocations.remove(&resource) {
+            self.memory.free(allocation);
+        }
+    }
+
+    fn submit(&mut self, passes: &[RenderPass]) -> Result<(), RendererErrors> {
+        if passes
+            .iter()
+            .any(|pass| pass.target == RenderTarget::Surface)
+        {
+            self.acquire_frame()?;
+        }
+        let surface_view = self.frame.as_ref().map(|frame| {
+            frame
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        });
+
+        let query_count = passes.len() as u32 * 2;
+        let slot = self.take_timing_slot(query_count);
+        let gpu = self.get_gpu()?;
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("aloy"),
+            });
+        let timings = slot.map(|slot| &self.timings[slot]);
+        for (index, pass) in passes.iter().enumerate() {
+            let timestamp_writes = timings.map(|timings| wgpu::RenderPassTimestampWrites {
+                query_set: &timings.query_set,
+                beginning_of_pass_write_index: Some(index as u32 * 2),
+                end_of_pass_write_index: Some(index as u32 * 2 + 1),
+            });
+            self.encode_pass(&mut encoder, pass, surface_view.as_ref(), timestamp_writes)?;
+        }
+        if let Some(timings) = timings {
+            encoder.resolve_query_set(&timings.query_set, 0..query_count, &timings.resolve, 0);
+            encoder.copy_buffer_to_buffer(
+                &timings.resolve,
+                0,
+                &timings.buffer,
+                0,
+                query_count as u64 * 8,
+            );
+        }
+        gpu.queue.submit([encoder.finish()]);
+
+        if let Some(slot) = slot {
+            let timings = &mut self.timings[slot];
+            timings.labels = Some(passes.iter().map(|pass| pass.label.clone()).collect());
+            map_read(&timings.buffer, &timings.mapped);
+        }
+        Ok(())
+    }
+
+    fn present(&mut self) -> Result<(), RendererErrors> {
+        let Some(frame) = self.frame.take() else {
+            warn!("present without anything drawn to the surface");
+            return Ok(());
+        };
+        let waiting: Vec<Readback> = self
+            .readbacks
+            .iter()
+            .filter(|(_, state)| matches!(state, ReadbackState::Surface))
+            .map(|(readback, _)| *readback)
+            .collect();
+        if !waiting.is_empty() {
+            let gpu = self.get_gpu()?;
+            let format = self.get_surface_format();
+            let readable = gpu.config.usage.contains(wgpu::TextureUsages::COPY_SRC);
+            let mut encoder = gpu
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("screenshot"),
+                });
+            let mut copies = Vec::new();
+            for readback in waiting {
+                let copy = match readable {
+                    true => self.encode_readback(&mut encoder, &frame.texture, format),
+                    false => Err(RendererErrors::Surface(
+                        "the surface cannot be read back".to_string(),
+                    )),
+                };
+                copies.push((readback, copy));
+            }
+            gpu.queue.submit([encoder.finish()]);
+            for (readback, copy) in copies {
+                let state = match copy {
+                    Ok(copy) => {
+                        copy.map();
+                        ReadbackState::Copying(copy)
+                    }
+                    Err(err) => ReadbackState::Failed(err.to_string()),
+                };
+                self.readbacks.insert(readback, state);
+            }
+        }
+        frame.present();
+        Ok(())
+    }
+
+    fn request_readback(&mut self, target: RenderTarget) -> Result<Readback, RendererErrors> {
+        let readback = Readback(self.next_id());
+        let state = match target {
+            RenderTarget::Surface => ReadbackState::Surface,
+            RenderTarget::Texture(texture) => {
+                let gpu = self.get_gpu()?;
+                let gpu_texture = self.get_texture(texture)?;
+                let mut encoder =
+                    gpu.device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                            label: Some("readback"),
+                        });
+                let copy = self.encode_readback(
+                    &mut encoder,
+                    &gpu_texture.texture,
+                    gpu_texture.desc.format,
+                )?;
+                gpu.queue.submit([encoder.finish()]);
+                copy.map();
+                ReadbackState::Copying(copy)
+            }
+        };
+        self.readbacks.insert(readback, state);
+        Ok(readback)
+    }
+
+    fn get_memory(&self) -> &GpuMemoryTracker {
+        &self.memory
+    }
+
+    fn get_memory_mut(&mut self) -> &mut GpuMemoryTracker {
+        &mut self.memory
+    }
+
+    fn get_allocation(&self, resource: Resource) -> Option<AllocationId> {
+        self.allocations.get(&resource).copied()
+    }
+
+    // The texture is allocated again with only the resident levels, the
+    // levels both have are copied over on the GPU
+    fn set_base_mip(&mut self, texture: Texture, base_mip: u32) -> Result<(), RendererErrors> {
+        let gpu = self.get_gpu()?;
+        let old = self.get_texture(texture)?;
+        let desc = &old.desc;
+        if desc.render_target || desc.format.is_depth() {
+            return Err(RendererErrors::Backend(format!(
+                "{} is a render target, it keeps all of its mips",
+                desc.label
+            )));
+        }
+        let levels = desc.mip_levels.max(1);
+        let base_mip = base_mip.min(levels - 1);
+        if base_mip == old.base_mip {
+            return Ok(());
+        }
+
+        let resized = create_gpu_texture(&gpu.device, desc, base_mip);
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("mip change"),
+            });
+        for level in base_mip.max(old.base_mip)..levels {
+            let (width, height) = desc.get_mip_size(level);
+            encoder.copy_texture_to_texture(
+                wgpu::TexelCopyTextureInfo {
+                    texture: &old.texture,
+                    mip_level: level - old.base_mip,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::TexelCopyTextureInfo {
+                    texture: &resized,
+                    mip_level: level - base_mip,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        gpu.queue.submit([encoder.finish()]);
+        let resident = desc.get_mip_byte_sizes()[base_mip as usize..].iter().sum();
+
+        if let Some(old) = self.textures.get_mut(&texture) {
+            old.view = resized.create_view(&wgpu::TextureViewDescriptor::default());
+            std::mem::replace(&mut old.texture, resized).destroy();
+            old.base_mip = base_mip;
+        }
+        if let Some(allocation) = self.allocations.get(&Resource::Texture(texture)) {
+            self.memory.resize(*allocation, resident);
+        }
+        Ok(())
+    }
+
+    fn take_pass_timings(&mut self) -> Vec<PassTiming> {
+        let Some(gpu) = self.gpu.as_ref() else {
+            return Vec::new();
+        };
+        let _ = gpu.device.poll(wgpu::Maintain::Poll);
+        let period = gpu.timestamp_period;
+        let mut finished = Vec::new();
+        for timings in self.timings.iter_mut() {
+            if timings.labels.is_some() && timings.is_mapped() {
+                finished.extend(timings.read(period));
+            }
+        }
+        finished
+    }
+
+    fn take_readback(
+        &mut self,
+        readback: Readback,
+    ) -> Option<Result<ReadbackImage, RendererErrors>> {
+        let gpu = self.gpu.as_ref()?;
+        let _ = gpu.device.poll(wgpu::Maintain::Poll);
+        let mapped = match self.readbacks.get(&readback)? {
+            ReadbackState::Surface => return None,
+            ReadbackState::Copying(copy) => copy.mapped.lock().ok()?.clone()?,
+            ReadbackState::Failed(_) => Ok(()),
+        };
+        Some(match (self.readbacks.remove(&readback)?, mapped) {
+            (ReadbackState::Copying(copy), Ok(())) => copy.read(),
+            (ReadbackState::Failed(err), _) => Err(RendererErrors::Surface(err)),
+            (_, Err(err)) => Err(RendererErrors::Backend(err)),
+            (ReadbackState::Surface, _) => return None,
+        })
+    }
+}
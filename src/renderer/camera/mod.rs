@@ -0,0 +1,21 @@
+pub mod orthographic;
+pub mod perspective;
+pub mod viewport;
+
+use crate::math::transform::Mat4;
+
+// Anything the renderers can look through. A bare matrix works as a camera
+// for tests and fixed views.
+pub trait Camera {
+    // World space to clip space
+    fn get_view_projection(&self) -> Mat4;
+
+    // Called when the viewport changes shape, fixed views ignore it
+    fn set_aspect_ratio(&mut self, _aspect_ratio: f32) {}
+}
+
+impl Camera for Mat4 {
+    fn get_view_projection(&self) -> Mat4 {
+        *self
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::HashSet;
+
+use crate::{
+    core::{
+        key_code::KeyCode,
+        window::dpi::{CursorPosition, WindowSize},
+    },
+    event_system::{engine_events::mouse_events::MouseButton, event::Event},
+    math::{
+        transform::{Mat4, Quat, Transform},
+        vector::{Vec2, Vec3},
+    },
+};
+
+use super::Camera;
+
+// Depth range of the 2D scene, sprites use z for layering inside it
+const NEAR: f32 = -100.0;
+const FAR: f32 = 100.0;
+
+// A 2D camera showing `height` world units vertically at zoom 1, as wide as
+// the aspect ratio makes it. y points up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrthographicCamera {
+    position: Vec2,
+    // radians, counter clockwise
+    rotation: f32,
+    zoom: f32,
+    aspect_ratio: f32,
+    height: f32,
+}
+
+impl OrthographicCamera {
+    pub fn new(aspect_ratio: f32, height: f32) -> Self {
+        Self {
+            position: Vec2::ZERO,
+            rotation: 0.0,
+            zoom: 1.0,
+            aspect_ratio,
+            height,
+        }
+    }
+
+    pub fn get_position(&self) -> Vec2 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Vec2) {
+        self.position = position;
+    }
+
+    pub fn get_rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+    }
+
+    pub fn get_zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    // Above 1 shows less of the world, bigger
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.max(f32::EPSILON);
+    }
+
+    pub fn get_aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        if aspect_ratio.is_finite() && aspect_ratio > 0.0 {
+            self.aspect_ratio = aspect_ratio;
+        }
+    }
+
+    // World units on screen at the current zoom
+    pub fn get_visible_size(&self) -> Vec2 {
+        let height = self.height / self.zoom;
+        Vec2::new(height * self.aspect_ratio, height)
+    }
+
+    pub fn get_projection(&self) -> Mat4 {
+        let half = self.get_visible_size() / 2.0;
+        Mat4::orthographic(-half.x, half.x, -half.y, half.y, NEAR, FAR)
+    }
+
+    pub fn get_view(&self) -> Mat4 {
+        let rotation = Quat::from_axis_angle(Vec3::Z, -self.rotation);
+        Transform {
+            translation: rotation.rotate(Vec3::new(-self.position.x, -self.position.y, 0.0)),
+            rotation,
+            ..Transform::IDENTITY
+        }
+        .to_matrix()
+    }
+
+    // The world position under a point of the viewport, in the same units as
+    // `viewport`, from the top left
+    pub fn screen_to_world(&self, point: Vec2, viewport: Vec2) -> Vec2 {
+        let visible = self.get_visible_size();
+        let offset = Vec2::new(
+            (point.x / viewport.x - 0.5) * visible.x,
+            (0.5 - point.y / viewport.y) * visible.y,
+        );
+        let rotation = Quat::from_axis_angle(Vec3::Z, self.rotation);
+        let offset = rotation.rotate(Vec3::new(offset.x, offset.y, 0.0));
+        self.position + Vec2::new(offset.x, offset.y)
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_view_projection(&self) -> Mat4 {
+        self.get_projection() * self.get_view()
+    }
+
+    fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        OrthographicCamera::set_aspect_ratio(self, aspect_ratio);
+    }
+}
+
+// Moves an orthographic camera from engine input: WASD or the arrow keys
+// pan, dragging with the middle mouse button pans, the scroll wheel zooms
+// and window resizes keep the aspect ratio. Feed it every event and update
+// it once per frame.
+#[derive(Debug, Clone)]
+pub struct OrthographicCameraController {
+    camera: OrthographicCamera,
+    // visible heights per second
+    pan_speed: f32,
+    // zoom factor per scrolled line
+    zoom_step: f32,
+    zoom_range: (f32, f32),
+    held: HashSet<KeyCode>,
+    dragging: bool,
+    cursor: Option<Vec2>,
+    // logical pixels
+    viewport: Vec2,
+}
+
+impl OrthographicCameraController {
+    pub fn new(camera: OrthographicCamera) -> Self {
+        Self {
+            camera,
+            pan_speed: 1.0,
+            zoom_step: 1.1,
+            zoom_range: (0.1, 10.0),
+            held: HashSet::new(),
+            dragging: false,
+            cursor: None,
+            viewport: Vec2::new(1.0, 1.0),
+        }
+    }
+
+    pub fn get_camera(&self) -> &OrthographicCamera {
+        &self.camera
+    }
+
+    pub fn get_camera_mut(&mut self) -> &mut OrthographicCamera {
+        &mut self.camera
+    }
+
+    pub fn set_pan_speed(&mut self, pan_speed: f32) {
+        self.pan_speed = pan_speed;
+    }
+
+    pub fn set_zoom_step(&mut self, zoom_step: f32) {
+        self.zoom_step = zoom_step.max(1.0);
+    }
+
+    pub fn set_zoom_range(&mut self, min: f32, max: f32) {
+        self.zoom_range = (min.min(max), max.max(min));
+        let zoom = self
+            .camera
+            .get_zoom()
+            .clamp(self.zoom_range.0, self.zoom_range.1);
+        self.camera.set_zoom(zoom);
+    }
+
+    pub fn handle_event(&mut self, event: &dyn Event) {
+        let Some(data) = event.get_data() else {
+            return;
+        };
+        match event.get_name().as_str() {
+            "KeyPressed" => data.get_ref::<KeyCode>().map(|key| self.held.insert(*key)),
+            "KeyReleased" => data.get_ref::<KeyCode>().map(|key| self.held.remove(key)),
+            "MouseButtonPressed" | "MouseButtonReleased" => data
+                .get_ref::<MouseButton>()
+                .filter(|button| **button == MouseButton::Middle)
+                .map(|_| {
+                    self.dragging = event.get_name() == "MouseButtonPressed";
+                    self.dragging
+                }),
+            "MouseMoved" => data.get_ref::<CursorPosition>().map(|cursor| {
+                let position = cursor.get_logical();
+                if let (true, Some(previous)) = (self.dragging, self.cursor) {
+                    self.drag(position - previous);
+                }
+                self.cursor = Some(position);
+                true
+            }),
+            "MouseScrolled" => data.get_ref::<Vec2>().map(|delta| {
+                let zoom = self.camera.get_zoom() * self.zoom_step.powf(delta.y);
+                let (min, max) = self.zoom_range;
+                self.camera.set_zoom(zoom.clamp(min, max));
+                true
+            }),
+            "Resized" => data.get_ref::<WindowSize>().map(|size| {
+                let logical = size.get_logical();
+                if logical.width > 0.0 && logical.height > 0.0 {
+                    self.viewport = Vec2::new(logical.width as f32, logical.height as f32);
+                    self.camera
+                        .set_aspect_ratio(self.viewport.x / self.viewport.y);
+                }
+                true
+            }),
+            // lost keys would keep the camera moving
+            "FocusChanged" => data
+                .get_ref::<bool>()
+                .filter(|focused| !**focused)
+                .map(|_| {
+                    self.held.clear();
+                    self.dragging = false;
+                    true
+                }),
+            _ => None,
+        };
+    }
+
+    // The world follows the cursor while dragging
+    fn drag(&mut self, delta: Vec2) {
+        let visible = self.camera.get_visible_size();
+        let world = Vec3::new(
+            -delta.x / self.viewport.x * visible.x,
+            delta.y / self.viewport.y * visible.y,
+            0.0,
+        );
+        let world = Quat::from_axis_angle(Vec3::Z, self.camera.get_rotation()).rotate(world);
+        let position = self.camera.get_position() + Vec2::new(world.x, world.y);
+        self.camera.set_position(position);
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        let is_held = |keys: [KeyCode; 2]| keys.iter().any(|key| self.held.contains(key));
+        let mut direction = Vec2::ZERO;
+        if is_held([KeyCode::W, KeyCode::Up]) {
+            direction.y += 1.0;
+        }
+        if is_held([KeyCode::S, KeyCode::Down]) {
+            direction.y -= 1.0;
+        }
+        if is_held([KeyCode::D, KeyCode::Right]) {
+            direction.x += 1.0;
+        }
+        if is_held([KeyCode::A, KeyCode::Left]) {
+            direction.x -= 1.0;
+        }
+        if direction == Vec2::ZERO {
+            return;
+        }
+        // the same screen speed at every zoom
+        let speed = self.pan_speed * self.camera.get_visible_size().y * delta;
+        let step = direction.normalize() * speed;
+        let step = Quat::from_axis_angle(Vec3::Z, self.camera.get_rotation())
+            .rotate(Vec3::new(step.x, step.y, 0.0));
+        let position = self.camera.get_position() + Vec2::new(step.x, step.y);
+        self.camera.set_position(position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_system::engine_events::{
+        keyboard_events::KeyboardEvent, mouse_events::MouseEvents, window_events::WindowEvents,
+    };
+
+    fn close(a: Vec2, b: Vec2) -> bool {
+        a.distance(b) < 1e-4
+    }
+
+    #[test]
+    fn test_controller_pans_zooms_and_resizes() {
+        let mut camera = OrthographicCamera::new(1.0, 10.0);
+        camera.set_position(Vec2::new(2.0, 0.0));
+        let clip = camera
+            .get_view_projection()
+            .transform_point(Vec3::new(7.0, 5.0, 0.0));
+        assert!(close(Vec2::new(clip.x, clip.y), Vec2::new(1.0, 1.0)));
+
+        let mut controller = OrthographicCameraController::new(camera);
+        controller.handle_event(&WindowEvents::Resized(WindowSize::new(1600, 800, 2.0)));
+        assert_eq!(controller.get_camera().get_aspect_ratio(), 2.0);
+        assert!(close(
+            controller
+                .get_camera()
+                .screen_to_world(Vec2::new(800.0, 0.0), Vec2::new(800.0, 400.0)),
+            Vec2::new(12.0, 5.0)
+        ));
+
+        controller.handle_event(&KeyboardEvent::KeyPressed {
+            key: KeyCode::W,
+            repeat: false,
+        });
+        controller.update(0.5);
+        assert!(close(
+            controller.get_camera().get_position(),
+            Vec2::new(2.0, 5.0)
+        ));
+        controller.handle_event(&KeyboardEvent::KeyReleased(KeyCode::W));
+        controller.update(0.5);
+        assert!(close(
+            controller.get_camera().get_position(),
+            Vec2::new(2.0, 5.0)
+        ));
+
+        controller.handle_event(&MouseEvents::Scrolled(Vec2::new(0.0, 100.0)));
+        assert_eq!(controller.get_camera().get_zoom(), 10.0);
+        controller.handle_event(&MouseEvents::Scrolled(Vec2::new(0.0, -100.0)));
+        assert_eq!(controller.get_camera().get_zoom(), 0.1);
+        controller.set_zoom_range(1.0, 1.0);
+
+        // dragging half the viewport to the right moves half the view left
+        let cursor = |x: f32| MouseEvents::Moved(CursorPosition::new(Vec2::new(x, 0.0), 2.0));
+        controller.handle_event(&cursor(0.0));
+        controller.handle_event(&MouseEvents::ButtonPressed(MouseButton::Middle));
+        controller.handle_event(&cursor(800.0));
+        assert!(close(
+            controller.get_camera().get_position(),
+            Vec2::new(-8.0, 5.0)
+        ));
+    }
+}
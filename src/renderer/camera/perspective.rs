@@ -0,0 +1,424 @@
+use std::{collections::HashSet, f32::consts::FRAC_PI_2};
+
+use crate::{
+    core::{
+        key_code::KeyCode,
+        window::dpi::{CursorPosition, WindowSize},
+    },
+    event_system::{engine_events::mouse_events::MouseButton, event::Event},
+    math::{
+        transform::Mat4,
+        vector::{Vec2, Vec3},
+    },
+};
+
+use super::Camera;
+
+// Looking straight up or down flips the view
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+// A 3D camera turned by yaw and pitch, y up. Yaw 0 looks down -z, positive
+// yaw turns right and positive pitch looks up.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PerspectiveCamera {
+    position: Vec3,
+    yaw: f32,
+    pitch: f32,
+    // vertical, radians
+    fov: f32,
+    aspect_ratio: f32,
+    near: f32,
+    far: f32,
+}
+
+impl PerspectiveCamera {
+    pub fn new(fov: f32, aspect_ratio: f32, near: f32, far: f32) -> Self {
+        Self {
+            position: Vec3::ZERO,
+            yaw: 0.0,
+            pitch: 0.0,
+            fov,
+            aspect_ratio,
+            near,
+            far,
+        }
+    }
+
+    pub fn get_position(&self) -> Vec3 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Vec3) {
+        self.position = position;
+    }
+
+    pub fn get_yaw(&self) -> f32 {
+        self.yaw
+    }
+
+    pub fn get_pitch(&self) -> f32 {
+        self.pitch
+    }
+
+    pub fn set_rotation(&mut self, yaw: f32, pitch: f32) {
+        self.yaw = yaw;
+        self.pitch = pitch.clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    pub fn look_at(&mut self, target: Vec3) {
+        let direction = target - self.position;
+        if direction.length_squared() == 0.0 {
+            return;
+        }
+        let direction = direction.normalize();
+        self.set_rotation(direction.x.atan2(-direction.z), direction.y.asin());
+    }
+
+    pub fn get_fov(&self) -> f32 {
+        self.fov
+    }
+
+    pub fn set_fov(&mut self, fov: f32) {
+        self.fov = fov.clamp(0.01, std::f32::consts::PI - 0.01);
+    }
+
+    pub fn get_aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        if aspect_ratio.is_finite() && aspect_ratio > 0.0 {
+            self.aspect_ratio = aspect_ratio;
+        }
+    }
+
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+        self.near = near;
+        self.far = far;
+    }
+
+    pub fn get_forward(&self) -> Vec3 {
+        let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
+        let (pitch_sin, pitch_cos) = self.pitch.sin_cos();
+        Vec3::new(yaw_sin * pitch_cos, pitch_sin, -yaw_cos * pitch_cos)
+    }
+
+    pub fn get_right(&self) -> Vec3 {
+        let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
+        Vec3::new(yaw_cos, 0.0, yaw_sin)
+    }
+
+    pub fn get_up(&self) -> Vec3 {
+        self.get_right().cross(self.get_forward())
+    }
+
+    pub fn get_view(&self) -> Mat4 {
+        Mat4::look_to(self.position, self.get_forward(), Vec3::Y)
+    }
+
+    pub fn get_projection(&self) -> Mat4 {
+        Mat4::perspective(self.fov, self.aspect_ratio, self.near, self.far)
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn get_view_projection(&self) -> Mat4 {
+        self.get_projection() * self.get_view()
+    }
+
+    fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        PerspectiveCamera::set_aspect_ratio(self, aspect_ratio);
+    }
+}
+
+// The mouse and window bookkeeping both controllers share
+#[derive(Debug, Clone, Default)]
+struct PointerState {
+    held_keys: HashSet<KeyCode>,
+    held_buttons: HashSet<MouseButton>,
+    cursor: Option<Vec2>,
+    // cursor motion since the last update, logical pixels
+    motion: Vec2,
+    scroll: f32,
+}
+
+impl PointerState {
+    // Returns the new aspect ratio on a resize
+    fn handle_event(&mut self, event: &dyn Event) -> Option<f32> {
+        let data = event.get_data()?;
+        match event.get_name().as_str() {
+            "KeyPressed" => {
+                self.held_keys.insert(*data.get_ref::<KeyCode>()?);
+            }
+            "KeyReleased" => {
+                self.held_keys.remove(data.get_ref::<KeyCode>()?);
+            }
+            "MouseButtonPressed" => {
+                self.held_buttons.insert(*data.get_ref::<MouseButton>()?);
+            }
+            "MouseButtonReleased" => {
+                self.held_buttons.remove(data.get_ref::<MouseButton>()?);
+            }
+            "MouseMoved" => {
+                let position = data.get_ref::<CursorPosition>()?.get_logical();
+                if let Some(previous) = self.cursor {
+                    self.motion += position - previous;
+                }
+                self.cursor = Some(position);
+            }
+            "MouseScrolled" => self.scroll += data.get_ref::<Vec2>()?.y,
+            "FocusChanged" if !*data.get_ref::<bool>()? => {
+                self.held_keys.clear();
+                self.held_buttons.clear();
+            }
+            "Resized" => {
+                let size = data.get_ref::<WindowSize>()?.physical;
+                if size.width > 0 && size.height > 0 {
+                    return Some(size.width as f32 / size.height as f32);
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn is_held(&self, keys: &[KeyCode]) -> bool {
+        keys.iter().any(|key| self.held_keys.contains(key))
+    }
+
+    fn take_motion(&mut self) -> Vec2 {
+        std::mem::replace(&mut self.motion, Vec2::ZERO)
+    }
+
+    fn take_scroll(&mut self) -> f32 {
+        std::mem::replace(&mut self.scroll, 0.0)
+    }
+}
+
+// Free flying editor style camera: WASD moves, E and Space rise, Q and
+// left control sink, holding the right mouse button looks around and
+// left shift moves faster
+#[derive(Debug, Clone)]
+pub struct FlyController {
+    camera: PerspectiveCamera,
+    pointer: PointerState,
+    // units per second
+    speed: f32,
+    boost: f32,
+    // radians per logical pixel
+    sensitivity: f32,
+}
+
+impl FlyController {
+    pub fn new(camera: PerspectiveCamera) -> Self {
+        Self {
+            camera,
+            pointer: PointerState::default(),
+            speed: 5.0,
+            boost: 4.0,
+            sensitivity: 0.003,
+        }
+    }
+
+    pub fn get_camera(&self) -> &PerspectiveCamera {
+        &self.camera
+    }
+
+    pub fn get_camera_mut(&mut self) -> &mut PerspectiveCamera {
+        &mut self.camera
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.sensitivity = sensitivity;
+    }
+
+    pub fn handle_event(&mut self, event: &dyn Event) {
+        if let Some(aspect_ratio) = self.pointer.handle_event(event) {
+            self.camera.set_aspect_ratio(aspect_ratio);
+        }
+    }
+
+    pub fn update(&mut self, delta: f32) {
+        let motion = self.pointer.take_motion();
+        self.pointer.take_scroll();
+        if self.pointer.held_buttons.contains(&MouseButton::Right) {
+            self.camera.set_rotation(
+                self.camera.get_yaw() + motion.x * self.sensitivity,
+                self.camera.get_pitch() - motion.y * self.sensitivity,
+            );
+        }
+
+        let pointer = &self.pointer;
+        let axis = |positive: &[KeyCode], negative: &[KeyCode]| {
+            pointer.is_held(positive) as i32 as f32 - pointer.is_held(negative) as i32 as f32
+        };
+        let forward = axis(&[KeyCode::W], &[KeyCode::S]);
+        let right = axis(&[KeyCode::D], &[KeyCode::A]);
+        let up = axis(
+            &[KeyCode::E, KeyCode::Space],
+            &[KeyCode::Q, KeyCode::LeftControl],
+        );
+        let direction =
+            self.camera.get_forward() * forward + self.camera.get_right() * right + Vec3::Y * up;
+        if direction.length_squared() == 0.0 {
+            return;
+        }
+        let mut speed = self.speed;
+        if pointer.is_held(&[KeyCode::LeftShift]) {
+            speed *= self.boost;
+        }
+        let position = self.camera.get_position() + direction.normalize() * (speed * delta);
+        self.camera.set_position(position);
+    }
+}
+
+// Circles a target point like a model viewer: dragging with the left mouse
+// button orbits, the middle button pans the target and scrolling moves
+// closer or further away
+#[derive(Debug, Clone)]
+pub struct OrbitController {
+    camera: PerspectiveCamera,
+    pointer: PointerState,
+    target: Vec3,
+    distance: f32,
+    distance_range: (f32, f32),
+    // radians per logical pixel
+    sensitivity: f32,
+    // distance factor per scrolled line
+    zoom_step: f32,
+}
+
+impl OrbitController {
+    pub fn new(camera: PerspectiveCamera, target: Vec3) -> Self {
+        let distance = camera.get_position().distance(target).max(0.01);
+        let mut controller = Self {
+            camera,
+            pointer: PointerState::default(),
+            target,
+            distance,
+            distance_range: (0.1, 1000.0),
+            sensitivity: 0.005,
+            zoom_step: 1.1,
+        };
+        controller.camera.look_at(target);
+        controller.apply();
+        controller
+    }
+
+    pub fn get_camera(&self) -> &PerspectiveCamera {
+        &self.camera
+    }
+
+    pub fn get_target(&self) -> Vec3 {
+        self.target
+    }
+
+    pub fn set_target(&mut self, target: Vec3) {
+        self.target = target;
+        self.apply();
+    }
+
+    pub fn get_distance(&self) -> f32 {
+        self.distance
+    }
+
+    pub fn set_distance_range(&mut self, min: f32, max: f32) {
+        self.distance_range = (min.min(max), max.max(min));
+        self.distance = self
+            .distance
+            .clamp(self.distance_range.0, self.distance_range.1);
+        self.apply();
+    }
+
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.sensitivity = sensitivity;
+    }
+
+    pub fn handle_event(&mut self, event: &dyn Event) {
+        if let Some(aspect_ratio) = self.pointer.handle_event(event) {
+            self.camera.set_aspect_ratio(aspect_ratio);
+        }
+    }
+
+    pub fn update(&mut self) {
+        let motion = self.pointer.take_motion();
+        let buttons = &self.pointer.held_buttons;
+        if buttons.contains(&MouseButton::Left) {
+            self.camera.set_rotation(
+                self.camera.get_yaw() + motion.x * self.sensitivity,
+                self.camera.get_pitch() - motion.y * self.sensitivity,
+            );
+        } else if buttons.contains(&MouseButton::Middle) {
+            // the target follows the cursor at the same speed at any distance
+            let scale = self.distance * self.sensitivity * 0.5;
+            self.target += self.camera.get_right() * (-motion.x * scale)
+                + self.camera.get_up() * (motion.y * scale);
+        }
+
+        let scroll = self.pointer.take_scroll();
+        let (min, max) = self.distance_range;
+        self.distance = (self.distance * self.zoom_step.powf(-scroll)).clamp(min, max);
+        self.apply();
+    }
+
+    fn apply(&mut self) {
+        let position = self.target - self.camera.get_forward() * self.distance;
+        self.camera.set_position(position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_system::engine_events::{
+        keyboard_events::KeyboardEvent, mouse_events::MouseEvents, window_events::WindowEvents,
+    };
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        a.distance(b) < 1e-3
+    }
+
+    #[test]
+    fn test_fly_and_orbit() {
+        let camera = PerspectiveCamera::new(FRAC_PI_2, 1.0, 0.1, 100.0);
+        let clip = camera
+            .get_view_projection()
+            .transform_vec4(Vec3::new(0.0, 0.0, -100.0).extend(1.0));
+        assert!((clip.z / clip.w - 1.0).abs() < 1e-4);
+
+        let mut fly = FlyController::new(camera.clone());
+        fly.handle_event(&WindowEvents::Resized(WindowSize::new(1600, 900, 1.0)));
+        assert!((fly.get_camera().get_aspect_ratio() - 16.0 / 9.0).abs() < 1e-6);
+        fly.handle_event(&KeyboardEvent::KeyPressed {
+            key: KeyCode::W,
+            repeat: false,
+        });
+        fly.update(1.0);
+        assert!(close(
+            fly.get_camera().get_position(),
+            Vec3::new(0.0, 0.0, -5.0)
+        ));
+
+        let mut orbit = OrbitController::new(camera, Vec3::new(0.0, 0.0, -10.0));
+        assert!(close(orbit.get_camera().get_position(), Vec3::ZERO));
+        let cursor = |x: f32| MouseEvents::Moved(CursorPosition::new(Vec2::new(x, 0.0), 1.0));
+        orbit.handle_event(&cursor(0.0));
+        orbit.handle_event(&MouseEvents::ButtonPressed(MouseButton::Left));
+        orbit.handle_event(&cursor(FRAC_PI_2 / 0.005));
+        orbit.update();
+        // a quarter turn right puts the camera on the -x side of the target
+        assert!(close(
+            orbit.get_camera().get_position(),
+            Vec3::new(-10.0, 0.0, -10.0)
+        ));
+        assert!(close(orbit.get_camera().get_forward(), Vec3::X));
+
+        orbit.handle_event(&MouseEvents::Scrolled(Vec2::new(0.0, 1000.0)));
+        orbit.update();
+        assert_eq!(orbit.get_distance(), 0.1);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{
+    core::window::dpi::{PhysicalSize, WindowSize},
+    event_system::event::Event,
+    math::vector::Vec2,
+    renderer::api::RenderPass,
+};
+
+use super::Camera;
+
+// How the game's picture is placed in the window
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ViewportPolicy {
+    // fills the window, the camera takes the window's aspect ratio
+    #[default]
+    Stretch,
+    // the largest rect with the aspect ratio of `width`x`height`, centered,
+    // with letterbox bars left in the pass's clear color
+    Fit {
+        width: u32,
+        height: u32,
+    },
+    // like Fit but only whole multiples of `width`x`height`, so pixel art
+    // stays crisp. Falls back to Fit in windows smaller than that.
+    PixelPerfect {
+        width: u32,
+        height: u32,
+    },
+}
+
+// Keeps the viewport of the game's passes in line with the window. Feed it
+// every event, after any camera controller so the camera ends up with the
+// viewport's aspect ratio, and apply it to the passes each frame:
+//
+//     if viewport.handle_event(event) {
+//         viewport.apply_to_camera(&mut camera);
+//     }
+//     ...
+//     viewport.apply(&mut pass);
+#[derive(Debug, Clone, PartialEq)]
+pub struct Viewport {
+    policy: ViewportPolicy,
+    window: PhysicalSize,
+    // physical pixels from the top left of the window
+    position: Vec2,
+    size: Vec2,
+}
+
+impl Viewport {
+    pub fn new(policy: ViewportPolicy) -> Self {
+        Self {
+            policy,
+            window: PhysicalSize::default(),
+            position: Vec2::ZERO,
+            size: Vec2::ZERO,
+        }
+    }
+
+    pub fn get_policy(&self) -> ViewportPolicy {
+        self.policy
+    }
+
+    pub fn set_policy(&mut self, policy: ViewportPolicy) {
+        self.policy = policy;
+        self.update();
+    }
+
+    // Physical pixels
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.window = PhysicalSize::new(width, height);
+        self.update();
+    }
+
+    // Follows `WindowEvents::Resized`, returns whether the event was one
+    pub fn handle_event(&mut self, event: &dyn Event) -> bool {
+        if event.get_name() != "Resized" {
+            return false;
+        }
+        let Some(size) = event
+            .get_data()
+            .and_then(|data| data.get_ref::<WindowSize>().copied())
+        else {
+            return false;
+        };
+        self.resize(size.physical.width, size.physical.height);
+        true
+    }
+
+    fn update(&mut self) {
+        let window = Vec2::new(self.window.width as f32, self.window.height as f32);
+        let (width, height, whole) = match self.policy {
+            ViewportPolicy::Stretch => {
+                self.position = Vec2::ZERO;
+                self.size = window;
+                return;
+            }
+            ViewportPolicy::Fit { width, height } => (width, height, false),
+            ViewportPolicy::PixelPerfect { width, height } => (width, height, true),
+        };
+        let virtual_size = Vec2::new(width.max(1) as f32, height.max(1) as f32);
+        let mut scale = (window.x / virtual_size.x).min(window.y / virtual_size.y);
+        if whole && scale >= 1.0 {
+            scale = scale.floor();
+        }
+        self.size = virtual_size * scale;
+        // whole pixels, or the picture is resampled across pixel boundaries
+        self.position = Vec2::new(
+            ((window.x - self.size.x) / 2.0).floor(),
+            ((window.y - self.size.y) / 2.0).floor(),
+        );
+    }
+
+    pub fn get_position(&self) -> Vec2 {
+        self.position
+    }
+
+    pub fn get_size(&self) -> Vec2 {
+        self.size
+    }
+
+    // Physical pixels per virtual pixel, 1 when stretching
+    pub fn get_scale(&self) -> f32 {
+        match self.policy {
+            ViewportPolicy::Stretch => 1.0,
+            ViewportPolicy::Fit { width, .. } | ViewportPolicy::PixelPerfect { width, .. } => {
+                self.size.x / width.max(1) as f32
+            }
+        }
+    }
+
+    pub fn get_aspect_ratio(&self) -> f32 {
+        match self.policy {
+            ViewportPolicy::Fit { width, height }
+            | ViewportPolicy::PixelPerfect { width, height } => {
+                width.max(1) as f32 / height.max(1) as f32
+            }
+            ViewportPolicy::Stretch if self.size.y > 0.0 => self.size.x / self.size.y,
+            ViewportPolicy::Stretch => 1.0,
+        }
+    }
+
+    // Nothing is drawn into a minimized window
+    pub fn is_empty(&self) -> bool {
+        self.size.x < 1.0 || self.size.y < 1.0
+    }
+
+    pub fn apply_to_camera(&self, camera: &mut dyn Camera) {
+        camera.set_aspect_ratio(self.get_aspect_ratio());
+    }
+
+    // Limits the draws recorded after this to the viewport, clear the pass
+    // first to paint the letterbox bars
+    pub fn apply(&self, pass: &mut RenderPass) {
+        if self.is_empty() {
+            return;
+        }
+        pass.set_viewport(self.position, self.size);
+        pass.set_scissor(
+            (self.position.x as u32, self.position.y as u32),
+            (self.size.x as u32, self.size.y as u32),
+        );
+    }
+
+    // A point in physical window pixels as a fraction of the viewport, from
+    // its top left. None over the letterbox bars.
+    pub fn window_to_viewport(&self, point: Vec2) -> Option<Vec2> {
+        if self.is_empty() {
+            return None;
+        }
+        let relative = point - self.position;
+        let fraction = Vec2::new(relative.x / self.size.x, relative.y / self.size.y);
+        let inside = (0.0..=1.0).contains(&fraction.x) && (0.0..=1.0).contains(&fraction.y);
+        inside.then_some(fraction)
+    }
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self::new(ViewportPolicy::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event_system::engine_events::window_events::WindowEvents,
+        renderer::{
+            api::{DrawCommand, RenderTarget},
+            camera::orthographic::OrthographicCamera,
+        },
+    };
+
+    #[test]
+    fn test_policies_follow_resizes() {
+        let mut viewport = Viewport::new(ViewportPolicy::Fit {
+            width: 320,
+            height: 180,
+        });
+        assert!(viewport.handle_event(&WindowEvents::Resized(WindowSize::new(1000, 1000, 2.0))));
+        assert_eq!(viewport.get_size(), Vec2::new(1000.0, 562.5));
+        assert_eq!(viewport.get_position(), Vec2::new(0.0, 218.0));
+        assert_eq!(viewport.window_to_viewport(Vec2::new(500.0, 100.0)), None);
+        assert_eq!(
+            viewport.window_to_viewport(Vec2::new(500.0, 218.0)),
+            Some(Vec2::new(0.5, 0.0))
+        );
+
+        let mut camera = OrthographicCamera::new(1.0, 180.0);
+        viewport.apply_to_camera(&mut camera);
+        assert_eq!(camera.get_aspect_ratio(), 320.0 / 180.0);
+
+        // 1000 / 320 rounds down to 3x
+        viewport.set_policy(ViewportPolicy::PixelPerfect {
+            width: 320,
+            height: 180,
+        });
+        assert_eq!(viewport.get_scale(), 3.0);
+        assert_eq!(viewport.get_size(), Vec2::new(960.0, 540.0));
+        let mut pass = RenderPass::new("world", RenderTarget::Surface);
+        viewport.apply(&mut pass);
+        assert_eq!(
+            pass.commands,
+            vec![
+                DrawCommand::SetViewport {
+                    position: Vec2::new(20.0, 230.0),
+                    size: Vec2::new(960.0, 540.0),
+                },
+                DrawCommand::SetScissor {
+                    position: (20, 230),
+                    size: (960, 540),
+                },
+            ]
+        );
+
+        viewport.set_policy(ViewportPolicy::Stretch);
+        viewport.resize(800, 0);
+        assert!(viewport.is_empty());
+        let mut pass = RenderPass::new("world", RenderTarget::Surface);
+        viewport.apply(&mut pass);
+        assert!(pass.commands.is_empty());
+    }
+}
@@ -0,0 +1,326 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    thread::{self, JoinHandle},
+};
+
+use log::{error, info, warn};
+use thiserror::Error;
+
+use crate::{core::bug_report::Screenshot, event_system::event::Event};
+
+use super::api::{Readback, ReadbackImage, RenderTarget, RendererAPI, RendererErrors};
+
+// Bound to F12 by default, takes a screenshot or toggles recording
+pub const SCREENSHOT_ACTION: &str = "CaptureScreenshot";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum CaptureErrors {
+    #[error("unable to write {0}: {1}")]
+    Io(String, String),
+
+    #[error("unable to encode png: {0}")]
+    Encode(String),
+
+    #[error("{0}")]
+    Renderer(#[from] RendererErrors),
+}
+
+// What SCREENSHOT_ACTION does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaptureMode {
+    #[default]
+    Screenshot,
+    // starts and stops writing every frame, e.g. for trailers and bug repros
+    Sequence,
+}
+
+#[derive(Debug)]
+struct Recording {
+    dir: PathBuf,
+    frames: u64,
+}
+
+type Writer = JoinHandle<Result<PathBuf, CaptureErrors>>;
+
+fn write_png(image: ReadbackImage, path: &Path) -> Result<PathBuf, CaptureErrors> {
+    let io = |err: std::io::Error| CaptureErrors::Io(path.display().to_string(), err.to_string());
+    let png = Screenshot::new(image.width, image.height, image.rgba)
+        .and_then(|screenshot| screenshot.encode_png())
+        .map_err(|err| CaptureErrors::Encode(err.to_string()))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io)?;
+    }
+    fs::write(path, png).map_err(io)?;
+    Ok(path.to_path_buf())
+}
+
+// Writes what the renderer drew to PNG files. Pixels are read back without
+// stalling the GPU and encoded on worker threads, so a capture shows up a
+// frame or two after it was asked for.
+//
+//     capture.handle_event(event);
+//     api.submit(&passes)?;
+//     for saved in capture.end_frame(api) { ... }
+//     api.present()?;
+#[derive(Debug)]
+pub struct ScreenCapture {
+    output_dir: PathBuf,
+    target: RenderTarget,
+    mode: CaptureMode,
+    // screenshots to read back at the end of this frame
+    requested: Vec<PathBuf>,
+    pending: Vec<(Readback, PathBuf)>,
+    writers: Vec<Writer>,
+    recording: Option<Recording>,
+}
+
+impl ScreenCapture {
+    pub fn new(output_dir: impl AsRef<Path>) -> Self {
+        Self {
+            output_dir: output_dir.as_ref().to_path_buf(),
+            target: RenderTarget::Surface,
+            mode: CaptureMode::default(),
+            requested: Vec::new(),
+            pending: Vec::new(),
+            writers: Vec::new(),
+            recording: None,
+        }
+    }
+
+    pub fn get_output_dir(&self) -> &Path {
+        &self.output_dir
+    }
+
+    // The surface by default, an offscreen target captures e.g. a minimap
+    pub fn set_target(&mut self, target: RenderTarget) {
+        self.target = target;
+    }
+
+    pub fn get_mode(&self) -> CaptureMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: CaptureMode) {
+        self.mode = mode;
+    }
+
+    // Saves the next frame to `path`
+    pub fn capture_screenshot(&mut self, path: impl AsRef<Path>) {
+        self.requested.push(path.as_ref().to_path_buf());
+    }
+
+    // `<output_dir>/screenshot-<timestamp>.png`
+    pub fn capture_timestamped(&mut self) -> PathBuf {
+        let name = format!(
+            "screenshot-{}.png",
+            chrono::Local::now().format("%Y%m%d-%H%M%S%.3f")
+        );
+        let path = self.output_dir.join(name);
+        self.capture_screenshot(&path);
+        path
+    }
+
+    // Writes every frame as `<output_dir>/recording-<timestamp>/000001.png`
+    // until `stop_recording`, returns the directory
+    pub fn start_recording(&mut self) -> PathBuf {
+        if let Some(recording) = self.recording.as_ref() {
+            return recording.dir.clone();
+        }
+        let name = format!(
+            "recording-{}",
+            chrono::Local::now().format("%Y%m%d-%H%M%S%.3f")
+        );
+        let dir = self.output_dir.join(name);
+        info!("recording frames to {}", dir.display());
+        self.recording = Some(Recording {
+            dir: dir.clone(),
+            frames: 0,
+        });
+        dir
+    }
+
+    // Returns how many frames were recorded
+    pub fn stop_recording(&mut self) -> u64 {
+        let Some(recording) = self.recording.take() else {
+            return 0;
+        };
+        info!(
+            "recorded {} frames to {}",
+            recording.frames,
+            recording.dir.display()
+        );
+        recording.frames
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    // Reacts to SCREENSHOT_ACTION, returns whether it did
+    pub fn handle_event(&mut self, event: &dyn Event) -> bool {
+        let is_capture = event.get_name() == "ActionStarted"
+            && event.get_data().is_some_and(|data| {
+                data.get_ref::<String>().map(|action| action.as_str()) == Some(SCREENSHOT_ACTION)
+            });
+        if !is_capture {
+            return false;
+        }
+        match self.mode {
+            CaptureMode::Screenshot => {
+                self.capture_timestamped();
+            }
+            CaptureMode::Sequence if self.is_recording() => {
+                self.stop_recording();
+            }
+            CaptureMode::Sequence => {
+                self.start_recording();
+            }
+        }
+        true
+    }
+
+    // Call after the frame was submitted and before it is presented. Reads
+    // back what was asked for this frame and returns the files written since
+    // the last call.
+    pub fn end_frame(&mut self, api: &mut dyn RendererAPI) -> Vec<Result<PathBuf, CaptureErrors>> {
+        let mut results = Vec::new();
+        let mut paths: Vec<PathBuf> = self.requested.drain(..).collect();
+        if let Some(recording) = self.recording.as_mut() {
+            recording.frames += 1;
+            paths.push(recording.dir.join(format!("{:06}.png", recording.frames)));
+        }
+        for path in paths {
+            match api.request_readback(self.target) {
+                Ok(readback) => self.pending.push((readback, path)),
+                Err(err) => results.push(Err(err.into())),
+            }
+        }
+
+        let mut waiting = Vec::new();
+        for (readback, path) in self.pending.drain(..) {
+            match api.take_readback(readback) {
+                None => waiting.push((readback, path)),
+                Some(Err(err)) => results.push(Err(err.into())),
+                Some(Ok(image)) => {
+                    let spawned = thread::Builder::new()
+                        .name("screen capture".to_string())
+                        .spawn({
+                            let path = path.clone();
+                            move || write_png(image, &path)
+                        });
+                    match spawned {
+                        Ok(writer) => self.writers.push(writer),
+                        Err(err) => {
+                            results.push(Err(CaptureErrors::Io(
+                                path.display().to_string(),
+                                err.to_string(),
+                            )));
+                        }
+                    }
+                }
+            }
+        }
+        self.pending = waiting;
+
+        let (finished, running) = self
+            .writers
+            .drain(..)
+            .partition(|writer: &Writer| writer.is_finished());
+        self.writers = running;
+        results.extend(finished.into_iter().map(Self::join));
+        results
+    }
+
+    fn join(writer: Writer) -> Result<PathBuf, CaptureErrors> {
+        let result = writer
+            .join()
+            .unwrap_or_else(|_| Err(CaptureErrors::Encode("writer panicked".to_string())));
+        match &result {
+            Ok(path) => info!("captured {}", path.display()),
+            Err(err) => error!("unable to capture {}", err),
+        }
+        result
+    }
+
+    // Waits for the files being written, e.g. before exiting. Readbacks still
+    // in flight are dropped.
+    pub fn flush(&mut self) -> Vec<Result<PathBuf, CaptureErrors>> {
+        if !self.pending.is_empty() {
+            warn!("dropping {} captures still on the GPU", self.pending.len());
+            self.pending.clear();
+        }
+        self.writers.drain(..).map(Self::join).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::{
+        event_system::engine_events::input_events::InputEvent,
+        math::vector::Vec4,
+        renderer::api::{headless::HeadlessRenderer, RenderPass},
+    };
+
+    fn draw_frame(api: &mut HeadlessRenderer, color: Vec4) {
+        let mut pass = RenderPass::new("world", RenderTarget::Surface);
+        pass.clear(color);
+        api.submit(&[pass]).unwrap();
+    }
+
+    fn finish(capture: &mut ScreenCapture, api: &mut HeadlessRenderer) -> Vec<PathBuf> {
+        let mut saved: Vec<PathBuf> = capture
+            .end_frame(api)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        api.present().unwrap();
+        capture.end_frame(api);
+        saved.extend(capture.flush().into_iter().map(Result::unwrap));
+        saved
+    }
+
+    #[test]
+    fn test_screenshots_and_sequences() {
+        let dir = env::temp_dir().join(format!("aloy-capture-test-{}", std::process::id()));
+        let mut api = HeadlessRenderer::new();
+        api.resize(2, 1);
+        let mut capture = ScreenCapture::new(&dir);
+
+        draw_frame(&mut api, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        let path = dir.join("red.png");
+        capture.capture_screenshot(&path);
+        assert_eq!(finish(&mut capture, &mut api), vec![path.clone()]);
+        let mut decoder = png::Decoder::new(fs::File::open(&path).unwrap())
+            .read_info()
+            .unwrap();
+        let mut pixels = vec![0; decoder.output_buffer_size()];
+        decoder.next_frame(&mut pixels).unwrap();
+        assert_eq!(pixels, vec![255, 0, 0, 255, 255, 0, 0, 255]);
+
+        // the action toggles recording in sequence mode
+        capture.set_mode(CaptureMode::Sequence);
+        let action = InputEvent::ActionStarted(SCREENSHOT_ACTION.to_string());
+        assert!(capture.handle_event(&action));
+        assert!(capture.is_recording());
+        let mut saved = Vec::new();
+        for _ in 0..3 {
+            draw_frame(&mut api, Vec4::splat(1.0));
+            saved.extend(capture.end_frame(&mut api));
+            api.present().unwrap();
+        }
+        assert!(capture.handle_event(&action));
+        assert!(!capture.is_recording());
+        saved.extend(capture.end_frame(&mut api));
+        saved.extend(capture.flush());
+        let mut saved: Vec<PathBuf> = saved.into_iter().map(Result::unwrap).collect();
+        saved.sort();
+        assert_eq!(saved.len(), 3);
+        assert!(saved[2].ends_with("000003.png"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,587 @@
+use std::{collections::HashMap, fs, path::Path, time::Duration};
+
+use log::{info, warn};
+use thiserror::Error;
+
+use crate::{
+    event_system::{engine_events::renderer_events::ColorGradingBlend, event::Event},
+    math::vector::{Vec3, Vec4},
+};
+
+use super::{
+    api::{
+        Binding, BindingKind, BlendMode, Buffer, BufferDescriptor, BufferUsage, Filter, Pipeline,
+        PipelineDescriptor, RenderPass, RendererAPI, RendererErrors, Resource, Sampler, Shader,
+        ShaderSource, Texture, TextureDescriptor, TextureFormat,
+    },
+    texture::{Image, TextureErrors},
+};
+
+// Size of the LUT used while no grading is set
+const IDENTITY_SIZE: usize = 2;
+
+#[derive(Debug, Error)]
+pub enum ColorGradingErrors {
+    #[error("unable to read lut {0}: {1}")]
+    Io(String, String),
+
+    #[error("invalid cube lut: {0}")]
+    InvalidCube(String),
+
+    #[error("invalid strip lut: {0}")]
+    InvalidStrip(String),
+
+    #[error("unknown lut {0}")]
+    UnknownLut(String),
+
+    #[error("{0}")]
+    Texture(#[from] TextureErrors),
+
+    #[error("{0}")]
+    Renderer(#[from] RendererErrors),
+}
+
+// A 3D color lookup table, `size` entries along each axis with red changing
+// fastest, then green, then blue like in .cube files
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut {
+    size: usize,
+    colors: Vec<Vec3>,
+}
+
+impl Lut {
+    // Leaves colors as they are
+    pub fn identity(size: usize) -> Self {
+        let size = size.max(2);
+        let step = 1.0 / (size - 1) as f32;
+        let colors = (0..size * size * size)
+            .map(|index| {
+                Vec3::new(
+                    (index % size) as f32 * step,
+                    (index / size % size) as f32 * step,
+                    (index / (size * size)) as f32 * step,
+                )
+            })
+            .collect();
+        Self { size, colors }
+    }
+
+    // Adobe/Resolve .cube, 3D tables only
+    pub fn from_cube(text: &str) -> Result<Self, ColorGradingErrors> {
+        let invalid = |reason: String| ColorGradingErrors::InvalidCube(reason);
+        let mut size = 0;
+        let mut domain = (Vec3::ZERO, Vec3::splat(1.0));
+        let mut colors = Vec::new();
+
+        let parse_vec3 = |values: &[&str]| -> Option<Vec3> {
+            match values {
+                [r, g, b] => Some(Vec3::new(r.parse().ok()?, g.parse().ok()?, b.parse().ok()?)),
+                _ => None,
+            }
+        };
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with("TITLE") {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words[0] {
+                "LUT_3D_SIZE" => {
+                    size = words
+                        .get(1)
+                        .and_then(|size| size.parse().ok())
+                        .ok_or_else(|| invalid(format!("bad size line {}", line)))?;
+                }
+                "LUT_1D_SIZE" => return Err(invalid("1D luts are not supported".to_string())),
+                "DOMAIN_MIN" => {
+                    domain.0 = parse_vec3(&words[1..])
+                        .ok_or_else(|| invalid(format!("bad domain line {}", line)))?;
+                }
+                "DOMAIN_MAX" => {
+                    domain.1 = parse_vec3(&words[1..])
+                        .ok_or_else(|| invalid(format!("bad domain line {}", line)))?;
+                }
+                _ => colors
+                    .push(parse_vec3(&words).ok_or_else(|| invalid(format!("bad line {}", line)))?),
+            }
+        }
+        if size < 2 || colors.len() != size * size * size {
+            return Err(invalid(format!(
+                "{} entries for a size of {}",
+                colors.len(),
+                size
+            )));
+        }
+        // the table maps the domain, stored for inputs from 0 to 1
+        let (min, max) = domain;
+        if min != Vec3::ZERO || max != Vec3::splat(1.0) {
+            warn!("cube lut domain is not 0..1, inputs are remapped");
+            let resampled = Self { size, colors };
+            let identity = Self::identity(size);
+            let colors = identity
+                .colors
+                .iter()
+                .map(|input| {
+                    let along = |value: f32, min: f32, max: f32| (value - min) / (max - min);
+                    resampled.sample(Vec3::new(
+                        along(input.x, min.x, max.x),
+                        along(input.y, min.y, max.y),
+                        along(input.z, min.z, max.z),
+                    ))
+                })
+                .collect();
+            return Ok(Self { size, colors });
+        }
+        Ok(Self { size, colors })
+    }
+
+    // A strip of `size` squares side by side, blue grows from square to
+    // square, red to the right and green downwards inside each
+    pub fn from_strip(image: &Image) -> Result<Self, ColorGradingErrors> {
+        let size = image.height as usize;
+        if size < 2 || image.width as usize != size * size {
+            return Err(ColorGradingErrors::InvalidStrip(format!(
+                "{}x{} is not a strip of squares",
+                image.width, image.height
+            )));
+        }
+        let channels = match image.format {
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => 4,
+            format => {
+                return Err(ColorGradingErrors::InvalidStrip(format!(
+                    "{:?} pixels",
+                    format
+                )))
+            }
+        };
+        let pixels = &image.levels[0];
+        let colors = (0..size * size * size)
+            .map(|index| {
+                let (r, g, b) = (index % size, index / size % size, index / (size * size));
+                let offset = (g * size * size + b * size + r) * channels;
+                Vec3::new(
+                    pixels[offset] as f32 / 255.0,
+                    pixels[offset + 1] as f32 / 255.0,
+                    pixels[offset + 2] as f32 / 255.0,
+                )
+            })
+            .collect();
+        Ok(Self { size, colors })
+    }
+
+    // .cube files are parsed as text, anything else is read as a strip image
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ColorGradingErrors> {
+        let path = path.as_ref();
+        let io = |err: std::io::Error| {
+            ColorGradingErrors::Io(path.display().to_string(), err.to_string())
+        };
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("cube") => Self::from_cube(&fs::read_to_string(path).map_err(io)?),
+            _ => Self::from_strip(&Image::decode(&fs::read(path).map_err(io)?)?),
+        }
+    }
+
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    fn get(&self, r: usize, g: usize, b: usize) -> Vec3 {
+        self.colors[(b * self.size + g) * self.size + r]
+    }
+
+    // Trilinear lookup, inputs outside 0..1 are clamped
+    pub fn sample(&self, color: Vec3) -> Vec3 {
+        let max = (self.size - 1) as f32;
+        let scaled = Vec3::new(
+            color.x.clamp(0.0, 1.0) * max,
+            color.y.clamp(0.0, 1.0) * max,
+            color.z.clamp(0.0, 1.0) * max,
+        );
+        let low = |value: f32| (value.floor() as usize).min(self.size - 2);
+        let (r, g, b) = (low(scaled.x), low(scaled.y), low(scaled.z));
+        let t = scaled - Vec3::new(r as f32, g as f32, b as f32);
+
+        let along_r = |g, b| self.get(r, g, b).lerp(self.get(r + 1, g, b), t.x);
+        let along_g = |b| along_r(g, b).lerp(along_r(g + 1, b), t.y);
+        along_g(b).lerp(along_g(b + 1), t.z)
+    }
+
+    // The strip layout of `from_strip`, for uploading
+    pub fn to_strip_rgba(&self) -> Vec<u8> {
+        let size = self.size;
+        let mut pixels = vec![0; size * size * size * 4];
+        for (index, color) in self.colors.iter().enumerate() {
+            let (r, g, b) = (index % size, index / size % size, index / (size * size));
+            let offset = (g * size * size + b * size + r) * 4;
+            let to_byte = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            pixels[offset..offset + 4].copy_from_slice(&[
+                to_byte(color.x),
+                to_byte(color.y),
+                to_byte(color.z),
+                255,
+            ]);
+        }
+        pixels
+    }
+}
+
+// Color grading of the final image with named LUTs. Switching LUTs fades
+// from the old grading to the new one, e.g. when the player walks into a
+// cave. Games trigger it with `blend_to` or by emitting a
+// `RendererEvents::ColorGradingBlend` that is passed to `handle_event`.
+#[derive(Debug, Clone, Default)]
+pub struct ColorGrading {
+    luts: HashMap<String, Lut>,
+    // None is no grading
+    from: Option<String>,
+    to: Option<String>,
+    duration: Duration,
+    elapsed: Duration,
+}
+
+impl ColorGrading {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_lut(&mut self, name: &str, lut: Lut) {
+        self.luts.insert(name.to_string(), lut);
+    }
+
+    pub fn load_lut(
+        &mut self,
+        name: &str,
+        path: impl AsRef<Path>,
+    ) -> Result<(), ColorGradingErrors> {
+        let lut = Lut::load(path.as_ref())?;
+        info!(
+            "loaded lut {} ({}^3) from {}",
+            name,
+            lut.get_size(),
+            path.as_ref().display()
+        );
+        self.add_lut(name, lut);
+        Ok(())
+    }
+
+    pub fn get_lut(&self, name: &str) -> Option<&Lut> {
+        self.luts.get(name)
+    }
+
+    // Fades to the LUT over `duration`, None fades back to no grading. A
+    // blend started halfway through another continues from the current mix
+    // of the two, so it snaps to what is halfway blended.
+    pub fn blend_to(
+        &mut self,
+        name: Option<&str>,
+        duration: Duration,
+    ) -> Result<(), ColorGradingErrors> {
+        if let Some(name) = name.filter(|name| !self.luts.contains_key(*name)) {
+            return Err(ColorGradingErrors::UnknownLut(name.to_string()));
+        }
+        if self.get_blend() >= 0.5 {
+            self.from = self.to.take();
+        }
+        self.to = name.map(|name| name.to_string());
+        self.duration = duration;
+        self.elapsed = Duration::ZERO;
+        Ok(())
+    }
+
+    // Grades with the LUT right away
+    pub fn set_lut(&mut self, name: Option<&str>) -> Result<(), ColorGradingErrors> {
+        self.blend_to(name, Duration::ZERO)
+    }
+
+    // Unscaled time, a paused game still finishes its fade
+    pub fn update(&mut self, delta: Duration) {
+        self.elapsed = (self.elapsed + delta).min(self.duration);
+        if self.get_blend() >= 1.0 && self.from != self.to {
+            self.from = self.to.clone();
+        }
+    }
+
+    // 0 shows the old LUT, 1 the new one
+    pub fn get_blend(&self) -> f32 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        self.elapsed.as_secs_f32() / self.duration.as_secs_f32()
+    }
+
+    pub fn get_from(&self) -> Option<&str> {
+        self.from.as_deref()
+    }
+
+    pub fn get_to(&self) -> Option<&str> {
+        self.to.as_deref()
+    }
+
+    pub fn handle_event(&mut self, event: &dyn Event) {
+        if event.get_name() != "ColorGradingBlend" {
+            return;
+        }
+        let Some(data) = event.get_data() else {
+            return;
+        };
+        if let Some(blend) = data.get_ref::<ColorGradingBlend>() {
+            if let Err(err) = self.blend_to(blend.lut.as_deref(), blend.duration) {
+                warn!("{}", err);
+            }
+        }
+    }
+
+    fn lookup(&self, name: Option<&String>, color: Vec3) -> Vec3 {
+        match name.and_then(|name| self.luts.get(name)) {
+            Some(lut) => lut.sample(color),
+            None => color,
+        }
+    }
+
+    pub fn grade(&self, color: Vec3) -> Vec3 {
+        let blend = self.get_blend();
+        let to = self.lookup(self.to.as_ref(), color);
+        if blend >= 1.0 {
+            return to;
+        }
+        self.lookup(self.from.as_ref(), color).lerp(to, blend)
+    }
+
+    // The grading pass on a CPU color buffer, alpha stays as it is
+    pub fn apply(&self, colors: &mut [Vec4]) {
+        if self.from.is_none() && self.to.is_none() {
+            return;
+        }
+        for color in colors.iter_mut() {
+            *color = self.grade(color.truncate()).extend(color.w);
+        }
+    }
+}
+
+const GRADING_SHADER: &str = r#"
+struct Grading {
+    blend: f32,
+    from_size: f32,
+    to_size: f32,
+    _padding: f32,
+}
+
+@group(0) @binding(0) var<uniform> grading: Grading;
+@group(0) @binding(1) var scene: texture_2d<f32>;
+@group(0) @binding(2) var lut_from: texture_2d<f32>;
+@group(0) @binding(3) var lut_to: texture_2d<f32>;
+@group(0) @binding(4) var linear_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// one triangle covering the screen
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// the strip is a 2D texture, blue is blended between two squares by hand
+fn sample_lut(lut: texture_2d<f32>, size: f32, color: vec3<f32>) -> vec3<f32> {
+    let scaled = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0)) * (size - 1.0);
+    let slice = min(floor(scaled.b), size - 2.0);
+    let uv = (scaled.rg + 0.5) / vec2<f32>(size * size, size);
+    let low = textureSampleLevel(lut, linear_sampler, uv + vec2<f32>(slice / size, 0.0), 0.0).rgb;
+    let high = textureSampleLevel(lut, linear_sampler, uv + vec2<f32>((slice + 1.0) / size, 0.0), 0.0).rgb;
+    return mix(low, high, scaled.b - slice);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSampleLevel(scene, linear_sampler, in.uv, 0.0);
+    let old_grade = sample_lut(lut_from, grading.from_size, color.rgb);
+    let new_grade = sample_lut(lut_to, grading.to_size, color.rgb);
+    return vec4<f32>(mix(old_grade, new_grade, grading.blend), color.a);
+}
+"#;
+
+// The GPU side of `ColorGrading`: draws the scene texture graded into the
+// pass's target. LUT textures are uploaded on first use and kept.
+#[derive(Debug)]
+pub struct ColorGradingPass {
+    shader: Shader,
+    pipeline: Pipeline,
+    uniform: Buffer,
+    identity: Texture,
+    luts: HashMap<String, (Texture, usize)>,
+}
+
+impl ColorGradingPass {
+    pub fn new(
+        api: &mut dyn RendererAPI,
+        target_format: TextureFormat,
+    ) -> Result<Self, RendererErrors> {
+        let shader = api.create_shader(&ShaderSource::wgsl("color grading", GRADING_SHADER))?;
+        let mut pipeline = PipelineDescriptor::new("color grading", shader, target_format);
+        pipeline.blend = BlendMode::Opaque;
+        pipeline.bindings = vec![
+            BindingKind::UniformBuffer,
+            BindingKind::Texture,
+            BindingKind::Texture,
+            BindingKind::Texture,
+            BindingKind::Sampler,
+        ];
+        let pipeline = api.create_pipeline(&pipeline)?;
+        let uniform = api.create_buffer(
+            &BufferDescriptor {
+                label: "color grading".to_string(),
+                usage: BufferUsage::Uniform,
+                size: 16,
+            },
+            None,
+        )?;
+        let identity = Self::upload(api, "identity lut", &Lut::identity(IDENTITY_SIZE))?;
+        Ok(Self {
+            shader,
+            pipeline,
+            uniform,
+            identity,
+            luts: HashMap::new(),
+        })
+    }
+
+    fn upload(
+        api: &mut dyn RendererAPI,
+        label: &str,
+        lut: &Lut,
+    ) -> Result<Texture, RendererErrors> {
+        let size = lut.get_size() as u32;
+        let texture = api.create_texture(&TextureDescriptor {
+            label: label.to_string(),
+            width: size * size,
+            height: size,
+            format: TextureFormat::Rgba8Unorm,
+            render_target: false,
+            mip_levels: 1,
+        })?;
+        api.write_texture(texture, &lut.to_strip_rgba())?;
+        Ok(texture)
+    }
+
+    fn get_texture(
+        &mut self,
+        api: &mut dyn RendererAPI,
+        grading: &ColorGrading,
+        name: Option<&str>,
+    ) -> Result<(Texture, usize), RendererErrors> {
+        let Some((name, lut)) = name.and_then(|name| Some((name, grading.get_lut(name)?))) else {
+            return Ok((self.identity, IDENTITY_SIZE));
+        };
+        if let Some(texture) = self.luts.get(name) {
+            return Ok(*texture);
+        }
+        let texture = (Self::upload(api, name, lut)?, lut.get_size());
+        self.luts.insert(name.to_string(), texture);
+        Ok(texture)
+    }
+
+    // Call after replacing a LUT with `ColorGrading::add_lut`
+    pub fn invalidate(&mut self, api: &mut dyn RendererAPI, name: &str) {
+        if let Some((texture, _)) = self.luts.remove(name) {
+            api.destroy(Resource::Texture(texture));
+        }
+    }
+
+    // Records the grading of `scene` into `pass`, whose target is the
+    // graded image
+    pub fn record(
+        &mut self,
+        api: &mut dyn RendererAPI,
+        grading: &ColorGrading,
+        scene: Texture,
+        pass: &mut RenderPass,
+    ) -> Result<(), RendererErrors> {
+        let (from, from_size) = self.get_texture(api, grading, grading.get_from())?;
+        let (to, to_size) = self.get_texture(api, grading, grading.get_to())?;
+        let uniform: Vec<u8> = [grading.get_blend(), from_size as f32, to_size as f32, 0.0]
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        api.write_buffer(self.uniform, 0, &uniform)?;
+
+        pass.set_pipeline(self.pipeline);
+        pass.set_bindings(vec![
+            Binding::Buffer(self.uniform),
+            Binding::Texture(scene),
+            Binding::Texture(from),
+            Binding::Texture(to),
+            Binding::Sampler(Sampler::new(Filter::Linear)),
+        ]);
+        pass.draw(0..3, 0..1);
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, api: &mut dyn RendererAPI) {
+        for (_, (texture, _)) in self.luts.drain() {
+            api.destroy(Resource::Texture(texture));
+        }
+        api.destroy(Resource::Texture(self.identity));
+        api.destroy(Resource::Buffer(self.uniform));
+        api.destroy(Resource::Pipeline(self.pipeline));
+        api.destroy(Resource::Shader(self.shader));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event_system::engine_events::renderer_events::RendererEvents,
+        renderer::api::{headless::HeadlessRenderer, RenderTarget},
+    };
+
+    fn close(a: Vec3, b: Vec3) -> bool {
+        a.distance(b) < 1e-4
+    }
+
+    #[test]
+    fn test_luts_blend_over_time() {
+        let invert = "TITLE \"invert\"\nLUT_3D_SIZE 2\n\
+            1 1 1\n0 1 1\n1 0 1\n0 0 1\n1 1 0\n0 1 0\n1 0 0\n0 0 0\n";
+        let invert = Lut::from_cube(invert).unwrap();
+        let color = Vec3::new(0.25, 0.5, 1.0);
+        assert!(close(invert.sample(color), Vec3::new(0.75, 0.5, 0.0)));
+        assert!(close(Lut::identity(17).sample(color), color));
+
+        let strip = Image::from_rgba(4, 2, invert.to_strip_rgba()).unwrap();
+        assert_eq!(Lut::from_strip(&strip).unwrap(), invert);
+
+        let mut grading = ColorGrading::new();
+        grading.add_lut("cave", invert);
+        grading.handle_event(&RendererEvents::ColorGradingBlend(ColorGradingBlend {
+            lut: Some("cave".to_string()),
+            duration: Duration::from_secs(2),
+        }));
+        grading.update(Duration::from_millis(500));
+        assert!(close(grading.grade(color), Vec3::new(0.375, 0.5, 0.75)));
+
+        grading.update(Duration::from_secs(2));
+        let mut colors = vec![color.extend(0.5)];
+        grading.apply(&mut colors);
+        assert_eq!(colors[0], Vec4::new(0.75, 0.5, 0.0, 0.5));
+        assert_eq!(grading.get_from(), Some("cave"));
+        assert!(grading.blend_to(Some("desert"), Duration::ZERO).is_err());
+
+        let mut api = HeadlessRenderer::new();
+        let mut pass = ColorGradingPass::new(&mut api, TextureFormat::Rgba8Unorm).unwrap();
+        let scene = ColorGradingPass::upload(&mut api, "scene", &Lut::identity(2)).unwrap();
+        let mut render_pass = RenderPass::new("grading", RenderTarget::Surface);
+        pass.record(&mut api, &grading, scene, &mut render_pass)
+            .unwrap();
+        api.submit(&[render_pass]).unwrap();
+        pass.destroy(&mut api);
+        api.destroy(Resource::Texture(scene));
+        assert_eq!(api.get_resource_count(), 0);
+    }
+}
@@ -0,0 +1,377 @@
+use std::{sync::Arc, time::Duration};
+
+use log::warn;
+
+use crate::{
+    animation::sprite::SpritePlayer,
+    core::window::{
+        cursor::CursorImage,
+        dpi::{CursorPosition, WindowSize},
+        Window, WindowErrors,
+    },
+    event_system::event::Event,
+    math::{
+        rect::Rect,
+        transform::{Mat4, Transform},
+        vector::{Vec2, Vec3, Vec4},
+    },
+};
+
+use super::{
+    api::{Filter, RendererAPI, Sampler, TextureFormat},
+    renderer2d::Renderer2D,
+    texture::{Image, Texture2D, TextureErrors},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CursorMode {
+    // drawn by the OS, keeps up with the mouse however slow the frames are
+    #[default]
+    Hardware,
+    // drawn by the engine over the frame, at any size and animated
+    Software,
+}
+
+// A cursor picture from an engine texture, `hotspot` is the pixel of a
+// frame that points, from its top left
+#[derive(Debug, Clone)]
+pub struct TextureCursor {
+    texture: Arc<Texture2D>,
+    // kept to hand hardware cursors to the OS
+    image: Option<Image>,
+    hotspot: Vec2,
+    player: Option<SpritePlayer>,
+}
+
+impl TextureCursor {
+    // The pixels of a texture stay on the GPU, so this one is software only
+    pub fn from_texture(texture: Arc<Texture2D>, hotspot: Vec2) -> Self {
+        Self {
+            texture,
+            image: None,
+            hotspot,
+            player: None,
+        }
+    }
+
+    pub fn from_image(
+        label: &str,
+        image: Image,
+        hotspot: Vec2,
+        api: &mut dyn RendererAPI,
+    ) -> Result<Self, TextureErrors> {
+        if !matches!(
+            image.format,
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb
+        ) {
+            return Err(TextureErrors::Unsupported(format!(
+                "{:?} cursor, cursors take rgba8",
+                image.format
+            )));
+        }
+        let texture = Texture2D::from_image(label, &image, Sampler::new(Filter::Nearest), api)?;
+        Ok(Self {
+            texture: Arc::new(texture),
+            image: Some(image),
+            hotspot,
+            player: None,
+        })
+    }
+
+    // Shows the frames `player` plays instead of the whole texture, the
+    // player's sheet cuts this texture
+    pub fn with_animation(mut self, player: SpritePlayer) -> Self {
+        self.player = Some(player);
+        self
+    }
+
+    pub fn get_texture(&self) -> &Texture2D {
+        &self.texture
+    }
+
+    pub fn get_hotspot(&self) -> Vec2 {
+        self.hotspot
+    }
+
+    pub fn get_player_mut(&mut self) -> Option<&mut SpritePlayer> {
+        self.player.as_mut()
+    }
+
+    fn get_frame(&self) -> u32 {
+        self.player
+            .as_ref()
+            .map(|player| player.get_frame())
+            .unwrap_or(0)
+    }
+
+    fn get_uv(&self) -> Rect {
+        self.player
+            .as_ref()
+            .map(|player| player.get_uv())
+            .unwrap_or(Rect::UNIT)
+    }
+
+    // Pixels of one frame
+    pub fn get_frame_size(&self) -> Vec2 {
+        let (width, height) = self.texture.get_size();
+        let uv = self.get_uv().get_size();
+        Vec2::new(width as f32 * uv.x.abs(), height as f32 * uv.y.abs())
+    }
+
+    // The current frame for the OS, None without pixels on the CPU. Flipped
+    // players show unflipped.
+    pub fn get_cursor_image(&self) -> Option<CursorImage> {
+        let image = self.image.as_ref()?;
+        let uv = self.get_uv();
+        let size = Vec2::new(image.width as f32, image.height as f32);
+        let left = (uv.min.x.min(uv.max.x) * size.x).round() as u32;
+        let top = (uv.min.y.min(uv.max.y) * size.y).round() as u32;
+        let frame = self.get_frame_size();
+        let (width, height) = (frame.x.round() as u32, frame.y.round() as u32);
+        let rgba: Vec<u8> = (top..top + height)
+            .flat_map(|y| {
+                let start = (y * image.width + left) as usize * 4;
+                image.levels[0][start..start + width as usize * 4].iter()
+            })
+            .copied()
+            .collect();
+        let hotspot = (
+            (self.hotspot.x as u32).min(width.saturating_sub(1)),
+            (self.hotspot.y as u32).min(height.saturating_sub(1)),
+        );
+        CursorImage::from_rgba(width, height, rgba, hotspot)
+            .map_err(|err| warn!("unable to make a cursor of the texture: {}", err))
+            .ok()
+    }
+}
+
+// Shows a TextureCursor in place of the system cursor. Hardware mode hands
+// the current frame to the window, software mode hides the system cursor
+// and draws the texture at the mouse in an overlay scene:
+//
+//     cursor.handle_event(event);
+//     cursor.update(delta);
+//     cursor.apply(window)?;
+//     renderer.begin_scene(&cursor.get_camera());
+//     cursor.draw(&mut renderer);
+//     renderer.end_scene(api, &mut overlay_pass)?;
+#[derive(Debug)]
+pub struct CursorRenderer {
+    mode: CursorMode,
+    cursor: Option<TextureCursor>,
+    // physical pixels from the top left, None until the mouse moved
+    position: Option<Vec2>,
+    window: Vec2,
+    scale: f32,
+    // the window shows an outdated cursor
+    dirty: bool,
+    shown_frame: u32,
+}
+
+impl Default for CursorRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CursorRenderer {
+    pub fn new() -> Self {
+        Self {
+            mode: CursorMode::default(),
+            cursor: None,
+            position: None,
+            window: Vec2::ZERO,
+            scale: 1.0,
+            dirty: true,
+            shown_frame: 0,
+        }
+    }
+
+    pub fn get_mode(&self) -> CursorMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: CursorMode) {
+        self.mode = mode;
+        self.dirty = true;
+    }
+
+    // None goes back to the system cursor
+    pub fn set_cursor(&mut self, cursor: Option<TextureCursor>) {
+        self.cursor = cursor;
+        self.dirty = true;
+    }
+
+    pub fn get_cursor_mut(&mut self) -> Option<&mut TextureCursor> {
+        self.cursor.as_mut()
+    }
+
+    // Physical pixels per texture pixel of software cursors, e.g. the UI scale
+    pub fn set_scale(&mut self, scale: f32) {
+        self.scale = scale.max(0.0);
+    }
+
+    // Follows the mouse and the window size
+    pub fn handle_event(&mut self, event: &dyn Event) {
+        let Some(data) = event.get_data() else {
+            return;
+        };
+        match event.get_name().as_str() {
+            "MouseMoved" => {
+                if let Some(cursor) = data.get_ref::<CursorPosition>() {
+                    self.position = Some(cursor.physical);
+                }
+            }
+            "Resized" => {
+                if let Some(size) = data.get_ref::<WindowSize>() {
+                    let size = size.physical;
+                    self.window = Vec2::new(size.width as f32, size.height as f32);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn update(&mut self, delta: Duration) {
+        if let Some(player) = self
+            .cursor
+            .as_mut()
+            .and_then(|cursor| cursor.player.as_mut())
+        {
+            player.update(delta);
+        }
+    }
+
+    // Tells the window what to show, only when that changed. Hardware
+    // cursors without pixels on the CPU switch to software mode.
+    pub fn apply(&mut self, window: &mut dyn Window) -> Result<(), WindowErrors> {
+        let frame = self.cursor.as_ref().map(|cursor| cursor.get_frame());
+        let animated = self.mode == CursorMode::Hardware && frame != Some(self.shown_frame);
+        if !self.dirty && !animated {
+            return Ok(());
+        }
+        self.dirty = false;
+        self.shown_frame = frame.unwrap_or(0);
+
+        let Some(cursor) = self.cursor.as_ref() else {
+            window.set_custom_cursor(None)?;
+            window.set_cursor_visible(true);
+            return Ok(());
+        };
+        if self.mode == CursorMode::Hardware {
+            if let Some(image) = cursor.get_cursor_image() {
+                window.set_custom_cursor(Some(&image))?;
+                window.set_cursor_visible(true);
+                return Ok(());
+            }
+            warn!("the cursor texture has no pixels for the OS, drawing it in software");
+            self.mode = CursorMode::Software;
+        }
+        window.set_custom_cursor(None)?;
+        window.set_cursor_visible(false);
+        Ok(())
+    }
+
+    // Physical pixels of the window with y up, what `draw` draws in
+    pub fn get_camera(&self) -> Mat4 {
+        Mat4::orthographic(0.0, self.window.x, 0.0, self.window.y, -1.0, 1.0)
+    }
+
+    // Draws the software cursor, nothing in hardware mode
+    pub fn draw(&self, renderer: &mut Renderer2D) {
+        let (Some(cursor), Some(position)) = (self.cursor.as_ref(), self.position) else {
+            return;
+        };
+        if self.mode != CursorMode::Software {
+            return;
+        }
+        let size = cursor.get_frame_size() * self.scale;
+        let top_left = position - cursor.get_hotspot() * self.scale;
+        let center = Vec2::new(
+            top_left.x + size.x / 2.0,
+            self.window.y - top_left.y - size.y / 2.0,
+        );
+        let transform = Transform {
+            translation: Vec3::new(center.x, center.y, 0.0),
+            scale: Vec3::new(size.x, size.y, 1.0),
+            ..Transform::IDENTITY
+        };
+        renderer.draw_sprite(
+            cursor.get_texture().get_handle(),
+            &transform,
+            Vec4::splat(1.0),
+            cursor.get_uv(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        animation::sprite::{SpriteClip, SpriteSheet},
+        core::window::headless_window::HeadlessWindow,
+        event_system::engine_events::{mouse_events::MouseEvents, window_events::WindowEvents},
+        renderer::api::{headless::HeadlessRenderer, RenderPass, RenderTarget},
+    };
+
+    #[test]
+    fn test_hardware_and_software_cursors() {
+        let mut api = HeadlessRenderer::new();
+        let mut window = HeadlessWindow::new();
+        // two 2x2 frames side by side, the first red and the second blue
+        let pixels: Vec<u8> = (0..8)
+            .flat_map(|index| match index % 4 < 2 {
+                true => [255, 0, 0, 255],
+                false => [0, 0, 255, 255],
+            })
+            .collect();
+        let image = Image::from_rgba(4, 2, pixels).unwrap();
+        let mut sheet = SpriteSheet::new(2, 1);
+        sheet
+            .add_clip(SpriteClip {
+                name: "spin".to_string(),
+                first: 0,
+                last: 1,
+                frame_time: 0.1,
+                ..SpriteClip::default()
+            })
+            .unwrap();
+        let mut player = SpritePlayer::new(Arc::new(sheet));
+        player.play("spin").unwrap();
+        let cursor = TextureCursor::from_image("cursor", image, Vec2::new(1.0, 1.0), &mut api)
+            .unwrap()
+            .with_animation(player);
+
+        let mut renderer = CursorRenderer::new();
+        renderer.set_cursor(Some(cursor));
+        renderer.apply(&mut window).unwrap();
+        let shown = window.get_custom_cursor().unwrap();
+        assert_eq!((shown.get_size(), shown.get_hotspot()), ((2, 2), (1, 1)));
+        assert_eq!(&shown.get_rgba()[..4], &[255, 0, 0, 255]);
+
+        // the next frame is handed over once it shows
+        renderer.update(Duration::from_millis(150));
+        renderer.apply(&mut window).unwrap();
+        let shown = window.get_custom_cursor().unwrap();
+        assert_eq!(&shown.get_rgba()[..4], &[0, 0, 255, 255]);
+
+        // software mode hides the system cursor and draws at the mouse
+        renderer.set_mode(CursorMode::Software);
+        renderer.set_scale(2.0);
+        renderer.apply(&mut window).unwrap();
+        let state = window.get_cursor_state();
+        assert!(!state.visible && !state.custom);
+        renderer.handle_event(&WindowEvents::Resized(WindowSize::new(100, 50, 1.0)));
+        renderer.handle_event(&MouseEvents::Moved(CursorPosition::new(
+            Vec2::new(10.0, 10.0),
+            1.0,
+        )));
+        let mut renderer2d = Renderer2D::new(&mut api).unwrap();
+        renderer2d.begin_scene(&renderer.get_camera());
+        renderer.draw(&mut renderer2d);
+        let mut pass = RenderPass::new("overlay", RenderTarget::Surface);
+        renderer2d.end_scene(&mut api, &mut pass).unwrap();
+        assert_eq!(renderer2d.get_stats().quads, 1);
+    }
+}
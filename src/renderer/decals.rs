@@ -0,0 +1,309 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::Duration,
+};
+
+use log::warn;
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::event::EntityId,
+    math::vector::{Vec2, Vec3},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DecalId(pub u64);
+
+// Normalized texture coordinates inside the decal atlas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UvRect {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl UvRect {
+    pub const FULL: Self = Self {
+        min: Vec2::ZERO,
+        max: Vec2::new(1.0, 1.0),
+    };
+}
+
+// All decals share one texture so they can be drawn in a single batch, each
+// decal kind is a named region of it
+#[derive(Debug, Clone, Default)]
+pub struct DecalAtlas {
+    regions: HashMap<String, UvRect>,
+}
+
+impl DecalAtlas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Names the cells of an evenly split atlas row by row
+    pub fn from_grid(columns: u32, rows: u32, names: &[&str]) -> Self {
+        let mut atlas = Self::new();
+        let cell = Vec2::new(1.0 / columns.max(1) as f32, 1.0 / rows.max(1) as f32);
+        for (index, name) in names.iter().enumerate().take((columns * rows) as usize) {
+            let column = index as u32 % columns;
+            let row = index as u32 / columns;
+            let min = Vec2::new(column as f32 * cell.x, row as f32 * cell.y);
+            atlas.add_region(
+                name,
+                UvRect {
+                    min,
+                    max: min + cell,
+                },
+            );
+        }
+        atlas
+    }
+
+    pub fn add_region(&mut self, name: &str, rect: UvRect) {
+        self.regions.insert(name.to_string(), rect);
+    }
+
+    pub fn get_region(&self, name: &str) -> Option<UvRect> {
+        self.regions.get(name).copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecalProjection {
+    // a box projected onto the depth buffer, works on any surface
+    #[default]
+    ScreenSpace,
+    // a quad clipped against the receiving mesh, cheaper for flat ground
+    Mesh,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decal {
+    pub position: Vec3,
+    // the decal is projected along -normal
+    pub normal: Vec3,
+    pub size: Vec2,
+    pub uv: UvRect,
+    pub projection: DecalProjection,
+    // None keeps the decal until the pool recycles it
+    pub lifetime: Option<Duration>,
+    // the last part of the lifetime is spent fading out
+    pub fade_out: Duration,
+    // decals owned by an entity are removed along with it
+    pub owner: Option<EntityId>,
+}
+
+impl Decal {
+    pub fn new(position: Vec3, normal: Vec3, size: Vec2, uv: UvRect) -> Self {
+        Self {
+            position,
+            normal,
+            size,
+            uv,
+            projection: DecalProjection::default(),
+            lifetime: None,
+            fade_out: Duration::ZERO,
+            owner: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LiveDecal {
+    decal: Decal,
+    age: Duration,
+}
+
+impl LiveDecal {
+    fn get_alpha(&self) -> f32 {
+        let Some(lifetime) = self.decal.lifetime else {
+            return 1.0;
+        };
+        let remaining = lifetime.saturating_sub(self.age);
+        if self.decal.fade_out.is_zero() || remaining >= self.decal.fade_out {
+            return 1.0;
+        }
+        remaining.as_secs_f32() / self.decal.fade_out.as_secs_f32()
+    }
+}
+
+// What the renderer draws for one decal
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecalInstance<'a> {
+    pub id: DecalId,
+    pub decal: &'a Decal,
+    pub alpha: f32,
+}
+
+// Keeps at most `budget` decals alive so their per-frame cost stays bounded,
+// spawning into a full pool recycles the oldest decal.
+#[derive(Debug)]
+pub struct DecalPool {
+    budget: usize,
+    next_id: u64,
+    // ordered by id, so the first entry is always the oldest
+    decals: BTreeMap<DecalId, LiveDecal>,
+}
+
+impl DecalPool {
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            next_id: 0,
+            decals: Default::default(),
+        }
+    }
+
+    pub fn get_budget(&self) -> usize {
+        self.budget
+    }
+
+    // Shrinking the budget drops the oldest decals right away
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        while self.decals.len() > self.budget {
+            self.decals.pop_first();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.decals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decals.is_empty()
+    }
+
+    // Returns None only for a zero budget
+    pub fn spawn(&mut self, decal: Decal) -> Option<DecalId> {
+        if self.budget == 0 {
+            warn!("decal budget is 0, dropping decal");
+            return None;
+        }
+        while self.decals.len() >= self.budget {
+            self.decals.pop_first();
+        }
+        let id = DecalId(self.next_id);
+        self.next_id += 1;
+        self.decals.insert(
+            id,
+            LiveDecal {
+                decal,
+                age: Duration::ZERO,
+            },
+        );
+        Some(id)
+    }
+
+    pub fn remove(&mut self, id: DecalId) -> bool {
+        self.decals.remove(&id).is_some()
+    }
+
+    // Called when the owning entity is despawned
+    pub fn remove_owned(&mut self, owner: EntityId) -> usize {
+        let before = self.decals.len();
+        self.decals
+            .retain(|_, live| live.decal.owner != Some(owner));
+        before - self.decals.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.decals.clear();
+    }
+
+    // Ages every decal and drops the expired ones
+    pub fn update(&mut self, delta: Duration) {
+        self.decals.retain(|_, live| {
+            live.age += delta;
+            live.decal
+                .lifetime
+                .is_none_or(|lifetime| live.age < lifetime)
+        });
+    }
+
+    // Oldest first, so newer decals are drawn on top
+    pub fn instances(&self) -> impl Iterator<Item = DecalInstance<'_>> {
+        self.decals.iter().map(|(id, live)| DecalInstance {
+            id: *id,
+            decal: &live.decal,
+            alpha: live.get_alpha(),
+        })
+    }
+}
+
+impl Default for DecalPool {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl Subsystem for DecalPool {
+    fn get_name(&self) -> &str {
+        "DecalPool"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    // Lifetimes run on scaled time, decals freeze while the game is paused
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        self.update(ctx.time.get_delta());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decal() -> Decal {
+        Decal::new(Vec3::ZERO, Vec3::Y, Vec2::splat(1.0), UvRect::FULL)
+    }
+
+    #[test]
+    fn test_pool_recycles_oldest() {
+        let mut pool = DecalPool::new(2);
+        let first = pool.spawn(decal()).unwrap();
+        let second = pool.spawn(decal()).unwrap();
+        let third = pool.spawn(decal()).unwrap();
+
+        let ids: Vec<DecalId> = pool.instances().map(|instance| instance.id).collect();
+        assert_eq!(ids, vec![second, third]);
+        assert!(!pool.remove(first));
+        assert_eq!(DecalPool::new(0).spawn(decal()), None);
+    }
+
+    #[test]
+    fn test_fade_out_and_owner() {
+        let mut pool = DecalPool::new(8);
+        let mut bullet_hole = decal();
+        bullet_hole.lifetime = Some(Duration::from_secs(4));
+        bullet_hole.fade_out = Duration::from_secs(2);
+        pool.spawn(bullet_hole);
+        let mut blob_shadow = decal();
+        blob_shadow.owner = Some(EntityId(7));
+        pool.spawn(blob_shadow);
+
+        pool.update(Duration::from_secs(3));
+        let alphas: Vec<f32> = pool.instances().map(|instance| instance.alpha).collect();
+        assert_eq!(alphas, vec![0.5, 1.0]);
+
+        pool.update(Duration::from_secs(1));
+        assert_eq!(pool.len(), 1, "Expired decals are removed");
+        assert_eq!(pool.remove_owned(EntityId(7)), 1);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn test_atlas_grid() {
+        let atlas = DecalAtlas::from_grid(2, 2, &["bullet", "scorch", "blood"]);
+        assert_eq!(
+            atlas.get_region("blood"),
+            Some(UvRect {
+                min: Vec2::new(0.0, 0.5),
+                max: Vec2::new(0.5, 1.0)
+            })
+        );
+        assert_eq!(atlas.get_region("footprint"), None);
+    }
+}
@@ -0,0 +1,671 @@
+use std::{collections::HashMap, time::Duration};
+
+pub use egui;
+use egui::{
+    epaint::{ImageDelta, Primitive},
+    ClippedPrimitive, Key, Modifiers, MouseWheelUnit, PointerButton, Pos2, RawInput, TextureId,
+    TexturesDelta, ViewportId,
+};
+
+use crate::{
+    core::{
+        key_code::KeyCode,
+        window::dpi::{CursorPosition, WindowSize},
+    },
+    event_system::{
+        engine_events::{keyboard_events::ImePreedit, mouse_events::MouseButton},
+        event::Event,
+    },
+    math::vector::Vec2,
+};
+
+use super::api::{
+    Binding, BindingKind, BlendMode, Buffer, BufferDescriptor, BufferUsage, Filter, Pipeline,
+    PipelineDescriptor, RenderPass, RendererAPI, RendererErrors, Resource, Sampler, Shader,
+    ShaderSource, Texture, TextureDescriptor, TextureFormat, VertexFormat, VertexLayout,
+};
+
+// egui hands out sRGB colors with premultiplied alpha, they are made linear
+// for sRGB targets and kept as they are for the others
+const EGUI_SHADER: &str = "
+struct Screen {
+    size: vec2<f32>,
+    srgb_target: f32,
+    padding: f32,
+};
+
+@group(0) @binding(0) var<uniform> screen: Screen;
+@group(0) @binding(1) var image: texture_2d<f32>;
+@group(0) @binding(2) var image_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+fn to_linear(srgb: vec3<f32>) -> vec3<f32> {
+    let low = srgb / 12.92;
+    let high = pow((srgb + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, srgb <= vec3<f32>(0.04045));
+}
+
+fn to_gamma(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+@vertex
+fn vs_main(
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = vec4<f32>(
+        2.0 * position.x / screen.size.x - 1.0,
+        1.0 - 2.0 * position.y / screen.size.y,
+        0.0,
+        1.0,
+    );
+    out.uv = uv;
+    out.color = vec4<f32>(to_linear(color.rgb), color.a);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = in.color * textureSample(image, image_sampler, in.uv);
+    if screen.srgb_target > 0.5 {
+        return color;
+    }
+    return vec4<f32>(to_gamma(color.rgb), color.a);
+}
+";
+
+// position, uv and color, 8 bytes each for the floats and 4 for the color
+const VERTEX_SIZE: usize = 2 * 4 + 2 * 4 + 4;
+
+#[derive(Debug)]
+struct EguiTexture {
+    texture: Texture,
+    size: [usize; 2],
+    filter: Filter,
+    // kept to apply partial updates, the API writes whole textures
+    pixels: Vec<u8>,
+}
+
+// Runs egui on top of the engine: engine input and window events are fed
+// to it through `handle_event`, `run` builds the UI of the frame and
+// `render` records it into a pass drawing over the scene.
+//
+//     if !egui_layer.handle_event(event) { game.handle_event(event) }
+//     egui_layer.run(delta, |ctx| {
+//         egui::Window::new("Stats").show(ctx, |ui| ui.label(fps));
+//     });
+//     egui_layer.render(api, &mut overlay_pass)?;
+pub struct EguiLayer {
+    context: egui::Context,
+    events: Vec<egui::Event>,
+    modifiers: Modifiers,
+    pointer: Pos2,
+    focused: bool,
+    time: Duration,
+    // in physical pixels
+    screen_size: (u32, u32),
+    scale_factor: f32,
+    primitives: Vec<ClippedPrimitive>,
+    textures_delta: TexturesDelta,
+    // freed after the frame that still drew with them
+    pending_free: Vec<TextureId>,
+    copied_text: Option<String>,
+
+    shader: Shader,
+    pipeline: Pipeline,
+    screen_buffer: Buffer,
+    srgb_target: bool,
+    textures: HashMap<TextureId, EguiTexture>,
+    // with their size in bytes, grown when a frame outgrows them
+    vertex_buffer: Option<(Buffer, u64)>,
+    index_buffer: Option<(Buffer, u64)>,
+}
+
+impl EguiLayer {
+    pub fn new(api: &mut dyn RendererAPI) -> Result<Self, RendererErrors> {
+        let shader = api.create_shader(&ShaderSource::wgsl("egui", EGUI_SHADER))?;
+        let format = api.get_surface_format();
+        let mut pipeline = PipelineDescriptor::new("egui", shader, format);
+        pipeline.vertex_layouts = vec![VertexLayout::packed(&[
+            VertexFormat::Float32x2,
+            VertexFormat::Float32x2,
+            VertexFormat::Unorm8x4,
+        ])];
+        pipeline.bindings = vec![
+            BindingKind::UniformBuffer,
+            BindingKind::Texture,
+            BindingKind::Sampler,
+        ];
+        pipeline.blend = BlendMode::PremultipliedAlpha;
+        let pipeline = api.create_pipeline(&pipeline)?;
+        let screen_buffer = api.create_buffer(
+            &BufferDescriptor {
+                label: "egui screen".to_string(),
+                usage: BufferUsage::Uniform,
+                size: 16,
+            },
+            None,
+        )?;
+
+        Ok(Self {
+            context: egui::Context::default(),
+            events: Vec::new(),
+            modifiers: Modifiers::default(),
+            pointer: Pos2::ZERO,
+            focused: true,
+            time: Duration::ZERO,
+            screen_size: api.get_surface_size(),
+            scale_factor: 1.0,
+            primitives: Vec::new(),
+            textures_delta: TexturesDelta::default(),
+            pending_free: Vec::new(),
+            copied_text: None,
+            shader,
+            pipeline,
+            screen_buffer,
+            srgb_target: format.is_srgb(),
+            textures: HashMap::new(),
+            vertex_buffer: None,
+            index_buffer: None,
+        })
+    }
+
+    pub fn get_context(&self) -> &egui::Context {
+        &self.context
+    }
+
+    // Text egui copied or cut this frame, for the clipboard
+    pub fn take_copied_text(&mut self) -> Option<String> {
+        self.copied_text.take()
+    }
+
+    fn to_points(&self, physical: Vec2) -> Pos2 {
+        Pos2::new(
+            physical.x / self.scale_factor,
+            physical.y / self.scale_factor,
+        )
+    }
+
+    fn set_modifier(&mut self, key: KeyCode, pressed: bool) {
+        match key {
+            KeyCode::LeftShift | KeyCode::RightShift => self.modifiers.shift = pressed,
+            KeyCode::LeftControl | KeyCode::RightControl => {
+                self.modifiers.ctrl = pressed;
+                self.modifiers.command = pressed;
+            }
+            KeyCode::LeftAlt | KeyCode::RightAlt => self.modifiers.alt = pressed,
+            KeyCode::LeftSuper | KeyCode::RightSuper => self.modifiers.mac_cmd = pressed,
+            _ => {}
+        }
+    }
+
+    fn on_key(&mut self, key: KeyCode, pressed: bool, repeat: bool) {
+        self.set_modifier(key, pressed);
+        if pressed && self.modifiers.command {
+            match key {
+                KeyCode::C => self.events.push(egui::Event::Copy),
+                KeyCode::X => self.events.push(egui::Event::Cut),
+                _ => {}
+            }
+        }
+        if let Some(key) = to_egui_key(key) {
+            self.events.push(egui::Event::Key {
+                key,
+                physical_key: Some(key),
+                pressed,
+                repeat,
+                modifiers: self.modifiers,
+            });
+        }
+    }
+
+    // Feeds an engine event to egui, true when egui uses the input so the
+    // game should ignore it (the cursor is over a window, a text field has
+    // focus...)
+    pub fn handle_event(&mut self, event: &dyn Event) -> bool {
+        let name = event.get_name();
+        let Some(data) = event.get_data() else {
+            return false;
+        };
+        match name.as_str() {
+            "MouseMoved" => {
+                if let Some(position) = data.get_ref::<CursorPosition>() {
+                    self.scale_factor = position.scale_factor as f32;
+                    self.pointer = self.to_points(position.physical);
+                    self.events.push(egui::Event::PointerMoved(self.pointer));
+                }
+                self.context.wants_pointer_input()
+            }
+            "MouseButtonPressed" | "MouseButtonReleased" => {
+                let Some(button) = data.get_ref::<MouseButton>().and_then(to_pointer_button) else {
+                    return false;
+                };
+                self.events.push(egui::Event::PointerButton {
+                    pos: self.pointer,
+                    button,
+                    pressed: name == "MouseButtonPressed",
+                    modifiers: self.modifiers,
+                });
+                self.context.wants_pointer_input()
+            }
+            "MouseScrolled" => {
+                if let Some(delta) = data.get_ref::<Vec2>() {
+                    self.events.push(egui::Event::MouseWheel {
+                        unit: MouseWheelUnit::Line,
+                        delta: egui::vec2(delta.x, delta.y),
+                        modifiers: self.modifiers,
+                    });
+                }
+                self.context.wants_pointer_input()
+            }
+            "KeyPressed" | "KeyReleased" => {
+                let Some(key) = data.get_ref::<KeyCode>().copied() else {
+                    return false;
+                };
+                let pressed = name == "KeyPressed";
+                // the repeat flag is not in the payload, a press of a key
+                // egui already holds is a repeat
+                let repeat = pressed
+                    && to_egui_key(key).is_some_and(|key| {
+                        self.context.input(|input| input.keys_down.contains(&key))
+                    });
+                self.on_key(key, pressed, repeat);
+                self.context.wants_keyboard_input()
+            }
+            "CharTyped" => {
+                match data.get_ref::<char>() {
+                    Some(character) if !character.is_control() => {
+                        self.events.push(egui::Event::Text(character.to_string()));
+                    }
+                    _ => {}
+                }
+                self.context.wants_keyboard_input()
+            }
+            "ClipboardPasted" => {
+                if let Some(text) = data.get_ref::<String>() {
+                    self.events.push(egui::Event::Paste(text.clone()));
+                }
+                self.context.wants_keyboard_input()
+            }
+            "ImePreedit" => {
+                if let Some(preedit) = data.get_ref::<ImePreedit>() {
+                    self.events.push(egui::Event::Ime(egui::ImeEvent::Preedit(
+                        preedit.text.clone(),
+                    )));
+                }
+                self.context.wants_keyboard_input()
+            }
+            "ImeCommit" => {
+                if let Some(text) = data.get_ref::<String>() {
+                    self.events
+                        .push(egui::Event::Ime(egui::ImeEvent::Commit(text.clone())));
+                }
+                self.context.wants_keyboard_input()
+            }
+            "Resized" => {
+                if let Some(size) = data.get_ref::<WindowSize>() {
+                    self.screen_size = (size.physical.width, size.physical.height);
+                    self.scale_factor = size.scale_factor as f32;
+                }
+                false
+            }
+            "ScaleFactorChanged" => {
+                if let Some(scale_factor) = data.get_ref::<f64>() {
+                    self.scale_factor = *scale_factor as f32;
+                }
+                false
+            }
+            "FocusChanged" => {
+                if let Some(focused) = data.get_ref::<bool>() {
+                    self.focused = *focused;
+                    self.events.push(egui::Event::WindowFocused(*focused));
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    // Builds the UI of this frame from the input gathered since the last one
+    pub fn run(&mut self, delta: Duration, ui: impl FnMut(&egui::Context)) {
+        self.time += delta;
+        let mut input = RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(
+                Pos2::ZERO,
+                egui::vec2(
+                    self.screen_size.0 as f32 / self.scale_factor,
+                    self.screen_size.1 as f32 / self.scale_factor,
+                ),
+            )),
+            time: Some(self.time.as_secs_f64()),
+            predicted_dt: delta.as_secs_f32(),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            focused: self.focused,
+            ..Default::default()
+        };
+        input
+            .viewports
+            .entry(ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(self.scale_factor);
+
+        let output = self.context.run(input, ui);
+        for command in output.platform_output.commands {
+            if let egui::OutputCommand::CopyText(text) = command {
+                self.copied_text = Some(text);
+            }
+        }
+        self.textures_delta.append(output.textures_delta);
+        self.primitives = self
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+    }
+
+    fn apply_image(
+        &mut self,
+        api: &mut dyn RendererAPI,
+        id: TextureId,
+        delta: &ImageDelta,
+    ) -> Result<(), RendererErrors> {
+        let egui::ImageData::Color(image) = &delta.image;
+        let filter = match delta.options.magnification {
+            egui::TextureFilter::Nearest => Filter::Nearest,
+            egui::TextureFilter::Linear => Filter::Linear,
+        };
+        let pixels: Vec<u8> = image
+            .pixels
+            .iter()
+            .flat_map(|color| color.to_array())
+            .collect();
+
+        let [x, y] = delta.pos.unwrap_or([0, 0]);
+        let existing = self.textures.get_mut(&id);
+        let texture = match (delta.pos, existing) {
+            (Some(_), Some(texture)) => {
+                let width = image.size[0];
+                for row in 0..image.size[1] {
+                    let start = ((y + row) * texture.size[0] + x) * 4;
+                    texture.pixels[start..start + width * 4]
+                        .copy_from_slice(&pixels[row * width * 4..(row + 1) * width * 4]);
+                }
+                texture.filter = filter;
+                texture
+            }
+            (_, existing) => {
+                if let Some(old) = existing {
+                    api.destroy(Resource::Texture(old.texture));
+                }
+                let texture = api.create_texture(&TextureDescriptor {
+                    label: format!("egui {:?}", id),
+                    width: image.size[0] as u32,
+                    height: image.size[1] as u32,
+                    format: TextureFormat::Rgba8UnormSrgb,
+                    render_target: false,
+                    mip_levels: 1,
+                })?;
+                self.textures.insert(
+                    id,
+                    EguiTexture {
+                        texture,
+                        size: image.size,
+                        filter,
+                        pixels,
+                    },
+                );
+                self.textures.get_mut(&id).expect("inserted above")
+            }
+        };
+        api.write_texture(texture.texture, &texture.pixels)
+    }
+
+    // Grows `buffer` to hold `data` and writes it
+    fn upload(
+        api: &mut dyn RendererAPI,
+        buffer: &mut Option<(Buffer, u64)>,
+        usage: BufferUsage,
+        data: &[u8],
+    ) -> Result<Buffer, RendererErrors> {
+        let handle = match *buffer {
+            Some((handle, size)) if size >= data.len() as u64 => handle,
+            old => {
+                if let Some((handle, _)) = old {
+                    api.destroy(Resource::Buffer(handle));
+                }
+                let size = (data.len() as u64).next_power_of_two();
+                let handle = api.create_buffer(
+                    &BufferDescriptor {
+                        label: "egui meshes".to_string(),
+                        usage,
+                        size,
+                    },
+                    None,
+                )?;
+                *buffer = Some((handle, size));
+                handle
+            }
+        };
+        api.write_buffer(handle, 0, data)?;
+        Ok(handle)
+    }
+
+    // Uploads the textures and meshes of the last `run` and records them
+    // into `pass`, which should target the surface without clearing it
+    pub fn render(
+        &mut self,
+        api: &mut dyn RendererAPI,
+        pass: &mut RenderPass,
+    ) -> Result<(), RendererErrors> {
+        for id in std::mem::take(&mut self.pending_free) {
+            if let Some(texture) = self.textures.remove(&id) {
+                api.destroy(Resource::Texture(texture.texture));
+            }
+        }
+        let delta = std::mem::take(&mut self.textures_delta);
+        for (id, image) in delta.set.iter() {
+            self.apply_image(api, *id, image)?;
+        }
+        self.pending_free = delta.free;
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut draws = Vec::new();
+        for clipped in self.primitives.iter() {
+            let Primitive::Mesh(mesh) = &clipped.primitive else {
+                continue;
+            };
+            if mesh.indices.is_empty() {
+                continue;
+            }
+            let base_vertex = (vertices.len() / VERTEX_SIZE) as i32;
+            let first_index = (indices.len() / 4) as u32;
+            for vertex in mesh.vertices.iter() {
+                vertices.extend_from_slice(&vertex.pos.x.to_le_bytes());
+                vertices.extend_from_slice(&vertex.pos.y.to_le_bytes());
+                vertices.extend_from_slice(&vertex.uv.x.to_le_bytes());
+                vertices.extend_from_slice(&vertex.uv.y.to_le_bytes());
+                vertices.extend_from_slice(&vertex.color.to_array());
+            }
+            for index in mesh.indices.iter() {
+                indices.extend_from_slice(&index.to_le_bytes());
+            }
+            let range = first_index..first_index + mesh.indices.len() as u32;
+            draws.push((clipped.clip_rect, mesh.texture_id, range, base_vertex));
+        }
+        if draws.is_empty() {
+            return Ok(());
+        }
+
+        let (width, height) = self.screen_size;
+        let screen = [
+            width as f32 / self.scale_factor,
+            height as f32 / self.scale_factor,
+            if self.srgb_target { 1.0 } else { 0.0 },
+            0.0,
+        ];
+        let screen: Vec<u8> = screen
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        api.write_buffer(self.screen_buffer, 0, &screen)?;
+        let vertex_buffer =
+            Self::upload(api, &mut self.vertex_buffer, BufferUsage::Vertex, &vertices)?;
+        let index_buffer = Self::upload(api, &mut self.index_buffer, BufferUsage::Index, &indices)?;
+
+        pass.set_pipeline(self.pipeline);
+        pass.set_vertex_buffer(0, vertex_buffer);
+        pass.set_index_buffer(index_buffer);
+        for (clip, texture_id, range, base_vertex) in draws {
+            let Some(texture) = self.textures.get(&texture_id) else {
+                continue;
+            };
+            // clip rects are in points, scissors in physical pixels inside
+            // the target
+            let min_x = (clip.min.x * self.scale_factor)
+                .round()
+                .clamp(0.0, width as f32) as u32;
+            let min_y = (clip.min.y * self.scale_factor)
+                .round()
+                .clamp(0.0, height as f32) as u32;
+            let max_x = (clip.max.x * self.scale_factor)
+                .round()
+                .clamp(0.0, width as f32) as u32;
+            let max_y = (clip.max.y * self.scale_factor)
+                .round()
+                .clamp(0.0, height as f32) as u32;
+            if max_x <= min_x || max_y <= min_y {
+                continue;
+            }
+            pass.set_scissor((min_x, min_y), (max_x - min_x, max_y - min_y));
+            pass.set_bindings(vec![
+                Binding::Buffer(self.screen_buffer),
+                Binding::Texture(texture.texture),
+                Binding::Sampler(Sampler::new(texture.filter)),
+            ]);
+            pass.draw_indexed(range, base_vertex, 0..1);
+        }
+        Ok(())
+    }
+
+    // Frees the GPU resources, the layer is unusable afterwards
+    pub fn destroy(&mut self, api: &mut dyn RendererAPI) {
+        for (_, texture) in self.textures.drain() {
+            api.destroy(Resource::Texture(texture.texture));
+        }
+        for (buffer, _) in [self.vertex_buffer.take(), self.index_buffer.take()]
+            .into_iter()
+            .flatten()
+        {
+            api.destroy(Resource::Buffer(buffer));
+        }
+        api.destroy(Resource::Buffer(self.screen_buffer));
+        api.destroy(Resource::Pipeline(self.pipeline));
+        api.destroy(Resource::Shader(self.shader));
+    }
+}
+
+fn to_pointer_button(button: &MouseButton) -> Option<PointerButton> {
+    match button {
+        MouseButton::Left => Some(PointerButton::Primary),
+        MouseButton::Right => Some(PointerButton::Secondary),
+        MouseButton::Middle => Some(PointerButton::Middle),
+        MouseButton::Back => Some(PointerButton::Extra1),
+        MouseButton::Forward => Some(PointerButton::Extra2),
+        MouseButton::Other(_) => None,
+    }
+}
+
+fn to_egui_key(key: KeyCode) -> Option<Key> {
+    let key = match key {
+        KeyCode::Left => Key::ArrowLeft,
+        KeyCode::Right => Key::ArrowRight,
+        KeyCode::Up => Key::ArrowUp,
+        KeyCode::Down => Key::ArrowDown,
+        KeyCode::Escape => Key::Escape,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Enter | KeyCode::KPEnter => Key::Enter,
+        KeyCode::Space => Key::Space,
+        KeyCode::Insert => Key::Insert,
+        KeyCode::Delete => Key::Delete,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Minus => Key::Minus,
+        KeyCode::Equal => Key::Equals,
+        KeyCode::D0 | KeyCode::KP0 => Key::Num0,
+        KeyCode::D1 | KeyCode::KP1 => Key::Num1,
+        KeyCode::D2 | KeyCode::KP2 => Key::Num2,
+        KeyCode::D3 | KeyCode::KP3 => Key::Num3,
+        KeyCode::D4 | KeyCode::KP4 => Key::Num4,
+        KeyCode::D5 | KeyCode::KP5 => Key::Num5,
+        KeyCode::D6 | KeyCode::KP6 => Key::Num6,
+        KeyCode::D7 | KeyCode::KP7 => Key::Num7,
+        KeyCode::D8 | KeyCode::KP8 => Key::Num8,
+        KeyCode::D9 | KeyCode::KP9 => Key::Num9,
+        // letters and function keys are named the same
+        other => return Key::from_name(&format!("{:?}", other)),
+    };
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event_system::engine_events::mouse_events::MouseEvents,
+        renderer::api::{headless::HeadlessRenderer, RenderTarget},
+    };
+
+    #[test]
+    fn test_feeds_input_and_renders() {
+        let mut api = HeadlessRenderer::new();
+        api.resize(320, 240);
+        let mut layer = EguiLayer::new(&mut api).unwrap();
+        let mut clicked = false;
+        let frame = |layer: &mut EguiLayer, clicked: &mut bool| {
+            layer.run(Duration::from_millis(16), |ctx| {
+                egui::Area::new(egui::Id::new("debug"))
+                    .fixed_pos(Pos2::ZERO)
+                    .show(ctx, |ui| {
+                        *clicked |= ui.button("Reload").clicked();
+                    });
+            });
+        };
+        frame(&mut layer, &mut clicked);
+
+        let over = CursorPosition::new(Vec2::new(10.0, 10.0), 1.0);
+        layer.handle_event(&MouseEvents::Moved(over));
+        frame(&mut layer, &mut clicked);
+        assert!(layer.handle_event(&MouseEvents::ButtonPressed(MouseButton::Left)));
+        frame(&mut layer, &mut clicked);
+        layer.handle_event(&MouseEvents::ButtonReleased(MouseButton::Left));
+        frame(&mut layer, &mut clicked);
+        assert!(clicked);
+
+        let away = CursorPosition::new(Vec2::new(500.0, 500.0), 1.0);
+        layer.handle_event(&MouseEvents::Moved(away));
+        frame(&mut layer, &mut clicked);
+        assert!(!layer.handle_event(&MouseEvents::ButtonPressed(MouseButton::Left)));
+
+        let mut pass = RenderPass::new("ui", RenderTarget::Surface);
+        layer.render(&mut api, &mut pass).unwrap();
+        assert!(pass.get_draw_count() > 0);
+        // the font atlas
+        assert_eq!(layer.textures.len(), 1);
+        api.submit(&[pass]).unwrap();
+        layer.destroy(&mut api);
+        assert_eq!(api.get_resource_count(), 0);
+    }
+}
@@ -0,0 +1,649 @@
+use std::{
+    collections::HashMap,
+    f32::consts::PI,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use log::{error, info, warn};
+use thiserror::Error;
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::engine_events::renderer_events::RendererEvents,
+    math::vector::Vec3,
+};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum EnvironmentErrors {
+    #[error("unable to read environment map: {0}")]
+    Io(String),
+
+    #[error("invalid hdr image: {0}")]
+    InvalidHdr(String),
+
+    #[error("unknown environment {0}")]
+    UnknownEnvironment(String),
+}
+
+// Linear HDR pixels, row by row from the top left
+#[derive(Debug, Clone, PartialEq)]
+pub struct HdrImage {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Vec3>,
+}
+
+fn rgbe_to_rgb(rgbe: [u8; 4]) -> Vec3 {
+    if rgbe[3] == 0 {
+        return Vec3::ZERO;
+    }
+    let scale = 2f32.powi(rgbe[3] as i32 - 136);
+    Vec3::new(
+        rgbe[0] as f32 * scale,
+        rgbe[1] as f32 * scale,
+        rgbe[2] as f32 * scale,
+    )
+}
+
+impl HdrImage {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, EnvironmentErrors> {
+        let bytes = fs::read(path).map_err(|err| EnvironmentErrors::Io(err.to_string()))?;
+        Self::from_radiance(&bytes)
+    }
+
+    // Radiance .hdr (RGBE), flat or run length encoded scanlines
+    pub fn from_radiance(bytes: &[u8]) -> Result<Self, EnvironmentErrors> {
+        let invalid = |reason: &str| EnvironmentErrors::InvalidHdr(reason.to_string());
+        let mut position = 0;
+        let mut next_line = || -> Result<String, EnvironmentErrors> {
+            let end = bytes[position..]
+                .iter()
+                .position(|byte| *byte == b'\n')
+                .ok_or_else(|| invalid("truncated header"))?;
+            let line = String::from_utf8_lossy(&bytes[position..position + end]).to_string();
+            position += end + 1;
+            Ok(line)
+        };
+
+        let magic = next_line()?;
+        if !magic.starts_with("#?RADIANCE") && !magic.starts_with("#?RGBE") {
+            return Err(invalid("missing radiance signature"));
+        }
+        loop {
+            let line = next_line()?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix("FORMAT=") {
+                if format != "32-bit_rle_rgbe" {
+                    return Err(invalid("only 32-bit_rle_rgbe is supported"));
+                }
+            }
+        }
+        let resolution = next_line()?;
+        let (height, width) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            ["-Y", height, "+X", width] => (
+                height.parse().map_err(|_| invalid("bad height"))?,
+                width.parse().map_err(|_| invalid("bad width"))?,
+            ),
+            _ => return Err(invalid("only -Y +X images are supported")),
+        };
+
+        let data = &bytes[position..];
+        let mut reader = 0;
+        let mut pixels = Vec::with_capacity(width * height);
+        let mut scanline = vec![[0u8; 4]; width];
+        for _ in 0..height {
+            let header = data
+                .get(reader..reader + 4)
+                .ok_or_else(|| invalid("truncated"))?;
+            let is_rle = (8..32768).contains(&width)
+                && header[0] == 2
+                && header[1] == 2
+                && ((header[2] as usize) << 8 | header[3] as usize) == width;
+
+            if is_rle {
+                reader += 4;
+                for channel in 0..4 {
+                    let mut x = 0;
+                    while x < width {
+                        let count = *data.get(reader).ok_or_else(|| invalid("truncated"))?;
+                        reader += 1;
+                        if count > 128 {
+                            let run = (count - 128) as usize;
+                            let value = *data.get(reader).ok_or_else(|| invalid("truncated"))?;
+                            reader += 1;
+                            if x + run > width {
+                                return Err(invalid("run past the scanline"));
+                            }
+                            scanline[x..x + run]
+                                .iter_mut()
+                                .for_each(|pixel| pixel[channel] = value);
+                            x += run;
+                        } else {
+                            let count = count as usize;
+                            let values = data
+                                .get(reader..reader + count)
+                                .ok_or_else(|| invalid("truncated"))?;
+                            if count == 0 || x + count > width {
+                                return Err(invalid("bad literal run"));
+                            }
+                            for (pixel, value) in scanline[x..x + count].iter_mut().zip(values) {
+                                pixel[channel] = *value;
+                            }
+                            reader += count;
+                            x += count;
+                        }
+                    }
+                }
+            } else {
+                for pixel in scanline.iter_mut() {
+                    let rgbe = data
+                        .get(reader..reader + 4)
+                        .ok_or_else(|| invalid("truncated"))?;
+                    pixel.copy_from_slice(rgbe);
+                    reader += 4;
+                }
+            }
+            pixels.extend(scanline.iter().map(|rgbe| rgbe_to_rgb(*rgbe)));
+        }
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    // Nearest pixel of an equirectangular (latitude/longitude) image
+    pub fn sample_equirect(&self, direction: Vec3) -> Vec3 {
+        let direction = direction.normalize();
+        let u = 0.5 + direction.z.atan2(direction.x) / (2.0 * PI);
+        let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+        let x = ((u * self.width as f32) as usize).min(self.width - 1);
+        let y = ((v * self.height as f32) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+// Faces are +X, -X, +Y, -Y, +Z, -Z like in GL
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cubemap {
+    pub size: usize,
+    pub faces: [Vec<Vec3>; 6],
+}
+
+// (face, u, v) with u and v in -1..1 to a direction
+fn face_direction(face: usize, u: f32, v: f32) -> Vec3 {
+    match face {
+        0 => Vec3::new(1.0, -v, -u),
+        1 => Vec3::new(-1.0, -v, u),
+        2 => Vec3::new(u, 1.0, v),
+        3 => Vec3::new(u, -1.0, -v),
+        4 => Vec3::new(u, -v, 1.0),
+        _ => Vec3::new(-u, -v, -1.0),
+    }
+}
+
+impl Cubemap {
+    pub fn from_faces(size: usize, faces: [Vec<Vec3>; 6]) -> Result<Self, EnvironmentErrors> {
+        if size == 0 || faces.iter().any(|face| face.len() != size * size) {
+            return Err(EnvironmentErrors::InvalidHdr(
+                "cubemap faces have to be size * size".to_string(),
+            ));
+        }
+        Ok(Self { size, faces })
+    }
+
+    // Each face has to be square and all of them the same size
+    pub fn from_face_images(images: [HdrImage; 6]) -> Result<Self, EnvironmentErrors> {
+        let size = images[0].width;
+        if images
+            .iter()
+            .any(|image| image.width != size || image.height != size)
+        {
+            return Err(EnvironmentErrors::InvalidHdr(
+                "cubemap faces have to be square and the same size".to_string(),
+            ));
+        }
+        Self::from_faces(size, images.map(|image| image.pixels))
+    }
+
+    pub fn from_equirect(image: &HdrImage, size: usize) -> Self {
+        let size = size.max(1);
+        let faces = std::array::from_fn(|face| {
+            (0..size * size)
+                .map(|index| {
+                    let (u, v) = Self::texel_uv(size, index);
+                    image.sample_equirect(face_direction(face, u, v))
+                })
+                .collect()
+        });
+        Self { size, faces }
+    }
+
+    fn texel_uv(size: usize, index: usize) -> (f32, f32) {
+        let x = index % size;
+        let y = index / size;
+        (
+            2.0 * (x as f32 + 0.5) / size as f32 - 1.0,
+            2.0 * (y as f32 + 0.5) / size as f32 - 1.0,
+        )
+    }
+
+    pub fn sample(&self, direction: Vec3) -> Vec3 {
+        let (ax, ay, az) = (direction.x.abs(), direction.y.abs(), direction.z.abs());
+        let (face, u, v) = if ax >= ay && ax >= az {
+            if direction.x > 0.0 {
+                (0, -direction.z / ax, -direction.y / ax)
+            } else {
+                (1, direction.z / ax, -direction.y / ax)
+            }
+        } else if ay >= az {
+            if direction.y > 0.0 {
+                (2, direction.x / ay, direction.z / ay)
+            } else {
+                (3, direction.x / ay, -direction.z / ay)
+            }
+        } else if direction.z > 0.0 {
+            (4, direction.x / az, -direction.y / az)
+        } else {
+            (5, -direction.x / az, -direction.y / az)
+        };
+        let texel = |coordinate: f32| {
+            (((coordinate + 1.0) / 2.0 * self.size as f32) as usize).min(self.size - 1)
+        };
+        self.faces[face][texel(v) * self.size + texel(u)]
+    }
+
+    // Half the size, every texel the average of four
+    pub fn downsample(&self) -> Self {
+        if self.size == 1 {
+            return self.clone();
+        }
+        let size = self.size / 2;
+        let faces = std::array::from_fn(|face| {
+            let source = &self.faces[face];
+            (0..size * size)
+                .map(|index| {
+                    let (x, y) = (index % size * 2, index / size * 2);
+                    let sum = source[y * self.size + x]
+                        + source[y * self.size + x + 1]
+                        + source[(y + 1) * self.size + x]
+                        + source[(y + 1) * self.size + x + 1];
+                    sum / 4.0
+                })
+                .collect()
+        });
+        Self { size, faces }
+    }
+}
+
+// Diffuse irradiance as 9 spherical harmonics coefficients, this is the
+// ambient term the material system reads
+#[derive(Debug, Clone, PartialEq)]
+pub struct IrradianceSh {
+    pub coefficients: [Vec3; 9],
+}
+
+fn sh_basis(direction: Vec3) -> [f32; 9] {
+    let Vec3 { x, y, z } = direction;
+    [
+        0.282095,
+        0.488603 * y,
+        0.488603 * z,
+        0.488603 * x,
+        1.092548 * x * y,
+        1.092548 * y * z,
+        0.315392 * (3.0 * z * z - 1.0),
+        1.092548 * x * z,
+        0.546274 * (x * x - y * y),
+    ]
+}
+
+impl IrradianceSh {
+    pub fn from_cubemap(cubemap: &Cubemap) -> Self {
+        let mut coefficients = [Vec3::ZERO; 9];
+        let mut total_weight = 0.0;
+        for (face, texels) in cubemap.faces.iter().enumerate() {
+            for (index, radiance) in texels.iter().enumerate() {
+                let (u, v) = Cubemap::texel_uv(cubemap.size, index);
+                // solid angle of the texel, texels near the face edges cover less
+                let weight = 1.0 / (1.0 + u * u + v * v).powf(1.5);
+                let basis = sh_basis(face_direction(face, u, v).normalize());
+                for (coefficient, basis) in coefficients.iter_mut().zip(basis) {
+                    *coefficient += *radiance * (basis * weight);
+                }
+                total_weight += weight;
+            }
+        }
+        let normalization = 4.0 * PI / total_weight;
+        Self {
+            coefficients: coefficients.map(|coefficient| coefficient * normalization),
+        }
+    }
+
+    // Irradiance divided by pi, multiply with the albedo for the diffuse
+    // ambient light
+    pub fn get_ambient(&self, normal: Vec3) -> Vec3 {
+        // cosine lobe convolution per band
+        const BANDS: [f32; 9] = [
+            PI,
+            2.0 * PI / 3.0,
+            2.0 * PI / 3.0,
+            2.0 * PI / 3.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+            PI / 4.0,
+        ];
+        let basis = sh_basis(normal.normalize());
+        let mut irradiance = Vec3::ZERO;
+        for index in 0..9 {
+            irradiance += self.coefficients[index] * (BANDS[index] * basis[index]);
+        }
+        irradiance / PI
+    }
+}
+
+// A prefiltered environment: the skybox, its irradiance for diffuse ambient
+// light and a mip chain for glossy reflections. The chain is box filtered, an
+// approximation of the GGX prefilter that is cheap enough for hot reloads.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Environment {
+    pub skybox: Cubemap,
+    pub irradiance: IrradianceSh,
+    pub specular: Vec<Cubemap>,
+}
+
+impl Environment {
+    pub fn new(skybox: Cubemap) -> Self {
+        let irradiance = IrradianceSh::from_cubemap(&skybox);
+        let mut specular = vec![skybox.clone()];
+        while specular.last().is_some_and(|level| level.size > 1) {
+            let next = specular.last().map(Cubemap::downsample);
+            specular.extend(next);
+        }
+        Self {
+            skybox,
+            irradiance,
+            specular,
+        }
+    }
+
+    pub fn get_ambient(&self, normal: Vec3) -> Vec3 {
+        self.irradiance.get_ambient(normal)
+    }
+
+    // Rougher surfaces read blurrier levels, roughness goes from 0 to 1
+    pub fn get_reflection(&self, direction: Vec3, roughness: f32) -> Vec3 {
+        let level = (roughness.clamp(0.0, 1.0) * (self.specular.len() - 1) as f32).round();
+        self.specular[level as usize].sample(direction)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EnvironmentSource {
+    Equirect(PathBuf),
+    // +X, -X, +Y, -Y, +Z, -Z
+    Cubemap([PathBuf; 6]),
+}
+
+impl EnvironmentSource {
+    fn paths(&self) -> Vec<&Path> {
+        match self {
+            Self::Equirect(path) => vec![path.as_path()],
+            Self::Cubemap(paths) => paths.iter().map(|path| path.as_path()).collect(),
+        }
+    }
+
+    fn modified(&self) -> Vec<Option<SystemTime>> {
+        self.paths()
+            .into_iter()
+            .map(|path| fs::metadata(path).and_then(|meta| meta.modified()).ok())
+            .collect()
+    }
+
+    pub fn load(&self, cubemap_size: usize) -> Result<Environment, EnvironmentErrors> {
+        let skybox = match self {
+            Self::Equirect(path) => Cubemap::from_equirect(&HdrImage::load(path)?, cubemap_size),
+            Self::Cubemap(paths) => {
+                let mut images = Vec::with_capacity(6);
+                for path in paths {
+                    images.push(HdrImage::load(path)?);
+                }
+                let images: [HdrImage; 6] = images.try_into().expect("six faces were loaded");
+                Cubemap::from_face_images(images)?
+            }
+        };
+        Ok(Environment::new(skybox))
+    }
+}
+
+struct LoadedEnvironment {
+    source: EnvironmentSource,
+    modified: Vec<Option<SystemTime>>,
+    environment: Environment,
+}
+
+// Named environments, one of them active. Scenes pick theirs with
+// `set_scene_environment`, the source files are watched and reloaded when
+// they change.
+pub struct Environments {
+    cubemap_size: usize,
+    environments: HashMap<String, LoadedEnvironment>,
+    scenes: HashMap<String, String>,
+    active: Option<String>,
+    poll_interval: Duration,
+    since_poll: Duration,
+}
+
+impl Environments {
+    pub fn new(cubemap_size: usize) -> Self {
+        Self {
+            cubemap_size,
+            environments: HashMap::new(),
+            scenes: HashMap::new(),
+            active: None,
+            poll_interval: Duration::from_millis(500),
+            since_poll: Duration::ZERO,
+        }
+    }
+
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+
+    pub fn add(&mut self, name: &str, source: EnvironmentSource) -> Result<(), EnvironmentErrors> {
+        let environment = source.load(self.cubemap_size)?;
+        info!("loaded environment {}", name);
+        self.environments.insert(
+            name.to_string(),
+            LoadedEnvironment {
+                modified: source.modified(),
+                source,
+                environment,
+            },
+        );
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Environment> {
+        self.environments
+            .get(name)
+            .map(|loaded| &loaded.environment)
+    }
+
+    pub fn select(&mut self, name: &str) -> Result<(), EnvironmentErrors> {
+        if !self.environments.contains_key(name) {
+            return Err(EnvironmentErrors::UnknownEnvironment(name.to_string()));
+        }
+        self.active = Some(name.to_string());
+        Ok(())
+    }
+
+    pub fn get_active(&self) -> Option<&Environment> {
+        self.get(self.active.as_deref()?)
+    }
+
+    pub fn get_active_name(&self) -> Option<&str> {
+        self.active.as_deref()
+    }
+
+    pub fn set_scene_environment(&mut self, scene: &str, name: &str) {
+        self.scenes.insert(scene.to_string(), name.to_string());
+    }
+
+    // Switches to the environment of the scene, scenes without one keep the
+    // current environment
+    pub fn enter_scene(&mut self, scene: &str) -> Result<(), EnvironmentErrors> {
+        match self.scenes.get(scene).cloned() {
+            Some(name) => self.select(&name),
+            None => Ok(()),
+        }
+    }
+
+    // Reloads the environments whose files changed, returns their names
+    pub fn reload_changed(&mut self) -> Vec<String> {
+        let mut reloaded = Vec::new();
+        for (name, loaded) in self.environments.iter_mut() {
+            let modified = loaded.source.modified();
+            if modified == loaded.modified {
+                continue;
+            }
+            loaded.modified = modified;
+            match loaded.source.load(self.cubemap_size) {
+                Ok(environment) => {
+                    info!("reloaded environment {}", name);
+                    loaded.environment = environment;
+                    reloaded.push(name.clone());
+                }
+                // keep the old one until the file is fixed
+                Err(err) => warn!("unable to reload environment {}: {}", name, err),
+            }
+        }
+        reloaded
+    }
+}
+
+impl Subsystem for Environments {
+    fn get_name(&self) -> &str {
+        "Environments"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        self.since_poll += ctx.time.get_unscaled_delta();
+        if self.since_poll < self.poll_interval {
+            return;
+        }
+        self.since_poll = Duration::ZERO;
+
+        for name in self.reload_changed() {
+            let event = RendererEvents::EnvironmentReloaded(name);
+            if let Err(err) = ctx.event_queue.emit(Box::new(event)) {
+                error!("unable to emit environment reloaded event {:?}", err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn flat_hdr(width: usize, height: usize, rgbe: [u8; 4]) -> Vec<u8> {
+        let mut bytes = format!(
+            "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+            height, width
+        )
+        .into_bytes();
+        for _ in 0..width * height {
+            bytes.extend_from_slice(&rgbe);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_parse_flat_and_rle() {
+        // 128 * 2^(129 - 136) = 1.0
+        let image = HdrImage::from_radiance(&flat_hdr(2, 1, [128, 64, 0, 129])).unwrap();
+        assert_eq!(image.pixels, vec![Vec3::new(1.0, 0.5, 0.0); 2]);
+
+        let mut rle = b"#?RADIANCE\n\n-Y 1 +X 8\n".to_vec();
+        rle.extend_from_slice(&[2, 2, 0, 8]);
+        // red is one run, green literal values, blue and exponent runs
+        rle.extend_from_slice(&[136, 128]);
+        rle.extend_from_slice(&[8, 0, 0, 0, 0, 128, 128, 128, 128]);
+        rle.extend_from_slice(&[136, 0]);
+        rle.extend_from_slice(&[136, 129]);
+        let image = HdrImage::from_radiance(&rle).unwrap();
+        assert_eq!(image.pixels[0], Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(image.pixels[7], Vec3::new(1.0, 1.0, 0.0));
+
+        assert!(HdrImage::from_radiance(b"P6\n").is_err());
+    }
+
+    #[test]
+    fn test_uniform_sky_ambient() {
+        let sky = HdrImage {
+            width: 8,
+            height: 4,
+            pixels: vec![Vec3::splat(1.0); 32],
+        };
+        let environment = Environment::new(Cubemap::from_equirect(&sky, 8));
+
+        for normal in [Vec3::X, -Vec3::Y, Vec3::new(1.0, 1.0, 0.0)] {
+            let ambient = environment.get_ambient(normal);
+            assert!((ambient.x - 1.0).abs() < 0.01, "got {:?}", ambient);
+        }
+        assert_eq!(environment.specular.len(), 4);
+        assert_eq!(environment.get_reflection(Vec3::Z, 1.0), Vec3::splat(1.0));
+    }
+
+    #[test]
+    fn test_cubemap_sampling_matches_faces() {
+        let faces = std::array::from_fn(|face| vec![Vec3::splat(face as f32); 4]);
+        let cubemap = Cubemap::from_faces(2, faces).unwrap();
+        assert_eq!(cubemap.sample(Vec3::X), Vec3::splat(0.0));
+        assert_eq!(cubemap.sample(-Vec3::Y), Vec3::splat(3.0));
+        assert_eq!(cubemap.sample(-Vec3::Z), Vec3::splat(5.0));
+    }
+
+    #[test]
+    fn test_scene_selection_and_reload() {
+        let path = env::temp_dir().join(format!("aloy-env-test-{}.hdr", std::process::id()));
+        fs::write(&path, flat_hdr(4, 2, [128, 128, 128, 129])).unwrap();
+
+        let mut environments = Environments::new(4);
+        environments
+            .add("day", EnvironmentSource::Equirect(path.clone()))
+            .unwrap();
+        environments.set_scene_environment("forest", "day");
+        assert!(environments.get_active().is_none());
+        environments.enter_scene("forest").unwrap();
+        assert_eq!(environments.get_active_name(), Some("day"));
+        assert!(environments.select("night").is_err());
+
+        fs::write(&path, flat_hdr(4, 2, [128, 0, 0, 130])).unwrap();
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(environments.reload_changed(), vec!["day".to_string()]);
+        let _ = fs::remove_file(&path);
+
+        let sky = environments.get_active().unwrap().skybox.sample(Vec3::Y);
+        assert_eq!(sky, Vec3::new(2.0, 0.0, 0.0));
+    }
+}
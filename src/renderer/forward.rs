@@ -0,0 +1,594 @@
+use log::warn;
+
+use crate::math::{
+    transform::{Mat4, Transform},
+    vector::Vec3,
+};
+
+use super::{
+    api::{
+        Binding, BindingKind, BlendMode, Buffer, BufferDescriptor, BufferUsage, Pipeline,
+        PipelineDescriptor, RenderPass, RendererAPI, RendererErrors, Resource, Shader,
+        ShaderSource, TextureFormat,
+    },
+    camera::Camera,
+    forward_plus::{DirectionalLight, LocalLight},
+    model::{get_vertex_layout, MaterialAlpha, Model, ModelMaterial},
+};
+
+// Lights past this are dropped, the closest local lights to the camera win.
+// Has to match the array in the shader.
+pub const MAX_LIGHTS: usize = 16;
+
+const FORWARD_SHADER: &str = "
+struct Scene {
+    view_projection: mat4x4<f32>,
+    camera_position: vec4<f32>,
+    ambient: vec4<f32>,
+};
+
+// position.w is the kind: 0 directional, 1 point, 2 spot
+struct Light {
+    position: vec4<f32>,
+    // w is the range
+    direction: vec4<f32>,
+    // rgb times the intensity
+    color: vec4<f32>,
+    // cosines of the inner and outer angle
+    cone: vec4<f32>,
+};
+
+struct Lights {
+    count: vec4<u32>,
+    lights: array<Light, 16>,
+};
+
+struct Object {
+    model: mat4x4<f32>,
+    normal: mat4x4<f32>,
+    base_color: vec4<f32>,
+    // w is the shininess
+    specular: vec4<f32>,
+    // w is the alpha cutoff, 0 without one
+    emissive: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> scene: Scene;
+@group(0) @binding(1) var<uniform> lights: Lights;
+@group(0) @binding(2) var<uniform> object: Object;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+};
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let world = object.model * vec4<f32>(position, 1.0);
+    out.position = scene.view_projection * world;
+    out.world_position = world.xyz;
+    out.normal = (object.normal * vec4<f32>(normal, 0.0)).xyz;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let base = object.base_color;
+    if base.a < object.emissive.w {
+        discard;
+    }
+    let normal = normalize(in.normal);
+    let view = normalize(scene.camera_position.xyz - in.world_position);
+    var color = scene.ambient.rgb * base.rgb + object.emissive.rgb;
+
+    for (var i = 0u; i < lights.count.x; i++) {
+        let light = lights.lights[i];
+        var to_light = -light.direction.xyz;
+        var attenuation = 1.0;
+        if light.position.w > 0.5 {
+            let offset = light.position.xyz - in.world_position;
+            let distance = length(offset);
+            to_light = offset / max(distance, 0.0001);
+            let falloff = clamp(1.0 - pow(distance / light.direction.w, 4.0), 0.0, 1.0);
+            attenuation = falloff * falloff / (distance * distance + 1.0);
+        }
+        if light.position.w > 1.5 {
+            let angle = dot(-to_light, light.direction.xyz);
+            attenuation *= smoothstep(light.cone.y, light.cone.x, angle);
+        }
+
+        let diffuse = max(dot(normal, to_light), 0.0);
+        let half_vector = normalize(to_light + view);
+        var specular = 0.0;
+        if diffuse > 0.0 {
+            specular = pow(max(dot(normal, half_vector), 0.0), object.specular.w);
+        }
+        color += (base.rgb * diffuse + object.specular.rgb * specular) * light.color.rgb * attenuation;
+    }
+    return vec4<f32>(color, base.a);
+}
+";
+
+// Floats of the structs above
+const SCENE_FLOATS: usize = 16 + 4 + 4;
+const LIGHT_FLOATS: usize = 4 * 4;
+const OBJECT_FLOATS: usize = 16 + 16 + 4 + 4 + 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ForwardStats {
+    pub draw_calls: u64,
+    // lights that made it into the light buffer
+    pub lights: u64,
+}
+
+#[derive(Debug)]
+struct QueuedDraw {
+    object: Vec<f32>,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    index_count: u32,
+    blended: bool,
+    // squared, to sort blended draws back to front
+    distance: f32,
+}
+
+fn to_floats(matrix: &Mat4) -> impl Iterator<Item = f32> {
+    matrix.to_cols_array().into_iter().flatten()
+}
+
+fn to_bytes(floats: &[f32]) -> Vec<u8> {
+    floats
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+// Transforms normals by the model matrix, scaled unevenly or not. The
+// cofactors are the inverse transpose up to a scale, which the shader
+// normalizes away.
+fn normal_matrix(model: &Mat4) -> Mat4 {
+    let [x, y, z, _] = model.to_cols_array().map(|[x, y, z, _]| Vec3::new(x, y, z));
+    let sign = if x.dot(y.cross(z)) < 0.0 { -1.0 } else { 1.0 };
+    let [x, y, z] = [y.cross(z), z.cross(x), x.cross(y)].map(|column| column * sign);
+    Mat4::from_cols_array([
+        [x.x, x.y, x.z, 0.0],
+        [y.x, y.y, y.z, 0.0],
+        [z.x, z.y, z.z, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ])
+}
+
+// Blinn-Phong stand ins for the metallic roughness parameters until the
+// renderer does PBR: metals tint their highlights and rough surfaces get
+// wide, dim ones
+fn material_floats(material: &ModelMaterial) -> [f32; 12] {
+    let base = material.base_color;
+    let metallic = material.metallic.clamp(0.0, 1.0);
+    let roughness = material.roughness.clamp(0.0, 1.0);
+    let diffuse = base.truncate() * (1.0 - metallic);
+    let specular = Vec3::splat(0.04).lerp(base.truncate(), metallic) * (1.0 - roughness * 0.9);
+    let shininess = (2.0 / roughness.powi(4).max(1e-4) - 2.0).clamp(1.0, 2048.0);
+    let cutoff = match material.alpha {
+        MaterialAlpha::Mask(cutoff) => cutoff as f32 / 255.0,
+        _ => 0.0,
+    };
+    let emissive = material.emissive;
+    [
+        diffuse.x, diffuse.y, diffuse.z, base.w, specular.x, specular.y, specular.z, shininess,
+        emissive.x, emissive.y, emissive.z, cutoff,
+    ]
+}
+
+// Draws lit models in a single forward pass with Blinn-Phong shading. The
+// lights live in a uniform buffer the renderer owns and rewrites at the end
+// of every scene, so adding or moving a light is only a CPU side change.
+// Positions and directions are in world space.
+//
+//     renderer.add_directional_light(sun);
+//     renderer.begin_scene(&camera, camera.get_position());
+//     renderer.draw_model(&model, &transform);
+//     let mut pass = RenderPass::new("world", RenderTarget::Surface);
+//     pass.set_depth(depth);
+//     renderer.end_scene(api, &mut pass)?;
+//
+// The pass needs a Depth32Float depth texture.
+#[derive(Debug)]
+pub struct ForwardRenderer {
+    shader: Shader,
+    opaque_pipeline: Pipeline,
+    blend_pipeline: Pipeline,
+    scene_buffer: Buffer,
+    light_buffer: Buffer,
+    // one per draw, kept between frames
+    object_buffers: Vec<Buffer>,
+    ambient: Vec3,
+    directional_lights: Vec<DirectionalLight>,
+    lights: Vec<LocalLight>,
+    view_projection: Mat4,
+    camera_position: Vec3,
+    draws: Vec<QueuedDraw>,
+    stats: ForwardStats,
+}
+
+impl ForwardRenderer {
+    pub fn new(api: &mut dyn RendererAPI) -> Result<Self, RendererErrors> {
+        let shader = api.create_shader(&ShaderSource::wgsl("forward", FORWARD_SHADER))?;
+        let mut pipeline = PipelineDescriptor::new("forward", shader, api.get_surface_format());
+        pipeline.vertex_layouts = vec![get_vertex_layout()];
+        pipeline.bindings = vec![BindingKind::UniformBuffer; 3];
+        pipeline.depth_format = Some(TextureFormat::Depth32Float);
+        pipeline.blend = BlendMode::Opaque;
+        let opaque_pipeline = api.create_pipeline(&pipeline)?;
+        pipeline.label = "forward blended".to_string();
+        pipeline.blend = BlendMode::Alpha;
+        let blend_pipeline = api.create_pipeline(&pipeline)?;
+
+        let scene_buffer = api.create_buffer(
+            &BufferDescriptor {
+                label: "forward scene".to_string(),
+                usage: BufferUsage::Uniform,
+                size: (SCENE_FLOATS * 4) as u64,
+            },
+            None,
+        )?;
+        let light_buffer = api.create_buffer(
+            &BufferDescriptor {
+                label: "forward lights".to_string(),
+                usage: BufferUsage::Uniform,
+                size: (16 + MAX_LIGHTS * LIGHT_FLOATS * 4) as u64,
+            },
+            None,
+        )?;
+
+        Ok(Self {
+            shader,
+            opaque_pipeline,
+            blend_pipeline,
+            scene_buffer,
+            light_buffer,
+            object_buffers: Vec::new(),
+            ambient: Vec3::splat(0.03),
+            directional_lights: Vec::new(),
+            lights: Vec::new(),
+            view_projection: Mat4::IDENTITY,
+            camera_position: Vec3::ZERO,
+            draws: Vec::new(),
+            stats: ForwardStats::default(),
+        })
+    }
+
+    // Light reaching every surface, in linear rgb
+    pub fn set_ambient(&mut self, ambient: Vec3) {
+        self.ambient = ambient;
+    }
+
+    pub fn get_ambient(&self) -> Vec3 {
+        self.ambient
+    }
+
+    pub fn add_directional_light(&mut self, light: DirectionalLight) -> usize {
+        self.directional_lights.push(light);
+        self.directional_lights.len() - 1
+    }
+
+    pub fn get_directional_lights_mut(&mut self) -> &mut Vec<DirectionalLight> {
+        &mut self.directional_lights
+    }
+
+    pub fn add_light(&mut self, light: LocalLight) -> usize {
+        self.lights.push(light);
+        self.lights.len() - 1
+    }
+
+    pub fn get_lights_mut(&mut self) -> &mut Vec<LocalLight> {
+        &mut self.lights
+    }
+
+    pub fn clear_lights(&mut self) {
+        self.directional_lights.clear();
+        self.lights.clear();
+    }
+
+    pub fn begin_scene(&mut self, camera: &dyn Camera, camera_position: Vec3) {
+        self.view_projection = camera.get_view_projection();
+        self.camera_position = camera_position;
+        self.draws.clear();
+        self.stats = ForwardStats::default();
+    }
+
+    // Every primitive of the model with its material's color, `transform`
+    // places the whole model in the world
+    pub fn draw_model(&mut self, model: &Model, transform: &Transform) {
+        let placement = transform.to_matrix();
+        let default_material = ModelMaterial::default();
+        for draw in model.get_draws() {
+            let world = placement * draw.world;
+            let material = draw
+                .primitive
+                .material
+                .and_then(|material| model.get_materials().get(material))
+                .unwrap_or(&default_material);
+            let mut object: Vec<f32> = to_floats(&world).collect();
+            object.extend(to_floats(&normal_matrix(&world)));
+            object.extend(material_floats(material));
+            self.draws.push(QueuedDraw {
+                object,
+                vertex_buffer: draw.primitive.vertex_buffer,
+                index_buffer: draw.primitive.index_buffer,
+                index_count: draw.primitive.index_count,
+                blended: material.alpha == MaterialAlpha::Blend,
+                distance: world
+                    .transform_point(Vec3::ZERO)
+                    .distance(self.camera_position)
+                    .powi(2),
+            });
+        }
+    }
+
+    // The directional lights first, then the local lights closest to the
+    // camera, packed like `Lights` in the shader
+    fn pack_lights(&self) -> (Vec<u8>, usize) {
+        let mut lights = self.lights.iter().collect::<Vec<_>>();
+        lights.sort_by(|a, b| {
+            let a = a.get_position().distance(self.camera_position);
+            let b = b.get_position().distance(self.camera_position);
+            a.total_cmp(&b)
+        });
+
+        let mut floats = Vec::with_capacity(MAX_LIGHTS * LIGHT_FLOATS);
+        for light in self.directional_lights.iter() {
+            let direction = light.direction.normalize();
+            let color = light.color * light.intensity;
+            floats.extend_from_slice(&[0.0; 4]);
+            floats.extend_from_slice(&[direction.x, direction.y, direction.z, 0.0]);
+            floats.extend_from_slice(&[color.x, color.y, color.z, 0.0]);
+            floats.extend_from_slice(&[0.0; 4]);
+        }
+        for light in lights {
+            let (kind, position, direction, color, intensity, range, cone) = match light {
+                LocalLight::Point {
+                    position,
+                    color,
+                    intensity,
+                    range,
+                    ..
+                } => (
+                    1.0,
+                    position,
+                    Vec3::ZERO,
+                    color,
+                    intensity,
+                    range,
+                    [1.0, 1.0],
+                ),
+                LocalLight::Spot {
+                    position,
+                    direction,
+                    color,
+                    intensity,
+                    range,
+                    inner_angle,
+                    outer_angle,
+                    ..
+                } => (
+                    2.0,
+                    position,
+                    direction.normalize(),
+                    color,
+                    intensity,
+                    range,
+                    [inner_angle.cos(), outer_angle.cos()],
+                ),
+            };
+            let color = *color * *intensity;
+            floats.extend_from_slice(&[position.x, position.y, position.z, kind]);
+            floats.extend_from_slice(&[direction.x, direction.y, direction.z, *range]);
+            floats.extend_from_slice(&[color.x, color.y, color.z, 0.0]);
+            floats.extend_from_slice(&[cone[0], cone[1], 0.0, 0.0]);
+        }
+
+        let count = floats.len() / LIGHT_FLOATS;
+        if count > MAX_LIGHTS {
+            warn!(
+                "{} lights in the scene, only {} are shaded",
+                count, MAX_LIGHTS
+            );
+        }
+        let count = count.min(MAX_LIGHTS);
+        floats.truncate(count * LIGHT_FLOATS);
+
+        let mut bytes = Vec::with_capacity(16 + floats.len() * 4);
+        bytes.extend_from_slice(&(count as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 12]);
+        bytes.extend(to_bytes(&floats));
+        (bytes, count)
+    }
+
+    // Uploads the scene and the lights and records the draws into `pass`,
+    // opaque ones first and blended ones back to front after them
+    pub fn end_scene(
+        &mut self,
+        api: &mut dyn RendererAPI,
+        pass: &mut RenderPass,
+    ) -> Result<(), RendererErrors> {
+        let mut scene: Vec<f32> = to_floats(&self.view_projection).collect();
+        let (camera, ambient) = (self.camera_position, self.ambient);
+        scene.extend([camera.x, camera.y, camera.z, 1.0]);
+        scene.extend([ambient.x, ambient.y, ambient.z, 0.0]);
+        api.write_buffer(self.scene_buffer, 0, &to_bytes(&scene))?;
+        let (lights, light_count) = self.pack_lights();
+        api.write_buffer(self.light_buffer, 0, &lights)?;
+        self.stats.lights = light_count as u64;
+
+        while self.object_buffers.len() < self.draws.len() {
+            let buffer = api.create_buffer(
+                &BufferDescriptor {
+                    label: "forward object".to_string(),
+                    usage: BufferUsage::Uniform,
+                    size: (OBJECT_FLOATS * 4) as u64,
+                },
+                None,
+            )?;
+            self.object_buffers.push(buffer);
+        }
+
+        self.draws.sort_by(|a, b| match (a.blended, b.blended) {
+            (false, false) => std::cmp::Ordering::Equal,
+            (true, true) => b.distance.total_cmp(&a.distance),
+            _ => a.blended.cmp(&b.blended),
+        });
+        let mut current = None;
+        for (draw, buffer) in self.draws.iter().zip(self.object_buffers.iter()) {
+            api.write_buffer(*buffer, 0, &to_bytes(&draw.object))?;
+            let pipeline = match draw.blended {
+                true => self.blend_pipeline,
+                false => self.opaque_pipeline,
+            };
+            if current != Some(pipeline) {
+                pass.set_pipeline(pipeline);
+                current = Some(pipeline);
+            }
+            pass.set_bindings(vec![
+                Binding::Buffer(self.scene_buffer),
+                Binding::Buffer(self.light_buffer),
+                Binding::Buffer(*buffer),
+            ]);
+            pass.set_vertex_buffer(0, draw.vertex_buffer);
+            pass.set_index_buffer(draw.index_buffer);
+            pass.draw_indexed(0..draw.index_count, 0, 0..1);
+            self.stats.draw_calls += 1;
+        }
+        Ok(())
+    }
+
+    // Statistics of the current scene, complete after `end_scene`
+    pub fn get_stats(&self) -> ForwardStats {
+        self.stats
+    }
+
+    // Frees the GPU resources, the renderer is unusable afterwards
+    pub fn destroy(&mut self, api: &mut dyn RendererAPI) {
+        for buffer in self.object_buffers.drain(..) {
+            api.destroy(Resource::Buffer(buffer));
+        }
+        api.destroy(Resource::Buffer(self.scene_buffer));
+        api.destroy(Resource::Buffer(self.light_buffer));
+        api.destroy(Resource::Pipeline(self.opaque_pipeline));
+        api.destroy(Resource::Pipeline(self.blend_pipeline));
+        api.destroy(Resource::Shader(self.shader));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::{
+        api::{headless::HeadlessRenderer, RenderTarget},
+        model::{MeshData, ModelData, ModelNode, PrimitiveData},
+    };
+
+    fn read_floats(bytes: &[u8]) -> Vec<f32> {
+        bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_lights_and_draws_reach_the_gpu() {
+        let mut api = HeadlessRenderer::new();
+        let mut renderer = ForwardRenderer::new(&mut api).unwrap();
+        let data = ModelData {
+            meshes: vec![MeshData {
+                name: "triangle".to_string(),
+                primitives: vec![PrimitiveData {
+                    positions: vec![Vec3::ZERO, Vec3::X, Vec3::Y],
+                    normals: vec![Vec3::Z; 3],
+                    uvs: Vec::new(),
+                    indices: vec![0, 1, 2],
+                    material: None,
+                }],
+            }],
+            nodes: vec![ModelNode {
+                name: "triangle".to_string(),
+                parent: None,
+                children: Vec::new(),
+                transform: Transform::IDENTITY,
+                mesh: Some(0),
+            }],
+            roots: vec![0],
+            ..Default::default()
+        };
+        let model = Model::upload("triangle", &data, &mut api).unwrap();
+
+        renderer.add_directional_light(DirectionalLight {
+            direction: Vec3::new(0.0, -2.0, 0.0),
+            color: Vec3::splat(1.0),
+            intensity: 2.0,
+            casts_shadows: false,
+        });
+        // the farther one is dropped once the buffer is full
+        for z in [50.0, 1.0] {
+            for _ in 0..MAX_LIGHTS / 2 {
+                renderer.add_light(LocalLight::Point {
+                    position: Vec3::new(0.0, 0.0, z),
+                    color: Vec3::new(1.0, 0.5, 0.0),
+                    intensity: 1.0,
+                    range: 10.0,
+                    casts_shadows: false,
+                });
+            }
+        }
+
+        renderer.begin_scene(&Mat4::IDENTITY, Vec3::ZERO);
+        let scale = Transform {
+            scale: Vec3::new(2.0, 1.0, 1.0),
+            ..Transform::IDENTITY
+        };
+        renderer.draw_model(&model, &scale);
+        let mut pass = RenderPass::new("world", RenderTarget::Surface);
+        renderer.end_scene(&mut api, &mut pass).unwrap();
+        assert_eq!(
+            renderer.get_stats(),
+            ForwardStats {
+                draw_calls: 1,
+                lights: MAX_LIGHTS as u64
+            }
+        );
+        assert_eq!(pass.get_draw_count(), 1);
+
+        let lights = api.get_buffer_contents(renderer.light_buffer).unwrap();
+        assert_eq!(u32::from_le_bytes(lights[0..4].try_into().unwrap()), 16);
+        let lights = read_floats(&lights[16..]);
+        // the sun, normalized and scaled by its intensity
+        assert_eq!(&lights[4..11], &[0.0, -1.0, 0.0, 0.0, 2.0, 2.0, 2.0]);
+        // then the closest point lights
+        assert_eq!(
+            &lights[LIGHT_FLOATS..LIGHT_FLOATS + 4],
+            &[0.0, 0.0, 1.0, 1.0]
+        );
+        assert_eq!(lights[(MAX_LIGHTS - 1) * LIGHT_FLOATS + 2], 50.0);
+
+        // normals of the stretched model are squashed along x
+        let object = read_floats(api.get_buffer_contents(renderer.object_buffers[0]).unwrap());
+        let normal = Mat4::from_cols_array([
+            object[16..20].try_into().unwrap(),
+            object[20..24].try_into().unwrap(),
+            object[24..28].try_into().unwrap(),
+            object[28..32].try_into().unwrap(),
+        ]);
+        let slanted = normal.transform_vector(Vec3::new(1.0, 1.0, 0.0));
+        assert!(slanted.x < slanted.y);
+
+        api.submit(&[pass]).unwrap();
+        renderer.destroy(&mut api);
+        model.destroy(&mut api);
+        assert_eq!(api.get_resource_count(), 0);
+    }
+}
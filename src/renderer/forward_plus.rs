@@ -0,0 +1,422 @@
+use log::warn;
+
+use crate::{core::settings::Settings, math::vector::Vec3};
+
+pub const LIGHTING_QUALITY_SETTING: &str = "render.lighting_quality";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LightingQuality {
+    Low,
+    #[default]
+    Medium,
+    High,
+    Ultra,
+}
+
+impl LightingQuality {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "low" => Some(Self::Low),
+            "medium" => Some(Self::Medium),
+            "high" => Some(Self::High),
+            "ultra" => Some(Self::Ultra),
+            _ => None,
+        }
+    }
+
+    pub fn get_config(&self) -> ForwardPlusConfig {
+        let (clusters, max_lights_per_cluster) = match self {
+            Self::Low => ([8, 4, 12], 32),
+            Self::Medium => ([16, 9, 24], 64),
+            Self::High => ([16, 9, 24], 128),
+            Self::Ultra => ([32, 18, 32], 256),
+        };
+        let (shadow_map_size, cascades, shadow_distance, shadowed_lights) = match self {
+            Self::Low => (1024, 1, 50.0, 0),
+            Self::Medium => (2048, 2, 100.0, 2),
+            Self::High => (2048, 3, 150.0, 4),
+            Self::Ultra => (4096, 4, 250.0, 8),
+        };
+        ForwardPlusConfig {
+            clusters,
+            max_lights_per_cluster,
+            shadow_map_size,
+            cascades,
+            shadow_distance,
+            cascade_lambda: 0.75,
+            shadowed_lights,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForwardPlusConfig {
+    // tiles along x and y, depth slices along z
+    pub clusters: [u32; 3],
+    // lights past this are dropped from the cluster
+    pub max_lights_per_cluster: usize,
+    pub shadow_map_size: u32,
+    // sun shadow cascades
+    pub cascades: u32,
+    pub shadow_distance: f32,
+    // 0 splits the cascades linearly, 1 logarithmically
+    pub cascade_lambda: f32,
+    // point and spot lights that get a shadow map, closest to the camera first
+    pub shadowed_lights: usize,
+}
+
+impl Default for ForwardPlusConfig {
+    fn default() -> Self {
+        LightingQuality::default().get_config()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectionalLight {
+    pub direction: Vec3,
+    pub color: Vec3,
+    pub intensity: f32,
+    pub casts_shadows: bool,
+}
+
+// Positions and directions are in view space: camera at the origin looking
+// down +z with y up. The renderer transforms the lights before clustering.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LocalLight {
+    Point {
+        position: Vec3,
+        color: Vec3,
+        intensity: f32,
+        range: f32,
+        casts_shadows: bool,
+    },
+    Spot {
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        range: f32,
+        // half angles in radians
+        inner_angle: f32,
+        outer_angle: f32,
+        casts_shadows: bool,
+    },
+}
+
+impl LocalLight {
+    pub fn get_position(&self) -> Vec3 {
+        match self {
+            Self::Point { position, .. } | Self::Spot { position, .. } => *position,
+        }
+    }
+
+    pub fn get_range(&self) -> f32 {
+        match self {
+            Self::Point { range, .. } | Self::Spot { range, .. } => *range,
+        }
+    }
+
+    pub fn casts_shadows(&self) -> bool {
+        match self {
+            Self::Point { casts_shadows, .. } | Self::Spot { casts_shadows, .. } => *casts_shadows,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClusterView {
+    // vertical field of view in radians
+    pub fov_y: f32,
+    pub aspect: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+// Result of the light assignment, uploaded as-is: every cluster owns the
+// range `offsets[i]..offsets[i + 1]` of `light_indices`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LightClusters {
+    pub dimensions: [u32; 3],
+    pub offsets: Vec<u32>,
+    pub light_indices: Vec<u32>,
+    // far distance of every sun cascade
+    pub cascade_splits: Vec<f32>,
+    // indices of the local lights that got a shadow map
+    pub shadowed_lights: Vec<u32>,
+}
+
+impl LightClusters {
+    pub fn get_cluster_index(&self, x: u32, y: u32, z: u32) -> usize {
+        let [width, height, _] = self.dimensions;
+        (x + y * width + z * width * height) as usize
+    }
+
+    pub fn get_lights(&self, x: u32, y: u32, z: u32) -> &[u32] {
+        let cluster = self.get_cluster_index(x, y, z);
+        let start = self.offsets[cluster] as usize;
+        let end = self.offsets[cluster + 1] as usize;
+        &self.light_indices[start..end]
+    }
+}
+
+// Splits the view frustum into a grid of clusters and lists the lights that
+// touch each one, so shading only loops over the lights nearby
+#[derive(Debug, Clone, Default)]
+pub struct ForwardPlus {
+    config: ForwardPlusConfig,
+    sun: Option<DirectionalLight>,
+    lights: Vec<LocalLight>,
+}
+
+impl ForwardPlus {
+    pub fn new(config: ForwardPlusConfig) -> Self {
+        Self {
+            config,
+            ..Default::default()
+        }
+    }
+
+    pub fn get_config(&self) -> &ForwardPlusConfig {
+        &self.config
+    }
+
+    pub fn set_quality(&mut self, quality: LightingQuality) {
+        self.config = quality.get_config();
+    }
+
+    // Reads `render.lighting_quality` (low, medium, high, ultra)
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        let Some(name) = settings.get_text(LIGHTING_QUALITY_SETTING) else {
+            return;
+        };
+        match LightingQuality::from_name(name) {
+            Some(quality) => self.set_quality(quality),
+            None => warn!("unknown lighting quality {}", name),
+        }
+    }
+
+    pub fn set_sun(&mut self, sun: Option<DirectionalLight>) {
+        self.sun = sun;
+    }
+
+    pub fn get_sun(&self) -> Option<&DirectionalLight> {
+        self.sun.as_ref()
+    }
+
+    pub fn add_light(&mut self, light: LocalLight) -> usize {
+        self.lights.push(light);
+        self.lights.len() - 1
+    }
+
+    pub fn get_lights(&self) -> &[LocalLight] {
+        &self.lights
+    }
+
+    pub fn clear_lights(&mut self) {
+        self.lights.clear();
+    }
+
+    // Near distance of the given depth slice, slices grow exponentially so
+    // they stay roughly cube shaped
+    fn slice_depth(&self, view: &ClusterView, slice: u32) -> f32 {
+        let slices = self.config.clusters[2].max(1) as f32;
+        view.near * (view.far / view.near).powf(slice as f32 / slices)
+    }
+
+    fn depth_to_slice(&self, view: &ClusterView, depth: f32) -> u32 {
+        let slices = self.config.clusters[2].max(1);
+        if depth <= view.near {
+            return 0;
+        }
+        let slice = ((depth / view.near).ln() / (view.far / view.near).ln() * slices as f32) as u32;
+        slice.min(slices - 1)
+    }
+
+    // Splits of the sun cascades, mixing logarithmic and linear splits
+    pub fn get_cascade_splits(&self, view: &ClusterView) -> Vec<f32> {
+        let cascades = self.config.cascades.max(1);
+        let far = view.far.min(self.config.shadow_distance);
+        let lambda = self.config.cascade_lambda.clamp(0.0, 1.0);
+        (1..=cascades)
+            .map(|cascade| {
+                let t = cascade as f32 / cascades as f32;
+                let logarithmic = view.near * (far / view.near).powf(t);
+                let linear = view.near + (far - view.near) * t;
+                lambda * logarithmic + (1.0 - lambda) * linear
+            })
+            .collect()
+    }
+
+    pub fn build(&self, view: &ClusterView) -> LightClusters {
+        let [width, height, slices] = self.config.clusters.map(|count| count.max(1));
+        let cluster_count = (width * height * slices) as usize;
+        let mut per_cluster: Vec<Vec<u32>> = vec![Vec::new(); cluster_count];
+        let tan_y = (view.fov_y / 2.0).tan();
+        let tan_x = tan_y * view.aspect;
+        let mut overflowed = false;
+
+        for (index, light) in self.lights.iter().enumerate() {
+            let position = light.get_position();
+            let range = light.get_range();
+            if position.z + range < view.near || position.z - range > view.far {
+                continue;
+            }
+            let first_slice = self.depth_to_slice(view, position.z - range);
+            let last_slice = self.depth_to_slice(view, position.z + range);
+
+            for z in first_slice..=last_slice {
+                let near = self.slice_depth(view, z);
+                let far = self.slice_depth(view, z + 1);
+                for y in 0..height {
+                    let (bottom, top) = tile_bounds(y, height, tan_y, near, far);
+                    for x in 0..width {
+                        let (left, right) = tile_bounds(x, width, tan_x, near, far);
+                        let min = Vec3::new(left, bottom, near);
+                        let max = Vec3::new(right, top, far);
+                        if !sphere_touches_box(position, range, min, max) {
+                            continue;
+                        }
+                        let cluster =
+                            &mut per_cluster[(x + y * width + z * width * height) as usize];
+                        if cluster.len() < self.config.max_lights_per_cluster {
+                            cluster.push(index as u32);
+                        } else {
+                            overflowed = true;
+                        }
+                    }
+                }
+            }
+        }
+        if overflowed {
+            warn!(
+                "more than {} lights in a cluster, some were dropped",
+                self.config.max_lights_per_cluster
+            );
+        }
+
+        let mut offsets = Vec::with_capacity(cluster_count + 1);
+        let mut light_indices = Vec::new();
+        offsets.push(0);
+        for cluster in per_cluster {
+            light_indices.extend(cluster);
+            offsets.push(light_indices.len() as u32);
+        }
+
+        let mut shadow_candidates: Vec<u32> = (0..self.lights.len() as u32)
+            .filter(|index| self.lights[*index as usize].casts_shadows())
+            .collect();
+        shadow_candidates.sort_by(|a, b| {
+            let a = self.lights[*a as usize].get_position().length_squared();
+            let b = self.lights[*b as usize].get_position().length_squared();
+            a.total_cmp(&b)
+        });
+        shadow_candidates.truncate(self.config.shadowed_lights);
+
+        let cascade_splits = match self.sun.as_ref() {
+            Some(sun) if sun.casts_shadows => self.get_cascade_splits(view),
+            _ => Vec::new(),
+        };
+
+        LightClusters {
+            dimensions: [width, height, slices],
+            offsets,
+            light_indices,
+            cascade_splits,
+            shadowed_lights: shadow_candidates,
+        }
+    }
+}
+
+// View space extent of a tile over the depth range of a slice
+fn tile_bounds(tile: u32, tiles: u32, tan: f32, near: f32, far: f32) -> (f32, f32) {
+    let start = -1.0 + 2.0 * tile as f32 / tiles as f32;
+    let end = -1.0 + 2.0 * (tile + 1) as f32 / tiles as f32;
+    let corners = [start * near, start * far, end * near, end * far].map(|corner| corner * tan);
+    let min = corners.iter().copied().fold(f32::INFINITY, f32::min);
+    let max = corners.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+    (min, max)
+}
+
+fn sphere_touches_box(center: Vec3, radius: f32, min: Vec3, max: Vec3) -> bool {
+    let closest = center.max(min).min(max);
+    closest.distance(center) <= radius
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use crate::core::settings::SettingValue;
+
+    use super::*;
+
+    fn view() -> ClusterView {
+        ClusterView {
+            fov_y: FRAC_PI_2,
+            aspect: 1.0,
+            near: 0.1,
+            far: 100.0,
+        }
+    }
+
+    fn point(position: Vec3, range: f32) -> LocalLight {
+        LocalLight::Point {
+            position,
+            color: Vec3::splat(1.0),
+            intensity: 1.0,
+            range,
+            casts_shadows: true,
+        }
+    }
+
+    #[test]
+    fn test_lights_land_in_their_clusters() {
+        let mut forward = ForwardPlus::new(ForwardPlusConfig {
+            clusters: [2, 2, 4],
+            ..Default::default()
+        });
+        // upper right, close to the camera
+        forward.add_light(point(Vec3::new(1.0, 1.0, 2.0), 0.5));
+        // behind the camera
+        forward.add_light(point(Vec3::new(0.0, 0.0, -5.0), 1.0));
+        let clusters = forward.build(&view());
+
+        let slice = forward.depth_to_slice(&view(), 2.0);
+        assert_eq!(clusters.get_lights(1, 1, slice), &[0]);
+        assert!(clusters.get_lights(0, 0, slice).is_empty());
+        assert!(!clusters.light_indices.contains(&1));
+        assert_eq!(clusters.offsets.len(), 2 * 2 * 4 + 1);
+    }
+
+    #[test]
+    fn test_quality_tiers_and_shadows() {
+        let mut settings = Settings::new();
+        settings.set(
+            LIGHTING_QUALITY_SETTING,
+            SettingValue::Text("low".to_string()),
+        );
+        let mut forward = ForwardPlus::default();
+        forward.apply_settings(&settings);
+        assert_eq!(forward.get_config(), &LightingQuality::Low.get_config());
+
+        forward.set_quality(LightingQuality::High);
+        forward.set_sun(Some(DirectionalLight {
+            direction: Vec3::new(0.0, -1.0, 1.0),
+            color: Vec3::splat(1.0),
+            intensity: 3.0,
+            casts_shadows: true,
+        }));
+        for z in [40.0, 5.0, 20.0, 10.0, 30.0] {
+            forward.add_light(point(Vec3::new(0.0, 0.0, z), 1.0));
+        }
+        let clusters = forward.build(&view());
+
+        assert_eq!(clusters.shadowed_lights, vec![1, 3, 2, 4]);
+        assert_eq!(clusters.cascade_splits.len(), 3);
+        assert!(clusters
+            .cascade_splits
+            .windows(2)
+            .all(|pair| pair[0] < pair[1]));
+        assert!((clusters.cascade_splits[2] - 100.0).abs() < 1e-3);
+    }
+}
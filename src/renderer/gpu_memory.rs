@@ -0,0 +1,386 @@
+use std::collections::{HashMap, HashSet};
+
+use log::{error, warn};
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::{
+        engine_events::renderer_events::{MemoryBudgetExceeded, RendererEvents},
+        event_queue::EventQueue,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GpuMemoryCategory {
+    Texture,
+    Buffer,
+    RenderTarget,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AllocationId(u64);
+
+// Book keeping of everything the renderer uploaded to the gpu. The backend
+// reports allocations here, the tracker itself never touches the gpu.
+#[derive(Debug, Default)]
+pub struct GpuMemoryTracker {
+    next_id: u64,
+    allocations: HashMap<AllocationId, (GpuMemoryCategory, u64)>,
+    usage: HashMap<GpuMemoryCategory, u64>,
+    budgets: HashMap<GpuMemoryCategory, u64>,
+    // categories we already warned about, so we only warn once per overrun
+    over_budget: HashSet<GpuMemoryCategory>,
+}
+
+impl GpuMemoryTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allocate(&mut self, category: GpuMemoryCategory, bytes: u64) -> AllocationId {
+        let id = AllocationId(self.next_id);
+        self.next_id += 1;
+        self.allocations.insert(id, (category, bytes));
+        *self.usage.entry(category).or_default() += bytes;
+        id
+    }
+
+    pub fn resize(&mut self, id: AllocationId, bytes: u64) -> bool {
+        let Some((category, size)) = self.allocations.get_mut(&id) else {
+            return false;
+        };
+        let usage = self.usage.entry(*category).or_default();
+        *usage = *usage - *size + bytes;
+        *size = bytes;
+        true
+    }
+
+    pub fn free(&mut self, id: AllocationId) -> Option<u64> {
+        let (category, bytes) = self.allocations.remove(&id)?;
+        if let Some(usage) = self.usage.get_mut(&category) {
+            *usage -= bytes;
+        }
+        Some(bytes)
+    }
+
+    pub fn get_usage(&self, category: GpuMemoryCategory) -> u64 {
+        self.usage.get(&category).copied().unwrap_or(0)
+    }
+
+    pub fn get_total_usage(&self) -> u64 {
+        self.usage.values().sum()
+    }
+
+    pub fn set_budget(&mut self, category: GpuMemoryCategory, bytes: u64) {
+        self.budgets.insert(category, bytes);
+    }
+
+    pub fn remove_budget(&mut self, category: GpuMemoryCategory) {
+        self.budgets.remove(&category);
+        self.over_budget.remove(&category);
+    }
+
+    pub fn get_budget(&self, category: GpuMemoryCategory) -> Option<u64> {
+        self.budgets.get(&category).copied()
+    }
+
+    // Bytes left before the budget is hit, None if the category has no budget
+    pub fn get_headroom(&self, category: GpuMemoryCategory) -> Option<u64> {
+        self.get_budget(category)
+            .map(|budget| budget.saturating_sub(self.get_usage(category)))
+    }
+
+    // Returns the categories that went over their budget since the last
+    // check, a category has to drop back under it before it is reported again
+    pub fn check_budgets(&mut self) -> Vec<MemoryBudgetExceeded> {
+        let mut exceeded = Vec::new();
+        for (category, budget) in self.budgets.iter() {
+            let used = self.get_usage(*category);
+            if used <= *budget {
+                self.over_budget.remove(category);
+            } else if self.over_budget.insert(*category) {
+                exceeded.push(MemoryBudgetExceeded {
+                    category: *category,
+                    used,
+                    budget: *budget,
+                });
+            }
+        }
+        exceeded
+    }
+
+    // Checks the budgets and emits a MemoryBudgetExceeded for every new
+    // overrun
+    pub fn report_budgets(&mut self, event_queue: &EventQueue) {
+        for exceeded in self.check_budgets() {
+            warn!(
+                "gpu memory budget for {:?} exceeded: {} of {} bytes",
+                exceeded.category, exceeded.used, exceeded.budget
+            );
+            if let Err(err) =
+                event_queue.emit(Box::new(RendererEvents::MemoryBudgetExceeded(exceeded)))
+            {
+                error!("unable to emit renderer event {:?}", err);
+            }
+        }
+    }
+}
+
+impl Subsystem for GpuMemoryTracker {
+    fn get_name(&self) -> &str {
+        "GpuMemoryTracker"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        self.report_budgets(ctx.event_queue);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureId(pub u64);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MipChange {
+    pub texture: TextureId,
+    // first mip level that stays resident, 0 is the full resolution
+    pub base_mip: usize,
+}
+
+#[derive(Debug)]
+struct ResidentTexture {
+    allocation: AllocationId,
+    // false for allocations of the backend, those are freed on destroy
+    owned: bool,
+    // size of every mip level, largest first
+    mip_sizes: Vec<u64>,
+    base_mip: usize,
+    distance: f32,
+}
+
+impl ResidentTexture {
+    fn resident_size(&self) -> u64 {
+        self.mip_sizes[self.base_mip..].iter().sum()
+    }
+}
+
+// Decides which mip levels of each texture stay on the gpu. When textures go
+// over budget the top mips of the most distant ones are dropped first, when
+// there is room again the closest ones get them back.
+#[derive(Debug, Default)]
+pub struct TextureResidency {
+    textures: HashMap<TextureId, ResidentTexture>,
+}
+
+impl TextureResidency {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_texture(
+        &mut self,
+        tracker: &mut GpuMemoryTracker,
+        texture: TextureId,
+        mip_sizes: Vec<u64>,
+    ) {
+        if mip_sizes.is_empty() {
+            warn!("ignoring texture {:?} without mip levels", texture);
+            return;
+        }
+        self.remove_texture(tracker, texture);
+        let allocation = tracker.allocate(GpuMemoryCategory::Texture, mip_sizes.iter().sum());
+        self.insert(texture, allocation, true, mip_sizes);
+    }
+
+    // Manages a texture the backend already reports, with the allocation
+    // from `RendererAPI::get_allocation`. Removing it leaves the allocation
+    // to the backend.
+    pub fn add_allocation(
+        &mut self,
+        tracker: &mut GpuMemoryTracker,
+        texture: TextureId,
+        allocation: AllocationId,
+        mip_sizes: Vec<u64>,
+    ) {
+        if mip_sizes.is_empty() {
+            warn!("ignoring texture {:?} without mip levels", texture);
+            return;
+        }
+        self.remove_texture(tracker, texture);
+        tracker.resize(allocation, mip_sizes.iter().sum());
+        self.insert(texture, allocation, false, mip_sizes);
+    }
+
+    fn insert(
+        &mut self,
+        texture: TextureId,
+        allocation: AllocationId,
+        owned: bool,
+        mip_sizes: Vec<u64>,
+    ) {
+        self.textures.insert(
+            texture,
+            ResidentTexture {
+                allocation,
+                owned,
+                mip_sizes,
+                base_mip: 0,
+                distance: 0.0,
+            },
+        );
+    }
+
+    pub fn remove_texture(&mut self, tracker: &mut GpuMemoryTracker, texture: TextureId) {
+        if let Some(resident) = self.textures.remove(&texture) {
+            if resident.owned {
+                tracker.free(resident.allocation);
+            }
+        }
+    }
+
+    // Distance to the camera, or any other priority where bigger means less
+    // important
+    pub fn set_distance(&mut self, texture: TextureId, distance: f32) {
+        if let Some(resident) = self.textures.get_mut(&texture) {
+            resident.distance = distance;
+        }
+    }
+
+    pub fn get_base_mip(&self, texture: TextureId) -> Option<usize> {
+        self.textures
+            .get(&texture)
+            .map(|resident| resident.base_mip)
+    }
+
+    // Demotes or promotes mip levels to fit the texture budget, returns the
+    // textures whose resident mips changed so the backend can apply it
+    pub fn enforce_budget(&mut self, tracker: &mut GpuMemoryTracker) -> Vec<MipChange> {
+        let Some(budget) = tracker.get_budget(GpuMemoryCategory::Texture) else {
+            return Vec::new();
+        };
+
+        let mut by_distance: Vec<TextureId> = self.textures.keys().copied().collect();
+        by_distance.sort_by(|a, b| {
+            self.textures[b]
+                .distance
+                .total_cmp(&self.textures[a].distance)
+        });
+
+        let mut changed = HashSet::new();
+
+        // farthest first, drop one mip per pass so the cost is spread
+        while tracker.get_usage(GpuMemoryCategory::Texture) > budget {
+            let mut dropped = false;
+            for id in by_distance.iter() {
+                if tracker.get_usage(GpuMemoryCategory::Texture) <= budget {
+                    break;
+                }
+                let resident = self.textures.get_mut(id).unwrap();
+                if resident.base_mip + 1 >= resident.mip_sizes.len() {
+                    continue;
+                }
+                resident.base_mip += 1;
+                tracker.resize(resident.allocation, resident.resident_size());
+                changed.insert(*id);
+                dropped = true;
+            }
+            if !dropped {
+                break;
+            }
+        }
+
+        // closest first, bring mips back while they fit
+        for id in by_distance.iter().rev() {
+            let resident = self.textures.get_mut(id).unwrap();
+            while resident.base_mip > 0 {
+                let extra = resident.mip_sizes[resident.base_mip - 1];
+                if tracker.get_usage(GpuMemoryCategory::Texture) + extra > budget {
+                    break;
+                }
+                resident.base_mip -= 1;
+                tracker.resize(resident.allocation, resident.resident_size());
+                changed.insert(*id);
+            }
+        }
+
+        changed
+            .into_iter()
+            .map(|texture| MipChange {
+                texture,
+                base_mip: self.textures[&texture].base_mip,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_warns_once_per_overrun() {
+        let mut tracker = GpuMemoryTracker::new();
+        tracker.set_budget(GpuMemoryCategory::Buffer, 100);
+
+        let buffer = tracker.allocate(GpuMemoryCategory::Buffer, 150);
+        tracker.allocate(GpuMemoryCategory::RenderTarget, 1000);
+        assert_eq!(tracker.get_total_usage(), 1150);
+
+        let exceeded = tracker.check_budgets();
+        assert_eq!(exceeded.len(), 1);
+        assert_eq!(exceeded[0].used, 150);
+        assert!(tracker.check_budgets().is_empty());
+
+        tracker.resize(buffer, 50);
+        assert!(tracker.check_budgets().is_empty());
+        assert_eq!(tracker.get_headroom(GpuMemoryCategory::Buffer), Some(50));
+
+        tracker.resize(buffer, 120);
+        assert_eq!(tracker.check_budgets().len(), 1);
+    }
+
+    #[test]
+    fn test_distant_textures_are_demoted_first() {
+        let mut tracker = GpuMemoryTracker::new();
+        let mut residency = TextureResidency::new();
+        let near = TextureId(1);
+        let far = TextureId(2);
+        residency.add_texture(&mut tracker, near, vec![64, 16, 4]);
+        residency.add_texture(&mut tracker, far, vec![64, 16, 4]);
+        residency.set_distance(near, 1.0);
+        residency.set_distance(far, 100.0);
+
+        tracker.set_budget(GpuMemoryCategory::Texture, 110);
+        let changes = residency.enforce_budget(&mut tracker);
+
+        assert_eq!(
+            changes,
+            vec![MipChange {
+                texture: far,
+                base_mip: 1
+            }]
+        );
+        assert_eq!(residency.get_base_mip(near), Some(0));
+        assert_eq!(tracker.get_usage(GpuMemoryCategory::Texture), 104);
+
+        // more room, the far texture gets its full mip chain back
+        tracker.set_budget(GpuMemoryCategory::Texture, 200);
+        residency.enforce_budget(&mut tracker);
+        assert_eq!(residency.get_base_mip(far), Some(0));
+    }
+
+    #[test]
+    fn test_smallest_mip_is_always_kept() {
+        let mut tracker = GpuMemoryTracker::new();
+        let mut residency = TextureResidency::new();
+        residency.add_texture(&mut tracker, TextureId(1), vec![64, 16, 4]);
+        tracker.set_budget(GpuMemoryCategory::Texture, 0);
+
+        residency.enforce_budget(&mut tracker);
+        assert_eq!(residency.get_base_mip(TextureId(1)), Some(2));
+        assert_eq!(tracker.get_usage(GpuMemoryCategory::Texture), 4);
+    }
+}
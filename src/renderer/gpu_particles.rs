@@ -0,0 +1,438 @@
+use std::time::Duration;
+
+use crate::math::vector::{Vec3, Vec4};
+
+use super::particles::{get_spawn_count, EmitterDesc, EmitterShape};
+
+pub const PARTICLE_WORKGROUP_SIZE: u32 = 256;
+
+// One particle in the storage buffer the compute passes work on
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GpuParticle {
+    // w: age in seconds
+    pub position: [f32; 4],
+    // w: lifetime in seconds, dead once the age reaches it
+    pub velocity: [f32; 4],
+}
+
+// The emitter description as the shaders read it, rebuilt every frame
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GpuEmitterUniforms {
+    // w: delta in seconds
+    pub origin: [f32; 4],
+    // w: drag
+    pub gravity: [f32; 4],
+    // w: 1 when sorted
+    pub camera: [f32; 4],
+    pub start_color: [f32; 4],
+    pub end_color: [f32; 4],
+    // kind (0 point, 1 sphere, 2 box, 3 cone) then its parameters
+    pub shape: [f32; 4],
+    // lifetime min, max, speed min, max
+    pub ranges: [f32; 4],
+    // start size, end size
+    pub sizes: [f32; 4],
+    // first slot, count, capacity, seed
+    pub spawn: [u32; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuParticlePass {
+    Spawn { workgroups: u32 },
+    Simulate { workgroups: u32 },
+    // one step of the bitonic sort of the (distance, index) keys, `k` and
+    // `j` go in as push constants
+    Sort { k: u32, j: u32, workgroups: u32 },
+}
+
+// What the renderer records for an emitter this frame: upload the
+// uniforms, dispatch the passes in order then draw `instance_count` quads
+// (six vertices each), dead particles collapse in the vertex shader
+#[derive(Debug, Clone, PartialEq)]
+pub struct GpuParticleFrame {
+    pub uniforms: GpuEmitterUniforms,
+    pub passes: Vec<GpuParticlePass>,
+    pub instance_count: u32,
+}
+
+fn get_workgroups(threads: u32) -> u32 {
+    threads.div_ceil(PARTICLE_WORKGROUP_SIZE)
+}
+
+// Particles live in a ring buffer of `max_particles` slots, new ones take
+// the slots after the last spawned. The CPU never reads them back, it only
+// tracks where the next spawn starts.
+#[derive(Debug, Clone)]
+pub struct GpuParticles {
+    capacity: u32,
+    next_slot: u32,
+    accumulator: f32,
+    started: bool,
+    seed: u64,
+    frame: u32,
+}
+
+impl GpuParticles {
+    pub fn new(capacity: u32, seed: u64) -> Self {
+        Self {
+            capacity,
+            next_slot: 0,
+            accumulator: 0.0,
+            started: false,
+            seed,
+            frame: 0,
+        }
+    }
+
+    pub fn get_capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    // Sorting works on a power of two, the extra keys are padding
+    pub fn get_sort_capacity(&self) -> u32 {
+        match self.capacity {
+            0 => 0,
+            capacity => capacity.next_power_of_two(),
+        }
+    }
+
+    // Bytes of the particle buffer plus the sort keys
+    pub fn get_buffer_size(&self) -> u64 {
+        self.capacity as u64 * std::mem::size_of::<GpuParticle>() as u64
+            + self.get_sort_capacity() as u64 * 8
+    }
+
+    pub fn update(
+        &mut self,
+        desc: &EmitterDesc,
+        origin: Vec3,
+        camera: Vec3,
+        delta: Duration,
+    ) -> GpuParticleFrame {
+        let delta = delta.as_secs_f32();
+        let mut count = get_spawn_count(desc, &mut self.accumulator, delta);
+        if !self.started {
+            self.started = true;
+            count += desc.burst;
+        }
+        let count = count.min(self.capacity);
+        let first = self.next_slot;
+        if self.capacity > 0 {
+            self.next_slot = (self.next_slot + count) % self.capacity;
+        }
+        // a new seed every frame so spawns do not repeat
+        let seed = (self.seed ^ (self.frame as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)) as u32;
+        self.frame = self.frame.wrapping_add(1);
+
+        let shape = match desc.shape {
+            EmitterShape::Point => [0.0; 4],
+            EmitterShape::Sphere { radius } => [1.0, radius, 0.0, 0.0],
+            EmitterShape::Box { half_extents } => {
+                [2.0, half_extents.x, half_extents.y, half_extents.z]
+            }
+            EmitterShape::Cone { angle, radius } => [3.0, angle, radius, 0.0],
+        };
+        let color = |color: Vec4| [color.x, color.y, color.z, color.w];
+        let uniforms = GpuEmitterUniforms {
+            origin: [origin.x, origin.y, origin.z, delta],
+            gravity: [desc.gravity.x, desc.gravity.y, desc.gravity.z, desc.drag],
+            camera: [camera.x, camera.y, camera.z, desc.sort as u32 as f32],
+            start_color: color(desc.start_color),
+            end_color: color(desc.end_color),
+            shape,
+            ranges: [
+                desc.lifetime_min,
+                desc.lifetime_max,
+                desc.speed_min,
+                desc.speed_max,
+            ],
+            sizes: [desc.start_size, desc.end_size, 0.0, 0.0],
+            spawn: [first, count, self.capacity, seed],
+        };
+
+        let mut passes = Vec::new();
+        if self.capacity > 0 {
+            // simulate before spawning so new particles start at age zero
+            // like on the CPU; with sorting it also writes the padding keys
+            let threads = match desc.sort {
+                true => self.get_sort_capacity(),
+                false => self.capacity,
+            };
+            passes.push(GpuParticlePass::Simulate {
+                workgroups: get_workgroups(threads),
+            });
+            if count > 0 {
+                passes.push(GpuParticlePass::Spawn {
+                    workgroups: get_workgroups(count),
+                });
+            }
+            if desc.sort {
+                let size = self.get_sort_capacity();
+                let mut k = 2;
+                while k <= size {
+                    let mut j = k / 2;
+                    while j > 0 {
+                        passes.push(GpuParticlePass::Sort {
+                            k,
+                            j,
+                            workgroups: get_workgroups(size),
+                        });
+                        j /= 2;
+                    }
+                    k *= 2;
+                }
+            }
+        }
+
+        GpuParticleFrame {
+            uniforms,
+            passes,
+            instance_count: self.capacity,
+        }
+    }
+}
+
+const PARTICLE_COMMON: &str = r#"#version 450
+
+layout(std140, binding = 0) uniform Emitter {
+    vec4 u_origin;
+    vec4 u_gravity;
+    vec4 u_camera;
+    vec4 u_start_color;
+    vec4 u_end_color;
+    vec4 u_shape;
+    vec4 u_ranges;
+    vec4 u_sizes;
+    uvec4 u_spawn;
+};
+
+struct Particle {
+    vec4 position;
+    vec4 velocity;
+};
+
+struct SortKey {
+    float distance;
+    uint index;
+};
+
+layout(std430, binding = 1) buffer Particles {
+    Particle particles[];
+};
+
+layout(std430, binding = 2) buffer SortKeys {
+    SortKey keys[];
+};
+"#;
+
+// Shaders are the common block followed by one of the bodies below
+pub fn get_particle_shader(body: &str) -> String {
+    format!("{}{}", PARTICLE_COMMON, body)
+}
+
+pub const PARTICLE_SPAWN_SHADER: &str = r#"
+layout(local_size_x = 256) in;
+
+uint hash(uint value) {
+    value ^= value >> 16;
+    value *= 0x7feb352du;
+    value ^= value >> 15;
+    value *= 0x846ca68bu;
+    value ^= value >> 16;
+    return value;
+}
+
+float random(inout uint state) {
+    state = hash(state);
+    return float(state >> 8) / 16777216.0;
+}
+
+vec3 random_direction(inout uint state) {
+    float z = random(state) * 2.0 - 1.0;
+    float angle = random(state) * 6.2831853;
+    float ring = sqrt(max(1.0 - z * z, 0.0));
+    return vec3(ring * cos(angle), ring * sin(angle), z);
+}
+
+void main() {
+    uint id = gl_GlobalInvocationID.x;
+    if (id >= u_spawn.y) {
+        return;
+    }
+    uint slot = (u_spawn.x + id) % u_spawn.z;
+    uint state = hash(u_spawn.w ^ hash(id));
+
+    vec3 offset = vec3(0.0);
+    vec3 direction = random_direction(state);
+    uint kind = uint(u_shape.x);
+    if (kind == 1u) {
+        offset = direction * u_shape.y * pow(random(state), 1.0 / 3.0);
+    } else if (kind == 2u) {
+        offset = (vec3(random(state), random(state), random(state)) * 2.0 - 1.0) * u_shape.yzw;
+    } else if (kind == 3u) {
+        float around = random(state) * 6.2831853;
+        float tilt = u_shape.y * sqrt(random(state));
+        float spread = sqrt(random(state)) * u_shape.z;
+        offset = vec3(cos(around), 0.0, sin(around)) * spread;
+        direction = vec3(sin(tilt) * cos(around), cos(tilt), sin(tilt) * sin(around));
+    }
+
+    float speed = mix(u_ranges.z, u_ranges.w, random(state));
+    float lifetime = mix(u_ranges.x, u_ranges.y, random(state));
+    particles[slot].position = vec4(u_origin.xyz + offset, 0.0);
+    particles[slot].velocity = vec4(direction * speed, lifetime);
+}
+"#;
+
+pub const PARTICLE_SIMULATE_SHADER: &str = r#"
+layout(local_size_x = 256) in;
+
+void main() {
+    uint id = gl_GlobalInvocationID.x;
+    bool sorted = u_camera.w > 0.5;
+    if (id >= u_spawn.z) {
+        if (sorted) {
+            keys[id] = SortKey(-1.0, id);
+        }
+        return;
+    }
+
+    Particle particle = particles[id];
+    float delta = u_origin.w;
+    bool alive = particle.position.w < particle.velocity.w;
+    if (alive) {
+        vec3 velocity = particle.velocity.xyz + u_gravity.xyz * delta;
+        velocity *= max(1.0 - u_gravity.w * delta, 0.0);
+        particle.position.xyz += velocity * delta;
+        particle.position.w += delta;
+        particle.velocity.xyz = velocity;
+        particles[id] = particle;
+        alive = particle.position.w < particle.velocity.w;
+    }
+    if (sorted) {
+        float distance = alive ? length(particle.position.xyz - u_camera.xyz) : -1.0;
+        keys[id] = SortKey(distance, id);
+    }
+}
+"#;
+
+// Farthest first, dead particles and padding end up last
+pub const PARTICLE_SORT_SHADER: &str = r#"
+layout(local_size_x = 256) in;
+
+layout(push_constant) uniform Step {
+    uint k;
+    uint j;
+};
+
+void main() {
+    uint id = gl_GlobalInvocationID.x;
+    uint other = id ^ j;
+    if (other <= id || other >= keys.length()) {
+        return;
+    }
+    SortKey a = keys[id];
+    SortKey b = keys[other];
+    bool descending = (id & k) == 0u;
+    if (descending == (a.distance < b.distance)) {
+        keys[id] = b;
+        keys[other] = a;
+    }
+}
+"#;
+
+pub const PARTICLE_VERTEX_SHADER: &str = r#"
+layout(std140, binding = 3) uniform Camera {
+    mat4 u_view;
+    mat4 u_projection;
+};
+
+layout(location = 0) out vec4 v_color;
+layout(location = 1) out vec2 v_uv;
+
+const vec2 CORNERS[6] = vec2[](
+    vec2(-0.5, -0.5), vec2(0.5, -0.5), vec2(0.5, 0.5),
+    vec2(-0.5, -0.5), vec2(0.5, 0.5), vec2(-0.5, 0.5)
+);
+
+void main() {
+    uint index = u_camera.w > 0.5 ? keys[gl_InstanceIndex].index : gl_InstanceIndex;
+    Particle particle = particles[index];
+    float age = particle.position.w;
+    float lifetime = particle.velocity.w;
+    if (age >= lifetime) {
+        gl_Position = vec4(0.0);
+        return;
+    }
+
+    float t = clamp(age / max(lifetime, 1e-6), 0.0, 1.0);
+    float size = mix(u_sizes.x, u_sizes.y, t);
+    vec2 corner = CORNERS[gl_VertexIndex];
+    vec3 right = vec3(u_view[0][0], u_view[1][0], u_view[2][0]);
+    vec3 up = vec3(u_view[0][1], u_view[1][1], u_view[2][1]);
+    vec3 position = particle.position.xyz + (right * corner.x + up * corner.y) * size;
+
+    v_color = mix(u_start_color, u_end_color, t);
+    v_uv = corner + 0.5;
+    gl_Position = u_projection * u_view * vec4(position, 1.0);
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_passes() {
+        let desc = EmitterDesc {
+            max_particles: 300_000,
+            spawn_rate: 1000.0,
+            burst: 5000,
+            sort: true,
+            ..Default::default()
+        };
+        let mut particles = GpuParticles::new(desc.max_particles, 1);
+        assert_eq!(particles.get_sort_capacity(), 1 << 19);
+        assert_eq!(particles.get_buffer_size(), 300_000 * 32 + (1 << 19) * 8);
+
+        let frame = particles.update(&desc, Vec3::ZERO, Vec3::Z, Duration::from_millis(10));
+        assert_eq!(
+            frame.uniforms.spawn,
+            [0, 5010, 300_000, frame.uniforms.spawn[3]]
+        );
+        assert_eq!(
+            frame.passes[..2],
+            [
+                GpuParticlePass::Simulate { workgroups: 2048 },
+                GpuParticlePass::Spawn { workgroups: 20 },
+            ]
+        );
+        // 19 * 20 / 2 bitonic steps for 2^19 keys
+        assert_eq!(frame.passes.len(), 2 + 190);
+        assert_eq!(
+            frame.passes.last(),
+            Some(&GpuParticlePass::Sort {
+                k: 1 << 19,
+                j: 1,
+                workgroups: 2048
+            })
+        );
+
+        let frame = particles.update(&desc, Vec3::ZERO, Vec3::Z, Duration::from_millis(10));
+        assert_eq!(frame.uniforms.spawn[..2], [5010, 10]);
+        assert_eq!(frame.instance_count, 300_000);
+
+        let unsorted = EmitterDesc {
+            sort: false,
+            spawn_rate: 0.0,
+            ..desc
+        };
+        let frame = particles.update(&unsorted, Vec3::ZERO, Vec3::Z, Duration::from_millis(10));
+        assert_eq!(
+            frame.passes,
+            vec![GpuParticlePass::Simulate { workgroups: 1172 }]
+        );
+    }
+}
@@ -0,0 +1,330 @@
+use crate::math::vector::{Vec2, Vec3, Vec4};
+
+// Colors are linear rgb, positions in world units with y growing downwards
+#[derive(Debug, Clone, PartialEq)]
+pub enum Light2D {
+    Ambient {
+        color: Vec3,
+        intensity: f32,
+    },
+    Point {
+        position: Vec2,
+        color: Vec3,
+        intensity: f32,
+        radius: f32,
+    },
+    Spot {
+        position: Vec2,
+        // does not have to be normalized
+        direction: Vec2,
+        color: Vec3,
+        intensity: f32,
+        radius: f32,
+        // half angles in radians, the light fades out between the two
+        inner_angle: f32,
+        outer_angle: f32,
+    },
+}
+
+impl Light2D {
+    pub fn point(position: Vec2, color: Vec3, intensity: f32, radius: f32) -> Self {
+        Self::Point {
+            position,
+            color,
+            intensity,
+            radius,
+        }
+    }
+
+    pub fn ambient(color: Vec3, intensity: f32) -> Self {
+        Self::Ambient { color, intensity }
+    }
+}
+
+// A closed polygon that blocks light, e.g. walls and crates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Occluder {
+    pub points: Vec<Vec2>,
+}
+
+impl Occluder {
+    pub fn new(points: Vec<Vec2>) -> Self {
+        Self { points }
+    }
+
+    pub fn rect(min: Vec2, max: Vec2) -> Self {
+        Self::new(vec![
+            min,
+            Vec2::new(max.x, min.y),
+            max,
+            Vec2::new(min.x, max.y),
+        ])
+    }
+
+    fn edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let count = self.points.len();
+        (0..count).map(move |index| (self.points[index], self.points[(index + 1) % count]))
+    }
+}
+
+fn cross(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+fn segments_intersect(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> bool {
+    let ab = b - a;
+    let cd = d - c;
+    let denominator = cross(ab, cd);
+    if denominator.abs() < f32::EPSILON {
+        return false;
+    }
+    let t = cross(c - a, cd) / denominator;
+    let u = cross(c - a, ab) / denominator;
+    (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightingSettings {
+    // how far above the sprites the lights are, flattens normal map lighting
+    // the higher it is
+    pub light_height: f32,
+    // size of the light sources, bigger sources cast softer shadows
+    pub source_radius: f32,
+    // rays traced per light for the penumbra, 1 gives hard shadows
+    pub shadow_samples: u32,
+}
+
+impl Default for LightingSettings {
+    fn default() -> Self {
+        Self {
+            light_height: 50.0,
+            source_radius: 8.0,
+            shadow_samples: 8,
+        }
+    }
+}
+
+// Lights and occluders of a 2D scene. The renderer runs `apply` as the
+// lighting pass of the post-processing stack, after the sprites were drawn
+// into the color and normal buffers.
+#[derive(Debug, Clone, Default)]
+pub struct Lighting2D {
+    settings: LightingSettings,
+    lights: Vec<Light2D>,
+    occluders: Vec<Occluder>,
+}
+
+impl Lighting2D {
+    pub fn new(settings: LightingSettings) -> Self {
+        Self {
+            settings,
+            ..Default::default()
+        }
+    }
+
+    pub fn get_settings(&self) -> &LightingSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: LightingSettings) {
+        self.settings = settings;
+    }
+
+    pub fn add_light(&mut self, light: Light2D) -> usize {
+        self.lights.push(light);
+        self.lights.len() - 1
+    }
+
+    pub fn get_lights_mut(&mut self) -> &mut Vec<Light2D> {
+        &mut self.lights
+    }
+
+    pub fn add_occluder(&mut self, occluder: Occluder) -> usize {
+        self.occluders.push(occluder);
+        self.occluders.len() - 1
+    }
+
+    pub fn get_occluders_mut(&mut self) -> &mut Vec<Occluder> {
+        &mut self.occluders
+    }
+
+    pub fn clear(&mut self) {
+        self.lights.clear();
+        self.occluders.clear();
+    }
+
+    // Fraction of the light source visible from `point`, 0 is full shadow
+    pub fn get_visibility(&self, light_position: Vec2, point: Vec2) -> f32 {
+        if self.occluders.is_empty() {
+            return 1.0;
+        }
+        let samples = self.settings.shadow_samples.max(1);
+        let to_point = point - light_position;
+        let side = Vec2::new(-to_point.y, to_point.x).normalize();
+
+        let visible = (0..samples)
+            .filter(|sample| {
+                // spread the samples across the source, perpendicular to the ray
+                let offset = if samples == 1 {
+                    0.0
+                } else {
+                    (*sample as f32 / (samples - 1) as f32 - 0.5) * 2.0
+                };
+                let source = light_position + side * (offset * self.settings.source_radius);
+                !self.occluders.iter().any(|occluder| {
+                    occluder
+                        .edges()
+                        .any(|(a, b)| segments_intersect(source, point, a, b))
+                })
+            })
+            .count();
+        visible as f32 / samples as f32
+    }
+
+    // Light reaching a pixel at `point` with the given normal map normal,
+    // Vec3::Z for sprites without a normal map
+    pub fn get_light(&self, point: Vec2, normal: Vec3) -> Vec3 {
+        let mut total = Vec3::ZERO;
+        for light in self.lights.iter() {
+            let (position, color, intensity, radius, cone) = match light {
+                Light2D::Ambient { color, intensity } => {
+                    total += *color * *intensity;
+                    continue;
+                }
+                Light2D::Point {
+                    position,
+                    color,
+                    intensity,
+                    radius,
+                } => (*position, *color, *intensity, *radius, 1.0),
+                Light2D::Spot {
+                    position,
+                    direction,
+                    color,
+                    intensity,
+                    radius,
+                    inner_angle,
+                    outer_angle,
+                } => {
+                    let angle = direction
+                        .normalize()
+                        .dot((point - *position).normalize())
+                        .clamp(-1.0, 1.0)
+                        .acos();
+                    let cone = if angle <= *inner_angle {
+                        1.0
+                    } else if angle >= *outer_angle {
+                        0.0
+                    } else {
+                        1.0 - (angle - inner_angle) / (outer_angle - inner_angle)
+                    };
+                    (*position, *color, *intensity, *radius, cone)
+                }
+            };
+
+            let distance = position.distance(point);
+            if cone <= 0.0 || distance >= radius {
+                continue;
+            }
+            let falloff = (1.0 - distance / radius).powi(2);
+            let to_light = position - point;
+            let direction =
+                Vec3::new(to_light.x, to_light.y, self.settings.light_height).normalize();
+            let diffuse = normal.normalize().dot(direction).max(0.0);
+            if diffuse <= 0.0 {
+                continue;
+            }
+            let visibility = self.get_visibility(position, point);
+            total += color * (intensity * falloff * cone * diffuse * visibility);
+        }
+        total
+    }
+
+    // The lighting pass: multiplies every pixel of a `width` wide color
+    // buffer with the light reaching it, `normals` is the matching normal
+    // buffer. Pixel centers are used as positions.
+    pub fn apply(&self, colors: &mut [Vec4], normals: &[Vec3], width: usize) {
+        if width == 0 {
+            return;
+        }
+        for (index, color) in colors.iter_mut().enumerate() {
+            let point = Vec2::new((index % width) as f32 + 0.5, (index / width) as f32 + 0.5);
+            let normal = normals.get(index).copied().unwrap_or(Vec3::Z);
+            let light = self.get_light(point, normal);
+            *color = Vec4::new(
+                color.x * light.x,
+                color.y * light.y,
+                color.z * light.z,
+                color.w,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_point_light_falloff_and_ambient() {
+        let mut lighting = Lighting2D::new(LightingSettings {
+            light_height: 0.0,
+            ..Default::default()
+        });
+        lighting.add_light(Light2D::ambient(Vec3::splat(1.0), 0.1));
+        lighting.add_light(Light2D::point(Vec2::ZERO, Vec3::splat(1.0), 1.0, 10.0));
+
+        // a normal facing the light gets the full falloff
+        let lit = lighting.get_light(Vec2::new(5.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        assert!((lit.x - 0.35).abs() < 1e-5);
+        // facing away only the ambient light remains
+        let unlit = lighting.get_light(Vec2::new(5.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert!((unlit.x - 0.1).abs() < 1e-5);
+        assert_eq!(
+            lighting.get_light(Vec2::new(20.0, 0.0), Vec3::Z),
+            Vec3::splat(0.1)
+        );
+    }
+
+    #[test]
+    fn test_soft_shadows() {
+        let mut lighting = Lighting2D::new(LightingSettings {
+            source_radius: 4.0,
+            shadow_samples: 4,
+            ..Default::default()
+        });
+        lighting.add_occluder(Occluder::rect(Vec2::new(9.0, -3.0), Vec2::new(11.0, 3.0)));
+
+        assert_eq!(
+            lighting.get_visibility(Vec2::ZERO, Vec2::new(20.0, 0.0)),
+            0.0
+        );
+        // partly behind the box, only some of the source is visible
+        let penumbra = lighting.get_visibility(Vec2::ZERO, Vec2::new(20.0, 3.0));
+        assert!(penumbra > 0.0 && penumbra < 1.0);
+        assert_eq!(
+            lighting.get_visibility(Vec2::ZERO, Vec2::new(0.0, 20.0)),
+            1.0
+        );
+    }
+
+    #[test]
+    fn test_spot_cone() {
+        let mut lighting = Lighting2D::default();
+        lighting.add_light(Light2D::Spot {
+            position: Vec2::ZERO,
+            direction: Vec2::new(1.0, 0.0),
+            color: Vec3::splat(1.0),
+            intensity: 1.0,
+            radius: 100.0,
+            inner_angle: 0.2,
+            outer_angle: 0.4,
+        });
+
+        assert!(lighting.get_light(Vec2::new(10.0, 0.0), Vec3::Z).x > 0.0);
+        assert_eq!(
+            lighting.get_light(Vec2::new(0.0, 10.0), Vec3::Z),
+            Vec3::ZERO
+        );
+    }
+}
@@ -0,0 +1,369 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::math::vector::Vec3;
+
+use super::render_stats::RenderStats;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LodId(u64);
+
+// How a group picks its level. Distance thresholds are the furthest distance
+// a level is used at and grow per level, screen size thresholds are the
+// smallest fraction of the screen height a level is used at and shrink.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LodSelection {
+    Distance,
+    ScreenSize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodLevel {
+    // mesh or sprite drawn at this level
+    pub asset: String,
+    pub threshold: f32,
+}
+
+impl LodLevel {
+    pub fn new(asset: &str, threshold: f32) -> Self {
+        Self {
+            asset: asset.to_string(),
+            threshold,
+        }
+    }
+}
+
+// The levels of one renderable, the first one is the most detailed. A level
+// only changes once the metric is past its threshold by the hysteresis
+// fraction, so objects sitting on a threshold do not pop every frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodGroup {
+    pub position: Vec3,
+    pub bounding_radius: f32,
+    pub selection: LodSelection,
+    pub hysteresis: f32,
+    levels: Vec<LodLevel>,
+    current: usize,
+}
+
+impl LodGroup {
+    pub fn new(selection: LodSelection, levels: Vec<LodLevel>) -> Self {
+        Self {
+            position: Vec3::ZERO,
+            bounding_radius: 1.0,
+            selection,
+            hysteresis: 0.1,
+            levels,
+            current: 0,
+        }
+    }
+
+    pub fn get_levels(&self) -> &[LodLevel] {
+        &self.levels
+    }
+
+    pub fn get_current(&self) -> usize {
+        self.current
+    }
+
+    pub fn get_current_level(&self) -> Option<&LodLevel> {
+        self.levels.get(self.current)
+    }
+
+    // Picks the level for the metric of the group's selection, returns true
+    // when it changed
+    pub fn select(&mut self, metric: f32) -> bool {
+        let last = self.levels.len().saturating_sub(1);
+        let mut level = last;
+        for (index, boundary) in self.levels.iter().enumerate().take(last) {
+            // the boundary moves away from the side the group is on
+            let stay = match self.current <= index {
+                true => self.hysteresis,
+                false => -self.hysteresis,
+            };
+            let finer = match self.selection {
+                LodSelection::Distance => metric < boundary.threshold * (1.0 + stay),
+                LodSelection::ScreenSize => metric >= boundary.threshold * (1.0 - stay),
+            };
+            if finer {
+                level = index;
+                break;
+            }
+        }
+        let changed = level != self.current;
+        self.current = level;
+        changed
+    }
+
+    // Fraction of the screen height the bounding sphere covers for a
+    // perspective camera
+    pub fn get_screen_size(&self, camera: Vec3, fov_y: f32) -> f32 {
+        let distance = self.position.distance(camera).max(f32::EPSILON);
+        self.bounding_radius / (distance * (fov_y / 2.0).tan())
+    }
+
+    pub fn update(&mut self, camera: Vec3, fov_y: f32) -> bool {
+        let metric = match self.selection {
+            LodSelection::Distance => self.position.distance(camera),
+            LodSelection::ScreenSize => self.get_screen_size(camera, fov_y),
+        };
+        self.select(metric)
+    }
+}
+
+// Every LOD group of the scene. The renderer calls `update` once per frame
+// before it draws, then draws the current level of every group.
+#[derive(Debug, Default)]
+pub struct Lods {
+    groups: BTreeMap<LodId, LodGroup>,
+    next_id: u64,
+}
+
+impl Lods {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, group: LodGroup) -> LodId {
+        let id = LodId(self.next_id);
+        self.next_id += 1;
+        self.groups.insert(id, group);
+        id
+    }
+
+    pub fn get(&self, id: LodId) -> Option<&LodGroup> {
+        self.groups.get(&id)
+    }
+
+    pub fn get_mut(&mut self, id: LodId) -> Option<&mut LodGroup> {
+        self.groups.get_mut(&id)
+    }
+
+    pub fn remove(&mut self, id: LodId) -> Option<LodGroup> {
+        self.groups.remove(&id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    // Sprites drawn by an orthographic camera use the screen size directly,
+    // pass it here instead of letting `update` compute it
+    pub fn select(&mut self, id: LodId, metric: f32, stats: &mut RenderStats) {
+        if let Some(group) = self.groups.get_mut(&id) {
+            let switched = group.select(metric);
+            stats.record_lod(group.current, switched);
+        }
+    }
+
+    pub fn update(&mut self, camera: Vec3, fov_y: f32, stats: &mut RenderStats) {
+        for group in self.groups.values_mut() {
+            let switched = group.update(camera, fov_y);
+            stats.record_lod(group.current, switched);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LodMesh {
+    pub positions: Vec<Vec3>,
+    pub indices: Vec<u32>,
+}
+
+impl LodMesh {
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    // Vertex clustering: vertices in the same grid cell are merged into their
+    // average and triangles that collapse are dropped
+    pub fn simplify(&self, resolution: u32) -> Self {
+        let Some(first) = self.positions.first() else {
+            return self.clone();
+        };
+        let (min, max) = self
+            .positions
+            .iter()
+            .fold((*first, *first), |(min, max), p| (min.min(*p), max.max(*p)));
+        let extent = (max - min).max(Vec3::splat(f32::EPSILON));
+        let resolution = resolution.max(1) as f32;
+        let cell = |p: Vec3| {
+            let t = Vec3::new(
+                (p.x - min.x) / extent.x,
+                (p.y - min.y) / extent.y,
+                (p.z - min.z) / extent.z,
+            ) * resolution;
+            let max_cell = resolution - 1.0;
+            (
+                t.x.min(max_cell) as u32,
+                t.y.min(max_cell) as u32,
+                t.z.min(max_cell) as u32,
+            )
+        };
+
+        let mut cells = HashMap::new();
+        let mut sums: Vec<(Vec3, f32)> = Vec::new();
+        let remap: Vec<u32> = self
+            .positions
+            .iter()
+            .map(|position| {
+                let index = *cells.entry(cell(*position)).or_insert_with(|| {
+                    sums.push((Vec3::ZERO, 0.0));
+                    sums.len() - 1
+                });
+                sums[index].0 += *position;
+                sums[index].1 += 1.0;
+                index as u32
+            })
+            .collect();
+
+        let indices = self
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| triangle.iter().map(|index| remap[*index as usize]))
+            .map(|triangle| triangle.collect::<Vec<_>>())
+            .filter(|t| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+            .flatten()
+            .collect();
+        Self {
+            positions: sums.into_iter().map(|(sum, count)| sum / count).collect(),
+            indices,
+        }
+    }
+
+    // The finest clustering that gets down to `ratio` of the triangles
+    pub fn simplify_to(&self, ratio: f32) -> Self {
+        let target = (self.triangle_count() as f32 * ratio.clamp(0.0, 1.0)) as usize;
+        let (mut low, mut high) = (1, 256);
+        let mut best = self.simplify(1);
+        while low <= high {
+            let resolution = (low + high) / 2;
+            let simplified = self.simplify(resolution);
+            if simplified.triangle_count() <= target {
+                best = simplified;
+                low = resolution + 1;
+            } else {
+                high = resolution - 1;
+            }
+        }
+        best
+    }
+}
+
+// Import settings of a mesh. With `generate` set the importer builds the
+// lower levels from the source mesh, one per ratio of the triangles kept.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodImportSettings {
+    pub generate: bool,
+    pub ratios: Vec<f32>,
+}
+
+impl Default for LodImportSettings {
+    fn default() -> Self {
+        Self {
+            generate: false,
+            ratios: vec![0.5, 0.25, 0.1],
+        }
+    }
+}
+
+impl LodImportSettings {
+    // The source mesh followed by the generated levels
+    pub fn build_levels(&self, mesh: LodMesh) -> Vec<LodMesh> {
+        let generated: Vec<LodMesh> = match self.generate {
+            true => self
+                .ratios
+                .iter()
+                .map(|ratio| mesh.simplify_to(*ratio))
+                .collect(),
+            false => Vec::new(),
+        };
+        std::iter::once(mesh).chain(generated).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels() -> Vec<LodLevel> {
+        vec![
+            LodLevel::new("rock_lod0", 10.0),
+            LodLevel::new("rock_lod1", 20.0),
+            LodLevel::new("rock_lod2", 0.0),
+        ]
+    }
+
+    #[test]
+    fn test_distance_selection_with_hysteresis() {
+        let mut group = LodGroup::new(LodSelection::Distance, levels());
+        assert!(!group.select(5.0));
+        // within 10% past the threshold the level sticks
+        assert!(!group.select(10.5));
+        assert!(group.select(11.5));
+        assert_eq!(group.get_current(), 1);
+        assert!(!group.select(9.5));
+        assert!(group.select(8.5));
+        assert!(group.select(100.0));
+        assert_eq!(group.get_current_level().unwrap().asset, "rock_lod2");
+    }
+
+    #[test]
+    fn test_screen_size_updates_stats() {
+        let mut group = LodGroup::new(
+            LodSelection::ScreenSize,
+            vec![
+                LodLevel::new("tree_lod0", 0.5),
+                LodLevel::new("tree_lod1", 0.0),
+            ],
+        );
+        group.position = Vec3::new(0.0, 0.0, 10.0);
+        let mut lods = Lods::new();
+        let id = lods.add(group);
+        let mut stats = RenderStats::new();
+
+        // a radius 1 sphere 10 units away with a 90 degree fov covers 0.1
+        lods.update(Vec3::ZERO, std::f32::consts::FRAC_PI_2, &mut stats);
+        assert_eq!(lods.get(id).unwrap().get_current(), 1);
+        lods.get_mut(id).unwrap().position = Vec3::new(0.0, 0.0, 1.0);
+        lods.update(Vec3::ZERO, std::f32::consts::FRAC_PI_2, &mut stats);
+        assert_eq!(lods.get(id).unwrap().get_current(), 0);
+
+        let frame = stats.get_current_frame();
+        assert_eq!(frame.lod_switches, 2);
+        assert_eq!(frame.lod_levels, vec![1, 1]);
+    }
+
+    #[test]
+    fn test_generated_levels_reduce_triangles() {
+        // a 16x16 quad grid
+        let mut mesh = LodMesh::default();
+        for y in 0..=16 {
+            for x in 0..=16 {
+                mesh.positions.push(Vec3::new(x as f32, y as f32, 0.0));
+            }
+        }
+        for y in 0..16 {
+            for x in 0..16 {
+                let i = y * 17 + x;
+                mesh.indices
+                    .extend([i, i + 1, i + 17, i + 1, i + 18, i + 17]);
+            }
+        }
+
+        let settings = LodImportSettings {
+            generate: true,
+            ..Default::default()
+        };
+        let levels = settings.build_levels(mesh);
+        assert_eq!(levels.len(), 4);
+        assert_eq!(levels[0].triangle_count(), 512);
+        for (level, ratio) in levels[1..].iter().zip(settings.ratios) {
+            assert!(level.triangle_count() > 0);
+            assert!(level.triangle_count() as f32 <= 512.0 * ratio);
+        }
+    }
+}
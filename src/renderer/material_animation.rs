@@ -0,0 +1,330 @@
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    animation::clip::{Interpolation, Track},
+    core::data_file::{load_data_file, DataFileErrors},
+    event_system::event::{EntityId, Event},
+    math::vector::Vec4,
+};
+
+use super::api::{Buffer, RendererAPI, RendererErrors};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum MaterialAnimationErrors {
+    #[error("unable to load material animations: {0}")]
+    File(#[from] DataFileErrors),
+
+    #[error("binding of {0} has no keys")]
+    NoKeys(String),
+}
+
+// How a curve's value combines with the parameter's base value
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ParamBlend {
+    #[default]
+    Replace,
+    Add,
+    // component wise, e.g. a tint flashing red
+    Multiply,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct ParamKey {
+    // seconds since the event
+    pub time: f32,
+    pub value: Vec4,
+}
+
+// Plays a curve on a parameter every time the event happens, e.g. `tint`
+// flashing red on DamageTaken
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParamBinding {
+    pub event: String,
+    pub parameter: String,
+    pub blend: ParamBlend,
+    pub keys: Vec<ParamKey>,
+    pub step: bool,
+    // plays until the event comes again instead of once
+    pub looping: bool,
+}
+
+impl Default for ParamBinding {
+    fn default() -> Self {
+        Self {
+            event: String::new(),
+            parameter: String::new(),
+            blend: ParamBlend::Replace,
+            keys: Vec::new(),
+            step: false,
+            looping: false,
+        }
+    }
+}
+
+// The asset a material's feedback effects are declared in
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaterialAnimationDesc {
+    pub bindings: Vec<ParamBinding>,
+}
+
+impl MaterialAnimationDesc {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, MaterialAnimationErrors> {
+        Ok(load_data_file(path)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Curve {
+    event: String,
+    parameter: String,
+    blend: ParamBlend,
+    track: Track<Vec4>,
+    looping: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Playing {
+    curve: usize,
+    time: f32,
+}
+
+// The parameters of one material and the curves events play on them.
+// Parameters are vec4s, a float parameter uses x. The renderer calls
+// `update` every frame and `upload` writes the result, in parameter name
+// order, to the material's uniform buffer.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MaterialAnimator {
+    base: BTreeMap<String, Vec4>,
+    curves: Vec<Curve>,
+    playing: Vec<Playing>,
+    // only targeted events for this entity play, untargeted ones always do
+    entity: Option<EntityId>,
+    dirty: bool,
+}
+
+impl MaterialAnimator {
+    pub fn new(desc: &MaterialAnimationDesc) -> Result<Self, MaterialAnimationErrors> {
+        let mut curves = Vec::new();
+        for binding in desc.bindings.iter() {
+            if binding.keys.is_empty() {
+                return Err(MaterialAnimationErrors::NoKeys(binding.parameter.clone()));
+            }
+            let interpolation = match binding.step {
+                true => Interpolation::Step,
+                false => Interpolation::Linear,
+            };
+            curves.push(Curve {
+                event: binding.event.clone(),
+                parameter: binding.parameter.clone(),
+                blend: binding.blend,
+                track: Track::new(
+                    binding.keys.iter().map(|key| key.time).collect(),
+                    binding.keys.iter().map(|key| key.value).collect(),
+                    interpolation,
+                ),
+                looping: binding.looping,
+            });
+        }
+        Ok(Self {
+            base: BTreeMap::new(),
+            curves,
+            playing: Vec::new(),
+            entity: None,
+            dirty: true,
+        })
+    }
+
+    pub fn set_entity(&mut self, entity: Option<EntityId>) {
+        self.entity = entity;
+    }
+
+    // The value without any curve playing
+    pub fn set_param(&mut self, name: &str, value: Vec4) {
+        self.base.insert(name.to_string(), value);
+        self.dirty = true;
+    }
+
+    pub fn get_base_param(&self, name: &str) -> Option<Vec4> {
+        self.base.get(name).copied()
+    }
+
+    pub fn is_playing(&self) -> bool {
+        !self.playing.is_empty()
+    }
+
+    // Starts the curves bound to the event, a curve that is already playing
+    // starts over. True when one started.
+    pub fn handle_event(&mut self, event: &dyn Event) -> bool {
+        if let (Some(target), Some(entity)) = (event.get_target(), self.entity) {
+            if target != entity {
+                return false;
+            }
+        }
+        let name = event.get_name();
+        let mut started = false;
+        for (curve, _) in self
+            .curves
+            .iter()
+            .enumerate()
+            .filter(|(_, curve)| curve.event == name)
+        {
+            self.playing.retain(|playing| playing.curve != curve);
+            self.playing.push(Playing { curve, time: 0.0 });
+            started = true;
+        }
+        self.dirty |= started;
+        started
+    }
+
+    pub fn update(&mut self, delta: Duration) {
+        if self.playing.is_empty() {
+            return;
+        }
+        let seconds = delta.as_secs_f32();
+        let curves = &self.curves;
+        for playing in self.playing.iter_mut() {
+            playing.time += seconds;
+            let curve = &curves[playing.curve];
+            let duration = curve.track.get_duration();
+            if curve.looping && duration > 0.0 {
+                playing.time %= duration;
+            }
+        }
+        // finished curves stop and their parameters return to the base value
+        self.playing.retain(|playing| {
+            let curve = &curves[playing.curve];
+            curve.looping || playing.time <= curve.track.get_duration()
+        });
+        self.dirty = true;
+    }
+
+    // The base value with the playing curves applied in the order they
+    // started
+    pub fn get_param(&self, name: &str) -> Option<Vec4> {
+        let mut value = self.base.get(name).copied();
+        for playing in self.playing.iter() {
+            let curve = &self.curves[playing.curve];
+            if curve.parameter != name {
+                continue;
+            }
+            let Some(sample) = curve.track.sample(playing.time) else {
+                continue;
+            };
+            let base = value.unwrap_or_default();
+            value = Some(match curve.blend {
+                ParamBlend::Replace => sample,
+                ParamBlend::Add => base + sample,
+                ParamBlend::Multiply => Vec4::new(
+                    base.x * sample.x,
+                    base.y * sample.y,
+                    base.z * sample.z,
+                    base.w * sample.w,
+                ),
+            });
+        }
+        value
+    }
+
+    // Every parameter with a base value, in name order
+    pub fn get_params(&self) -> BTreeMap<String, Vec4> {
+        self.base
+            .keys()
+            .filter_map(|name| Some((name.clone(), self.get_param(name)?)))
+            .collect()
+    }
+
+    // One vec4 per parameter in name order, the layout the material's
+    // uniform block declares
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.get_params()
+            .values()
+            .flat_map(|value| [value.x, value.y, value.z, value.w])
+            .flat_map(|value| value.to_le_bytes())
+            .collect()
+    }
+
+    // Writes the parameters when they changed since the last upload
+    pub fn upload(
+        &mut self,
+        api: &mut dyn RendererAPI,
+        buffer: Buffer,
+    ) -> Result<(), RendererErrors> {
+        if self.dirty {
+            api.write_buffer(buffer, 0, &self.to_bytes())?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{core::net::wire::WireEvent, event_system::event::TargetedEvent};
+
+    #[test]
+    fn test_events_play_curves() {
+        let desc: MaterialAnimationDesc = toml::from_str(
+            r#"
+            [[bindings]]
+            event = "DamageTaken"
+            parameter = "tint"
+            blend = "Multiply"
+            keys = [
+                { time = 0.0, value = { x = 1.0, y = 0.0, z = 0.0, w = 1.0 } },
+                { time = 0.5, value = { x = 1.0, y = 1.0, z = 1.0, w = 1.0 } },
+            ]
+
+            [[bindings]]
+            event = "PowerUp"
+            parameter = "emissive"
+            blend = "Add"
+            looping = true
+            keys = [
+                { time = 0.0, value = { x = 0.0, y = 0.0, z = 0.0, w = 0.0 } },
+                { time = 1.0, value = { x = 2.0, y = 0.0, z = 0.0, w = 0.0 } },
+            ]
+            "#,
+        )
+        .unwrap();
+        let mut material = MaterialAnimator::new(&desc).unwrap();
+        material.set_entity(Some(EntityId(4)));
+        material.set_param("tint", Vec4::splat(0.5));
+        material.set_param("emissive", Vec4::new(1.0, 0.0, 0.0, 0.0));
+
+        // damage to another entity leaves this material alone
+        let other = TargetedEvent::new(EntityId(9), WireEvent::new("DamageTaken", Vec::new()));
+        assert!(!material.handle_event(&other));
+        let hit = TargetedEvent::new(EntityId(4), WireEvent::new("DamageTaken", Vec::new()));
+        assert!(material.handle_event(&hit));
+        assert_eq!(
+            material.get_param("tint"),
+            Some(Vec4::new(0.5, 0.0, 0.0, 0.5))
+        );
+
+        material.update(Duration::from_millis(250));
+        assert_eq!(
+            material.get_param("tint"),
+            Some(Vec4::new(0.5, 0.25, 0.25, 0.5))
+        );
+        material.update(Duration::from_millis(500));
+        assert!(!material.is_playing());
+        assert_eq!(material.get_param("tint"), Some(Vec4::splat(0.5)));
+
+        material.handle_event(&WireEvent::new("PowerUp", Vec::new()));
+        material.update(Duration::from_millis(1500));
+        assert!(material.is_playing());
+        assert_eq!(material.get_param("emissive").unwrap().x, 2.0);
+
+        // emissive comes before tint in the uniform block
+        let bytes = material.to_bytes();
+        assert_eq!(bytes.len(), 32);
+        assert_eq!(f32::from_le_bytes(bytes[0..4].try_into().unwrap()), 2.0);
+        assert_eq!(f32::from_le_bytes(bytes[16..20].try_into().unwrap()), 0.5);
+    }
+}
@@ -0,0 +1,30 @@
+pub mod api;
+pub mod camera;
+pub mod capture;
+pub mod color_grading;
+pub mod cursor;
+pub mod decals;
+#[cfg(feature = "aloy_egui")]
+pub mod egui_layer;
+pub mod environment;
+pub mod forward;
+pub mod forward_plus;
+pub mod gpu_memory;
+pub mod gpu_particles;
+pub mod lighting2d;
+pub mod lod;
+pub mod material_animation;
+pub mod model;
+pub mod morph_targets;
+pub mod occlusion;
+pub mod particles;
+pub mod render_graph;
+pub mod render_stats;
+pub mod renderer2d;
+pub mod shader;
+pub mod skinning;
+pub mod sprite_slicing;
+pub mod text;
+pub mod texture;
+pub mod texture_atlas;
+pub mod vector;
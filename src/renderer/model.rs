@@ -0,0 +1,662 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use gltf::{image::Source as ImageSource, material::AlphaMode, mesh::Mode, Gltf};
+use log::{error, info, warn};
+use thiserror::Error;
+
+use crate::{
+    animation::import::read_buffers,
+    event_system::{
+        engine_events::renderer_events::{ModelLoadProgress, RendererEvents},
+        event_queue::EventQueue,
+    },
+    math::{
+        transform::{Mat4, Quat, Transform},
+        vector::{Vec2, Vec3, Vec4},
+    },
+};
+
+use super::api::{
+    Buffer, BufferDescriptor, BufferUsage, RendererAPI, RendererErrors, Resource, VertexFormat,
+    VertexLayout,
+};
+
+// Files at least this big report their progress while they load
+pub const LARGE_MODEL_SIZE: u64 = 4 * 1024 * 1024;
+
+// position, normal and uv
+const VERTEX_FLOATS: usize = 3 + 3 + 2;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ModelErrors {
+    #[error("unable to read model {0}: {1}")]
+    Io(String, String),
+
+    #[error("invalid glTF: {0}")]
+    Gltf(String),
+
+    #[error("{0}")]
+    Renderer(#[from] RendererErrors),
+}
+
+// The attributes every model vertex has, at locations 0, 1 and 2
+pub fn get_vertex_layout() -> VertexLayout {
+    VertexLayout::packed(&[
+        VertexFormat::Float32x3,
+        VertexFormat::Float32x3,
+        VertexFormat::Float32x2,
+    ])
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MaterialAlpha {
+    #[default]
+    Opaque,
+    // pixels under the cutoff are discarded
+    Mask(u8),
+    Blend,
+}
+
+// The metallic roughness material of glTF, textures are paths next to the
+// model for the TextureManager to load
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelMaterial {
+    pub name: String,
+    pub base_color: Vec4,
+    pub base_color_texture: Option<PathBuf>,
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: Vec3,
+    pub alpha: MaterialAlpha,
+    pub double_sided: bool,
+}
+
+impl Default for ModelMaterial {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            base_color: Vec4::splat(1.0),
+            base_color_texture: None,
+            metallic: 1.0,
+            roughness: 1.0,
+            emissive: Vec3::ZERO,
+            alpha: MaterialAlpha::Opaque,
+            double_sided: false,
+        }
+    }
+}
+
+// One draw of a mesh, triangles sharing a material
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PrimitiveData {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    pub uvs: Vec<Vec2>,
+    pub indices: Vec<u32>,
+    pub material: Option<usize>,
+}
+
+impl PrimitiveData {
+    // Interleaved in the layout of `get_vertex_layout`
+    pub fn to_vertex_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.positions.len() * VERTEX_FLOATS * 4);
+        for (index, position) in self.positions.iter().enumerate() {
+            let normal = self.normals.get(index).copied().unwrap_or(Vec3::Y);
+            let uv = self.uvs.get(index).copied().unwrap_or_default();
+            let floats = [
+                position.x, position.y, position.z, normal.x, normal.y, normal.z, uv.x, uv.y,
+            ];
+            for value in floats {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        bytes
+    }
+
+    pub fn to_index_bytes(&self) -> Vec<u8> {
+        self.indices
+            .iter()
+            .flat_map(|index| index.to_le_bytes())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MeshData {
+    pub name: String,
+    pub primitives: Vec<PrimitiveData>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelNode {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    // relative to the parent
+    pub transform: Transform,
+    pub mesh: Option<usize>,
+}
+
+// A whole glTF scene on the CPU. Importing does not touch the GPU so it can
+// run on a loading thread, `Model::upload` then creates the buffers.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ModelData {
+    pub meshes: Vec<MeshData>,
+    pub materials: Vec<ModelMaterial>,
+    pub nodes: Vec<ModelNode>,
+    // nodes without a parent
+    pub roots: Vec<usize>,
+}
+
+impl ModelData {
+    // The model space matrix of every node
+    pub fn get_world_matrices(&self) -> Vec<Mat4> {
+        let mut worlds = vec![Mat4::IDENTITY; self.nodes.len()];
+        let mut stack: Vec<(usize, Mat4)> = self
+            .roots
+            .iter()
+            .map(|root| (*root, Mat4::IDENTITY))
+            .collect();
+        while let Some((node, parent)) = stack.pop() {
+            let world = parent * self.nodes[node].transform.to_matrix();
+            worlds[node] = world;
+            stack.extend(
+                self.nodes[node]
+                    .children
+                    .iter()
+                    .map(|child| (*child, world)),
+            );
+        }
+        worlds
+    }
+}
+
+// Smooth normals from the triangles, for primitives that come without
+fn compute_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize);
+        if a.max(b).max(c) >= positions.len() {
+            continue;
+        }
+        // not normalized, bigger triangles weigh more
+        let normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+        for vertex in [a, b, c] {
+            normals[vertex] += normal;
+        }
+    }
+    normals.into_iter().map(Vec3::normalize).collect()
+}
+
+fn to_vec3([x, y, z]: [f32; 3]) -> Vec3 {
+    Vec3::new(x, y, z)
+}
+
+// Reads the default scene, or every node when the file has no scene.
+// `progress` gets the fraction of the meshes imported so far. `base` is
+// where external buffers and textures are looked up.
+pub fn import_model(
+    bytes: &[u8],
+    base: Option<&Path>,
+    progress: &mut dyn FnMut(f32),
+) -> Result<ModelData, ModelErrors> {
+    let gltf = Gltf::from_slice(bytes).map_err(|err| ModelErrors::Gltf(err.to_string()))?;
+    let buffers = read_buffers(&gltf, base).map_err(|err| ModelErrors::Gltf(err.to_string()))?;
+    let get_buffer = |buffer: gltf::Buffer| buffers.get(buffer.index()).map(Vec::as_slice);
+    progress(0.0);
+
+    let materials = gltf
+        .materials()
+        .map(|material| {
+            let pbr = material.pbr_metallic_roughness();
+            let base_color_texture =
+                pbr.base_color_texture()
+                    .and_then(|info| match info.texture().source().source() {
+                        ImageSource::Uri { uri, .. } if !uri.starts_with("data:") => {
+                            Some(base.unwrap_or(Path::new("")).join(uri))
+                        }
+                        _ => {
+                            warn!("only textures in files next to the model are supported");
+                            None
+                        }
+                    });
+            let alpha = match material.alpha_mode() {
+                AlphaMode::Opaque => MaterialAlpha::Opaque,
+                AlphaMode::Mask => {
+                    let cutoff = material.alpha_cutoff().unwrap_or(0.5);
+                    MaterialAlpha::Mask((cutoff.clamp(0.0, 1.0) * 255.0).round() as u8)
+                }
+                AlphaMode::Blend => MaterialAlpha::Blend,
+            };
+            let [r, g, b, a] = pbr.base_color_factor();
+            ModelMaterial {
+                name: material
+                    .name()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("material{}", material.index().unwrap_or(0))),
+                base_color: Vec4::new(r, g, b, a),
+                base_color_texture,
+                metallic: pbr.metallic_factor(),
+                roughness: pbr.roughness_factor(),
+                emissive: to_vec3(material.emissive_factor()),
+                alpha,
+                double_sided: material.double_sided(),
+            }
+        })
+        .collect();
+
+    let mesh_count = gltf.meshes().len().max(1);
+    let mut meshes = Vec::new();
+    for mesh in gltf.meshes() {
+        let mut data = MeshData {
+            name: mesh
+                .name()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("mesh{}", mesh.index())),
+            primitives: Vec::new(),
+        };
+        for primitive in mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                warn!(
+                    "skipping a {:?} primitive of {}",
+                    primitive.mode(),
+                    data.name
+                );
+                continue;
+            }
+            let reader = primitive.reader(get_buffer);
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+            let positions: Vec<Vec3> = positions.map(to_vec3).collect();
+            let indices: Vec<u32> = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            let normals = match reader.read_normals() {
+                Some(normals) => normals.map(to_vec3).collect(),
+                None => compute_normals(&positions, &indices),
+            };
+            let uvs = reader
+                .read_tex_coords(0)
+                .map(|uvs| uvs.into_f32().map(|[u, v]| Vec2::new(u, v)).collect())
+                .unwrap_or_default();
+            data.primitives.push(PrimitiveData {
+                positions,
+                normals,
+                uvs,
+                indices,
+                material: primitive.material().index(),
+            });
+        }
+        meshes.push(data);
+        progress(meshes.len() as f32 / mesh_count as f32);
+    }
+
+    let mut nodes: Vec<ModelNode> = gltf
+        .nodes()
+        .map(|node| {
+            let (translation, [x, y, z, w], scale) = node.transform().decomposed();
+            ModelNode {
+                name: node
+                    .name()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("node{}", node.index())),
+                parent: None,
+                children: node.children().map(|child| child.index()).collect(),
+                transform: Transform {
+                    translation: to_vec3(translation),
+                    rotation: Quat::new(x, y, z, w),
+                    scale: to_vec3(scale),
+                },
+                mesh: node.mesh().map(|mesh| mesh.index()),
+            }
+        })
+        .collect();
+    for index in 0..nodes.len() {
+        for child in nodes[index].children.clone() {
+            nodes[child].parent = Some(index);
+        }
+    }
+    let roots = match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+        Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+        None => (0..nodes.len())
+            .filter(|node| nodes[*node].parent.is_none())
+            .collect(),
+    };
+    progress(1.0);
+
+    Ok(ModelData {
+        meshes,
+        materials,
+        nodes,
+        roots,
+    })
+}
+
+pub fn load_model(path: &Path, progress: &mut dyn FnMut(f32)) -> Result<ModelData, ModelErrors> {
+    let bytes = fs::read(path)
+        .map_err(|err| ModelErrors::Io(path.display().to_string(), err.to_string()))?;
+    import_model(&bytes, path.parent(), progress)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelPrimitive {
+    pub vertex_buffer: Buffer,
+    pub index_buffer: Buffer,
+    pub index_count: u32,
+    pub material: Option<usize>,
+}
+
+// What to draw for one primitive of one node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelDraw {
+    pub node: usize,
+    // model space, multiply with the model's transform
+    pub world: Mat4,
+    pub primitive: ModelPrimitive,
+}
+
+// A model whose meshes live in GPU buffers, drawn with the vertex layout of
+// `get_vertex_layout` and its index buffers
+#[derive(Debug)]
+pub struct Model {
+    name: String,
+    meshes: Vec<Vec<ModelPrimitive>>,
+    materials: Vec<ModelMaterial>,
+    nodes: Vec<ModelNode>,
+    worlds: Vec<Mat4>,
+}
+
+impl Model {
+    pub fn upload(
+        name: &str,
+        data: &ModelData,
+        api: &mut dyn RendererAPI,
+    ) -> Result<Self, RendererErrors> {
+        let mut meshes = Vec::new();
+        for mesh in data.meshes.iter() {
+            let mut primitives = Vec::new();
+            for primitive in mesh.primitives.iter() {
+                let vertices = primitive.to_vertex_bytes();
+                let indices = primitive.to_index_bytes();
+                let vertex_buffer = api.create_buffer(
+                    &BufferDescriptor {
+                        label: format!("{} {} vertices", name, mesh.name),
+                        usage: BufferUsage::Vertex,
+                        size: vertices.len() as u64,
+                    },
+                    Some(&vertices),
+                )?;
+                let index_buffer = api.create_buffer(
+                    &BufferDescriptor {
+                        label: format!("{} {} indices", name, mesh.name),
+                        usage: BufferUsage::Index,
+                        size: indices.len() as u64,
+                    },
+                    Some(&indices),
+                )?;
+                primitives.push(ModelPrimitive {
+                    vertex_buffer,
+                    index_buffer,
+                    index_count: primitive.indices.len() as u32,
+                    material: primitive.material,
+                });
+            }
+            meshes.push(primitives);
+        }
+        Ok(Self {
+            name: name.to_string(),
+            meshes,
+            materials: data.materials.clone(),
+            nodes: data.nodes.clone(),
+            worlds: data.get_world_matrices(),
+        })
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_materials(&self) -> &[ModelMaterial] {
+        &self.materials
+    }
+
+    pub fn get_nodes(&self) -> &[ModelNode] {
+        &self.nodes
+    }
+
+    pub fn get_mesh(&self, index: usize) -> Option<&[ModelPrimitive]> {
+        self.meshes.get(index).map(Vec::as_slice)
+    }
+
+    // Every primitive of every node with a mesh, in node order
+    pub fn get_draws(&self) -> Vec<ModelDraw> {
+        let mut draws = Vec::new();
+        for (node, data) in self.nodes.iter().enumerate() {
+            let Some(mesh) = data.mesh.and_then(|mesh| self.meshes.get(mesh)) else {
+                continue;
+            };
+            draws.extend(mesh.iter().map(|primitive| ModelDraw {
+                node,
+                world: self.worlds[node],
+                primitive: *primitive,
+            }));
+        }
+        draws
+    }
+
+    pub fn destroy(&self, api: &mut dyn RendererAPI) {
+        for primitive in self.meshes.iter().flatten() {
+            api.destroy(Resource::Buffer(primitive.vertex_buffer));
+            api.destroy(Resource::Buffer(primitive.index_buffer));
+        }
+    }
+}
+
+// Loads every model file once and hands out shared references, like the
+// TextureManager. Loading a file of LARGE_MODEL_SIZE or more emits
+// ModelLoadProgress events on the queue when there is one.
+#[derive(Debug, Default)]
+pub struct ModelManager {
+    models: HashMap<PathBuf, Arc<Model>>,
+    event_queue: Option<Arc<EventQueue>>,
+}
+
+impl ModelManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_event_queue(event_queue: Arc<EventQueue>) -> Self {
+        Self {
+            models: HashMap::new(),
+            event_queue: Some(event_queue),
+        }
+    }
+
+    pub fn load(
+        &mut self,
+        path: impl AsRef<Path>,
+        api: &mut dyn RendererAPI,
+    ) -> Result<Arc<Model>, ModelErrors> {
+        let path = path.as_ref();
+        if let Some(model) = self.models.get(path) {
+            return Ok(Arc::clone(model));
+        }
+
+        let label = path.display().to_string();
+        let bytes =
+            fs::read(path).map_err(|err| ModelErrors::Io(label.clone(), err.to_string()))?;
+        let queue = self
+            .event_queue
+            .as_ref()
+            .filter(|_| bytes.len() as u64 >= LARGE_MODEL_SIZE);
+        let mut progress = |progress: f32| {
+            let Some(queue) = queue else {
+                return;
+            };
+            let event = RendererEvents::ModelLoadProgress(ModelLoadProgress {
+                path: label.clone(),
+                progress,
+            });
+            if let Err(err) = queue.emit(Box::new(event)) {
+                error!("unable to emit model load progress {:?}", err);
+            }
+        };
+        let data = import_model(&bytes, path.parent(), &mut progress)?;
+        let model = Arc::new(Model::upload(&label, &data, api)?);
+        info!(
+            "loaded model {} ({} meshes, {} nodes)",
+            label,
+            data.meshes.len(),
+            data.nodes.len()
+        );
+        self.models.insert(path.to_path_buf(), Arc::clone(&model));
+        Ok(model)
+    }
+
+    // References held outside the manager
+    pub fn get_ref_count(&self, path: impl AsRef<Path>) -> usize {
+        self.models
+            .get(path.as_ref())
+            .map_or(0, |model| Arc::strong_count(model) - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.models.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    // Destroys the models nobody holds, returns how many
+    pub fn collect(&mut self, api: &mut dyn RendererAPI) -> usize {
+        let unused: Vec<PathBuf> = self
+            .models
+            .iter()
+            .filter(|(_, model)| Arc::strong_count(model) == 1)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in unused.iter() {
+            if let Some(model) = self.models.remove(path) {
+                model.destroy(api);
+            }
+        }
+        unused.len()
+    }
+
+    // Destroys every model, references still held outside dangle
+    pub fn clear(&mut self, api: &mut dyn RendererAPI) {
+        for (_, model) in self.models.drain() {
+            model.destroy(api);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::api::headless::HeadlessRenderer;
+
+    // A binary glTF with one triangle without normals, a red material and
+    // the mesh on a child node
+    fn triangle_glb() -> Vec<u8> {
+        let mut bin = Vec::new();
+        for value in [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0] {
+            bin.extend(value.to_le_bytes());
+        }
+        for index in [0u16, 1, 2, 0] {
+            bin.extend(index.to_le_bytes());
+        }
+
+        let json = format!(
+            r#"{{
+            "asset": {{"version": "2.0"}},
+            "scene": 0,
+            "scenes": [{{"nodes": [0]}}],
+            "nodes": [
+                {{"name": "root", "translation": [0.0, 2.0, 0.0], "children": [1]}},
+                {{"name": "tri", "translation": [1.0, 0.0, 0.0], "mesh": 0}}
+            ],
+            "meshes": [{{"name": "tri", "primitives": [
+                {{"attributes": {{"POSITION": 0}}, "indices": 1, "material": 0}}
+            ]}}],
+            "materials": [{{"name": "red", "alphaMode": "MASK",
+                "pbrMetallicRoughness": {{"baseColorFactor": [1.0, 0.0, 0.0, 1.0]}}}}],
+            "buffers": [{{"byteLength": {}}}],
+            "bufferViews": [
+                {{"buffer": 0, "byteOffset": 0, "byteLength": 36}},
+                {{"buffer": 0, "byteOffset": 36, "byteLength": 6}}
+            ],
+            "accessors": [
+                {{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                  "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 0.0]}},
+                {{"bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR"}}
+            ]
+        }}"#,
+            bin.len()
+        );
+        let mut json = json.into_bytes();
+        json.resize(json.len().div_ceil(4) * 4, b' ');
+
+        let length = 12 + 8 + json.len() + 8 + bin.len();
+        let mut glb = Vec::new();
+        glb.extend(b"glTF");
+        glb.extend(2u32.to_le_bytes());
+        glb.extend((length as u32).to_le_bytes());
+        glb.extend((json.len() as u32).to_le_bytes());
+        glb.extend(b"JSON");
+        glb.extend(json);
+        glb.extend((bin.len() as u32).to_le_bytes());
+        glb.extend(b"BIN\0");
+        glb.extend(bin);
+        glb
+    }
+
+    #[test]
+    fn test_import_and_upload_gltf() {
+        let mut reports = Vec::new();
+        let data = import_model(&triangle_glb(), None, &mut |progress| {
+            reports.push(progress)
+        })
+        .unwrap();
+        assert_eq!(reports, vec![0.0, 1.0, 1.0]);
+
+        let primitive = &data.meshes[0].primitives[0];
+        assert_eq!(primitive.indices, vec![0, 1, 2]);
+        // computed from the winding
+        assert_eq!(primitive.normals, vec![Vec3::Z; 3]);
+        assert_eq!(primitive.material, Some(0));
+        let material = &data.materials[0];
+        assert_eq!(material.base_color, Vec4::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(material.alpha, MaterialAlpha::Mask(128));
+
+        assert_eq!(data.roots, vec![0]);
+        assert_eq!(data.nodes[1].parent, Some(0));
+        let worlds = data.get_world_matrices();
+        assert_eq!(
+            worlds[1].transform_point(Vec3::ZERO),
+            Vec3::new(1.0, 2.0, 0.0)
+        );
+
+        let mut api = HeadlessRenderer::new();
+        let model = Model::upload("tri", &data, &mut api).unwrap();
+        let draws = model.get_draws();
+        assert_eq!(draws.len(), 1);
+        assert_eq!(draws[0].node, 1);
+        assert_eq!(draws[0].primitive.index_count, 3);
+        let vertices = api
+            .get_buffer_contents(draws[0].primitive.vertex_buffer)
+            .unwrap();
+        assert_eq!(vertices.len(), 3 * VERTEX_FLOATS * 4);
+        model.destroy(&mut api);
+        assert_eq!(api.get_resource_count(), 0);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::{animation::morph::MorphMesh, math::vector::Vec3};
+
+// Faces use dozens of targets but only a handful are far from zero at once,
+// only those are blended per vertex
+pub const MAX_ACTIVE_MORPHS: usize = 8;
+
+// Deltas of every target, packed once when the mesh is loaded into a
+// storage buffer: two texels (position, normal) per vertex per target.
+// Only the few active weights change per frame.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MorphTargetBuffer {
+    pub vertex_count: usize,
+    pub target_count: usize,
+    pub texels: Vec<[f32; 4]>,
+}
+
+impl MorphTargetBuffer {
+    pub fn from_mesh(mesh: &MorphMesh) -> Self {
+        let vertex_count = mesh.positions.len();
+        let mut texels = Vec::with_capacity(mesh.targets.len() * vertex_count * 2);
+        for target in mesh.targets.iter() {
+            for vertex in 0..vertex_count {
+                let position = target.positions.get(vertex).copied().unwrap_or_default();
+                let normal = target.normals.get(vertex).copied().unwrap_or_default();
+                texels.push([position.x, position.y, position.z, 0.0]);
+                texels.push([normal.x, normal.y, normal.z, 0.0]);
+            }
+        }
+        Self {
+            vertex_count,
+            target_count: mesh.targets.len(),
+            texels,
+        }
+    }
+
+    pub fn get_size(&self) -> u64 {
+        std::mem::size_of_val(self.texels.as_slice()) as u64
+    }
+
+    fn get_deltas(&self, target: usize, vertex: usize) -> (Vec3, Vec3) {
+        let index = (target * self.vertex_count + vertex) * 2;
+        let [px, py, pz, _] = self.texels[index];
+        let [nx, ny, nz, _] = self.texels[index + 1];
+        (Vec3::new(px, py, pz), Vec3::new(nx, ny, nz))
+    }
+}
+
+// The per draw uniform. Compare with the last one uploaded to skip the
+// upload when a face is not moving.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ActiveMorphs {
+    pub count: u32,
+    pub targets: [u32; MAX_ACTIVE_MORPHS],
+    pub weights: [f32; MAX_ACTIVE_MORPHS],
+}
+
+impl ActiveMorphs {
+    // Keeps the strongest weights, the rest are too small to notice
+    pub fn from_weights(weights: &[f32]) -> Self {
+        let mut strongest: Vec<(usize, f32)> = weights
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, weight)| *weight != 0.0)
+            .collect();
+        strongest.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+        strongest.truncate(MAX_ACTIVE_MORPHS);
+
+        let mut active = Self::default();
+        for (slot, (target, weight)) in strongest.into_iter().enumerate() {
+            active.targets[slot] = target as u32;
+            active.weights[slot] = weight;
+            active.count += 1;
+        }
+        active
+    }
+}
+
+pub const MORPH_VERTEX_SHADER: &str = r#"#version 450
+
+layout(location = 0) in vec3 a_position;
+layout(location = 1) in vec3 a_normal;
+
+layout(std140, binding = 0) uniform Camera {
+    mat4 u_view_projection;
+};
+
+layout(std140, binding = 2) uniform Morphs {
+    mat4 u_model;
+    uint u_vertex_count;
+    uint u_morph_count;
+    uvec4 u_morph_targets[2];
+    vec4 u_morph_weights[2];
+};
+
+layout(std430, binding = 3) readonly buffer MorphDeltas {
+    vec4 deltas[];
+};
+
+layout(location = 0) out vec3 v_normal;
+
+void main() {
+    vec3 position = a_position;
+    vec3 normal = a_normal;
+    for (uint i = 0; i < u_morph_count; i++) {
+        uint target = u_morph_targets[i / 4][i % 4];
+        float weight = u_morph_weights[i / 4][i % 4];
+        uint texel = (target * u_vertex_count + gl_VertexIndex) * 2;
+        position += deltas[texel].xyz * weight;
+        normal += deltas[texel + 1].xyz * weight;
+    }
+    v_normal = normalize(mat3(u_model) * normal);
+    gl_Position = u_view_projection * u_model * vec4(position, 1.0);
+}
+"#;
+
+// Same math as the shader, for picking, bounds and tests
+pub fn morph_vertex(
+    buffer: &MorphTargetBuffer,
+    active: &ActiveMorphs,
+    vertex: usize,
+    position: Vec3,
+    normal: Vec3,
+) -> (Vec3, Vec3) {
+    let (mut position, mut normal) = (position, normal);
+    for slot in 0..active.count as usize {
+        let (position_delta, normal_delta) =
+            buffer.get_deltas(active.targets[slot] as usize, vertex);
+        position += position_delta * active.weights[slot];
+        normal += normal_delta * active.weights[slot];
+    }
+    (position, normal.normalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::animation::morph::MorphTarget;
+
+    use super::*;
+
+    #[test]
+    fn test_gpu_layout_matches_cpu_blend() {
+        let targets = (0..10)
+            .map(|index| MorphTarget {
+                name: format!("target{}", index),
+                positions: vec![Vec3::X * index as f32, Vec3::Y],
+                normals: Vec::new(),
+            })
+            .collect();
+        let mesh = MorphMesh {
+            name: "face".to_string(),
+            positions: vec![Vec3::ZERO, Vec3::Z],
+            normals: vec![Vec3::Z, Vec3::Z],
+            targets,
+            default_weights: vec![0.0; 10],
+        };
+        let buffer = MorphTargetBuffer::from_mesh(&mesh);
+        assert_eq!(buffer.get_size(), 10 * 2 * 2 * 16);
+
+        let mut weights = vec![0.0; 10];
+        weights[3] = 0.5;
+        weights[9] = -0.1;
+        let active = ActiveMorphs::from_weights(&weights);
+        assert_eq!(active.count, 2);
+        assert_eq!(&active.targets[..2], &[3, 9]);
+
+        let (blended, _) = mesh.blend(&weights);
+        for (vertex, expected) in blended.iter().enumerate() {
+            let (position, _) =
+                morph_vertex(&buffer, &active, vertex, mesh.positions[vertex], Vec3::Z);
+            assert!(position.distance(*expected) < 1e-5);
+        }
+        assert_eq!(ActiveMorphs::from_weights(&[1.0; 12]).count, 8);
+    }
+}
@@ -0,0 +1,325 @@
+use crate::math::vector::{Vec3, Vec4};
+
+use super::{forward_plus::ClusterView, render_stats::RenderStats};
+
+// A view space box, +z is forward like in the light clusters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ViewBox {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl ViewBox {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    fn corners(&self) -> [Vec3; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Vec3::new(min.x, min.y, min.z),
+            Vec3::new(max.x, min.y, min.z),
+            Vec3::new(min.x, max.y, min.z),
+            Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z),
+            Vec3::new(max.x, min.y, max.z),
+            Vec3::new(min.x, max.y, max.z),
+            Vec3::new(max.x, max.y, max.z),
+        ]
+    }
+}
+
+// Screen rectangle in buffer texels, max is exclusive
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ScreenRect {
+    min: (usize, usize),
+    max: (usize, usize),
+}
+
+impl ScreenRect {
+    fn is_empty(&self) -> bool {
+        self.min.0 >= self.max.0 || self.min.1 >= self.max.1
+    }
+}
+
+// Software HiZ culling. Large occluders (walls, terrain chunks, buildings)
+// are rasterized into a small depth buffer, which is reduced into a pyramid
+// keeping the farthest depth of every 2x2 block. An object is culled when
+// its nearest point is behind the farthest occluder depth over its whole
+// screen rectangle. Everything is conservative: when in doubt an object is
+// drawn.
+#[derive(Debug, Clone)]
+pub struct OcclusionCuller {
+    width: usize,
+    height: usize,
+    // level 0 is the full buffer, every level halves the size
+    pyramid: Vec<Vec<f32>>,
+    view: ClusterView,
+    occluders: usize,
+    culled: Vec<(usize, ViewBox)>,
+}
+
+impl OcclusionCuller {
+    // 256x128 is plenty, occluders only have to be roughly right
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width: width.max(1),
+            height: height.max(1),
+            pyramid: Vec::new(),
+            view: ClusterView {
+                fov_y: std::f32::consts::FRAC_PI_2,
+                aspect: width as f32 / height.max(1) as f32,
+                near: 0.1,
+                far: 1000.0,
+            },
+            occluders: 0,
+            culled: Vec::new(),
+        }
+    }
+
+    // Starts a frame, clears the depth buffer to the far plane
+    pub fn begin(&mut self, view: ClusterView) {
+        self.view = view;
+        self.pyramid = vec![vec![f32::INFINITY; self.width * self.height]];
+        self.occluders = 0;
+        self.culled.clear();
+    }
+
+    fn project(&self, point: Vec3) -> (f32, f32) {
+        let tan = (self.view.fov_y / 2.0).tan();
+        let ndc_x = point.x / (point.z * tan * self.view.aspect);
+        let ndc_y = point.y / (point.z * tan);
+        (
+            (ndc_x + 1.0) / 2.0 * self.width as f32,
+            (1.0 - ndc_y) / 2.0 * self.height as f32,
+        )
+    }
+
+    // Rectangle of the corners projected. `inner` keeps the texels that are
+    // fully inside, for occluders, otherwise every touched texel counts.
+    fn project_rect(&self, corners: &[Vec3], inner: bool) -> ScreenRect {
+        let (mut min_x, mut min_y) = (f32::INFINITY, f32::INFINITY);
+        let (mut max_x, mut max_y) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for corner in corners {
+            let (x, y) = self.project(*corner);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+        let clamp_x = |x: f32| x.clamp(0.0, self.width as f32) as usize;
+        let clamp_y = |y: f32| y.clamp(0.0, self.height as f32) as usize;
+        match inner {
+            true => ScreenRect {
+                min: (clamp_x(min_x.ceil()), clamp_y(min_y.ceil())),
+                max: (clamp_x(max_x.floor()), clamp_y(max_y.floor())),
+            },
+            false => ScreenRect {
+                min: (clamp_x(min_x.floor()), clamp_y(min_y.floor())),
+                max: (clamp_x(max_x.ceil()), clamp_y(max_y.ceil())),
+            },
+        }
+    }
+
+    // Only the front face of the box is drawn: rays through it hit the box
+    // at its nearest z, so that is the depth written
+    pub fn add_occluder(&mut self, occluder: ViewBox) {
+        let depth = occluder.min.z;
+        if depth <= self.view.near || self.pyramid.is_empty() {
+            return;
+        }
+        let front = [
+            Vec3::new(occluder.min.x, occluder.min.y, depth),
+            Vec3::new(occluder.max.x, occluder.max.y, depth),
+        ];
+        let rect = self.project_rect(&front, true);
+        if rect.is_empty() {
+            return;
+        }
+        let buffer = &mut self.pyramid[0];
+        for y in rect.min.1..rect.max.1 {
+            for texel in &mut buffer[y * self.width + rect.min.0..y * self.width + rect.max.0] {
+                *texel = texel.min(depth);
+            }
+        }
+        self.occluders += 1;
+    }
+
+    // Builds the pyramid, call it after the last occluder
+    pub fn finish_occluders(&mut self) {
+        self.pyramid.truncate(1);
+        if self.pyramid.is_empty() {
+            return;
+        }
+        let (mut width, mut height) = (self.width, self.height);
+        while width > 1 || height > 1 {
+            let (next_width, next_height) = (width.div_ceil(2), height.div_ceil(2));
+            let previous = &self.pyramid[self.pyramid.len() - 1];
+            let mut level = vec![0.0; next_width * next_height];
+            for y in 0..next_height {
+                for x in 0..next_width {
+                    let mut farthest: f32 = 0.0;
+                    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let (sx, sy) = ((x * 2 + dx).min(width - 1), (y * 2 + dy).min(height - 1));
+                        farthest = farthest.max(previous[sy * width + sx]);
+                    }
+                    level[y * next_width + x] = farthest;
+                }
+            }
+            self.pyramid.push(level);
+            (width, height) = (next_width, next_height);
+        }
+    }
+
+    pub fn is_visible(&self, bounds: &ViewBox) -> bool {
+        let nearest = bounds.min.z;
+        // crossing the near plane or no occluders this frame
+        if nearest <= self.view.near || self.occluders == 0 || self.pyramid.is_empty() {
+            return true;
+        }
+        let rect = self.project_rect(&bounds.corners(), false);
+        if rect.is_empty() {
+            // off screen, frustum culling deals with it
+            return true;
+        }
+
+        // the level where the rect spans about two texels
+        let span = (rect.max.0 - rect.min.0).max(rect.max.1 - rect.min.1);
+        let level = (span.max(1) as f32).log2().ceil().max(0.0) as usize;
+        let level = level.saturating_sub(1).min(self.pyramid.len() - 1);
+        let level_width = self.width.div_ceil(1 << level);
+        let depths = &self.pyramid[level];
+        for y in (rect.min.1 >> level)..=((rect.max.1 - 1) >> level) {
+            for x in (rect.min.0 >> level)..=((rect.max.0 - 1) >> level) {
+                if depths[y * level_width + x] >= nearest {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    // Tests every object, returns the visible ones and records the numbers
+    // into the frame stats. `id` is whatever the caller uses for its draws.
+    pub fn cull(&mut self, objects: &[(usize, ViewBox)], stats: &mut RenderStats) -> Vec<usize> {
+        let culled_before = self.culled.len();
+        let mut visible = Vec::with_capacity(objects.len());
+        for (id, bounds) in objects {
+            match self.is_visible(bounds) {
+                true => visible.push(*id),
+                false => self.culled.push((*id, *bounds)),
+            }
+        }
+        stats.record_occlusion(
+            objects.len() as u64,
+            (self.culled.len() - culled_before) as u64,
+        );
+        visible
+    }
+
+    pub fn get_culled(&self) -> impl Iterator<Item = usize> + '_ {
+        self.culled.iter().map(|(id, _)| *id)
+    }
+
+    pub fn get_occluder_count(&self) -> usize {
+        self.occluders
+    }
+
+    // The occlusion buffer as an image for the debug overlay: occluders in
+    // grey, brighter is closer, and culled objects outlined in red
+    pub fn get_debug_image(&self) -> Vec<Vec4> {
+        let Some(depths) = self.pyramid.first() else {
+            return Vec::new();
+        };
+        let mut image: Vec<Vec4> = depths
+            .iter()
+            .map(|depth| match depth.is_finite() {
+                true => {
+                    let shade = 1.0 - (depth / self.view.far).clamp(0.0, 1.0);
+                    Vec4::new(shade, shade, shade, 1.0)
+                }
+                false => Vec4::ZERO,
+            })
+            .collect();
+        let red = Vec4::new(1.0, 0.0, 0.0, 1.0);
+        for (_, bounds) in self.culled.iter() {
+            let rect = self.project_rect(&bounds.corners(), false);
+            if rect.is_empty() {
+                continue;
+            }
+            for x in rect.min.0..rect.max.0 {
+                image[rect.min.1 * self.width + x] = red;
+                image[(rect.max.1 - 1) * self.width + x] = red;
+            }
+            for y in rect.min.1..rect.max.1 {
+                image[y * self.width + rect.min.0] = red;
+                image[y * self.width + rect.max.0 - 1] = red;
+            }
+        }
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn culler() -> OcclusionCuller {
+        let mut culler = OcclusionCuller::new(64, 64);
+        culler.begin(ClusterView {
+            fov_y: std::f32::consts::FRAC_PI_2,
+            aspect: 1.0,
+            near: 0.1,
+            far: 100.0,
+        });
+        // a wall 10 units ahead covering the middle of the screen
+        culler.add_occluder(ViewBox::new(
+            Vec3::new(-5.0, -5.0, 10.0),
+            Vec3::new(5.0, 5.0, 11.0),
+        ));
+        culler.finish_occluders();
+        culler
+    }
+
+    #[test]
+    fn test_objects_behind_the_wall_are_culled() {
+        let culler = culler();
+        assert_eq!(culler.get_occluder_count(), 1);
+
+        let behind = ViewBox::new(Vec3::new(-1.0, -1.0, 20.0), Vec3::new(1.0, 1.0, 22.0));
+        let in_front = ViewBox::new(Vec3::new(-1.0, -1.0, 5.0), Vec3::new(1.0, 1.0, 6.0));
+        let beside = ViewBox::new(Vec3::new(12.0, -1.0, 20.0), Vec3::new(14.0, 1.0, 22.0));
+        // behind the wall but peeking out over its edge
+        let peeking = ViewBox::new(Vec3::new(-1.0, 8.0, 20.0), Vec3::new(1.0, 12.0, 22.0));
+
+        assert!(!culler.is_visible(&behind));
+        assert!(culler.is_visible(&in_front));
+        assert!(culler.is_visible(&beside));
+        assert!(culler.is_visible(&peeking));
+    }
+
+    #[test]
+    fn test_cull_records_stats_and_debug_image() {
+        let mut culler = culler();
+        let mut stats = RenderStats::new();
+        let objects = [
+            (
+                7,
+                ViewBox::new(Vec3::new(-1.0, -1.0, 20.0), Vec3::new(1.0, 1.0, 22.0)),
+            ),
+            (
+                8,
+                ViewBox::new(Vec3::new(-1.0, -1.0, 5.0), Vec3::new(1.0, 1.0, 6.0)),
+            ),
+        ];
+
+        assert_eq!(culler.cull(&objects, &mut stats), vec![8]);
+        assert_eq!(culler.get_culled().collect::<Vec<_>>(), vec![7]);
+        let frame = stats.get_current_frame();
+        assert_eq!((frame.occlusion_tested, frame.occlusion_culled), (2, 1));
+
+        let image = culler.get_debug_image();
+        assert_eq!(image.len(), 64 * 64);
+        assert!(image.contains(&Vec4::new(1.0, 0.0, 0.0, 1.0)));
+    }
+}
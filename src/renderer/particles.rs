@@ -0,0 +1,504 @@
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    core::{
+        data_file::{load_data_file, DataFileErrors},
+        runner::subsystem::{Subsystem, SubsystemContext},
+    },
+    math::{
+        random::Random,
+        vector::{Vec3, Vec4},
+    },
+};
+
+use super::gpu_particles::{GpuParticleFrame, GpuParticles};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ParticleErrors {
+    #[error("unable to load emitter file: {0}")]
+    File(#[from] DataFileErrors),
+}
+
+// Where an emitter simulates. The CPU handles a few thousand particles and
+// lets gameplay read them back, the GPU handles hundreds of thousands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ParticleBackend {
+    #[default]
+    Cpu,
+    Gpu,
+}
+
+// Volume new particles start in, relative to the emitter position. Cones
+// open around +Y, the other shapes shoot in every direction.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum EmitterShape {
+    #[default]
+    Point,
+    Sphere {
+        radius: f32,
+    },
+    Box {
+        half_extents: Vec3,
+    },
+    Cone {
+        angle: f32,
+        radius: f32,
+    },
+}
+
+// The emitter description, shared by both backends so an effect can move
+// between them by changing `backend` alone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EmitterDesc {
+    pub name: String,
+    pub backend: ParticleBackend,
+    pub max_particles: u32,
+    // particles per second
+    pub spawn_rate: f32,
+    // particles spawned at once when the emitter starts
+    pub burst: u32,
+    pub shape: EmitterShape,
+    pub lifetime_min: f32,
+    pub lifetime_max: f32,
+    pub speed_min: f32,
+    pub speed_max: f32,
+    pub gravity: Vec3,
+    pub drag: f32,
+    pub start_size: f32,
+    pub end_size: f32,
+    pub start_color: Vec4,
+    pub end_color: Vec4,
+    // back to front, needed for alpha blended particles
+    pub sort: bool,
+}
+
+impl Default for EmitterDesc {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            backend: ParticleBackend::Cpu,
+            max_particles: 1000,
+            spawn_rate: 100.0,
+            burst: 0,
+            shape: EmitterShape::Point,
+            lifetime_min: 1.0,
+            lifetime_max: 1.0,
+            speed_min: 1.0,
+            speed_max: 1.0,
+            gravity: Vec3::ZERO,
+            drag: 0.0,
+            start_size: 0.1,
+            end_size: 0.1,
+            start_color: Vec4::splat(1.0),
+            end_color: Vec4::new(1.0, 1.0, 1.0, 0.0),
+            sort: false,
+        }
+    }
+}
+
+impl EmitterDesc {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ParticleErrors> {
+        Ok(load_data_file(path)?)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Particle {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub age: f32,
+    pub lifetime: f32,
+}
+
+impl Particle {
+    pub fn is_alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+}
+
+// What the renderer draws for one particle, a camera facing quad
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ParticleInstance {
+    pub position: Vec3,
+    pub size: f32,
+    pub color: Vec4,
+}
+
+fn random_direction(random: &mut Random) -> Vec3 {
+    let z = random.range_f32(-1.0, 1.0);
+    let angle = random.range_f32(0.0, std::f32::consts::TAU);
+    let ring = (1.0 - z * z).max(0.0).sqrt();
+    Vec3::new(ring * angle.cos(), ring * angle.sin(), z)
+}
+
+// The spawn, simulate and instance functions below are what the compute
+// shaders in gpu_particles do, keep them in sync
+pub fn spawn_particle(desc: &EmitterDesc, origin: Vec3, random: &mut Random) -> Particle {
+    let (offset, direction) = match desc.shape {
+        EmitterShape::Point => (Vec3::ZERO, random_direction(random)),
+        EmitterShape::Sphere { radius } => {
+            let direction = random_direction(random);
+            (direction * (radius * random.next_f32().cbrt()), direction)
+        }
+        EmitterShape::Box { half_extents } => {
+            let offset = Vec3::new(
+                random.range_f32(-half_extents.x, half_extents.x),
+                random.range_f32(-half_extents.y, half_extents.y),
+                random.range_f32(-half_extents.z, half_extents.z),
+            );
+            (offset, random_direction(random))
+        }
+        EmitterShape::Cone { angle, radius } => {
+            let around = random.range_f32(0.0, std::f32::consts::TAU);
+            let tilt = angle * random.next_f32().sqrt();
+            let spread = random.next_f32().sqrt() * radius;
+            let offset = Vec3::new(around.cos() * spread, 0.0, around.sin() * spread);
+            let direction = Vec3::new(
+                tilt.sin() * around.cos(),
+                tilt.cos(),
+                tilt.sin() * around.sin(),
+            );
+            (offset, direction)
+        }
+    };
+    Particle {
+        position: origin + offset,
+        velocity: direction * random.range_f32(desc.speed_min, desc.speed_max),
+        age: 0.0,
+        lifetime: random.range_f32(desc.lifetime_min, desc.lifetime_max),
+    }
+}
+
+pub fn simulate(desc: &EmitterDesc, particle: &mut Particle, delta: f32) {
+    particle.velocity += desc.gravity * delta;
+    particle.velocity = particle.velocity * (1.0 - desc.drag * delta).max(0.0);
+    particle.position += particle.velocity * delta;
+    particle.age += delta;
+}
+
+pub fn get_instance(desc: &EmitterDesc, particle: &Particle) -> ParticleInstance {
+    let t = (particle.age / particle.lifetime.max(f32::EPSILON)).clamp(0.0, 1.0);
+    ParticleInstance {
+        position: particle.position,
+        size: desc.start_size + (desc.end_size - desc.start_size) * t,
+        color: desc.start_color.lerp(desc.end_color, t),
+    }
+}
+
+// Counts the particles due this frame, `accumulator` carries the fraction
+// over so low rates still spawn
+pub(crate) fn get_spawn_count(desc: &EmitterDesc, accumulator: &mut f32, delta: f32) -> u32 {
+    *accumulator += desc.spawn_rate.max(0.0) * delta;
+    let count = accumulator.floor();
+    *accumulator -= count;
+    count as u32
+}
+
+#[derive(Debug, Clone)]
+pub struct CpuParticles {
+    particles: Vec<Particle>,
+    accumulator: f32,
+    started: bool,
+    random: Random,
+}
+
+impl CpuParticles {
+    pub fn new(random: Random) -> Self {
+        Self {
+            particles: Vec::new(),
+            accumulator: 0.0,
+            started: false,
+            random,
+        }
+    }
+
+    pub fn update(&mut self, desc: &EmitterDesc, origin: Vec3, delta: Duration) {
+        let delta = delta.as_secs_f32();
+        for particle in self.particles.iter_mut() {
+            simulate(desc, particle, delta);
+        }
+        self.particles.retain(Particle::is_alive);
+
+        let mut count = get_spawn_count(desc, &mut self.accumulator, delta);
+        if !self.started {
+            self.started = true;
+            count += desc.burst;
+        }
+        let free = (desc.max_particles as usize).saturating_sub(self.particles.len());
+        for _ in 0..free.min(count as usize) {
+            let particle = spawn_particle(desc, origin, &mut self.random);
+            self.particles.push(particle);
+        }
+    }
+
+    pub fn get_particles(&self) -> &[Particle] {
+        &self.particles
+    }
+
+    // Back to front from `camera` when the emitter asks for sorting
+    pub fn get_instances(&self, desc: &EmitterDesc, camera: Vec3) -> Vec<ParticleInstance> {
+        let mut instances: Vec<ParticleInstance> = self
+            .particles
+            .iter()
+            .map(|particle| get_instance(desc, particle))
+            .collect();
+        if desc.sort {
+            instances.sort_by(|a, b| {
+                let a = a.position.distance(camera);
+                let b = b.position.distance(camera);
+                b.total_cmp(&a)
+            });
+        }
+        instances
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EmitterId(u64);
+
+#[derive(Debug, Clone)]
+enum Simulation {
+    Cpu(CpuParticles),
+    Gpu(GpuParticles),
+}
+
+#[derive(Debug, Clone)]
+struct Emitter {
+    desc: EmitterDesc,
+    position: Vec3,
+    simulation: Simulation,
+    frame: Option<GpuParticleFrame>,
+}
+
+impl Emitter {
+    fn start(desc: &EmitterDesc, mut random: Random) -> Simulation {
+        match desc.backend {
+            ParticleBackend::Cpu => Simulation::Cpu(CpuParticles::new(random)),
+            ParticleBackend::Gpu => {
+                Simulation::Gpu(GpuParticles::new(desc.max_particles, random.next_u64()))
+            }
+        }
+    }
+}
+
+// Every emitter of the game, each on the backend its description asks for.
+// CPU emitters hand over instances to draw, GPU emitters the compute passes
+// to dispatch this frame.
+#[derive(Debug)]
+pub struct ParticleEmitters {
+    emitters: BTreeMap<EmitterId, Emitter>,
+    next_id: u64,
+    camera: Vec3,
+    random: Random,
+}
+
+impl Default for ParticleEmitters {
+    fn default() -> Self {
+        Self::new(Random::from_entropy())
+    }
+}
+
+impl ParticleEmitters {
+    pub fn new(random: Random) -> Self {
+        Self {
+            emitters: BTreeMap::new(),
+            next_id: 0,
+            camera: Vec3::ZERO,
+            random,
+        }
+    }
+
+    fn get_random(&mut self) -> Random {
+        Random::new(self.random.next_u64())
+    }
+
+    pub fn add(&mut self, desc: EmitterDesc, position: Vec3) -> EmitterId {
+        let id = EmitterId(self.next_id);
+        self.next_id += 1;
+        let simulation = Emitter::start(&desc, self.get_random());
+        self.emitters.insert(
+            id,
+            Emitter {
+                desc,
+                position,
+                simulation,
+                frame: None,
+            },
+        );
+        id
+    }
+
+    pub fn remove(&mut self, id: EmitterId) -> Option<EmitterDesc> {
+        self.emitters.remove(&id).map(|emitter| emitter.desc)
+    }
+
+    pub fn get_desc(&self, id: EmitterId) -> Option<&EmitterDesc> {
+        self.emitters.get(&id).map(|emitter| &emitter.desc)
+    }
+
+    pub fn set_position(&mut self, id: EmitterId, position: Vec3) {
+        if let Some(emitter) = self.emitters.get_mut(&id) {
+            emitter.position = position;
+        }
+    }
+
+    // Live particles do not move between backends, the emitter starts over
+    pub fn set_backend(&mut self, id: EmitterId, backend: ParticleBackend) {
+        let random = self.get_random();
+        if let Some(emitter) = self.emitters.get_mut(&id) {
+            if emitter.desc.backend == backend {
+                return;
+            }
+            emitter.desc.backend = backend;
+            emitter.simulation = Emitter::start(&emitter.desc, random);
+            emitter.frame = None;
+        }
+    }
+
+    pub fn get_backend(&self, id: EmitterId) -> Option<ParticleBackend> {
+        self.get_desc(id).map(|desc| desc.backend)
+    }
+
+    // Sorting is back to front from here
+    pub fn set_camera_position(&mut self, camera: Vec3) {
+        self.camera = camera;
+    }
+
+    pub fn update(&mut self, delta: Duration) {
+        for emitter in self.emitters.values_mut() {
+            match &mut emitter.simulation {
+                Simulation::Cpu(particles) => {
+                    particles.update(&emitter.desc, emitter.position, delta)
+                }
+                Simulation::Gpu(particles) => {
+                    emitter.frame =
+                        Some(particles.update(&emitter.desc, emitter.position, self.camera, delta))
+                }
+            }
+        }
+    }
+
+    // None for unknown and GPU emitters
+    pub fn get_cpu_instances(&self, id: EmitterId) -> Option<Vec<ParticleInstance>> {
+        let emitter = self.emitters.get(&id)?;
+        match &emitter.simulation {
+            Simulation::Cpu(particles) => Some(particles.get_instances(&emitter.desc, self.camera)),
+            Simulation::Gpu(_) => None,
+        }
+    }
+
+    // The work recorded by the last update, None for CPU emitters
+    pub fn get_gpu_frame(&self, id: EmitterId) -> Option<&GpuParticleFrame> {
+        self.emitters.get(&id)?.frame.as_ref()
+    }
+}
+
+impl Subsystem for ParticleEmitters {
+    fn get_name(&self) -> &str {
+        "ParticleEmitters"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        self.update(ctx.time.get_delta());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn desc() -> EmitterDesc {
+        EmitterDesc {
+            name: "sparks".to_string(),
+            max_particles: 50,
+            spawn_rate: 100.0,
+            burst: 10,
+            lifetime_min: 0.5,
+            lifetime_max: 0.5,
+            sort: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_desc_from_ron() {
+        let path = std::env::temp_dir().join("aloy_test_emitter.ron");
+        fs::write(
+            &path,
+            r#"(
+                name: "smoke",
+                backend: Gpu,
+                max_particles: 200000,
+                shape: Cone(angle: 0.3, radius: 0.5),
+                gravity: (x: 0.0, y: -9.8, z: 0.0),
+            )"#,
+        )
+        .unwrap();
+        let desc = EmitterDesc::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(desc.backend, ParticleBackend::Gpu);
+        assert_eq!(desc.max_particles, 200000);
+        assert_eq!(
+            desc.shape,
+            EmitterShape::Cone {
+                angle: 0.3,
+                radius: 0.5
+            }
+        );
+        assert_eq!(desc.gravity, Vec3::new(0.0, -9.8, 0.0));
+        assert_eq!(desc.spawn_rate, EmitterDesc::default().spawn_rate);
+    }
+
+    #[test]
+    fn test_cpu_spawn_expire_and_sort() {
+        let desc = desc();
+        let mut particles = CpuParticles::new(Random::new(3));
+        particles.update(&desc, Vec3::ZERO, Duration::from_millis(100));
+        // burst plus a tenth of a second at 100 per second
+        assert_eq!(particles.get_particles().len(), 20);
+
+        particles.update(&desc, Vec3::ZERO, Duration::from_millis(300));
+        assert_eq!(particles.get_particles().len(), 50);
+
+        // everything alive now is older than its lifetime after this
+        particles.update(&desc, Vec3::ZERO, Duration::from_millis(600));
+        assert!(particles
+            .get_particles()
+            .iter()
+            .all(|particle| particle.age == 0.0));
+
+        let camera = Vec3::new(0.0, 0.0, 10.0);
+        particles.update(&desc, Vec3::ZERO, Duration::from_millis(100));
+        let instances = particles.get_instances(&desc, camera);
+        for pair in instances.windows(2) {
+            assert!(pair[0].position.distance(camera) >= pair[1].position.distance(camera));
+        }
+    }
+
+    #[test]
+    fn test_switch_backend() {
+        let mut emitters = ParticleEmitters::new(Random::new(9));
+        let id = emitters.add(desc(), Vec3::ZERO);
+        emitters.update(Duration::from_millis(100));
+        assert_eq!(emitters.get_cpu_instances(id).map(|i| i.len()), Some(20));
+        assert!(emitters.get_gpu_frame(id).is_none());
+
+        emitters.set_backend(id, ParticleBackend::Gpu);
+        assert_eq!(emitters.get_backend(id), Some(ParticleBackend::Gpu));
+        assert!(emitters.get_cpu_instances(id).is_none());
+        emitters.update(Duration::from_millis(100));
+        let frame = emitters.get_gpu_frame(id).unwrap();
+        assert_eq!(frame.uniforms.spawn[1], 20);
+    }
+}
@@ -0,0 +1,608 @@
+use std::collections::{HashMap, HashSet};
+
+use log::debug;
+use thiserror::Error;
+
+use crate::math::vector::Vec4;
+
+use super::api::{
+    RenderPass, RenderTarget, RendererAPI, RendererErrors, Resource, Texture, TextureDescriptor,
+    TextureFormat,
+};
+
+// The window's image of the frame, imported into every graph
+pub const SURFACE: &str = "surface";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum RenderGraphErrors {
+    #[error("pass {0} is added twice")]
+    DuplicatePass(String),
+
+    #[error("texture {0} is declared twice")]
+    DuplicateTexture(String),
+
+    #[error("pass {pass} uses {texture} which is not declared")]
+    UnknownTexture { pass: String, texture: String },
+
+    #[error("pass {pass} reads {texture} while writing it")]
+    ReadWriteConflict { pass: String, texture: String },
+
+    #[error("passes {0:?} depend on each other")]
+    Cycle(Vec<String>),
+
+    #[error(transparent)]
+    Renderer(#[from] RendererErrors),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TransientSize {
+    // a fraction of the surface, e.g. 0.5 for half resolution bloom
+    Surface(f32),
+    Fixed(u32, u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GraphTexture {
+    Transient(TransientSize, TextureFormat),
+    Imported(RenderTarget),
+}
+
+// What a pass renders into and what it samples. The target is cleared when
+// a clear color is set, otherwise the pass draws over what the passes
+// before it left.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphPassDescriptor {
+    pub name: String,
+    pub target: String,
+    pub depth: Option<String>,
+    pub reads: Vec<String>,
+    pub clear_color: Option<Vec4>,
+}
+
+impl GraphPassDescriptor {
+    pub fn new(name: &str, target: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            target: target.to_string(),
+            depth: None,
+            reads: Vec::new(),
+            clear_color: None,
+        }
+    }
+
+    pub fn with_read(mut self, texture: &str) -> Self {
+        self.reads.push(texture.to_string());
+        self
+    }
+
+    pub fn with_depth(mut self, texture: &str) -> Self {
+        self.depth = Some(texture.to_string());
+        self
+    }
+
+    pub fn with_clear(mut self, color: Vec4) -> Self {
+        self.clear_color = Some(color);
+        self
+    }
+
+    fn get_writes(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.target).chain(self.depth.iter())
+    }
+}
+
+// Handed to a pass while it records
+pub struct GraphContext<'g> {
+    pub api: &'g mut dyn RendererAPI,
+    pub pass: &'g mut RenderPass,
+    textures: &'g HashMap<String, Texture>,
+}
+
+impl GraphContext<'_> {
+    // The texture behind a declared name, None for the surface
+    pub fn get_texture(&self, name: &str) -> Option<Texture> {
+        self.textures.get(name).copied()
+    }
+}
+
+type RecordFn<'a> = Box<dyn FnOnce(&mut GraphContext) -> Result<(), RendererErrors> + 'a>;
+
+struct GraphPass<'a> {
+    descriptor: GraphPassDescriptor,
+    record: RecordFn<'a>,
+}
+
+// Transient textures kept between frames so a graph built every frame does
+// not create its textures every frame
+#[derive(Debug, Default)]
+pub struct RenderGraphCache {
+    free: Vec<((u32, u32, TextureFormat), Texture)>,
+}
+
+impl RenderGraphCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.free.is_empty()
+    }
+
+    pub fn destroy(&mut self, api: &mut dyn RendererAPI) {
+        for (_, texture) in self.free.drain(..) {
+            api.destroy(Resource::Texture(texture));
+        }
+    }
+}
+
+// The passes of one frame with the textures they pass to each other. Passes
+// are declared in any order, the graph runs writers before readers (and
+// several writers of a texture in declaration order), leaves out passes
+// whose output nobody uses and holds transient textures only from their
+// first to their last use, so textures whose uses never overlap share one.
+//
+//     let mut graph = RenderGraph::new();
+//     graph.create_texture("hdr", TransientSize::Surface(1.0), TextureFormat::Rgba16Float)?;
+//     graph.add_pass(GraphPassDescriptor::new("tonemap", SURFACE).with_read("hdr"), |ctx| {
+//         tonemap.record(ctx.api, ctx.get_texture("hdr").unwrap(), ctx.pass)
+//     })?;
+//     graph.add_pass(GraphPassDescriptor::new("scene", "hdr").with_clear(black), |ctx| {
+//         renderer.end_scene(ctx.api, ctx.pass)
+//     })?;
+//     graph.execute(api, &mut cache)?;
+pub struct RenderGraph<'a> {
+    textures: HashMap<String, GraphTexture>,
+    passes: Vec<GraphPass<'a>>,
+}
+
+impl<'a> RenderGraph<'a> {
+    pub fn new() -> Self {
+        let mut textures = HashMap::new();
+        textures.insert(
+            SURFACE.to_string(),
+            GraphTexture::Imported(RenderTarget::Surface),
+        );
+        Self {
+            textures,
+            passes: Vec::new(),
+        }
+    }
+
+    fn declare(&mut self, name: &str, texture: GraphTexture) -> Result<(), RenderGraphErrors> {
+        if self.textures.contains_key(name) {
+            return Err(RenderGraphErrors::DuplicateTexture(name.to_string()));
+        }
+        self.textures.insert(name.to_string(), texture);
+        Ok(())
+    }
+
+    // Allocated by the graph for the passes that use it
+    pub fn create_texture(
+        &mut self,
+        name: &str,
+        size: TransientSize,
+        format: TextureFormat,
+    ) -> Result<(), RenderGraphErrors> {
+        self.declare(name, GraphTexture::Transient(size, format))
+    }
+
+    // A texture owned elsewhere, e.g. a shadow map kept between frames.
+    // Passes writing it always run.
+    pub fn import_texture(
+        &mut self,
+        name: &str,
+        texture: Texture,
+    ) -> Result<(), RenderGraphErrors> {
+        self.declare(name, GraphTexture::Imported(RenderTarget::Texture(texture)))
+    }
+
+    pub fn add_pass(
+        &mut self,
+        descriptor: GraphPassDescriptor,
+        record: impl FnOnce(&mut GraphContext) -> Result<(), RendererErrors> + 'a,
+    ) -> Result<(), RenderGraphErrors> {
+        if self
+            .passes
+            .iter()
+            .any(|pass| pass.descriptor.name == descriptor.name)
+        {
+            return Err(RenderGraphErrors::DuplicatePass(descriptor.name));
+        }
+        for texture in descriptor.reads.iter().chain(descriptor.get_writes()) {
+            if !self.textures.contains_key(texture) {
+                return Err(RenderGraphErrors::UnknownTexture {
+                    pass: descriptor.name.clone(),
+                    texture: texture.clone(),
+                });
+            }
+        }
+        if let Some(texture) = descriptor
+            .reads
+            .iter()
+            .find(|read| descriptor.get_writes().any(|write| write == *read))
+        {
+            return Err(RenderGraphErrors::ReadWriteConflict {
+                pass: descriptor.name.clone(),
+                texture: texture.clone(),
+            });
+        }
+        self.passes.push(GraphPass {
+            descriptor,
+            record: Box::new(record),
+        });
+        Ok(())
+    }
+
+    // For every pass, the passes that have to run before it. A reader sees
+    // what the writers declared before it left, or every writer when it is
+    // declared ahead of all of them; a writer comes after the earlier
+    // writers and after the earlier readers that saw them.
+    fn get_dependencies(&self) -> Vec<Vec<usize>> {
+        let mut writers: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (index, pass) in self.passes.iter().enumerate() {
+            for write in pass.descriptor.get_writes() {
+                writers.entry(write.as_str()).or_default().push(index);
+            }
+        }
+
+        let mut dependencies = vec![Vec::new(); self.passes.len()];
+        let mut readers: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (index, pass) in self.passes.iter().enumerate() {
+            for read in pass.descriptor.reads.iter() {
+                let all = writers.get(read.as_str()).map(Vec::as_slice).unwrap_or(&[]);
+                let earlier: Vec<usize> = all.iter().copied().filter(|&w| w < index).collect();
+                if earlier.is_empty() {
+                    dependencies[index].extend(all);
+                } else {
+                    dependencies[index].extend(earlier);
+                    readers.entry(read.as_str()).or_default().push(index);
+                }
+            }
+            for write in pass.descriptor.get_writes() {
+                dependencies[index].extend(
+                    writers[write.as_str()]
+                        .iter()
+                        .take_while(|&&writer| writer != index),
+                );
+                dependencies[index].extend(readers.get(write.as_str()).into_iter().flatten());
+            }
+        }
+        dependencies
+    }
+
+    // The passes to run in order, without the ones nothing uses
+    fn schedule(&self) -> Result<Vec<usize>, RenderGraphErrors> {
+        let dependencies = self.get_dependencies();
+
+        // passes writing something outside the graph are the roots
+        let mut needed = HashSet::new();
+        let mut stack: Vec<usize> = (0..self.passes.len())
+            .filter(|&index| {
+                self.passes[index]
+                    .descriptor
+                    .get_writes()
+                    .any(|write| matches!(self.textures[write], GraphTexture::Imported(_)))
+            })
+            .collect();
+        while let Some(index) = stack.pop() {
+            if needed.insert(index) {
+                stack.extend(dependencies[index].iter());
+            }
+        }
+
+        // ready passes run in declaration order
+        let mut order = Vec::new();
+        let mut done = HashSet::new();
+        while order.len() < needed.len() {
+            let next = (0..self.passes.len()).find(|index| {
+                needed.contains(index)
+                    && !done.contains(index)
+                    && dependencies[*index].iter().all(|dep| done.contains(dep))
+            });
+            let Some(next) = next else {
+                let mut stuck: Vec<String> = needed
+                    .iter()
+                    .filter(|index| !done.contains(*index))
+                    .map(|&index| self.passes[index].descriptor.name.clone())
+                    .collect();
+                stuck.sort();
+                return Err(RenderGraphErrors::Cycle(stuck));
+            };
+            done.insert(next);
+            order.push(next);
+        }
+        Ok(order)
+    }
+
+    // The names of the passes in the order `execute` would run them
+    pub fn get_order(&self) -> Result<Vec<String>, RenderGraphErrors> {
+        Ok(self
+            .schedule()?
+            .into_iter()
+            .map(|index| self.passes[index].descriptor.name.clone())
+            .collect())
+    }
+
+    // Records the passes and submits them, returns the names of the passes
+    // that ran
+    pub fn execute(
+        self,
+        api: &mut dyn RendererAPI,
+        cache: &mut RenderGraphCache,
+    ) -> Result<Vec<String>, RenderGraphErrors> {
+        let order = self.schedule()?;
+
+        // the last pass using each transient texture
+        let mut last_use = HashMap::new();
+        for (position, &index) in order.iter().enumerate() {
+            let descriptor = &self.passes[index].descriptor;
+            for texture in descriptor.reads.iter().chain(descriptor.get_writes()) {
+                last_use.insert(texture.clone(), position);
+            }
+        }
+
+        let (surface_width, surface_height) = api.get_surface_size();
+        let mut available = std::mem::take(&mut cache.free);
+        let mut used = Vec::new();
+        let mut touched = HashSet::new();
+        let mut live: HashMap<String, Texture> = HashMap::new();
+        let mut passes: Vec<Option<GraphPass>> = self.passes.into_iter().map(Some).collect();
+        let mut recorded = Vec::new();
+        let mut ran = Vec::new();
+
+        for (position, &index) in order.iter().enumerate() {
+            let Some(graph_pass) = passes[index].take() else {
+                continue;
+            };
+            let descriptor = graph_pass.descriptor;
+
+            for name in descriptor.reads.iter().chain(descriptor.get_writes()) {
+                match self.textures[name] {
+                    GraphTexture::Imported(RenderTarget::Texture(texture)) => {
+                        live.insert(name.clone(), texture);
+                    }
+                    GraphTexture::Transient(size, format) if !live.contains_key(name) => {
+                        let (width, height) = match size {
+                            TransientSize::Surface(scale) => (
+                                ((surface_width as f32 * scale) as u32).max(1),
+                                ((surface_height as f32 * scale) as u32).max(1),
+                            ),
+                            TransientSize::Fixed(width, height) => (width, height),
+                        };
+                        let key = (width, height, format);
+                        let texture = match available.iter().position(|(k, _)| *k == key) {
+                            Some(found) => available.swap_remove(found).1,
+                            None => {
+                                debug!("render graph allocates {} {}x{}", name, width, height);
+                                api.create_texture(&TextureDescriptor {
+                                    label: name.clone(),
+                                    width,
+                                    height,
+                                    format,
+                                    render_target: true,
+                                    mip_levels: 1,
+                                })?
+                            }
+                        };
+                        touched.insert(texture);
+                        used.push((key, texture));
+                        live.insert(name.clone(), texture);
+                    }
+                    _ => {}
+                }
+            }
+
+            let target = match self.textures[&descriptor.target] {
+                GraphTexture::Imported(target) => target,
+                GraphTexture::Transient(..) => RenderTarget::Texture(live[&descriptor.target]),
+            };
+            let mut pass = RenderPass::new(&descriptor.name, target);
+            pass.clear_color = descriptor.clear_color;
+            pass.depth = descriptor
+                .depth
+                .as_ref()
+                .and_then(|depth| live.get(depth).copied());
+            (graph_pass.record)(&mut GraphContext {
+                api: &mut *api,
+                pass: &mut pass,
+                textures: &live,
+            })?;
+            recorded.push(pass);
+
+            // textures nobody needs anymore go back for later passes
+            for name in descriptor.reads.iter().chain(descriptor.get_writes()) {
+                if last_use.get(name) != Some(&position) {
+                    continue;
+                }
+                if let (Some(texture), GraphTexture::Transient(..)) =
+                    (live.remove(name), self.textures[name])
+                {
+                    if let Some(found) = used.iter().position(|(_, t)| *t == texture) {
+                        available.push(used.swap_remove(found));
+                    }
+                }
+            }
+            ran.push(descriptor.name);
+        }
+
+        api.submit(&recorded)?;
+
+        // textures of older frames that this one did not need are freed
+        for (key, texture) in available.into_iter().chain(used) {
+            if touched.contains(&texture) {
+                cache.free.push((key, texture));
+            } else {
+                api.destroy(Resource::Texture(texture));
+            }
+        }
+        Ok(ran)
+    }
+}
+
+impl Default for RenderGraph<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::api::headless::HeadlessRenderer;
+
+    #[test]
+    fn test_orders_culls_and_aliases() {
+        let mut api = HeadlessRenderer::new();
+        let mut cache = RenderGraphCache::new();
+        let half = TransientSize::Fixed(4, 4);
+        let sampled = std::cell::RefCell::new(Vec::new());
+
+        let mut graph = RenderGraph::new();
+        graph
+            .create_texture(
+                "hdr",
+                TransientSize::Fixed(8, 8),
+                TextureFormat::Rgba16Float,
+            )
+            .unwrap();
+        graph
+            .create_texture("bright", half, TextureFormat::Rgba16Float)
+            .unwrap();
+        graph
+            .create_texture("blur", half, TextureFormat::Rgba16Float)
+            .unwrap();
+        graph
+            .create_texture("debug", half, TextureFormat::Rgba8Unorm)
+            .unwrap();
+        // declared back to front, readers first
+        graph
+            .add_pass(
+                GraphPassDescriptor::new("tonemap", SURFACE)
+                    .with_read("hdr")
+                    .with_read("blur"),
+                |ctx| {
+                    sampled.borrow_mut().push(ctx.get_texture("blur").unwrap());
+                    Ok(())
+                },
+            )
+            .unwrap();
+        graph
+            .add_pass(
+                GraphPassDescriptor::new("blur", "blur").with_read("bright"),
+                |ctx| {
+                    sampled
+                        .borrow_mut()
+                        .push(ctx.get_texture("bright").unwrap());
+                    Ok(())
+                },
+            )
+            .unwrap();
+        graph
+            .add_pass(
+                GraphPassDescriptor::new("bright", "bright").with_read("hdr"),
+                |_| Ok(()),
+            )
+            .unwrap();
+        graph
+            .add_pass(
+                GraphPassDescriptor::new("scene", "hdr").with_clear(Vec4::splat(0.0)),
+                |_| Ok(()),
+            )
+            .unwrap();
+        // nothing reads it so it never runs
+        graph
+            .add_pass(GraphPassDescriptor::new("debug", "debug"), |_| Ok(()))
+            .unwrap();
+        assert_eq!(
+            graph.add_pass(
+                GraphPassDescriptor::new("loop", "hdr").with_read("hdr"),
+                |_| Ok(())
+            ),
+            Err(RenderGraphErrors::ReadWriteConflict {
+                pass: "loop".to_string(),
+                texture: "hdr".to_string()
+            })
+        );
+
+        let ran = graph.execute(&mut api, &mut cache).unwrap();
+        assert_eq!(ran, vec!["scene", "bright", "blur", "tonemap"]);
+        assert_eq!(api.get_submitted().len(), 4);
+        // bright and blur overlap in the blur pass so they do not share
+        let sampled = sampled.into_inner();
+        assert_ne!(sampled[0], sampled[1]);
+        assert_eq!(cache.len(), 3);
+        let textures = api.get_resource_count();
+
+        // the next frame reuses the cached textures
+        let mut graph = RenderGraph::new();
+        graph
+            .create_texture("a", half, TextureFormat::Rgba16Float)
+            .unwrap();
+        graph
+            .create_texture("b", half, TextureFormat::Rgba16Float)
+            .unwrap();
+        graph
+            .add_pass(GraphPassDescriptor::new("a", "a"), |_| Ok(()))
+            .unwrap();
+        graph
+            .add_pass(
+                GraphPassDescriptor::new("b", "b").with_read("a"),
+                |_| Ok(()),
+            )
+            .unwrap();
+        graph
+            .add_pass(
+                GraphPassDescriptor::new("c", "a").with_read("b"),
+                |_| Ok(()),
+            )
+            .unwrap();
+        graph
+            .add_pass(
+                GraphPassDescriptor::new("d", SURFACE).with_read("a"),
+                |_| Ok(()),
+            )
+            .unwrap();
+        assert_eq!(graph.get_order().unwrap(), vec!["a", "b", "c", "d"]);
+        graph.execute(&mut api, &mut cache).unwrap();
+        // the 8x8 hdr texture was not needed and is freed
+        assert_eq!(api.get_resource_count(), textures - 1);
+        assert_eq!(cache.len(), 2);
+
+        let mut graph = RenderGraph::new();
+        graph
+            .create_texture("a", half, TextureFormat::Rgba8Unorm)
+            .unwrap();
+        graph
+            .create_texture("b", half, TextureFormat::Rgba8Unorm)
+            .unwrap();
+        graph
+            .add_pass(
+                GraphPassDescriptor::new("a", "a").with_read("b"),
+                |_| Ok(()),
+            )
+            .unwrap();
+        graph
+            .add_pass(
+                GraphPassDescriptor::new("b", "b").with_read("a"),
+                |_| Ok(()),
+            )
+            .unwrap();
+        graph
+            .add_pass(
+                GraphPassDescriptor::new("present", SURFACE).with_read("a"),
+                |_| Ok(()),
+            )
+            .unwrap();
+        assert_eq!(
+            graph.get_order(),
+            Err(RenderGraphErrors::Cycle(vec![
+                "a".to_string(),
+                "b".to_string(),
+                "present".to_string()
+            ]))
+        );
+    }
+}
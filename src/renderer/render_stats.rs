@@ -0,0 +1,336 @@
+use std::{collections::HashMap, time::Duration};
+
+use log::{error, warn};
+
+use crate::{
+    core::runner::subsystem::{Subsystem, SubsystemContext},
+    event_system::{
+        engine_events::renderer_events::{RenderBudgetExceeded, RendererEvents},
+        event_queue::EventQueue,
+    },
+};
+
+use super::api::{Binding, DrawCommand, PassTiming, RenderPass};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RenderStat {
+    DrawCalls,
+    Batches,
+    Vertices,
+    Triangles,
+    TextureBinds,
+    LodSwitches,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrameStats {
+    pub draw_calls: u64,
+    pub batches: u64,
+    pub vertices: u64,
+    pub triangles: u64,
+    pub texture_binds: u64,
+    // groups that changed level this frame
+    pub lod_switches: u64,
+    // how many groups were drawn at each level
+    pub lod_levels: Vec<u64>,
+    pub occlusion_tested: u64,
+    pub occlusion_culled: u64,
+    // GPU times that came back this frame, from a frame or two before
+    pub pass_timings: Vec<PassTiming>,
+}
+
+impl FrameStats {
+    pub fn get(&self, stat: RenderStat) -> u64 {
+        match stat {
+            RenderStat::DrawCalls => self.draw_calls,
+            RenderStat::Batches => self.batches,
+            RenderStat::Vertices => self.vertices,
+            RenderStat::Triangles => self.triangles,
+            RenderStat::TextureBinds => self.texture_binds,
+            RenderStat::LodSwitches => self.lod_switches,
+        }
+    }
+
+    pub fn get_gpu_time(&self) -> Duration {
+        self.pass_timings.iter().map(|timing| timing.gpu_time).sum()
+    }
+}
+
+// The renderer records into the current frame while it draws, every tick
+// closes the frame and checks it against the registered thresholds.
+#[derive(Debug, Default)]
+pub struct RenderStats {
+    current: FrameStats,
+    last_frame: FrameStats,
+    thresholds: HashMap<RenderStat, u64>,
+}
+
+impl RenderStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record_draw_call(&mut self, vertices: u64) {
+        self.current.draw_calls += 1;
+        self.current.vertices += vertices;
+    }
+
+    pub fn record_triangles(&mut self, triangles: u64) {
+        self.current.triangles += triangles;
+    }
+
+    // Counts the draws of passes about to be submitted, for renderers that
+    // do not record their own. Draws are taken to be triangle lists.
+    pub fn record_passes(&mut self, passes: &[RenderPass]) {
+        for command in passes.iter().flat_map(|pass| pass.commands.iter()) {
+            match command {
+                DrawCommand::Draw {
+                    vertices: range,
+                    instances,
+                }
+                | DrawCommand::DrawIndexed {
+                    indices: range,
+                    instances,
+                    ..
+                } => {
+                    let vertices = range.len() as u64 * instances.len() as u64;
+                    self.record_draw_call(vertices);
+                    self.record_triangles(vertices / 3);
+                }
+                DrawCommand::SetBindings(bindings) => {
+                    let textures = bindings
+                        .iter()
+                        .filter(|binding| matches!(binding, Binding::Texture(_)))
+                        .count();
+                    self.current.texture_binds += textures as u64;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // What `RendererAPI::take_pass_timings` returned this frame
+    pub fn record_pass_timings(&mut self, timings: Vec<PassTiming>) {
+        self.current.pass_timings.extend(timings);
+    }
+
+    pub fn record_batch(&mut self) {
+        self.current.batches += 1;
+    }
+
+    pub fn record_texture_bind(&mut self) {
+        self.current.texture_binds += 1;
+    }
+
+    pub fn record_lod(&mut self, level: usize, switched: bool) {
+        if self.current.lod_levels.len() <= level {
+            self.current.lod_levels.resize(level + 1, 0);
+        }
+        self.current.lod_levels[level] += 1;
+        self.current.lod_switches += switched as u64;
+    }
+
+    pub fn record_occlusion(&mut self, tested: u64, culled: u64) {
+        self.current.occlusion_tested += tested;
+        self.current.occlusion_culled += culled;
+    }
+
+    pub fn get_current_frame(&self) -> &FrameStats {
+        &self.current
+    }
+
+    // Stats of the last completed frame
+    pub fn get_last_frame(&self) -> &FrameStats {
+        &self.last_frame
+    }
+
+    pub fn set_threshold(&mut self, stat: RenderStat, max: u64) {
+        self.thresholds.insert(stat, max);
+    }
+
+    pub fn remove_threshold(&mut self, stat: RenderStat) {
+        self.thresholds.remove(&stat);
+    }
+
+    pub fn get_threshold(&self, stat: RenderStat) -> Option<u64> {
+        self.thresholds.get(&stat).copied()
+    }
+
+    // Closes the current frame and returns every threshold it went over
+    pub fn end_frame(&mut self) -> Vec<RenderBudgetExceeded> {
+        self.last_frame = std::mem::take(&mut self.current);
+        self.thresholds
+            .iter()
+            .filter_map(|(stat, threshold)| {
+                let value = self.last_frame.get(*stat);
+                (value > *threshold).then_some(RenderBudgetExceeded {
+                    stat: *stat,
+                    value,
+                    threshold: *threshold,
+                })
+            })
+            .collect()
+    }
+}
+
+impl RenderStats {
+    // Ends the frame and emits a RenderBudgetExceeded for every threshold it
+    // went over
+    pub fn report_frame(&mut self, event_queue: &EventQueue) {
+        for exceeded in self.end_frame() {
+            warn!(
+                "render budget for {:?} exceeded: {} > {}",
+                exceeded.stat, exceeded.value, exceeded.threshold
+            );
+            if let Err(err) =
+                event_queue.emit(Box::new(RendererEvents::RenderBudgetExceeded(exceeded)))
+            {
+                error!("unable to emit renderer event {:?}", err);
+            }
+        }
+    }
+}
+
+impl Subsystem for RenderStats {
+    fn get_name(&self) -> &str {
+        "RenderStats"
+    }
+
+    fn init(&mut self, _ctx: &mut SubsystemContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn tick(&mut self, ctx: &mut SubsystemContext) {
+        self.report_frame(ctx.event_queue);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::api::{
+        headless::HeadlessRenderer, Buffer, BufferDescriptor, BufferUsage, PipelineDescriptor,
+        RenderTarget, RendererAPI, ShaderSource, Texture, TextureDescriptor, TextureFormat,
+    };
+
+    #[test]
+    fn test_end_frame_checks_thresholds() {
+        let mut stats = RenderStats::new();
+        stats.set_threshold(RenderStat::DrawCalls, 2);
+        stats.set_threshold(RenderStat::Vertices, 1000);
+
+        stats.record_batch();
+        stats.record_texture_bind();
+        for _ in 0..3 {
+            stats.record_draw_call(6);
+        }
+        let exceeded = stats.end_frame();
+
+        assert_eq!(
+            exceeded,
+            vec![RenderBudgetExceeded {
+                stat: RenderStat::DrawCalls,
+                value: 3,
+                threshold: 2
+            }]
+        );
+        assert_eq!(stats.get_last_frame().vertices, 18);
+        assert_eq!(stats.get_current_frame(), &FrameStats::default());
+        assert!(stats.end_frame().is_empty());
+
+        let mut pass = RenderPass::new("world", RenderTarget::Surface);
+        pass.set_bindings(vec![
+            Binding::Buffer(Buffer(1)),
+            Binding::Texture(Texture(2)),
+        ]);
+        pass.draw(0..6, 0..10);
+        pass.draw_indexed(0..3, 0, 0..1);
+        stats.record_passes(&[pass]);
+        let timing = |label: &str, micros| PassTiming {
+            label: label.to_string(),
+            gpu_time: Duration::from_micros(micros),
+        };
+        stats.record_pass_timings(vec![timing("shadows", 300), timing("world", 1200)]);
+        stats.end_frame();
+        let frame = stats.get_last_frame();
+        assert_eq!((frame.draw_calls, frame.triangles), (2, 21));
+        assert_eq!(frame.texture_binds, 1);
+        assert_eq!(frame.get_gpu_time(), Duration::from_micros(1500));
+    }
+
+    #[test]
+    fn test_headless_passes_without_timestamps() {
+        let mut api = HeadlessRenderer::new();
+        let shader = api
+            .create_shader(&ShaderSource::wgsl("mesh", "@vertex fn vs_main() {}"))
+            .unwrap();
+        let pipeline = api
+            .create_pipeline(&PipelineDescriptor::new(
+                "mesh",
+                shader,
+                api.get_surface_format(),
+            ))
+            .unwrap();
+        let vertices = api
+            .create_buffer(
+                &BufferDescriptor {
+                    label: "vertices".to_string(),
+                    usage: BufferUsage::Vertex,
+                    size: 64,
+                },
+                None,
+            )
+            .unwrap();
+        let texture = |api: &mut HeadlessRenderer, label: &str| {
+            api.create_texture(&TextureDescriptor {
+                label: label.to_string(),
+                width: 1,
+                height: 1,
+                format: TextureFormat::Rgba8Unorm,
+                render_target: false,
+                mip_levels: 1,
+            })
+            .unwrap()
+        };
+        let albedo = texture(&mut api, "albedo");
+        let normals = texture(&mut api, "normals");
+
+        let mut shadows = RenderPass::new("shadows", RenderTarget::Surface);
+        shadows.set_pipeline(pipeline);
+        shadows.set_vertex_buffer(0, vertices);
+        shadows.draw(0..3, 0..4);
+        let mut world = RenderPass::new("world", RenderTarget::Surface);
+        world.set_pipeline(pipeline);
+        world.set_bindings(vec![
+            Binding::Texture(albedo),
+            Binding::Texture(normals),
+            Binding::Buffer(vertices),
+        ]);
+        world.draw_indexed(0..6, 0, 0..2);
+        world.set_bindings(vec![Binding::Texture(albedo)]);
+        world.draw(0..4, 0..1);
+        let passes = [shadows, world];
+
+        let mut stats = RenderStats::new();
+        stats.set_threshold(RenderStat::Triangles, 8);
+        for _ in 0..2 {
+            stats.record_passes(&passes);
+            api.submit(&passes).unwrap();
+            api.present().unwrap();
+            // headless has no timestamp queries, frames come back untimed
+            stats.record_pass_timings(api.take_pass_timings());
+            let exceeded = stats.end_frame();
+
+            let frame = stats.get_last_frame();
+            assert_eq!(frame.get(RenderStat::DrawCalls), 3);
+            assert_eq!(frame.get(RenderStat::Vertices), 12 + 12 + 4);
+            // 4 vertices of a list are one triangle, the last one is cut
+            assert_eq!(frame.get(RenderStat::Triangles), 4 + 4 + 1);
+            assert_eq!(frame.get(RenderStat::TextureBinds), 3);
+            assert!(frame.pass_timings.is_empty());
+            assert_eq!(frame.get_gpu_time(), Duration::ZERO);
+            assert_eq!(exceeded.len(), 1);
+            assert_eq!(exceeded[0].value, 9);
+        }
+    }
+}
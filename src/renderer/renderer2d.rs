@@ -0,0 +1,554 @@
+use std::collections::HashMap;
+
+use crate::{
+    animation::sprite::SpritePlayer,
+    math::{
+        rect::Rect,
+        transform::{Mat4, Transform},
+        vector::{Vec2, Vec3, Vec4},
+    },
+};
+
+use super::{
+    api::{
+        Binding, BindingKind, Buffer, BufferDescriptor, BufferUsage, Filter, Pipeline,
+        PipelineDescriptor, RenderPass, RendererAPI, RendererErrors, Resource, Sampler, Shader,
+        ShaderSource, Texture, TextureDescriptor, TextureFormat, VertexFormat, VertexLayout,
+    },
+    camera::Camera,
+    sprite_slicing::{build_sprite_quads, SpriteDrawMode},
+    text::{layout_text, Font, GlyphAtlas, TextOptions},
+    texture_atlas::TextureAtlas,
+    vector::tessellate::VectorMesh,
+};
+
+const GLYPH_ATLAS_SIZE: u32 = 1024;
+
+const SPRITE_SHADER: &str = "
+struct Scene {
+    view_projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> scene: Scene;
+@group(0) @binding(1) var sprite: texture_2d<f32>;
+@group(0) @binding(2) var sprite_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) tint: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) tint: vec4<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = scene.view_projection * vec4<f32>(position, 1.0);
+    out.uv = uv;
+    out.tint = tint;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(sprite, sprite_sampler, in.uv) * in.tint;
+}
+";
+
+// position, uv and tint
+const VERTEX_FLOATS: usize = 3 + 2 + 4;
+const VERTICES_PER_QUAD: usize = 6;
+
+// Corners of the unit quad around the origin with their uv fraction, y up in
+// the world and down in the texture
+const QUAD_CORNERS: [(f32, f32, f32, f32); VERTICES_PER_QUAD] = [
+    (-0.5, 0.5, 0.0, 0.0),
+    (-0.5, -0.5, 0.0, 1.0),
+    (0.5, -0.5, 1.0, 1.0),
+    (-0.5, 0.5, 0.0, 0.0),
+    (0.5, -0.5, 1.0, 1.0),
+    (0.5, 0.5, 1.0, 0.0),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Renderer2DStats {
+    pub draw_calls: u64,
+    pub quads: u64,
+    pub triangles: u64,
+}
+
+#[derive(Debug)]
+struct Batch {
+    texture: Texture,
+    vertices: Vec<f32>,
+}
+
+// Draws textured and colored quads. Everything drawn between `begin_scene`
+// and `end_scene` is batched by texture, so a scene using one atlas is a
+// single draw call no matter how many sprites it has.
+//
+//     renderer.begin_scene(&camera);
+//     renderer.draw_quad(&Transform::from_translation(position), red);
+//     renderer.draw_sprite(atlas, &transform, Vec4::splat(1.0), frame_rect);
+//     let mut pass = RenderPass::new("world", RenderTarget::Surface);
+//     renderer.end_scene(api, &mut pass)?;
+//     api.submit(&[pass])?;
+#[derive(Debug)]
+pub struct Renderer2D {
+    shader: Shader,
+    pipeline: Pipeline,
+    scene_buffer: Buffer,
+    // one pixel of white, tinted by `draw_quad`
+    white: Texture,
+    glyphs: GlyphAtlas,
+    // named sprites of the added atlases
+    atlas_sprites: HashMap<String, (Texture, Rect)>,
+    filter: Filter,
+    view_projection: Mat4,
+    batches: Vec<Batch>,
+    batch_indices: HashMap<Texture, usize>,
+    // vertex buffers kept between frames with their size in bytes, grown
+    // when a batch outgrows them
+    vertex_buffers: HashMap<Texture, (Buffer, u64)>,
+    stats: Renderer2DStats,
+}
+
+impl Renderer2D {
+    pub fn new(api: &mut dyn RendererAPI) -> Result<Self, RendererErrors> {
+        let shader = api.create_shader(&ShaderSource::wgsl("sprite", SPRITE_SHADER))?;
+        let mut pipeline = PipelineDescriptor::new("sprite", shader, api.get_surface_format());
+        pipeline.vertex_layouts = vec![VertexLayout::packed(&[
+            VertexFormat::Float32x3,
+            VertexFormat::Float32x2,
+            VertexFormat::Float32x4,
+        ])];
+        pipeline.bindings = vec![
+            BindingKind::UniformBuffer,
+            BindingKind::Texture,
+            BindingKind::Sampler,
+        ];
+        let pipeline = api.create_pipeline(&pipeline)?;
+
+        let scene_buffer = api.create_buffer(
+            &BufferDescriptor {
+                label: "sprite scene".to_string(),
+                usage: BufferUsage::Uniform,
+                size: 64,
+            },
+            None,
+        )?;
+        let white = api.create_texture(&TextureDescriptor {
+            label: "white".to_string(),
+            width: 1,
+            height: 1,
+            format: TextureFormat::Rgba8Unorm,
+            render_target: false,
+            mip_levels: 1,
+        })?;
+        api.write_texture(white, &[255; 4])?;
+        let glyphs = GlyphAtlas::new(api, GLYPH_ATLAS_SIZE)?;
+
+        Ok(Self {
+            shader,
+            pipeline,
+            scene_buffer,
+            white,
+            glyphs,
+            atlas_sprites: HashMap::new(),
+            filter: Filter::Linear,
+            view_projection: Mat4::IDENTITY,
+            batches: Vec::new(),
+            batch_indices: HashMap::new(),
+            vertex_buffers: HashMap::new(),
+            stats: Renderer2DStats::default(),
+        })
+    }
+
+    // Nearest keeps pixel art sharp
+    pub fn set_filter(&mut self, filter: Filter) {
+        self.filter = filter;
+    }
+
+    pub fn begin_scene(&mut self, camera: &dyn Camera) {
+        self.view_projection = camera.get_view_projection();
+        self.batches.clear();
+        self.batch_indices.clear();
+        self.stats = Renderer2DStats::default();
+    }
+
+    // A unit quad around the transform's origin, scale it to size it
+    pub fn draw_quad(&mut self, transform: &Transform, color: Vec4) {
+        self.draw_sprite(self.white, transform, color, Rect::UNIT);
+    }
+
+    // `uv` is the part of the texture to show, in fractions of its size, so
+    // atlas frames are drawn with the atlas texture and their rect
+    pub fn draw_sprite(&mut self, texture: Texture, transform: &Transform, tint: Vec4, uv: Rect) {
+        let index = *self.batch_indices.entry(texture).or_insert_with(|| {
+            self.batches.push(Batch {
+                texture,
+                vertices: Vec::new(),
+            });
+            self.batches.len() - 1
+        });
+        let matrix = transform.to_matrix();
+        let vertices = &mut self.batches[index].vertices;
+        for (x, y, u, v) in QUAD_CORNERS {
+            let position = matrix.transform_point(Vec3::new(x, y, 0.0));
+            let uv = uv.lerp(Vec2::new(u, v));
+            vertices.extend_from_slice(&[
+                position.x, position.y, position.z, uv.x, uv.y, tint.x, tint.y, tint.z, tint.w,
+            ]);
+        }
+        self.stats.quads += 1;
+    }
+
+    // Fills `dest`, a world rect with y up like `draw_quad`, with the sprite
+    // cut up as `mode` says. `sprite_size` is the sprite's size in pixels,
+    // the top of the artwork ends up at `dest.max.y`.
+    pub fn draw_sprite_with_mode(
+        &mut self,
+        texture: Texture,
+        dest: Rect,
+        uv: Rect,
+        sprite_size: Vec2,
+        tint: Vec4,
+        mode: &SpriteDrawMode,
+    ) {
+        // the quads are laid out y down from dest.min, mirror them into dest
+        let mirror = dest.min.y + dest.max.y;
+        for quad in build_sprite_quads(mode, dest, uv, sprite_size) {
+            let size = quad.rect.get_size();
+            let center = Vec2::new(
+                quad.rect.min.x + size.x / 2.0,
+                mirror - quad.rect.min.y - size.y / 2.0,
+            );
+            let transform = Transform {
+                translation: Vec3::new(center.x, center.y, 0.0),
+                scale: Vec3::new(size.x, size.y, 1.0),
+                ..Transform::IDENTITY
+            };
+            self.draw_sprite(texture, &transform, tint, quad.uv);
+        }
+    }
+
+    // A tessellated vector shape, like an svg icon, batched with the colored
+    // quads. Svg documents are y down, flip them with a negative y scale.
+    pub fn draw_vector(&mut self, mesh: &VectorMesh, transform: &Transform) {
+        let index = *self.batch_indices.entry(self.white).or_insert_with(|| {
+            self.batches.push(Batch {
+                texture: self.white,
+                vertices: Vec::new(),
+            });
+            self.batches.len() - 1
+        });
+        let matrix = transform.to_matrix();
+        let vertices = &mut self.batches[index].vertices;
+        for index in mesh.indices.iter() {
+            let Some(vertex) = mesh.vertices.get(*index as usize) else {
+                continue;
+            };
+            let position =
+                matrix.transform_point(Vec3::new(vertex.position.x, vertex.position.y, 0.0));
+            let color = vertex.color;
+            vertices.extend_from_slice(&[
+                position.x, position.y, position.z, 0.5, 0.5, color.x, color.y, color.z, color.w,
+            ]);
+        }
+        self.stats.triangles += mesh.get_triangle_count() as u64;
+    }
+
+    // Makes the atlas's images drawable by name with `draw_atlas_sprite`,
+    // names already added by another atlas point to this one afterwards
+    pub fn add_atlas(&mut self, atlas: &TextureAtlas) {
+        let texture = atlas.get_texture().get_handle();
+        for name in atlas.get_names() {
+            if let Some(uv) = atlas.get_uv(name) {
+                self.atlas_sprites.insert(name.to_string(), (texture, uv));
+            }
+        }
+    }
+
+    pub fn remove_atlas(&mut self, atlas: &TextureAtlas) {
+        let texture = atlas.get_texture().get_handle();
+        self.atlas_sprites
+            .retain(|_, (sprite_texture, _)| *sprite_texture != texture);
+    }
+
+    // An image packed into one of the added atlases, false if none has it
+    pub fn draw_atlas_sprite(&mut self, name: &str, transform: &Transform, tint: Vec4) -> bool {
+        let Some((texture, uv)) = self.atlas_sprites.get(name).copied() else {
+            return false;
+        };
+        self.draw_sprite(texture, transform, tint, uv);
+        true
+    }
+
+    // The player's current frame of its sheet, `texture` is the sheet's
+    pub fn draw_animated_sprite(
+        &mut self,
+        texture: Texture,
+        transform: &Transform,
+        tint: Vec4,
+        player: &SpritePlayer,
+    ) {
+        self.draw_sprite(texture, transform, tint, player.get_uv());
+    }
+
+    // `position` is the top left of the text and `size` its height in
+    // pixels, which are world units under a pixel sized camera
+    pub fn draw_text(&mut self, text: &str, font: &Font, size: f32, position: Vec2, color: Vec4) {
+        self.draw_text_with(text, font, size, position, color, &TextOptions::default());
+    }
+
+    pub fn draw_text_with(
+        &mut self,
+        text: &str,
+        font: &Font,
+        size: f32,
+        position: Vec2,
+        color: Vec4,
+        options: &TextOptions,
+    ) {
+        let layout = layout_text(text, font, size, options);
+        // glyphs are rasterized at whole pixel sizes and scaled to fit
+        let scale = size / size.round().max(1.0);
+        for placed in layout.glyphs {
+            let Some(glyph) = self.glyphs.get_glyph(font, placed.glyph, size) else {
+                continue;
+            };
+            let glyph_size = glyph.size * scale;
+            let bottom_left = Vec2::new(
+                position.x + placed.position.x + glyph.offset.x * scale,
+                position.y - placed.position.y + glyph.offset.y * scale,
+            );
+            let center = bottom_left + glyph_size * 0.5;
+            let transform = Transform {
+                translation: Vec3::new(center.x, center.y, 0.0),
+                scale: Vec3::new(glyph_size.x, glyph_size.y, 1.0),
+                ..Transform::IDENTITY
+            };
+            self.draw_sprite(self.glyphs.get_texture(), &transform, color, glyph.uv);
+        }
+    }
+
+    // Glyphs drawn with `draw_text` are cached here
+    pub fn get_glyph_atlas_mut(&mut self) -> &mut GlyphAtlas {
+        &mut self.glyphs
+    }
+
+    // Uploads the batches and records one draw per texture into `pass`
+    pub fn end_scene(
+        &mut self,
+        api: &mut dyn RendererAPI,
+        pass: &mut RenderPass,
+    ) -> Result<(), RendererErrors> {
+        let scene: Vec<u8> = self
+            .view_projection
+            .to_cols_array()
+            .iter()
+            .flatten()
+            .flat_map(|value| value.to_le_bytes())
+            .collect();
+        api.write_buffer(self.scene_buffer, 0, &scene)?;
+        self.glyphs.upload(api)?;
+
+        pass.set_pipeline(self.pipeline);
+        for batch in self.batches.iter() {
+            let data: Vec<u8> = batch
+                .vertices
+                .iter()
+                .flat_map(|value| value.to_le_bytes())
+                .collect();
+            let buffer = match self.vertex_buffers.get(&batch.texture) {
+                Some((buffer, size)) if *size >= data.len() as u64 => *buffer,
+                old => {
+                    if let Some((buffer, _)) = old {
+                        api.destroy(Resource::Buffer(*buffer));
+                    }
+                    // room to grow so a few more sprites do not reallocate
+                    let size = (data.len() as u64).next_power_of_two();
+                    let buffer = api.create_buffer(
+                        &BufferDescriptor {
+                            label: "sprite batch".to_string(),
+                            usage: BufferUsage::Vertex,
+                            size,
+                        },
+                        None,
+                    )?;
+                    self.vertex_buffers.insert(batch.texture, (buffer, size));
+                    buffer
+                }
+            };
+            api.write_buffer(buffer, 0, &data)?;
+
+            pass.set_bindings(vec![
+                Binding::Buffer(self.scene_buffer),
+                Binding::Texture(batch.texture),
+                Binding::Sampler(Sampler::new(self.filter)),
+            ]);
+            pass.set_vertex_buffer(0, buffer);
+            pass.draw(0..(batch.vertices.len() / VERTEX_FLOATS) as u32, 0..1);
+            self.stats.draw_calls += 1;
+        }
+        Ok(())
+    }
+
+    // Statistics of the current scene, complete after `end_scene`
+    pub fn get_stats(&self) -> Renderer2DStats {
+        self.stats
+    }
+
+    // Frees the GPU resources, the renderer is unusable afterwards
+    pub fn destroy(&mut self, api: &mut dyn RendererAPI) {
+        for (_, (buffer, _)) in self.vertex_buffers.drain() {
+            api.destroy(Resource::Buffer(buffer));
+        }
+        api.destroy(Resource::Buffer(self.scene_buffer));
+        api.destroy(Resource::Texture(self.white));
+        self.glyphs.destroy(api);
+        api.destroy(Resource::Pipeline(self.pipeline));
+        api.destroy(Resource::Shader(self.shader));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::{
+        api::{headless::HeadlessRenderer, RenderTarget},
+        sprite_slicing::SliceBorders,
+        vector::tessellate::VectorVertex,
+    };
+
+    #[test]
+    fn test_batches_by_texture() {
+        let mut api = HeadlessRenderer::new();
+        let mut renderer = Renderer2D::new(&mut api).unwrap();
+        let atlas = api
+            .create_texture(&TextureDescriptor {
+                label: "atlas".to_string(),
+                width: 4,
+                height: 4,
+                format: TextureFormat::Rgba8Unorm,
+                render_target: false,
+                mip_levels: 1,
+            })
+            .unwrap();
+
+        renderer.begin_scene(&Mat4::IDENTITY);
+        let red = Vec4::new(1.0, 0.0, 0.0, 1.0);
+        renderer.draw_quad(&Transform::IDENTITY, red);
+        let frame = Rect::new(Vec2::ZERO, Vec2::new(0.5, 0.5));
+        renderer.draw_sprite(atlas, &Transform::IDENTITY, Vec4::splat(1.0), frame);
+        renderer.draw_quad(&Transform::from_translation(Vec3::new(2.0, 0.0, 0.0)), red);
+        renderer.draw_sprite(atlas, &Transform::IDENTITY, Vec4::splat(1.0), frame);
+
+        let mut pass = RenderPass::new("world", RenderTarget::Surface);
+        renderer.end_scene(&mut api, &mut pass).unwrap();
+        assert_eq!(
+            renderer.get_stats(),
+            Renderer2DStats {
+                draw_calls: 2,
+                quads: 4,
+                triangles: 0
+            }
+        );
+        assert_eq!(pass.get_draw_count(), 2);
+
+        // the second quad of the white batch starts at its translated corner
+        let (buffer, _) = renderer.vertex_buffers[&renderer.white];
+        let contents = api.get_buffer_contents(buffer).unwrap();
+        let offset = VERTICES_PER_QUAD * VERTEX_FLOATS * 4;
+        let x = f32::from_le_bytes(contents[offset..offset + 4].try_into().unwrap());
+        assert_eq!(x, 1.5);
+        api.submit(&[pass]).unwrap();
+
+        // vector shapes share the colored quads' batch
+        let mut mesh = VectorMesh::new();
+        mesh.vertices = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]
+            .map(|(x, y)| VectorVertex {
+                position: Vec2::new(x, y),
+                color: red,
+            })
+            .to_vec();
+        mesh.indices = vec![0, 1, 2];
+        renderer.begin_scene(&Mat4::IDENTITY);
+        renderer.draw_quad(&Transform::IDENTITY, red);
+        renderer.draw_vector(
+            &mesh,
+            &Transform::from_translation(Vec3::new(1.0, 0.0, 0.0)),
+        );
+        let mut pass = RenderPass::new("world", RenderTarget::Surface);
+        renderer.end_scene(&mut api, &mut pass).unwrap();
+        assert_eq!(
+            renderer.get_stats(),
+            Renderer2DStats {
+                draw_calls: 1,
+                quads: 1,
+                triangles: 1
+            }
+        );
+        let (buffer, _) = renderer.vertex_buffers[&renderer.white];
+        let contents = api.get_buffer_contents(buffer).unwrap();
+        let x = f32::from_le_bytes(
+            contents[offset + 4 * VERTEX_FLOATS..][..4]
+                .try_into()
+                .unwrap(),
+        );
+        assert_eq!(x, 2.0);
+    }
+
+    #[test]
+    fn test_sprite_draw_modes() {
+        let mut api = HeadlessRenderer::new();
+        let mut renderer = Renderer2D::new(&mut api).unwrap();
+        let panel = api
+            .create_texture(&TextureDescriptor {
+                label: "panel".to_string(),
+                width: 16,
+                height: 16,
+                format: TextureFormat::Rgba8Unorm,
+                render_target: false,
+                mip_levels: 1,
+            })
+            .unwrap();
+
+        renderer.begin_scene(&Mat4::IDENTITY);
+        let dest = Rect::from_size(Vec2::ZERO, Vec2::new(40.0, 16.0));
+        let size = Vec2::splat(16.0);
+        let nine_slice = SpriteDrawMode::nine_slice(SliceBorders::uniform(4.0));
+        renderer.draw_sprite_with_mode(
+            panel,
+            dest,
+            Rect::UNIT,
+            size,
+            Vec4::splat(1.0),
+            &nine_slice,
+        );
+        let tiled = SpriteDrawMode::tiled();
+        renderer.draw_sprite_with_mode(panel, dest, Rect::UNIT, size, Vec4::splat(1.0), &tiled);
+
+        let mut pass = RenderPass::new("ui", RenderTarget::Surface);
+        renderer.end_scene(&mut api, &mut pass).unwrap();
+        assert_eq!(
+            renderer.get_stats(),
+            Renderer2DStats {
+                draw_calls: 1,
+                quads: 9 + 3,
+                triangles: 0
+            }
+        );
+
+        // the top left corner is drawn at the top of dest, y up
+        let (buffer, _) = renderer.vertex_buffers[&panel];
+        let contents = api.get_buffer_contents(buffer).unwrap();
+        let float =
+            |index: usize| f32::from_le_bytes(contents[index * 4..][..4].try_into().unwrap());
+        assert_eq!((float(0), float(1)), (0.0, 16.0));
+        assert_eq!((float(3), float(4)), (0.0, 0.0));
+    }
+}
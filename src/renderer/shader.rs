@@ -0,0 +1,382 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use log::{error, info};
+use thiserror::Error;
+
+use crate::event_system::{
+    engine_events::renderer_events::RendererEvents, event_queue::EventQueue,
+};
+
+use super::api::{self, RendererAPI, RendererErrors, Resource, ShaderSource, ShaderStage};
+
+const INCLUDE_DIRECTIVE: &str = "#include";
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ShaderErrors {
+    #[error("unable to read shader {0}: {1}")]
+    Io(String, String),
+
+    #[error("{file} includes {include} which does not exist")]
+    IncludeNotFound { file: String, include: String },
+
+    #[error("{0} includes itself")]
+    IncludeCycle(String),
+
+    #[error("unknown shader language of {0}, use .wgsl, .vert or .frag")]
+    UnknownLanguage(String),
+
+    #[error("shader failed to compile: {0}")]
+    Compile(RendererErrors),
+
+    #[error("unknown shader {0}")]
+    UnknownShader(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderLanguage {
+    Wgsl,
+    Glsl(ShaderStage),
+}
+
+impl ShaderLanguage {
+    // .vert and .frag are GLSL stages, .wgsl is WGSL
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "wgsl" => Some(Self::Wgsl),
+            "vert" => Some(Self::Glsl(ShaderStage::Vertex)),
+            "frag" => Some(Self::Glsl(ShaderStage::Fragment)),
+            _ => None,
+        }
+    }
+}
+
+// Reads `path` and pastes in the files named by `#include "file"` lines,
+// relative to the including file. Every file is included once, the first
+// time it is named, so shared headers can be included from everywhere.
+fn compose(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+    out: &mut String,
+) -> Result<(), ShaderErrors> {
+    let display = path.display().to_string();
+    let source = fs::read_to_string(path)
+        .map_err(|err| ShaderErrors::Io(display.clone(), err.to_string()))?;
+    stack.push(path.to_path_buf());
+    files.push(path.to_path_buf());
+
+    for line in source.lines() {
+        let Some(rest) = line.trim_start().strip_prefix(INCLUDE_DIRECTIVE) else {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        };
+        let name = rest
+            .trim()
+            .trim_matches(|c| c == '"' || c == '<' || c == '>');
+        let include = path.parent().unwrap_or(Path::new("")).join(name);
+        if !include.is_file() {
+            return Err(ShaderErrors::IncludeNotFound {
+                file: display,
+                include: name.to_string(),
+            });
+        }
+        if stack.contains(&include) {
+            return Err(ShaderErrors::IncludeCycle(include.display().to_string()));
+        }
+        if files.contains(&include) {
+            continue;
+        }
+        compose(&include, stack, files, out)?;
+    }
+    stack.pop();
+    Ok(())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+// A shader loaded from a file together with everything it includes. It is
+// compiled through the RendererAPI and recompiled when any of its files
+// changes; a broken edit is logged and the last working version stays.
+#[derive(Debug)]
+pub struct Shader {
+    path: PathBuf,
+    language: ShaderLanguage,
+    source: String,
+    // the shader file and its includes with their modification times
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+    handle: Option<api::Shader>,
+}
+
+impl Shader {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ShaderErrors> {
+        let path = path.as_ref();
+        let language = ShaderLanguage::from_path(path)
+            .ok_or_else(|| ShaderErrors::UnknownLanguage(path.display().to_string()))?;
+        Self::load_as(path, language)
+    }
+
+    // For files whose extension does not tell the language
+    pub fn load_as(path: impl AsRef<Path>, language: ShaderLanguage) -> Result<Self, ShaderErrors> {
+        let mut shader = Self {
+            path: path.as_ref().to_path_buf(),
+            language,
+            source: String::new(),
+            files: Vec::new(),
+            handle: None,
+        };
+        shader.read()?;
+        Ok(shader)
+    }
+
+    fn read(&mut self) -> Result<(), ShaderErrors> {
+        let mut files = Vec::new();
+        let mut source = String::new();
+        compose(&self.path, &mut Vec::new(), &mut files, &mut source)?;
+        self.source = source;
+        self.files = files
+            .into_iter()
+            .map(|file| {
+                let time = modified(&file);
+                (file, time)
+            })
+            .collect();
+        Ok(())
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get_language(&self) -> ShaderLanguage {
+        self.language
+    }
+
+    // With the includes pasted in
+    pub fn get_source(&self) -> &str {
+        &self.source
+    }
+
+    // The shader file first, then its includes
+    pub fn get_files(&self) -> Vec<&Path> {
+        self.files.iter().map(|(file, _)| file.as_path()).collect()
+    }
+
+    // None until compiled
+    pub fn get_handle(&self) -> Option<api::Shader> {
+        self.handle
+    }
+
+    pub fn is_changed(&self) -> bool {
+        self.files
+            .iter()
+            .any(|(file, time)| modified(file) != *time)
+    }
+
+    pub fn compile(&mut self, api: &mut dyn RendererAPI) -> Result<api::Shader, ShaderErrors> {
+        let label = self.path.display().to_string();
+        let source = match self.language {
+            ShaderLanguage::Wgsl => ShaderSource::wgsl(&label, &self.source),
+            ShaderLanguage::Glsl(stage) => ShaderSource::glsl(&label, &self.source, stage),
+        };
+        let handle = api.create_shader(&source).map_err(|err| {
+            error!("{}", err);
+            ShaderErrors::Compile(err)
+        })?;
+        if let Some(old) = self.handle.replace(handle) {
+            api.destroy(Resource::Shader(old));
+        }
+        Ok(handle)
+    }
+
+    // Reads and compiles again if a file changed, true when the new version
+    // is in use
+    pub fn reload(&mut self, api: &mut dyn RendererAPI) -> Result<bool, ShaderErrors> {
+        if !self.is_changed() {
+            return Ok(false);
+        }
+        if let Err(err) = self.read() {
+            error!("unable to reload shader {}: {}", self.path.display(), err);
+            // remember the times so a broken file is not read every poll
+            for (file, time) in self.files.iter_mut() {
+                *time = modified(file);
+            }
+            return Err(err);
+        }
+        self.compile(api)?;
+        info!("reloaded shader {}", self.path.display());
+        Ok(true)
+    }
+
+    pub fn destroy(&mut self, api: &mut dyn RendererAPI) {
+        if let Some(handle) = self.handle.take() {
+            api.destroy(Resource::Shader(handle));
+        }
+    }
+}
+
+// Named shaders, checked for changes every poll interval. A ShaderReloaded
+// event with the name follows every successful reload, pipelines built from
+// the shader have to be created again with the new handle.
+#[derive(Debug)]
+pub struct ShaderLibrary {
+    shaders: HashMap<String, Shader>,
+    poll_interval: Duration,
+    since_poll: Duration,
+}
+
+impl ShaderLibrary {
+    pub fn new() -> Self {
+        Self {
+            shaders: HashMap::new(),
+            poll_interval: Duration::from_millis(500),
+            since_poll: Duration::ZERO,
+        }
+    }
+
+    pub fn set_poll_interval(&mut self, poll_interval: Duration) {
+        self.poll_interval = poll_interval;
+    }
+
+    pub fn add(
+        &mut self,
+        name: &str,
+        path: impl AsRef<Path>,
+        api: &mut dyn RendererAPI,
+    ) -> Result<api::Shader, ShaderErrors> {
+        let mut shader = Shader::load(path)?;
+        let handle = shader.compile(api)?;
+        if let Some(mut old) = self.shaders.insert(name.to_string(), shader) {
+            old.destroy(api);
+        }
+        Ok(handle)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Shader> {
+        self.shaders.get(name)
+    }
+
+    pub fn get_handle(&self, name: &str) -> Result<api::Shader, ShaderErrors> {
+        self.shaders
+            .get(name)
+            .and_then(|shader| shader.get_handle())
+            .ok_or_else(|| ShaderErrors::UnknownShader(name.to_string()))
+    }
+
+    pub fn remove(&mut self, name: &str, api: &mut dyn RendererAPI) -> bool {
+        match self.shaders.remove(name) {
+            Some(mut shader) => {
+                shader.destroy(api);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Reloads the shaders whose files changed, returns their names
+    pub fn reload_changed(&mut self, api: &mut dyn RendererAPI) -> Vec<String> {
+        let mut reloaded = Vec::new();
+        for (name, shader) in self.shaders.iter_mut() {
+            // failures were logged, the old shader keeps working
+            if let Ok(true) = shader.reload(api) {
+                reloaded.push(name.clone());
+            }
+        }
+        reloaded
+    }
+
+    // Call once per frame with the unscaled delta
+    pub fn poll(&mut self, delta: Duration, api: &mut dyn RendererAPI, event_queue: &EventQueue) {
+        self.since_poll += delta;
+        if self.since_poll < self.poll_interval {
+            return;
+        }
+        self.since_poll = Duration::ZERO;
+
+        for name in self.reload_changed(api) {
+            let event = RendererEvents::ShaderReloaded(name);
+            if let Err(err) = event_queue.emit(Box::new(event)) {
+                error!("unable to emit shader reloaded event {:?}", err);
+            }
+        }
+    }
+}
+
+impl Default for ShaderLibrary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::renderer::api::headless::HeadlessRenderer;
+
+    fn touch(path: &Path, contents: &str, seconds: u64) {
+        fs::write(path, contents).unwrap();
+        let later = SystemTime::now() + Duration::from_secs(seconds);
+        fs::File::options()
+            .write(true)
+            .open(path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_includes_and_hot_reload() {
+        let dir = env::temp_dir().join("aloy_shader_test");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        fs::write(dir.join("lib/common.wgsl"), "const PI: f32 = 3.14;").unwrap();
+        fs::write(
+            dir.join("lib/light.wgsl"),
+            "#include \"common.wgsl\"\nfn light() {}",
+        )
+        .unwrap();
+        let main = dir.join("sprite.wgsl");
+        fs::write(
+            &main,
+            "#include \"lib/common.wgsl\"\n#include \"lib/light.wgsl\"\n@vertex fn vs_main() {}",
+        )
+        .unwrap();
+
+        let mut api = HeadlessRenderer::new();
+        let mut library = ShaderLibrary::new();
+        let first = library.add("sprite", &main, &mut api).unwrap();
+        let shader = library.get("sprite").unwrap();
+        assert_eq!(
+            shader.get_source(),
+            "const PI: f32 = 3.14;\nfn light() {}\n@vertex fn vs_main() {}\n"
+        );
+        assert_eq!(shader.get_files().len(), 3);
+
+        touch(&dir.join("lib/light.wgsl"), "fn light() { }", 5);
+        assert_eq!(library.reload_changed(&mut api), vec!["sprite".to_string()]);
+        assert_ne!(library.get_handle("sprite").unwrap(), first);
+        assert_eq!(api.get_resource_count(), 1);
+
+        // an edit that breaks the include keeps the working shader
+        let working = library.get_handle("sprite").unwrap();
+        touch(&main, "#include \"missing.wgsl\"", 10);
+        assert!(library.reload_changed(&mut api).is_empty());
+        assert_eq!(library.get_handle("sprite").unwrap(), working);
+
+        fs::write(dir.join("lib/loop.wgsl"), "#include \"loop.wgsl\"").unwrap();
+        assert_eq!(
+            Shader::load(dir.join("lib/loop.wgsl")).unwrap_err(),
+            ShaderErrors::IncludeCycle(dir.join("lib/loop.wgsl").display().to_string())
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::math::{transform::Mat4, vector::Vec3};
+
+// Most rigs fit, the palette is a uniform array so it has a fixed size
+pub const MAX_JOINTS: usize = 128;
+
+// The vertex stage of skinned meshes. `joint_palette` is filled every frame
+// from Pose::get_skinning_matrices.
+pub const SKINNING_VERTEX_SHADER: &str = r#"#version 450
+
+layout(location = 0) in vec3 a_position;
+layout(location = 1) in vec3 a_normal;
+layout(location = 2) in uvec4 a_joints;
+layout(location = 3) in vec4 a_weights;
+
+layout(std140, binding = 0) uniform Camera {
+    mat4 u_view_projection;
+};
+
+layout(std140, binding = 1) uniform Skin {
+    mat4 u_model;
+    mat4 joint_palette[128];
+};
+
+layout(location = 0) out vec3 v_normal;
+
+void main() {
+    mat4 skin = a_weights.x * joint_palette[a_joints.x]
+              + a_weights.y * joint_palette[a_joints.y]
+              + a_weights.z * joint_palette[a_joints.z]
+              + a_weights.w * joint_palette[a_joints.w];
+    mat4 model = u_model * skin;
+    v_normal = normalize(mat3(model) * a_normal);
+    gl_Position = u_view_projection * model * vec4(a_position, 1.0);
+}
+"#;
+
+// Bind pose vertex influenced by up to four joints, weights add up to one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkinnedVertex {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub joints: [u16; 4],
+    pub weights: [f32; 4],
+}
+
+// Same math as the shader, for picking, bounds and tests. Joints outside the
+// palette are ignored.
+pub fn skin_vertex(vertex: &SkinnedVertex, palette: &[Mat4]) -> (Vec3, Vec3) {
+    let mut skin: Option<Mat4> = None;
+    for (joint, weight) in vertex.joints.iter().zip(vertex.weights) {
+        let Some(matrix) = palette.get(*joint as usize) else {
+            continue;
+        };
+        if weight == 0.0 {
+            continue;
+        }
+        skin = Some(match skin {
+            Some(skin) => skin + *matrix * weight,
+            None => *matrix * weight,
+        });
+    }
+    let skin = skin.unwrap_or_default();
+    (
+        skin.transform_point(vertex.position),
+        skin.transform_vector(vertex.normal).normalize(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::transform::Transform;
+
+    use super::*;
+
+    #[test]
+    fn test_skin_vertex_blends_joints() {
+        let palette = [
+            Mat4::IDENTITY,
+            Transform::from_translation(Vec3::new(0.0, 2.0, 0.0)).to_matrix(),
+        ];
+        let vertex = SkinnedVertex {
+            position: Vec3::X,
+            normal: Vec3::Z,
+            joints: [0, 1, 0, 0],
+            weights: [0.5, 0.5, 0.0, 0.0],
+        };
+
+        let (position, normal) = skin_vertex(&vertex, &palette);
+        assert_eq!(position, Vec3::new(1.0, 1.0, 0.0));
+        assert_eq!(normal, Vec3::Z);
+        assert!(SKINNING_VERTEX_SHADER.contains(&format!("joint_palette[{}]", MAX_JOINTS)));
+    }
+}
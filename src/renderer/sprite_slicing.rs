@@ -0,0 +1,277 @@
+use crate::math::{rect::Rect, vector::Vec2};
+
+// Widths of the frame around the sprite that must not stretch, in pixels of
+// the sprite
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SliceBorders {
+    pub left: f32,
+    pub right: f32,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+impl SliceBorders {
+    pub fn new(left: f32, right: f32, top: f32, bottom: f32) -> Self {
+        Self {
+            left,
+            right,
+            top,
+            bottom,
+        }
+    }
+
+    pub fn uniform(border: f32) -> Self {
+        Self::new(border, border, border, border)
+    }
+}
+
+// How the parts of a sprite that do change size are filled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SliceFill {
+    #[default]
+    Stretch,
+    // repeats the artwork at its own size, the last copy is cut off
+    Tile,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SpriteDrawMode {
+    #[default]
+    Simple,
+    // Corners keep their size, edges and the center fill the rest. Panels,
+    // buttons and progress bars scale without smearing their frame.
+    NineSlice {
+        borders: SliceBorders,
+        fill: SliceFill,
+        // size of a sprite pixel on screen, e.g. 2 for chunky pixel art frames
+        border_scale: f32,
+    },
+    // The whole sprite repeated from the top left, `tile_size` None keeps the
+    // sprite's pixel size
+    Tiled {
+        tile_size: Option<Vec2>,
+    },
+}
+
+impl SpriteDrawMode {
+    pub fn nine_slice(borders: SliceBorders) -> Self {
+        Self::NineSlice {
+            borders,
+            fill: SliceFill::Stretch,
+            border_scale: 1.0,
+        }
+    }
+
+    pub fn tiled() -> Self {
+        Self::Tiled { tile_size: None }
+    }
+}
+
+// One textured quad for the 2D batch, `uv` is in texture coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SpriteQuad {
+    pub rect: Rect,
+    pub uv: Rect,
+}
+
+// Covers `dest` with `uv`, cut into copies of `tile` (the screen size of one
+// copy of the artwork) when tiling
+fn fill_region(quads: &mut Vec<SpriteQuad>, dest: Rect, uv: Rect, tile: Option<Vec2>) {
+    if dest.is_empty() || uv.is_empty() {
+        return;
+    }
+    let Some(tile) = tile.filter(|tile| tile.x > 0.0 && tile.y > 0.0) else {
+        quads.push(SpriteQuad { rect: dest, uv });
+        return;
+    };
+
+    let size = dest.get_size();
+    let mut y = 0.0;
+    while y < size.y {
+        let height = tile.y.min(size.y - y);
+        let mut x = 0.0;
+        while x < size.x {
+            let width = tile.x.min(size.x - x);
+            let rect = Rect::from_size(dest.min + Vec2::new(x, y), Vec2::new(width, height));
+            let cut = Vec2::new(width / tile.x, height / tile.y);
+            quads.push(SpriteQuad {
+                rect,
+                uv: uv.sub(Vec2::ZERO, cut),
+            });
+            x += tile.x;
+        }
+        y += tile.y;
+    }
+}
+
+// The quads that draw a sprite into `dest`. `uv` is the sprite's region of
+// its texture or atlas, `sprite_size` its size in pixels.
+pub fn build_sprite_quads(
+    mode: &SpriteDrawMode,
+    dest: Rect,
+    uv: Rect,
+    sprite_size: Vec2,
+) -> Vec<SpriteQuad> {
+    let mut quads = Vec::new();
+    match mode {
+        SpriteDrawMode::Simple => fill_region(&mut quads, dest, uv, None),
+        SpriteDrawMode::Tiled { tile_size } => {
+            fill_region(&mut quads, dest, uv, Some(tile_size.unwrap_or(sprite_size)))
+        }
+        SpriteDrawMode::NineSlice {
+            borders,
+            fill,
+            border_scale,
+        } => {
+            if sprite_size.x <= 0.0 || sprite_size.y <= 0.0 {
+                return quads;
+            }
+            let size = dest.get_size();
+            let scale = border_scale.max(0.0);
+            // borders shrink together when the destination is too small for
+            // them
+            let shrink = |first: f32, second: f32, available: f32| {
+                let total = (first + second) * scale;
+                match total > available && total > 0.0 {
+                    true => available / total * scale,
+                    false => scale,
+                }
+            };
+            let horizontal = shrink(borders.left, borders.right, size.x);
+            let vertical = shrink(borders.top, borders.bottom, size.y);
+
+            let xs = [
+                dest.min.x,
+                dest.min.x + borders.left * horizontal,
+                dest.max.x - borders.right * horizontal,
+                dest.max.x,
+            ];
+            let ys = [
+                dest.min.y,
+                dest.min.y + borders.top * vertical,
+                dest.max.y - borders.bottom * vertical,
+                dest.max.y,
+            ];
+            let us = [
+                0.0,
+                borders.left / sprite_size.x,
+                1.0 - borders.right / sprite_size.x,
+                1.0,
+            ]
+            .map(|u| uv.min.x + uv.get_size().x * u);
+            let vs = [
+                0.0,
+                borders.top / sprite_size.y,
+                1.0 - borders.bottom / sprite_size.y,
+                1.0,
+            ]
+            .map(|v| uv.min.y + uv.get_size().y * v);
+
+            for row in 0..3 {
+                for column in 0..3 {
+                    let rect = Rect::new(
+                        Vec2::new(xs[column], ys[row]),
+                        Vec2::new(xs[column + 1], ys[row + 1]),
+                    );
+                    let source = Rect::new(
+                        Vec2::new(us[column], vs[row]),
+                        Vec2::new(us[column + 1], vs[row + 1]),
+                    );
+                    let stretches = column == 1 || row == 1;
+                    let tile = match (fill, stretches) {
+                        (SliceFill::Tile, true) => {
+                            // the slice's own size on screen, only the
+                            // stretching direction repeats
+                            let pixels = Vec2::new(
+                                (source.get_size().x / uv.get_size().x) * sprite_size.x * scale,
+                                (source.get_size().y / uv.get_size().y) * sprite_size.y * scale,
+                            );
+                            Some(Vec2::new(
+                                if column == 1 {
+                                    pixels.x
+                                } else {
+                                    rect.get_size().x
+                                },
+                                if row == 1 {
+                                    pixels.y
+                                } else {
+                                    rect.get_size().y
+                                },
+                            ))
+                        }
+                        _ => None,
+                    };
+                    fill_region(&mut quads, rect, source, tile);
+                }
+            }
+        }
+    }
+    quads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(quads: &[SpriteQuad]) -> f32 {
+        quads
+            .iter()
+            .map(|quad| quad.rect.get_size().x * quad.rect.get_size().y)
+            .sum()
+    }
+
+    #[test]
+    fn test_nine_slice() {
+        let mode = SpriteDrawMode::nine_slice(SliceBorders::uniform(4.0));
+        let dest = Rect::from_size(Vec2::new(10.0, 10.0), Vec2::new(100.0, 20.0));
+        let quads = build_sprite_quads(&mode, dest, Rect::UNIT, Vec2::splat(16.0));
+        assert_eq!(quads.len(), 9);
+        assert!((area(&quads) - 2000.0).abs() < 1e-3);
+
+        // corners keep their size and uvs
+        assert_eq!(
+            quads[0].rect,
+            Rect::from_size(Vec2::new(10.0, 10.0), Vec2::splat(4.0))
+        );
+        assert_eq!(quads[0].uv, Rect::new(Vec2::ZERO, Vec2::splat(0.25)));
+        assert_eq!(quads[8].rect.get_size(), Vec2::splat(4.0));
+        // the center stretches
+        assert_eq!(quads[4].rect.get_size(), Vec2::new(92.0, 12.0));
+        assert_eq!(quads[4].uv, Rect::new(Vec2::splat(0.25), Vec2::splat(0.75)));
+
+        // too small for the borders, they shrink and the center vanishes
+        let small = Rect::from_size(Vec2::ZERO, Vec2::new(4.0, 20.0));
+        let quads = build_sprite_quads(&mode, small, Rect::UNIT, Vec2::splat(16.0));
+        assert_eq!(quads.len(), 6);
+        assert_eq!(quads[0].rect.get_size(), Vec2::new(2.0, 4.0));
+    }
+
+    #[test]
+    fn test_tiled() {
+        let uv = Rect::new(Vec2::new(0.5, 0.0), Vec2::new(1.0, 0.5));
+        let dest = Rect::from_size(Vec2::ZERO, Vec2::new(40.0, 16.0));
+        let quads = build_sprite_quads(&SpriteDrawMode::tiled(), dest, uv, Vec2::splat(16.0));
+        // two whole tiles and half of one
+        assert_eq!(quads.len(), 3);
+        assert_eq!(
+            quads[2].rect,
+            Rect::new(Vec2::new(32.0, 0.0), Vec2::new(40.0, 16.0))
+        );
+        assert_eq!(
+            quads[2].uv,
+            Rect::new(Vec2::new(0.5, 0.0), Vec2::new(0.75, 0.5))
+        );
+
+        // tiled edges of a nine slice repeat along their length only
+        let mode = SpriteDrawMode::NineSlice {
+            borders: SliceBorders::uniform(4.0),
+            fill: SliceFill::Tile,
+            border_scale: 1.0,
+        };
+        let dest = Rect::from_size(Vec2::ZERO, Vec2::new(24.0, 8.0));
+        let quads = build_sprite_quads(&mode, dest, Rect::UNIT, Vec2::splat(16.0));
+        // corners, then a 16 wide top and bottom edge made of 8 wide copies
+        assert_eq!(quads.len(), 4 + 2 * 2);
+        assert!((area(&quads) - 192.0).abs() < 1e-3);
+    }
+}
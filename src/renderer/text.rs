@@ -0,0 +1,500 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use fontdue::FontSettings;
+use log::warn;
+use thiserror::Error;
+
+use crate::math::{rect::Rect, vector::Vec2};
+
+use super::api::{
+    RendererAPI, RendererErrors, Resource, Texture, TextureDescriptor, TextureFormat,
+};
+
+// Free pixels around every glyph so filtering never picks up a neighbour
+const GLYPH_PADDING: u32 = 1;
+
+static NEXT_FONT_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Error, PartialEq)]
+pub enum TextErrors {
+    #[error("unable to read font {0}: {1}")]
+    Io(String, String),
+
+    #[error("unable to parse font {0}: {1}")]
+    Font(String, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMetrics {
+    // above the baseline, positive
+    pub ascent: f32,
+    // below the baseline, negative
+    pub descent: f32,
+    pub line_gap: f32,
+}
+
+impl LineMetrics {
+    pub fn get_line_height(&self) -> f32 {
+        self.ascent - self.descent + self.line_gap
+    }
+}
+
+// What the layout needs to know about a font, sizes are in pixels
+pub trait GlyphMetrics {
+    fn get_advance(&self, glyph: char, size: f32) -> f32;
+
+    fn get_kerning(&self, left: char, right: char, size: f32) -> f32;
+
+    fn get_line_metrics(&self, size: f32) -> LineMetrics;
+}
+
+// A TrueType or OpenType font, glyphs are rasterized on demand by the
+// GlyphAtlas
+#[derive(Debug)]
+pub struct Font {
+    id: u64,
+    name: String,
+    font: fontdue::Font,
+}
+
+impl Font {
+    pub fn from_bytes(name: &str, bytes: &[u8]) -> Result<Self, TextErrors> {
+        let font = fontdue::Font::from_bytes(bytes, FontSettings::default())
+            .map_err(|err| TextErrors::Font(name.to_string(), err.to_string()))?;
+        Ok(Self {
+            id: NEXT_FONT_ID.fetch_add(1, Ordering::Relaxed),
+            name: name.to_string(),
+            font,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, TextErrors> {
+        let path = path.as_ref();
+        let display = path.display().to_string();
+        let bytes =
+            fs::read(path).map_err(|err| TextErrors::Io(display.clone(), err.to_string()))?;
+        Self::from_bytes(&display, &bytes)
+    }
+
+    pub fn get_id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn has_glyph(&self, glyph: char) -> bool {
+        self.font.has_glyph(glyph)
+    }
+}
+
+impl GlyphMetrics for Font {
+    fn get_advance(&self, glyph: char, size: f32) -> f32 {
+        self.font.metrics(glyph, size).advance_width
+    }
+
+    fn get_kerning(&self, left: char, right: char, size: f32) -> f32 {
+        self.font.horizontal_kern(left, right, size).unwrap_or(0.0)
+    }
+
+    fn get_line_metrics(&self, size: f32) -> LineMetrics {
+        match self.font.horizontal_line_metrics(size) {
+            Some(metrics) => LineMetrics {
+                ascent: metrics.ascent,
+                descent: metrics.descent,
+                line_gap: metrics.line_gap,
+            },
+            // fonts without a horizontal header, guess from the size
+            None => LineMetrics {
+                ascent: size * 0.8,
+                descent: -size * 0.2,
+                line_gap: 0.0,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextAlign {
+    #[default]
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextOptions {
+    pub align: TextAlign,
+    // lines wrap between words to stay within it, a word longer than the
+    // width is broken between characters
+    pub max_width: Option<f32>,
+    // multiplies the font's line height
+    pub line_spacing: f32,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        Self {
+            align: TextAlign::Left,
+            max_width: None,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+    pub glyph: char,
+    // pen position on the baseline, from the top left of the text with y
+    // going down
+    pub position: Vec2,
+    pub line: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TextLayout {
+    // whitespace is left out
+    pub glyphs: Vec<PositionedGlyph>,
+    pub line_count: usize,
+    pub size: Vec2,
+}
+
+fn measure(line: &[char], metrics: &dyn GlyphMetrics, size: f32) -> f32 {
+    let mut width = 0.0;
+    for (index, glyph) in line.iter().enumerate() {
+        if index > 0 {
+            width += metrics.get_kerning(line[index - 1], *glyph, size);
+        }
+        width += metrics.get_advance(*glyph, size);
+    }
+    width
+}
+
+// Splits a paragraph into lines no wider than `max_width`
+fn wrap(
+    paragraph: &str,
+    metrics: &dyn GlyphMetrics,
+    size: f32,
+    max_width: f32,
+    lines: &mut Vec<Vec<char>>,
+) {
+    let mut line: Vec<char> = Vec::new();
+    for word in paragraph.split(' ') {
+        let mut candidate = line.clone();
+        if !line.is_empty() {
+            candidate.push(' ');
+        }
+        candidate.extend(word.chars());
+        if !line.is_empty() && measure(&candidate, metrics, size) > max_width {
+            lines.push(std::mem::take(&mut line));
+            line.extend(word.chars());
+        } else {
+            line = candidate;
+        }
+
+        while line.len() > 1 && measure(&line, metrics, size) > max_width {
+            let mut fits = 1;
+            while fits + 1 < line.len() && measure(&line[..fits + 1], metrics, size) <= max_width {
+                fits += 1;
+            }
+            let rest = line.split_off(fits);
+            lines.push(std::mem::replace(&mut line, rest));
+        }
+    }
+    lines.push(line);
+}
+
+// Places the glyphs of `text` at `size` pixels, lines break at '\n' and
+// when they would be wider than the options' max width
+pub fn layout_text(
+    text: &str,
+    metrics: &dyn GlyphMetrics,
+    size: f32,
+    options: &TextOptions,
+) -> TextLayout {
+    let mut lines = Vec::new();
+    for paragraph in text.split('\n') {
+        match options.max_width {
+            Some(max_width) => wrap(paragraph, metrics, size, max_width, &mut lines),
+            None => lines.push(paragraph.chars().collect()),
+        }
+    }
+
+    let widths: Vec<f32> = lines
+        .iter()
+        .map(|line| measure(line, metrics, size))
+        .collect();
+    let width = options
+        .max_width
+        .unwrap_or_else(|| widths.iter().copied().fold(0.0, f32::max));
+    let line_metrics = metrics.get_line_metrics(size);
+    let line_height = line_metrics.get_line_height() * options.line_spacing;
+
+    let mut glyphs = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let mut x = match options.align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => (width - widths[index]) / 2.0,
+            TextAlign::Right => width - widths[index],
+        };
+        let y = line_metrics.ascent + line_height * index as f32;
+        let mut previous = None;
+        for glyph in line.iter().copied() {
+            if let Some(previous) = previous {
+                x += metrics.get_kerning(previous, glyph, size);
+            }
+            if !glyph.is_whitespace() {
+                glyphs.push(PositionedGlyph {
+                    glyph,
+                    position: Vec2::new(x, y),
+                    line: index,
+                });
+            }
+            x += metrics.get_advance(glyph, size);
+            previous = Some(glyph);
+        }
+    }
+
+    TextLayout {
+        glyphs,
+        line_count: lines.len(),
+        size: Vec2::new(width, line_height * lines.len() as f32),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct GlyphKey {
+    font: u64,
+    glyph: char,
+    size: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasGlyph {
+    // in fractions of the atlas
+    pub uv: Rect,
+    // of the bitmap in pixels
+    pub size: Vec2,
+    // bottom left of the bitmap from the pen position, y up
+    pub offset: Vec2,
+}
+
+// Rasterized glyphs packed into shelves of one texture, white with the
+// coverage in alpha so sprites tinted with the text color draw them. A full
+// atlas logs a warning and leaves out the glyphs that do not fit until it is
+// cleared.
+#[derive(Debug)]
+pub struct GlyphAtlas {
+    texture: Texture,
+    size: u32,
+    pixels: Vec<u8>,
+    // None for glyphs with nothing to draw or that did not fit
+    glyphs: HashMap<GlyphKey, Option<AtlasGlyph>>,
+    shelf_x: u32,
+    shelf_y: u32,
+    shelf_height: u32,
+    dirty: bool,
+}
+
+impl GlyphAtlas {
+    pub fn new(api: &mut dyn RendererAPI, size: u32) -> Result<Self, RendererErrors> {
+        let texture = api.create_texture(&TextureDescriptor {
+            label: "glyph atlas".to_string(),
+            width: size,
+            height: size,
+            format: TextureFormat::Rgba8Unorm,
+            render_target: false,
+            mip_levels: 1,
+        })?;
+        let mut atlas = Self {
+            texture,
+            size,
+            pixels: Vec::new(),
+            glyphs: HashMap::new(),
+            shelf_x: 0,
+            shelf_y: 0,
+            shelf_height: 0,
+            dirty: false,
+        };
+        atlas.clear();
+        Ok(atlas)
+    }
+
+    pub fn get_texture(&self) -> Texture {
+        self.texture
+    }
+
+    pub fn len(&self) -> usize {
+        self.glyphs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    // Forgets every glyph, they are rasterized again when next drawn
+    pub fn clear(&mut self) {
+        self.pixels = [255, 255, 255, 0].repeat((self.size * self.size) as usize);
+        self.glyphs.clear();
+        self.shelf_x = 0;
+        self.shelf_y = 0;
+        self.shelf_height = 0;
+        self.dirty = true;
+    }
+
+    // `size` is rounded to whole pixels so nearby sizes share glyphs
+    pub fn get_glyph(&mut self, font: &Font, glyph: char, size: f32) -> Option<AtlasGlyph> {
+        let key = GlyphKey {
+            font: font.get_id(),
+            glyph,
+            size: size.round().max(1.0) as u32,
+        };
+        if let Some(cached) = self.glyphs.get(&key) {
+            return *cached;
+        }
+        let (metrics, coverage) = font.font.rasterize(glyph, key.size as f32);
+        let placed = self
+            .insert(metrics.width as u32, metrics.height as u32, &coverage)
+            .map(|(uv, size)| AtlasGlyph {
+                uv,
+                size,
+                offset: Vec2::new(metrics.xmin as f32, metrics.ymin as f32),
+            });
+        self.glyphs.insert(key, placed);
+        placed
+    }
+
+    // Copies a coverage bitmap into the next free spot
+    fn insert(&mut self, width: u32, height: u32, coverage: &[u8]) -> Option<(Rect, Vec2)> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let (x, y) = self.allocate(width + GLYPH_PADDING, height + GLYPH_PADDING)?;
+        for row in 0..height {
+            for column in 0..width {
+                let pixel = ((y + row) * self.size + x + column) as usize * 4;
+                self.pixels[pixel + 3] = coverage[(row * width + column) as usize];
+            }
+        }
+        self.dirty = true;
+        let atlas = self.size as f32;
+        let uv = Rect::from_size(
+            Vec2::new(x as f32 / atlas, y as f32 / atlas),
+            Vec2::new(width as f32 / atlas, height as f32 / atlas),
+        );
+        Some((uv, Vec2::new(width as f32, height as f32)))
+    }
+
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if self.shelf_x + width > self.size {
+            self.shelf_y += self.shelf_height;
+            self.shelf_x = 0;
+            self.shelf_height = 0;
+        }
+        if width > self.size || self.shelf_y + height > self.size {
+            warn!("glyph atlas of {0}x{0} is full", self.size);
+            return None;
+        }
+        let position = (self.shelf_x, self.shelf_y);
+        self.shelf_x += width;
+        self.shelf_height = self.shelf_height.max(height);
+        Some(position)
+    }
+
+    // Writes the new glyphs to the texture, call before drawing with it
+    pub fn upload(&mut self, api: &mut dyn RendererAPI) -> Result<(), RendererErrors> {
+        if self.dirty {
+            api.write_texture(self.texture, &self.pixels)?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+
+    pub fn destroy(&mut self, api: &mut dyn RendererAPI) {
+        api.destroy(Resource::Texture(self.texture));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::api::headless::HeadlessRenderer;
+
+    // Every glyph 10 pixels wide, "AV" pulled together by 2
+    struct Monospace;
+
+    impl GlyphMetrics for Monospace {
+        fn get_advance(&self, _glyph: char, _size: f32) -> f32 {
+            10.0
+        }
+
+        fn get_kerning(&self, left: char, right: char, _size: f32) -> f32 {
+            match (left, right) {
+                ('A', 'V') => -2.0,
+                _ => 0.0,
+            }
+        }
+
+        fn get_line_metrics(&self, size: f32) -> LineMetrics {
+            LineMetrics {
+                ascent: size * 0.75,
+                descent: -size * 0.25,
+                line_gap: 0.0,
+            }
+        }
+    }
+
+    #[test]
+    fn test_layout_and_atlas() {
+        let options = TextOptions {
+            align: TextAlign::Right,
+            max_width: Some(60.0),
+            line_spacing: 1.0,
+        };
+        let layout = layout_text("AV go far\nabcdefgh", &Monospace, 20.0, &options);
+        // "AV go" fits, "far" wraps and the long word breaks at 6 glyphs
+        assert_eq!(layout.line_count, 4);
+        assert_eq!(layout.size, Vec2::new(60.0, 80.0));
+        let first: Vec<_> = layout.glyphs.iter().filter(|g| g.line == 0).collect();
+        assert_eq!(first[0].position, Vec2::new(12.0, 15.0));
+        assert_eq!(first[1].position.x, 20.0);
+        let far = layout.glyphs.iter().find(|g| g.glyph == 'f').unwrap();
+        assert_eq!(far.position, Vec2::new(30.0, 35.0));
+        let last = layout.glyphs.last().unwrap();
+        assert_eq!((last.glyph, last.line), ('h', 3));
+        assert_eq!(last.position.x, 50.0);
+
+        let centered = layout_text(
+            "ab\nabcd",
+            &Monospace,
+            20.0,
+            &TextOptions {
+                align: TextAlign::Center,
+                ..Default::default()
+            },
+        );
+        assert_eq!(centered.glyphs[0].position.x, 10.0);
+
+        let mut api = HeadlessRenderer::new();
+        let mut atlas = GlyphAtlas::new(&mut api, 16).unwrap();
+        let (uv, size) = atlas.insert(6, 4, &[200; 24]).unwrap();
+        assert_eq!(uv, Rect::new(Vec2::ZERO, Vec2::new(0.375, 0.25)));
+        assert_eq!(size, Vec2::new(6.0, 4.0));
+        // the next shelf starts below the tallest glyph with its padding
+        atlas.insert(8, 8, &[255; 64]).unwrap();
+        let (uv, _) = atlas.insert(4, 4, &[255; 16]).unwrap();
+        assert_eq!(uv.min, Vec2::new(0.0, 9.0 / 16.0));
+        assert_eq!(atlas.insert(16, 16, &[255; 256]), None);
+
+        atlas.upload(&mut api).unwrap();
+        let pixels = api.get_texture_pixels(atlas.get_texture()).unwrap();
+        assert_eq!(&pixels[..4], &[255, 255, 255, 200]);
+        assert_eq!(pixels[6 * 4 + 3], 0);
+    }
+}
@@ -0,0 +1,653 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use log::{info, warn};
+use thiserror::Error;
+
+use crate::assets::asset_cache::{AssetCache, AssetCacheErrors};
+
+use super::api::{
+    Binding, RendererAPI, RendererErrors, Resource, Sampler, Texture, TextureDescriptor,
+    TextureFormat,
+};
+
+// Bump when the decoded artifact layout or the mip filter changes
+const TEXTURE_IMPORTER_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum TextureErrors {
+    #[error("unable to read texture {0}: {1}")]
+    Io(String, String),
+
+    #[error("unable to decode texture: {0}")]
+    Decode(String),
+
+    #[error("unsupported texture: {0}")]
+    Unsupported(String),
+
+    #[error("{0}")]
+    Renderer(#[from] RendererErrors),
+
+    #[error("{0}")]
+    AssetCache(#[from] AssetCacheErrors),
+}
+
+// Told apart by the first bytes, not the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    Ktx2,
+}
+
+impl ImageFormat {
+    pub fn detect(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(Self::Png)
+        } else if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            Some(Self::Jpeg)
+        } else if bytes.starts_with(b"\xabKTX 20\xbb\r\n\x1a\n") {
+            Some(Self::Ktx2)
+        } else {
+            None
+        }
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    match value <= 0.04045 {
+        true => value / 12.92,
+        false => ((value + 0.055) / 1.055).powf(2.4),
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let value = match value <= 0.0031308 {
+        true => value * 12.92,
+        false => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+    };
+    (value * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+// Decoded pixels on the CPU, level 0 first and then the smaller mip levels
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub format: TextureFormat,
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl Image {
+    pub fn from_rgba(width: u32, height: u32, pixels: Vec<u8>) -> Result<Self, TextureErrors> {
+        if pixels.len() != width as usize * height as usize * 4 {
+            return Err(TextureErrors::Decode(format!(
+                "{} bytes for a {}x{} image",
+                pixels.len(),
+                width,
+                height
+            )));
+        }
+        Ok(Self {
+            width,
+            height,
+            format: TextureFormat::Rgba8Unorm,
+            levels: vec![pixels],
+        })
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, TextureErrors> {
+        match ImageFormat::detect(bytes) {
+            Some(ImageFormat::Png) => Self::decode_png(bytes),
+            Some(ImageFormat::Jpeg) => Self::decode_jpeg(bytes),
+            Some(ImageFormat::Ktx2) => Self::decode_ktx2(bytes),
+            None => Err(TextureErrors::Unsupported(
+                "not a png, jpeg or ktx2 file".to_string(),
+            )),
+        }
+    }
+
+    fn decode_png(bytes: &[u8]) -> Result<Self, TextureErrors> {
+        let decode = |err: png::DecodingError| TextureErrors::Decode(err.to_string());
+        let mut decoder = png::Decoder::new(bytes);
+        // palettes and 16 bit channels come out as plain 8 bit
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info().map_err(decode)?;
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).map_err(decode)?;
+        pixels.truncate(info.buffer_size());
+
+        let rgba = match info.color_type {
+            png::ColorType::Rgba => pixels,
+            png::ColorType::Rgb => pixels
+                .chunks_exact(3)
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => pixels
+                .chunks_exact(2)
+                .flat_map(|pixel| [pixel[0], pixel[0], pixel[0], pixel[1]])
+                .collect(),
+            png::ColorType::Grayscale => pixels
+                .iter()
+                .flat_map(|gray| [*gray, *gray, *gray, 255])
+                .collect(),
+            other => {
+                return Err(TextureErrors::Unsupported(format!(
+                    "png color type {:?}",
+                    other
+                )))
+            }
+        };
+        Self::from_rgba(info.width, info.height, rgba)
+    }
+
+    fn decode_jpeg(bytes: &[u8]) -> Result<Self, TextureErrors> {
+        let mut decoder = jpeg_decoder::Decoder::new(bytes);
+        let pixels = decoder
+            .decode()
+            .map_err(|err| TextureErrors::Decode(err.to_string()))?;
+        let info = decoder
+            .info()
+            .ok_or_else(|| TextureErrors::Decode("jpeg without a frame".to_string()))?;
+
+        let rgba = match info.pixel_format {
+            jpeg_decoder::PixelFormat::RGB24 => pixels
+                .chunks_exact(3)
+                .flat_map(|pixel| [pixel[0], pixel[1], pixel[2], 255])
+                .collect(),
+            jpeg_decoder::PixelFormat::L8 => pixels
+                .iter()
+                .flat_map(|gray| [*gray, *gray, *gray, 255])
+                .collect(),
+            other => {
+                return Err(TextureErrors::Unsupported(format!(
+                    "jpeg pixel format {:?}",
+                    other
+                )))
+            }
+        };
+        Self::from_rgba(info.width as u32, info.height as u32, rgba)
+    }
+
+    // Uncompressed 2D images only, the mip levels in the file are kept
+    fn decode_ktx2(bytes: &[u8]) -> Result<Self, TextureErrors> {
+        let reader =
+            ktx2::Reader::new(bytes).map_err(|err| TextureErrors::Decode(err.to_string()))?;
+        let header = reader.header();
+        if header.supercompression_scheme.is_some() {
+            return Err(TextureErrors::Unsupported(
+                "supercompressed ktx2".to_string(),
+            ));
+        }
+        if header.pixel_depth > 1 || header.layer_count > 1 || header.face_count > 1 {
+            return Err(TextureErrors::Unsupported(
+                "ktx2 arrays, cubemaps and 3D textures".to_string(),
+            ));
+        }
+        let format = match header.format {
+            Some(ktx2::Format::R8G8B8A8_UNORM) => TextureFormat::Rgba8Unorm,
+            Some(ktx2::Format::R8G8B8A8_SRGB) => TextureFormat::Rgba8UnormSrgb,
+            Some(ktx2::Format::B8G8R8A8_UNORM) => TextureFormat::Bgra8Unorm,
+            Some(ktx2::Format::B8G8R8A8_SRGB) => TextureFormat::Bgra8UnormSrgb,
+            Some(ktx2::Format::R8_UNORM) => TextureFormat::R8Unorm,
+            Some(ktx2::Format::R16G16B16A16_SFLOAT) => TextureFormat::Rgba16Float,
+            other => {
+                return Err(TextureErrors::Unsupported(format!(
+                    "ktx2 format {:?}",
+                    other
+                )))
+            }
+        };
+
+        let image = Self {
+            width: header.pixel_width,
+            height: header.pixel_height,
+            format,
+            levels: reader.levels().map(|level| level.data.to_vec()).collect(),
+        };
+        for (level, pixels) in image.levels.iter().enumerate() {
+            if pixels.len() as u64 != image.get_descriptor("").get_mip_byte_size(level as u32) {
+                return Err(TextureErrors::Decode(format!(
+                    "ktx2 level {} has the wrong size",
+                    level
+                )));
+            }
+        }
+        Ok(image)
+    }
+
+    pub fn is_srgb(&self) -> bool {
+        matches!(
+            self.format,
+            TextureFormat::Rgba8UnormSrgb | TextureFormat::Bgra8UnormSrgb
+        )
+    }
+
+    // Colors authored for the screen are sRGB, data like normal maps is not.
+    // Only switches how the GPU reads the bytes.
+    pub fn set_srgb(&mut self, srgb: bool) {
+        self.format = match (self.format, srgb) {
+            (TextureFormat::Rgba8Unorm, true) => TextureFormat::Rgba8UnormSrgb,
+            (TextureFormat::Rgba8UnormSrgb, false) => TextureFormat::Rgba8Unorm,
+            (TextureFormat::Bgra8Unorm, true) => TextureFormat::Bgra8UnormSrgb,
+            (TextureFormat::Bgra8UnormSrgb, false) => TextureFormat::Bgra8Unorm,
+            (format, _) => format,
+        };
+    }
+
+    pub fn get_descriptor(&self, label: &str) -> TextureDescriptor {
+        TextureDescriptor {
+            label: label.to_string(),
+            width: self.width,
+            height: self.height,
+            format: self.format,
+            render_target: false,
+            mip_levels: self.levels.len().max(1) as u32,
+        }
+    }
+
+    // Box filters the full chain down to 1x1 from level 0. sRGB images are
+    // averaged in linear space, otherwise the mips come out too dark.
+    pub fn generate_mips(&mut self) {
+        let channels = match self.format {
+            TextureFormat::Rgba8Unorm
+            | TextureFormat::Rgba8UnormSrgb
+            | TextureFormat::Bgra8Unorm
+            | TextureFormat::Bgra8UnormSrgb => 4,
+            TextureFormat::R8Unorm => 1,
+            format => {
+                warn!("no mipmaps for {:?} textures", format);
+                return;
+            }
+        };
+        let srgb = self.is_srgb();
+        let desc = self.get_descriptor("");
+        self.levels.truncate(1);
+
+        for level in 1..TextureDescriptor::get_max_mip_levels(self.width, self.height) {
+            let (src_width, src_height) = desc.get_mip_size(level - 1);
+            let (width, height) = desc.get_mip_size(level);
+            let src = &self.levels[level as usize - 1];
+            let mut pixels = Vec::with_capacity((width * height) as usize * channels);
+            for y in 0..height {
+                for x in 0..width {
+                    let corners = [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(dx, dy)| {
+                        let sx = (x * 2 + dx).min(src_width - 1);
+                        let sy = (y * 2 + dy).min(src_height - 1);
+                        (sy * src_width + sx) as usize * channels
+                    });
+                    for channel in 0..channels {
+                        // alpha is always linear
+                        let linear = srgb && channel != 3;
+                        let sum: f32 = corners
+                            .iter()
+                            .map(|offset| {
+                                let value = src[offset + channel];
+                                match linear {
+                                    true => srgb_to_linear(value),
+                                    false => value as f32 / 255.0,
+                                }
+                            })
+                            .sum();
+                        let average = sum / 4.0;
+                        pixels.push(match linear {
+                            true => linear_to_srgb(average),
+                            false => (average * 255.0).round() as u8,
+                        });
+                    }
+                }
+            }
+            self.levels.push(pixels);
+        }
+    }
+
+    // The artifact kept in the asset cache
+    fn to_bytes(&self) -> Vec<u8> {
+        let format = TextureFormats::ALL
+            .iter()
+            .position(|format| *format == self.format)
+            .unwrap_or(0) as u8;
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&self.width.to_le_bytes());
+        bytes.extend_from_slice(&self.height.to_le_bytes());
+        bytes.push(format);
+        for level in self.levels.iter() {
+            bytes.extend_from_slice(&(level.len() as u64).to_le_bytes());
+            bytes.extend_from_slice(level);
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let read_u32 =
+            |at: usize| Some(u32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+        let mut image = Self {
+            width: read_u32(0)?,
+            height: read_u32(4)?,
+            format: *TextureFormats::ALL.get(*bytes.get(8)? as usize)?,
+            levels: Vec::new(),
+        };
+        let mut at = 9;
+        while at < bytes.len() {
+            let len = u64::from_le_bytes(bytes.get(at..at + 8)?.try_into().ok()?) as usize;
+            image.levels.push(bytes.get(at + 8..at + 8 + len)?.to_vec());
+            at += 8 + len;
+        }
+        Some(image)
+    }
+}
+
+struct TextureFormats;
+
+impl TextureFormats {
+    // Order is part of the cached artifact
+    const ALL: [TextureFormat; 7] = [
+        TextureFormat::Rgba8Unorm,
+        TextureFormat::Rgba8UnormSrgb,
+        TextureFormat::Bgra8Unorm,
+        TextureFormat::Bgra8UnormSrgb,
+        TextureFormat::R8Unorm,
+        TextureFormat::Rgba16Float,
+        TextureFormat::Depth32Float,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TextureOptions {
+    // false for data textures like normal and roughness maps
+    pub srgb: bool,
+    // files that bring their own mip levels keep them
+    pub generate_mips: bool,
+    pub sampler: Sampler,
+}
+
+impl Default for TextureOptions {
+    fn default() -> Self {
+        Self {
+            srgb: true,
+            generate_mips: true,
+            sampler: Sampler::default(),
+        }
+    }
+}
+
+// A sampled 2D texture on the GPU with the sampler it is drawn with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Texture2D {
+    handle: Texture,
+    desc: TextureDescriptor,
+    sampler: Sampler,
+}
+
+impl Texture2D {
+    pub fn from_image(
+        label: &str,
+        image: &Image,
+        sampler: Sampler,
+        api: &mut dyn RendererAPI,
+    ) -> Result<Self, TextureErrors> {
+        let desc = image.get_descriptor(label);
+        let handle = api.create_texture(&desc)?;
+        for (level, pixels) in image.levels.iter().enumerate() {
+            if let Err(err) = api.write_texture_mip(handle, level as u32, pixels) {
+                api.destroy(Resource::Texture(handle));
+                return Err(err.into());
+            }
+        }
+        Ok(Self {
+            handle,
+            desc,
+            sampler,
+        })
+    }
+
+    pub fn from_bytes(
+        label: &str,
+        bytes: &[u8],
+        options: &TextureOptions,
+        api: &mut dyn RendererAPI,
+    ) -> Result<Self, TextureErrors> {
+        let mut image = Image::decode(bytes)?;
+        image.set_srgb(options.srgb);
+        if options.generate_mips && image.levels.len() == 1 {
+            image.generate_mips();
+        }
+        Self::from_image(label, &image, options.sampler, api)
+    }
+
+    pub fn from_file(
+        path: impl AsRef<Path>,
+        options: &TextureOptions,
+        api: &mut dyn RendererAPI,
+    ) -> Result<Self, TextureErrors> {
+        let path = path.as_ref();
+        let label = path.display().to_string();
+        let bytes =
+            fs::read(path).map_err(|err| TextureErrors::Io(label.clone(), err.to_string()))?;
+        Self::from_bytes(&label, &bytes, options, api)
+    }
+
+    pub fn get_handle(&self) -> Texture {
+        self.handle
+    }
+
+    pub fn get_size(&self) -> (u32, u32) {
+        (self.desc.width, self.desc.height)
+    }
+
+    pub fn get_format(&self) -> TextureFormat {
+        self.desc.format
+    }
+
+    pub fn get_mip_levels(&self) -> u32 {
+        self.desc.mip_levels
+    }
+
+    pub fn get_sampler(&self) -> Sampler {
+        self.sampler
+    }
+
+    pub fn set_sampler(&mut self, sampler: Sampler) {
+        self.sampler = sampler;
+    }
+
+    // The texture and its sampler, for two consecutive bindings
+    pub fn get_bindings(&self) -> [Binding; 2] {
+        [
+            Binding::Texture(self.handle),
+            Binding::Sampler(self.sampler),
+        ]
+    }
+
+    pub fn destroy(self, api: &mut dyn RendererAPI) {
+        api.destroy(Resource::Texture(self.handle));
+    }
+}
+
+// Loads every file once per set of options and hands out shared references.
+// A texture stays on the GPU while anything holds its Arc, `collect` frees
+// the ones nobody uses anymore. With an asset cache the decoded and mipped
+// pixels are kept on disk so the next run skips decoding.
+#[derive(Debug, Default)]
+pub struct TextureManager {
+    textures: HashMap<(PathBuf, TextureOptions), Arc<Texture2D>>,
+    asset_cache: Option<AssetCache>,
+}
+
+impl TextureManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_asset_cache(asset_cache: AssetCache) -> Self {
+        Self {
+            textures: HashMap::new(),
+            asset_cache: Some(asset_cache),
+        }
+    }
+
+    fn decode(&self, bytes: &[u8], options: &TextureOptions) -> Result<Image, TextureErrors> {
+        let import = |bytes: &[u8]| {
+            let mut image = Image::decode(bytes)?;
+            if options.generate_mips && image.levels.len() == 1 {
+                image.generate_mips();
+            }
+            Ok::<Image, TextureErrors>(image)
+        };
+        let Some(asset_cache) = self.asset_cache.as_ref() else {
+            return import(bytes);
+        };
+        // sRGB only changes the format, the same artifact serves both
+        let importer = match options.generate_mips {
+            true => "texture",
+            false => "texture-nomips",
+        };
+        let artifact =
+            asset_cache.get_or_import(bytes, importer, TEXTURE_IMPORTER_VERSION, |bytes| {
+                import(bytes)
+                    .map(|image| image.to_bytes())
+                    .map_err(|err| err.to_string())
+            })?;
+        Image::from_bytes(&artifact)
+            .ok_or_else(|| TextureErrors::Decode("broken texture artifact".to_string()))
+    }
+
+    pub fn load(
+        &mut self,
+        path: impl AsRef<Path>,
+        options: &TextureOptions,
+        api: &mut dyn RendererAPI,
+    ) -> Result<Arc<Texture2D>, TextureErrors> {
+        let path = path.as_ref();
+        let key = (path.to_path_buf(), *options);
+        if let Some(texture) = self.textures.get(&key) {
+            return Ok(Arc::clone(texture));
+        }
+
+        let label = path.display().to_string();
+        let bytes =
+            fs::read(path).map_err(|err| TextureErrors::Io(label.clone(), err.to_string()))?;
+        let mut image = self.decode(&bytes, options)?;
+        image.set_srgb(options.srgb);
+        let texture = Arc::new(Texture2D::from_image(&label, &image, options.sampler, api)?);
+        info!(
+            "loaded texture {} ({}x{}, {} mips)",
+            label,
+            image.width,
+            image.height,
+            image.levels.len()
+        );
+        self.textures.insert(key, Arc::clone(&texture));
+        Ok(texture)
+    }
+
+    // References held outside the manager
+    pub fn get_ref_count(&self, path: impl AsRef<Path>, options: &TextureOptions) -> usize {
+        self.textures
+            .get(&(path.as_ref().to_path_buf(), *options))
+            .map_or(0, |texture| Arc::strong_count(texture) - 1)
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+
+    // Destroys the textures nobody holds, returns how many
+    pub fn collect(&mut self, api: &mut dyn RendererAPI) -> usize {
+        let unused: Vec<(PathBuf, TextureOptions)> = self
+            .textures
+            .iter()
+            .filter(|(_, texture)| Arc::strong_count(texture) == 1)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in unused.iter() {
+            if let Some(texture) = self.textures.remove(key) {
+                api.destroy(Resource::Texture(texture.get_handle()));
+            }
+        }
+        unused.len()
+    }
+
+    // Destroys every texture, references still held outside dangle
+    pub fn clear(&mut self, api: &mut dyn RendererAPI) {
+        for (_, texture) in self.textures.drain() {
+            api.destroy(Resource::Texture(texture.get_handle()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::api::headless::HeadlessRenderer;
+
+    fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(rgba).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_load_mips_and_ref_counts() {
+        let dir = std::env::temp_dir().join(format!("aloy-texture-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        // black and white columns average to mid grey, lighter in sRGB
+        let rgba: Vec<u8> = (0..8)
+            .flat_map(|pixel| match pixel % 2 {
+                0 => [0, 0, 0, 255],
+                _ => [255, 255, 255, 255],
+            })
+            .collect();
+        let path = dir.join("checker.png");
+        fs::write(&path, encode_png(4, 2, &rgba)).unwrap();
+
+        let mut api = HeadlessRenderer::new();
+        let mut manager = TextureManager::with_asset_cache(AssetCache::new(dir.join("cache")));
+        let options = TextureOptions::default();
+        let texture = manager.load(&path, &options, &mut api).unwrap();
+        let again = manager.load(&path, &options, &mut api).unwrap();
+        assert_eq!(texture.get_handle(), again.get_handle());
+        assert_eq!(texture.get_mip_levels(), 3);
+        assert_eq!(texture.get_format(), TextureFormat::Rgba8UnormSrgb);
+        assert_eq!(manager.get_ref_count(&path, &options), 2);
+
+        let mut image = Image::from_rgba(4, 2, rgba).unwrap();
+        image.generate_mips();
+        assert_eq!(&image.levels[1][..4], &[128, 128, 128, 255]);
+        image.set_srgb(true);
+        image.generate_mips();
+        assert_eq!(&image.levels[1][..4], &[188, 188, 188, 255]);
+        assert_eq!(image.levels[2].len(), 4);
+
+        // the second manager reads the artifact instead of decoding
+        let mut cached = TextureManager::with_asset_cache(AssetCache::new(dir.join("cache")));
+        let data = TextureOptions {
+            srgb: false,
+            ..options
+        };
+        let linear = cached.load(&path, &data, &mut api).unwrap();
+        assert_eq!(linear.get_format(), TextureFormat::Rgba8Unorm);
+        assert_eq!(linear.get_mip_levels(), 3);
+
+        drop((texture, again, linear));
+        assert_eq!(manager.collect(&mut api), 1);
+        cached.clear(&mut api);
+        assert_eq!(api.get_resource_count(), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,431 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    core::bug_report::Screenshot,
+    math::{rect::Rect, vector::Vec2},
+};
+
+use super::{
+    api::{RendererAPI, Sampler, TextureFormat},
+    texture::{Image, Texture2D, TextureErrors},
+};
+
+#[derive(Debug, Error)]
+pub enum AtlasErrors {
+    #[error("unable to access atlas {0}: {1}")]
+    Io(String, String),
+
+    #[error("unable to parse atlas layout {0}: {1}")]
+    Parse(String, String),
+
+    #[error("unable to fit {0} into a {1}x{1} atlas")]
+    TooLarge(String, u32),
+
+    #[error("{0}")]
+    Texture(#[from] TextureErrors),
+}
+
+// Where one image ended up, in pixels of the atlas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AtlasRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// The lookup table written next to an atlas packed offline
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct AtlasLayout {
+    pub width: u32,
+    pub height: u32,
+    pub regions: BTreeMap<String, AtlasRegion>,
+}
+
+impl AtlasLayout {
+    // Fractions of the atlas size, what `Renderer2D::draw_sprite` takes
+    pub fn get_uv(&self, name: &str) -> Option<Rect> {
+        let region = self.regions.get(name)?;
+        let size = Vec2::new(self.width.max(1) as f32, self.height.max(1) as f32);
+        let min = Vec2::new(region.x as f32 / size.x, region.y as f32 / size.y);
+        let max = Vec2::new(
+            (region.x + region.width) as f32 / size.x,
+            (region.y + region.height) as f32 / size.y,
+        );
+        Some(Rect::new(min, max))
+    }
+}
+
+// The pixels of a packed atlas and where each image is in them
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedAtlas {
+    pub image: Image,
+    pub layout: AtlasLayout,
+}
+
+fn layout_path(png: &Path) -> PathBuf {
+    png.with_extension("ron")
+}
+
+impl PackedAtlas {
+    // Writes `path` as a png and the layout next to it as `.ron`, for
+    // atlases packed offline by a build step
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), AtlasErrors> {
+        let path = path.as_ref();
+        let io = |path: &Path, err: String| AtlasErrors::Io(path.display().to_string(), err);
+        let png = Screenshot::new(
+            self.image.width,
+            self.image.height,
+            self.image.levels[0].clone(),
+        )
+        .and_then(|image| image.encode_png())
+        .map_err(|err| io(path, err.to_string()))?;
+        let layout = ron::ser::to_string_pretty(&self.layout, ron::ser::PrettyConfig::default())
+            .map_err(|err| io(path, err.to_string()))?;
+        fs::write(path, png).map_err(|err| io(path, err.to_string()))?;
+        let path = layout_path(path);
+        fs::write(&path, layout).map_err(|err| io(&path, err.to_string()))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, AtlasErrors> {
+        let path = path.as_ref();
+        let read = |path: &Path| {
+            fs::read(path)
+                .map_err(|err| AtlasErrors::Io(path.display().to_string(), err.to_string()))
+        };
+        let image = Image::decode(&read(path)?)?;
+        let layout_path = layout_path(path);
+        let layout: AtlasLayout = ron::de::from_bytes(&read(&layout_path)?).map_err(|err| {
+            AtlasErrors::Parse(layout_path.display().to_string(), err.to_string())
+        })?;
+        Ok(Self { image, layout })
+    }
+}
+
+// Packs many small images into one texture so sprites drawn from them share
+// a batch of `Renderer2D`. Images are placed on shelves, tallest first, in
+// the smallest power of two sized atlas they fit in.
+//
+//     let mut builder = AtlasBuilder::new(2048);
+//     builder.add_file("sprites/player.png")?;
+//     builder.add("coin", coin_image);
+//     let atlas = TextureAtlas::upload("sprites", &builder.build()?, sampler, api)?;
+//     renderer.add_atlas(&atlas);
+#[derive(Debug, Clone)]
+pub struct AtlasBuilder {
+    max_size: u32,
+    padding: u32,
+    images: Vec<(String, Image)>,
+}
+
+impl AtlasBuilder {
+    pub fn new(max_size: u32) -> Self {
+        Self {
+            max_size: max_size.max(1),
+            padding: 1,
+            images: Vec::new(),
+        }
+    }
+
+    // Pixels around each image filled with its edge, so filtering and mips
+    // do not pull in the neighbours. 1 by default.
+    pub fn set_padding(&mut self, padding: u32) {
+        self.padding = padding;
+    }
+
+    // A later image with the same name replaces the earlier one
+    pub fn add(&mut self, name: &str, image: Image) {
+        self.images.retain(|(other, _)| other != name);
+        self.images.push((name.to_string(), image));
+    }
+
+    // Named after the file stem, "sprites/player.png" is "player"
+    pub fn add_file(&mut self, path: impl AsRef<Path>) -> Result<(), AtlasErrors> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)
+            .map_err(|err| AtlasErrors::Io(path.display().to_string(), err.to_string()))?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.add(&name, Image::decode(&bytes)?);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    pub fn build(&self) -> Result<PackedAtlas, AtlasErrors> {
+        if let Some((name, image)) = self.images.iter().find(|(_, image)| {
+            !matches!(
+                image.format,
+                TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb
+            )
+        }) {
+            return Err(TextureErrors::Unsupported(format!(
+                "{} is {:?}, atlases take rgba8",
+                name, image.format
+            ))
+            .into());
+        }
+
+        let sizes: Vec<(u32, u32)> = self
+            .images
+            .iter()
+            .map(|(_, image)| {
+                (
+                    image.width + 2 * self.padding,
+                    image.height + 2 * self.padding,
+                )
+            })
+            .collect();
+        if let Some(index) = sizes
+            .iter()
+            .position(|(width, height)| *width > self.max_size || *height > self.max_size)
+        {
+            return Err(AtlasErrors::TooLarge(
+                self.images[index].0.clone(),
+                self.max_size,
+            ));
+        }
+        let mut order: Vec<usize> = (0..sizes.len()).collect();
+        order.sort_by_key(|index| {
+            let (width, height) = sizes[*index];
+            std::cmp::Reverse((height, width))
+        });
+
+        let area: u64 = sizes.iter().map(|(w, h)| *w as u64 * *h as u64).sum();
+        let side = ((area as f64).sqrt().ceil() as u32).next_power_of_two();
+        let (mut width, mut height) = (side.min(self.max_size), side.min(self.max_size));
+        let positions = loop {
+            if let Some(positions) = pack_shelves(&sizes, &order, width, height) {
+                break positions;
+            }
+            if width >= self.max_size && height >= self.max_size {
+                return Err(AtlasErrors::TooLarge(
+                    format!("{} images", self.images.len()),
+                    self.max_size,
+                ));
+            }
+            if width <= height && width < self.max_size {
+                width *= 2;
+            } else {
+                height *= 2;
+            }
+            width = width.min(self.max_size);
+            height = height.min(self.max_size);
+        };
+
+        let mut pixels = vec![0; width as usize * height as usize * 4];
+        let mut layout = AtlasLayout {
+            width,
+            height,
+            regions: BTreeMap::new(),
+        };
+        let srgb = self
+            .images
+            .iter()
+            .any(|(_, image)| image.format == TextureFormat::Rgba8UnormSrgb);
+        for ((name, image), (x, y)) in self.images.iter().zip(positions) {
+            let region = AtlasRegion {
+                x: x + self.padding,
+                y: y + self.padding,
+                width: image.width,
+                height: image.height,
+            };
+            blit_padded(&mut pixels, width, image, region, self.padding);
+            layout.regions.insert(name.clone(), region);
+        }
+        info!(
+            "packed {} images into a {}x{} atlas",
+            self.images.len(),
+            width,
+            height
+        );
+
+        let mut image = Image::from_rgba(width, height, pixels)?;
+        image.set_srgb(srgb);
+        Ok(PackedAtlas { image, layout })
+    }
+}
+
+// Top left corners of the padded images, in the order of `sizes`
+fn pack_shelves(
+    sizes: &[(u32, u32)],
+    order: &[usize],
+    width: u32,
+    height: u32,
+) -> Option<Vec<(u32, u32)>> {
+    let mut positions = vec![(0, 0); sizes.len()];
+    let (mut shelf_x, mut shelf_y, mut shelf_height) = (0, 0, 0);
+    for index in order.iter().copied() {
+        let (w, h) = sizes[index];
+        if shelf_x + w > width {
+            shelf_y += shelf_height;
+            shelf_x = 0;
+            shelf_height = 0;
+        }
+        if w > width || shelf_y + h > height {
+            return None;
+        }
+        positions[index] = (shelf_x, shelf_y);
+        shelf_x += w;
+        shelf_height = shelf_height.max(h);
+    }
+    Some(positions)
+}
+
+// Copies the image into `region` and repeats its edge pixels over the
+// padding around it
+fn blit_padded(
+    pixels: &mut [u8],
+    atlas_width: u32,
+    image: &Image,
+    region: AtlasRegion,
+    padding: u32,
+) {
+    let source = &image.levels[0];
+    let padding = padding as i64;
+    for y in -padding..region.height as i64 + padding {
+        for x in -padding..region.width as i64 + padding {
+            let source_x = x.clamp(0, region.width as i64 - 1) as usize;
+            let source_y = y.clamp(0, region.height as i64 - 1) as usize;
+            let from = (source_y * image.width as usize + source_x) * 4;
+            let to_x = (region.x as i64 + x) as usize;
+            let to_y = (region.y as i64 + y) as usize;
+            let to = (to_y * atlas_width as usize + to_x) * 4;
+            pixels[to..to + 4].copy_from_slice(&source[from..from + 4]);
+        }
+    }
+}
+
+// A packed atlas on the GPU with its lookup table
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextureAtlas {
+    texture: Texture2D,
+    uvs: HashMap<String, Rect>,
+}
+
+impl TextureAtlas {
+    pub fn upload(
+        label: &str,
+        atlas: &PackedAtlas,
+        sampler: Sampler,
+        api: &mut dyn RendererAPI,
+    ) -> Result<Self, AtlasErrors> {
+        let texture = Texture2D::from_image(label, &atlas.image, sampler, api)?;
+        let uvs = atlas
+            .layout
+            .regions
+            .keys()
+            .filter_map(|name| Some((name.clone(), atlas.layout.get_uv(name)?)))
+            .collect();
+        Ok(Self { texture, uvs })
+    }
+
+    pub fn get_texture(&self) -> &Texture2D {
+        &self.texture
+    }
+
+    pub fn get_uv(&self, name: &str) -> Option<Rect> {
+        self.uvs.get(name).copied()
+    }
+
+    pub fn get_names(&self) -> impl Iterator<Item = &str> {
+        self.uvs.keys().map(|name| name.as_str())
+    }
+
+    pub fn destroy(self, api: &mut dyn RendererAPI) {
+        self.texture.destroy(api);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::{
+        math::{
+            transform::{Mat4, Transform},
+            vector::Vec4,
+        },
+        renderer::{
+            api::{headless::HeadlessRenderer, RenderPass, RenderTarget},
+            renderer2d::{Renderer2D, Renderer2DStats},
+        },
+    };
+
+    fn solid(width: u32, height: u32, value: u8) -> Image {
+        Image::from_rgba(width, height, vec![value; (width * height * 4) as usize]).unwrap()
+    }
+
+    #[test]
+    fn test_pack_and_draw_from_atlas() {
+        let mut builder = AtlasBuilder::new(64);
+        builder.add("wide", solid(30, 10, 1));
+        builder.add("tall", solid(10, 30, 2));
+        builder.add("small", solid(4, 4, 3));
+        let packed = builder.build().unwrap();
+        let layout = &packed.layout;
+        assert_eq!((layout.width, layout.height), (64, 32));
+
+        // padding repeats the edge and no two regions overlap
+        let tall = layout.regions["tall"];
+        assert_eq!((tall.x, tall.y), (1, 1));
+        let at = |x: u32, y: u32| packed.image.levels[0][((y * 64 + x) * 4) as usize];
+        assert_eq!(at(0, 0), 2);
+        let regions: Vec<AtlasRegion> = layout.regions.values().copied().collect();
+        for (index, a) in regions.iter().enumerate() {
+            for b in regions[index + 1..].iter() {
+                assert!(
+                    a.x + a.width <= b.x
+                        || b.x + b.width <= a.x
+                        || a.y + a.height <= b.y
+                        || b.y + b.height <= a.y
+                );
+            }
+        }
+
+        let path = env::temp_dir().join(format!("aloy-atlas-test-{}.png", std::process::id()));
+        packed.save(&path).unwrap();
+        let loaded = PackedAtlas::load(&path).unwrap();
+        assert_eq!(&loaded.layout, layout);
+        let _ = fs::remove_file(layout_path(&path));
+        let _ = fs::remove_file(&path);
+
+        let mut api = HeadlessRenderer::new();
+        let atlas = TextureAtlas::upload("sprites", &loaded, Sampler::default(), &mut api).unwrap();
+        assert_eq!(atlas.get_uv("small"), layout.get_uv("small"),);
+        let mut renderer = Renderer2D::new(&mut api).unwrap();
+        renderer.add_atlas(&atlas);
+        renderer.begin_scene(&Mat4::IDENTITY);
+        for name in ["wide", "tall", "small"] {
+            assert!(renderer.draw_atlas_sprite(name, &Transform::IDENTITY, Vec4::splat(1.0)));
+        }
+        assert!(!renderer.draw_atlas_sprite("missing", &Transform::IDENTITY, Vec4::splat(1.0)));
+        let mut pass = RenderPass::new("world", RenderTarget::Surface);
+        renderer.end_scene(&mut api, &mut pass).unwrap();
+        assert_eq!(
+            renderer.get_stats(),
+            Renderer2DStats {
+                draw_calls: 1,
+                quads: 3,
+                triangles: 0
+            }
+        );
+    }
+}
@@ -0,0 +1,3 @@
+pub mod path;
+pub mod svg;
+pub mod tessellate;
@@ -0,0 +1,326 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use crate::math::vector::Vec2;
+
+// Control point distance that makes a cubic follow a quarter circle
+const KAPPA: f32 = 0.552_284_8;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathCommand {
+    MoveTo(Vec2),
+    LineTo(Vec2),
+    QuadTo(Vec2, Vec2),
+    CubicTo(Vec2, Vec2, Vec2),
+    Close,
+}
+
+// A polyline made of straight segments, what curves turn into before
+// tessellation
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Contour {
+    pub points: Vec<Vec2>,
+    pub closed: bool,
+}
+
+// Outlines built from lines and bezier curves, in the same units as the
+// sprites they are drawn with. Every `move_to` starts a new sub path.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Path {
+    commands: Vec<PathCommand>,
+    current: Vec2,
+    start: Vec2,
+}
+
+impl Path {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_commands(&self) -> &[PathCommand] {
+        &self.commands
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    // Where the next segment starts
+    pub fn get_current(&self) -> Vec2 {
+        self.current
+    }
+
+    pub fn move_to(&mut self, to: Vec2) -> &mut Self {
+        self.commands.push(PathCommand::MoveTo(to));
+        self.current = to;
+        self.start = to;
+        self
+    }
+
+    pub fn line_to(&mut self, to: Vec2) -> &mut Self {
+        self.commands.push(PathCommand::LineTo(to));
+        self.current = to;
+        self
+    }
+
+    pub fn quad_to(&mut self, control: Vec2, to: Vec2) -> &mut Self {
+        self.commands.push(PathCommand::QuadTo(control, to));
+        self.current = to;
+        self
+    }
+
+    pub fn cubic_to(&mut self, control1: Vec2, control2: Vec2, to: Vec2) -> &mut Self {
+        self.commands
+            .push(PathCommand::CubicTo(control1, control2, to));
+        self.current = to;
+        self
+    }
+
+    pub fn close(&mut self) -> &mut Self {
+        self.commands.push(PathCommand::Close);
+        self.current = self.start;
+        self
+    }
+
+    // Elliptical arc as SVG describes it: from the current point to `to`,
+    // the flags pick one of the four arcs that fit
+    pub fn arc_to(
+        &mut self,
+        radii: Vec2,
+        rotation: f32,
+        large_arc: bool,
+        sweep: bool,
+        to: Vec2,
+    ) -> &mut Self {
+        let from = self.current;
+        let (mut rx, mut ry) = (radii.x.abs(), radii.y.abs());
+        if from == to {
+            return self;
+        }
+        if rx == 0.0 || ry == 0.0 {
+            return self.line_to(to);
+        }
+
+        // endpoint to center parameterization, SVG 1.1 appendix F.6.5
+        let (sin, cos) = rotation.sin_cos();
+        let half = (from - to) * 0.5;
+        let x1 = cos * half.x + sin * half.y;
+        let y1 = -sin * half.x + cos * half.y;
+        let scale = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
+        if scale > 1.0 {
+            rx *= scale.sqrt();
+            ry *= scale.sqrt();
+        }
+        let numerator = rx * rx * ry * ry - rx * rx * y1 * y1 - ry * ry * x1 * x1;
+        let denominator = rx * rx * y1 * y1 + ry * ry * x1 * x1;
+        let mut factor = (numerator / denominator).max(0.0).sqrt();
+        if large_arc == sweep {
+            factor = -factor;
+        }
+        let cx1 = factor * rx * y1 / ry;
+        let cy1 = -factor * ry * x1 / rx;
+        let middle = (from + to) * 0.5;
+        let center = Vec2::new(
+            cos * cx1 - sin * cy1 + middle.x,
+            sin * cx1 + cos * cy1 + middle.y,
+        );
+
+        let angle = |x: f32, y: f32| y.atan2(x);
+        let start = angle((x1 - cx1) / rx, (y1 - cy1) / ry);
+        let end = angle((-x1 - cx1) / rx, (-y1 - cy1) / ry);
+        let mut delta = end - start;
+        if sweep && delta < 0.0 {
+            delta += TAU;
+        } else if !sweep && delta > 0.0 {
+            delta -= TAU;
+        }
+
+        // one cubic per quarter turn at most
+        let segments = (delta.abs() / FRAC_PI_2).ceil().max(1.0) as usize;
+        let step = delta / segments as f32;
+        let handle = 4.0 / 3.0 * (step / 4.0).tan();
+        let point = |theta: f32| {
+            let (s, c) = theta.sin_cos();
+            let (x, y) = (rx * c, ry * s);
+            Vec2::new(cos * x - sin * y, sin * x + cos * y) + center
+        };
+        let tangent = |theta: f32| {
+            let (s, c) = theta.sin_cos();
+            let (x, y) = (-rx * s, ry * c);
+            Vec2::new(cos * x - sin * y, sin * x + cos * y)
+        };
+        for segment in 0..segments {
+            let theta1 = start + step * segment as f32;
+            let theta2 = theta1 + step;
+            let end = match segment + 1 == segments {
+                true => to,
+                false => point(theta2),
+            };
+            self.cubic_to(
+                point(theta1) + tangent(theta1) * handle,
+                point(theta2) - tangent(theta2) * handle,
+                end,
+            );
+        }
+        self
+    }
+
+    pub fn rect(&mut self, min: Vec2, max: Vec2) -> &mut Self {
+        self.move_to(min)
+            .line_to(Vec2::new(max.x, min.y))
+            .line_to(max)
+            .line_to(Vec2::new(min.x, max.y))
+            .close()
+    }
+
+    pub fn ellipse(&mut self, center: Vec2, radii: Vec2) -> &mut Self {
+        let (rx, ry) = (radii.x, radii.y);
+        let (kx, ky) = (rx * KAPPA, ry * KAPPA);
+        let at = |x: f32, y: f32| center + Vec2::new(x, y);
+        self.move_to(at(rx, 0.0))
+            .cubic_to(at(rx, ky), at(kx, ry), at(0.0, ry))
+            .cubic_to(at(-kx, ry), at(-rx, ky), at(-rx, 0.0))
+            .cubic_to(at(-rx, -ky), at(-kx, -ry), at(0.0, -ry))
+            .cubic_to(at(kx, -ry), at(rx, -ky), at(rx, 0.0))
+            .close()
+    }
+
+    pub fn circle(&mut self, center: Vec2, radius: f32) -> &mut Self {
+        self.ellipse(center, Vec2::splat(radius))
+    }
+
+    // The same outline with every point moved, exact for affine transforms
+    pub fn map_points(&self, map: impl Fn(Vec2) -> Vec2) -> Self {
+        let commands = self
+            .commands
+            .iter()
+            .map(|command| match *command {
+                PathCommand::MoveTo(to) => PathCommand::MoveTo(map(to)),
+                PathCommand::LineTo(to) => PathCommand::LineTo(map(to)),
+                PathCommand::QuadTo(control, to) => PathCommand::QuadTo(map(control), map(to)),
+                PathCommand::CubicTo(control1, control2, to) => {
+                    PathCommand::CubicTo(map(control1), map(control2), map(to))
+                }
+                PathCommand::Close => PathCommand::Close,
+            })
+            .collect();
+        Self {
+            commands,
+            current: map(self.current),
+            start: map(self.start),
+        }
+    }
+
+    // Curves are cut into segments that stay within `tolerance` of them
+    pub fn flatten(&self, tolerance: f32) -> Vec<Contour> {
+        let tolerance = tolerance.max(1e-4);
+        let mut contours = Vec::new();
+        let mut contour = Contour::default();
+        let mut last = Vec2::ZERO;
+
+        let mut finish = |contour: &mut Contour, closed: bool| {
+            let done = std::mem::take(contour);
+            if done.points.len() > 1 {
+                contours.push(Contour { closed, ..done });
+            }
+        };
+
+        for command in self.commands.iter() {
+            match *command {
+                PathCommand::MoveTo(to) => {
+                    finish(&mut contour, false);
+                    contour.points.push(to);
+                    last = to;
+                }
+                PathCommand::LineTo(to) => {
+                    if contour.points.is_empty() {
+                        contour.points.push(last);
+                    }
+                    contour.points.push(to);
+                    last = to;
+                }
+                PathCommand::QuadTo(control, to) => {
+                    if contour.points.is_empty() {
+                        contour.points.push(last);
+                    }
+                    let deviation = (last - control * 2.0 + to).length();
+                    let segments = get_segments(deviation / (8.0 * tolerance));
+                    for step in 1..=segments {
+                        let t = step as f32 / segments as f32;
+                        let point = last.lerp(control, t).lerp(control.lerp(to, t), t);
+                        contour.points.push(point);
+                    }
+                    last = to;
+                }
+                PathCommand::CubicTo(control1, control2, to) => {
+                    if contour.points.is_empty() {
+                        contour.points.push(last);
+                    }
+                    let deviation = (last - control1 * 2.0 + control2)
+                        .length()
+                        .max((control1 - control2 * 2.0 + to).length());
+                    let segments = get_segments(3.0 * deviation / (4.0 * tolerance));
+                    for step in 1..=segments {
+                        let t = step as f32 / segments as f32;
+                        let u = 1.0 - t;
+                        let point = last * (u * u * u)
+                            + control1 * (3.0 * u * u * t)
+                            + control2 * (3.0 * u * t * t)
+                            + to * (t * t * t);
+                        contour.points.push(point);
+                    }
+                    last = to;
+                }
+                PathCommand::Close => {
+                    let start = contour.points.first().copied();
+                    // the closing segment is implied
+                    if contour.points.len() > 1 && contour.points.last().copied() == start {
+                        contour.points.pop();
+                    }
+                    finish(&mut contour, true);
+                    if let Some(start) = start {
+                        last = start;
+                    }
+                }
+            }
+        }
+        finish(&mut contour, false);
+        contours
+    }
+}
+
+fn get_segments(squared: f32) -> usize {
+    (squared.sqrt().ceil() as usize).clamp(1, 256)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten_curves() {
+        let mut path = Path::new();
+        path.circle(Vec2::new(10.0, 10.0), 5.0);
+        let contours = path.flatten(0.01);
+        assert_eq!(contours.len(), 1);
+        assert!(contours[0].closed);
+        for point in contours[0].points.iter() {
+            assert!((point.distance(Vec2::new(10.0, 10.0)) - 5.0).abs() < 0.01);
+        }
+
+        // half circle from the left to the right of the origin, through y < 0
+        let mut arc = Path::new();
+        arc.move_to(Vec2::new(-1.0, 0.0)).arc_to(
+            Vec2::splat(1.0),
+            0.0,
+            false,
+            true,
+            Vec2::new(1.0, 0.0),
+        );
+        let points = &arc.flatten(0.001)[0].points;
+        assert_eq!(points.last(), Some(&Vec2::new(1.0, 0.0)));
+        for point in points.iter() {
+            assert!((point.length() - 1.0).abs() < 0.01);
+            assert!(point.y <= 1e-5);
+        }
+    }
+}
@@ -0,0 +1,855 @@
+use std::{collections::HashMap, fs, path::Path as FilePath};
+
+use log::warn;
+use thiserror::Error;
+
+use crate::math::vector::{Vec2, Vec4};
+
+use super::{
+    path::Path,
+    tessellate::{FillRule, LineCap, LineJoin, StrokeStyle, VectorMesh},
+};
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SvgErrors {
+    #[error("unable to read svg file: {0}")]
+    Io(String),
+
+    #[error("invalid svg: {0}")]
+    Parse(String),
+}
+
+// One drawable element, in the units of the document
+#[derive(Debug, Clone, PartialEq)]
+pub struct VectorShape {
+    pub path: Path,
+    pub fill: Option<Vec4>,
+    pub fill_rule: FillRule,
+    pub stroke: Option<Vec4>,
+    pub stroke_style: StrokeStyle,
+}
+
+impl VectorShape {
+    pub fn tessellate(&self, mesh: &mut VectorMesh, tolerance: f32) {
+        if let Some(color) = self.fill {
+            mesh.fill(&self.path, self.fill_rule, color, tolerance);
+        }
+        if let Some(color) = self.stroke {
+            mesh.stroke(&self.path, &self.stroke_style, color, tolerance);
+        }
+    }
+}
+
+// The subset of SVG that UI art exported from vector editors uses: paths,
+// basic shapes, groups, transforms and flat colors. Gradients, text, masks
+// and filters are skipped with a warning.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SvgDocument {
+    // the view box, shapes are in these units
+    pub origin: Vec2,
+    pub size: Vec2,
+    pub shapes: Vec<VectorShape>,
+}
+
+// Row major 2x3 affine transform: x' = a x + c y + e, y' = b x + d y + f
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Affine([f32; 6]);
+
+impl Affine {
+    const IDENTITY: Self = Self([1.0, 0.0, 0.0, 1.0, 0.0, 0.0]);
+
+    fn apply(&self, point: Vec2) -> Vec2 {
+        let [a, b, c, d, e, f] = self.0;
+        Vec2::new(a * point.x + c * point.y + e, b * point.x + d * point.y + f)
+    }
+
+    // `other` first, then `self`
+    fn after(&self, other: &Self) -> Self {
+        let [a1, b1, c1, d1, e1, f1] = other.0;
+        let [a2, b2, c2, d2, e2, f2] = self.0;
+        Self([
+            a2 * a1 + c2 * b1,
+            b2 * a1 + d2 * b1,
+            a2 * c1 + c2 * d1,
+            b2 * c1 + d2 * d1,
+            a2 * e1 + c2 * f1 + e2,
+            b2 * e1 + d2 * f1 + f2,
+        ])
+    }
+
+    fn get_scale(&self) -> f32 {
+        let [a, b, c, d, ..] = self.0;
+        (a * d - b * c).abs().sqrt()
+    }
+}
+
+// What children inherit from their groups
+#[derive(Debug, Clone)]
+struct Style {
+    fill: Option<Vec4>,
+    fill_rule: FillRule,
+    stroke: Option<Vec4>,
+    stroke_style: StrokeStyle,
+    opacity: f32,
+    fill_opacity: f32,
+    stroke_opacity: f32,
+    transform: Affine,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fill: Some(Vec4::new(0.0, 0.0, 0.0, 1.0)),
+            fill_rule: FillRule::NonZero,
+            stroke: None,
+            stroke_style: StrokeStyle::default(),
+            opacity: 1.0,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+            transform: Affine::IDENTITY,
+        }
+    }
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    attributes: HashMap<&'a str, &'a str>,
+    closing: bool,
+    self_closing: bool,
+}
+
+fn parse_error(message: impl Into<String>) -> SvgErrors {
+    SvgErrors::Parse(message.into())
+}
+
+// Splits the markup into tags, text between them does not matter here
+fn parse_tags(source: &str) -> Result<Vec<Tag<'_>>, SvgErrors> {
+    let mut tags = Vec::new();
+    let mut rest = source;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        let skip_until = |rest: &str, end: &str| rest.find(end).map(|index| index + end.len());
+        let skipped = if rest.starts_with("<!--") {
+            skip_until(rest, "-->")
+        } else if rest.starts_with("<![CDATA[") {
+            skip_until(rest, "]]>")
+        } else if rest.starts_with("<?") {
+            skip_until(rest, "?>")
+        } else if rest.starts_with("<!") {
+            skip_until(rest, ">")
+        } else {
+            None
+        };
+        if let Some(skipped) = skipped {
+            rest = &rest[skipped..];
+            continue;
+        }
+
+        let end = find_tag_end(rest).ok_or_else(|| parse_error("unterminated tag"))?;
+        let body = &rest[1..end];
+        rest = &rest[end + 1..];
+        let closing = body.starts_with('/');
+        let self_closing = body.ends_with('/');
+        let body = body.trim_start_matches('/').trim_end_matches('/');
+        let name_end = body.find(|c: char| c.is_whitespace()).unwrap_or(body.len());
+        tags.push(Tag {
+            name: &body[..name_end],
+            attributes: parse_attributes(&body[name_end..])?,
+            closing,
+            self_closing,
+        });
+    }
+    Ok(tags)
+}
+
+// The closing '>' of the tag, skipping the ones inside quoted values
+fn find_tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_attributes(mut source: &str) -> Result<HashMap<&str, &str>, SvgErrors> {
+    let mut attributes = HashMap::new();
+    loop {
+        source = source.trim_start();
+        if source.is_empty() {
+            return Ok(attributes);
+        }
+        let equals = source
+            .find('=')
+            .ok_or_else(|| parse_error(format!("attribute without value in {}", source)))?;
+        let name = source[..equals].trim();
+        source = source[equals + 1..].trim_start();
+        let quote = source
+            .chars()
+            .next()
+            .filter(|c| *c == '"' || *c == '\'')
+            .ok_or_else(|| parse_error(format!("unquoted value of {}", name)))?;
+        let end = source[1..]
+            .find(quote)
+            .ok_or_else(|| parse_error(format!("unterminated value of {}", name)))?;
+        attributes.insert(name, &source[1..end + 1]);
+        source = &source[end + 2..];
+    }
+}
+
+// Numbers in path data and lists may run together: "1.5.5-2" is 1.5 .5 -2
+struct Numbers<'a> {
+    source: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Numbers<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source: source.as_bytes(),
+            position: 0,
+        }
+    }
+
+    fn skip_separators(&mut self) {
+        while self
+            .source
+            .get(self.position)
+            .is_some_and(|c| c.is_ascii_whitespace() || *c == b',')
+        {
+            self.position += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_separators();
+        self.source.get(self.position).copied()
+    }
+
+    fn has_number(&mut self) -> bool {
+        self.peek()
+            .is_some_and(|c| c.is_ascii_digit() || matches!(c, b'-' | b'+' | b'.'))
+    }
+
+    fn number(&mut self) -> Result<f32, SvgErrors> {
+        self.skip_separators();
+        let start = self.position;
+        let mut seen_dot = false;
+        let mut seen_exponent = false;
+        while let Some(c) = self.source.get(self.position).copied() {
+            let at_start =
+                self.position == start || matches!(self.source[self.position - 1], b'e' | b'E');
+            match c {
+                b'-' | b'+' if at_start => {}
+                b'0'..=b'9' => {}
+                b'.' if !seen_dot && !seen_exponent => seen_dot = true,
+                b'e' | b'E' if !seen_exponent && self.position > start => seen_exponent = true,
+                _ => break,
+            }
+            self.position += 1;
+        }
+        let text = std::str::from_utf8(&self.source[start..self.position]).unwrap_or_default();
+        text.parse()
+            .map_err(|_| parse_error(format!("expected a number at {}", start)))
+    }
+
+    // Arc flags are single digits that may touch the next number
+    fn flag(&mut self) -> Result<bool, SvgErrors> {
+        match self.peek() {
+            Some(b'0') => {
+                self.position += 1;
+                Ok(false)
+            }
+            Some(b'1') => {
+                self.position += 1;
+                Ok(true)
+            }
+            _ => Err(parse_error(format!("expected a flag at {}", self.position))),
+        }
+    }
+
+    fn point(&mut self) -> Result<Vec2, SvgErrors> {
+        Ok(Vec2::new(self.number()?, self.number()?))
+    }
+}
+
+// The `d` attribute of a path element
+pub fn parse_path_data(data: &str) -> Result<Path, SvgErrors> {
+    let mut path = Path::new();
+    let mut numbers = Numbers::new(data);
+    let mut command = None;
+    // reflected by the smooth curve commands
+    let mut last_control: Option<Vec2> = None;
+
+    while let Some(next) = numbers.peek() {
+        if next.is_ascii_alphabetic() {
+            numbers.position += 1;
+            command = Some(next);
+        } else if command.is_none() {
+            return Err(parse_error("expected a path command"));
+        }
+        let Some(current_command) = command else {
+            break;
+        };
+        let relative = current_command.is_ascii_lowercase();
+        let origin = match relative {
+            true => path.get_current(),
+            false => Vec2::ZERO,
+        };
+        let current = path.get_current();
+        let reflected = last_control.map_or(current, |control| current * 2.0 - control);
+        last_control = None;
+
+        match current_command.to_ascii_uppercase() {
+            b'M' => {
+                path.move_to(origin + numbers.point()?);
+                // extra pairs after a move are lines
+                command = Some(if relative { b'l' } else { b'L' });
+            }
+            b'L' => {
+                path.line_to(origin + numbers.point()?);
+            }
+            b'H' => {
+                let x = numbers.number()? + origin.x;
+                path.line_to(Vec2::new(x, current.y));
+            }
+            b'V' => {
+                let y = numbers.number()? + origin.y;
+                path.line_to(Vec2::new(current.x, y));
+            }
+            b'C' => {
+                let control1 = origin + numbers.point()?;
+                let control2 = origin + numbers.point()?;
+                let to = origin + numbers.point()?;
+                path.cubic_to(control1, control2, to);
+                last_control = Some(control2);
+            }
+            b'S' => {
+                let control2 = origin + numbers.point()?;
+                let to = origin + numbers.point()?;
+                path.cubic_to(reflected, control2, to);
+                last_control = Some(control2);
+            }
+            b'Q' => {
+                let control = origin + numbers.point()?;
+                let to = origin + numbers.point()?;
+                path.quad_to(control, to);
+                last_control = Some(control);
+            }
+            b'T' => {
+                let to = origin + numbers.point()?;
+                path.quad_to(reflected, to);
+                last_control = Some(reflected);
+            }
+            b'A' => {
+                let radii = numbers.point()?;
+                let rotation = numbers.number()?.to_radians();
+                let large_arc = numbers.flag()?;
+                let sweep = numbers.flag()?;
+                let to = origin + numbers.point()?;
+                path.arc_to(radii, rotation, large_arc, sweep, to);
+            }
+            b'Z' => {
+                path.close();
+                command = None;
+                continue;
+            }
+            other => {
+                return Err(parse_error(format!(
+                    "unknown path command {}",
+                    other as char
+                )))
+            }
+        }
+        if !numbers.has_number() && numbers.peek().is_some_and(|c| !c.is_ascii_alphabetic()) {
+            return Err(parse_error(format!(
+                "unexpected character at {}",
+                numbers.position
+            )));
+        }
+    }
+    Ok(path)
+}
+
+fn parse_numbers(source: &str) -> Result<Vec<f32>, SvgErrors> {
+    let mut numbers = Numbers::new(source);
+    let mut values = Vec::new();
+    while numbers.has_number() {
+        values.push(numbers.number()?);
+    }
+    Ok(values)
+}
+
+fn parse_transform(source: &str) -> Result<Affine, SvgErrors> {
+    let mut transform = Affine::IDENTITY;
+    let mut rest = source.trim();
+    while !rest.is_empty() {
+        let open = rest
+            .find('(')
+            .ok_or_else(|| parse_error(format!("invalid transform {}", source)))?;
+        let close = rest[open..]
+            .find(')')
+            .map(|close| open + close)
+            .ok_or_else(|| parse_error(format!("invalid transform {}", source)))?;
+        let name = rest[..open].trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let values = parse_numbers(&rest[open + 1..close])?;
+        let value = |index: usize| values.get(index).copied().unwrap_or(0.0);
+        let next = match (name, values.len()) {
+            ("matrix", 6) => Affine([
+                values[0], values[1], values[2], values[3], values[4], values[5],
+            ]),
+            ("translate", 1 | 2) => Affine([1.0, 0.0, 0.0, 1.0, value(0), value(1)]),
+            ("scale", 1) => Affine([value(0), 0.0, 0.0, value(0), 0.0, 0.0]),
+            ("scale", 2) => Affine([value(0), 0.0, 0.0, value(1), 0.0, 0.0]),
+            ("rotate", 1 | 3) => {
+                let (sin, cos) = value(0).to_radians().sin_cos();
+                let (x, y) = (value(1), value(2));
+                let around = Affine([1.0, 0.0, 0.0, 1.0, x, y]);
+                let back = Affine([1.0, 0.0, 0.0, 1.0, -x, -y]);
+                around.after(&Affine([cos, sin, -sin, cos, 0.0, 0.0]).after(&back))
+            }
+            ("skewX", 1) => Affine([1.0, 0.0, value(0).to_radians().tan(), 1.0, 0.0, 0.0]),
+            ("skewY", 1) => Affine([1.0, value(0).to_radians().tan(), 0.0, 1.0, 0.0, 0.0]),
+            _ => return Err(parse_error(format!("invalid transform {}", source))),
+        };
+        // later transforms in the list apply first
+        transform = transform.after(&next);
+        rest = rest[close + 1..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+    }
+    Ok(transform)
+}
+
+// None for `none`, Err for colors this importer does not know
+fn parse_color(source: &str) -> Result<Option<Vec4>, SvgErrors> {
+    let source = source.trim();
+    let rgb = |r: u8, g: u8, b: u8| {
+        Some(Vec4::new(
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0,
+            1.0,
+        ))
+    };
+    let invalid = || parse_error(format!("unsupported color {}", source));
+    if let Some(hex) = source.strip_prefix('#') {
+        let digit = |index: usize, len: usize| {
+            u8::from_str_radix(hex.get(index..index + len).unwrap_or("x"), 16)
+        };
+        return match hex.len() {
+            3 => {
+                let [r, g, b] = [0, 1, 2].map(|i| digit(i, 1).map(|value| value * 17));
+                Ok(rgb(
+                    r.map_err(|_| invalid())?,
+                    g.map_err(|_| invalid())?,
+                    b.map_err(|_| invalid())?,
+                ))
+            }
+            6 => {
+                let [r, g, b] = [0, 2, 4].map(|i| digit(i, 2));
+                Ok(rgb(
+                    r.map_err(|_| invalid())?,
+                    g.map_err(|_| invalid())?,
+                    b.map_err(|_| invalid())?,
+                ))
+            }
+            _ => Err(invalid()),
+        };
+    }
+    if let Some(values) = source
+        .strip_prefix("rgb(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let values = parse_numbers(values)?;
+        if values.len() != 3 {
+            return Err(invalid());
+        }
+        let channel = |value: f32| value.clamp(0.0, 255.0) as u8;
+        return Ok(rgb(
+            channel(values[0]),
+            channel(values[1]),
+            channel(values[2]),
+        ));
+    }
+    match source {
+        "none" | "transparent" => Ok(None),
+        "black" | "currentColor" => Ok(rgb(0, 0, 0)),
+        "white" => Ok(rgb(255, 255, 255)),
+        "red" => Ok(rgb(255, 0, 0)),
+        "green" => Ok(rgb(0, 128, 0)),
+        "lime" => Ok(rgb(0, 255, 0)),
+        "blue" => Ok(rgb(0, 0, 255)),
+        "yellow" => Ok(rgb(255, 255, 0)),
+        "gray" | "grey" => Ok(rgb(128, 128, 128)),
+        _ => Err(invalid()),
+    }
+}
+
+fn parse_number(name: &str, value: &str) -> Result<f32, SvgErrors> {
+    value
+        .trim()
+        .trim_end_matches("px")
+        .parse()
+        .map_err(|_| parse_error(format!("invalid {} {}", name, value)))
+}
+
+impl Style {
+    fn apply(&mut self, name: &str, value: &str) -> Result<(), SvgErrors> {
+        match name {
+            "fill" => self.fill = parse_color_or_skip(value)?,
+            "stroke" => self.stroke = parse_color_or_skip(value)?,
+            "fill-rule" => {
+                self.fill_rule = match value.trim() {
+                    "evenodd" => FillRule::EvenOdd,
+                    _ => FillRule::NonZero,
+                }
+            }
+            "stroke-width" => self.stroke_style.width = parse_number(name, value)?,
+            "stroke-linejoin" => {
+                self.stroke_style.join = match value.trim() {
+                    "round" => LineJoin::Round,
+                    "bevel" => LineJoin::Bevel,
+                    _ => LineJoin::Miter,
+                }
+            }
+            "stroke-linecap" => {
+                self.stroke_style.cap = match value.trim() {
+                    "round" => LineCap::Round,
+                    "square" => LineCap::Square,
+                    _ => LineCap::Butt,
+                }
+            }
+            "stroke-miterlimit" => self.stroke_style.miter_limit = parse_number(name, value)?,
+            "opacity" => self.opacity *= parse_number(name, value)?,
+            "fill-opacity" => self.fill_opacity = parse_number(name, value)?,
+            "stroke-opacity" => self.stroke_opacity = parse_number(name, value)?,
+            "transform" => self.transform = self.transform.after(&parse_transform(value)?),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // Presentation attributes first, the style attribute overrides them
+    fn inherit(&self, tag: &Tag) -> Result<Self, SvgErrors> {
+        let mut style = self.clone();
+        for (name, value) in tag.attributes.iter() {
+            style.apply(name, value)?;
+        }
+        if let Some(declarations) = tag.attributes.get("style") {
+            for declaration in declarations.split(';') {
+                if let Some((name, value)) = declaration.split_once(':') {
+                    style.apply(name.trim(), value)?;
+                }
+            }
+        }
+        Ok(style)
+    }
+}
+
+// Paint servers like url(#gradient) are not supported, the shape keeps its
+// outline but loses that paint
+fn parse_color_or_skip(value: &str) -> Result<Option<Vec4>, SvgErrors> {
+    if value.trim().starts_with("url(") {
+        warn!("svg paint {} is not supported", value);
+        return Ok(None);
+    }
+    parse_color(value)
+}
+
+fn get_attribute(tag: &Tag, name: &str) -> Result<f32, SvgErrors> {
+    tag.attributes
+        .get(name)
+        .map_or(Ok(0.0), |value| parse_number(name, value))
+}
+
+fn get_points(tag: &Tag) -> Result<Vec<Vec2>, SvgErrors> {
+    let values = parse_numbers(tag.attributes.get("points").copied().unwrap_or_default())?;
+    Ok(values
+        .chunks_exact(2)
+        .map(|pair| Vec2::new(pair[0], pair[1]))
+        .collect())
+}
+
+fn get_shape_path(tag: &Tag) -> Result<Option<Path>, SvgErrors> {
+    let mut path = Path::new();
+    match tag.name {
+        "path" => {
+            return tag
+                .attributes
+                .get("d")
+                .map(|data| parse_path_data(data))
+                .transpose()
+        }
+        "rect" => {
+            let min = Vec2::new(get_attribute(tag, "x")?, get_attribute(tag, "y")?);
+            let size = Vec2::new(get_attribute(tag, "width")?, get_attribute(tag, "height")?);
+            let (mut rx, mut ry) = (get_attribute(tag, "rx")?, get_attribute(tag, "ry")?);
+            if rx == 0.0 {
+                rx = ry;
+            }
+            if ry == 0.0 {
+                ry = rx;
+            }
+            let (rx, ry) = (rx.min(size.x * 0.5), ry.min(size.y * 0.5));
+            let max = min + size;
+            if rx <= 0.0 || ry <= 0.0 {
+                path.rect(min, max);
+            } else {
+                let radii = Vec2::new(rx, ry);
+                path.move_to(Vec2::new(min.x + rx, min.y))
+                    .line_to(Vec2::new(max.x - rx, min.y))
+                    .arc_to(radii, 0.0, false, true, Vec2::new(max.x, min.y + ry))
+                    .line_to(Vec2::new(max.x, max.y - ry))
+                    .arc_to(radii, 0.0, false, true, Vec2::new(max.x - rx, max.y))
+                    .line_to(Vec2::new(min.x + rx, max.y))
+                    .arc_to(radii, 0.0, false, true, Vec2::new(min.x, max.y - ry))
+                    .line_to(Vec2::new(min.x, min.y + ry))
+                    .arc_to(radii, 0.0, false, true, Vec2::new(min.x + rx, min.y))
+                    .close();
+            }
+        }
+        "circle" => {
+            let center = Vec2::new(get_attribute(tag, "cx")?, get_attribute(tag, "cy")?);
+            path.circle(center, get_attribute(tag, "r")?);
+        }
+        "ellipse" => {
+            let center = Vec2::new(get_attribute(tag, "cx")?, get_attribute(tag, "cy")?);
+            let radii = Vec2::new(get_attribute(tag, "rx")?, get_attribute(tag, "ry")?);
+            path.ellipse(center, radii);
+        }
+        "line" => {
+            path.move_to(Vec2::new(
+                get_attribute(tag, "x1")?,
+                get_attribute(tag, "y1")?,
+            ))
+            .line_to(Vec2::new(
+                get_attribute(tag, "x2")?,
+                get_attribute(tag, "y2")?,
+            ));
+        }
+        "polyline" | "polygon" => {
+            let points = get_points(tag)?;
+            let Some((first, rest)) = points.split_first() else {
+                return Ok(None);
+            };
+            path.move_to(*first);
+            for point in rest {
+                path.line_to(*point);
+            }
+            if tag.name == "polygon" {
+                path.close();
+            }
+        }
+        _ => return Ok(None),
+    }
+    Ok(Some(path))
+}
+
+// Containers whose content is not drawn directly
+const SKIPPED: [&str; 7] = [
+    "defs",
+    "clipPath",
+    "mask",
+    "linearGradient",
+    "radialGradient",
+    "pattern",
+    "symbol",
+];
+
+impl SvgDocument {
+    pub fn parse(source: &str) -> Result<Self, SvgErrors> {
+        let tags = parse_tags(source)?;
+        let mut document = Self::default();
+        let mut styles = vec![Style::default()];
+        let mut skipping = 0;
+        let mut found_root = false;
+
+        for tag in tags.iter() {
+            if tag.closing {
+                if SKIPPED.contains(&tag.name) {
+                    skipping -= 1;
+                } else if skipping == 0 && matches!(tag.name, "g" | "svg" | "a") {
+                    styles.pop();
+                }
+                continue;
+            }
+            if SKIPPED.contains(&tag.name) {
+                if !tag.self_closing {
+                    skipping += 1;
+                }
+                continue;
+            }
+            if skipping > 0 {
+                continue;
+            }
+            let parent = styles.last().cloned().unwrap_or_default();
+            let style = parent.inherit(tag)?;
+
+            match tag.name {
+                "svg" => {
+                    if !found_root {
+                        found_root = true;
+                        document.read_view_box(tag)?;
+                    }
+                    if !tag.self_closing {
+                        styles.push(style);
+                    }
+                }
+                "g" | "a" => {
+                    if !tag.self_closing {
+                        styles.push(style);
+                    }
+                }
+                name => match get_shape_path(tag)? {
+                    Some(path) => document.push_shape(path, &style),
+                    None => {
+                        if matches!(name, "text" | "image" | "use" | "filter") {
+                            warn!("svg element {} is not supported", name);
+                        }
+                    }
+                },
+            }
+        }
+        if !found_root {
+            return Err(parse_error("no svg element"));
+        }
+        Ok(document)
+    }
+
+    pub fn from_file(path: impl AsRef<FilePath>) -> Result<Self, SvgErrors> {
+        let source =
+            fs::read_to_string(path.as_ref()).map_err(|err| SvgErrors::Io(err.to_string()))?;
+        Self::parse(&source)
+    }
+
+    fn read_view_box(&mut self, tag: &Tag) -> Result<(), SvgErrors> {
+        if let Some(view_box) = tag.attributes.get("viewBox") {
+            let values = parse_numbers(view_box)?;
+            if values.len() != 4 {
+                return Err(parse_error(format!("invalid viewBox {}", view_box)));
+            }
+            self.origin = Vec2::new(values[0], values[1]);
+            self.size = Vec2::new(values[2], values[3]);
+        } else {
+            self.size = Vec2::new(get_attribute(tag, "width")?, get_attribute(tag, "height")?);
+        }
+        Ok(())
+    }
+
+    fn push_shape(&mut self, path: Path, style: &Style) {
+        let transform = style.transform;
+        let path = match transform == Affine::IDENTITY {
+            true => path,
+            false => path.map_points(|point| transform.apply(point)),
+        };
+        let with_alpha = |color: Vec4, alpha: f32| Vec4::new(color.x, color.y, color.z, alpha);
+        let fill = style
+            .fill
+            .map(|color| with_alpha(color, color.w * style.fill_opacity * style.opacity));
+        let stroke = style
+            .stroke
+            .map(|color| with_alpha(color, color.w * style.stroke_opacity * style.opacity));
+        let stroke_style = StrokeStyle {
+            width: style.stroke_style.width * transform.get_scale(),
+            ..style.stroke_style
+        };
+        self.shapes.push(VectorShape {
+            path,
+            fill,
+            fill_rule: style.fill_rule,
+            stroke: stroke.filter(|_| stroke_style.width > 0.0),
+            stroke_style,
+        });
+    }
+
+    // Every shape in document order, `scale` maps view box units to the
+    // units the mesh is drawn in. Tessellate again after a big change of
+    // scale to keep curves smooth.
+    pub fn tessellate(&self, scale: f32, tolerance: f32) -> VectorMesh {
+        let mut mesh = VectorMesh::new();
+        for shape in self.shapes.iter() {
+            let scaled = VectorShape {
+                path: shape.path.map_points(|point| (point - self.origin) * scale),
+                stroke_style: StrokeStyle {
+                    width: shape.stroke_style.width * scale,
+                    ..shape.stroke_style
+                },
+                ..shape.clone()
+            };
+            scaled.tessellate(&mut mesh, tolerance);
+        }
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_data() {
+        let path = parse_path_data("M10,10h5v5H10z m1-1l1.5.5").unwrap();
+        let contours = path.flatten(0.1);
+        assert_eq!(contours.len(), 2);
+        assert_eq!(
+            contours[0].points,
+            vec![
+                Vec2::new(10.0, 10.0),
+                Vec2::new(15.0, 10.0),
+                Vec2::new(15.0, 15.0),
+                Vec2::new(10.0, 15.0),
+            ]
+        );
+        // relative move after a close starts at the closed sub path's start
+        assert_eq!(
+            contours[1].points,
+            vec![Vec2::new(11.0, 9.0), Vec2::new(12.5, 9.5)]
+        );
+
+        let arc = parse_path_data("M0 0a5 5 0 1010 0").unwrap();
+        assert_eq!(arc.get_current(), Vec2::new(10.0, 0.0));
+        assert!(parse_path_data("10 10").is_err());
+        assert!(parse_path_data("M0 0 X").is_err());
+    }
+
+    #[test]
+    fn test_parse_document() {
+        let svg = r##"<?xml version="1.0"?>
+            <svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 20">
+              <!-- background -->
+              <defs><linearGradient id="g"><stop offset="0"/></linearGradient></defs>
+              <rect width="20" height="20" fill="#fff"/>
+              <g transform="translate(10 10)" style="fill: #f00; opacity: 0.5">
+                <rect x="-2" y="-2" width="4" height="4"/>
+                <circle r="1" fill="none" stroke="blue" stroke-width="2"/>
+              </g>
+            </svg>"##;
+        let document = SvgDocument::parse(svg).unwrap();
+        assert_eq!(document.size, Vec2::new(20.0, 20.0));
+        assert_eq!(document.shapes.len(), 3);
+        assert_eq!(document.shapes[0].fill, Some(Vec4::splat(1.0)));
+
+        let square = &document.shapes[1];
+        assert_eq!(square.fill, Some(Vec4::new(1.0, 0.0, 0.0, 0.5)));
+        assert_eq!(square.path.flatten(0.1)[0].points[0], Vec2::new(8.0, 8.0));
+        let ring = &document.shapes[2];
+        assert_eq!(ring.fill, None);
+        assert_eq!(ring.stroke, Some(Vec4::new(0.0, 0.0, 1.0, 0.5)));
+
+        let mesh = document.tessellate(2.0, 0.01);
+        assert!(!mesh.is_empty());
+        let background: f32 = 40.0 * 40.0;
+        assert!(mesh.get_area() > background);
+        assert!(matches!(
+            SvgDocument::parse("<svg><rect fill='#12'/></svg>"),
+            Err(SvgErrors::Parse(_))
+        ));
+        assert!(matches!(
+            parse_transform(")rotate(4"),
+            Err(SvgErrors::Parse(_))
+        ));
+    }
+}
@@ -0,0 +1,470 @@
+use std::f32::consts::PI;
+
+use crate::math::vector::{Vec2, Vec4};
+
+use super::path::{Contour, Path};
+
+const EPSILON: f32 = 1e-5;
+
+// Which areas enclosed by overlapping sub paths are inside, as in SVG
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FillRule {
+    #[default]
+    NonZero,
+    EvenOdd,
+}
+
+impl FillRule {
+    fn is_inside(&self, winding: i32) -> bool {
+        match self {
+            Self::NonZero => winding != 0,
+            Self::EvenOdd => winding % 2 != 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineJoin {
+    #[default]
+    Miter,
+    Bevel,
+    Round,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineCap {
+    #[default]
+    Butt,
+    Square,
+    Round,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokeStyle {
+    pub width: f32,
+    pub join: LineJoin,
+    pub cap: LineCap,
+    // miters longer than this many half widths fall back to bevels
+    pub miter_limit: f32,
+}
+
+impl Default for StrokeStyle {
+    fn default() -> Self {
+        Self {
+            width: 1.0,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+            miter_limit: 4.0,
+        }
+    }
+}
+
+impl StrokeStyle {
+    pub fn new(width: f32) -> Self {
+        Self {
+            width,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct VectorVertex {
+    pub position: Vec2,
+    pub color: Vec4,
+}
+
+// Colored triangles ready for the 2D batch, shapes of any color end up in
+// one mesh so a whole icon is a single draw
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct VectorMesh {
+    pub vertices: Vec<VectorVertex>,
+    pub indices: Vec<u32>,
+}
+
+impl VectorMesh {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    pub fn get_triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    pub fn append(&mut self, other: &VectorMesh) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices
+            .extend(other.indices.iter().map(|index| index + offset));
+    }
+
+    // Area covered by the triangles, for tests and bounds checks
+    pub fn get_area(&self) -> f32 {
+        self.indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| self.vertices[triangle[i] as usize].position);
+                ((b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y)).abs() * 0.5
+            })
+            .sum()
+    }
+
+    fn push_polygon(&mut self, points: &[Vec2], color: Vec4) {
+        let first = self.vertices.len() as u32;
+        self.vertices
+            .extend(points.iter().map(|position| VectorVertex {
+                position: *position,
+                color,
+            }));
+        for index in 1..points.len().saturating_sub(1) as u32 {
+            self.indices
+                .extend([first, first + index, first + index + 1]);
+        }
+    }
+
+    // Every sub path counts as closed
+    pub fn fill(&mut self, path: &Path, rule: FillRule, color: Vec4, tolerance: f32) {
+        fill_contours(self, &path.flatten(tolerance), rule, color);
+    }
+
+    pub fn stroke(&mut self, path: &Path, style: &StrokeStyle, color: Vec4, tolerance: f32) {
+        for contour in path.flatten(tolerance) {
+            stroke_contour(self, &contour, style, color, tolerance);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+    top: Vec2,
+    bottom: Vec2,
+    winding: i32,
+}
+
+impl Edge {
+    fn get_x(&self, y: f32) -> f32 {
+        let t = (y - self.top.y) / (self.bottom.y - self.top.y);
+        self.top.x + (self.bottom.x - self.top.x) * t
+    }
+}
+
+// Where two edges cross, every band has to be free of crossings
+fn get_crossing(a: &Edge, b: &Edge) -> Option<f32> {
+    let (p, r) = (a.top, a.bottom - a.top);
+    let (q, s) = (b.top, b.bottom - b.top);
+    let denominator = r.x * s.y - r.y * s.x;
+    if denominator.abs() < EPSILON {
+        return None;
+    }
+    let qp = q - p;
+    let t = (qp.x * s.y - qp.y * s.x) / denominator;
+    let u = (qp.x * r.y - qp.y * r.x) / denominator;
+    ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(p.y + r.y * t)
+}
+
+// Cuts the shape into horizontal bands at every vertex and crossing, inside
+// a band edges do not cross so the filled spans between them are trapezoids.
+// Holes and self intersections follow the fill rule.
+fn fill_contours(mesh: &mut VectorMesh, contours: &[Contour], rule: FillRule, color: Vec4) {
+    let mut edges = Vec::new();
+    for contour in contours {
+        let points = &contour.points;
+        for (index, from) in points.iter().enumerate() {
+            let to = points[(index + 1) % points.len()];
+            if (from.y - to.y).abs() < EPSILON {
+                continue;
+            }
+            edges.push(match from.y < to.y {
+                true => Edge {
+                    top: *from,
+                    bottom: to,
+                    winding: 1,
+                },
+                false => Edge {
+                    top: to,
+                    bottom: *from,
+                    winding: -1,
+                },
+            });
+        }
+    }
+
+    let mut bands: Vec<f32> = edges
+        .iter()
+        .flat_map(|edge| [edge.top.y, edge.bottom.y])
+        .collect();
+    for (index, a) in edges.iter().enumerate() {
+        for b in edges[index + 1..].iter() {
+            if a.top.y < b.bottom.y && b.top.y < a.bottom.y {
+                bands.extend(get_crossing(a, b));
+            }
+        }
+    }
+    bands.sort_by(f32::total_cmp);
+    bands.dedup_by(|a, b| (*a - *b).abs() < EPSILON);
+
+    for band in bands.windows(2) {
+        let (top, bottom) = (band[0], band[1]);
+        let middle = (top + bottom) * 0.5;
+        let mut crossing: Vec<&Edge> = edges
+            .iter()
+            .filter(|edge| edge.top.y < middle && edge.bottom.y > middle)
+            .collect();
+        crossing.sort_by(|a, b| a.get_x(middle).total_cmp(&b.get_x(middle)));
+
+        let mut winding = 0;
+        let mut left: Option<&Edge> = None;
+        for edge in crossing {
+            let was_inside = rule.is_inside(winding);
+            winding += edge.winding;
+            match (was_inside, rule.is_inside(winding)) {
+                (false, true) => left = Some(edge),
+                (true, false) => {
+                    if let Some(left) = left.take() {
+                        let trapezoid = [
+                            Vec2::new(left.get_x(top), top),
+                            Vec2::new(edge.get_x(top), top),
+                            Vec2::new(edge.get_x(bottom), bottom),
+                            Vec2::new(left.get_x(bottom), bottom),
+                        ];
+                        mesh.push_polygon(&trapezoid, color);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn get_normal(direction: Vec2) -> Vec2 {
+    Vec2::new(-direction.y, direction.x)
+}
+
+// Points on the arc around `center` from `from` to `to`, both offsets of
+// length `radius`, turning the short way
+fn get_arc(center: Vec2, from: Vec2, to: Vec2, radius: f32, tolerance: f32) -> Vec<Vec2> {
+    let start = from.y.atan2(from.x);
+    let mut delta = to.y.atan2(to.x) - start;
+    if delta > PI {
+        delta -= 2.0 * PI;
+    } else if delta < -PI {
+        delta += 2.0 * PI;
+    }
+    let step = 2.0
+        * (1.0 - tolerance / radius.max(tolerance))
+            .clamp(-1.0, 1.0)
+            .acos();
+    let segments = (delta.abs() / step.max(0.01)).ceil().clamp(1.0, 64.0) as usize;
+    (0..=segments)
+        .map(|segment| {
+            let angle = start + delta * segment as f32 / segments as f32;
+            center + Vec2::new(angle.cos(), angle.sin()) * radius
+        })
+        .collect()
+}
+
+fn stroke_contour(
+    mesh: &mut VectorMesh,
+    contour: &Contour,
+    style: &StrokeStyle,
+    color: Vec4,
+    tolerance: f32,
+) {
+    let half = style.width * 0.5;
+    let mut points = contour.points.clone();
+    points.dedup_by(|a, b| a.distance(*b) < EPSILON);
+    if contour.closed && points.len() > 2 && points[0].distance(points[points.len() - 1]) < EPSILON
+    {
+        points.pop();
+    }
+    if points.len() < 2 || half <= 0.0 {
+        return;
+    }
+
+    let count = points.len();
+    let segments = match contour.closed {
+        true => count,
+        false => count - 1,
+    };
+    let directions: Vec<Vec2> = (0..segments)
+        .map(|index| (points[(index + 1) % count] - points[index]).normalize())
+        .collect();
+
+    for (index, direction) in directions.iter().enumerate() {
+        let (from, to) = (points[index], points[(index + 1) % count]);
+        let normal = get_normal(*direction) * half;
+        mesh.push_polygon(
+            &[from + normal, from - normal, to - normal, to + normal],
+            color,
+        );
+    }
+
+    // joins at every point with a segment on both sides
+    let joins = match contour.closed {
+        true => 0..count,
+        false => 1..count - 1,
+    };
+    for index in joins {
+        let before = directions[(index + segments - 1) % segments];
+        let after = directions[index % segments];
+        let turn = before.x * after.y - before.y * after.x;
+        if turn.abs() < EPSILON {
+            continue;
+        }
+        // the gap opens on the outside of the turn
+        let side = if turn > 0.0 { -1.0 } else { 1.0 };
+        let point = points[index];
+        let outer_before = get_normal(before) * (half * side);
+        let outer_after = get_normal(after) * (half * side);
+        match style.join {
+            LineJoin::Round => {
+                let mut fan = vec![point];
+                fan.extend(get_arc(point, outer_before, outer_after, half, tolerance));
+                mesh.push_polygon(&fan, color);
+            }
+            join => {
+                let bisector = (outer_before + outer_after).normalize();
+                let length = half / bisector.dot(outer_before / half).max(EPSILON);
+                if join == LineJoin::Miter && length <= half * style.miter_limit {
+                    let tip = point + bisector * length;
+                    mesh.push_polygon(
+                        &[point, point + outer_before, tip, point + outer_after],
+                        color,
+                    );
+                } else {
+                    mesh.push_polygon(&[point, point + outer_before, point + outer_after], color);
+                }
+            }
+        }
+    }
+
+    if contour.closed {
+        return;
+    }
+    let ends = [
+        (points[0], -directions[0]),
+        (points[count - 1], directions[segments - 1]),
+    ];
+    for (point, outwards) in ends {
+        let normal = get_normal(outwards) * half;
+        match style.cap {
+            LineCap::Butt => {}
+            LineCap::Square => {
+                let out = outwards * half;
+                mesh.push_polygon(
+                    &[
+                        point + normal,
+                        point - normal,
+                        point - normal + out,
+                        point + normal + out,
+                    ],
+                    color,
+                );
+            }
+            LineCap::Round => {
+                let mut fan = vec![point];
+                fan.extend(get_arc(point, normal, outwards * half, half, tolerance));
+                fan.extend(
+                    get_arc(point, outwards * half, -normal, half, tolerance)
+                        .into_iter()
+                        .skip(1),
+                );
+                mesh.push_polygon(&fan, color);
+            }
+        }
+    }
+}
+
+pub const VECTOR_VERTEX_SHADER: &str = r#"#version 450
+
+layout(location = 0) in vec2 a_position;
+layout(location = 1) in vec4 a_color;
+
+layout(std140, binding = 0) uniform Camera {
+    mat4 u_view_projection;
+};
+
+layout(location = 0) out vec4 v_color;
+
+void main() {
+    v_color = a_color;
+    gl_Position = u_view_projection * vec4(a_position, 0.0, 1.0);
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_rules_and_holes() {
+        let mut path = Path::new();
+        path.rect(Vec2::ZERO, Vec2::splat(10.0));
+        // same winding, a hole only for even odd
+        path.rect(Vec2::splat(2.0), Vec2::splat(8.0));
+
+        let mut mesh = VectorMesh::new();
+        mesh.fill(&path, FillRule::EvenOdd, Vec4::splat(1.0), 0.1);
+        assert!((mesh.get_area() - 64.0).abs() < 1e-3);
+
+        let mut mesh = VectorMesh::new();
+        mesh.fill(&path, FillRule::NonZero, Vec4::splat(1.0), 0.1);
+        assert!((mesh.get_area() - 100.0).abs() < 1e-3);
+
+        // a bow tie crosses itself in the middle
+        let mut bow = Path::new();
+        bow.move_to(Vec2::ZERO)
+            .line_to(Vec2::new(2.0, 2.0))
+            .line_to(Vec2::new(2.0, 0.0))
+            .line_to(Vec2::new(0.0, 2.0))
+            .close();
+        let mut mesh = VectorMesh::new();
+        mesh.fill(&bow, FillRule::NonZero, Vec4::splat(1.0), 0.1);
+        assert!((mesh.get_area() - 2.0).abs() < 1e-3);
+
+        let mut circle = Path::new();
+        circle.circle(Vec2::ZERO, 1.0);
+        let mut mesh = VectorMesh::new();
+        mesh.fill(&circle, FillRule::NonZero, Vec4::splat(1.0), 0.001);
+        assert!((mesh.get_area() - PI).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_stroke_joins_and_caps() {
+        let mut line = Path::new();
+        line.move_to(Vec2::ZERO).line_to(Vec2::new(10.0, 0.0));
+        let mut mesh = VectorMesh::new();
+        mesh.stroke(&line, &StrokeStyle::new(2.0), Vec4::splat(1.0), 0.01);
+        assert!((mesh.get_area() - 20.0).abs() < 1e-3);
+
+        let square = StrokeStyle {
+            cap: LineCap::Square,
+            ..StrokeStyle::new(2.0)
+        };
+        let mut mesh = VectorMesh::new();
+        mesh.stroke(&line, &square, Vec4::splat(1.0), 0.01);
+        assert!((mesh.get_area() - 24.0).abs() < 1e-3);
+
+        // a right angle corner adds the miter square
+        let mut corner = Path::new();
+        corner
+            .move_to(Vec2::ZERO)
+            .line_to(Vec2::new(10.0, 0.0))
+            .line_to(Vec2::new(10.0, 10.0));
+        let mut mesh = VectorMesh::new();
+        mesh.stroke(&corner, &StrokeStyle::new(2.0), Vec4::splat(1.0), 0.01);
+        assert!((mesh.get_area() - (40.0 + 1.0)).abs() < 1e-3);
+
+        let bevel = StrokeStyle {
+            join: LineJoin::Bevel,
+            ..StrokeStyle::new(2.0)
+        };
+        let mut mesh = VectorMesh::new();
+        mesh.stroke(&corner, &bevel, Vec4::splat(1.0), 0.01);
+        assert!((mesh.get_area() - (40.0 + 0.5)).abs() < 1e-3);
+    }
+}